@@ -0,0 +1,105 @@
+package com.transport_services.android;
+
+import android.content.Context;
+import android.net.ConnectivityManager;
+import android.net.LinkProperties;
+import android.net.Network;
+import android.net.NetworkCapabilities;
+import android.net.NetworkRequest;
+
+/**
+ * Forwards ConnectivityManager network callbacks to the path monitor.
+ *
+ * Created by the native library once a watcher is registered. Requires
+ * API level 24 and the ACCESS_NETWORK_STATE permission.
+ */
+public final class NetworkMonitorSupport {
+    private final ConnectivityManager connectivityManager;
+    private ConnectivityManager.NetworkCallback networksCallback;
+    private ConnectivityManager.NetworkCallback defaultCallback;
+
+    public NetworkMonitorSupport(Context context) {
+        connectivityManager =
+                (ConnectivityManager) context.getSystemService(Context.CONNECTIVITY_SERVICE);
+    }
+
+    /** Register the callbacks; changes are reported until stopNetworkWatch. */
+    public synchronized void startNetworkWatch() {
+        if (networksCallback != null) {
+            return;
+        }
+
+        // Every network, VPNs included, so each interface can be used as a path
+        NetworkRequest request = new NetworkRequest.Builder()
+                .removeCapability(NetworkCapabilities.NET_CAPABILITY_NOT_VPN)
+                .build();
+        networksCallback = new ConnectivityManager.NetworkCallback() {
+            @Override
+            public void onAvailable(Network network) {
+                nativeNetworksChanged();
+            }
+
+            @Override
+            public void onLost(Network network) {
+                nativeNetworksChanged();
+            }
+
+            @Override
+            public void onCapabilitiesChanged(Network network, NetworkCapabilities capabilities) {
+                nativeNetworksChanged();
+            }
+
+            @Override
+            public void onLinkPropertiesChanged(Network network, LinkProperties properties) {
+                nativeNetworksChanged();
+            }
+        };
+        connectivityManager.registerNetworkCallback(request, networksCallback);
+
+        defaultCallback = new ConnectivityManager.NetworkCallback() {
+            @Override
+            public void onCapabilitiesChanged(Network network, NetworkCapabilities capabilities) {
+                reportDefault(network, capabilities);
+            }
+
+            @Override
+            public void onLinkPropertiesChanged(Network network, LinkProperties properties) {
+                reportDefault(network, connectivityManager.getNetworkCapabilities(network));
+            }
+
+            @Override
+            public void onLost(Network network) {
+                nativeDefaultNetworkChanged(null, false, false);
+            }
+        };
+        connectivityManager.registerDefaultNetworkCallback(defaultCallback);
+    }
+
+    /** Unregister the callbacks. */
+    public synchronized void stopNetworkWatch() {
+        if (networksCallback != null) {
+            connectivityManager.unregisterNetworkCallback(networksCallback);
+            networksCallback = null;
+        }
+        if (defaultCallback != null) {
+            connectivityManager.unregisterNetworkCallback(defaultCallback);
+            defaultCallback = null;
+        }
+    }
+
+    private void reportDefault(Network network, NetworkCapabilities capabilities) {
+        LinkProperties properties = connectivityManager.getLinkProperties(network);
+        if (properties == null || properties.getInterfaceName() == null || capabilities == null) {
+            return;
+        }
+        nativeDefaultNetworkChanged(
+                properties.getInterfaceName(),
+                !capabilities.hasCapability(NetworkCapabilities.NET_CAPABILITY_NOT_METERED),
+                capabilities.hasTransport(NetworkCapabilities.TRANSPORT_VPN));
+    }
+
+    private static native void nativeNetworksChanged();
+
+    private static native void nativeDefaultNetworkChanged(
+            String interfaceName, boolean metered, boolean vpn);
+}
@@ -0,0 +1,175 @@
+using System.Threading.Channels;
+using TransportServices.Native;
+
+namespace TransportServices;
+
+/// <summary>Where the library's callbacks for one connection deliver to.</summary>
+internal sealed class ConnectionPump
+{
+    internal Channel<ReceivedMessage> Messages { get; } = EventDispatch.CreateChannel<ReceivedMessage>();
+    internal Channel<ConnectionEventArgs> Events { get; } = EventDispatch.CreateChannel<ConnectionEventArgs>();
+
+    internal void Complete()
+    {
+        Messages.Writer.TryComplete();
+        Events.Writer.TryComplete();
+    }
+}
+
+/// <summary>
+/// A connection to a peer (RFC 9622 Section 8).
+/// </summary>
+/// <remarks>
+/// Received messages are buffered from the moment the connection exists, so
+/// nothing is lost between establishment and the first
+/// <see cref="ReceiveAsync"/>. Events are raised in order on the thread pool.
+/// Dispose the connection when done.
+/// </remarks>
+public sealed class Connection : IAsyncDisposable
+{
+    private readonly ConnectionPump _pump = new();
+    private readonly IntPtr _userData;
+    private IntPtr _handle;
+
+    internal Connection(IntPtr handle)
+    {
+        _handle = handle;
+        _userData = NativeCallbacks.Register(_pump);
+        NativeError result;
+        unsafe
+        {
+            result = NativeMethods.ConnectionSetCallbacks(
+                handle, NativeCallbacks.MessageReceived, NativeCallbacks.EventReceived, _userData);
+        }
+        if (result != NativeError.Success)
+        {
+            NativeCallbacks.Unregister(_userData);
+            NativeMethods.ConnectionFree(handle);
+            _handle = IntPtr.Zero;
+            TransportServicesException.ThrowIfFailed(result, "Connection setup");
+        }
+        EventDispatch.Start(_pump.Events.Reader, e => EventOccurred?.Invoke(this, e));
+    }
+
+    /// <summary>Raised on the thread pool for each event other than received data.</summary>
+    public event EventHandler<ConnectionEventArgs>? EventOccurred;
+
+    /// <summary>The connection's current state.</summary>
+    public ConnectionState State =>
+        _handle == IntPtr.Zero ? ConnectionState.Closed : (ConnectionState)NativeMethods.ConnectionGetState(_handle);
+
+    /// <summary>Send a message, completing once the library has sent it.</summary>
+    public Task SendAsync(ReadOnlyMemory<byte> data, MessageOptions? options = null, CancellationToken cancellationToken = default)
+    {
+        var handle = Handle();
+        var pending = new TaskCompletionSource(TaskCreationOptions.RunContinuationsAsynchronously);
+        var userData = NativeCallbacks.Pass(pending);
+        NativeError result;
+        unsafe
+        {
+            using var pin = data.Pin();
+            var message = new NativeMessage
+            {
+                Data = (byte*)pin.Pointer,
+                Length = (nuint)data.Length,
+                LifetimeMs = (ulong)(options?.Lifetime?.TotalMilliseconds ?? 0),
+                Priority = options?.Priority ?? 0,
+                Idempotent = (byte)(options?.Idempotent == true ? 1 : 0),
+                FinalMessage = (byte)(options?.Final == true ? 1 : 0),
+            };
+            // The library copies the data before returning
+            result = NativeMethods.ConnectionSend(handle, &message, NativeCallbacks.SendCompleted, userData);
+        }
+        if (result != NativeError.Success)
+        {
+            NativeCallbacks.Release(userData);
+            TransportServicesException.ThrowIfFailed(result, "Send");
+        }
+        return pending.Task.WaitAsync(cancellationToken);
+    }
+
+    /// <summary>Wait for the next received message.</summary>
+    /// <exception cref="TransportServicesException">The connection closed first.</exception>
+    public async ValueTask<ReceivedMessage> ReceiveAsync(CancellationToken cancellationToken = default)
+    {
+        try
+        {
+            return await _pump.Messages.Reader.ReadAsync(cancellationToken).ConfigureAwait(false);
+        }
+        catch (ChannelClosedException)
+        {
+            throw new TransportServicesException(TransportServicesErrorCode.ConnectionClosed, "The connection is closed");
+        }
+    }
+
+    /// <summary>Every received message until the connection closes.</summary>
+    public IAsyncEnumerable<ReceivedMessage> ReceiveAllAsync(CancellationToken cancellationToken = default) =>
+        _pump.Messages.Reader.ReadAllAsync(cancellationToken);
+
+    /// <summary>Close gracefully once queued messages are sent.</summary>
+    public Task CloseAsync()
+    {
+        var handle = Handle();
+        var pending = new TaskCompletionSource(TaskCreationOptions.RunContinuationsAsynchronously);
+        var userData = NativeCallbacks.Pass(pending);
+        NativeError result;
+        unsafe
+        {
+            result = NativeMethods.ConnectionCloseAsync(handle, NativeCallbacks.Completed, userData);
+        }
+        if (result != NativeError.Success)
+        {
+            NativeCallbacks.Release(userData);
+            TransportServicesException.ThrowIfFailed(result, "Close");
+        }
+        return pending.Task;
+    }
+
+    /// <summary>Close immediately, discarding queued messages.</summary>
+    public void Abort() => TransportServicesException.ThrowIfFailed(NativeMethods.ConnectionAbort(Handle()), "Abort");
+
+    /// <summary>
+    /// A connection property as JSON, such as <c>connTimeout</c> or
+    /// <c>connCapacityProfile</c>, or null when it is unset or unknown.
+    /// </summary>
+    public string? GetProperty(string key) =>
+        NativeMethods.TakeString(NativeMethods.ConnectionGetPropertyJson(Handle(), key));
+
+    /// <summary>Set a connection property from JSON, such as <c>30000</c> or <c>"scavenger"</c>.</summary>
+    public void SetProperty(string key, string json) =>
+        TransportServicesException.ThrowIfFailed(NativeMethods.ConnectionSetPropertyJson(Handle(), key, json), $"Setting {key}");
+
+    /// <summary>Close the connection if it is open and release it.</summary>
+    public async ValueTask DisposeAsync()
+    {
+        if (_handle == IntPtr.Zero)
+        {
+            return;
+        }
+        if (State is ConnectionState.Establishing or ConnectionState.Established)
+        {
+            try
+            {
+                await CloseAsync().ConfigureAwait(false);
+            }
+            catch (TransportServicesException)
+            {
+                // Already failed or closing; freeing is all that is left
+            }
+        }
+        var handle = Interlocked.Exchange(ref _handle, IntPtr.Zero);
+        if (handle != IntPtr.Zero)
+        {
+            NativeMethods.ConnectionFree(handle);
+            NativeCallbacks.Unregister(_userData);
+            _pump.Complete();
+        }
+    }
+
+    private IntPtr Handle()
+    {
+        var handle = _handle;
+        ObjectDisposedException.ThrowIf(handle == IntPtr.Zero, this);
+        return handle;
+    }
+}
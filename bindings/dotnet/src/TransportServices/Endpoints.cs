@@ -0,0 +1,59 @@
+using System.Runtime.InteropServices;
+using TransportServices.Native;
+
+namespace TransportServices;
+
+/// <summary>Where a connection is made to (RFC 9622 Section 6.1).</summary>
+/// <param name="Host">Host name or IP address.</param>
+/// <param name="Port">Port, or 0 to use <paramref name="Service"/>.</param>
+/// <param name="Service">Service name such as <c>https</c>, resolved to a port.</param>
+public sealed record RemoteEndpoint(string Host, ushort Port = 0, string? Service = null)
+{
+    /// <summary>An endpoint named by host and service.</summary>
+    public static RemoteEndpoint WithService(string host, string service) => new(host, 0, service);
+
+    internal unsafe NativeError AddTo(IntPtr preconnection)
+    {
+        using var host = new Utf8String(Host);
+        using var service = new Utf8String(Service);
+        var endpoint = new NativeEndpoint { Hostname = host.Pointer, Port = Port, Service = service.Pointer };
+        return NativeMethods.PreconnectionAddRemoteEndpoint(preconnection, &endpoint);
+    }
+}
+
+/// <summary>Where a connection is made or accepted from (RFC 9622 Section 6.1).</summary>
+/// <param name="Address">IP address or host name to bind, or null for any.</param>
+/// <param name="Port">Port, or 0 to pick one.</param>
+/// <param name="Interface">Interface name to bind to, or null for any.</param>
+public sealed record LocalEndpoint(string? Address = null, ushort Port = 0, string? Interface = null)
+{
+    /// <summary>The loopback address on a port.</summary>
+    public static LocalEndpoint Loopback(ushort port = 0) => new("127.0.0.1", port);
+
+    internal unsafe NativeError AddTo(IntPtr preconnection)
+    {
+        using var address = new Utf8String(Address);
+        using var iface = new Utf8String(Interface);
+        var endpoint = new NativeEndpoint { Hostname = address.Pointer, Port = Port, Interface = iface.Pointer };
+        return NativeMethods.PreconnectionAddLocalEndpoint(preconnection, &endpoint);
+    }
+}
+
+/// <summary>A NUL-terminated UTF-8 copy of a string for the duration of a call.</summary>
+internal readonly struct Utf8String : IDisposable
+{
+    public IntPtr Pointer { get; }
+
+    public Utf8String(string? value)
+    {
+        Pointer = value is null ? IntPtr.Zero : Marshal.StringToCoTaskMemUTF8(value);
+    }
+
+    public void Dispose()
+    {
+        if (Pointer != IntPtr.Zero)
+        {
+            Marshal.FreeCoTaskMem(Pointer);
+        }
+    }
+}
@@ -0,0 +1,132 @@
+namespace TransportServices;
+
+/// <summary>How strongly a selection property is wanted (RFC 9622 Section 6.2).</summary>
+public enum Preference
+{
+    /// <summary>Select only protocols and paths providing the property.</summary>
+    Require = 0,
+    /// <summary>Prefer protocols and paths providing the property.</summary>
+    Prefer = 1,
+    /// <summary>The property does not affect selection.</summary>
+    NoPreference = 2,
+    /// <summary>Prefer protocols and paths not providing the property.</summary>
+    Avoid = 3,
+    /// <summary>Select only protocols and paths not providing the property.</summary>
+    Prohibit = 4,
+}
+
+/// <summary>Whether and how a connection uses multiple paths.</summary>
+public enum MultipathConfig
+{
+    /// <summary>Use a single path.</summary>
+    Disabled = 0,
+    /// <summary>Open subflows on further paths.</summary>
+    Active = 1,
+    /// <summary>Accept subflows the peer opens.</summary>
+    Passive = 2,
+}
+
+/// <summary>Which directions a connection carries data in.</summary>
+public enum CommunicationDirection
+{
+    /// <summary>Send and receive.</summary>
+    Bidirectional = 0,
+    /// <summary>Send only.</summary>
+    UnidirectionalSend = 1,
+    /// <summary>Receive only.</summary>
+    UnidirectionalReceive = 2,
+}
+
+/// <summary>Which address family to try first when racing candidates.</summary>
+public enum AddressFamilyPreference
+{
+    /// <summary>Try IPv6 first, then IPv4.</summary>
+    PreferIpv6 = 0,
+    /// <summary>Try IPv4 first, then IPv6.</summary>
+    PreferIpv4 = 1,
+    /// <summary>Use only IPv6.</summary>
+    Ipv6Only = 2,
+    /// <summary>Use only IPv4.</summary>
+    Ipv4Only = 3,
+}
+
+/// <summary>The ECN codepoint a message arrived with.</summary>
+public enum EcnMarking
+{
+    /// <summary>Not ECN-capable transport.</summary>
+    NotEct = 0,
+    /// <summary>ECN-capable transport, ECT(0).</summary>
+    Ect0 = 1,
+    /// <summary>ECN-capable transport, ECT(1).</summary>
+    Ect1 = 2,
+    /// <summary>Congestion experienced.</summary>
+    Ce = 3,
+}
+
+/// <summary>The state of a connection (RFC 9622 Section 11).</summary>
+public enum ConnectionState
+{
+    /// <summary>The connection is being established.</summary>
+    Establishing = 0,
+    /// <summary>The connection can send and receive.</summary>
+    Established = 1,
+    /// <summary>The connection is closing.</summary>
+    Closing = 2,
+    /// <summary>The connection is closed.</summary>
+    Closed = 3,
+}
+
+/// <summary>What a <see cref="ConnectionEventArgs"/> reports.</summary>
+public enum ConnectionEventKind
+{
+    /// <summary>The connection was established.</summary>
+    Ready = 0,
+    /// <summary>Establishment failed.</summary>
+    EstablishmentError = 1,
+    /// <summary>The established connection failed.</summary>
+    ConnectionError = 2,
+    /// <summary>The connection moved to another path.</summary>
+    PathChange = 3,
+    /// <summary>An ICMP or similar error was reported.</summary>
+    SoftError = 4,
+    /// <summary>The connection closed.</summary>
+    Closed = 5,
+    /// <summary>A message was sent.</summary>
+    Sent = 6,
+    /// <summary>A message expired before it was sent.</summary>
+    Expired = 7,
+    /// <summary>A message could not be sent.</summary>
+    SendError = 8,
+    /// <summary>The peer finished sending.</summary>
+    FinalReceived = 11,
+    /// <summary>Connection statistics are available.</summary>
+    Stats = 12,
+    /// <summary>The peer stopped responding.</summary>
+    Unresponsive = 13,
+    /// <summary>A message could not be received.</summary>
+    ReceiveError = 14,
+}
+
+/// <summary>Whether a network interface is up.</summary>
+public enum InterfaceStatus
+{
+    /// <summary>The interface is up.</summary>
+    Up = 0,
+    /// <summary>The interface is down.</summary>
+    Down = 1,
+    /// <summary>The status is not known.</summary>
+    Unknown = 2,
+}
+
+/// <summary>What a <see cref="NetworkChange"/> reports.</summary>
+public enum PathChangeKind
+{
+    /// <summary>An interface appeared.</summary>
+    Added = 0,
+    /// <summary>An interface went away.</summary>
+    Removed = 1,
+    /// <summary>An interface's addresses or status changed.</summary>
+    Modified = 2,
+    /// <summary>The default path changed.</summary>
+    PathChanged = 3,
+}
@@ -0,0 +1,31 @@
+using System.Runtime.ExceptionServices;
+using System.Threading.Channels;
+
+namespace TransportServices;
+
+/// <summary>Raises events from a channel, in order, on the .NET thread pool.</summary>
+internal static class EventDispatch
+{
+    internal static void Start<T>(ChannelReader<T> reader, Action<T> raise)
+    {
+        _ = Task.Run(async () =>
+        {
+            await foreach (var item in reader.ReadAllAsync().ConfigureAwait(false))
+            {
+                try
+                {
+                    raise(item);
+                }
+                catch (Exception e)
+                {
+                    // A throwing handler is unhandled, as with any thread pool work
+                    ThreadPool.UnsafeQueueUserWorkItem(static error => ExceptionDispatchInfo.Throw(error), e, preferLocal: false);
+                }
+            }
+        });
+    }
+
+    /// <summary>A channel written by one library callback thread at a time.</summary>
+    internal static Channel<T> CreateChannel<T>() =>
+        Channel.CreateUnbounded<T>(new UnboundedChannelOptions { SingleWriter = true });
+}
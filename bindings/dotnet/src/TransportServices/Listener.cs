@@ -0,0 +1,118 @@
+using System.Threading.Channels;
+using TransportServices.Native;
+
+namespace TransportServices;
+
+/// <summary>A failure to establish an incoming connection.</summary>
+public sealed class ListenerErrorEventArgs : EventArgs
+{
+    /// <summary>The library's description, including the peer if known.</summary>
+    public string Message { get; }
+
+    internal ListenerErrorEventArgs(string message)
+    {
+        Message = message;
+    }
+}
+
+/// <summary>Accepts incoming connections (RFC 9622 Section 7.2).</summary>
+public sealed class Listener : IAsyncDisposable
+{
+    /// <summary>Where the library's callbacks for the listener deliver to.</summary>
+    internal sealed class Callbacks
+    {
+        internal Channel<Connection> Accepted { get; } = EventDispatch.CreateChannel<Connection>();
+        internal Channel<ListenerErrorEventArgs> Errors { get; } = EventDispatch.CreateChannel<ListenerErrorEventArgs>();
+
+        internal void RaiseError(string message) => Errors.Writer.TryWrite(new ListenerErrorEventArgs(message));
+    }
+
+    private readonly Callbacks _callbacks = new();
+    private readonly IntPtr _userData;
+    private IntPtr _handle;
+
+    internal Listener(IntPtr handle)
+    {
+        _handle = handle;
+        _userData = NativeCallbacks.Register(_callbacks);
+        NativeError result;
+        unsafe
+        {
+            result = NativeMethods.ListenerSetCallbacks(
+                handle, NativeCallbacks.ConnectionAccepted, NativeCallbacks.ListenerFailed, _userData);
+        }
+        if (result != NativeError.Success)
+        {
+            NativeCallbacks.Unregister(_userData);
+            NativeMethods.ListenerFree(handle);
+            _handle = IntPtr.Zero;
+            TransportServicesException.ThrowIfFailed(result, "Listener setup");
+        }
+        EventDispatch.Start(_callbacks.Errors.Reader, e => ErrorOccurred?.Invoke(this, e));
+    }
+
+    /// <summary>Raised on the thread pool when an incoming connection fails to establish.</summary>
+    public event EventHandler<ListenerErrorEventArgs>? ErrorOccurred;
+
+    /// <summary>Whether the listener is accepting connections.</summary>
+    public bool IsActive => _handle != IntPtr.Zero && NativeMethods.ListenerIsActive(_handle);
+
+    /// <summary>Wait for the next incoming connection.</summary>
+    /// <exception cref="TransportServicesException">The listener stopped first.</exception>
+    public async ValueTask<Connection> AcceptAsync(CancellationToken cancellationToken = default)
+    {
+        try
+        {
+            return await _callbacks.Accepted.Reader.ReadAsync(cancellationToken).ConfigureAwait(false);
+        }
+        catch (ChannelClosedException)
+        {
+            throw new TransportServicesException(TransportServicesErrorCode.InvalidState, "The listener is stopped");
+        }
+    }
+
+    /// <summary>Every incoming connection until the listener stops.</summary>
+    public IAsyncEnumerable<Connection> AcceptAllAsync(CancellationToken cancellationToken = default) =>
+        _callbacks.Accepted.Reader.ReadAllAsync(cancellationToken);
+
+    /// <summary>Stop accepting connections; accepted ones stay open.</summary>
+    public async Task StopAsync()
+    {
+        var handle = _handle;
+        ObjectDisposedException.ThrowIf(handle == IntPtr.Zero, this);
+        var pending = new TaskCompletionSource(TaskCreationOptions.RunContinuationsAsynchronously);
+        var userData = NativeCallbacks.Pass(pending);
+        NativeError result;
+        unsafe
+        {
+            result = NativeMethods.ListenerStopAsync(handle, NativeCallbacks.Completed, userData);
+        }
+        if (result != NativeError.Success)
+        {
+            NativeCallbacks.Release(userData);
+            TransportServicesException.ThrowIfFailed(result, "Stop");
+        }
+        await pending.Task.ConfigureAwait(false);
+        _callbacks.Accepted.Writer.TryComplete();
+        _callbacks.Errors.Writer.TryComplete();
+    }
+
+    /// <summary>Stop the listener if it is active and release it.</summary>
+    public async ValueTask DisposeAsync()
+    {
+        if (_handle == IntPtr.Zero)
+        {
+            return;
+        }
+        if (IsActive)
+        {
+            await StopAsync().ConfigureAwait(false);
+        }
+        var handle = Interlocked.Exchange(ref _handle, IntPtr.Zero);
+        if (handle != IntPtr.Zero)
+        {
+            NativeMethods.ListenerFree(handle);
+            NativeCallbacks.Unregister(_userData);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+using TransportServices.Native;
+
+namespace TransportServices;
+
+/// <summary>Per-message properties for <see cref="Connection.SendAsync"/> (RFC 9622 Section 9.1.3).</summary>
+public sealed record MessageOptions
+{
+    /// <summary>How long the message may wait before it is dropped.</summary>
+    public TimeSpan? Lifetime { get; init; }
+    /// <summary>Priority among the connection's messages; lower is more important.</summary>
+    public int Priority { get; init; }
+    /// <summary>Whether the message may be replayed, as 0-RTT data requires.</summary>
+    public bool Idempotent { get; init; }
+    /// <summary>Whether this is the last message sent on the connection.</summary>
+    public bool Final { get; init; }
+}
+
+/// <summary>A message received on a <see cref="Connection"/>, with its context.</summary>
+public sealed class ReceivedMessage
+{
+    /// <summary>The message data.</summary>
+    public ReadOnlyMemory<byte> Data { get; }
+    /// <summary>The peer's address as <c>host:port</c>, if known.</summary>
+    public string? RemoteEndpoint { get; }
+    /// <summary>The local address as <c>host:port</c>, if known.</summary>
+    public string? LocalEndpoint { get; }
+    /// <summary>The ECN codepoint the message arrived with, if reported.</summary>
+    public EcnMarking? Ecn { get; }
+    /// <summary>Whether the message arrived as 0-RTT data.</summary>
+    public bool IsEarlyData { get; }
+    /// <summary>Whether the peer sends nothing after this message.</summary>
+    public bool IsFinal { get; }
+    /// <summary>Whether the data completes its message; false for earlier partial deliveries.</summary>
+    public bool IsEndOfMessage { get; }
+    /// <summary>When the message was received, if known.</summary>
+    public DateTimeOffset? ReceivedAt { get; }
+
+    private ReceivedMessage(
+        byte[] data,
+        string? remoteEndpoint,
+        string? localEndpoint,
+        EcnMarking? ecn,
+        bool isEarlyData,
+        bool isFinal,
+        bool isEndOfMessage,
+        DateTimeOffset? receivedAt)
+    {
+        Data = data;
+        RemoteEndpoint = remoteEndpoint;
+        LocalEndpoint = localEndpoint;
+        Ecn = ecn;
+        IsEarlyData = isEarlyData;
+        IsFinal = isFinal;
+        IsEndOfMessage = isEndOfMessage;
+        ReceivedAt = receivedAt;
+    }
+
+    /// <summary>Copy a message and its context, which are only valid during the callback.</summary>
+    internal static unsafe ReceivedMessage Copy(NativeMessage* message, IntPtr context)
+    {
+        var data = new ReadOnlySpan<byte>(message->Data, checked((int)message->Length)).ToArray();
+        if (context == IntPtr.Zero)
+        {
+            return new ReceivedMessage(data, null, null, null, false, message->FinalMessage != 0, true, null);
+        }
+
+        EcnMarking? ecn = NativeMethods.MessageContextGetEcn(context, out var marking) == 0 ? marking : null;
+        DateTimeOffset? receivedAt = NativeMethods.MessageContextGetReceivedTime(context, out var micros) == 0
+            ? DateTimeOffset.UnixEpoch.AddTicks((long)micros * 10)
+            : null;
+        return new ReceivedMessage(
+            data,
+            NativeMethods.TakeString(NativeMethods.MessageContextGetRemoteEndpoint(context)),
+            NativeMethods.TakeString(NativeMethods.MessageContextGetLocalEndpoint(context)),
+            ecn,
+            NativeMethods.MessageContextIsEarlyData(context),
+            NativeMethods.MessageContextIsFinal(context),
+            NativeMethods.MessageContextIsEndOfMessage(context),
+            receivedAt);
+    }
+}
+
+/// <summary>An event on a <see cref="Connection"/> other than received data.</summary>
+public sealed class ConnectionEventArgs : EventArgs
+{
+    /// <summary>What happened.</summary>
+    public ConnectionEventKind Kind { get; }
+    /// <summary>The library's description, such as an error message.</summary>
+    public string? Message { get; }
+
+    internal ConnectionEventArgs(ConnectionEventKind kind, string? message)
+    {
+        Kind = kind;
+        Message = message;
+    }
+}
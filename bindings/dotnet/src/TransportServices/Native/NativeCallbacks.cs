@@ -0,0 +1,211 @@
+using System.Collections.Concurrent;
+using System.Runtime.CompilerServices;
+using System.Runtime.InteropServices;
+using System.Threading.Channels;
+
+namespace TransportServices.Native;
+
+/// <summary>
+/// Entry points the library calls back into, from its own worker threads.
+/// </summary>
+/// <remarks>
+/// One-shot callbacks get a <see cref="GCHandle"/> to the task waiting on
+/// them and free it. Streaming callbacks get an id registered here instead,
+/// since the library may call once more after the owner stops it; a call
+/// for an unregistered id is dropped. Callbacks only copy data out and
+/// complete tasks or write to channels, so no application code runs on a
+/// library thread. Exceptions must not unwind into native code, so every
+/// callback catches.
+/// </remarks>
+internal static unsafe class NativeCallbacks
+{
+    internal static delegate* unmanaged[Cdecl]<IntPtr, IntPtr, void> Initiated => &OnInitiated;
+    internal static delegate* unmanaged[Cdecl]<NativeError, IntPtr, IntPtr, void> InitiateFailed => &OnInitiateFailed;
+    internal static delegate* unmanaged[Cdecl]<NativeError, IntPtr, IntPtr, void> SendCompleted => &OnSendCompleted;
+    internal static delegate* unmanaged[Cdecl]<NativeError, IntPtr, void> Completed => &OnCompleted;
+    internal static delegate* unmanaged[Cdecl]<NativeMessage*, IntPtr, IntPtr, void> MessageReceived => &OnMessageReceived;
+    internal static delegate* unmanaged[Cdecl]<NativeEventType, IntPtr, IntPtr, void> EventReceived => &OnEventReceived;
+    internal static delegate* unmanaged[Cdecl]<IntPtr, IntPtr, void> ConnectionAccepted => &OnConnectionAccepted;
+    internal static delegate* unmanaged[Cdecl]<NativeError, IntPtr, IntPtr, void> ListenerFailed => &OnListenerFailed;
+    internal static delegate* unmanaged[Cdecl]<NativeChangeEvent*, IntPtr, void> PathChanged => &OnPathChanged;
+
+    /// <summary>User data for a callback that will receive <paramref name="state"/>.</summary>
+    internal static IntPtr Pass(object state) => GCHandle.ToIntPtr(GCHandle.Alloc(state));
+
+    /// <summary>Free user data whose callback will not be called.</summary>
+    internal static void Release(IntPtr userData) => GCHandle.FromIntPtr(userData).Free();
+
+    private static readonly ConcurrentDictionary<IntPtr, object> Streams = new();
+    private static long _nextStream;
+
+    /// <summary>User data for streaming callbacks delivering to <paramref name="target"/>.</summary>
+    internal static IntPtr Register(object target)
+    {
+        var id = (IntPtr)Interlocked.Increment(ref _nextStream);
+        Streams[id] = target;
+        return id;
+    }
+
+    /// <summary>Drop any further streaming callbacks for an id.</summary>
+    internal static void Unregister(IntPtr id) => Streams.TryRemove(id, out _);
+
+    private static T? Lookup<T>(IntPtr id) where T : class =>
+        Streams.TryGetValue(id, out var target) ? (T)target : null;
+
+    private static T Take<T>(IntPtr userData)
+    {
+        var handle = GCHandle.FromIntPtr(userData);
+        var target = (T)handle.Target!;
+        handle.Free();
+        return target;
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static void OnInitiated(IntPtr connection, IntPtr userData)
+    {
+        var pending = Take<TaskCompletionSource<Connection>>(userData);
+        try
+        {
+            var established = new Connection(connection);
+            // Initiation was cancelled while in flight
+            if (!pending.TrySetResult(established))
+            {
+                established.Abort();
+                _ = established.DisposeAsync();
+            }
+        }
+        catch (Exception e)
+        {
+            pending.TrySetException(e);
+        }
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static void OnInitiateFailed(NativeError error, IntPtr message, IntPtr userData)
+    {
+        var pending = Take<TaskCompletionSource<Connection>>(userData);
+        pending.TrySetException(TransportServicesException.FromNative(
+            error, NativeMethods.PtrToString(message), "Connection initiation failed"));
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static void OnSendCompleted(NativeError error, IntPtr message, IntPtr userData)
+    {
+        var pending = Take<TaskCompletionSource>(userData);
+        if (error == NativeError.Success)
+        {
+            pending.TrySetResult();
+        }
+        else
+        {
+            pending.TrySetException(TransportServicesException.FromNative(
+                error, NativeMethods.PtrToString(message), "Send failed"));
+        }
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static void OnCompleted(NativeError error, IntPtr userData)
+    {
+        var pending = Take<TaskCompletionSource>(userData);
+        if (error == NativeError.Success)
+        {
+            pending.TrySetResult();
+        }
+        else
+        {
+            pending.TrySetException(TransportServicesException.FromNative(error, null, $"Operation failed ({error})"));
+        }
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static void OnMessageReceived(NativeMessage* message, IntPtr context, IntPtr userData)
+    {
+        try
+        {
+            Lookup<ConnectionPump>(userData)?.Messages.Writer.TryWrite(ReceivedMessage.Copy(message, context));
+        }
+        catch (Exception e)
+        {
+            Fail(e);
+        }
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static void OnEventReceived(NativeEventType type, IntPtr message, IntPtr userData)
+    {
+        try
+        {
+            if (Lookup<ConnectionPump>(userData) is not { } pump)
+            {
+                return;
+            }
+            pump.Events.Writer.TryWrite(new ConnectionEventArgs((ConnectionEventKind)type, NativeMethods.PtrToString(message)));
+
+            // The library stops calling after Closed
+            if (type == NativeEventType.Closed)
+            {
+                pump.Complete();
+                Unregister(userData);
+            }
+        }
+        catch (Exception e)
+        {
+            Fail(e);
+        }
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static void OnConnectionAccepted(IntPtr connection, IntPtr userData)
+    {
+        try
+        {
+            var wrapped = new Connection(connection);
+            // Arrived as the listener was disposed
+            if (Lookup<Listener.Callbacks>(userData)?.Accepted.Writer.TryWrite(wrapped) != true)
+            {
+                wrapped.Abort();
+                _ = wrapped.DisposeAsync();
+            }
+        }
+        catch (Exception e)
+        {
+            Fail(e);
+        }
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static void OnListenerFailed(NativeError error, IntPtr message, IntPtr userData)
+    {
+        try
+        {
+            Lookup<Listener.Callbacks>(userData)?.RaiseError(
+                NativeMethods.PtrToString(message) ?? $"Establishment failed ({error})");
+        }
+        catch (Exception e)
+        {
+            Fail(e);
+        }
+    }
+
+    [UnmanagedCallersOnly(CallConvs = new[] { typeof(CallConvCdecl) })]
+    private static void OnPathChanged(NativeChangeEvent* change, IntPtr userData)
+    {
+        try
+        {
+            Lookup<Channel<NetworkChange>>(userData)?.Writer.TryWrite(NetworkChange.Copy(change));
+        }
+        catch (Exception e)
+        {
+            Fail(e);
+        }
+    }
+
+    // A bug in the bindings; surface it on the thread pool like any unhandled exception
+    private static void Fail(Exception e)
+    {
+        ThreadPool.UnsafeQueueUserWorkItem(
+            static error => System.Runtime.ExceptionServices.ExceptionDispatchInfo.Throw(error),
+            e,
+            preferLocal: false);
+    }
+}
@@ -0,0 +1,275 @@
+using System.Runtime.CompilerServices;
+using System.Runtime.InteropServices;
+
+namespace TransportServices.Native;
+
+/// <summary>
+/// P/Invoke declarations for the C API in <c>transport_services.h</c>.
+/// </summary>
+/// <remarks>
+/// Callbacks are passed as unmanaged function pointers to static
+/// <see cref="UnmanagedCallersOnlyAttribute"/> methods so the bindings work
+/// under NativeAOT and on iOS, where the library is linked statically.
+/// </remarks>
+internal static unsafe partial class NativeMethods
+{
+    internal const string LibraryName = "transport_services";
+
+    [ModuleInitializer]
+    internal static void RegisterResolver()
+    {
+        NativeLibrary.SetDllImportResolver(typeof(NativeMethods).Assembly, Resolve);
+    }
+
+    // iOS apps link the static library into the executable
+    private static IntPtr Resolve(string name, System.Reflection.Assembly assembly, DllImportSearchPath? path)
+    {
+        if (name == LibraryName && (OperatingSystem.IsIOS() || OperatingSystem.IsTvOS() || OperatingSystem.IsMacCatalyst()))
+        {
+            return NativeLibrary.GetMainProgramHandle();
+        }
+        return IntPtr.Zero;
+    }
+
+    // Runtime
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_init_runtime")]
+    internal static partial int InitRuntime();
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_shutdown_runtime")]
+    internal static partial void ShutdownRuntime();
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_version")]
+    internal static partial IntPtr Version();
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_get_last_error")]
+    internal static partial IntPtr GetLastError();
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_free_string")]
+    internal static partial void FreeString(IntPtr s);
+
+    // Transport properties
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_new_transport_properties")]
+    internal static partial IntPtr NewTransportProperties();
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_free_transport_properties")]
+    internal static partial void FreeTransportProperties(IntPtr handle);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_set_preference")]
+    internal static partial int SetPreference(IntPtr handle, int property, Preference preference);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_set_multipath")]
+    internal static partial int SetMultipath(IntPtr handle, MultipathConfig config);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_set_direction")]
+    internal static partial int SetDirection(IntPtr handle, CommunicationDirection direction);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_set_advertises_altaddr")]
+    internal static partial int SetAdvertisesAltaddr(IntPtr handle, [MarshalAs(UnmanagedType.U1)] bool value);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_set_interface", StringMarshalling = StringMarshalling.Utf8)]
+    internal static partial int SetInterface(IntPtr handle, string name, Preference preference);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_set_pvd", StringMarshalling = StringMarshalling.Utf8)]
+    internal static partial int SetPvd(IntPtr handle, string pvd, Preference preference);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_set_connection_priority")]
+    internal static partial int SetConnectionPriority(IntPtr handle, int priority);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_set_duration_property")]
+    internal static partial int SetDurationProperty(IntPtr handle, int property, ulong valueMs);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_set_size_property")]
+    internal static partial int SetSizeProperty(IntPtr handle, int property, nuint value);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_set_bool_property")]
+    internal static partial int SetBoolProperty(IntPtr handle, int property, [MarshalAs(UnmanagedType.U1)] bool value);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_set_rate_property")]
+    internal static partial int SetRateProperty(IntPtr handle, int property, ulong rateBps);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_set_address_family_preference")]
+    internal static partial int SetAddressFamilyPreference(IntPtr handle, AddressFamilyPreference preference);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_get_preference")]
+    internal static partial int GetPreference(IntPtr handle, int property, out Preference preference);
+
+    // Security parameters
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_new_security_parameters")]
+    internal static partial IntPtr NewSecurityParameters();
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_new_disabled_security_parameters")]
+    internal static partial IntPtr NewDisabledSecurityParameters();
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_new_opportunistic_security_parameters")]
+    internal static partial IntPtr NewOpportunisticSecurityParameters();
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_free_security_parameters")]
+    internal static partial void FreeSecurityParameters(IntPtr handle);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_set_alpn")]
+    internal static partial int SetAlpn(IntPtr handle, IntPtr* protocols, nuint count);
+
+    // Preconnection
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_preconnection_new")]
+    internal static partial IntPtr PreconnectionNew();
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_preconnection_add_local_endpoint")]
+    internal static partial NativeError PreconnectionAddLocalEndpoint(IntPtr handle, NativeEndpoint* endpoint);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_preconnection_add_remote_endpoint")]
+    internal static partial NativeError PreconnectionAddRemoteEndpoint(IntPtr handle, NativeEndpoint* endpoint);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_preconnection_use_transport_properties")]
+    internal static partial NativeError PreconnectionUseTransportProperties(IntPtr handle, IntPtr properties);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_preconnection_use_security_parameters")]
+    internal static partial NativeError PreconnectionUseSecurityParameters(IntPtr handle, IntPtr parameters);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_preconnection_initiate")]
+    internal static partial NativeError PreconnectionInitiate(
+        IntPtr handle,
+        delegate* unmanaged[Cdecl]<IntPtr, IntPtr, void> callback,
+        delegate* unmanaged[Cdecl]<NativeError, IntPtr, IntPtr, void> errorCallback,
+        IntPtr userData);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_preconnection_listen")]
+    internal static partial IntPtr PreconnectionListen(IntPtr handle);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_preconnection_free")]
+    internal static partial void PreconnectionFree(IntPtr handle);
+
+    // Connection
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_connection_get_state")]
+    internal static partial NativeConnectionState ConnectionGetState(IntPtr handle);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_connection_send")]
+    internal static partial NativeError ConnectionSend(
+        IntPtr handle,
+        NativeMessage* message,
+        delegate* unmanaged[Cdecl]<NativeError, IntPtr, IntPtr, void> callback,
+        IntPtr userData);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_connection_set_callbacks")]
+    internal static partial NativeError ConnectionSetCallbacks(
+        IntPtr handle,
+        delegate* unmanaged[Cdecl]<NativeMessage*, IntPtr, IntPtr, void> messageCallback,
+        delegate* unmanaged[Cdecl]<NativeEventType, IntPtr, IntPtr, void> eventCallback,
+        IntPtr userData);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_connection_close_async")]
+    internal static partial NativeError ConnectionCloseAsync(
+        IntPtr handle,
+        delegate* unmanaged[Cdecl]<NativeError, IntPtr, void> callback,
+        IntPtr userData);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_connection_abort")]
+    internal static partial NativeError ConnectionAbort(IntPtr handle);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_connection_set_property_json", StringMarshalling = StringMarshalling.Utf8)]
+    internal static partial NativeError ConnectionSetPropertyJson(IntPtr handle, string key, string jsonValue);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_connection_get_property_json", StringMarshalling = StringMarshalling.Utf8)]
+    internal static partial IntPtr ConnectionGetPropertyJson(IntPtr handle, string key);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_connection_free")]
+    internal static partial void ConnectionFree(IntPtr handle);
+
+    // Received message context, valid only during the receive callback
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_message_context_get_remote_endpoint")]
+    internal static partial IntPtr MessageContextGetRemoteEndpoint(IntPtr context);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_message_context_get_local_endpoint")]
+    internal static partial IntPtr MessageContextGetLocalEndpoint(IntPtr context);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_message_context_get_ecn")]
+    internal static partial int MessageContextGetEcn(IntPtr context, out EcnMarking ecn);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_message_context_is_early_data")]
+    [return: MarshalAs(UnmanagedType.U1)]
+    internal static partial bool MessageContextIsEarlyData(IntPtr context);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_message_context_is_final")]
+    [return: MarshalAs(UnmanagedType.U1)]
+    internal static partial bool MessageContextIsFinal(IntPtr context);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_message_context_is_end_of_message")]
+    [return: MarshalAs(UnmanagedType.U1)]
+    internal static partial bool MessageContextIsEndOfMessage(IntPtr context);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_message_context_get_received_time")]
+    internal static partial int MessageContextGetReceivedTime(IntPtr context, out ulong unixMicros);
+
+    // Listener
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_listener_set_callbacks")]
+    internal static partial NativeError ListenerSetCallbacks(
+        IntPtr handle,
+        delegate* unmanaged[Cdecl]<IntPtr, IntPtr, void> connectionReceivedCallback,
+        delegate* unmanaged[Cdecl]<NativeError, IntPtr, IntPtr, void> errorCallback,
+        IntPtr userData);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_listener_stop_async")]
+    internal static partial NativeError ListenerStopAsync(
+        IntPtr handle,
+        delegate* unmanaged[Cdecl]<NativeError, IntPtr, void> callback,
+        IntPtr userData);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_listener_is_active")]
+    [return: MarshalAs(UnmanagedType.U1)]
+    internal static partial bool ListenerIsActive(IntPtr handle);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_listener_free")]
+    internal static partial void ListenerFree(IntPtr handle);
+
+    // Path monitor
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_path_monitor_create")]
+    internal static partial IntPtr PathMonitorCreate();
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_path_monitor_destroy")]
+    internal static partial void PathMonitorDestroy(IntPtr handle);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_path_monitor_list_interfaces")]
+    internal static partial int PathMonitorListInterfaces(IntPtr handle, NativeInterface*** interfaces, nuint* count);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_path_monitor_free_interfaces")]
+    internal static partial void PathMonitorFreeInterfaces(NativeInterface** interfaces, nuint count);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_path_monitor_start_watching")]
+    internal static partial IntPtr PathMonitorStartWatching(
+        IntPtr handle,
+        delegate* unmanaged[Cdecl]<NativeChangeEvent*, IntPtr, void> callback,
+        IntPtr userData);
+
+    [LibraryImport(LibraryName, EntryPoint = "transport_services_path_monitor_stop_watching")]
+    internal static partial void PathMonitorStopWatching(IntPtr handle);
+
+    /// <summary>Copy a UTF-8 string the library owns.</summary>
+    internal static string? PtrToString(IntPtr s) => s == IntPtr.Zero ? null : Marshal.PtrToStringUTF8(s);
+
+    /// <summary>Copy and free a UTF-8 string the library handed over.</summary>
+    internal static string? TakeString(IntPtr s)
+    {
+        if (s == IntPtr.Zero)
+        {
+            return null;
+        }
+        try
+        {
+            return Marshal.PtrToStringUTF8(s);
+        }
+        finally
+        {
+            FreeString(s);
+        }
+    }
+
+    /// <summary>The calling thread's last error message, if any.</summary>
+    internal static string? LastError() => PtrToString(GetLastError());
+}
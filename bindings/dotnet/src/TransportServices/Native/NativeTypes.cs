@@ -0,0 +1,120 @@
+using System.Runtime.InteropServices;
+
+namespace TransportServices.Native;
+
+/// <summary>Mirrors <c>transport_services_error_t</c>.</summary>
+internal enum NativeError
+{
+    Success = 0,
+    InvalidParameters = -1,
+    EstablishmentFailed = -2,
+    ConnectionFailed = -3,
+    SendFailed = -4,
+    ReceiveFailed = -5,
+    NotSupported = -6,
+    Timeout = -7,
+    InvalidState = -8,
+    SecurityError = -9,
+    IoError = -10,
+    RuntimeError = -11,
+    WouldBlock = -12,
+    Unknown = -99,
+}
+
+/// <summary>Mirrors <c>transport_services_connection_state_t</c>.</summary>
+internal enum NativeConnectionState
+{
+    Establishing = 0,
+    Established = 1,
+    Closing = 2,
+    Closed = 3,
+}
+
+/// <summary>Mirrors <c>transport_services_connection_event_type_t</c>.</summary>
+internal enum NativeEventType
+{
+    Ready = 0,
+    EstablishmentError = 1,
+    ConnectionError = 2,
+    PathChange = 3,
+    SoftError = 4,
+    Closed = 5,
+    Sent = 6,
+    Expired = 7,
+    SendError = 8,
+    Received = 9,
+    ReceivedPartial = 10,
+    FinalReceived = 11,
+    Stats = 12,
+    Unresponsive = 13,
+    ReceiveError = 14,
+}
+
+/// <summary>Mirrors <c>transport_services_endpoint_t</c>.</summary>
+[StructLayout(LayoutKind.Sequential)]
+internal struct NativeEndpoint
+{
+    public IntPtr Hostname;
+    public ushort Port;
+    public IntPtr Service;
+    public IntPtr Interface;
+}
+
+/// <summary>Mirrors <c>transport_services_message_t</c>.</summary>
+[StructLayout(LayoutKind.Sequential)]
+internal unsafe struct NativeMessage
+{
+    public byte* Data;
+    public nuint Length;
+    public ulong LifetimeMs;
+    public int Priority;
+    public byte Idempotent;
+    public byte FinalMessage;
+}
+
+/// <summary>Mirrors <c>transport_services_interface_t</c>.</summary>
+[StructLayout(LayoutKind.Sequential)]
+internal unsafe struct NativeInterface
+{
+    public IntPtr Name;
+    public uint Index;
+    public IntPtr* Ips;
+    public nuint IpCount;
+    public InterfaceStatus Status;
+    public IntPtr InterfaceType;
+    public byte IsExpensive;
+}
+
+/// <summary>Mirrors <c>transport_services_change_event_t</c>.</summary>
+[StructLayout(LayoutKind.Sequential)]
+internal unsafe struct NativeChangeEvent
+{
+    public PathChangeKind EventType;
+    public NativeInterface* Interface;
+    public NativeInterface* OldInterface;
+    public IntPtr Description;
+}
+
+/// <summary>Property constants from <c>transport_services.h</c>.</summary>
+internal static class NativeProperty
+{
+    public const int ConnectionTimeout = 13;
+    public const int KeepAliveTimeout = 14;
+    public const int StatsInterval = 15;
+    public const int NatKeepaliveInterval = 16;
+    public const int ConnectionAttemptDelay = 17;
+    public const int CandidateTimeout = 18;
+
+    public const int MaximumMessageSizeOnSend = 19;
+    public const int MaximumMessageSizeOnReceive = 20;
+    public const int SendBufferSize = 21;
+    public const int ReceiveBufferSize = 22;
+    public const int MaxParallelAttempts = 25;
+
+    public const int NoDelay = 26;
+
+    public const int MaxSendRate = 30;
+    public const int MaxRecvRate = 31;
+
+    public const int PathPinning = 32;
+}
@@ -0,0 +1,201 @@
+using System.Threading.Channels;
+using TransportServices.Native;
+
+namespace TransportServices;
+
+/// <summary>A network interface as the path monitor sees it.</summary>
+/// <param name="Name">Interface name, such as <c>en0</c> or <c>wlan0</c>.</param>
+/// <param name="Index">The system's interface index.</param>
+/// <param name="Addresses">Addresses assigned to the interface.</param>
+/// <param name="Status">Whether the interface is up.</param>
+/// <param name="Type">Kind of interface, such as <c>wifi</c> or <c>cellular</c>.</param>
+/// <param name="IsExpensive">Whether traffic on it may cost the user, as on cellular.</param>
+public sealed record NetworkInterface(
+    string Name,
+    uint Index,
+    IReadOnlyList<string> Addresses,
+    InterfaceStatus Status,
+    string? Type,
+    bool IsExpensive)
+{
+    internal static unsafe NetworkInterface? Copy(NativeInterface* native)
+    {
+        if (native == null)
+        {
+            return null;
+        }
+        var addresses = new string[checked((int)native->IpCount)];
+        for (var i = 0; i < addresses.Length; i++)
+        {
+            addresses[i] = NativeMethods.PtrToString(native->Ips[i]) ?? "";
+        }
+        return new NetworkInterface(
+            NativeMethods.PtrToString(native->Name) ?? "",
+            native->Index,
+            addresses,
+            native->Status,
+            NativeMethods.PtrToString(native->InterfaceType),
+            native->IsExpensive != 0);
+    }
+}
+
+/// <summary>A change in the available network interfaces or paths.</summary>
+public sealed class NetworkChange : EventArgs
+{
+    /// <summary>What changed.</summary>
+    public PathChangeKind Kind { get; }
+    /// <summary>The interface added, removed or as modified.</summary>
+    public NetworkInterface? Interface { get; }
+    /// <summary>The interface before modification.</summary>
+    public NetworkInterface? OldInterface { get; }
+    /// <summary>Description of a path change.</summary>
+    public string? Description { get; }
+
+    private NetworkChange(PathChangeKind kind, NetworkInterface? iface, NetworkInterface? oldInterface, string? description)
+    {
+        Kind = kind;
+        Interface = iface;
+        OldInterface = oldInterface;
+        Description = description;
+    }
+
+    /// <summary>Copy an event, which is only valid during the callback.</summary>
+    internal static unsafe NetworkChange Copy(NativeChangeEvent* change) => new(
+        change->EventType,
+        NetworkInterface.Copy(change->Interface),
+        NetworkInterface.Copy(change->OldInterface),
+        NativeMethods.PtrToString(change->Description));
+}
+
+/// <summary>
+/// Watches network interfaces so applications can steer path selection,
+/// for example preferring Wi-Fi and avoiding expensive cellular paths.
+/// </summary>
+/// <remarks>
+/// Watching starts with the first <see cref="Changed"/> handler and stops
+/// with the last. Changes are raised in order on the thread pool.
+/// </remarks>
+public sealed class PathMonitor : IDisposable
+{
+    private readonly object _lock = new();
+    private IntPtr _handle;
+    private IntPtr _watcher;
+    private IntPtr _userData;
+    private Channel<NetworkChange>? _changes;
+    private EventHandler<NetworkChange>? _changed;
+
+    /// <summary>Create a path monitor.</summary>
+    public PathMonitor()
+    {
+        _handle = NativeMethods.PathMonitorCreate();
+        if (_handle == IntPtr.Zero)
+        {
+            throw new TransportServicesException(
+                TransportServicesErrorCode.NotSupported,
+                NativeMethods.LastError() ?? "Failed to create network monitor");
+        }
+    }
+
+    /// <summary>Raised on the thread pool when interfaces or paths change.</summary>
+    public event EventHandler<NetworkChange>? Changed
+    {
+        add
+        {
+            lock (_lock)
+            {
+                _changed += value;
+                if (_watcher == IntPtr.Zero && _changed is not null)
+                {
+                    StartWatching();
+                }
+            }
+        }
+        remove
+        {
+            lock (_lock)
+            {
+                _changed -= value;
+                if (_changed is null)
+                {
+                    StopWatching();
+                }
+            }
+        }
+    }
+
+    /// <summary>The current network interfaces.</summary>
+    public unsafe IReadOnlyList<NetworkInterface> GetInterfaces()
+    {
+        lock (_lock)
+        {
+            ObjectDisposedException.ThrowIf(_handle == IntPtr.Zero, this);
+            NativeInterface** interfaces = null;
+            nuint count = 0;
+            if (NativeMethods.PathMonitorListInterfaces(_handle, &interfaces, &count) != 0)
+            {
+                throw new TransportServicesException(
+                    TransportServicesErrorCode.IoError,
+                    NativeMethods.LastError() ?? "Failed to list interfaces");
+            }
+            try
+            {
+                var result = new List<NetworkInterface>(checked((int)count));
+                for (nuint i = 0; i < count; i++)
+                {
+                    result.Add(NetworkInterface.Copy(interfaces[i])!);
+                }
+                return result;
+            }
+            finally
+            {
+                NativeMethods.PathMonitorFreeInterfaces(interfaces, count);
+            }
+        }
+    }
+
+    /// <summary>Stop watching and release the monitor.</summary>
+    public void Dispose()
+    {
+        lock (_lock)
+        {
+            StopWatching();
+            if (_handle != IntPtr.Zero)
+            {
+                NativeMethods.PathMonitorDestroy(_handle);
+                _handle = IntPtr.Zero;
+            }
+        }
+    }
+
+    private unsafe void StartWatching()
+    {
+        ObjectDisposedException.ThrowIf(_handle == IntPtr.Zero, this);
+        var changes = EventDispatch.CreateChannel<NetworkChange>();
+        _userData = NativeCallbacks.Register(changes);
+        _watcher = NativeMethods.PathMonitorStartWatching(_handle, NativeCallbacks.PathChanged, _userData);
+        if (_watcher == IntPtr.Zero)
+        {
+            NativeCallbacks.Unregister(_userData);
+            _userData = IntPtr.Zero;
+            throw new TransportServicesException(
+                TransportServicesErrorCode.NotSupported,
+                NativeMethods.LastError() ?? "Failed to watch for network changes");
+        }
+        _changes = changes;
+        EventDispatch.Start(changes.Reader, change => _changed?.Invoke(this, change));
+    }
+
+    private void StopWatching()
+    {
+        if (_watcher == IntPtr.Zero)
+        {
+            return;
+        }
+        NativeMethods.PathMonitorStopWatching(_watcher);
+        _watcher = IntPtr.Zero;
+        NativeCallbacks.Unregister(_userData);
+        _userData = IntPtr.Zero;
+        _changes?.Writer.TryComplete();
+        _changes = null;
+    }
+}
@@ -0,0 +1,148 @@
+using TransportServices.Native;
+
+namespace TransportServices;
+
+/// <summary>
+/// Endpoints and properties from which connections are initiated or
+/// accepted (RFC 9622 Section 6).
+/// </summary>
+public sealed class Preconnection : IDisposable
+{
+    private IntPtr _handle;
+
+    /// <summary>Create a preconnection.</summary>
+    /// <param name="remoteEndpoints">Where to connect to.</param>
+    /// <param name="localEndpoints">Where to connect or listen from.</param>
+    /// <param name="transportProperties">Path and protocol selection preferences.</param>
+    /// <param name="securityParameters">Security to use; TLS by default.</param>
+    public Preconnection(
+        IEnumerable<RemoteEndpoint>? remoteEndpoints = null,
+        IEnumerable<LocalEndpoint>? localEndpoints = null,
+        TransportProperties? transportProperties = null,
+        SecurityParameters? securityParameters = null)
+    {
+        TransportServicesRuntime.EnsureInitialized();
+        _handle = NativeMethods.PreconnectionNew();
+        try
+        {
+            foreach (var endpoint in remoteEndpoints ?? Enumerable.Empty<RemoteEndpoint>())
+            {
+                AddRemote(endpoint);
+            }
+            foreach (var endpoint in localEndpoints ?? Enumerable.Empty<LocalEndpoint>())
+            {
+                AddLocal(endpoint);
+            }
+            if (transportProperties is not null)
+            {
+                SetTransportProperties(transportProperties);
+            }
+            if (securityParameters is not null)
+            {
+                SetSecurityParameters(securityParameters);
+            }
+        }
+        catch
+        {
+            Dispose();
+            throw;
+        }
+    }
+
+    /// <summary>Add a remote endpoint.</summary>
+    public void AddRemote(RemoteEndpoint endpoint) =>
+        TransportServicesException.ThrowIfFailed(endpoint.AddTo(Handle()), "Adding remote endpoint");
+
+    /// <summary>Add a local endpoint.</summary>
+    public void AddLocal(LocalEndpoint endpoint) =>
+        TransportServicesException.ThrowIfFailed(endpoint.AddTo(Handle()), "Adding local endpoint");
+
+    /// <summary>Replace the transport properties.</summary>
+    public void SetTransportProperties(TransportProperties properties)
+    {
+        var native = properties.CreateNative();
+        try
+        {
+            TransportServicesException.ThrowIfFailed(
+                NativeMethods.PreconnectionUseTransportProperties(Handle(), native), "Setting transport properties");
+        }
+        finally
+        {
+            NativeMethods.FreeTransportProperties(native);
+        }
+    }
+
+    /// <summary>Replace the security parameters.</summary>
+    public void SetSecurityParameters(SecurityParameters parameters)
+    {
+        var native = parameters.CreateNative();
+        try
+        {
+            TransportServicesException.ThrowIfFailed(
+                NativeMethods.PreconnectionUseSecurityParameters(Handle(), native), "Setting security parameters");
+        }
+        finally
+        {
+            NativeMethods.FreeSecurityParameters(native);
+        }
+    }
+
+    /// <summary>
+    /// Race candidate paths and protocols to the remote endpoints, completing
+    /// with the first connection established.
+    /// </summary>
+    public Task<Connection> InitiateAsync(CancellationToken cancellationToken = default)
+    {
+        var handle = Handle();
+        var pending = new TaskCompletionSource<Connection>(TaskCreationOptions.RunContinuationsAsynchronously);
+        var userData = NativeCallbacks.Pass(pending);
+        NativeError result;
+        unsafe
+        {
+            result = NativeMethods.PreconnectionInitiate(
+                handle, NativeCallbacks.Initiated, NativeCallbacks.InitiateFailed, userData);
+        }
+        if (result != NativeError.Success)
+        {
+            NativeCallbacks.Release(userData);
+            TransportServicesException.ThrowIfFailed(result, "Initiate");
+        }
+        // A connection established after cancellation is aborted by the callback
+        if (cancellationToken.CanBeCanceled)
+        {
+            var registration = cancellationToken.Register(() => pending.TrySetCanceled(cancellationToken));
+            pending.Task.ContinueWith(_ => registration.Dispose(), TaskScheduler.Default);
+        }
+        return pending.Task;
+    }
+
+    /// <summary>Listen for connections on the local endpoints.</summary>
+    public Listener Listen()
+    {
+        var listener = NativeMethods.PreconnectionListen(Handle());
+        if (listener == IntPtr.Zero)
+        {
+            throw new TransportServicesException(
+                TransportServicesErrorCode.EstablishmentFailed,
+                NativeMethods.LastError() ?? "Listen failed");
+        }
+        return new Listener(listener);
+    }
+
+    /// <summary>Release the preconnection; connections made from it are unaffected.</summary>
+    public void Dispose()
+    {
+        var handle = Interlocked.Exchange(ref _handle, IntPtr.Zero);
+        if (handle != IntPtr.Zero)
+        {
+            NativeMethods.PreconnectionFree(handle);
+        }
+    }
+
+    private IntPtr Handle()
+    {
+        var handle = _handle;
+        ObjectDisposedException.ThrowIf(handle == IntPtr.Zero, this);
+        return handle;
+    }
+}
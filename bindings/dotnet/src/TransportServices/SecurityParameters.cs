@@ -0,0 +1,72 @@
+using TransportServices.Native;
+
+namespace TransportServices;
+
+/// <summary>Security parameters for a <see cref="Preconnection"/> (RFC 9622 Section 6.3).</summary>
+public sealed class SecurityParameters
+{
+    private enum Mode
+    {
+        Default,
+        Disabled,
+        Opportunistic,
+    }
+
+    private readonly Mode _mode;
+
+    private SecurityParameters(Mode mode)
+    {
+        _mode = mode;
+    }
+
+    /// <summary>Require TLS with certificate verification.</summary>
+    public static SecurityParameters Default() => new(Mode.Default);
+
+    /// <summary>Use no security protocol.</summary>
+    public static SecurityParameters Disabled() => new(Mode.Disabled);
+
+    /// <summary>Use TLS when the peer supports it, without verification.</summary>
+    public static SecurityParameters Opportunistic() => new(Mode.Opportunistic);
+
+    /// <summary>Application protocols to offer in ALPN, most preferred first.</summary>
+    public IList<string> Alpn { get; } = new List<string>();
+
+    /// <summary>Build the native object; the caller frees it.</summary>
+    internal unsafe IntPtr CreateNative()
+    {
+        var handle = _mode switch
+        {
+            Mode.Disabled => NativeMethods.NewDisabledSecurityParameters(),
+            Mode.Opportunistic => NativeMethods.NewOpportunisticSecurityParameters(),
+            _ => NativeMethods.NewSecurityParameters(),
+        };
+        if (Alpn.Count == 0)
+        {
+            return handle;
+        }
+
+        var protocols = Alpn.Select(p => new Utf8String(p)).ToArray();
+        try
+        {
+            var pointers = protocols.Select(p => p.Pointer).ToArray();
+            fixed (IntPtr* first = pointers)
+            {
+                if (NativeMethods.SetAlpn(handle, first, (nuint)pointers.Length) != 0)
+                {
+                    NativeMethods.FreeSecurityParameters(handle);
+                    throw new TransportServicesException(
+                        TransportServicesErrorCode.InvalidParameters,
+                        "Invalid ALPN protocol list");
+                }
+            }
+            return handle;
+        }
+        finally
+        {
+            foreach (var protocol in protocols)
+            {
+                protocol.Dispose();
+            }
+        }
+    }
+}
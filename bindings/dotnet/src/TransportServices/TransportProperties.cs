@@ -0,0 +1,201 @@
+using TransportServices.Native;
+
+namespace TransportServices;
+
+/// <summary>
+/// Selection and connection properties for a <see cref="Preconnection"/>
+/// (RFC 9622 Sections 6.2 and 8.1).
+/// </summary>
+/// <remarks>
+/// Properties left null keep the library's defaults. Interface and PvD
+/// preferences steer path selection: require an interface to pin a
+/// connection to it, or avoid an expensive one such as cellular.
+/// </remarks>
+public sealed class TransportProperties
+{
+    private readonly Dictionary<string, Preference> _interfaces = new();
+    private readonly Dictionary<string, Preference> _pvds = new();
+
+    /// <summary>Reliable data transfer.</summary>
+    public Preference? Reliability { get; set; }
+    /// <summary>Message boundaries are preserved.</summary>
+    public Preference? PreserveMessageBoundaries { get; set; }
+    /// <summary>Reliability can be chosen per message.</summary>
+    public Preference? PerMessageReliability { get; set; }
+    /// <summary>Messages are delivered in order.</summary>
+    public Preference? PreserveOrder { get; set; }
+    /// <summary>Messages can be sent as 0-RTT data.</summary>
+    public Preference? ZeroRttMessage { get; set; }
+    /// <summary>Several connections share one transport association.</summary>
+    public Preference? Multistreaming { get; set; }
+    /// <summary>Congestion control is applied.</summary>
+    public Preference? CongestionControl { get; set; }
+    /// <summary>Keep-alive packets are sent.</summary>
+    public Preference? KeepAlive { get; set; }
+    /// <summary>A temporary local address is used.</summary>
+    public Preference? UseTemporaryLocalAddress { get; set; }
+    /// <summary>ICMP and similar errors are reported as soft errors.</summary>
+    public Preference? SoftErrorNotify { get; set; }
+
+    /// <summary>Whether and how multiple paths are used.</summary>
+    public MultipathConfig? Multipath { get; set; }
+    /// <summary>Whether the connection sends, receives or both.</summary>
+    public CommunicationDirection? Direction { get; set; }
+    /// <summary>Whether alternate addresses are advertised to the peer.</summary>
+    public bool? AdvertisesAlternateAddresses { get; set; }
+    /// <summary>Which address family to try first.</summary>
+    public AddressFamilyPreference? AddressFamilyPreference { get; set; }
+
+    /// <summary>How long establishment may take.</summary>
+    public TimeSpan? ConnectionTimeout { get; set; }
+    /// <summary>How often keep-alives are sent.</summary>
+    public TimeSpan? KeepAliveTimeout { get; set; }
+    /// <summary>How long to wait before racing the next candidate.</summary>
+    public TimeSpan? ConnectionAttemptDelay { get; set; }
+    /// <summary>Priority of the connection within its group; lower is more important.</summary>
+    public int? ConnectionPriority { get; set; }
+    /// <summary>Disable Nagle's algorithm.</summary>
+    public bool? NoDelay { get; set; }
+    /// <summary>Abort the connection with an error rather than let it migrate off its path.</summary>
+    public bool? PathPinning { get; set; }
+    /// <summary>Sending rate cap in bits per second.</summary>
+    public ulong? MaxSendRate { get; set; }
+    /// <summary>Receiving rate cap in bits per second.</summary>
+    public ulong? MaxReceiveRate { get; set; }
+
+    /// <summary>Interface preferences by name.</summary>
+    public IReadOnlyDictionary<string, Preference> Interfaces => _interfaces;
+    /// <summary>Provisioning domain preferences by name.</summary>
+    public IReadOnlyDictionary<string, Preference> ProvisioningDomains => _pvds;
+
+    /// <summary>Set how strongly an interface, such as <c>en0</c>, is wanted.</summary>
+    public TransportProperties WithInterface(string name, Preference preference)
+    {
+        _interfaces[name] = preference;
+        return this;
+    }
+
+    /// <summary>Set how strongly a provisioning domain is wanted.</summary>
+    public TransportProperties WithProvisioningDomain(string pvd, Preference preference)
+    {
+        _pvds[pvd] = preference;
+        return this;
+    }
+
+    /// <summary>Reliable, ordered byte stream, as TCP provides.</summary>
+    public static TransportProperties ReliableStream() => new()
+    {
+        Reliability = Preference.Require,
+        PreserveOrder = Preference.Require,
+    };
+
+    /// <summary>Unreliable datagrams, as UDP provides.</summary>
+    public static TransportProperties UnreliableDatagram() => new()
+    {
+        Reliability = Preference.Prohibit,
+        PreserveOrder = Preference.Prohibit,
+        PreserveMessageBoundaries = Preference.Require,
+        CongestionControl = Preference.NoPreference,
+    };
+
+    /// <summary>Build the native object; the caller frees it.</summary>
+    internal IntPtr CreateNative()
+    {
+        var handle = NativeMethods.NewTransportProperties();
+        try
+        {
+            SetPreference(handle, 0, Reliability);
+            SetPreference(handle, 1, PreserveMessageBoundaries);
+            SetPreference(handle, 2, PerMessageReliability);
+            SetPreference(handle, 3, PreserveOrder);
+            SetPreference(handle, 4, ZeroRttMessage);
+            SetPreference(handle, 5, Multistreaming);
+            SetPreference(handle, 8, CongestionControl);
+            SetPreference(handle, 9, KeepAlive);
+            SetPreference(handle, 10, UseTemporaryLocalAddress);
+            SetPreference(handle, 11, SoftErrorNotify);
+
+            if (Multipath is { } multipath)
+            {
+                Check(NativeMethods.SetMultipath(handle, multipath), nameof(Multipath));
+            }
+            if (Direction is { } direction)
+            {
+                Check(NativeMethods.SetDirection(handle, direction), nameof(Direction));
+            }
+            if (AdvertisesAlternateAddresses is { } advertises)
+            {
+                Check(NativeMethods.SetAdvertisesAltaddr(handle, advertises), nameof(AdvertisesAlternateAddresses));
+            }
+            if (AddressFamilyPreference is { } family)
+            {
+                Check(NativeMethods.SetAddressFamilyPreference(handle, family), nameof(AddressFamilyPreference));
+            }
+
+            SetDuration(handle, NativeProperty.ConnectionTimeout, ConnectionTimeout, nameof(ConnectionTimeout));
+            SetDuration(handle, NativeProperty.KeepAliveTimeout, KeepAliveTimeout, nameof(KeepAliveTimeout));
+            SetDuration(handle, NativeProperty.ConnectionAttemptDelay, ConnectionAttemptDelay, nameof(ConnectionAttemptDelay));
+            if (ConnectionPriority is { } priority)
+            {
+                Check(NativeMethods.SetConnectionPriority(handle, priority), nameof(ConnectionPriority));
+            }
+            if (NoDelay is { } noDelay)
+            {
+                Check(NativeMethods.SetBoolProperty(handle, NativeProperty.NoDelay, noDelay), nameof(NoDelay));
+            }
+            if (PathPinning is { } pathPinning)
+            {
+                Check(NativeMethods.SetBoolProperty(handle, NativeProperty.PathPinning, pathPinning), nameof(PathPinning));
+            }
+            if (MaxSendRate is { } sendRate)
+            {
+                Check(NativeMethods.SetRateProperty(handle, NativeProperty.MaxSendRate, sendRate), nameof(MaxSendRate));
+            }
+            if (MaxReceiveRate is { } recvRate)
+            {
+                Check(NativeMethods.SetRateProperty(handle, NativeProperty.MaxRecvRate, recvRate), nameof(MaxReceiveRate));
+            }
+
+            foreach (var (name, preference) in _interfaces)
+            {
+                Check(NativeMethods.SetInterface(handle, name, preference), nameof(Interfaces));
+            }
+            foreach (var (pvd, preference) in _pvds)
+            {
+                Check(NativeMethods.SetPvd(handle, pvd, preference), nameof(ProvisioningDomains));
+            }
+            return handle;
+        }
+        catch
+        {
+            NativeMethods.FreeTransportProperties(handle);
+            throw;
+        }
+    }
+
+    private static void SetPreference(IntPtr handle, int property, Preference? preference)
+    {
+        if (preference is { } value)
+        {
+            Check(NativeMethods.SetPreference(handle, property, value), "preference");
+        }
+    }
+
+    private static void SetDuration(IntPtr handle, int property, TimeSpan? value, string name)
+    {
+        if (value is { } duration)
+        {
+            Check(NativeMethods.SetDurationProperty(handle, property, (ulong)duration.TotalMilliseconds), name);
+        }
+    }
+
+    private static void Check(int result, string property)
+    {
+        if (result != 0)
+        {
+            throw new TransportServicesException(
+                TransportServicesErrorCode.InvalidParameters,
+                $"Invalid transport property {property}");
+        }
+    }
+}
@@ -0,0 +1,67 @@
+using TransportServices.Native;
+
+namespace TransportServices;
+
+/// <summary>Why a Transport Services operation failed.</summary>
+public enum TransportServicesErrorCode
+{
+    /// <summary>An argument was invalid.</summary>
+    InvalidParameters = -1,
+    /// <summary>No candidate could be established.</summary>
+    EstablishmentFailed = -2,
+    /// <summary>The connection failed.</summary>
+    ConnectionFailed = -3,
+    /// <summary>A message could not be sent.</summary>
+    SendFailed = -4,
+    /// <summary>A message could not be received.</summary>
+    ReceiveFailed = -5,
+    /// <summary>The operation is not supported.</summary>
+    NotSupported = -6,
+    /// <summary>The operation timed out.</summary>
+    Timeout = -7,
+    /// <summary>The object is in the wrong state for the operation.</summary>
+    InvalidState = -8,
+    /// <summary>Security negotiation failed.</summary>
+    SecurityError = -9,
+    /// <summary>An I/O error occurred.</summary>
+    IoError = -10,
+    /// <summary>The runtime is not initialized or failed.</summary>
+    RuntimeError = -11,
+    /// <summary>The send queue is full.</summary>
+    WouldBlock = -12,
+    /// <summary>The connection is closed.</summary>
+    ConnectionClosed = -98,
+    /// <summary>Any other failure.</summary>
+    Unknown = -99,
+}
+
+/// <summary>A failure reported by the Transport Services library.</summary>
+public sealed class TransportServicesException : Exception
+{
+    /// <summary>Why the operation failed.</summary>
+    public TransportServicesErrorCode Code { get; }
+
+    /// <summary>Create an exception for a failure.</summary>
+    public TransportServicesException(TransportServicesErrorCode code, string message)
+        : base(message)
+    {
+        Code = code;
+    }
+
+    internal static TransportServicesException FromNative(NativeError error, string? message, string fallback)
+    {
+        var code = Enum.IsDefined(typeof(TransportServicesErrorCode), (int)error)
+            ? (TransportServicesErrorCode)(int)error
+            : TransportServicesErrorCode.Unknown;
+        return new TransportServicesException(code, message ?? fallback);
+    }
+
+    /// <summary>Throw for a failed call, with the calling thread's last error.</summary>
+    internal static void ThrowIfFailed(NativeError error, string operation)
+    {
+        if (error != NativeError.Success)
+        {
+            throw FromNative(error, NativeMethods.LastError(), $"{operation} failed ({error})");
+        }
+    }
+}
@@ -0,0 +1,79 @@
+using TransportServices.Native;
+
+namespace TransportServices;
+
+/// <summary>
+/// The library's shared runtime, which runs every connection's I/O.
+/// </summary>
+/// <remarks>
+/// Initialization is reference counted: each <see cref="Initialize"/> needs a
+/// matching <see cref="Shutdown"/>, and the runtime stops with the last one.
+/// The native runtime is created once per process, so initializing again
+/// after a shutdown reuses it.
+/// </remarks>
+public static class TransportServicesRuntime
+{
+    private static readonly object Lock = new();
+    private static int _references;
+    private static bool _created;
+
+    /// <summary>The native library's version.</summary>
+    public static string Version => NativeMethods.TakeString(NativeMethods.Version()) ?? "unknown";
+
+    /// <summary>Whether the runtime is running.</summary>
+    public static bool IsInitialized
+    {
+        get
+        {
+            lock (Lock)
+            {
+                return _references > 0;
+            }
+        }
+    }
+
+    /// <summary>Start the runtime, or take another reference to it.</summary>
+    public static void Initialize()
+    {
+        lock (Lock)
+        {
+            if (!_created)
+            {
+                if (NativeMethods.InitRuntime() != 0)
+                {
+                    throw new TransportServicesException(
+                        TransportServicesErrorCode.RuntimeError,
+                        "Failed to initialize the Transport Services runtime");
+                }
+                _created = true;
+            }
+            _references++;
+        }
+    }
+
+    /// <summary>Release a reference, stopping the runtime with the last one.</summary>
+    public static void Shutdown()
+    {
+        lock (Lock)
+        {
+            if (_references == 0)
+            {
+                return;
+            }
+            if (--_references == 0)
+            {
+                NativeMethods.ShutdownRuntime();
+            }
+        }
+    }
+
+    internal static void EnsureInitialized()
+    {
+        if (!IsInitialized)
+        {
+            throw new TransportServicesException(
+                TransportServicesErrorCode.RuntimeError,
+                "Call TransportServicesRuntime.Initialize() first");
+        }
+    }
+}
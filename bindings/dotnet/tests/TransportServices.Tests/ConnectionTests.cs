@@ -0,0 +1,116 @@
+using System.Net;
+using System.Net.Sockets;
+using System.Text;
+
+namespace TransportServices.Tests;
+
+[Collection("Runtime")]
+public sealed class ConnectionTests
+{
+    private static readonly TimeSpan Timeout = TimeSpan.FromSeconds(5);
+
+    /// <summary>A loopback port nothing is listening on.</summary>
+    private static ushort FreePort()
+    {
+        var probe = new TcpListener(IPAddress.Loopback, 0);
+        probe.Start();
+        var port = (ushort)((IPEndPoint)probe.LocalEndpoint).Port;
+        probe.Stop();
+        return port;
+    }
+
+    [Fact]
+    public async Task MessagesRoundTripOverLoopback()
+    {
+        var port = FreePort();
+        using var server = new Preconnection(
+            localEndpoints: [LocalEndpoint.Loopback(port)],
+            securityParameters: SecurityParameters.Disabled());
+        await using var listener = server.Listen();
+        Assert.True(listener.IsActive);
+
+        using var client = new Preconnection(
+            remoteEndpoints: [new RemoteEndpoint("127.0.0.1", port)],
+            transportProperties: TransportProperties.ReliableStream(),
+            securityParameters: SecurityParameters.Disabled());
+        await using var connection = await client.InitiateAsync().WaitAsync(Timeout);
+        await using var accepted = await listener.AcceptAsync().AsTask().WaitAsync(Timeout);
+
+        await connection.SendAsync(Encoding.UTF8.GetBytes("ping")).WaitAsync(Timeout);
+        var request = await accepted.ReceiveAsync().AsTask().WaitAsync(Timeout);
+        Assert.Equal("ping", Encoding.UTF8.GetString(request.Data.Span));
+        Assert.NotNull(request.RemoteEndpoint);
+
+        await accepted.SendAsync(Encoding.UTF8.GetBytes("pong")).WaitAsync(Timeout);
+        var response = await connection.ReceiveAsync().AsTask().WaitAsync(Timeout);
+        Assert.Equal("pong", Encoding.UTF8.GetString(response.Data.Span));
+    }
+
+    [Fact]
+    public async Task EventsAreRaisedOnTheThreadPool()
+    {
+        var port = FreePort();
+        using var server = new Preconnection(
+            localEndpoints: [LocalEndpoint.Loopback(port)],
+            securityParameters: SecurityParameters.Disabled());
+        await using var listener = server.Listen();
+
+        using var client = new Preconnection(
+            remoteEndpoints: [new RemoteEndpoint("127.0.0.1", port)],
+            securityParameters: SecurityParameters.Disabled());
+        var connection = await client.InitiateAsync().WaitAsync(Timeout);
+        var closed = new TaskCompletionSource<bool>(TaskCreationOptions.RunContinuationsAsynchronously);
+        connection.EventOccurred += (_, e) =>
+        {
+            if (e.Kind == ConnectionEventKind.Closed)
+            {
+                closed.TrySetResult(Thread.CurrentThread.IsThreadPoolThread);
+            }
+        };
+
+        await connection.CloseAsync().WaitAsync(Timeout);
+        Assert.True(await closed.Task.WaitAsync(Timeout));
+        await connection.DisposeAsync();
+        await Assert.ThrowsAsync<ObjectDisposedException>(() => connection.SendAsync(new byte[] { 1 }));
+    }
+
+    [Fact]
+    public async Task PropertiesAreReadAndWrittenAsJson()
+    {
+        var port = FreePort();
+        using var server = new Preconnection(
+            localEndpoints: [LocalEndpoint.Loopback(port)],
+            securityParameters: SecurityParameters.Disabled());
+        await using var listener = server.Listen();
+
+        using var client = new Preconnection(
+            remoteEndpoints: [new RemoteEndpoint("127.0.0.1", port)],
+            securityParameters: SecurityParameters.Disabled());
+        await using var connection = await client.InitiateAsync().WaitAsync(Timeout);
+
+        connection.SetProperty("connTimeout", "30000");
+        Assert.Equal("30000", connection.GetProperty("connTimeout"));
+        Assert.Equal("\"established\"", connection.GetProperty("connState"));
+
+        var error = Assert.Throws<TransportServicesException>(() => connection.SetProperty("connState", "\"closed\""));
+        Assert.Equal(TransportServicesErrorCode.InvalidParameters, error.Code);
+    }
+
+    [Fact]
+    public async Task InitiateFailsWithoutAPeer()
+    {
+        using var client = new Preconnection(
+            remoteEndpoints: [new RemoteEndpoint("127.0.0.1", FreePort())],
+            transportProperties: new TransportProperties { ConnectionTimeout = TimeSpan.FromSeconds(2) },
+            securityParameters: SecurityParameters.Disabled());
+
+        await Assert.ThrowsAsync<TransportServicesException>(() => client.InitiateAsync().WaitAsync(Timeout));
+    }
+
+    [Fact]
+    public void ListenWithoutLocalEndpointFails()
+    {
+        using var server = new Preconnection(securityParameters: SecurityParameters.Disabled());
+        Assert.Throws<TransportServicesException>(() => server.Listen());
+    }
+}
@@ -0,0 +1,35 @@
+using TransportServices.Native;
+
+namespace TransportServices.Tests;
+
+public sealed class PropertiesTests
+{
+    [Fact]
+    public void SelectionPreferencesReachTheNativeObject()
+    {
+        var properties = new TransportProperties
+        {
+            Reliability = Preference.Require,
+            Multipath = MultipathConfig.Active,
+            ConnectionTimeout = TimeSpan.FromSeconds(10),
+            MaxSendRate = 1_000_000,
+        }.WithInterface("wlan0", Preference.Prefer).WithInterface("rmnet0", Preference.Avoid);
+
+        var handle = properties.CreateNative();
+        try
+        {
+            Assert.Equal(0, NativeMethods.GetPreference(handle, 0, out var reliability));
+            Assert.Equal(Preference.Require, reliability);
+        }
+        finally
+        {
+            NativeMethods.FreeTransportProperties(handle);
+        }
+    }
+
+    [Fact]
+    public void VersionIsReported()
+    {
+        Assert.False(string.IsNullOrEmpty(TransportServicesRuntime.Version));
+    }
+}
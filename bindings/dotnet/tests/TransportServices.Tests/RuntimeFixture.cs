@@ -0,0 +1,14 @@
+namespace TransportServices.Tests;
+
+/// <summary>Holds a runtime reference for the tests in a collection.</summary>
+public sealed class RuntimeFixture : IDisposable
+{
+    public RuntimeFixture() => TransportServicesRuntime.Initialize();
+
+    public void Dispose() => TransportServicesRuntime.Shutdown();
+}
+
+[CollectionDefinition("Runtime")]
+public sealed class RuntimeCollection : ICollectionFixture<RuntimeFixture>
+{
+}
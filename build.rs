@@ -48,8 +48,6 @@ fn main() {
         // Only run cbindgen if the cbindgen dependency is available
         #[cfg(feature = "cbindgen")]
         {
-            let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
-
             // Don't generate header here - it should be done by build scripts
             // that place it in the appropriate build directory
         }
@@ -84,15 +84,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     );
 
     // Find preferred interface for internet connectivity
-    let preferred = interfaces
-        .iter()
-        .filter(|i| {
-            i.status == Status::Up
-                && !i.is_expensive
-                && !i.ips.is_empty()
-                && (i.interface_type == "wifi" || i.interface_type == "ethernet")
-        })
-        .next();
+    let preferred = interfaces.iter().find(|i| {
+        i.status == Status::Up
+            && !i.is_expensive
+            && !i.ips.is_empty()
+            && (i.interface_type == "wifi" || i.interface_type == "ethernet")
+    });
 
     if let Some(pref) = preferred {
         println!(
@@ -0,0 +1,160 @@
+//! Configurable Transport Services client
+//!
+//! Connects to a server, sends each message as a request and prints the
+//! response with its round-trip time. Selection properties and framing are
+//! set from the command line, so it doubles as an interop tool against other
+//! TAPS implementations and plain TCP servers.
+//!
+//! Usage: taps-client [OPTIONS] HOST PORT
+//!
+//! Options:
+//!   --message TEXT        Message to send (repeatable, default "hello")
+//!   --count N             Send the messages N times (default 1)
+//!   --framer NAME         Message framing: none or length-prefix (default none)
+//!   --family FAMILY       Address family: any, ipv4 or ipv6 (default any)
+//!   --keep-alive          Require keep-alive
+//!   --timeout SECS        Timeout for establishment and each response (default 5)
+//!
+//! No TLS options are offered: this crate does not provide a TLS protocol
+//! stack yet, so connections are always plaintext TCP.
+
+use std::time::{Duration, Instant};
+use transport_services::{
+    AddressFamilyPreference, Preconnection, Preference, RemoteEndpoint, SecurityParameters,
+    TransportProperties,
+};
+
+struct Options {
+    host: String,
+    port: u16,
+    messages: Vec<String>,
+    count: usize,
+    length_prefix: bool,
+    family: Option<AddressFamilyPreference>,
+    keep_alive: bool,
+    timeout: Duration,
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: taps-client [--message TEXT]... [--count N] [--framer none|length-prefix]\n\
+         \x20                  [--family any|ipv4|ipv6] [--keep-alive] [--timeout SECS] HOST PORT"
+    );
+    std::process::exit(2);
+}
+
+fn parse_args() -> Options {
+    let mut options = Options {
+        host: String::new(),
+        port: 0,
+        messages: Vec::new(),
+        count: 1,
+        length_prefix: false,
+        family: None,
+        keep_alive: false,
+        timeout: Duration::from_secs(5),
+    };
+    let mut positional = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--message" => options.messages.push(value()),
+            "--count" => options.count = value().parse().unwrap_or_else(|_| usage()),
+            "--framer" => {
+                options.length_prefix = match value().as_str() {
+                    "none" => false,
+                    "length-prefix" => true,
+                    _ => usage(),
+                }
+            }
+            "--family" => {
+                options.family = match value().as_str() {
+                    "any" => None,
+                    "ipv4" => Some(AddressFamilyPreference::Ipv4Only),
+                    "ipv6" => Some(AddressFamilyPreference::Ipv6Only),
+                    _ => usage(),
+                }
+            }
+            "--keep-alive" => options.keep_alive = true,
+            "--timeout" => {
+                let secs: u64 = value().parse().unwrap_or_else(|_| usage());
+                options.timeout = Duration::from_secs(secs);
+            }
+            "-h" | "--help" => usage(),
+            _ if arg.starts_with("--") => usage(),
+            _ => positional.push(arg),
+        }
+    }
+
+    let [host, port] = positional.as_slice() else {
+        usage();
+    };
+    options.host = host.clone();
+    options.port = port.parse().unwrap_or_else(|_| usage());
+    if options.messages.is_empty() {
+        options.messages.push("hello".to_string());
+    }
+    options
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+    let options = parse_args();
+
+    let mut properties = TransportProperties::builder();
+    if options.keep_alive {
+        properties = properties.keep_alive(Preference::Require);
+    }
+    let mut properties = properties.build();
+    if let Some(family) = options.family {
+        properties
+            .connection_properties
+            .happy_eyeballs
+            .address_family_preference = family;
+    }
+
+    let remote = RemoteEndpoint::builder()
+        .hostname(&options.host)
+        .port(options.port)
+        .build();
+    let preconnection = Preconnection::new(
+        vec![],
+        vec![remote],
+        properties,
+        SecurityParameters::new_disabled(),
+    );
+
+    let connection = preconnection
+        .initiate_with_timeout(Some(options.timeout))
+        .await?;
+    connection
+        .wait_for_established(Some(options.timeout))
+        .await?;
+    if options.length_prefix {
+        connection.use_length_prefix_framer().await?;
+    }
+    if let Some(remote) = connection.remote_endpoint().await {
+        println!("Connected to {remote:?}");
+    }
+
+    for round in 0..options.count {
+        for message in &options.messages {
+            let started = Instant::now();
+            let (response, _) = connection
+                .request_with_timeout(message.as_str(), Some(options.timeout))
+                .await?;
+            println!(
+                "[{round}] {} bytes in {:?}: {}",
+                response.data().len(),
+                started.elapsed(),
+                String::from_utf8_lossy(response.data())
+            );
+        }
+    }
+
+    connection.close().await?;
+    Ok(())
+}
@@ -0,0 +1,153 @@
+//! Configurable Transport Services server
+//!
+//! Listens for connections and answers every received message, either by
+//! echoing it or with a minimal HTTP/1.1 response. Pairs with taps-client
+//! and works as an interop peer for other TAPS implementations.
+//!
+//! Usage: taps-server [OPTIONS] [PORT]
+//!
+//! Options:
+//!   --bind ADDR           Local address to listen on (default 0.0.0.0)
+//!   --mode MODE           echo or http (default echo)
+//!   --framer NAME         Message framing: none or length-prefix (default none)
+//!   --datagram            Listen for datagrams instead of a byte stream
+//!
+//! PORT defaults to 7777.
+
+use std::net::IpAddr;
+use transport_services::{
+    Connection, ConnectionEvent, LocalEndpoint, Preconnection, Preference, SecurityParameters,
+    TransportProperties,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Echo,
+    Http,
+}
+
+struct Options {
+    bind: IpAddr,
+    port: u16,
+    mode: Mode,
+    length_prefix: bool,
+    datagram: bool,
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: taps-server [--bind ADDR] [--mode echo|http] [--framer none|length-prefix]\n\
+         \x20                  [--datagram] [PORT]"
+    );
+    std::process::exit(2);
+}
+
+fn parse_args() -> Options {
+    let mut options = Options {
+        bind: IpAddr::from([0, 0, 0, 0]),
+        port: 7777,
+        mode: Mode::Echo,
+        length_prefix: false,
+        datagram: false,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--bind" => options.bind = value().parse().unwrap_or_else(|_| usage()),
+            "--mode" => {
+                options.mode = match value().as_str() {
+                    "echo" => Mode::Echo,
+                    "http" => Mode::Http,
+                    _ => usage(),
+                }
+            }
+            "--framer" => {
+                options.length_prefix = match value().as_str() {
+                    "none" => false,
+                    "length-prefix" => true,
+                    _ => usage(),
+                }
+            }
+            "--datagram" => options.datagram = true,
+            "-h" | "--help" => usage(),
+            _ if arg.starts_with("--") => usage(),
+            _ => options.port = arg.parse().unwrap_or_else(|_| usage()),
+        }
+    }
+    options
+}
+
+/// Build the reply to one request
+fn respond(mode: Mode, request: &[u8]) -> Vec<u8> {
+    match mode {
+        Mode::Echo => request.to_vec(),
+        Mode::Http => {
+            let body = format!("Hello from taps-server ({} byte request)\n", request.len());
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\
+                 Connection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .into_bytes()
+        }
+    }
+}
+
+async fn serve(connection: Connection, mode: Mode) {
+    while let Some(event) = connection.next_event().await {
+        match event {
+            ConnectionEvent::Received { message_data, .. } => {
+                if let Err(e) = connection.send(respond(mode, &message_data)).await {
+                    eprintln!("Send failed: {e}");
+                    break;
+                }
+                if mode == Mode::Http {
+                    let _ = connection.close().await;
+                    break;
+                }
+            }
+            ConnectionEvent::Closed | ConnectionEvent::ConnectionError(_) => break,
+            _ => {}
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+    let options = parse_args();
+
+    let mut properties = TransportProperties::builder();
+    if options.datagram {
+        properties = properties.reliability(Preference::Prohibit);
+    }
+
+    let local = LocalEndpoint::builder()
+        .ip_address(options.bind)
+        .port(options.port)
+        .build();
+    let preconnection = Preconnection::new(
+        vec![local],
+        vec![],
+        properties.build(),
+        SecurityParameters::new_disabled(),
+    );
+
+    let listener = preconnection.listen().await?;
+    if let Some(addr) = listener.local_addr().await {
+        println!("Listening on {addr}");
+    }
+
+    loop {
+        let connection = listener.accept().await?;
+        if options.length_prefix {
+            connection.use_length_prefix_framer().await?;
+        }
+        if let Some(remote) = connection.remote_endpoint().await {
+            println!("Accepted {remote:?}");
+        }
+        tokio::spawn(serve(connection, options.mode));
+    }
+}
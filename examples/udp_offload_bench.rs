@@ -0,0 +1,112 @@
+//! Compare sending datagrams with and without UDP segmentation offload
+//!
+//! Sends batches of equally sized datagrams from a Listener-accepted flow
+//! over loopback, once with offload enabled and once disabled, and reports
+//! the send system calls made and the time taken for each.
+//!
+//! Usage: udp-offload-bench [DATAGRAMS] [SIZE]
+//!
+//! DATAGRAMS defaults to 64000 and SIZE to 1200 bytes.
+
+use std::time::{Duration, Instant};
+use transport_services::{
+    ConnectionEvent, EndpointIdentifier, LocalEndpoint, Preconnection, Preference,
+    SecurityParameters, TransportProperties,
+};
+
+/// Messages per batch, matching the kernel's limit on segments per send
+const BATCH: usize = 64;
+
+async fn run(
+    offload: bool,
+    datagrams: usize,
+    size: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let preconn = Preconnection::new(
+        vec![LocalEndpoint {
+            identifiers: vec![
+                EndpointIdentifier::IpAddress("127.0.0.1".parse()?),
+                EndpointIdentifier::Port(0),
+            ],
+        }],
+        vec![],
+        TransportProperties::builder()
+            .reliability(Preference::Prohibit)
+            .udp_offload(offload)
+            .build(),
+        SecurityParameters::new_disabled(),
+    );
+    let listener = preconn.listen().await?;
+    let addr = listener
+        .local_addr()
+        .await
+        .ok_or("Listener has no address")?;
+
+    // The peer opens the flow, then drains what is sent to it
+    let peer = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+    peer.send_to(b"open", addr).await?;
+    let conn = listener.accept().await?;
+    let drain = tokio::spawn(async move {
+        let mut buffer = vec![0u8; 65536];
+        let mut received = 0;
+        while let Ok(Ok((n, _))) =
+            tokio::time::timeout(Duration::from_millis(200), peer.recv_from(&mut buffer)).await
+        {
+            received += n;
+        }
+        received
+    });
+
+    let payload = vec![0u8; size];
+    let started = Instant::now();
+    for chunk in (0..datagrams).collect::<Vec<_>>().chunks(BATCH) {
+        let mut batch = conn.batch();
+        for _ in chunk {
+            batch.add(payload.clone());
+        }
+        batch.commit().await?;
+    }
+    let elapsed = started.elapsed();
+    let stats = conn.stats().await;
+
+    // Sent events are not needed
+    while let Ok(Some(event)) =
+        tokio::time::timeout(Duration::from_millis(10), conn.next_event()).await
+    {
+        if let ConnectionEvent::SendError { error, .. } = event {
+            return Err(error.into());
+        }
+    }
+    let received = drain.await?;
+
+    println!(
+        "offload {:<3}  {:>7} datagrams  {:>7} send calls  {:>8.1} ms  {:>8.0} MB/s sent  {:>6.1}% received",
+        if offload { "on" } else { "off" },
+        stats.datagrams_sent,
+        stats.datagram_send_calls,
+        elapsed.as_secs_f64() * 1000.0,
+        stats.bytes_sent as f64 / elapsed.as_secs_f64() / 1e6,
+        received as f64 * 100.0 / stats.bytes_sent.max(1) as f64,
+    );
+    listener.stop().await?;
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let datagrams = args
+        .next()
+        .map(|arg| arg.parse())
+        .transpose()?
+        .unwrap_or(64000);
+    let size = args
+        .next()
+        .map(|arg| arg.parse())
+        .transpose()?
+        .unwrap_or(1200);
+
+    run(false, datagrams, size).await?;
+    run(true, datagrams, size).await?;
+    Ok(())
+}
@@ -0,0 +1,270 @@
+//! Admission control for Listeners
+//! Limits how many connections a Listener admits, per source and overall
+
+use crate::{Connection, ConnectionState};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Instant;
+use tokio::sync::watch;
+
+/// Token buckets kept before idle, fully refilled ones are discarded
+const MAX_IDLE_BUCKETS: usize = 1024;
+
+/// Token-bucket rate for accepting connections from one source
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AcceptRate {
+    /// Sustained accepts per second
+    pub per_second: f64,
+    /// Accepts allowed in a burst
+    pub burst: u32,
+}
+
+/// Server-side admission control settings for a Listener
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AdmissionPolicy {
+    /// Maximum concurrent connections from one remote IP address
+    pub max_connections_per_ip: Option<usize>,
+    /// Accept rate allowed per remote IP address
+    pub accept_rate_per_ip: Option<AcceptRate>,
+    /// Maximum concurrent connections across all sources
+    pub max_concurrent_connections: Option<usize>,
+}
+
+impl AdmissionPolicy {
+    /// Create a policy that admits everything
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit concurrent connections per remote IP address
+    pub fn with_max_connections_per_ip(mut self, limit: usize) -> Self {
+        self.max_connections_per_ip = Some(limit);
+        self
+    }
+
+    /// Limit the accept rate per remote IP address
+    pub fn with_accept_rate_per_ip(mut self, per_second: f64, burst: u32) -> Self {
+        self.accept_rate_per_ip = Some(AcceptRate { per_second, burst });
+        self
+    }
+
+    /// Limit concurrent connections across all sources
+    pub fn with_max_concurrent_connections(mut self, limit: usize) -> Self {
+        self.max_concurrent_connections = Some(limit);
+        self
+    }
+}
+
+/// Counters for connections a Listener admitted or rejected
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ListenerStats {
+    /// Connections delivered to the application
+    pub accepted: u64,
+    /// Attempts rejected by the new connection limit
+    pub rejected_connection_limit: u64,
+    /// Attempts rejected by the per-IP concurrency limit
+    pub rejected_per_ip_limit: u64,
+    /// Attempts rejected by the per-IP accept rate
+    pub rejected_rate_limit: u64,
+    /// Attempts rejected by the global concurrency limit
+    pub rejected_concurrency_limit: u64,
+}
+
+impl ListenerStats {
+    /// Total number of rejected attempts
+    pub fn rejected(&self) -> u64 {
+        self.rejected_connection_limit
+            + self.rejected_per_ip_limit
+            + self.rejected_rate_limit
+            + self.rejected_concurrency_limit
+    }
+}
+
+/// Why an incoming connection was not admitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Rejection {
+    NewConnections,
+    PerSource,
+    AcceptRate,
+    Concurrency,
+}
+
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rejection::NewConnections => write!(f, "new connection limit reached"),
+            Rejection::PerSource => write!(f, "per-source connection limit reached"),
+            Rejection::AcceptRate => write!(f, "per-source accept rate exceeded"),
+            Rejection::Concurrency => write!(f, "concurrent connection limit reached"),
+        }
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn refill(&mut self, rate: AcceptRate) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate.per_second).min(rate.burst as f64);
+        self.last_refill = now;
+    }
+}
+
+/// A place among a Listener's connections, reserved by a passed admission check
+///
+/// Dropping the ticket before the connection is admitted, as when its
+/// handshake fails, gives the place back.
+pub(crate) struct AdmissionTicket(Arc<()>);
+
+/// What holds a place among a Listener's connections
+enum Occupant {
+    // Still being sniffed or in its handshake
+    Pending(Weak<()>),
+    // Delivered, and watched without keeping the connection alive
+    Open(watch::Receiver<ConnectionState>),
+}
+
+impl Occupant {
+    /// Whether the place is still taken, decided without waiting on the connection
+    fn is_live(&self) -> bool {
+        match self {
+            Occupant::Pending(ticket) => ticket.strong_count() > 0,
+            // An error means the connection is gone
+            Occupant::Open(state) => {
+                state.has_changed().is_ok() && *state.borrow() != ConnectionState::Closed
+            }
+        }
+    }
+}
+
+/// Admission state owned by a Listener's accept loop
+pub(crate) struct AdmissionControl {
+    policy: Arc<Mutex<AdmissionPolicy>>,
+    stats: Arc<Mutex<ListenerStats>>,
+    buckets: HashMap<IpAddr, TokenBucket>,
+    // Connections from admission check until close, pruned on every check
+    active: Vec<(IpAddr, Occupant)>,
+}
+
+impl AdmissionControl {
+    pub(crate) fn new(
+        policy: Arc<Mutex<AdmissionPolicy>>,
+        stats: Arc<Mutex<ListenerStats>>,
+    ) -> Self {
+        Self {
+            policy,
+            stats,
+            buckets: HashMap::new(),
+            active: Vec::new(),
+        }
+    }
+
+    /// Decide whether a connection from `source` may be admitted
+    ///
+    /// An admitted connection holds its place from here on, so connections
+    /// still in their handshake count against the limits. A rejection is
+    /// counted in the listener stats.
+    pub(crate) fn check(
+        &mut self,
+        source: IpAddr,
+    ) -> std::result::Result<AdmissionTicket, Rejection> {
+        let policy = self.policy.lock().unwrap().clone();
+        let result = self.evaluate(&policy, source);
+        match result {
+            Ok(()) => {
+                let ticket = AdmissionTicket(Arc::new(()));
+                self.active
+                    .push((source, Occupant::Pending(Arc::downgrade(&ticket.0))));
+                Ok(ticket)
+            }
+            Err(rejection) => {
+                self.reject(rejection);
+                Err(rejection)
+            }
+        }
+    }
+
+    fn evaluate(
+        &mut self,
+        policy: &AdmissionPolicy,
+        source: IpAddr,
+    ) -> std::result::Result<(), Rejection> {
+        // Pruned whatever the policy, which can gain limits later
+        self.active.retain(|(_, occupant)| occupant.is_live());
+
+        if let Some(limit) = policy.max_concurrent_connections {
+            if self.active.len() >= limit {
+                return Err(Rejection::Concurrency);
+            }
+        }
+
+        if let Some(limit) = policy.max_connections_per_ip {
+            if self.active.iter().filter(|(ip, _)| *ip == source).count() >= limit {
+                return Err(Rejection::PerSource);
+            }
+        }
+
+        if let Some(rate) = policy.accept_rate_per_ip {
+            if self.buckets.len() > MAX_IDLE_BUCKETS {
+                self.buckets.retain(|_, bucket| {
+                    bucket.refill(rate);
+                    bucket.tokens < rate.burst as f64
+                });
+            }
+
+            let bucket = self.buckets.entry(source).or_insert_with(|| TokenBucket {
+                tokens: rate.burst as f64,
+                last_refill: Instant::now(),
+            });
+            bucket.refill(rate);
+            if bucket.tokens < 1.0 {
+                return Err(Rejection::AcceptRate);
+            }
+            bucket.tokens -= 1.0;
+        }
+
+        Ok(())
+    }
+
+    /// Record a connection that was delivered to the application
+    ///
+    /// It keeps the place its ticket reserved until it is closed.
+    pub(crate) async fn admit(
+        &mut self,
+        source: IpAddr,
+        ticket: AdmissionTicket,
+        connection: &Connection,
+    ) {
+        let state = connection.state_watch().await;
+        let reserved = Arc::downgrade(&ticket.0);
+        match self.active.iter_mut().find(|(_, occupant)| {
+            matches!(occupant, Occupant::Pending(pending) if pending.ptr_eq(&reserved))
+        }) {
+            Some((_, occupant)) => *occupant = Occupant::Open(state),
+            None => self.active.push((source, Occupant::Open(state))),
+        }
+        self.stats.lock().unwrap().accepted += 1;
+    }
+
+    /// Number of admitted connections being tracked
+    #[cfg(test)]
+    pub(crate) fn tracked(&self) -> usize {
+        self.active.len()
+    }
+
+    /// Count an attempt rejected outside the admission policy
+    pub(crate) fn reject(&self, rejection: Rejection) {
+        let mut stats = self.stats.lock().unwrap();
+        match rejection {
+            Rejection::NewConnections => stats.rejected_connection_limit += 1,
+            Rejection::PerSource => stats.rejected_per_ip_limit += 1,
+            Rejection::AcceptRate => stats.rejected_rate_limit += 1,
+            Rejection::Concurrency => stats.rejected_concurrency_limit += 1,
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//! Protocol stack capability introspection
+//! Reports which protocols and transport features this build can provide on
+//! the running platform, so applications can set selection properties up
+//! front instead of discovering NotSupported when initiating.
+
+use crate::Protocol;
+
+/// Protocols and transport features available to connections
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// TCP, for initiate and listen
+    pub tcp: bool,
+    /// UDP, for datagram listeners
+    pub udp: bool,
+    /// TLS over TCP; needs the `tls` feature
+    pub tls: bool,
+    /// QUIC
+    pub quic: bool,
+    /// SCTP
+    pub sctp: bool,
+    /// UDP-Lite, for partial checksum coverage
+    pub udp_lite: bool,
+    /// Multipath TCP
+    pub mptcp: bool,
+    /// TCP Fast Open, for 0-RTT data on TCP
+    pub tcp_fast_open: bool,
+    /// TCP Fast Open on Listeners: data in a client's SYN is delivered as
+    /// early data when zeroRttMsg is preferred, if the system allows it
+    pub tcp_fast_open_listen: bool,
+    /// ECN marks of received datagrams, reported in MessageContext
+    pub ecn: bool,
+}
+
+impl Capabilities {
+    /// Whether connections can use `protocol`
+    pub fn supports(&self, protocol: Protocol) -> bool {
+        match protocol {
+            Protocol::TCP => self.tcp,
+            Protocol::UDP => self.udp,
+            Protocol::TLS => self.tls,
+            Protocol::QUIC => self.quic,
+            Protocol::SCTP => self.sctp,
+            // No DTLS stack, whatever the platform
+            Protocol::DTLS => false,
+        }
+    }
+}
+
+/// The protocols and features this build provides on this platform
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        tcp: true,
+        udp: true,
+        tls: cfg!(feature = "tls"),
+        // Connections are built on TCP and UDP sockets only; the `quic`
+        // feature does not yet provide a QUIC transport
+        quic: false,
+        sctp: false,
+        udp_lite: false,
+        mptcp: false,
+        tcp_fast_open: false,
+        tcp_fast_open_listen: cfg!(target_os = "linux"),
+        // Traffic class reception is only implemented on these platforms
+        ecn: cfg!(any(
+            target_os = "linux",
+            target_os = "android",
+            target_vendor = "apple"
+        )),
+    }
+}
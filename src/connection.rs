@@ -1,1430 +1,5499 @@
 //! Connection implementation for Transport Services
 //! Based on RFC 9622 Section 3 (API Summary) and Section 8 (Managing Connections)
 
+use crate::connection_group::{GroupMember, RateLimiter, SendGrant};
+use crate::fault::{FaultDirection, FaultInjector};
+use crate::heartbeat::Heartbeat;
+use crate::ledbat::LedbatPacer;
+use crate::memory::{self, MemoryAccount, MemoryUsage};
+use crate::proxy::{self, ProxyConfig, ProxyKind, ProxyTarget};
+use crate::reassembly::Reassembler;
+use crate::shaping::{NetworkConditions, TrafficShaper};
+use crate::sniff::{Sniffed, SniffedData};
+use crate::udp_offload;
 use crate::{
-    CommunicationDirection, ConnectionEvent, ConnectionGroup, ConnectionGroupId,
-    ConnectionProperties, ConnectionProperty, ConnectionState, EndpointIdentifier, FramerStack,
-    LocalEndpoint, Message, MessageContext, Preconnection, Preference, RemoteEndpoint, Result,
-    TimeoutValue, TransportProperties, TransportServicesError,
+    path_monitor, racing, runtime, state_machine, CapacityProfile, CommunicationDirection,
+    ConnectionEvent, ConnectionGroup, ConnectionGroupId, ConnectionProperties, ConnectionProperty,
+    ConnectionState, ConnectionStats, DropPolicy, EndpointIdentifier, EventClass, EventClasses,
+    EventTimestamps, Framer, FramerHandshake, FramerStack, LocalEndpoint, Message,
+    MessageCapacityProfile, MessageContext, PathInfo, Preconnection, Preference, PropertyNamespace,
+    ProtocolStack, RemoteEndpoint, Result, SchedulerType, SecurityParameters, StackLayer,
+    TimedEvent, TimeoutValue, TransportProperties, TransportServicesError,
 };
+use bytes::{Buf, Bytes};
+use futures::Stream;
 #[cfg(not(target_os = "windows"))]
 use socket2::Socket;
-use std::net::SocketAddr;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::collections::VecDeque;
+use std::io::IoSlice;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::task::{Context, Poll};
 use std::time::{Duration, Instant};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
-use tokio::sync::{mpsc, RwLock};
-use tokio::time::timeout;
+use tokio::io::{AsyncWriteExt, Interest};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::{mpsc, watch, Mutex, OwnedMutexGuard, RwLock};
+
+/// Events each class's buffer holds for `Connection::events_filtered`
+const FILTERED_EVENT_CAPACITY: usize = 256;
+
+/// Error reported when the fault injector resets a connection
+const INJECTED_RESET: &str = "Connection reset by fault injector";
 
 /// A Connection represents an instance of a transport Protocol Stack
 /// on which data can be sent to and/or received from a Remote Endpoint
 pub struct Connection {
     inner: Arc<RwLock<ConnectionInner>>,
-    event_sender: mpsc::UnboundedSender<ConnectionEvent>,
-    event_receiver: Arc<RwLock<mpsc::UnboundedReceiver<ConnectionEvent>>>,
+    event_sender: EventSender,
+    event_receiver: Arc<RwLock<mpsc::UnboundedReceiver<TimedEvent>>>,
+    // Kept outside the lock, which a stalled write holds
+    send_stall: Arc<SendStall>,
+    // Also kept outside the lock, so taking an event needs no lock
+    memory: Arc<MemoryAccount>,
+    // Shared by all user-held handles; None for internal clones
+    handle: Option<Arc<HandleGuard>>,
+}
+
+/// Applies the connection's DropPolicy once the last user-held handle is dropped
+pub(crate) struct HandleGuard {
+    inner: Arc<RwLock<ConnectionInner>>,
+    event_sender: EventSender,
+    event_receiver: Arc<RwLock<mpsc::UnboundedReceiver<TimedEvent>>>,
+    send_stall: Arc<SendStall>,
+    memory: Arc<MemoryAccount>,
+}
+
+/// Sending side of a connection's event queue, which timestamps each event
+#[derive(Debug, Clone)]
+pub(crate) struct EventSender {
+    queue: mpsc::UnboundedSender<TimedEvent>,
+    // Subscriptions taking classes of events out of the queue
+    filters: Arc<std::sync::Mutex<Vec<EventFilter>>>,
+}
+
+impl EventSender {
+    fn new(queue: mpsc::UnboundedSender<TimedEvent>) -> Self {
+        Self {
+            queue,
+            filters: Arc::default(),
+        }
+    }
+
+    /// Queue an event; returns false once the receiving side is gone
+    pub(crate) fn send(&self, event: ConnectionEvent) -> bool {
+        self.send_stamped(event, EventTimestamps::queued_now())
+    }
+
+    /// Queue an event with timestamps of the work behind it
+    pub(crate) fn send_stamped(&self, event: ConnectionEvent, timestamps: EventTimestamps) -> bool {
+        let timed = TimedEvent {
+            event,
+            timestamps,
+            // Set when the application takes the event
+            dequeued: timestamps.enqueued,
+        };
+        match self.route(timed) {
+            Some(timed) => self.queue.send(timed).is_ok(),
+            None => true,
+        }
+    }
+
+    /// Hand an event to the subscriptions filtering for its class, or give
+    /// it back for the queue if there are none
+    fn route(&self, timed: TimedEvent) -> Option<TimedEvent> {
+        let mut filters = self.filters.lock().unwrap();
+        if filters.is_empty() {
+            return Some(timed);
+        }
+        filters.retain(|filter| !filter.is_closed());
+
+        let class = timed.event.class();
+        let matching = filters
+            .iter()
+            .filter(|filter| filter.classes.contains(class))
+            .collect::<Vec<_>>();
+        let Some((last, others)) = matching.split_last() else {
+            return Some(timed);
+        };
+        // Bounded buffers hold the event now, not the queue
+        last.memory.event_taken(&timed.event);
+        for filter in others {
+            filter.deliver(class, timed.clone());
+        }
+        last.deliver(class, timed);
+        None
+    }
+
+    /// Start taking the given classes of events out of the queue
+    fn subscribe(
+        &self,
+        classes: EventClasses,
+        capacity: usize,
+        memory: Arc<MemoryAccount>,
+    ) -> FilteredEvents {
+        let dropped = Arc::new(AtomicU64::new(0));
+        let (senders, receivers) = classes
+            .iter()
+            .map(|class| {
+                let (sender, receiver) = mpsc::channel(capacity.max(1));
+                ((class, sender), (class, receiver))
+            })
+            .unzip();
+        self.filters.lock().unwrap().push(EventFilter {
+            classes,
+            senders,
+            dropped: Arc::clone(&dropped),
+            memory,
+        });
+        FilteredEvents {
+            classes,
+            receivers,
+            dropped,
+        }
+    }
+}
+
+/// A subscription's sending side, with a bounded buffer per event class
+#[derive(Debug)]
+struct EventFilter {
+    classes: EventClasses,
+    senders: Vec<(EventClass, mpsc::Sender<TimedEvent>)>,
+    dropped: Arc<AtomicU64>,
+    memory: Arc<MemoryAccount>,
+}
+
+impl EventFilter {
+    fn is_closed(&self) -> bool {
+        self.senders.iter().all(|(_, sender)| sender.is_closed())
+    }
+
+    /// Buffer an event; one arriving at a full buffer is dropped, so a slow
+    /// consumer of one class holds up neither the others nor the connection
+    fn deliver(&self, class: EventClass, timed: TimedEvent) {
+        let Some((_, sender)) = self.senders.iter().find(|(c, _)| *c == class) else {
+            return;
+        };
+        if let Err(mpsc::error::TrySendError::Full(_)) = sender.try_send(timed) {
+            log::debug!("{class:?} event buffer full, dropping event");
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Events of chosen classes, taken out of a connection's event queue by
+/// `Connection::events_filtered`
+///
+/// Each class has its own bounded buffer. Events are taken class by class
+/// in the order of `EventClass::ALL`, so Lifecycle events such as Closed
+/// come first however much data is waiting; within a class they keep their
+/// order.
+pub struct FilteredEvents {
+    classes: EventClasses,
+    receivers: Vec<(EventClass, mpsc::Receiver<TimedEvent>)>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl FilteredEvents {
+    /// The classes of events taken
+    pub fn classes(&self) -> EventClasses {
+        self.classes
+    }
+
+    /// Number of events dropped because their class's buffer was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Get the next event, or None once the connection is gone
+    pub async fn next_event(&mut self) -> Option<ConnectionEvent> {
+        Some(self.next_timed_event().await?.event)
+    }
+
+    /// Get the next event with its timestamps, as `Connection::next_timed_event`
+    pub async fn next_timed_event(&mut self) -> Option<TimedEvent> {
+        let mut timed = std::future::poll_fn(|cx| self.poll_next_timed(cx)).await?;
+        timed.dequeued = Instant::now();
+        Some(timed)
+    }
+
+    fn poll_next_timed(&mut self, cx: &mut Context<'_>) -> Poll<Option<TimedEvent>> {
+        let mut open = false;
+        for (_, receiver) in &mut self.receivers {
+            match receiver.poll_recv(cx) {
+                Poll::Ready(Some(timed)) => return Poll::Ready(Some(timed)),
+                Poll::Ready(None) => {}
+                Poll::Pending => open = true,
+            }
+        }
+        if open {
+            Poll::Pending
+        } else {
+            Poll::Ready(None)
+        }
+    }
+}
+
+impl Stream for FilteredEvents {
+    type Item = ConnectionEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut()
+            .poll_next_timed(cx)
+            .map(|timed| timed.map(|timed| timed.event))
+    }
+}
+
+/// Progress of the write in flight, and whether the peer's flow control has
+/// stalled it
+#[derive(Default)]
+pub(crate) struct SendStall {
+    /// Bytes written to the transport so far
+    written: AtomicU64,
+    /// When the write stopped making progress, once it counts as stalled
+    since: std::sync::Mutex<Option<Instant>>,
+}
+
+impl SendStall {
+    /// How long sends have been stalled, if they are
+    fn duration(&self) -> Option<Duration> {
+        self.since.lock().unwrap().map(|since| since.elapsed())
+    }
+
+    fn set(&self, since: Option<Instant>) {
+        *self.since.lock().unwrap() = since;
+    }
+}
+
+/// How a Scavenger connection yields to other traffic
+enum Scavenger {
+    /// The kernel's low-priority congestion control, replacing `previous`
+    Kernel { previous: String },
+    /// LEDBAT pacing of sends
+    Paced(LedbatPacer),
+}
+
+/// Bytes queued for sending, which send() waits on once they reach the high
+/// watermark
+#[derive(Default)]
+struct SendQueue {
+    /// Bytes of messages held until establishment, the peer or a bundle
+    held: AtomicUsize,
+    /// Bytes of messages whose send() is waiting its turn or writing
+    in_flight: AtomicUsize,
+    closed: AtomicBool,
+    drained: tokio::sync::Notify,
+}
+
+impl SendQueue {
+    fn queued(&self) -> usize {
+        self.held.load(Ordering::Relaxed) + self.in_flight.load(Ordering::Relaxed)
+    }
+
+    fn set_held(&self, bytes: usize) {
+        if self.held.swap(bytes, Ordering::Relaxed) > bytes {
+            self.drained.notify_waiters();
+        }
+    }
+
+    /// Release waiting senders, which find the connection closed
+    fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+        self.drained.notify_waiters();
+    }
+
+    /// Count a message as queued until the returned guard is dropped
+    fn enter(self: &Arc<Self>, bytes: usize) -> InFlight {
+        self.in_flight.fetch_add(bytes, Ordering::Relaxed);
+        InFlight {
+            queue: Arc::clone(self),
+            bytes,
+        }
+    }
+
+    /// Wait until fewer than `low` bytes are queued, or the connection closes
+    async fn drain_below(&self, low: usize) {
+        loop {
+            let drained = self.drained.notified();
+            tokio::pin!(drained);
+            drained.as_mut().enable();
+            if self.queued() < low || self.closed.load(Ordering::Relaxed) {
+                return;
+            }
+            drained.await;
+        }
+    }
+}
+
+/// A message counted in the send queue while its send() runs
+struct InFlight {
+    queue: Arc<SendQueue>,
+    bytes: usize,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.queue
+            .in_flight
+            .fetch_sub(self.bytes, Ordering::Relaxed);
+        self.queue.drained.notify_waiters();
+    }
+}
+
+impl Drop for HandleGuard {
+    fn drop(&mut self) {
+        // Teardown is async; without a runtime the socket is simply dropped
+        let executor = runtime::executor();
+        if !executor.can_spawn() {
+            return;
+        }
+
+        let connection = Connection {
+            inner: Arc::clone(&self.inner),
+            event_sender: self.event_sender.clone(),
+            event_receiver: Arc::clone(&self.event_receiver),
+            send_stall: Arc::clone(&self.send_stall),
+            memory: Arc::clone(&self.memory),
+            handle: None,
+        };
+
+        executor.spawn(Box::pin(async move {
+            let policy = {
+                let inner = connection.inner.read().await;
+                if inner.state == ConnectionState::Closed {
+                    return;
+                }
+                inner.drop_policy
+            };
+
+            match policy {
+                DropPolicy::Close => {
+                    let _ = connection.close().await;
+                }
+                DropPolicy::Abort => {
+                    let _ = connection.abort().await;
+                }
+                DropPolicy::Linger(linger) => {
+                    if runtime::timeout(linger, connection.close()).await.is_err() {
+                        let _ = connection.abort().await;
+                    }
+                }
+            }
+        }));
+    }
 }
 
 pub(crate) struct ConnectionInner {
     preconnection: Preconnection,
     state: ConnectionState,
+    // Publishes every state change to state watchers
+    state_sender: watch::Sender<ConnectionState>,
     local_endpoint: Option<LocalEndpoint>,
     remote_endpoint: Option<RemoteEndpoint>,
     #[allow(dead_code)]
     transport_properties: TransportProperties,
     // Actual network stream (for now just TCP)
     tcp_stream: Option<TcpStream>,
+    // Datagram socket shared with the Listener for connectionless transports
+    udp_socket: Option<Arc<UdpSocket>>,
+    // Proxy the TCP stream tunnels through
+    tunnel: Option<ProxyKind>,
     // Message queue for messages sent before connection is established
     pending_messages: Vec<Message>,
+    // Pending messages are held until the peer sends first (activeReadBeforeSend)
+    awaiting_peer_data: bool,
+    // Taken by each send while it queues or writes, so messages keep call order
+    send_order: Arc<Mutex<()>>,
+    // Bytes queued for sending, against the send watermarks
+    send_queue: Arc<SendQueue>,
+    // How a Scavenger connection yields to other traffic
+    scavenger: Option<Scavenger>,
+    // Holds sends to maxSendRate
+    send_rate: RateLimiter,
     // Connection group this connection belongs to
     connection_group: Option<Arc<ConnectionGroup>>,
-    // Batching state
+    // Share of the group's sending under proportional-rate scheduling
+    scheduling_weight: u32,
+    // Batching state; also holds bundled messages awaiting the end of their bundle
     batch_mode: bool,
     batched_messages: Vec<Message>,
     // Message ID counter
     next_message_id: Arc<AtomicU64>,
     // Message framers for this connection
     framers: FramerStack,
+    // TLS session started in-band with start_security
+    #[cfg(feature = "tls")]
+    tls: Option<crate::tls::TlsSession>,
     // Receive buffer for incoming data
     receive_buffer: Vec<u8>,
+    // The receive buffer holds data that arrived in the SYN (TCP Fast Open)
+    early_data: bool,
+    // Where the last datagram from the peer arrived, for Listener flows
+    packet_info: Option<PacketInfo>,
+    // Source address replies are sent from, when the Listener's socket is
+    // bound to a wildcard address
+    reply_source: Option<PacketInfo>,
+    // Reassembly of framed messages from the receive buffer
+    reassembly: Reassembler,
+    // Messages decoded by the framer stack, awaiting delivery
+    decoded: VecDeque<(Message, MessageContext)>,
     // Connection properties
     properties: ConnectionProperties,
     // Track if a Final message was sent
     final_message_sent: bool,
     // Track if a Final message was received
     final_message_received: bool,
+    // What to do when the last handle is dropped
+    drop_policy: DropPolicy,
+    // Perturbs this connection's I/O in tests
+    fault_injector: Option<Arc<FaultInjector>>,
+    // Emulates link conditions for data this connection sends
+    shaper: Option<Arc<TrafficShaper>>,
+    // Current path, refreshed on PathChange
+    path_info: Option<PathInfo>,
+    // Whether a task watches for the loss of a pinned path
+    path_watched: bool,
+    // Kernel timestamp of the most recently received data
+    receive_timestamp: Option<Instant>,
+    // When received data was last read from the transport
+    received_at: Option<Instant>,
+    // Byte counters reported in statistics snapshots
+    bytes_sent: u64,
+    bytes_received: u64,
+    datagrams_sent: u64,
+    datagram_send_calls: u64,
+    // Set once the kernel refuses a segmented send, after which datagrams go singly
+    udp_offload_failed: bool,
+    // Last datagram sent or received, which defers NAT keepalives
+    last_traffic: Instant,
+    // Active heartbeat; its task stops once this is replaced or dropped
+    heartbeat: Option<Arc<Heartbeat>>,
+    // What the Listener peeked from the start of the stream
+    sniffed: Option<SniffedData>,
+    // Bytes held by this connection, counted against the memory budget
+    memory: Arc<MemoryAccount>,
 }
 
-impl Clone for Connection {
-    fn clone(&self) -> Self {
-        // When cloning a Connection, we don't want to affect the connection count
-        // This is just cloning the handle, not creating a new connection
-        Self {
-            inner: Arc::clone(&self.inner),
-            event_sender: self.event_sender.clone(),
-            event_receiver: Arc::clone(&self.event_receiver),
+impl ConnectionInner {
+    /// Change the state and notify state watchers
+    ///
+    /// Transitions the state machine does not allow are ignored, so a late
+    /// update cannot revive a closed connection. Returns whether the state changed.
+    fn set_state(&mut self, state: ConnectionState) -> bool {
+        if !state_machine::is_valid_transition(self.state, state) {
+            if self.state != state {
+                log::debug!("Ignoring state change from {:?} to {state:?}", self.state);
+            }
+            return false;
         }
+        self.state = state;
+        self.state_sender.send_replace(state);
+        true
     }
-}
-
-impl Connection {
-    /// Create a new Connection with pre-populated data (for initiate)
-    pub(crate) fn new_with_data(
-        preconnection: Preconnection,
-        state: ConnectionState,
-        local_endpoint: Option<LocalEndpoint>,
-        remote_endpoint: Option<RemoteEndpoint>,
-        transport_properties: TransportProperties,
-    ) -> Self {
-        let (event_sender, event_receiver) = mpsc::unbounded_channel();
 
-        Self {
-            inner: Arc::new(RwLock::new(ConnectionInner {
-                preconnection,
-                state,
-                local_endpoint,
-                remote_endpoint,
-                transport_properties,
-                tcp_stream: None,
-                pending_messages: Vec::new(),
-                connection_group: None,
-                batch_mode: false,
-                batched_messages: Vec::new(),
-                next_message_id: Arc::new(AtomicU64::new(1)),
-                framers: FramerStack::new(), // Will be populated from preconnection async
-                receive_buffer: Vec::new(),
-                properties: ConnectionProperties::new(),
-                final_message_sent: false,
-                final_message_received: false,
-            })),
-            event_sender,
-            event_receiver: Arc::new(RwLock::new(event_receiver)),
+    /// Move to Closed, leaving the live count of the connection group
+    ///
+    /// Every path that closes a connection goes through here so the count
+    /// stays consistent however the connection ends.
+    pub(crate) fn set_closed(&mut self) {
+        self.send_queue.close();
+        if self.set_state(ConnectionState::Closed) {
+            if let Some(ref group) = self.connection_group {
+                group.remove_connection();
+            }
         }
     }
 
-    /// Get the current state of the connection
-    pub async fn state(&self) -> ConnectionState {
-        let inner = self.inner.read().await;
-        inner.state
-    }
+    /// Close immediately, resetting the transport and discarding queued data
+    fn reset_transport(&mut self) {
+        self.set_closed();
 
-    /// Send a message on the connection
-    /// RFC Section 9.2
-    pub async fn send(&self, mut message: Message) -> Result<()> {
-        // Assign message ID if not already set
-        if message.id().is_none() {
-            let id = self.get_next_message_id().await;
-            message = message.with_id(id);
+        if let Some(stream) = self.tcp_stream.take() {
+            reset_tcp_stream(stream);
+        }
+        self.udp_socket = None;
+        #[cfg(feature = "tls")]
+        {
+            self.tls = None;
         }
 
-        // Check if message has expired
-        if let Some(context) = message.send_context() {
-            if let Some(expiry) = context.expiry {
-                if Instant::now() >= expiry {
-                    // Notify about expiration
-                    let _ = self.event_sender.send(ConnectionEvent::Expired {
-                        message_id: message.id(),
+        self.pending_messages.clear();
+        self.batched_messages.clear();
+        self.clear_receive_buffer();
+    }
+
+    /// Add received bytes to the receive buffer, decrypting them first on a
+    /// secured connection
+    ///
+    /// A TLS failure ends the connection: ReceiveError and ConnectionError
+    /// are emitted, the transport is reset, and the error is returned.
+    fn accept_received(&mut self, data: Vec<u8>, event_sender: &EventSender) -> Result<()> {
+        self.received_at = Some(Instant::now());
+        #[cfg(feature = "tls")]
+        if let Some(tls) = self.tls.as_mut() {
+            let result = tls.decrypt(&data).and_then(|plaintext| {
+                // Records the session owes the peer, e.g. a key update
+                let output = tls.take_output()?;
+                Ok((plaintext, output))
+            });
+            match result {
+                Ok((plaintext, output)) => {
+                    if let (false, Some(stream)) = (output.is_empty(), self.tcp_stream.as_ref()) {
+                        let _ = stream.try_write(&output);
+                    }
+                    self.receive_buffer.extend(plaintext);
+                    self.account_memory();
+                    return Ok(());
+                }
+                Err(e) => {
+                    let error = e.to_string();
+                    let _ = event_sender.send(ConnectionEvent::ReceiveError {
+                        error: error.clone(),
                     });
-                    return Err(TransportServicesError::MessageExpired);
+                    self.reset_transport();
+                    let _ = event_sender.send(ConnectionEvent::ConnectionError(error));
+                    return Err(e);
                 }
             }
         }
+        let _ = event_sender;
+        self.receive_buffer.extend(data);
+        self.account_memory();
+        Ok(())
+    }
 
-        let mut inner = self.inner.write().await;
+    /// Update the memory account from the data buffered for receiving and sending
+    fn account_memory(&self) {
+        let receive_buffer = self.receive_buffer.len()
+            + self
+                .decoded
+                .iter()
+                .map(|(message, _)| message.len())
+                .sum::<usize>();
+        let send_queue = self
+            .pending_messages
+            .iter()
+            .chain(&self.batched_messages)
+            .map(Message::len)
+            .sum();
+        self.memory.set_buffered(receive_buffer, send_queue);
+        self.send_queue.set_held(send_queue);
+    }
 
-        match inner.state {
-            ConnectionState::Established => {
-                if inner.batch_mode {
-                    // Add to batch
-                    inner.batched_messages.push(message);
-                    Ok(())
-                } else {
-                    // Send immediately
-                    drop(inner);
-                    self.send_message_internal(message).await
+    /// Start or stop yielding to other traffic as the capacity profile
+    /// becomes or stops being Scavenger
+    ///
+    /// The kernel's TCP-LP does this where available; otherwise sends are
+    /// paced by LEDBAT from the round trips TCP_INFO reports.
+    fn update_scavenger(&mut self) {
+        let scavenger = matches!(
+            self.properties.get("connCapacityProfile"),
+            Some(ConnectionProperty::ConnCapacityProfile(
+                CapacityProfile::Scavenger
+            ))
+        );
+        let Some(ref stream) = self.tcp_stream else {
+            return;
+        };
+        self.scavenger = match (scavenger, self.scavenger.take()) {
+            (true, Some(current)) => Some(current),
+            (true, None) => Some(match enable_low_priority_congestion(stream) {
+                Some(previous) => Scavenger::Kernel { previous },
+                None => Scavenger::Paced(LedbatPacer::new(Instant::now())),
+            }),
+            (false, Some(Scavenger::Kernel { previous })) => {
+                set_tcp_congestion(stream, &previous);
+                None
+            }
+            (false, _) => None,
+        };
+    }
+
+    /// How long a Scavenger connection's write of `bytes` must wait for its
+    /// pacing rate, if at all
+    /// How long a send of `bytes` must wait for maxSendRate, the group's
+    /// aggregate rate cap and Scavenger pacing, if at all
+    fn send_delay(&mut self, bytes: usize) -> Option<Duration> {
+        let now = Instant::now();
+        let rate = match self.properties.get("maxSendRate") {
+            Some(ConnectionProperty::MaxSendRate(rate)) => *rate,
+            _ => None,
+        };
+        self.send_rate.set_rate(rate);
+        let mut group_rate = self
+            .connection_group
+            .as_ref()
+            .map(|group| group.send_rate.lock().unwrap());
+
+        // The send starts once both rates allow it, and counts against both
+        let start = [
+            self.send_rate.ready_at(now),
+            group_rate
+                .as_ref()
+                .and_then(|limiter| limiter.ready_at(now)),
+        ]
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap_or(now);
+        self.send_rate.consume(bytes, start);
+        if let Some(limiter) = group_rate.as_mut() {
+            limiter.consume(bytes, start);
+        }
+        drop(group_rate);
+
+        let wait = (start - now).max(self.scavenger_delay(bytes, now));
+        Some(wait).filter(|wait| !wait.is_zero())
+    }
+
+    fn scavenger_delay(&mut self, bytes: usize, now: Instant) -> Duration {
+        let Some(Scavenger::Paced(pacer)) = self.scavenger.as_mut() else {
+            return Duration::ZERO;
+        };
+        if pacer.needs_sample(now) {
+            if let Some(ref stream) = self.tcp_stream {
+                let mut stats = ConnectionStats::default();
+                read_tcp_info(stream, &mut stats);
+                if let Some(rtt) = stats.rtt {
+                    pacer.on_rtt(rtt, now);
                 }
             }
-            ConnectionState::Establishing => {
-                // Queue message for sending after establishment
-                inner.pending_messages.push(message);
-                Ok(())
+        }
+        pacer.reserve(bytes, now)
+    }
+
+    /// The send queue's high and low watermarks, if it is bounded
+    fn send_watermarks(&self) -> Option<(usize, usize)> {
+        let properties = &self.transport_properties.connection_properties;
+        let high = properties.send_high_watermark?;
+        let low = properties.send_low_watermark.unwrap_or(high / 2);
+        Some((high, low.min(high)))
+    }
+
+    /// Time allowed for writing these messages before the write is abandoned
+    /// The shortest of sendTimeout and the messages' deadlines applies
+    fn send_deadline(&self, messages: &[Message]) -> Option<Duration> {
+        let send_timeout = match self.properties.get("sendTimeout") {
+            Some(ConnectionProperty::SendTimeout(TimeoutValue::Duration(timeout))) => {
+                Some(*timeout)
             }
-            _ => Err(TransportServicesError::InvalidState(
-                "Cannot send on a closed connection".to_string(),
-            )),
+            _ => None,
+        };
+        messages
+            .iter()
+            .filter_map(|message| message.properties().deadline)
+            .chain(send_timeout)
+            .min()
+    }
+
+    /// How long a write may make no progress before it counts as stalled
+    fn send_stall_threshold(&self) -> Option<Duration> {
+        match self.properties.get("sendStallThreshold") {
+            Some(ConnectionProperty::SendStallThreshold(TimeoutValue::Duration(threshold))) => {
+                Some(*threshold)
+            }
+            _ => None,
         }
     }
 
-    /// Internal method to actually send a message
-    async fn send_message_internal(&self, message: Message) -> Result<()> {
-        let mut inner = self.inner.write().await;
+    /// Whether SoftError events are wanted
+    fn soft_error_notify(&self) -> bool {
+        matches!(
+            self.transport_properties
+                .selection_properties
+                .soft_error_notify,
+            Preference::Require | Preference::Prefer
+        )
+    }
 
-        // Check if this is a Final message
-        if message.properties().final_message {
-            inner.final_message_sent = true;
+    /// Socket address of the remote endpoint, if known
+    fn remote_socket_addr(&self) -> Option<SocketAddr> {
+        self.remote_endpoint.as_ref().and_then(|remote| {
+            remote.identifiers.iter().find_map(|id| match id {
+                EndpointIdentifier::SocketAddress(addr) => Some(*addr),
+                _ => None,
+            })
+        })
+    }
+
+    /// Path details that can be read from the socket itself
+    fn socket_path(&self) -> PathInfo {
+        if let Some(ref stream) = self.tcp_stream {
+            PathInfo {
+                local_address: stream.local_addr().ok(),
+                remote_address: stream.peer_addr().ok(),
+                path_mtu: path_mtu(socket2::SockRef::from(stream)),
+                ..PathInfo::default()
+            }
+        } else if let Some(ref socket) = self.udp_socket {
+            PathInfo {
+                local_address: socket.local_addr().ok(),
+                remote_address: self.remote_socket_addr(),
+                path_mtu: path_mtu(socket2::SockRef::from(socket.as_ref())),
+                ..PathInfo::default()
+            }
+        } else {
+            PathInfo::default()
         }
+    }
 
-        // Frame the message if framers are available
-        let data_to_send = if !inner.framers.is_empty() {
-            let context = MessageContext::new(); // Use MessageContext for framing
-            inner.framers.frame_message(&message, &context).await?
+    /// Describe the protocol stack carrying the connection, outermost first
+    fn protocol_stack(&self) -> ProtocolStack {
+        let mut layers = Vec::new();
+        if let Some(ConnectionProperty::SecurityProtocolInUse(Some(protocol))) =
+            self.properties.get("securityProtocol")
+        {
+            layers.push(StackLayer::Security(*protocol));
+        }
+        if let Some(kind) = self.tunnel {
+            layers.push(StackLayer::Proxy(kind));
+        }
+        let local = if let Some(ref stream) = self.tcp_stream {
+            layers.push(StackLayer::Tcp);
+            stream.local_addr().ok()
+        } else if let Some(ref socket) = self.udp_socket {
+            layers.push(StackLayer::Udp);
+            socket.local_addr().ok()
         } else {
-            message.data().to_vec()
+            // Nothing carries the connection before it is established or once closed
+            return ProtocolStack::default();
         };
+        match local {
+            Some(SocketAddr::V4(_)) => layers.push(StackLayer::Ipv4),
+            Some(SocketAddr::V6(v6)) if v6.ip().to_ipv4_mapped().is_some() => {
+                layers.push(StackLayer::Ipv4)
+            }
+            Some(SocketAddr::V6(_)) => layers.push(StackLayer::Ipv6),
+            None => {}
+        }
+        ProtocolStack { layers }
+    }
 
-        if let Some(ref mut stream) = inner.tcp_stream {
-            let message_id = message.id();
-            let event_sender = self.event_sender.clone();
+    /// Check for, and consume, a reset requested by the fault injector
+    fn injected_reset(&self) -> bool {
+        self.fault_injector
+            .as_ref()
+            .is_some_and(|injector| injector.take_reset())
+    }
 
-            // Send the message
-            match stream.write_all(&data_to_send).await {
-                Ok(_) => {
-                    match stream.flush().await {
-                        Ok(_) => {
-                            // Notify successful send
-                            let _ = event_sender.send(ConnectionEvent::Sent { message_id });
-                            Ok(())
-                        }
-                        Err(e) => {
-                            let error_msg = e.to_string();
-                            let _ = event_sender.send(ConnectionEvent::SendError {
-                                message_id,
-                                error: error_msg.clone(),
-                            });
+    /// Check that the protocol stack can honour a message's reliability
+    /// RFC Section 9.1.3.7
+    fn check_message_reliability(&self, message: &Message) -> Result<()> {
+        match message.properties().reliable {
+            Some(true) if self.udp_socket.is_some() => Err(TransportServicesError::NotSupported(
+                "UDP cannot deliver messages reliably".to_string(),
+            )),
+            // TCP delivers unreliable messages reliably when partial reliability was
+            // requested, since no stream supports it; otherwise the request is an error
+            Some(false) if self.udp_socket.is_none() => {
+                match self
+                    .transport_properties
+                    .selection_properties
+                    .per_msg_reliability
+                {
+                    Preference::Require | Preference::Prefer => Ok(()),
+                    _ => Err(TransportServicesError::InvalidParameters(
+                        "Unreliable messages require the perMsgReliability property".to_string(),
+                    )),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
 
-                            // Check if this might be a soft error
-                            if error_msg.contains("broken pipe")
-                                || error_msg.contains("connection reset")
-                                || error_msg.contains("connection refused")
-                            {
-                                drop(inner);
-                                self.emit_soft_error(format!(
-                                    "Network error during flush: {error_msg}"
-                                ))
-                                .await;
-                            }
-
-                            Err(TransportServicesError::SendFailed(error_msg))
-                        }
-                    }
-                }
-                Err(e) => {
-                    let error_msg = e.to_string();
-                    let _ = event_sender.send(ConnectionEvent::SendError {
-                        message_id,
-                        error: error_msg.clone(),
-                    });
+    /// Whether a message should be delivered in order
+    /// RFC Section 9.1.3.3
+    ///
+    /// Unset messages inherit the connection's preserveOrder property.
+    fn message_ordered(&self, message: &Message) -> bool {
+        message.properties().ordered.unwrap_or(!matches!(
+            self.transport_properties
+                .selection_properties
+                .preserve_order,
+            Preference::Prohibit | Preference::Avoid
+        ))
+    }
 
-                    // Check if this might be a soft error (network-related)
-                    if error_msg.contains("broken pipe")
-                        || error_msg.contains("connection reset")
-                        || error_msg.contains("connection refused")
-                    {
-                        drop(inner);
-                        self.emit_soft_error(format!("Network error during send: {error_msg}"))
-                            .await;
-                    }
+    /// Check that the protocol stack can honour a message's ordering
+    ///
+    /// TCP always delivers in order, which also satisfies unordered messages.
+    fn check_message_ordering(&self, message: &Message) -> Result<()> {
+        if self.udp_socket.is_some() && message.properties().ordered == Some(true) {
+            return Err(TransportServicesError::NotSupported(
+                "UDP cannot preserve message order".to_string(),
+            ));
+        }
+        Ok(())
+    }
 
-                    Err(TransportServicesError::SendFailed(error_msg))
+    /// Apply a property that has just been set to the underlying socket
+    ///
+    /// Used for this connection and for every member of its group, so the
+    /// sockets of a group follow its shared properties.
+    fn apply_to_transport(&mut self, key: &str, value: &ConnectionProperty) -> Result<()> {
+        match key {
+            "connTimeout" => {
+                // RFC 8.2.3: tcp.userTimeoutChangeable becomes false when connTimeout is used
+                if matches!(
+                    value,
+                    ConnectionProperty::ConnTimeout(TimeoutValue::Duration(_))
+                ) {
+                    self.properties.set(
+                        "tcp.userTimeoutChangeable",
+                        ConnectionProperty::TcpUserTimeoutChangeable(false),
+                    )?;
+                }
+                // Connection timeouts are enforced by the crate, not the socket
+                log::debug!("Connection timeout set to {value:?}");
+            }
+            "keepAliveTimeout" => {
+                if let (Some(ref stream), ConnectionProperty::KeepAliveTimeout(timeout)) =
+                    (&self.tcp_stream, value)
+                {
+                    set_keep_alive(stream, *timeout);
+                }
+            }
+            "connPriority" | "connCapacityProfile" => {
+                if let Some(ref stream) = self.tcp_stream {
+                    apply_traffic_class(stream, &self.properties);
+                }
+                self.update_scavenger();
+            }
+            "tcp.userTimeoutEnabled" | "tcp.userTimeoutValue" => {
+                if let Some(ref stream) = self.tcp_stream {
+                    set_user_timeout(stream, self.user_timeout());
+                }
+            }
+            // Datagrams carry the hop limit with each send instead
+            "ipHopLimit" => {
+                if let Some(ref stream) = self.tcp_stream {
+                    set_hop_limit(stream, self.hop_limit());
                 }
             }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Check that a protocol-specific property applies to the stack in use
+    ///
+    /// Before establishment any namespace is accepted, as the stack has not
+    /// been selected yet.
+    fn check_property_namespace(&self, key: &str) -> Result<()> {
+        let Some(namespace) = PropertyNamespace::of(key) else {
+            return Ok(());
+        };
+        let stack = if self.tcp_stream.is_some() {
+            "TCP"
+        } else if self.udp_socket.is_some() {
+            "UDP"
         } else {
-            Err(TransportServicesError::InvalidState(
-                "No active stream".to_string(),
-            ))
+            return Ok(());
+        };
+        if namespace == PropertyNamespace::Tcp && stack == "TCP" {
+            return Ok(());
         }
+        Err(TransportServicesError::NotSupported(format!(
+            "{}.* properties do not apply to a connection using {stack}",
+            namespace.prefix()
+        )))
     }
 
-    /// Start batching messages
-    /// RFC Section 9.2.4
-    pub async fn start_batch(&self) -> Result<()> {
-        let mut inner = self.inner.write().await;
-        inner.batch_mode = true;
-        Ok(())
+    /// The TCP user timeout requested through the tcp.userTimeout* properties
+    /// RFC Section 8.2
+    fn user_timeout(&self) -> Option<Duration> {
+        let enabled = matches!(
+            self.properties.get("tcp.userTimeoutEnabled"),
+            Some(ConnectionProperty::TcpUserTimeoutEnabled(true))
+        );
+        match self.properties.get("tcp.userTimeoutValue") {
+            Some(ConnectionProperty::TcpUserTimeoutValue(Some(timeout))) if enabled => {
+                Some(*timeout)
+            }
+            _ => None,
+        }
     }
 
-    /// End batching and send all batched messages
-    /// RFC Section 9.2.4
-    pub async fn end_batch(&self) -> Result<()> {
-        let mut inner = self.inner.write().await;
-        inner.batch_mode = false;
-        let messages = inner.batched_messages.drain(..).collect::<Vec<_>>();
-        drop(inner);
+    /// Whether the pathPinning property keeps the connection on its path
+    fn path_pinned(&self) -> bool {
+        matches!(
+            self.properties.get("pathPinning"),
+            Some(ConnectionProperty::PathPinning(true))
+        )
+    }
 
-        // Send all batched messages
-        for message in messages {
-            self.send_message_internal(message).await?;
+    /// The TTL or hop limit requested through the ipHopLimit property
+    fn hop_limit(&self) -> Option<u8> {
+        match self.properties.get("ipHopLimit") {
+            Some(ConnectionProperty::IpHopLimit(hop_limit)) => *hop_limit,
+            _ => None,
         }
+    }
 
-        Ok(())
+    /// connPriority, where lower values are more important (RFC Section 8.1.2)
+    fn priority(&self) -> u32 {
+        match self.properties.get("connPriority") {
+            Some(ConnectionProperty::ConnPriority(priority)) => *priority,
+            _ => 100,
+        }
     }
 
-    /// Get the next message ID
-    async fn get_next_message_id(&self) -> u64 {
-        let inner = self.inner.read().await;
-        inner.next_message_id.fetch_add(1, Ordering::SeqCst)
+    /// Statistics snapshot, without throughput which needs an earlier snapshot
+    fn stats(&self) -> ConnectionStats {
+        let mut stats = ConnectionStats {
+            bytes_sent: self.bytes_sent,
+            bytes_received: self.bytes_received,
+            send_queue: self.pending_messages.len() + self.batched_messages.len(),
+            receive_queue: self.receive_buffer.len(),
+            datagrams_sent: self.datagrams_sent,
+            datagram_send_calls: self.datagram_send_calls,
+            memory: self.memory.usage(),
+            ..ConnectionStats::default()
+        };
+        if let Some(stream) = &self.tcp_stream {
+            read_tcp_info(stream, &mut stats);
+        }
+        stats
     }
 
-    /// Use length-prefix framer for messages
-    pub async fn use_length_prefix_framer(&self) -> Result<()> {
-        use crate::LengthPrefixFramer;
-        let mut inner = self.inner.write().await;
-        inner
-            .framers
-            .add_framer(Box::new(LengthPrefixFramer::new()));
-        Ok(())
+    /// Whether received data should carry kernel timestamps
+    fn receive_timestamps(&self) -> bool {
+        self.transport_properties
+            .connection_properties
+            .receive_timestamps
+            == Some(true)
     }
 
-    /// Receive messages from the connection
-    /// RFC Section 9.3.1 - Enqueuing Receives
-    pub async fn receive(&self) -> Result<(Message, MessageContext)> {
-        self.receive_with_params(None, None).await
+    /// Whether datagrams may be batched through segmentation offload
+    fn udp_offload(&self) -> bool {
+        self.transport_properties.connection_properties.udp_offload != Some(false)
+            && !self.udp_offload_failed
     }
 
-    /// Receive messages with buffer management parameters
-    /// RFC Section 9.3.1 - Enqueuing Receives
+    /// Record where a datagram from the peer arrived
     ///
-    /// minIncompleteLength: Minimum number of bytes to deliver for a partial message
-    /// maxLength: Maximum number of bytes to accept for a single message
-    pub async fn receive_with_params(
-        &self,
-        _min_incomplete_length: Option<usize>,
-        max_length: Option<usize>,
-    ) -> Result<(Message, MessageContext)> {
-        let state = {
-            let inner = self.inner.read().await;
-            inner.state
+    /// The local endpoint becomes the address it was sent to, on the
+    /// interface it came in on. On a socket bound to a wildcard address that
+    /// address is also the source of replies, so the peer sees them come from
+    /// where it sent to; group and broadcast addresses cannot be a source.
+    fn set_packet_info(&mut self, packet_info: PacketInfo) {
+        if self.packet_info == Some(packet_info) {
+            return;
+        }
+        self.packet_info = Some(packet_info);
+        let Some(bound) = self.udp_socket.as_ref().and_then(|s| s.local_addr().ok()) else {
+            return;
         };
 
-        match state {
-            ConnectionState::Established | ConnectionState::Establishing => {
-                // Keep reading until we have a complete message
-                let mut buffer = [0u8; 8192];
-
-                loop {
-                    // Check if we have a complete message in the buffer already
-                    let (has_complete_message, result) = {
-                        let mut inner = self.inner.write().await;
-
-                        if inner.receive_buffer.is_empty() {
-                            (false, None)
-                        } else if !inner.framers.is_empty() {
-                            // Use the framer to parse - we need to manually check for complete messages
-                            // Since we have length-prefix framing, let's implement simple length-prefix parsing here
-                            if inner.receive_buffer.len() >= 4 {
-                                let len_bytes = &inner.receive_buffer[0..4];
-                                let expected_len = u32::from_be_bytes([
-                                    len_bytes[0],
-                                    len_bytes[1],
-                                    len_bytes[2],
-                                    len_bytes[3],
-                                ]) as usize;
-
-                                if inner.receive_buffer.len() >= 4 + expected_len {
-                                    // We have a complete message
-                                    let message_data = &inner.receive_buffer[4..4 + expected_len];
-                                    let message = Message::from_bytes(message_data);
-                                    let mut context = MessageContext::new();
-                                    // Set remote endpoint if available
-                                    context.remote_endpoint = inner.remote_endpoint.clone();
-
-                                    // Remove the processed message from buffer
-                                    inner.receive_buffer.drain(..4 + expected_len);
-
-                                    // Check max_length constraint
-                                    if let Some(max_len) = max_length {
-                                        if message.data().len() > max_len {
-                                            (
-                                                true,
-                                                Some(Err(TransportServicesError::MessageTooLarge(
-                                                    format!(
-                                                        "Message size {} exceeds max length {}",
-                                                        message.data().len(),
-                                                        max_len
-                                                    ),
-                                                ))),
-                                            )
-                                        } else {
-                                            (true, Some(Ok((message, context))))
-                                        }
-                                    } else {
-                                        (true, Some(Ok((message, context))))
-                                    }
-                                } else {
-                                    (false, None)
-                                }
-                            } else {
-                                (false, None)
-                            }
-                        } else {
-                            // No framers - return all buffered data as one message
-                            let message = Message::from_bytes(&inner.receive_buffer);
-                            let mut context = MessageContext::new();
-                            // Set remote endpoint if available
-                            context.remote_endpoint = inner.remote_endpoint.clone();
-                            inner.receive_buffer.clear();
-                            (true, Some(Ok((message, context))))
-                        }
-                    };
-
-                    if has_complete_message {
-                        if let Some(result) = result {
-                            match result {
-                                Ok((message, context)) => {
-                                    // Send Received event
-                                    let _ = self.event_sender.send(ConnectionEvent::Received {
-                                        message_data: message.data().to_vec(),
-                                        message_context: context.clone(),
-                                    });
-
-                                    return Ok((message, context));
-                                }
-                                Err(e) => return Err(e),
-                            }
-                        }
-                    }
+        let destination = packet_info.destination.to_canonical();
+        let mut identifiers = vec![EndpointIdentifier::SocketAddress(SocketAddr::new(
+            destination,
+            bound.port(),
+        ))];
+        if let Some(name) = interface_name(packet_info.interface_index) {
+            identifiers.push(EndpointIdentifier::Interface(name));
+        }
+        self.local_endpoint = Some(LocalEndpoint { identifiers });
 
-                    // No complete message yet - read more data
-                    let read_result = {
-                        let mut inner = self.inner.write().await;
-                        if let Some(ref mut stream) = inner.tcp_stream {
-                            stream.read(&mut buffer).await
-                        } else {
-                            return Err(TransportServicesError::InvalidState(
-                                "No active stream".to_string(),
-                            ));
-                        }
-                    };
+        let group = match destination {
+            IpAddr::V4(v4) => v4.is_multicast() || v4.is_broadcast(),
+            IpAddr::V6(v6) => v6.is_multicast(),
+        };
+        self.reply_source = (bound.ip().is_unspecified() && !group).then_some(packet_info);
+    }
 
-                    match read_result {
-                        Ok(0) => {
-                            // Connection closed by peer
-                            let mut inner = self.inner.write().await;
-                            inner.state = ConnectionState::Closed;
-                            let _ = self.event_sender.send(ConnectionEvent::Closed);
-                            return Err(TransportServicesError::ConnectionFailed(
-                                "Connection closed by peer".to_string(),
-                            ));
-                        }
-                        Ok(n) => {
-                            // Add data to receive buffer
-                            let mut inner = self.inner.write().await;
-                            inner.receive_buffer.extend_from_slice(&buffer[..n]);
-                            // Continue loop to try parsing again
-                        }
-                        Err(e) => {
-                            let error_msg = e.to_string();
-                            let _ = self.event_sender.send(ConnectionEvent::ReceiveError {
-                                error: error_msg.clone(),
-                            });
+    /// Build the MessageContext for a message received on this connection
+    /// RFC Section 9.3.2.1
+    fn receive_context(&self) -> MessageContext {
+        let mut context = MessageContext::new();
+        context.local_endpoint = self.local_endpoint.clone();
+        context.remote_endpoint = self.remote_endpoint.clone();
+        context.interface_timestamp = self.receive_timestamp;
+        context.early_data = self.early_data;
+        // TCP delivers the byte stream in order; datagrams may be reordered
+        context.with_ordered(self.udp_socket.is_none())
+    }
 
-                            // Check if this might be a soft error
-                            if error_msg.contains("broken pipe")
-                                || error_msg.contains("connection reset")
-                                || error_msg.contains("connection refused")
-                                || error_msg.contains("timed out")
-                            {
-                                self.emit_soft_error(format!(
-                                    "Network error during receive: {error_msg}"
-                                ))
-                                .await;
-                            }
+    /// Take the next framed message, or part of one, from the receive buffer
+    /// Returns the message, its context and whether it is a partial delivery
+    ///
+    /// An outermost length-prefix framer is reassembled by the connection, so
+    /// its messages can be delivered in parts; the framers stacked inside it
+    /// decode those parts as they arrive and yield whole messages. Any other
+    /// outermost framer parses the buffered data itself.
+    async fn next_framed(
+        &mut self,
+        max_length: Option<usize>,
+        min_incomplete_length: Option<usize>,
+    ) -> Result<Option<(Message, MessageContext, bool)>> {
+        loop {
+            if let Some((message, context)) = self.decoded.pop_front() {
+                return Ok(Some((message, context, false)));
+            }
 
-                            return Err(TransportServicesError::ReceiveFailed(error_msg));
-                        }
-                    }
+            if !self.framers.outermost_length_prefixed() {
+                if self.receive_buffer.is_empty() {
+                    return Ok(None);
                 }
+                let data = std::mem::take(&mut self.receive_buffer);
+                let parsed = self.framers.parse_data(&data).await?;
+                self.queue_decoded(self.receive_context(), parsed);
+                if self.decoded.is_empty() {
+                    return Ok(None);
+                }
+                continue;
             }
-            _ => Err(TransportServicesError::InvalidState(
-                "Cannot receive on a closed connection".to_string(),
-            )),
+
+            let Some(delivery) =
+                self.reassembly
+                    .next(&mut self.receive_buffer, max_length, min_incomplete_length)
+            else {
+                return Ok(None);
+            };
+            let context = self.receive_context().with_framer_metadata(
+                "length",
+                (delivery.message_length as u32).to_be_bytes().to_vec(),
+            );
+            if !self.framers.has_inner() {
+                let message =
+                    Message::new(delivery.data).with_end_of_message(delivery.end_of_message);
+                return Ok(Some((message, context, delivery.partial)));
+            }
+
+            let parsed = self.framers.parse_inner(&delivery.data).await?;
+            self.queue_decoded(context, parsed);
         }
     }
 
-    /// Close the connection gracefully
-    /// RFC Section 10
-    pub async fn close(&self) -> Result<()> {
-        let mut inner = self.inner.write().await;
-
-        match inner.state {
-            ConnectionState::Established | ConnectionState::Establishing => {
-                inner.state = ConnectionState::Closing;
+    /// Queue messages decoded by framers, keeping the framers' metadata
+    fn queue_decoded(&mut self, context: MessageContext, parsed: Vec<(Message, MessageContext)>) {
+        for (message, framer_context) in parsed {
+            let mut context = context.clone();
+            context
+                .message_properties
+                .framer_metadata
+                .extend(framer_context.message_properties.framer_metadata);
+            self.decoded.push_back((message, context));
+        }
+    }
 
-                // If this connection is part of a group, decrement the connection count
-                if let Some(ref group) = inner.connection_group {
-                    group.remove_connection();
+    /// Deliver the messages in the receive buffer as Received events
+    ///
+    /// Stops at a Final message, or when an oversized message aborts the
+    /// connection.
+    async fn deliver_buffered(&mut self, event_sender: &EventSender) {
+        loop {
+            let message_result = if !self.framers.is_empty() {
+                let delivery = match self.next_framed(None, None).await {
+                    Ok(delivery) => delivery,
+                    Err(e) => {
+                        let _ = event_sender.send(ConnectionEvent::ReceiveError {
+                            error: e.to_string(),
+                        });
+                        continue;
+                    }
+                };
+                if self.report_oversized(event_sender).is_some() {
+                    if self.state == ConnectionState::Closed {
+                        break; // Aborted
+                    }
+                    continue; // Discard it and carry on
                 }
+                delivery
+            } else if !self.receive_buffer.is_empty() {
+                // No framers - treat all data as one message
+                let message = Message::new(std::mem::take(&mut self.receive_buffer));
+                Some((message, self.receive_context(), false))
+            } else {
+                None
+            };
 
-                // Send any pending batched messages before closing
-                let batched_messages = inner.batched_messages.drain(..).collect::<Vec<_>>();
-
-                // Perform graceful close on TCP stream
-                if let Some(ref mut stream) = inner.tcp_stream {
-                    // Try to flush any buffered data (ignore errors if connection is broken)
-                    let _ = tokio::time::timeout(Duration::from_secs(1), stream.flush()).await;
+            if let Some((message, context, partial)) = message_result {
+                let context = context.with_final(message.properties().final_message);
 
-                    // Try to shutdown the write side (ignore errors if connection is broken)
-                    // This sends a TCP FIN packet
-                    let _ = tokio::time::timeout(Duration::from_secs(1), stream.shutdown()).await;
-                }
+                let is_final = context.is_final();
 
-                // Drop the write lock to send batched messages
-                drop(inner);
+                // Send Received or ReceivedPartial event
+                let event = receive_event(&message, &context, partial);
+                self.memory.event_queued(&event);
+                let _ = event_sender.send_stamped(event, receive_timestamps(self.received_at));
 
-                // Send any remaining batched messages (with timeout to avoid hanging)
-                for message in batched_messages {
-                    let _ = tokio::time::timeout(
-                        Duration::from_millis(100),
-                        self.send_message_internal(message),
-                    )
-                    .await;
+                // A Final message closes the read side
+                if is_final {
+                    if self.mark_final_received() {
+                        let _ = event_sender.send(ConnectionEvent::FinalReceived);
+                    }
+                    break;
                 }
-
-                // Re-acquire lock to update state
-                let mut inner = self.inner.write().await;
-                inner.state = ConnectionState::Closed;
-
-                // Clear any remaining state
-                inner.pending_messages.clear();
-                inner.receive_buffer.clear();
-                inner.tcp_stream = None;
-
-                let _ = self.event_sender.send(ConnectionEvent::Closed);
-                Ok(())
-            }
-            ConnectionState::Closing => {
-                // Already closing, just wait
-                Ok(())
+            } else {
+                break; // No more complete messages
             }
-            ConnectionState::Closed => Ok(()), // Already closed
         }
+        self.account_memory();
     }
 
-    /// Abort the connection immediately
-    /// RFC Section 10 - Connection Termination
+    /// Report a framed message discarded for exceeding the maximum message
+    /// size on receive, aborting the connection if configured to
     ///
-    /// Unlike close(), abort() immediately terminates the connection without
-    /// attempting to deliver any outstanding data.
-    pub async fn abort(&self) -> Result<()> {
-        let mut inner = self.inner.write().await;
+    /// Returns the error reported, if a message was discarded.
+    fn report_oversized(&mut self, event_sender: &EventSender) -> Option<String> {
+        let length = self.reassembly.take_rejected()?;
+        let error = format!(
+            "Message of {length} bytes exceeds the maximum message size on receive of {} bytes",
+            self.reassembly.max_message_size().unwrap_or_default()
+        );
+        let _ = event_sender.send(ConnectionEvent::ReceiveError {
+            error: error.clone(),
+        });
 
-        // Only proceed if we're not already closed
-        let was_not_closed = inner.state != ConnectionState::Closed;
-        if !was_not_closed {
-            return Ok(());
+        if self
+            .transport_properties
+            .connection_properties
+            .abort_on_oversized_message
+            .unwrap_or(false)
+        {
+            self.reset_transport();
+            let _ = event_sender.send(ConnectionEvent::ConnectionError(error.clone()));
         }
+        Some(error)
+    }
 
-        // Immediately set state to Closed
-        inner.state = ConnectionState::Closed;
+    /// Discard buffered received data, including any message being reassembled
+    fn clear_receive_buffer(&mut self) {
+        self.receive_buffer.clear();
+        self.reassembly.reset();
+        self.decoded.clear();
+        self.framers.reset();
+        self.account_memory();
+    }
 
-        // Force close the TCP stream if it exists
-        if let Some(stream) = inner.tcp_stream.take() {
-            // Drop the stream to force immediate closure
-            // This will send a TCP RST instead of graceful FIN
-            drop(stream);
+    /// Record that the peer's Final message was received, closing the read side
+    /// Returns true if this is the first time the Final message was seen
+    fn mark_final_received(&mut self) -> bool {
+        if self.final_message_received {
+            return false;
         }
+        self.final_message_received = true;
+        // Any data after the Final message is not delivered
+        self.clear_receive_buffer();
+        true
+    }
+}
 
-        // Clear any pending messages since we're aborting
-        inner.pending_messages.clear();
-        inner.batched_messages.clear();
-        inner.receive_buffer.clear();
-
-        // If this connection is part of a group, decrement the connection count
-        if let Some(ref group) = inner.connection_group {
-            group.remove_connection();
+impl Clone for Connection {
+    fn clone(&self) -> Self {
+        // When cloning a Connection, we don't want to affect the connection count
+        // This is just cloning the handle, not creating a new connection
+        Self {
+            inner: Arc::clone(&self.inner),
+            event_sender: self.event_sender.clone(),
+            event_receiver: Arc::clone(&self.event_receiver),
+            send_stall: Arc::clone(&self.send_stall),
+            memory: Arc::clone(&self.memory),
+            handle: self.handle.clone(),
         }
-
-        // Send ConnectionError event for abort (as per RFC Section 10)
-        let _ = self.event_sender.send(ConnectionEvent::ConnectionError(
-            "Connection aborted".to_string(),
-        ));
-
-        Ok(())
     }
+}
 
-    /// Clone the connection to create a new connection in the same group
-    /// RFC Section 7.4
-    pub async fn clone_connection(&self) -> Result<Connection> {
-        let inner = self.inner.read().await;
-
-        match inner.state {
-            ConnectionState::Established => {
-                // Get or create connection group
-                let (group, was_not_grouped) = if let Some(ref group) = inner.connection_group {
-                    (Arc::clone(group), false)
-                } else {
-                    // Create a new connection group for this connection
-                    let new_group = Arc::new(ConnectionGroup::new(
-                        inner.transport_properties.clone(),
-                        inner
-                            .local_endpoint
-                            .as_ref()
-                            .map(|e| vec![e.clone()])
-                            .unwrap_or_default(),
-                        inner
-                            .remote_endpoint
-                            .as_ref()
-                            .map(|e| vec![e.clone()])
-                            .unwrap_or_default(),
-                    ));
-                    (new_group, true)
-                };
+impl Connection {
+    /// Create a new Connection with pre-populated data (for initiate)
+    pub(crate) fn new_with_data(
+        preconnection: Preconnection,
+        state: ConnectionState,
+        local_endpoint: Option<LocalEndpoint>,
+        remote_endpoint: Option<RemoteEndpoint>,
+        transport_properties: TransportProperties,
+    ) -> Self {
+        let (event_sender, event_receiver) = mpsc::unbounded_channel();
+        let event_sender = EventSender::new(event_sender);
 
-                // Get preconnection before dropping inner
-                let preconn = inner.preconnection.clone();
+        // RFC 8.1.2: connPriority may already be set on the Preconnection
+        let mut properties = ConnectionProperties::new();
+        if let Some(priority) = transport_properties
+            .connection_properties
+            .connection_priority
+        {
+            let _ = properties.set(
+                "connPriority",
+                ConnectionProperty::ConnPriority(priority.max(0) as u32),
+            );
+        }
+
+        if let Some(hop_limit) = transport_properties.connection_properties.hop_limit {
+            let _ = properties.set(
+                "ipHopLimit",
+                ConnectionProperty::IpHopLimit(Some(hop_limit)),
+            );
+        }
+
+        if let Some(pinned) = transport_properties.connection_properties.path_pinning {
+            let _ = properties.set("pathPinning", ConnectionProperty::PathPinning(pinned));
+        }
+
+        if let Some(rate) = transport_properties.connection_properties.max_send_rate {
+            let _ = properties.set("maxSendRate", ConnectionProperty::MaxSendRate(Some(rate)));
+        }
+        if let Some(rate) = transport_properties.connection_properties.max_recv_rate {
+            let _ = properties.set("maxRecvRate", ConnectionProperty::MaxRecvRate(Some(rate)));
+        }
+
+        let mut reassembly = Reassembler::default();
+        reassembly.set_max_message_size(
+            transport_properties
+                .connection_properties
+                .maximum_message_size_on_receive,
+        );
+
+        let memory = Arc::new(MemoryAccount::default());
+        let connection = Self {
+            inner: Arc::new(RwLock::new(ConnectionInner {
+                preconnection,
+                state,
+                state_sender: watch::Sender::new(state),
+                local_endpoint,
+                remote_endpoint,
+                transport_properties,
+                tcp_stream: None,
+                udp_socket: None,
+                tunnel: None,
+                pending_messages: Vec::new(),
+                awaiting_peer_data: false,
+                send_order: Arc::new(Mutex::new(())),
+                send_queue: Arc::default(),
+                scavenger: None,
+                send_rate: RateLimiter::default(),
+                connection_group: None,
+                scheduling_weight: 1,
+                batch_mode: false,
+                batched_messages: Vec::new(),
+                next_message_id: Arc::new(AtomicU64::new(1)),
+                framers: FramerStack::new(), // Will be populated from preconnection async
+                #[cfg(feature = "tls")]
+                tls: None,
+                receive_buffer: Vec::new(),
+                early_data: false,
+                packet_info: None,
+                reply_source: None,
+                reassembly,
+                decoded: VecDeque::new(),
+                properties,
+                final_message_sent: false,
+                final_message_received: false,
+                drop_policy: DropPolicy::default(),
+                fault_injector: None,
+                shaper: None,
+                path_info: None,
+                path_watched: false,
+                receive_timestamp: None,
+                received_at: None,
+                bytes_sent: 0,
+                bytes_received: 0,
+                datagrams_sent: 0,
+                datagram_send_calls: 0,
+                udp_offload_failed: false,
+                last_traffic: Instant::now(),
+                heartbeat: None,
+                sniffed: None,
+                memory: Arc::clone(&memory),
+            })),
+            event_sender,
+            event_receiver: Arc::new(RwLock::new(event_receiver)),
+            send_stall: Arc::new(SendStall::default()),
+            memory,
+            handle: None,
+        };
+        let handle = HandleGuard {
+            inner: Arc::clone(&connection.inner),
+            event_sender: connection.event_sender.clone(),
+            event_receiver: Arc::clone(&connection.event_receiver),
+            send_stall: Arc::clone(&connection.send_stall),
+            memory: Arc::clone(&connection.memory),
+        };
+
+        let connection = Self {
+            handle: Some(Arc::new(handle)),
+            ..connection
+        };
+        MemoryAccount::register(&connection.memory, connection.group_member());
+        crate::shutdown::register_connection(connection.group_member());
+        connection
+    }
+
+    /// Clone the connection for internal bookkeeping
+    ///
+    /// Internal clones do not count as handles, so they do not keep the
+    /// connection open once the application drops its last handle.
+    pub(crate) fn internal_clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+            event_sender: self.event_sender.clone(),
+            event_receiver: Arc::clone(&self.event_receiver),
+            send_stall: Arc::clone(&self.send_stall),
+            memory: Arc::clone(&self.memory),
+            handle: None,
+        }
+    }
+
+    /// Describe this connection for registration with a connection group
+    pub(crate) fn group_member(&self) -> GroupMember {
+        GroupMember {
+            inner: Arc::downgrade(&self.inner),
+            event_sender: self.event_sender.clone(),
+            event_receiver: Arc::downgrade(&self.event_receiver),
+            send_stall: Arc::downgrade(&self.send_stall),
+            memory: Arc::downgrade(&self.memory),
+            handle: self.handle.as_ref().map(Arc::downgrade),
+        }
+    }
+
+    /// Rebuild a handle to a connection group member, if it is still alive
+    ///
+    /// Members whose user-held handles have all been dropped are being torn
+    /// down by their drop policy and are not returned.
+    pub(crate) fn from_group_member(member: &GroupMember) -> Option<Self> {
+        let handle = match &member.handle {
+            Some(handle) => Some(handle.upgrade()?),
+            None => None,
+        };
+        Some(Self {
+            inner: member.inner.upgrade()?,
+            event_sender: member.event_sender.clone(),
+            event_receiver: member.event_receiver.upgrade()?,
+            send_stall: member.send_stall.upgrade()?,
+            memory: member.memory.upgrade()?,
+            handle,
+        })
+    }
+
+    /// Set what happens when the last handle to this connection is dropped
+    pub async fn set_drop_policy(&self, policy: DropPolicy) {
+        let mut inner = self.inner.write().await;
+        inner.drop_policy = policy;
+    }
+
+    /// Set the largest framed message buffered whole before delivery
+    ///
+    /// Larger messages are delivered as ReceivedPartial events as their bytes
+    /// arrive, so a peer cannot make the receiver buffer without bound.
+    pub async fn set_reassembly_limit(&self, limit: usize) {
+        let mut inner = self.inner.write().await;
+        inner.reassembly.set_limit(limit);
+    }
+
+    /// Get the largest framed message buffered whole before delivery
+    pub async fn reassembly_limit(&self) -> usize {
+        let inner = self.inner.read().await;
+        inner.reassembly.limit()
+    }
+
+    /// Get what happens when the last handle to this connection is dropped
+    pub async fn drop_policy(&self) -> DropPolicy {
+        let inner = self.inner.read().await;
+        inner.drop_policy
+    }
+
+    /// Perturb this connection's I/O with the given fault injector
+    pub async fn set_fault_injector(&self, injector: Arc<FaultInjector>) {
+        let mut inner = self.inner.write().await;
+        inner.fault_injector = Some(injector);
+    }
+
+    /// Probe the peer with heartbeat messages, emitting Unresponsive when
+    /// they go unanswered
+    ///
+    /// Replaces any heartbeat already running. The peer's application must
+    /// answer probes; any data received within the response window counts.
+    pub async fn start_heartbeat(&self, heartbeat: Heartbeat) -> Result<()> {
+        if heartbeat.interval.is_zero() {
+            return Err(TransportServicesError::InvalidParameters(
+                "Heartbeat interval must be positive".to_string(),
+            ));
+        }
+        let mut inner = self.inner.write().await;
+        if inner.state == ConnectionState::Closed {
+            return Err(TransportServicesError::InvalidState(
+                "Cannot probe a closed connection".to_string(),
+            ));
+        }
+        let heartbeat = Arc::new(heartbeat);
+        let active = Arc::downgrade(&heartbeat);
+        inner.heartbeat = Some(heartbeat);
+        drop(inner);
+
+        let member = self.group_member();
+        runtime::spawn(async move {
+            let mut missed = 0;
+            while let Some(interval) = active.upgrade().map(|h| h.interval) {
+                runtime::sleep(interval).await;
+
+                // Held only while probing, so the heartbeat does not keep a
+                // dropped connection alive
+                let Some(connection) = Connection::from_group_member(&member) else {
+                    break;
+                };
+                let Some(heartbeat) = active.upgrade() else {
+                    break;
+                };
+                let inner = connection.inner.read().await;
+                if inner.state == ConnectionState::Closed {
+                    break;
+                }
+                let received = inner.bytes_received;
+                let established = inner.state == ConnectionState::Established;
                 drop(inner);
+                if !established {
+                    continue;
+                }
+                if connection.send(heartbeat.probe.clone()).await.is_err() {
+                    break;
+                }
+                let (window, limit) = (heartbeat.response_window, heartbeat.missed_probes);
+                drop((connection, heartbeat));
 
-                // Update the original connection to be part of the group if it wasn't already
-                if was_not_grouped {
-                    let mut inner_mut = self.inner.write().await;
-                    inner_mut.connection_group = Some(Arc::clone(&group));
-                    drop(inner_mut);
-                    // Add the original connection to the group
-                    group.add_connection();
-                    // Register this connection with the group
-                    group.register_connection(Arc::downgrade(&self.inner)).await;
+                runtime::sleep(window).await;
+                let Some(shared) = member.inner.upgrade() else {
+                    break;
+                };
+                let inner = shared.read().await;
+                if active.strong_count() == 0 || inner.state == ConnectionState::Closed {
+                    break;
                 }
+                if inner.bytes_received > received {
+                    missed = 0;
+                    continue;
+                }
+                missed += 1;
+                // Reported once per silence, when it reaches the limit
+                if missed == limit {
+                    let _ = member.event_sender.send(ConnectionEvent::Unresponsive {
+                        missed_probes: missed,
+                    });
+                }
+            }
+        });
+        Ok(())
+    }
 
-                // Create a new connection in the same group
-                let new_conn = preconn.initiate().await?;
+    /// Stop probing the peer
+    pub async fn stop_heartbeat(&self) {
+        self.inner.write().await.heartbeat = None;
+    }
 
-                // Set the connection group on the new connection
+    /// Emulate the given link conditions for data sent on this connection
+    pub async fn set_network_conditions(&self, conditions: NetworkConditions) {
+        let connection = Arc::downgrade(&self.inner);
+        let event_sender = self.event_sender.clone();
+
+        let shaper = TrafficShaper::new(conditions, move |unit| {
+            let connection = connection.clone();
+            let event_sender = event_sender.clone();
+            Box::pin(async move {
+                let Some(connection) = connection.upgrade() else {
+                    return;
+                };
+                let mut inner = connection.write().await;
+                let result = if let Some(stream) = inner.tcp_stream.as_mut() {
+                    match stream.write_all(&unit).await {
+                        Ok(()) => stream.flush().await,
+                        Err(e) => Err(e),
+                    }
+                } else if let (Some(socket), Some(peer)) =
+                    (inner.udp_socket.clone(), inner.remote_socket_addr())
                 {
-                    let mut new_inner = new_conn.inner.write().await;
-                    new_inner.connection_group = Some(Arc::clone(&group));
+                    socket.send_to(&unit, peer).await.map(|_| ())
+                } else {
+                    Ok(())
+                };
 
-                    // Share transport properties from the group
-                    let shared_props = group.transport_properties.read().await;
-                    new_inner.transport_properties = shared_props.clone();
+                if let Err(e) = result {
+                    let _ = event_sender.send(ConnectionEvent::SoftError(format!(
+                        "Shaped delivery failed: {e}"
+                    )));
                 }
+            })
+        });
 
-                // Increment connection count for the new connection
-                group.add_connection();
-                // Register the new connection with the group
-                group
-                    .register_connection(Arc::downgrade(&new_conn.inner))
-                    .await;
+        let mut inner = self.inner.write().await;
+        inner.shaper = Some(Arc::new(shaper));
+    }
 
-                Ok(new_conn)
+    /// Get the link conditions emulated for this connection, if any
+    pub async fn network_conditions(&self) -> Option<NetworkConditions> {
+        let inner = self.inner.read().await;
+        inner
+            .shaper
+            .as_ref()
+            .map(|shaper| shaper.conditions().clone())
+    }
+
+    /// Get a snapshot of the connection's transport statistics
+    ///
+    /// Throughput is only measured between the periodic Stats events and is
+    /// zero here.
+    pub async fn stats(&self) -> ConnectionStats {
+        let inner = self.inner.read().await;
+        inner.stats()
+    }
+
+    /// Bytes this connection holds, counted against the memory budget
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.memory.usage()
+    }
+
+    /// Get the current state of the connection
+    pub async fn state(&self) -> ConnectionState {
+        let inner = self.inner.read().await;
+        inner.state
+    }
+
+    /// Subscribe to changes of the connection state
+    ///
+    /// The receiver starts at the current state and sees every later change.
+    pub async fn state_watch(&self) -> watch::Receiver<ConnectionState> {
+        let inner = self.inner.read().await;
+        inner.state_sender.subscribe()
+    }
+
+    /// Wait until the connection is established, failing with `Timeout` after `timeout`
+    ///
+    /// Fails if the connection closes before it is established.
+    pub async fn wait_for_established(&self, timeout: Option<Duration>) -> Result<()> {
+        let state = self
+            .wait_for_state(timeout, |state| state != ConnectionState::Establishing)
+            .await?;
+        match state {
+            ConnectionState::Established => Ok(()),
+            _ => Err(TransportServicesError::EstablishmentFailed(
+                "Connection closed before it was established".to_string(),
+            )),
+        }
+    }
+
+    /// Wait until the connection is closed, failing with `Timeout` after `timeout`
+    pub async fn wait_for_closed(&self, timeout: Option<Duration>) -> Result<()> {
+        self.wait_for_state(timeout, |state| state == ConnectionState::Closed)
+            .await
+            .map(|_| ())
+    }
+
+    async fn wait_for_state(
+        &self,
+        timeout: Option<Duration>,
+        done: impl Fn(ConnectionState) -> bool,
+    ) -> Result<ConnectionState> {
+        let mut watch = self.state_watch().await;
+        let wait = async move {
+            // The sender lives as long as the connection, which this handle keeps alive
+            watch
+                .wait_for(|state| done(*state))
+                .await
+                .map(|state| *state)
+                .map_err(|_| {
+                    TransportServicesError::InvalidState("Connection was dropped".to_string())
+                })
+        };
+        match timeout {
+            Some(duration) => runtime::timeout(duration, wait)
+                .await
+                .map_err(|_| TransportServicesError::Timeout)?,
+            None => wait.await,
+        }
+    }
+
+    /// Send a message on the connection
+    /// RFC Section 9.2
+    ///
+    /// Accepts anything convertible to a Message, such as `&str`, `Vec<u8>`,
+    /// `Bytes` or `(data, MessageProperties)`. Once the send queue holds the
+    /// high watermark set by `TransportPropertiesBuilder::send_watermarks`,
+    /// this waits until it drains below the low watermark.
+    pub async fn send(&self, message: impl Into<Message>) -> Result<()> {
+        let (message, queue, watermarks) = self.prepare_send(message.into()).await;
+        if let Some((high, low)) = watermarks {
+            if queue.queued() >= high {
+                queue.drain_below(low).await;
+            }
+        }
+        let _in_flight = queue.enter(message.len());
+        self.send_queued(message).await
+    }
+
+    /// Send a message unless the send queue holds its high watermark, failing
+    /// with `WouldBlock` instead of waiting for it to drain
+    ///
+    /// Otherwise the same as `send`, including waiting for the write.
+    pub async fn try_send(&self, message: impl Into<Message>) -> Result<()> {
+        let (message, queue, watermarks) = self.prepare_send(message.into()).await;
+        if watermarks.is_some_and(|(high, _)| queue.queued() >= high) {
+            return Err(TransportServicesError::WouldBlock);
+        }
+        let _in_flight = queue.enter(message.len());
+        self.send_queued(message).await
+    }
+
+    /// Bytes currently queued for sending: held until establishment, the peer
+    /// or a bundle completes, or in sends waiting their turn or writing
+    pub async fn send_queue_len(&self) -> usize {
+        self.inner.read().await.send_queue.queued()
+    }
+
+    /// Assign the message its ID, if not already set, and get the send queue
+    /// with its watermarks, unless batching, as a batch is only sent once the
+    /// caller ends it
+    async fn prepare_send(
+        &self,
+        mut message: Message,
+    ) -> (Message, Arc<SendQueue>, Option<(usize, usize)>) {
+        let inner = self.inner.read().await;
+        if message.id().is_none() {
+            let id = inner.next_message_id.fetch_add(1, Ordering::SeqCst);
+            message = message.with_id(id);
+        }
+        let watermarks = inner.send_watermarks().filter(|_| !inner.batch_mode);
+        (message, Arc::clone(&inner.send_queue), watermarks)
+    }
+
+    async fn send_queued(&self, message: Message) -> Result<()> {
+        // Check if message has expired
+        if let Some(context) = message.send_context() {
+            if let Some(expiry) = context.expiry {
+                if Instant::now() >= expiry {
+                    // Notify about expiration
+                    let _ = self.event_sender.send(ConnectionEvent::Expired {
+                        message_id: message.id(),
+                    });
+                    return Err(TransportServicesError::MessageExpired);
+                }
+            }
+        }
+
+        let _turn = self.send_turn().await;
+        let mut inner = self.inner.write().await;
+
+        inner.check_message_reliability(&message)?;
+        inner.check_message_ordering(&message)?;
+
+        match inner.state {
+            ConnectionState::Established if inner.awaiting_peer_data => {
+                // Hold the message until the peer has spoken first
+                inner.pending_messages.push(message);
+                inner.account_memory();
+                Ok(())
+            }
+            ConnectionState::Established => {
+                if inner.batch_mode || is_bundled(&message) {
+                    // Add to batch, or hold until the bundle is complete
+                    inner.batched_messages.push(message);
+                    inner.account_memory();
+                    Ok(())
+                } else if !inner.batched_messages.is_empty() {
+                    // This message completes a bundle
+                    let mut messages = inner.batched_messages.drain(..).collect::<Vec<_>>();
+                    messages.push(message);
+                    inner.account_memory();
+                    drop(inner);
+                    self.send_bundles(messages).await
+                } else {
+                    // Send immediately
+                    drop(inner);
+                    self.send_message_internal(message).await
+                }
+            }
+            ConnectionState::Establishing => {
+                // Queue message for sending after establishment
+                inner.pending_messages.push(message);
+                inner.account_memory();
+                Ok(())
             }
             _ => Err(TransportServicesError::InvalidState(
-                "Can only clone established connections".to_string(),
+                "Cannot send on a closed connection".to_string(),
             )),
         }
     }
 
-    /// Add a remote endpoint to the connection
-    /// RFC Section 7.5
-    pub async fn add_remote(&self, endpoint: RemoteEndpoint) -> Result<()> {
-        let mut inner = self.inner.write().await;
+    /// Wait for this caller's turn to send
+    ///
+    /// Sends, batch flushes and the establishment flush hold the turn while
+    /// they queue or write, so messages reach the transport in call order,
+    /// including across the Establishing to Established transition.
+    async fn send_turn(&self) -> OwnedMutexGuard<()> {
+        let send_order = Arc::clone(&self.inner.read().await.send_order);
+        send_order.lock_owned().await
+    }
+
+    /// Wait for this connection's turn to send when its group schedules
+    /// members' sends by proportional rate
+    async fn scheduling_grant(&self, messages: &[Message]) -> Option<SendGrant> {
+        let (scheduler, weight) = {
+            let inner = self.inner.read().await;
+            let group = inner.connection_group.as_ref()?;
+            match inner.properties.get("connScheduler") {
+                Some(ConnectionProperty::ConnScheduler(SchedulerType::ProportionalRate)) => {}
+                _ => return None,
+            }
+            (Arc::clone(&group.scheduler), inner.scheduling_weight)
+        };
+        let bytes = messages.iter().map(Message::len).sum();
+        let member = Arc::as_ptr(&self.inner) as usize;
+        Some(scheduler.admit(member, weight, bytes).await)
+    }
+
+    pub(crate) async fn set_scheduling_weight(&self, weight: u32) {
+        self.inner.write().await.scheduling_weight = weight;
+    }
+
+    pub(crate) async fn scheduling_weight(&self) -> u32 {
+        self.inner.read().await.scheduling_weight
+    }
+
+    /// Internal method to actually send a message
+    async fn send_message_internal(&self, message: Message) -> Result<()> {
+        self.send_messages_internal(vec![message]).await
+    }
+
+    /// Send messages grouped by their bundle flag
+    ///
+    /// Consecutive bundled messages and the message that follows them are
+    /// written together.
+    async fn send_bundles(&self, messages: Vec<Message>) -> Result<()> {
+        // Without framers each message is a datagram of its own however they
+        // are grouped, so all are handed over at once for segmentation offload
+        let unframed_datagrams = {
+            let inner = self.inner.read().await;
+            inner.udp_socket.is_some() && inner.framers.is_empty()
+        };
+        if unframed_datagrams {
+            return self.send_messages_internal(messages).await;
+        }
+
+        let mut group = Vec::new();
+        for message in messages {
+            let bundled = is_bundled(&message);
+            group.push(message);
+            if !bundled {
+                self.send_messages_internal(std::mem::take(&mut group))
+                    .await?;
+            }
+        }
+        if !group.is_empty() {
+            self.send_messages_internal(group).await?;
+        }
+        Ok(())
+    }
+
+    /// Run a write, reporting it as stalled by the peer's flow control once
+    /// it has made no progress for `threshold` while the peer still
+    /// acknowledges data
+    ///
+    /// A peer that stops acknowledging is a dead path rather than a slow
+    /// consumer, and is left to sendTimeout and the transport's own timeouts.
+    async fn watch_stall<F: std::future::Future>(
+        &self,
+        threshold: Option<Duration>,
+        peer_acking: impl Fn() -> bool,
+        notify: bool,
+        write: F,
+    ) -> F::Output {
+        let Some(threshold) = threshold else {
+            return write.await;
+        };
+        let mut write = std::pin::pin!(write);
+        let mut written = self.send_stall.written.load(Ordering::Relaxed);
+        let mut idle_since = Instant::now();
+        loop {
+            let tick = runtime::sleep(threshold / 4);
+            if let futures::future::Either::Left((output, _)) =
+                futures::future::select(write.as_mut(), std::pin::pin!(tick)).await
+            {
+                return output;
+            }
+
+            let now_written = self.send_stall.written.load(Ordering::Relaxed);
+            if now_written != written {
+                written = now_written;
+                idle_since = Instant::now();
+                self.send_stall.set(None);
+            } else if idle_since.elapsed() >= threshold
+                && self.send_stall.duration().is_none()
+                && peer_acking()
+            {
+                self.send_stall.set(Some(idle_since));
+                if notify {
+                    let _ = self.event_sender.send(ConnectionEvent::SoftError(format!(
+                        "Sends stalled by the peer's flow control for {:?}",
+                        idle_since.elapsed()
+                    )));
+                }
+            }
+        }
+    }
+
+    /// Internal method to actually send messages as a single write
+    async fn send_messages_internal(&self, messages: Vec<Message>) -> Result<()> {
+        let _grant = self.scheduling_grant(&messages).await;
+        let mut inner = self.inner.write().await;
+        let bytes = messages.iter().map(Message::len).sum();
+        if let Some(wait) = inner.send_delay(bytes) {
+            drop(inner);
+            runtime::sleep(wait).await;
+            inner = self.inner.write().await;
+        }
+
+        if inner.injected_reset() {
+            inner.reset_transport();
+            drop(inner);
+            return Err(self.report_injected_reset(&messages));
+        }
+
+        // Check if this is a Final message
+        if messages.iter().any(|m| m.properties().final_message) {
+            inner.final_message_sent = true;
+        }
+
+        // Frame the messages if framers are available
+        let mut segments_to_send = Vec::new();
+        // Where each message ends in the written bytes, while that is known
+        let mut message_ends = Some(Vec::with_capacity(messages.len()));
+        let mut framed_length = 0;
+        for message in &messages {
+            let segments = if !inner.framers.is_empty() {
+                // Framers see the ordering the message is sent with
+                let context = MessageContext::new().with_ordered(inner.message_ordered(message));
+                inner.framers.frame_segments(message, &context).await?
+            } else {
+                message.segments()
+            };
+            framed_length += segments.iter().map(|segment| segment.len()).sum::<usize>();
+            if let Some(ends) = message_ends.as_mut() {
+                ends.push(framed_length);
+            }
+            segments_to_send.extend(segments);
+        }
+        let message_ids = messages.iter().map(|m| m.id()).collect::<Vec<_>>();
+
+        // On a secured connection everything below handles TLS records
+        #[cfg(feature = "tls")]
+        if let Some(tls) = inner.tls.as_mut() {
+            segments_to_send = vec![Bytes::from(tls.encrypt(&segments_to_send.concat())?)];
+            message_ends = None;
+        }
+
+        if let (Some(injector), true) = (inner.fault_injector.clone(), inner.tcp_stream.is_some()) {
+            // Faults apply to the write as a whole; the lock is released while delayed
+            message_ends = None;
+            drop(inner);
+            let units = injector
+                .perturb(FaultDirection::Send, segments_to_send.concat())
+                .await;
+            segments_to_send = units.into_iter().map(Bytes::from).collect();
+            inner = self.inner.write().await;
+        }
+
+        if let (Some(shaper), true) = (inner.shaper.clone(), inner.tcp_stream.is_some()) {
+            // The shaper's delay queue writes to the stream; streams never lose data
+            let data = segments_to_send.concat();
+            inner.bytes_sent += data.len() as u64;
+            let send_started = Instant::now();
+            shaper.enqueue(data, true);
+            for message_id in message_ids {
+                let _ = self.event_sender.send_stamped(
+                    ConnectionEvent::Sent { message_id },
+                    send_timestamps(send_started, None),
+                );
+            }
+            return Ok(());
+        }
+
+        let deadline = inner.send_deadline(&messages);
+        let stall_threshold = inner.send_stall_threshold();
+        let notify = inner.soft_error_notify();
+        let length: usize = segments_to_send.iter().map(|segment| segment.len()).sum();
+        if let Some(ref mut stream) = inner.tcp_stream {
+            let event_sender = self.event_sender.clone();
+            let peer_acking = ack_probe(stream);
+            let written_before = self.send_stall.written.load(Ordering::Relaxed);
+            let send_started = Instant::now();
+
+            // Send the messages with one vectored write
+            let write = async {
+                write_segments(stream, segments_to_send, &self.send_stall.written)
+                    .await
+                    .map_err(|e| ("send", e))?;
+                stream.flush().await.map_err(|e| ("flush", e))
+            };
+            let write = self.watch_stall(stall_threshold, peer_acking, notify, write);
+            let result = match deadline {
+                Some(deadline) => runtime::timeout(deadline, write).await.unwrap_or_else(|_| {
+                    Err((
+                        "send",
+                        std::io::Error::new(
+                            std::io::ErrorKind::TimedOut,
+                            format!("Send stalled for {deadline:?}"),
+                        ),
+                    ))
+                }),
+                None => write.await,
+            };
+            self.send_stall.set(None);
+            let send_completed = Instant::now();
+
+            match result {
+                Ok(_) => {
+                    inner.bytes_sent += length as u64;
+                    // Notify successful send
+                    for message_id in message_ids {
+                        let _ = event_sender.send_stamped(
+                            ConnectionEvent::Sent { message_id },
+                            send_timestamps(send_started, Some(send_completed)),
+                        );
+                    }
+                    Ok(())
+                }
+                Err((stage, e)) => {
+                    let written =
+                        (self.send_stall.written.load(Ordering::Relaxed) - written_before) as usize;
+                    inner.bytes_sent += written as u64;
+                    let error_msg = e.to_string();
+                    report_partial_send(
+                        &event_sender,
+                        send_timestamps(send_started, Some(send_completed)),
+                        &message_ids,
+                        message_ends.as_deref(),
+                        written,
+                        length,
+                        &error_msg,
+                    );
+
+                    // Part of the data may already be on the wire, and the
+                    // stream has failed, so it cannot carry further messages;
+                    // later sends fail on the closed connection
+                    inner.reset_transport();
+                    let _ = event_sender.send(ConnectionEvent::ConnectionError(format!(
+                        "Connection failed during {stage} after writing {written} of {length} bytes: {error_msg}"
+                    )));
+
+                    if e.kind() == std::io::ErrorKind::TimedOut {
+                        return Err(TransportServicesError::Timeout);
+                    }
+                    Err(TransportServicesError::SendFailed(error_msg))
+                }
+            }
+        } else if let Some(socket) = inner.udp_socket.clone() {
+            let peer = inner.remote_socket_addr().ok_or_else(|| {
+                TransportServicesError::InvalidState("No remote address".to_string())
+            })?;
+
+            // A message's capacity profile sets the traffic class of its
+            // datagram, and its hop limit overrides the connection's; a bundle
+            // takes those of its first message with one
+            let hop_limit = inner.hop_limit();
+            let source = inner.reply_source;
+            let marks = |messages: &[Message]| DatagramMarks {
+                dscp: messages
+                    .iter()
+                    .find_map(|m| m.properties().capacity_profile)
+                    .map(message_capacity_profile_dscp),
+                hop_limit: messages
+                    .iter()
+                    .find_map(|m| m.properties().hop_limit)
+                    .or(hop_limit),
+                source,
+            };
+            // Bundled messages share a datagram only when a framer delimits them
+            let datagrams = if inner.framers.is_empty() {
+                messages
+                    .iter()
+                    .map(|m| {
+                        let marks = marks(std::slice::from_ref(m));
+                        (vec![m.id()], m.segments().concat(), marks)
+                    })
+                    .collect::<Vec<_>>()
+            } else {
+                vec![(message_ids, segments_to_send.concat(), marks(&messages))]
+            };
+            let injector = inner.fault_injector.clone();
+            let shaper = inner.shaper.clone();
+            // Perturbed and shaped datagrams are handed over one by one
+            let groups = if injector.is_none() && shaper.is_none() && inner.udp_offload() {
+                udp_offload::group(datagrams, |(_, data, _)| data.len(), |(_, _, marks)| *marks)
+            } else {
+                datagrams
+                    .into_iter()
+                    .map(|datagram| vec![datagram])
+                    .collect()
+            };
+            drop(inner);
+
+            for group in groups {
+                if group.len() > 1 && group[0].2.is_unmarked() {
+                    let buffer = group
+                        .iter()
+                        .map(|(_, data, _)| data.as_slice())
+                        .collect::<Vec<_>>()
+                        .concat();
+                    let segment_size = group[0].1.len();
+                    let send_started = Instant::now();
+                    match udp_offload::send_segmented(&socket, &buffer, segment_size, peer).await {
+                        Err(e) if udp_offload::is_unsupported(&e) => {
+                            log::debug!(
+                                "UDP segmentation offload unavailable, sending singly: {e}"
+                            );
+                            self.inner.write().await.udp_offload_failed = true;
+                        }
+                        result => {
+                            let sent = group
+                                .into_iter()
+                                .map(|(message_ids, data, _)| (message_ids, data.len()))
+                                .collect();
+                            self.report_datagrams(sent, 1, send_started, result).await?;
+                            continue;
+                        }
+                    }
+                }
+
+                for (message_ids, datagram, marks) in group {
+                    let length = datagram.len();
+                    let send_started = Instant::now();
+                    let units = match &injector {
+                        Some(injector) => injector.perturb(FaultDirection::Send, datagram).await,
+                        None => vec![datagram],
+                    };
+                    let mut calls = 0;
+                    let mut result = Ok(0);
+                    for unit in units {
+                        result = match &shaper {
+                            // Lost datagrams count as sent, as on a real link
+                            Some(shaper) => {
+                                shaper.enqueue(unit, false);
+                                Ok(0)
+                            }
+                            None if marks.is_unmarked() => {
+                                send_datagram(&socket, &unit, peer).await
+                            }
+                            None => send_to_marked(&socket, &unit, peer, marks).await,
+                        };
+                        calls += u64::from(shaper.is_none());
+                        if result.is_err() {
+                            break;
+                        }
+                    }
+                    self.report_datagrams(vec![(message_ids, length)], calls, send_started, result)
+                        .await?;
+                }
+            }
+            Ok(())
+        } else {
+            Err(TransportServicesError::InvalidState(
+                "No active stream".to_string(),
+            ))
+        }
+    }
+
+    /// Account for datagrams handed to the transport in `calls` system calls
+    /// and notify the application of their messages' fate
+    async fn report_datagrams(
+        &self,
+        datagrams: Vec<(Vec<Option<u64>>, usize)>,
+        calls: u64,
+        send_started: Instant,
+        result: std::io::Result<usize>,
+    ) -> Result<()> {
+        let send_completed = Instant::now();
+        match result {
+            Ok(_) => {
+                let mut inner = self.inner.write().await;
+                inner.bytes_sent += datagrams
+                    .iter()
+                    .map(|(_, length)| *length as u64)
+                    .sum::<u64>();
+                inner.datagrams_sent += datagrams.len() as u64;
+                inner.datagram_send_calls += calls;
+                inner.last_traffic = Instant::now();
+                drop(inner);
+                for message_id in datagrams.into_iter().flat_map(|(ids, _)| ids) {
+                    let _ = self.event_sender.send_stamped(
+                        ConnectionEvent::Sent { message_id },
+                        send_timestamps(send_started, Some(send_completed)),
+                    );
+                }
+                Ok(())
+            }
+            Err(e) => {
+                let error_msg = e.to_string();
+                for message_id in datagrams.into_iter().flat_map(|(ids, _)| ids) {
+                    let _ = self.event_sender.send(ConnectionEvent::SendError {
+                        message_id,
+                        error: error_msg.clone(),
+                    });
+                }
+                Err(TransportServicesError::SendFailed(error_msg))
+            }
+        }
+    }
+
+    /// Report a reset requested by the fault injector while sending
+    fn report_injected_reset(&self, messages: &[Message]) -> TransportServicesError {
+        for message in messages {
+            let _ = self.event_sender.send(ConnectionEvent::SendError {
+                message_id: message.id(),
+                error: INJECTED_RESET.to_string(),
+            });
+        }
+        let _ = self
+            .event_sender
+            .send(ConnectionEvent::ConnectionError(INJECTED_RESET.to_string()));
+        TransportServicesError::ConnectionFailed(INJECTED_RESET.to_string())
+    }
+
+    /// Start a batch of messages scoped to the returned Batch
+    /// RFC Section 9.2.4
+    ///
+    /// Unlike start_batch()/end_batch(), which switch the whole connection into
+    /// batching mode, each Batch collects its own messages, so concurrent
+    /// users cannot mix their messages into each other's batches.
+    pub fn batch(&self) -> Batch {
+        Batch {
+            connection: self.clone(),
+            messages: Vec::new(),
+        }
+    }
+
+    /// Send the messages of a committed Batch as one unit
+    ///
+    /// Either every message is accepted or none is: an expired message or
+    /// one the protocol stack cannot honour fails the whole batch.
+    async fn send_batch(&self, messages: Vec<Message>) -> Result<()> {
+        let mut batch = Vec::with_capacity(messages.len());
+        for message in messages {
+            let message = match message.id() {
+                Some(_) => message,
+                None => {
+                    let id = self.get_next_message_id().await;
+                    message.with_id(id)
+                }
+            };
+            let expired = message
+                .send_context()
+                .and_then(|context| context.expiry)
+                .is_some_and(|expiry| Instant::now() >= expiry);
+            if expired {
+                let _ = self.event_sender.send(ConnectionEvent::Expired {
+                    message_id: message.id(),
+                });
+                return Err(TransportServicesError::MessageExpired);
+            }
+            batch.push(message);
+        }
+
+        let _turn = self.send_turn().await;
+        let mut inner = self.inner.write().await;
+
+        for message in &batch {
+            inner.check_message_reliability(message)?;
+            inner.check_message_ordering(message)?;
+        }
+
+        match inner.state {
+            ConnectionState::Established if inner.awaiting_peer_data => {
+                inner.pending_messages.extend(batch);
+                inner.account_memory();
+                Ok(())
+            }
+            ConnectionState::Established if inner.batch_mode => {
+                inner.batched_messages.extend(batch);
+                inner.account_memory();
+                Ok(())
+            }
+            ConnectionState::Established => {
+                // Complete any bundle waiting for its last message
+                let mut messages = inner.batched_messages.drain(..).collect::<Vec<_>>();
+                messages.extend(batch);
+                inner.account_memory();
+                drop(inner);
+                self.send_bundles(messages).await
+            }
+            ConnectionState::Establishing => {
+                inner.pending_messages.extend(batch);
+                inner.account_memory();
+                Ok(())
+            }
+            _ => Err(TransportServicesError::InvalidState(
+                "Cannot send on a closed connection".to_string(),
+            )),
+        }
+    }
+
+    /// Start batching messages
+    /// RFC Section 9.2.4
+    pub async fn start_batch(&self) -> Result<()> {
+        let mut inner = self.inner.write().await;
+        inner.batch_mode = true;
+        Ok(())
+    }
+
+    /// End batching and send all batched messages
+    /// RFC Section 9.2.4
+    pub async fn end_batch(&self) -> Result<()> {
+        let _turn = self.send_turn().await;
+        let mut inner = self.inner.write().await;
+        inner.batch_mode = false;
+        let messages = inner.batched_messages.drain(..).collect::<Vec<_>>();
+        inner.account_memory();
+        drop(inner);
+
+        // Send all batched messages, coalescing bundles
+        self.send_bundles(messages).await
+    }
+
+    /// Get the next message ID
+    async fn get_next_message_id(&self) -> u64 {
+        let inner = self.inner.read().await;
+        inner.next_message_id.fetch_add(1, Ordering::SeqCst)
+    }
+
+    /// Use length-prefix framer for messages
+    pub async fn use_length_prefix_framer(&self) -> Result<()> {
+        use crate::LengthPrefixFramer;
+        self.add_framer(Box::new(LengthPrefixFramer::new())).await
+    }
+
+    /// Replace the framers, e.g. with those instantiated from the Preconnection
+    pub(crate) async fn set_framers(&self, framers: FramerStack) -> Result<()> {
+        framers.on_attach().await?;
+        self.inner.write().await.framers = framers;
+        Ok(())
+    }
+
+    /// Add a framer inside those already on the connection
+    /// RFC Section 9.1.2
+    ///
+    /// The first framer added is outermost: it frames last on send and parses
+    /// first on receive.
+    pub async fn add_framer(&self, framer: Box<dyn Framer>) -> Result<()> {
+        // Messages being framed finish under the old stack
+        let _turn = self.send_turn().await;
+        framer.on_attach().await?;
+        let mut inner = self.inner.write().await;
+        inner.framers.add_framer(framer);
+        Ok(())
+    }
+
+    /// Replace the connection's framers, e.g. to upgrade the protocol after
+    /// negotiating it
+    ///
+    /// Sends and receives are quiesced while the stack is swapped: messages
+    /// already sent were framed by the old stack, and bytes received but not
+    /// yet parsed are parsed again by the new one. Messages already decoded
+    /// are still delivered. Fails if a message is partly received, as the
+    /// rest of it belongs to the old framing.
+    pub async fn replace_framers(&self, framers: FramerStack) -> Result<()> {
+        let _turn = self.send_turn().await;
+        let mut inner = self.inner.write().await;
+        if inner.reassembly.in_progress() {
+            return Err(TransportServicesError::InvalidState(
+                "Cannot replace framers while a message is partly received".to_string(),
+            ));
+        }
+        framers.on_attach().await?;
+
+        let old = std::mem::replace(&mut inner.framers, framers);
+        let mut unparsed = old.take_unparsed();
+        unparsed.append(&mut inner.receive_buffer);
+        inner.receive_buffer = unparsed;
+        if let Err(e) = old.on_detach().await {
+            log::debug!("Replaced framer failed to detach: {e}");
+        }
+
+        // Bytes that arrived under the old framing are delivered now rather
+        // than when more data arrives
+        if inner.tcp_stream.is_some() && !inner.receive_buffer.is_empty() {
+            inner.deliver_buffered(&self.event_sender).await;
+        }
+        Ok(())
+    }
+
+    /// Secure an established cleartext connection with TLS, e.g. after the
+    /// application has negotiated STARTTLS with the peer
+    ///
+    /// The handshake runs in-band on the TCP stream; once it completes, sends
+    /// and receives go through the TLS session and the securityProtocol and
+    /// securityAlpn properties report what was negotiated. The server's
+    /// certificate is verified against `pinned_server_certificate` and the
+    /// Remote Endpoint's host name, or its address if it has none. A failed
+    /// handshake leaves nothing usable on the stream, so the connection is
+    /// reset.
+    pub async fn start_security(&self, parameters: SecurityParameters) -> Result<()> {
+        #[cfg(feature = "tls")]
+        {
+            use crate::tls::{TlsServer, TlsSession};
+
+            // Messages already being written stay in cleartext
+            let _turn = self.send_turn().await;
+            let mut inner = self.inner.write().await;
+            if inner.state != ConnectionState::Established {
+                return Err(TransportServicesError::InvalidState(
+                    "Security can only be started on an established connection".to_string(),
+                ));
+            }
+            if inner.tls.is_some() {
+                return Err(TransportServicesError::InvalidState(
+                    "Security is already started on this connection".to_string(),
+                ));
+            }
+            let Some(peer) = inner.tcp_stream.as_ref().and_then(|s| s.peer_addr().ok()) else {
+                return Err(TransportServicesError::NotSupported(
+                    "Security can only be started on a TCP connection".to_string(),
+                ));
+            };
+
+            // Aliases of the endpoint connected to share its host name
+            let host_name = match &inner.remote_endpoint {
+                Some(remote) => inner.preconnection.host_name(remote).await,
+                None => None,
+            };
+            let server = match host_name {
+                Some(name) => TlsServer::Name(name),
+                None => TlsServer::Address(peer.ip()),
+            };
+            let sessions = inner.preconnection.tls_sessions().await;
+            let mut session = TlsSession::client(&parameters, server, &sessions)?;
+
+            let timeout = inner
+                .transport_properties
+                .connection_properties
+                .connection_timeout
+                .unwrap_or(Duration::from_secs(30));
+            let stream = inner.tcp_stream.as_mut().expect("checked above");
+            let result = match runtime::timeout(timeout, session.handshake(stream)).await {
+                Ok(result) => result,
+                Err(_) => Err(TransportServicesError::Timeout),
+            };
+
+            match result {
+                Ok(received) => {
+                    inner.properties.update_security(
+                        session.protocol(),
+                        session.alpn(),
+                        session.server_name(),
+                    );
+                    inner.tls = Some(session);
+                    inner.receive_buffer.extend(received);
+                    if !inner.receive_buffer.is_empty() {
+                        inner.deliver_buffered(&self.event_sender).await;
+                    }
+                    Ok(())
+                }
+                Err(e) => {
+                    let error = format!("TLS handshake failed: {e}");
+                    inner.reset_transport();
+                    let _ = self
+                        .event_sender
+                        .send(ConnectionEvent::ConnectionError(error.clone()));
+                    Err(TransportServicesError::SecurityError(error))
+                }
+            }
+        }
+        #[cfg(not(feature = "tls"))]
+        {
+            let _ = parameters;
+            Err(TransportServicesError::NotSupported(
+                "Built without the tls feature".to_string(),
+            ))
+        }
+    }
+
+    /// Receive messages from the connection
+    /// RFC Section 9.3.1 - Enqueuing Receives
+    pub async fn receive(&self) -> Result<(Message, MessageContext)> {
+        self.receive_with_params(None, None).await
+    }
+
+    /// Receive messages with buffer management parameters
+    /// RFC Section 9.3.1 - Enqueuing Receives
+    ///
+    /// minIncompleteLength: Minimum number of bytes to deliver for a partial message
+    /// maxLength: Maximum number of bytes to accept for a single message; larger
+    /// framed messages are delivered in parts with end_of_message on the last
+    pub async fn receive_with_params(
+        &self,
+        min_incomplete_length: Option<usize>,
+        max_length: Option<usize>,
+    ) -> Result<(Message, MessageContext)> {
+        self.receive_before(min_incomplete_length, max_length, None)
+            .await
+    }
+
+    /// Receive a message, giving up with a Timeout error once `timeout` has
+    /// elapsed
+    ///
+    /// Data that arrived in the meantime stays buffered for the next receive.
+    pub async fn receive_timeout(&self, timeout: Duration) -> Result<(Message, MessageContext)> {
+        self.receive_before(None, None, Some(Instant::now() + timeout))
+            .await
+    }
+
+    /// Receive a message if one is ready, without waiting
+    ///
+    /// Returns None if no complete message is buffered and the data the
+    /// transport has ready does not complete one.
+    pub async fn try_receive(&self) -> Result<Option<(Message, MessageContext)>> {
+        match self.receive_before(None, None, Some(Instant::now())).await {
+            Ok(received) => Ok(Some(received)),
+            Err(TransportServicesError::Timeout) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Receive a message, reading from the transport until `deadline`
+    async fn receive_before(
+        &self,
+        min_incomplete_length: Option<usize>,
+        max_length: Option<usize>,
+        deadline: Option<Instant>,
+    ) -> Result<(Message, MessageContext)> {
+        let state = {
+            let inner = self.inner.read().await;
+            if inner.final_message_received {
+                return Err(TransportServicesError::InvalidState(
+                    "Final message already received".to_string(),
+                ));
+            }
+            if inner.udp_socket.is_some() {
+                return Err(TransportServicesError::NotSupported(
+                    "Datagram connections deliver messages through Received events".to_string(),
+                ));
+            }
+            inner.state
+        };
+
+        match state {
+            ConnectionState::Established | ConnectionState::Establishing => {
+                // Keep reading until we have a complete message
+                let mut buffer = [0u8; 8192];
+
+                loop {
+                    // Check if we have a message, or part of one, in the buffer already
+                    let delivery = {
+                        let mut inner = self.inner.write().await;
+
+                        let delivery = if !inner.framers.is_empty() {
+                            let delivery =
+                                match inner.next_framed(max_length, min_incomplete_length).await {
+                                    Ok(delivery) => delivery,
+                                    Err(e) => {
+                                        let _ =
+                                            self.event_sender.send(ConnectionEvent::ReceiveError {
+                                                error: e.to_string(),
+                                            });
+                                        return Err(TransportServicesError::ReceiveFailed(
+                                            e.to_string(),
+                                        ));
+                                    }
+                                };
+                            if let Some(error) = inner.report_oversized(&self.event_sender) {
+                                return Err(TransportServicesError::ReceiveFailed(error));
+                            }
+                            delivery
+                        } else if inner.receive_buffer.is_empty() {
+                            None
+                        } else {
+                            // No framers - return all buffered data as one message
+                            let message = Message::new(std::mem::take(&mut inner.receive_buffer));
+                            Some((message, inner.receive_context(), false))
+                        };
+                        inner.account_memory();
+                        delivery.map(|delivery| (delivery, inner.received_at))
+                    };
+
+                    if let Some(((message, context, partial), received_at)) = delivery {
+                        let context = context.with_final(message.properties().final_message);
+                        let event = receive_event(&message, &context, partial);
+                        self.memory.event_queued(&event);
+                        let _ = self
+                            .event_sender
+                            .send_stamped(event, receive_timestamps(received_at));
+
+                        if context.is_final() {
+                            self.handle_final_received().await;
+                        }
+
+                        return Ok((message, context));
+                    }
+
+                    // No complete message yet - read more data
+                    let read_result = {
+                        let inner = self.inner.write().await;
+                        if let Some(ref stream) = inner.tcp_stream {
+                            let read =
+                                read_timestamped(stream, &mut buffer, inner.receive_timestamps());
+                            match deadline {
+                                // The read is cancel safe, so nothing is lost
+                                Some(deadline) => runtime::timeout(
+                                    deadline.saturating_duration_since(Instant::now()),
+                                    read,
+                                )
+                                .await
+                                .map_err(|_| TransportServicesError::Timeout)?,
+                                None => read.await,
+                            }
+                        } else {
+                            return Err(TransportServicesError::InvalidState(
+                                "No active stream".to_string(),
+                            ));
+                        }
+                    };
+
+                    match read_result {
+                        Ok((0, _)) => {
+                            // Connection closed by peer; the FIN ends the peer's data
+                            self.handle_final_received().await;
+                            let mut inner = self.inner.write().await;
+                            inner.set_closed();
+                            let _ = self.event_sender.send(ConnectionEvent::Closed);
+                            return Err(TransportServicesError::ConnectionFailed(
+                                "Connection closed by peer".to_string(),
+                            ));
+                        }
+                        Ok((n, timestamp)) => {
+                            // Add data to receive buffer
+                            let data = receive_faults(&self.inner, &buffer[..n]).await;
+                            let mut inner = self.inner.write().await;
+                            inner.bytes_received += n as u64;
+                            inner.accept_received(data.concat(), &self.event_sender)?;
+                            inner.receive_timestamp = timestamp;
+                            drop(inner);
+                            // Failed writes have reported SendError
+                            let _ = self.release_held_messages().await;
+                            // Continue loop to try parsing again
+                        }
+                        Err(e) => {
+                            let error_msg = e.to_string();
+                            let _ = self.event_sender.send(ConnectionEvent::ReceiveError {
+                                error: error_msg.clone(),
+                            });
+
+                            // Check if this might be a soft error
+                            if error_msg.contains("broken pipe")
+                                || error_msg.contains("connection reset")
+                                || error_msg.contains("connection refused")
+                                || error_msg.contains("timed out")
+                            {
+                                self.emit_soft_error(format!(
+                                    "Network error during receive: {error_msg}"
+                                ))
+                                .await;
+                            }
+
+                            return Err(TransportServicesError::ReceiveFailed(error_msg));
+                        }
+                    }
+                }
+            }
+            _ => Err(TransportServicesError::InvalidState(
+                "Cannot receive on a closed connection".to_string(),
+            )),
+        }
+    }
+
+    /// Close the connection gracefully
+    /// RFC Section 10
+    pub async fn close(&self) -> Result<()> {
+        let mut inner = self.inner.write().await;
+
+        match inner.state {
+            ConnectionState::Established | ConnectionState::Establishing => {
+                inner.set_state(ConnectionState::Closing);
+                drop(inner);
+                self.finish_close().await;
+                Ok(())
+            }
+            ConnectionState::Closing => {
+                // Already closing, just wait
+                Ok(())
+            }
+            ConnectionState::Closed => Ok(()), // Already closed
+        }
+    }
+
+    /// Deliver outstanding data and close a connection already in Closing
+    async fn finish_close(&self) {
+        // Send any held, batched or bundled messages before closing
+        let batched_messages = {
+            let mut inner = self.inner.write().await;
+            let mut messages = Vec::new();
+            if std::mem::take(&mut inner.awaiting_peer_data) {
+                messages.append(&mut inner.pending_messages);
+            }
+            messages.append(&mut inner.batched_messages);
+            inner.account_memory();
+            messages
+        };
+
+        // Send any remaining batched messages (with timeout to avoid hanging)
+        if !batched_messages.is_empty() {
+            let _ = runtime::timeout(Duration::from_millis(100), async {
+                let _turn = self.send_turn().await;
+                self.send_bundles(batched_messages).await
+            })
+            .await;
+        }
+
+        // Let data held by the shaper reach the network first
+        let shaper = self.inner.read().await.shaper.clone();
+        if let Some(shaper) = shaper {
+            shaper.drained().await;
+        }
+
+        // Re-acquire lock to update state
+        let mut inner = self.inner.write().await;
+        if inner.state == ConnectionState::Closed {
+            // Aborted while outstanding data was delivered
+            return;
+        }
+
+        // Let the framers send their closing preambles before the FIN
+        let ConnectionInner {
+            framers,
+            tcp_stream,
+            #[cfg(feature = "tls")]
+            tls,
+            ..
+        } = &mut *inner;
+        if let (Some(stream), false) = (tcp_stream.as_mut(), framers.is_empty()) {
+            let handshake = FramerHandshake::new(stream, Vec::new());
+            #[cfg(feature = "tls")]
+            let handshake = handshake.with_tls(tls.as_mut());
+            let mut handshake = handshake;
+            match runtime::timeout(Duration::from_secs(1), framers.stop(&mut handshake)).await {
+                Ok(Err(e)) => log::debug!("Framer stop failed: {e}"),
+                Err(_) => log::debug!("Framer stop timed out"),
+                Ok(Ok(())) => {}
+            }
+        }
+
+        // End the TLS session before the FIN
+        #[cfg(feature = "tls")]
+        if let (Some(stream), Some(mut session)) = (tcp_stream.as_mut(), tls.take()) {
+            let alert = session.close_notify();
+            let _ = runtime::timeout(Duration::from_secs(1), stream.write_all(&alert)).await;
+        }
+
+        // Perform graceful close on TCP stream
+        if let Some(ref mut stream) = inner.tcp_stream {
+            // Try to flush any buffered data (ignore errors if connection is broken)
+            let _ = runtime::timeout(Duration::from_secs(1), stream.flush()).await;
+
+            // Try to shutdown the write side (ignore errors if connection is broken)
+            // This sends a TCP FIN packet
+            let _ = runtime::timeout(Duration::from_secs(1), stream.shutdown()).await;
+        }
+
+        inner.set_closed();
+
+        // Clear any remaining state
+        inner.pending_messages.clear();
+        inner.clear_receive_buffer();
+        inner.tcp_stream = None;
+        inner.udp_socket = None;
+
+        let _ = self.event_sender.send(ConnectionEvent::Closed);
+    }
+
+    /// Abort the connection immediately
+    /// RFC Section 10 - Connection Termination
+    ///
+    /// Unlike close(), abort() immediately terminates the connection without
+    /// attempting to deliver any outstanding data.
+    pub async fn abort(&self) -> Result<()> {
+        self.abort_internal("Connection aborted".to_string()).await
+    }
+
+    /// Abort the connection, signalling an application error code to the peer
+    /// RFC Section 10 - Connection Termination
+    ///
+    /// The code is carried by protocols that support it (e.g. QUIC CONNECTION_CLOSE);
+    /// for TCP the connection is reset and the code is only reported locally.
+    pub async fn abort_with_code(&self, code: u64, reason: impl Into<String>) -> Result<()> {
+        self.abort_internal(format!(
+            "Connection aborted with code {code}: {}",
+            reason.into()
+        ))
+        .await
+    }
+
+    pub(crate) async fn abort_internal(&self, error: String) -> Result<()> {
+        let mut inner = self.inner.write().await;
+
+        // Only proceed if we're not already closed
+        let was_not_closed = inner.state != ConnectionState::Closed;
+        if !was_not_closed {
+            return Ok(());
+        }
+
+        // Close immediately, resetting the transport and discarding queued data
+        inner.reset_transport();
+
+        // Send ConnectionError event for abort (as per RFC Section 10)
+        let _ = self
+            .event_sender
+            .send(ConnectionEvent::ConnectionError(error));
+
+        Ok(())
+    }
+
+    /// Clone the connection to create a new connection in the same group
+    /// RFC Section 7.4
+    pub async fn clone_connection(&self) -> Result<Connection> {
+        let inner = self.inner.read().await;
+
+        match inner.state {
+            ConnectionState::Established => {
+                // Get or create connection group
+                let (group, was_not_grouped) = if let Some(ref group) = inner.connection_group {
+                    (Arc::clone(group), false)
+                } else {
+                    // Create a new connection group for this connection
+                    let new_group = Arc::new(ConnectionGroup::new(
+                        inner.transport_properties.clone(),
+                        inner
+                            .local_endpoint
+                            .as_ref()
+                            .map(|e| vec![e.clone()])
+                            .unwrap_or_default(),
+                        inner
+                            .remote_endpoint
+                            .as_ref()
+                            .map(|e| vec![e.clone()])
+                            .unwrap_or_default(),
+                    ));
+                    (new_group, true)
+                };
+
+                // Get preconnection before dropping inner
+                let preconn = inner.preconnection.clone();
+                drop(inner);
+
+                // Update the original connection to be part of the group if it wasn't already
+                if was_not_grouped {
+                    let mut inner_mut = self.inner.write().await;
+                    inner_mut.connection_group = Some(Arc::clone(&group));
+                    drop(inner_mut);
+                    // Add the original connection to the group
+                    group.add_connection();
+                    // Register this connection with the group
+                    group.register_connection(self.group_member()).await;
+                }
+
+                // Create a new connection in the same group
+                let new_conn = preconn.initiate().await?;
+
+                // Set the connection group on the new connection
+                {
+                    let mut new_inner = new_conn.inner.write().await;
+                    new_inner.connection_group = Some(Arc::clone(&group));
+
+                    // Share transport properties from the group
+                    let shared_props = group.transport_properties.read().await;
+                    new_inner.transport_properties = shared_props.clone();
+
+                    // Increment connection count for the new connection, unless
+                    // establishment already failed before it joined the group
+                    if new_inner.state != ConnectionState::Closed {
+                        group.add_connection();
+                    }
+                }
+
+                // Register the new connection with the group
+                group.register_connection(new_conn.group_member()).await;
+
+                Ok(new_conn)
+            }
+            _ => Err(TransportServicesError::InvalidState(
+                "Can only clone established connections".to_string(),
+            )),
+        }
+    }
+
+    /// Add a remote endpoint to the connection
+    /// RFC Section 7.5
+    pub async fn add_remote(&self, endpoint: RemoteEndpoint) -> Result<()> {
+        let mut inner = self.inner.write().await;
+
+        match inner.state {
+            ConnectionState::Established | ConnectionState::Establishing => {
+                // For single-path TCP connections, we can only have one remote endpoint
+                // In a real implementation with multipath support (like MPTCP or QUIC),
+                // we would add this to a list of available endpoints
+
+                // Check if this is the same endpoint we already have
+                if let Some(ref current_remote) = inner.remote_endpoint {
+                    // Check if any identifiers match
+                    for new_id in &endpoint.identifiers {
+                        for existing_id in &current_remote.identifiers {
+                            if new_id == existing_id {
+                                // Endpoint already known, ignore as per RFC
+                                return Ok(());
+                            }
+                        }
+                    }
+                }
+
+                // For now, since we only support single-path TCP, we can only
+                // update the remote endpoint if we don't have an established connection yet
+                if inner.state == ConnectionState::Establishing && inner.tcp_stream.is_none() {
+                    // Update the remote endpoint for future connection attempts
+                    inner.remote_endpoint = Some(endpoint);
+                    drop(inner);
+
+                    // Emit PathChange event since endpoints changed
+                    self.emit_path_change().await;
+
+                    Ok(())
+                } else {
+                    // Log that we received the endpoint but can't use it with current transport
+                    // In a multipath implementation, we would:
+                    // 1. Store this endpoint in a list
+                    // 2. Potentially establish a new subflow to this endpoint
+                    // 3. Update routing tables
+
+                    // For now, we just acknowledge receipt but don't use it
+                    Ok(())
+                }
+            }
+            _ => Err(TransportServicesError::InvalidState(
+                "Cannot add endpoints to a closed connection".to_string(),
+            )),
+        }
+    }
+
+    /// Add a local endpoint to the connection
+    pub async fn add_local(&self, endpoint: LocalEndpoint) -> Result<()> {
+        let mut inner = self.inner.write().await;
+
+        match inner.state {
+            ConnectionState::Established | ConnectionState::Establishing => {
+                // For single-path TCP connections, we can only have one local endpoint
+                // In a real implementation with multipath support (like MPTCP or QUIC),
+                // we would add this to a list of available endpoints
+
+                // Check if this is the same endpoint we already have
+                if let Some(ref current_local) = inner.local_endpoint {
+                    // Check if any identifiers match
+                    for new_id in &endpoint.identifiers {
+                        for existing_id in &current_local.identifiers {
+                            if new_id == existing_id {
+                                // Endpoint already known, ignore
+                                return Ok(());
+                            }
+                        }
+                    }
+                }
+
+                // For now, since we only support single-path TCP, we can only
+                // update the local endpoint if we don't have an established connection yet
+                if inner.state == ConnectionState::Establishing && inner.tcp_stream.is_none() {
+                    // Update the local endpoint for future connection attempts
+                    inner.local_endpoint = Some(endpoint);
+                    drop(inner);
+
+                    // Emit PathChange event since endpoints changed
+                    self.emit_path_change().await;
+
+                    Ok(())
+                } else {
+                    // In a multipath implementation, we would:
+                    // 1. Store this endpoint in a list
+                    // 2. Potentially bind a new socket to this endpoint
+                    // 3. Use it for new subflows
+
+                    // For now, we just acknowledge receipt but don't use it
+                    Ok(())
+                }
+            }
+            _ => Err(TransportServicesError::InvalidState(
+                "Cannot add endpoints to a closed connection".to_string(),
+            )),
+        }
+    }
+
+    /// Take the given classes of events out of the queue `next_event` reads,
+    /// into buffers of their own holding up to 256 events per class
+    ///
+    /// For example `conn.events_filtered(EventClass::Data | EventClass::Lifecycle)`.
+    /// Events already queued stay there. An event of a class several
+    /// subscriptions take goes to each of them.
+    pub fn events_filtered(&self, classes: impl Into<EventClasses>) -> FilteredEvents {
+        self.events_filtered_with_capacity(classes, FILTERED_EVENT_CAPACITY)
+    }
+
+    /// Take the given classes of events out of the queue, into buffers holding
+    /// up to `capacity` events per class
+    pub fn events_filtered_with_capacity(
+        &self,
+        classes: impl Into<EventClasses>,
+        capacity: usize,
+    ) -> FilteredEvents {
+        self.event_sender
+            .subscribe(classes.into(), capacity, Arc::clone(&self.memory))
+    }
+
+    /// Get the next event from the connection
+    pub async fn next_event(&self) -> Option<ConnectionEvent> {
+        Some(self.next_timed_event().await?.event)
+    }
+
+    /// Get the next event with when it was queued and, for Sent and
+    /// received data, when the work behind it happened
+    pub async fn next_timed_event(&self) -> Option<TimedEvent> {
+        let mut receiver = self.event_receiver.write().await;
+        let mut timed = receiver.recv().await?;
+        timed.dequeued = Instant::now();
+        self.memory.event_taken(&timed.event);
+        Some(timed)
+    }
+
+    /// Send a message and wait for the next complete message as its response
+    ///
+    /// Responses use next-message semantics: the first complete message
+    /// received after the request is returned. Other events delivered while
+    /// waiting are consumed.
+    pub async fn request(&self, message: impl Into<Message>) -> Result<(Message, MessageContext)> {
+        self.request_with_timeout(message, None).await
+    }
+
+    /// Send a request and wait for its response, failing with `Timeout` after `timeout`
+    pub async fn request_with_timeout(
+        &self,
+        message: impl Into<Message>,
+        timeout: Option<Duration>,
+    ) -> Result<(Message, MessageContext)> {
+        let mut message = message.into();
+        // Assign the ID here so a SendError can be matched to this request
+        if message.id().is_none() {
+            let id = self.get_next_message_id().await;
+            message = message.with_id(id);
+        }
+        let message_id = message.id();
+
+        self.send(message).await?;
+
+        let response = self.await_response(message_id);
+        match timeout {
+            Some(duration) => runtime::timeout(duration, response)
+                .await
+                .map_err(|_| TransportServicesError::Timeout)?,
+            None => response.await,
+        }
+    }
+
+    async fn await_response(&self, message_id: Option<u64>) -> Result<(Message, MessageContext)> {
+        let mut partial = Vec::new();
+        loop {
+            match self.next_event().await {
+                Some(ConnectionEvent::Received {
+                    message_data,
+                    message_context,
+                }) => return Ok((Message::from_bytes(&message_data), message_context)),
+                Some(ConnectionEvent::ReceivedPartial {
+                    message_data,
+                    message_context,
+                    end_of_message,
+                }) => {
+                    partial.extend_from_slice(&message_data);
+                    if end_of_message {
+                        return Ok((Message::new(partial), message_context));
+                    }
+                }
+                Some(ConnectionEvent::SendError {
+                    message_id: id,
+                    error,
+                }) if id == message_id => return Err(TransportServicesError::SendFailed(error)),
+                Some(ConnectionEvent::ReceiveError { error }) => {
+                    return Err(TransportServicesError::ReceiveFailed(error))
+                }
+                Some(ConnectionEvent::ConnectionError(error))
+                | Some(ConnectionEvent::EstablishmentError(error)) => {
+                    return Err(TransportServicesError::ConnectionFailed(error))
+                }
+                Some(ConnectionEvent::Closed) | Some(ConnectionEvent::FinalReceived) | None => {
+                    return Err(TransportServicesError::InvalidState(
+                        "Connection closed before a response was received".to_string(),
+                    ))
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    /// Internal method to establish TCP connection
+    pub(crate) async fn establish_tcp(
+        &self,
+        candidates: Vec<(SocketAddr, RemoteEndpoint)>,
+        connection_timeout: Option<Duration>,
+        tunnel: Option<(ProxyConfig, ProxyTarget)>,
+    ) -> Result<()> {
+        let timeout_duration = connection_timeout.unwrap_or(Duration::from_secs(30));
+        let (happy_eyeballs, buffer_sizes) = {
+            let inner = self.inner.read().await;
+            let properties = &inner.transport_properties.connection_properties;
+            (
+                properties.happy_eyeballs,
+                (properties.send_buffer_size, properties.receive_buffer_size),
+            )
+        };
+
+        // The framers are held aside while their Start events run, so the
+        // connection is not locked during their preambles
+        let framers = std::mem::take(&mut self.inner.write().await.framers);
+
+        // Candidates are proxy addresses when tunneling; the proxy and framer
+        // handshakes count against the connection timeout
+        let connect = async {
+            let mut stream = racing::race_tcp(
+                candidates.iter().map(|(addr, _)| *addr).collect(),
+                &happy_eyeballs,
+                buffer_sizes,
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+            if let Some((proxy, target)) = &tunnel {
+                proxy::open_tunnel(&mut stream, proxy, target)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            let received = framers.start(&mut stream).await?;
+            Ok::<_, String>((stream, received))
+        };
+        // Closing or aborting the connection abandons the race, closing the
+        // sockets of every candidate still in it
+        let mut state = self.state_watch().await;
+        let result = tokio::select! {
+            result = runtime::timeout(timeout_duration, connect) => Some(result),
+            _ = state.wait_for(|state| *state != ConnectionState::Establishing) => None,
+        };
+        self.inner.write().await.framers = framers;
+        let Some(result) = result else {
+            return Err(establishment_abandoned());
+        };
+
+        match result {
+            Ok(Ok((stream, received))) => {
+                let mut inner = self.inner.write().await;
+                if inner.state != ConnectionState::Establishing {
+                    // Closed while the winner finished its handshakes
+                    reset_tcp_stream(stream);
+                    return Err(establishment_abandoned());
+                }
+                configure_tcp_stream(&stream, &inner.transport_properties);
+                apply_traffic_class(&stream, &inner.properties);
+                if let Some(hop_limit) = inner.hop_limit() {
+                    set_hop_limit(&stream, Some(hop_limit));
+                }
+                inner.tcp_stream = Some(stream);
+                inner.update_scavenger();
+                inner.tunnel = tunnel.as_ref().map(|(proxy, _)| proxy.kind);
+                // Data the peer sent after the framer preambles
+                inner.receive_buffer.extend(received);
+
+                // Set local endpoint based on actual connection
+                if let Ok(local_addr) = inner.tcp_stream.as_ref().unwrap().local_addr() {
+                    inner.local_endpoint = Some(LocalEndpoint {
+                        identifiers: vec![EndpointIdentifier::SocketAddress(local_addr)],
+                    });
+                }
+
+                // The remote endpoint is the one whose candidate won the race
+                if let Ok(peer_addr) = inner.tcp_stream.as_ref().unwrap().peer_addr() {
+                    if let Some((_, remote)) = candidates.iter().find(|(a, _)| *a == peer_addr) {
+                        inner.remote_endpoint = Some(remote.clone());
+                    }
+                }
+
+                drop(inner);
+
+                // Send any pending messages on the winning candidate
+                self.flush_pending_messages().await?;
+
+                // Start background reading task
+                self.start_reading_task().await?;
+                self.start_stats_task().await;
+                self.start_path_watch().await;
+
+                // Signal Ready event
+                let _ = self.event_sender.send(ConnectionEvent::Ready);
+                Ok(())
+            }
+            Ok(Err(e)) => {
+                self.fail_pending_messages(&format!("Failed to connect: {e}"))
+                    .await;
+                let _ = self
+                    .event_sender
+                    .send(ConnectionEvent::EstablishmentError(format!(
+                        "Failed to connect: {e}"
+                    )));
+                Err(TransportServicesError::EstablishmentFailed(e))
+            }
+            Err(_) => {
+                self.fail_pending_messages("Connection timeout").await;
+                let _ = self.event_sender.send(ConnectionEvent::EstablishmentError(
+                    "Connection timeout".to_string(),
+                ));
+                Err(TransportServicesError::Timeout)
+            }
+        }
+    }
+
+    /// Send messages queued during establishment, then mark the connection Established
+    ///
+    /// The send turn is held until the connection is Established, so messages
+    /// sent while the queue drains wait behind it rather than overtaking it.
+    /// Each message is written exactly once, on the candidate that won.
+    ///
+    /// With activeReadBeforeSend preferred or required, the queue is instead
+    /// held until the peer's first data arrives.
+    async fn flush_pending_messages(&self) -> Result<()> {
+        let _turn = self.send_turn().await;
+
+        let pending = {
+            let mut inner = self.inner.write().await;
+            if inner.state != ConnectionState::Establishing {
+                return Err(TransportServicesError::InvalidState(
+                    "Connection closed during establishment".to_string(),
+                ));
+            }
+            if matches!(
+                inner
+                    .transport_properties
+                    .selection_properties
+                    .active_read_before_send,
+                Preference::Require | Preference::Prefer
+            ) {
+                inner.awaiting_peer_data = true;
+                Vec::new()
+            } else {
+                let pending = std::mem::take(&mut inner.pending_messages);
+                inner.account_memory();
+                pending
+            }
+        };
+
+        // Use send_bundles to avoid re-queuing
+        if let Err(e) = self.send_bundles(pending).await {
+            // The failed write has reported SendError; the transport is unusable
+            let mut inner = self.inner.write().await;
+            inner.reset_transport();
+            let _ = self
+                .event_sender
+                .send(ConnectionEvent::ConnectionError(e.to_string()));
+            return Err(e);
+        }
+
+        let mut inner = self.inner.write().await;
+        if !inner.set_state(ConnectionState::Established) {
+            return Err(TransportServicesError::InvalidState(
+                "Connection closed during establishment".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Send the messages held for activeReadBeforeSend once the peer has spoken
+    async fn release_held_messages(&self) -> Result<()> {
+        let _turn = self.send_turn().await;
+
+        let held = {
+            let mut inner = self.inner.write().await;
+            if !std::mem::take(&mut inner.awaiting_peer_data) {
+                return Ok(());
+            }
+            let held = std::mem::take(&mut inner.pending_messages);
+            inner.account_memory();
+            held
+        };
+
+        self.send_bundles(held).await
+    }
+
+    /// Close the connection after failed establishment, failing queued messages
+    async fn fail_pending_messages(&self, error: &str) {
+        let mut inner = self.inner.write().await;
+        inner.set_closed();
+        for message in inner.pending_messages.drain(..) {
+            let _ = self.event_sender.send(ConnectionEvent::SendError {
+                message_id: message.id(),
+                error: error.to_string(),
+            });
+        }
+        inner.account_memory();
+    }
+
+    /// Get local endpoint information
+    pub async fn local_endpoint(&self) -> Option<LocalEndpoint> {
+        let inner = self.inner.read().await;
+        inner.local_endpoint.clone()
+    }
+
+    /// Get remote endpoint information
+    pub async fn remote_endpoint(&self) -> Option<RemoteEndpoint> {
+        let inner = self.inner.read().await;
+        inner.remote_endpoint.clone()
+    }
+
+    /// Set a connection property
+    /// RFC Section 8: Connection.SetProperty(property, value)
+    pub async fn set_property(&self, key: &str, value: ConnectionProperty) -> Result<()> {
+        let mut inner = self.inner.write().await;
+        inner.check_property_namespace(key)?;
+
+        // For properties in a connection group, update all connections
+        if let Some(ref group) = inner.connection_group {
+            // connPriority is not shared across the group (per RFC), and
+            // members pin their own paths
+            if key != "connPriority" && key != "pathPinning" {
+                // Clone the group reference and value to avoid holding locks
+                let group_clone = Arc::clone(group);
+                let key_clone = key.to_string();
+                let value_clone = value.clone();
+
+                // Release the lock before updating other connections
+                drop(inner);
+
+                // Get all connections in the group
+                let connections = group_clone.get_connections().await;
+
+                // Update property on all other connections in parallel, including their sockets
+                let mut update_tasks = Vec::new();
+                for conn_inner in connections {
+                    if Arc::ptr_eq(&conn_inner, &self.inner) {
+                        continue;
+                    }
+                    let key = key_clone.clone();
+                    let val = value_clone.clone();
+                    let task = async move {
+                        let mut inner = conn_inner.write().await;
+                        if inner.properties.set(&key, val.clone()).is_ok() {
+                            if let Err(e) = inner.apply_to_transport(&key, &val) {
+                                log::warn!("Failed to apply {key} to group member: {e}");
+                            }
+                        }
+                    };
+                    update_tasks.push(task);
+                }
+
+                // Wait for all updates
+                futures::future::join_all(update_tasks).await;
+
+                // Re-acquire lock to update this connection
+                inner = self.inner.write().await;
+            }
+        }
+
+        // Set the property on this connection
+        inner.properties.set(key, value.clone())?;
+
+        // Apply property changes that need immediate action
+        inner.apply_to_transport(key, &value)?;
+        drop(inner);
+        if key == "pathPinning" {
+            self.start_path_watch().await;
+        }
+        Ok(())
+    }
+
+    /// Get all connection properties
+    /// RFC Section 8: ConnectionProperties := Connection.GetProperties()
+    pub async fn get_properties(&self) -> ConnectionProperties {
+        let path = self.path_info().await;
+        let inner = self.inner.read().await;
+        let mut props = inner.properties.clone();
+
+        // Get the transport properties to check direction
+        let direction = inner.transport_properties.selection_properties.direction;
+
+        // RFC 8.1.11.2: Can Send Data
+        // Check against direction Selection Property and Final message state
+        let can_send = match inner.state {
+            ConnectionState::Established => {
+                // Can't send if:
+                // 1. Direction is unidirectional receive, or
+                // 2. A Final message was already sent
+                match direction {
+                    CommunicationDirection::UnidirectionalReceive => false,
+                    _ => !inner.final_message_sent,
+                }
+            }
+            ConnectionState::Establishing => false, // Could buffer, but say false for now
+            _ => false,
+        };
+
+        // RFC 8.1.11.3: Can Receive Data
+        // Check against direction Selection Property and Final message state
+        let can_receive = match inner.state {
+            ConnectionState::Established => {
+                // Can't receive if:
+                // 1. Direction is unidirectional send, or
+                // 2. A Final message was already received (implementation specific)
+                match direction {
+                    CommunicationDirection::UnidirectionalSend => false,
+                    _ => !inner.final_message_received,
+                }
+            }
+            _ => false,
+        };
+
+        // Update the basic read-only properties
+        props.update_readonly(inner.state, can_send, can_receive);
+        props.update_path(&path);
+        props.properties.insert(
+            "protocolStack".to_string(),
+            ConnectionProperty::ProtocolStack(inner.protocol_stack()),
+        );
+        props.properties.insert(
+            "sendStall".to_string(),
+            ConnectionProperty::SendStall(self.send_stall.duration()),
+        );
+        props.properties.insert(
+            "memoryUsage".to_string(),
+            ConnectionProperty::MemoryUsage(self.memory.usage()),
+        );
+
+        // Update MTU-related properties if we have a TCP stream
+        if let Some(ref stream) = inner.tcp_stream {
+            // RFC 8.1.11.4: Maximum Message Size Before Fragmentation
+            // Query actual MSS from socket
+            let mss = self.get_tcp_mss(stream).await.unwrap_or(1460); // Default to typical value if query fails
+
+            props.properties.insert(
+                "singularTransmissionMsgMaxLen".to_string(),
+                ConnectionProperty::SingularTransmissionMsgMaxLen(Some(mss)),
+            );
+
+            // RFC 8.1.11.5: Maximum Message Size on Send
+            // For TCP, there's no inherent limit (streaming protocol)
+            // Return 0 if sending is not possible
+            let send_msg_max = if can_send {
+                None // No limit for TCP
+            } else {
+                Some(0) // Cannot send
+            };
+            props.properties.insert(
+                "sendMsgMaxLen".to_string(),
+                ConnectionProperty::SendMsgMaxLen(send_msg_max),
+            );
+
+            // RFC 8.1.11.6: Maximum Message Size on Receive
+            // For TCP, there's no inherent limit (streaming protocol)
+            // Return 0 if receiving is not possible
+            let recv_msg_max = if can_receive {
+                // TCP has no inherent limit, only the configured one
+                inner.reassembly.max_message_size()
+            } else {
+                Some(0) // Cannot receive
+            };
+            props.properties.insert(
+                "recvMsgMaxLen".to_string(),
+                ConnectionProperty::RecvMsgMaxLen(recv_msg_max),
+            );
+        } else {
+            // No stream - set appropriate values based on connection state
+            if inner.state == ConnectionState::Establishing {
+                // Don't add properties for connections that haven't been established yet
+                // This matches the expectation of test_mss_property_not_set_before_connection
+            } else {
+                // For closed connections or other states, add properties with 0 values
+                props.properties.insert(
+                    "singularTransmissionMsgMaxLen".to_string(),
+                    ConnectionProperty::SingularTransmissionMsgMaxLen(None),
+                ); // Not applicable
+                props.properties.insert(
+                    "sendMsgMaxLen".to_string(),
+                    ConnectionProperty::SendMsgMaxLen(Some(0)),
+                ); // Cannot send without stream
+                props.properties.insert(
+                    "recvMsgMaxLen".to_string(),
+                    ConnectionProperty::RecvMsgMaxLen(Some(0)),
+                ); // Cannot receive without stream
+            }
+        }
+
+        props
+    }
+
+    /// Get the protocol stack the connection is using, outermost layer
+    /// first, e.g. TLS1.3/TCP/IPv6; empty unless established
+    ///
+    /// Also available as the read-only property protocolStack.
+    pub async fn protocol_stack(&self) -> ProtocolStack {
+        self.inner.read().await.protocol_stack()
+    }
+
+    /// Get a specific connection property value
+    pub async fn get_property(&self, key: &str) -> Option<ConnectionProperty> {
+        // Readable while a stalled write holds the connection
+        if key == "sendStall" {
+            return Some(ConnectionProperty::SendStall(self.send_stall.duration()));
+        }
+        if key == "memoryUsage" {
+            return Some(ConnectionProperty::MemoryUsage(self.memory.usage()));
+        }
+        let props = self.get_properties().await;
+        props.get(key).cloned()
+    }
+
+    /// Get the connection group ID if this connection is part of a group
+    pub async fn connection_group_id(&self) -> Option<ConnectionGroupId> {
+        let inner = self.inner.read().await;
+        inner.connection_group.as_ref().map(|g| g.id)
+    }
+
+    /// Get the connection group this connection belongs to
+    pub async fn connection_group(&self) -> Option<Arc<ConnectionGroup>> {
+        let inner = self.inner.read().await;
+        inner.connection_group.clone()
+    }
+
+    /// Check if this connection is part of a connection group
+    pub async fn is_grouped(&self) -> bool {
+        let inner = self.inner.read().await;
+        inner.connection_group.is_some()
+    }
+
+    /// Get the number of connections in this connection's group
+    pub async fn group_connection_count(&self) -> Option<u64> {
+        let inner = self.inner.read().await;
+        inner
+            .connection_group
+            .as_ref()
+            .map(|g| g.connection_count())
+    }
+
+    /// Close all connections in the group
+    /// RFC Section 10
+    pub async fn close_group(&self) -> Result<()> {
+        // Check if we have a group
+        let has_group = {
+            let inner = self.inner.read().await;
+            inner.connection_group.is_some()
+        };
+
+        if has_group {
+            let inner = self.inner.read().await;
+            let group = inner.connection_group.as_ref().unwrap();
+            // Get all connections in the group
+            let members = group.get_members().await;
+            drop(inner); // Release lock before closing connections
+
+            // Close all connections in parallel
+            let mut close_tasks = Vec::new();
+            for (conn_inner, event_sender) in members {
+                let task = async move {
+                    let mut inner = conn_inner.write().await;
+
+                    match inner.state {
+                        ConnectionState::Established | ConnectionState::Establishing => {
+                            inner.set_state(ConnectionState::Closing);
+
+                            // Clear any pending batched messages before closing
+                            inner.batched_messages.clear();
+
+                            // Perform graceful close on TCP stream
+                            if let Some(ref mut stream) = inner.tcp_stream {
+                                let _ = stream.flush().await;
+                                let _ = stream.shutdown().await;
+                            }
+
+                            inner.set_closed();
+                            inner.pending_messages.clear();
+                            inner.clear_receive_buffer();
+                            inner.tcp_stream = None;
+                            inner.udp_socket = None;
+
+                            // Note: We don't decrement connection count here as it's handled by each connection
+
+                            // Every member's handles observe the closure
+                            let _ = event_sender.send(ConnectionEvent::Closed);
+                        }
+                        _ => {} // Already closing or closed
+                    }
+                };
+                close_tasks.push(task);
+            }
+
+            // Wait for all connections to close
+            futures::future::join_all(close_tasks).await;
+
+            Ok(())
+        } else {
+            // No group, just close this connection
+            self.close().await
+        }
+    }
+
+    /// Close all connections in the group, draining outstanding data until a deadline
+    ///
+    /// Sends fail on every member as soon as the drain starts. Members then
+    /// close gracefully in connPriority order, those of equal priority
+    /// together, and any member still open when the deadline passes is aborted.
+    pub async fn close_group_with_drain(&self, deadline: Duration) -> Result<()> {
+        let group = self.inner.read().await.connection_group.clone();
+        let members = match group {
+            Some(group) => group.members().await,
+            None => vec![self.internal_clone()],
+        };
+
+        // Stop new sends across the group before any member drains
+        let mut draining = Vec::new();
+        for member in members {
+            let mut inner = member.inner.write().await;
+            if matches!(
+                inner.state,
+                ConnectionState::Established | ConnectionState::Establishing
+            ) {
+                inner.set_state(ConnectionState::Closing);
+                let priority = inner.priority();
+                drop(inner);
+                draining.push((priority, member));
+            }
+        }
+        draining.sort_by_key(|(priority, _)| *priority);
+
+        let drain = async {
+            for tier in draining.chunk_by(|a, b| a.0 == b.0) {
+                futures::future::join_all(tier.iter().map(|(_, member)| member.finish_close()))
+                    .await;
+            }
+        };
+        if runtime::timeout(deadline, drain).await.is_err() {
+            for (_, member) in &draining {
+                let _ = member
+                    .abort_internal("Group drain deadline passed".to_string())
+                    .await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Abort all connections in the group
+    pub async fn abort_group(&self) -> Result<()> {
+        // Check if we have a group
+        let has_group = {
+            let inner = self.inner.read().await;
+            inner.connection_group.is_some()
+        };
+
+        if has_group {
+            let inner = self.inner.read().await;
+            let group = inner.connection_group.as_ref().unwrap();
+            // Get all connections in the group
+            let members = group.get_members().await;
+            drop(inner); // Release lock before aborting connections
+
+            // Abort all connections in parallel
+            let mut abort_tasks = Vec::new();
+            for (conn_inner, event_sender) in members {
+                let task = async move {
+                    let mut inner = conn_inner.write().await;
+
+                    let was_not_closed = inner.state != ConnectionState::Closed;
+                    if was_not_closed {
+                        // Immediately set state to Closed
+                        inner.set_closed();
+
+                        // Reset the TCP stream
+                        if let Some(stream) = inner.tcp_stream.take() {
+                            reset_tcp_stream(stream);
+                        }
+                        inner.udp_socket = None;
+
+                        // Clear all buffers
+                        inner.pending_messages.clear();
+                        inner.batched_messages.clear();
+                        inner.clear_receive_buffer();
+
+                        // Every member's handles observe the abort
+                        let _ = event_sender.send(ConnectionEvent::ConnectionError(
+                            "Connection group aborted".to_string(),
+                        ));
+                    }
+                };
+                abort_tasks.push(task);
+            }
+
+            // Wait for all connections to abort
+            futures::future::join_all(abort_tasks).await;
+
+            Ok(())
+        } else {
+            // No group, just abort this connection
+            self.abort().await
+        }
+    }
+
+    // Internal method to update state
+    #[allow(dead_code)]
+    pub(crate) async fn set_state(&self, state: ConnectionState) {
+        let mut inner = self.inner.write().await;
+        inner.set_state(state);
+
+        if state == ConnectionState::Established {
+            let _ = self.event_sender.send(ConnectionEvent::Ready);
+        }
+    }
+
+    // Internal method to attach the TLS session a Listener terminated, with
+    // any data the client sent along with its last handshake records
+    #[cfg(feature = "tls")]
+    pub(crate) async fn set_tls_session(&self, session: crate::tls::TlsSession, received: Vec<u8>) {
+        let mut inner = self.inner.write().await;
+        inner
+            .properties
+            .update_security(session.protocol(), session.alpn(), session.server_name());
+        inner
+            .properties
+            .update_client_certificate(session.peer_certificates());
+        inner.tls = Some(session);
+        inner.receive_buffer.extend(received);
+    }
+
+    // Internal method to queue data a Listener accepted in the SYN (TCP
+    // Fast Open), delivered as early data before the stream is read
+    pub(crate) async fn set_early_data(&self, data: Vec<u8>) {
+        let mut inner = self.inner.write().await;
+        inner.receive_buffer.extend(data);
+        inner.early_data = true;
+    }
+
+    /// What the Listener peeked from the start of this connection's
+    /// stream, when it sniffs incoming protocols
+    pub async fn sniffed(&self) -> Option<SniffedData> {
+        self.inner.read().await.sniffed.clone()
+    }
+
+    // Internal method to apply what a listener sniffed, before the stream is attached
+    pub(crate) async fn set_sniffed(&self, sniffed: Sniffed) -> Result<()> {
+        if let Some(framers) = sniffed.framers {
+            self.set_framers(framers).await?;
+        }
+        self.inner.write().await.sniffed = Some(sniffed.data);
+        Ok(())
+    }
+
+    // Internal method to set TCP stream (for listener)
+    pub(crate) async fn set_tcp_stream(&mut self, stream: TcpStream) {
+        let mut inner = self.inner.write().await;
+        configure_tcp_stream(&stream, &inner.transport_properties);
+        apply_traffic_class(&stream, &inner.properties);
+        if let Some(hop_limit) = inner.hop_limit() {
+            set_hop_limit(&stream, Some(hop_limit));
+        }
+        inner.tcp_stream = Some(stream);
+        inner.update_scavenger();
+        inner.set_state(ConnectionState::Established);
+        drop(inner);
+
+        // Start background reading task
+        let _ = self.start_reading_task().await;
+        self.start_stats_task().await;
+        self.start_path_watch().await;
+
+        let _ = self.event_sender.send(ConnectionEvent::Ready);
+    }
+
+    // Internal method to attach a Listener's datagram socket (connectionless transports)
+    pub(crate) async fn set_udp_socket(&mut self, socket: Arc<UdpSocket>) {
+        let mut inner = self.inner.write().await;
+        inner.udp_socket = Some(socket);
+        inner.set_state(ConnectionState::Established);
+        drop(inner);
+        self.start_stats_task().await;
+        self.start_nat_keepalive_task().await;
+        self.start_path_watch().await;
+
+        let _ = self.event_sender.send(ConnectionEvent::Ready);
+    }
+
+    /// Deliver a datagram received on a Listener's socket to this connection
+    /// RFC Section 9.3.2.1: the MessageContext carries the actual source of the
+    /// datagram, and the local address and interface it arrived on
+    pub(crate) async fn deliver_datagram(
+        &self,
+        data: &[u8],
+        source: SocketAddr,
+        received: &ReceiveInfo,
+    ) {
+        let received_at = Instant::now();
+        {
+            let mut inner = self.inner.write().await;
+            if inner.injected_reset() {
+                inner.reset_transport();
+                let _ = self
+                    .event_sender
+                    .send(ConnectionEvent::ConnectionError(INJECTED_RESET.to_string()));
+                return;
+            }
+        }
+        let datagrams = receive_faults(&self.inner, data).await;
+
+        let mut inner = self.inner.write().await;
+        if inner.state != ConnectionState::Established || inner.final_message_received {
+            return;
+        }
+        inner.bytes_received += data.len() as u64;
+        inner.last_traffic = Instant::now();
+
+        // Replies go to the most recent sender; a new source address means
+        // the peer's NAT rebound the flow
+        let rebound = inner
+            .remote_socket_addr()
+            .is_some_and(|previous| previous != source);
+        if rebound && inner.path_pinned() {
+            inner.reset_transport();
+            let _ = self
+                .event_sender
+                .send(ConnectionEvent::ConnectionError(format!(
+                    "Peer moved to {source} on a pinned path"
+                )));
+            return;
+        }
+        inner.remote_endpoint = Some(RemoteEndpoint {
+            identifiers: vec![EndpointIdentifier::SocketAddress(source)],
+            protocol: None,
+        });
+        if rebound {
+            drop(inner);
+            self.emit_path_change().await;
+            inner = self.inner.write().await;
+        }
+        if let Some(packet_info) = received.packet_info {
+            inner.set_packet_info(packet_info);
+        }
+
+        // Under memory backpressure datagrams are dropped as a full socket would
+        if memory::backpressure() {
+            return;
+        }
+
+        for datagram in datagrams {
+            // A zero-length datagram is an empty message, e.g. a NAT keepalive;
+            // framers find no message in it
+            let parsed = if datagram.is_empty() && inner.framers.is_empty() {
+                Ok(vec![(Message::new(Vec::new()), MessageContext::new())])
+            } else {
+                inner.framers.parse_data(&datagram).await
+            };
+            match parsed {
+                Ok(messages) => {
+                    for (message, _) in messages {
+                        let mut message_context = inner.receive_context();
+                        message_context.interface_timestamp = received.timestamp;
+                        if let Some(traffic_class) = received.traffic_class {
+                            message_context = message_context.with_traffic_class(traffic_class);
+                        }
+                        let event = ConnectionEvent::Received {
+                            message_data: message.data().to_vec(),
+                            message_context,
+                        };
+                        self.memory.event_queued(&event);
+                        let _ = self
+                            .event_sender
+                            .send_stamped(event, receive_timestamps(Some(received_at)));
+                    }
+                }
+                Err(e) => {
+                    let _ = self.event_sender.send(ConnectionEvent::ReceiveError {
+                        error: e.to_string(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Close the read side after the peer's Final message and notify the application
+    /// RFC Section 9.3.2.1
+    async fn handle_final_received(&self) {
+        let mut inner = self.inner.write().await;
+        if inner.mark_final_received() {
+            let _ = self.event_sender.send(ConnectionEvent::FinalReceived);
+        }
+    }
+
+    /// Emit a SoftError event
+    /// RFC Section 8.3.1 - Soft Errors
+    pub(crate) async fn emit_soft_error(&self, error_message: String) {
+        // Only emit if soft error notifications are requested
+        let inner = self.inner.read().await;
+        if inner
+            .transport_properties
+            .selection_properties
+            .soft_error_notify
+            == Preference::Require
+            || inner
+                .transport_properties
+                .selection_properties
+                .soft_error_notify
+                == Preference::Prefer
+        {
+            drop(inner);
+            let _ = self
+                .event_sender
+                .send(ConnectionEvent::SoftError(error_message));
+        }
+    }
+
+    /// Emit a PathChange event  
+    /// RFC Section 8.3.2 - Path Change
+    pub(crate) async fn emit_path_change(&self) {
+        self.refresh_path_info().await;
+        let _ = self.event_sender.send(ConnectionEvent::PathChange);
+    }
+
+    /// Get the network path this connection is currently using
+    ///
+    /// Also available as the read-only properties pathInterface,
+    /// pathInterfaceType, pathLocalAddress, pathRemoteAddress and pathMtu.
+    pub async fn path_info(&self) -> PathInfo {
+        if let Some(path) = self.inner.read().await.path_info.clone() {
+            return path;
+        }
+        self.refresh_path_info().await
+    }
+
+    /// Re-read the current path from the socket and the NetworkMonitor
+    async fn refresh_path_info(&self) -> PathInfo {
+        let mut path = self.inner.read().await.socket_path();
+
+        if let Some(local) = path.local_address {
+            if !local.ip().is_unspecified() {
+                if let Some(interface) = path_monitor::interface_for_address(local.ip()).await {
+                    path.interface_type = Some(interface.interface_type);
+                    path.is_expensive = interface.is_expensive;
+                    // The platform's view of the path is more precise, where it has one
+                    if let Some(system) = path_monitor::system_path(&interface.name).await {
+                        if system.interface_type.is_some() {
+                            path.interface_type = system.interface_type;
+                        }
+                        path.is_expensive = system.is_expensive;
+                        path.is_constrained = system.is_constrained;
+                        path.status = Some(system.status);
+                        path.has_dns = Some(system.has_dns);
+                    }
+                    path.interface = Some(interface.name);
+                }
+            }
+        }
+
+        // Only a path with a local address is worth keeping
+        self.inner.write().await.path_info = path.local_address.is_some().then(|| path.clone());
+        path
+    }
+
+    /// Inspect the options of the underlying TCP socket
+    #[cfg(test)]
+    pub(crate) async fn inspect_tcp_socket<R>(
+        &self,
+        f: impl FnOnce(socket2::SockRef<'_>) -> R,
+    ) -> Option<R> {
+        let inner = self.inner.read().await;
+        inner
+            .tcp_stream
+            .as_ref()
+            .map(|stream| f(socket2::SockRef::from(stream)))
+    }
+
+    /// Get the TCP Maximum Segment Size (MSS) from a TcpStream
+    async fn get_tcp_mss(&self, #[allow(unused_variables)] stream: &TcpStream) -> Result<usize> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::io::{AsRawFd, FromRawFd};
+
+            // Get the raw file descriptor from the TcpStream
+            let fd = stream.as_raw_fd();
+
+            // Create a Socket from the raw fd
+            // SAFETY: We're borrowing the fd from TcpStream, not taking ownership
+            let socket = unsafe { Socket::from_raw_fd(fd) };
+
+            // Get the MSS value
+            #[cfg(not(target_os = "redox"))]
+            let mss_result = socket.mss();
+
+            #[cfg(target_os = "redox")]
+            let mss_result = Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "TCP MSS query not supported on Redox",
+            ));
+
+            // Important: We must forget the socket to prevent it from closing the fd
+            // when it goes out of scope (since we don't own the fd)
+            std::mem::forget(socket);
+
+            match mss_result {
+                Ok(mss) => Ok(mss as usize),
+                Err(e) => {
+                    // Log the error but don't fail
+                    log::debug!("Failed to get TCP MSS: {e}");
+                    Err(TransportServicesError::NotSupported(format!(
+                        "Failed to get TCP MSS: {e}"
+                    )))
+                }
+            }
+        }
+
+        #[cfg(not(unix))]
+        {
+            // On non-Unix platforms, return a typical default value
+            Ok(1460)
+        }
+    }
+
+    /// Abort a pinned connection once the local address of its path is gone
+    ///
+    /// Runs while pathPinning is set and the path has a specific local
+    /// address; a connection pinned again later is watched anew.
+    async fn start_path_watch(&self) {
+        let mut inner = self.inner.write().await;
+        if inner.path_watched || !inner.path_pinned() {
+            return;
+        }
+        let Some(local) = inner
+            .socket_path()
+            .local_address
+            .map(|address| address.ip().to_canonical())
+            .filter(|ip| !ip.is_unspecified())
+        else {
+            return;
+        };
+        inner.path_watched = true;
+        drop(inner);
+
+        // Held weakly so the watch does not keep a dropped connection alive
+        let connection = Arc::downgrade(&self.inner);
+        let event_sender = self.event_sender.clone();
+        let mut state = self.state_watch().await;
+        let mut addresses = path_monitor::watch_addresses();
+        runtime::spawn(async move {
+            loop {
+                // A path whose state is unknown is left be
+                let lost = addresses
+                    .borrow_and_update()
+                    .as_ref()
+                    .is_some_and(|addresses| !addresses.contains(&local));
+                let Some(shared) = connection.upgrade() else {
+                    break;
+                };
+                let mut inner = shared.write().await;
+                if inner.state == ConnectionState::Closed {
+                    break;
+                }
+                if !inner.path_pinned() {
+                    inner.path_watched = false;
+                    break;
+                }
+                if lost {
+                    inner.reset_transport();
+                    let _ = event_sender.send(ConnectionEvent::ConnectionError(format!(
+                        "Path through {local} was lost on a pinned connection"
+                    )));
+                    break;
+                }
+                // Not held while waiting, so a dropped connection goes away
+                drop(inner);
+                drop(shared);
+
+                tokio::select! {
+                    changed = addresses.changed() => {
+                        if changed.is_err() {
+                            break;
+                        }
+                    }
+                    _ = state.wait_for(|state| *state == ConnectionState::Closed) => break,
+                }
+            }
+        });
+    }
+
+    /// Start emitting Stats events, if a stats interval is set
+    async fn start_stats_task(&self) {
+        let inner = self.inner.read().await;
+        let Some(interval) = inner
+            .transport_properties
+            .connection_properties
+            .stats_interval
+        else {
+            return;
+        };
+        drop(inner);
+
+        // Held weakly so telemetry does not keep a dropped connection alive
+        let connection = Arc::downgrade(&self.inner);
+        let event_sender = self.event_sender.clone();
+        runtime::spawn(async move {
+            let mut previous: Option<(Instant, u64, u64)> = None;
+            loop {
+                runtime::sleep(interval).await;
+                let Some(inner) = connection.upgrade() else {
+                    break;
+                };
+                let inner = inner.read().await;
+                if inner.state == ConnectionState::Closed {
+                    break;
+                }
+                let mut stats = inner.stats();
+                drop(inner);
+
+                let now = Instant::now();
+                let (since, sent, received) = previous.unwrap_or((now - interval, 0, 0));
+                let elapsed = now.duration_since(since).as_secs_f64();
+                if elapsed > 0.0 {
+                    stats.send_throughput = (stats.bytes_sent - sent) as f64 / elapsed;
+                    stats.receive_throughput = (stats.bytes_received - received) as f64 / elapsed;
+                }
+                previous = Some((now, stats.bytes_sent, stats.bytes_received));
+
+                if !event_sender.send(ConnectionEvent::Stats(stats)) {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Start sending NAT keepalives on idle UDP flows, if an interval is set
+    ///
+    /// A keepalive is an empty datagram, sent only once no datagram has been
+    /// sent or received for a whole interval.
+    async fn start_nat_keepalive_task(&self) {
+        let inner = self.inner.read().await;
+        let Some(interval) = inner
+            .transport_properties
+            .connection_properties
+            .nat_keepalive_interval
+        else {
+            return;
+        };
+        drop(inner);
+
+        let connection = Arc::downgrade(&self.inner);
+        runtime::spawn(async move {
+            loop {
+                let Some(inner) = connection.upgrade() else {
+                    break;
+                };
+                let guard = inner.read().await;
+                if guard.state == ConnectionState::Closed {
+                    break;
+                }
+                let idle = guard.last_traffic.elapsed();
+                let target = (guard.udp_socket.clone(), guard.remote_socket_addr());
+                drop(guard);
 
-        match inner.state {
-            ConnectionState::Established | ConnectionState::Establishing => {
-                // For single-path TCP connections, we can only have one remote endpoint
-                // In a real implementation with multipath support (like MPTCP or QUIC),
-                // we would add this to a list of available endpoints
+                if idle < interval {
+                    drop(inner);
+                    runtime::sleep(interval - idle).await;
+                    continue;
+                }
 
-                // Check if this is the same endpoint we already have
-                if let Some(ref current_remote) = inner.remote_endpoint {
-                    // Check if any identifiers match
-                    for new_id in &endpoint.identifiers {
-                        for existing_id in &current_remote.identifiers {
-                            if new_id == existing_id {
-                                // Endpoint already known, ignore as per RFC
-                                return Ok(());
-                            }
+                if let (Some(socket), Some(peer)) = target {
+                    match socket.send_to(&[], peer).await {
+                        Ok(_) => inner.write().await.last_traffic = Instant::now(),
+                        Err(e) => {
+                            log::debug!("NAT keepalive to {peer} failed: {e}");
+                            drop(inner);
+                            runtime::sleep(interval).await;
                         }
                     }
+                } else {
+                    break;
                 }
+            }
+        });
+    }
 
-                // For now, since we only support single-path TCP, we can only
-                // update the remote endpoint if we don't have an established connection yet
-                if inner.state == ConnectionState::Establishing && inner.tcp_stream.is_none() {
-                    // Update the remote endpoint for future connection attempts
-                    inner.remote_endpoint = Some(endpoint);
-                    drop(inner);
+    /// Start a background task to continuously read from the connection
+    /// This enables passive message reception via events
+    async fn start_reading_task(&self) -> Result<()> {
+        // Clone necessary handles for the background task
+        let inner_clone = Arc::clone(&self.inner);
+        let event_sender = self.event_sender.clone();
+        let connection = self.internal_clone();
 
-                    // Emit PathChange event since endpoints changed
-                    self.emit_path_change().await;
+        // Spawn the background reading task
+        runtime::spawn(async move {
+            let mut buffer = vec![0u8; 8192];
 
-                    Ok(())
-                } else {
-                    // Log that we received the endpoint but can't use it with current transport
-                    // In a multipath implementation, we would:
-                    // 1. Store this endpoint in a list
-                    // 2. Potentially establish a new subflow to this endpoint
-                    // 3. Update routing tables
+            // Deliver data that arrived with the framers' preambles or in the SYN
+            {
+                let mut inner = inner_clone.write().await;
+                inner.deliver_buffered(&event_sender).await;
+                inner.early_data = false;
+            }
 
-                    // For now, we just acknowledge receipt but don't use it
-                    Ok(())
+            loop {
+                // Check if connection is still active
+                let should_continue = {
+                    let inner = inner_clone.read().await;
+                    matches!(inner.state, ConnectionState::Established)
+                };
+
+                if !should_continue {
+                    break;
                 }
-            }
-            _ => Err(TransportServicesError::InvalidState(
-                "Cannot add endpoints to a closed connection".to_string(),
-            )),
-        }
-    }
 
-    /// Add a local endpoint to the connection
-    pub async fn add_local(&self, endpoint: LocalEndpoint) -> Result<()> {
-        let mut inner = self.inner.write().await;
+                // Leave data with the transport until the application catches up
+                if memory::backpressure() {
+                    runtime::sleep(Duration::from_millis(10)).await;
+                    continue;
+                }
 
-        match inner.state {
-            ConnectionState::Established | ConnectionState::Establishing => {
-                // For single-path TCP connections, we can only have one local endpoint
-                // In a real implementation with multipath support (like MPTCP or QUIC),
-                // we would add this to a list of available endpoints
+                {
+                    let mut inner = inner_clone.write().await;
+                    if inner.injected_reset() {
+                        inner.reset_transport();
+                        let _ = event_sender
+                            .send(ConnectionEvent::ConnectionError(INJECTED_RESET.to_string()));
+                        break;
+                    }
+                }
 
-                // Check if this is the same endpoint we already have
-                if let Some(ref current_local) = inner.local_endpoint {
-                    // Check if any identifiers match
-                    for new_id in &endpoint.identifiers {
-                        for existing_id in &current_local.identifiers {
-                            if new_id == existing_id {
-                                // Endpoint already known, ignore
-                                return Ok(());
+                // Try to read data from the stream
+                let read_result = {
+                    let inner = inner_clone.read().await;
+                    if let Some(ref stream) = inner.tcp_stream {
+                        match try_read_timestamped(stream, &mut buffer, inner.receive_timestamps())
+                        {
+                            Ok(n) => Some(Ok(n)),
+                            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                                // No data available, continue
+                                None
                             }
+                            Err(e) => Some(Err(e)),
                         }
+                    } else {
+                        break; // No stream available
                     }
-                }
+                };
 
-                // For now, since we only support single-path TCP, we can only
-                // update the local endpoint if we don't have an established connection yet
-                if inner.state == ConnectionState::Establishing && inner.tcp_stream.is_none() {
-                    // Update the local endpoint for future connection attempts
-                    inner.local_endpoint = Some(endpoint);
-                    drop(inner);
+                match read_result {
+                    Some(Ok((0, _))) => {
+                        // Connection closed by peer; the FIN ends the peer's data
+                        let mut inner = inner_clone.write().await;
+                        if inner.mark_final_received() {
+                            let _ = event_sender.send(ConnectionEvent::FinalReceived);
+                        }
+                        inner.set_closed();
+                        let _ = event_sender.send(ConnectionEvent::Closed);
+                        break;
+                    }
+                    Some(Ok((n, timestamp))) => {
+                        // Add data to receive buffer and try to parse messages
+                        let data = receive_faults(&inner_clone, &buffer[..n]).await;
+                        let mut inner = inner_clone.write().await;
+                        inner.bytes_received += n as u64;
+                        if inner.final_message_received {
+                            // Read side is closed; discard anything after the Final message
+                            continue;
+                        }
+                        if inner.accept_received(data.concat(), &event_sender).is_err() {
+                            break;
+                        }
+                        inner.receive_timestamp = timestamp;
 
-                    // Emit PathChange event since endpoints changed
-                    self.emit_path_change().await;
+                        inner.deliver_buffered(&event_sender).await;
 
-                    Ok(())
-                } else {
-                    // In a multipath implementation, we would:
-                    // 1. Store this endpoint in a list
-                    // 2. Potentially bind a new socket to this endpoint
-                    // 3. Use it for new subflows
+                        drop(inner);
+                        // Failed writes have reported SendError
+                        let _ = connection.release_held_messages().await;
+                    }
+                    Some(Err(e)) => {
+                        let error_msg = e.to_string();
+                        let _ = event_sender.send(ConnectionEvent::ReceiveError {
+                            error: error_msg.clone(),
+                        });
 
-                    // For now, we just acknowledge receipt but don't use it
-                    Ok(())
+                        // Check if this is a fatal error
+                        if error_msg.contains("broken pipe")
+                            || error_msg.contains("connection reset")
+                        {
+                            let mut inner = inner_clone.write().await;
+                            inner.set_closed();
+                            let _ = event_sender.send(ConnectionEvent::Closed);
+                            break;
+                        }
+                    }
+                    None => {
+                        // WouldBlock - yield to allow other tasks to run
+                        runtime::sleep(Duration::from_millis(10)).await;
+                    }
                 }
             }
-            _ => Err(TransportServicesError::InvalidState(
-                "Cannot add endpoints to a closed connection".to_string(),
-            )),
-        }
+        });
+
+        Ok(())
     }
+}
 
-    /// Get the next event from the connection
-    pub async fn next_event(&self) -> Option<ConnectionEvent> {
-        let mut receiver = self.event_receiver.write().await;
-        receiver.recv().await
+/// Apply injected receive faults to bytes read from the network
+async fn receive_faults(inner: &RwLock<ConnectionInner>, data: &[u8]) -> Vec<Vec<u8>> {
+    let injector = inner.read().await.fault_injector.clone();
+    match injector {
+        Some(injector) => {
+            injector
+                .perturb(FaultDirection::Receive, data.to_vec())
+                .await
+        }
+        None => vec![data.to_vec()],
     }
+}
 
-    /// Internal method to establish TCP connection
-    pub(crate) async fn establish_tcp(
-        &self,
-        addr: SocketAddr,
-        connection_timeout: Option<Duration>,
-    ) -> Result<()> {
-        let timeout_duration = connection_timeout.unwrap_or(Duration::from_secs(30));
+/// Build the event announcing received data
+fn receive_event(message: &Message, context: &MessageContext, partial: bool) -> ConnectionEvent {
+    if partial {
+        ConnectionEvent::ReceivedPartial {
+            message_data: message.data().to_vec(),
+            message_context: context.clone(),
+            end_of_message: message.is_end_of_message(),
+        }
+    } else {
+        ConnectionEvent::Received {
+            message_data: message.data().to_vec(),
+            message_context: context.clone(),
+        }
+    }
+}
 
-        match timeout(timeout_duration, TcpStream::connect(addr)).await {
-            Ok(Ok(stream)) => {
-                let mut inner = self.inner.write().await;
-                inner.tcp_stream = Some(stream);
-                inner.state = ConnectionState::Established;
+/// Timestamps of an event for data read from the transport at `received`
+fn receive_timestamps(received: Option<Instant>) -> EventTimestamps {
+    EventTimestamps {
+        received,
+        ..EventTimestamps::queued_now()
+    }
+}
 
-                // Set local endpoint based on actual connection
-                if let Ok(local_addr) = inner.tcp_stream.as_ref().unwrap().local_addr() {
-                    inner.local_endpoint = Some(LocalEndpoint {
-                        identifiers: vec![EndpointIdentifier::SocketAddress(local_addr)],
-                    });
-                }
+/// Timestamps of a Sent event for a write from `started` to `completed`
+fn send_timestamps(started: Instant, completed: Option<Instant>) -> EventTimestamps {
+    EventTimestamps {
+        send_started: Some(started),
+        send_completed: completed,
+        ..EventTimestamps::queued_now()
+    }
+}
 
-                // Send any pending messages
-                let pending = inner.pending_messages.drain(..).collect::<Vec<_>>();
-                drop(inner); // Release lock before sending
+/// Check whether a message asked to be bundled with the messages after it
+fn is_bundled(message: &Message) -> bool {
+    message.send_context().is_some_and(|context| context.bundle)
+}
 
-                for msg in pending {
-                    // Use send_message_internal to avoid re-queuing
-                    self.send_message_internal(msg).await?;
-                }
+/// Write as much of `segments` as the socket takes in one go
+///
+/// With the io_uring backend the write goes through the shared ring, and
+/// only waits for readiness when the socket buffer is full.
+async fn write_vectored(stream: &mut TcpStream, segments: &[Bytes]) -> std::io::Result<usize> {
+    #[cfg(all(target_os = "linux", feature = "io-uring-writes"))]
+    if let Some(driver) = crate::uring::driver() {
+        use std::os::unix::io::AsRawFd;
+        match driver.writev(stream.as_raw_fd(), segments.to_vec()).await {
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+            result => return result,
+        }
+    }
+    let slices: Vec<IoSlice<'_>> = segments.iter().map(|s| IoSlice::new(s)).collect();
+    stream.write_vectored(&slices).await
+}
 
-                // Start background reading task
-                self.start_reading_task().await?;
+/// Send a datagram, through the shared ring with the io_uring backend
+async fn send_datagram(
+    socket: &UdpSocket,
+    data: &[u8],
+    peer: SocketAddr,
+) -> std::io::Result<usize> {
+    #[cfg(all(target_os = "linux", feature = "io-uring-writes"))]
+    if let Some(driver) = crate::uring::driver() {
+        use std::os::unix::io::AsRawFd;
+        let data = Bytes::copy_from_slice(data);
+        match driver.send_to(socket.as_raw_fd(), data, peer).await {
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+            result => return result,
+        }
+    }
+    socket.send_to(data, peer).await
+}
 
-                // Signal Ready event
-                let _ = self.event_sender.send(ConnectionEvent::Ready);
-                Ok(())
+/// Write all segments to the stream using vectored I/O
+async fn write_segments(
+    stream: &mut TcpStream,
+    mut segments: Vec<Bytes>,
+    written_total: &AtomicU64,
+) -> std::io::Result<()> {
+    segments.retain(|s| !s.is_empty());
+    let mut start = 0;
+
+    while start < segments.len() {
+        let written = write_vectored(stream, &segments[start..]).await?;
+
+        if written == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::WriteZero,
+                "failed to write message segments",
+            ));
+        }
+        written_total.fetch_add(written as u64, Ordering::Relaxed);
+
+        // Skip fully written segments and trim a partially written one
+        let mut remaining = written;
+        while remaining > 0 {
+            let len = segments[start].len();
+            if remaining >= len {
+                remaining -= len;
+                start += 1;
+            } else {
+                segments[start].advance(remaining);
+                remaining = 0;
             }
-            Ok(Err(e)) => {
-                let mut inner = self.inner.write().await;
-                inner.state = ConnectionState::Closed;
-                let _ = self
-                    .event_sender
-                    .send(ConnectionEvent::EstablishmentError(format!(
-                        "Failed to connect: {e}"
-                    )));
-                Err(TransportServicesError::EstablishmentFailed(e.to_string()))
+        }
+    }
+
+    Ok(())
+}
+
+/// Report the outcome of each message in a write that failed after
+/// `written` of its `length` bytes
+///
+/// Messages written in full before the failure count as sent. When the
+/// message boundaries in the written bytes are not known, e.g. inside TLS
+/// records, every message fails with the progress of the whole write.
+fn report_partial_send(
+    event_sender: &EventSender,
+    timestamps: EventTimestamps,
+    message_ids: &[Option<u64>],
+    message_ends: Option<&[usize]>,
+    written: usize,
+    length: usize,
+    error: &str,
+) {
+    let mut start = 0;
+    for (index, &message_id) in message_ids.iter().enumerate() {
+        let progress = match message_ends {
+            Some(ends) => {
+                let end = ends[index];
+                let progress = (written.clamp(start, end) - start, end - start);
+                start = end;
+                progress
             }
-            Err(_) => {
-                let mut inner = self.inner.write().await;
-                inner.state = ConnectionState::Closed;
-                let _ = self.event_sender.send(ConnectionEvent::EstablishmentError(
-                    "Connection timeout".to_string(),
-                ));
-                Err(TransportServicesError::Timeout)
+            None => (written, length),
+        };
+        let event = match progress {
+            (sent, total) if sent == total && message_ends.is_some() => {
+                ConnectionEvent::Sent { message_id }
             }
+            (0, _) => ConnectionEvent::SendError {
+                message_id,
+                error: error.to_string(),
+            },
+            (sent, total) => ConnectionEvent::SendError {
+                message_id,
+                error: format!("{error} after writing {sent} of {total} bytes"),
+            },
+        };
+        let _ = match event {
+            ConnectionEvent::Sent { .. } => event_sender.send_stamped(event, timestamps),
+            _ => event_sender.send(event),
+        };
+    }
+}
+
+/// Apply socket-level Transport Properties to a newly established TCP stream
+///
+/// Shared by the initiating and accepting paths so both sides honour the same
+/// keep-alive, buffer size and Nagle settings.
+fn configure_tcp_stream(stream: &TcpStream, properties: &TransportProperties) {
+    let socket = socket2::SockRef::from(stream);
+    let connection_properties = &properties.connection_properties;
+
+    // RFC 6.2.10: keepAlive, with the interval from keepAliveTimeout (RFC 8.1.4)
+    let keep_alive = matches!(
+        properties.selection_properties.keep_alive,
+        Preference::Require | Preference::Prefer
+    );
+    if keep_alive || connection_properties.keep_alive_timeout.is_some() {
+        let mut keepalive = socket2::TcpKeepalive::new();
+        if let Some(interval) = connection_properties.keep_alive_timeout {
+            keepalive = keepalive.with_time(interval);
+        }
+        if let Err(e) = socket.set_tcp_keepalive(&keepalive) {
+            log::warn!("Failed to set TCP keep-alive: {e}");
         }
     }
 
-    /// Get local endpoint information
-    pub async fn local_endpoint(&self) -> Option<LocalEndpoint> {
-        let inner = self.inner.read().await;
-        inner.local_endpoint.clone()
+    if let Some(no_delay) = connection_properties.no_delay {
+        if let Err(e) = stream.set_nodelay(no_delay) {
+            log::warn!("Failed to set TCP_NODELAY: {e}");
+        }
     }
 
-    /// Get remote endpoint information
-    pub async fn remote_endpoint(&self) -> Option<RemoteEndpoint> {
-        let inner = self.inner.read().await;
-        inner.remote_endpoint.clone()
+    if connection_properties.receive_timestamps == Some(true) {
+        enable_receive_timestamps(&socket);
     }
+}
 
-    /// Set a connection property
-    /// RFC Section 8: Connection.SetProperty(property, value)
-    pub async fn set_property(&self, key: &str, value: ConnectionProperty) -> Result<()> {
-        let mut inner = self.inner.write().await;
+/// Apply sendBufferSize and receiveBufferSize to a TCP socket
+///
+/// Done before connect or listen, as the receive buffer bounds the window
+/// scale negotiated in the handshake; accepted sockets inherit the sizes of
+/// the listening socket.
+pub(crate) fn set_buffer_sizes(
+    socket: &socket2::SockRef<'_>,
+    send_buffer_size: Option<usize>,
+    receive_buffer_size: Option<usize>,
+) {
+    if let Some(size) = send_buffer_size {
+        if let Err(e) = socket.set_send_buffer_size(size) {
+            log::warn!("Failed to set send buffer size: {e}");
+        }
+    }
 
-        // For properties in a connection group, update all connections
-        if let Some(ref group) = inner.connection_group {
-            // connPriority is not shared across the group (per RFC)
-            if key != "connPriority" {
-                // Clone the group reference and value to avoid holding locks
-                let group_clone = Arc::clone(group);
-                let key_clone = key.to_string();
-                let value_clone = value.clone();
+    if let Some(size) = receive_buffer_size {
+        if let Err(e) = socket.set_recv_buffer_size(size) {
+            log::warn!("Failed to set receive buffer size: {e}");
+        }
+    }
+}
 
-                // Release the lock before updating other connections
-                drop(inner);
+/// Ask the kernel to timestamp data as it arrives on a socket
+///
+/// Linux reports software receive timestamps through SO_TIMESTAMPNS, which,
+/// unlike SO_TIMESTAMPING, stamps datagrams at read time if they arrived
+/// before timestamping was switched on.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn enable_receive_timestamps(socket: &socket2::SockRef<'_>) {
+    set_int_option(
+        socket,
+        libc::SOL_SOCKET,
+        libc::SO_TIMESTAMPNS,
+        1,
+        "SO_TIMESTAMPNS",
+    );
+}
 
-                // Get all connections in the group
-                let connections = group_clone.get_connections().await;
+/// Ask the kernel to timestamp data as it arrives on a socket
+#[cfg(target_vendor = "apple")]
+pub(crate) fn enable_receive_timestamps(socket: &socket2::SockRef<'_>) {
+    set_int_option(
+        socket,
+        libc::SOL_SOCKET,
+        libc::SO_TIMESTAMP,
+        1,
+        "SO_TIMESTAMP",
+    );
+}
 
-                // Update property on all connections in parallel
-                let mut update_tasks = Vec::new();
-                for conn_inner in connections {
-                    let key = key_clone.clone();
-                    let val = value_clone.clone();
-                    let task = tokio::spawn(async move {
-                        let mut inner = conn_inner.write().await;
-                        let _ = inner.properties.set(&key, val);
-                    });
-                    update_tasks.push(task);
-                }
+#[cfg(not(any(target_os = "linux", target_os = "android", target_vendor = "apple")))]
+pub(crate) fn enable_receive_timestamps(_socket: &socket2::SockRef<'_>) {
+    log::debug!("Receive timestamps not supported on this platform");
+}
 
-                // Wait for all updates
-                for task in update_tasks {
-                    let _ = task.await;
-                }
+/// Ask the kernel to report the traffic class of each datagram received
+///
+/// Both options are set, as a dual-stack IPv6 socket receives IPv4
+/// datagrams too; the one not applying to the socket fails quietly.
+#[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+pub(crate) fn enable_receive_traffic_class(socket: &socket2::SockRef<'_>) {
+    use std::os::unix::io::AsRawFd;
+
+    let enable = |level, name| {
+        let value: libc::c_int = 1;
+        // SAFETY: the fd is valid for the lifetime of the borrowed socket and
+        // the option value points to a c_int of the length passed
+        unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                level,
+                name,
+                &value as *const libc::c_int as *const libc::c_void,
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            ) == 0
+        }
+    };
+    let ipv4 = enable(libc::IPPROTO_IP, libc::IP_RECVTOS);
+    let ipv6 = enable(libc::IPPROTO_IPV6, libc::IPV6_RECVTCLASS);
+    if !ipv4 && !ipv6 {
+        log::warn!(
+            "Failed to enable traffic class reception: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
 
-                // Re-acquire lock to update this connection
-                inner = self.inner.write().await;
-            }
+#[cfg(not(any(target_os = "linux", target_os = "android", target_vendor = "apple")))]
+pub(crate) fn enable_receive_traffic_class(_socket: &socket2::SockRef<'_>) {
+    log::debug!("Traffic class reception not supported on this platform");
+}
+
+/// Ask the kernel to report the destination address and arrival interface
+/// of each datagram received, as IP_PKTINFO and IPV6_PKTINFO
+///
+/// As with the traffic class, both options are set for dual-stack sockets.
+#[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+pub(crate) fn enable_receive_packet_info(socket: &socket2::SockRef<'_>) {
+    use std::os::unix::io::AsRawFd;
+
+    let enable = |level, name| {
+        let value: libc::c_int = 1;
+        // SAFETY: the fd is valid for the lifetime of the borrowed socket and
+        // the option value points to a c_int of the length passed
+        unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                level,
+                name,
+                &value as *const libc::c_int as *const libc::c_void,
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            ) == 0
         }
+    };
+    let ipv4 = enable(libc::IPPROTO_IP, libc::IP_PKTINFO);
+    let ipv6 = enable(libc::IPPROTO_IPV6, libc::IPV6_RECVPKTINFO);
+    if !ipv4 && !ipv6 {
+        log::warn!(
+            "Failed to enable packet info reception: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
 
-        // Set the property on this connection
-        inner.properties.set(key, value.clone())?;
+#[cfg(not(any(target_os = "linux", target_os = "android", target_vendor = "apple")))]
+pub(crate) fn enable_receive_packet_info(_socket: &socket2::SockRef<'_>) {
+    log::debug!("Packet info reception not supported on this platform");
+}
 
-        // Apply property changes that need immediate action
-        match key {
-            "connTimeout" => {
-                // RFC 8.2.3: tcp.userTimeoutChangeable becomes false when connTimeout is used
-                if matches!(
-                    value,
-                    ConnectionProperty::ConnTimeout(TimeoutValue::Duration(_))
-                ) {
-                    inner.properties.set(
-                        "tcp.userTimeoutChangeable",
-                        ConnectionProperty::TcpUserTimeoutChangeable(false),
-                    )?;
-                }
+/// Name of the interface with the given index
+#[cfg(unix)]
+fn interface_name(index: u32) -> Option<String> {
+    let mut name = [0 as libc::c_char; libc::IF_NAMESIZE];
+    // SAFETY: the buffer has the IF_NAMESIZE bytes if_indextoname writes at most
+    let found = unsafe { libc::if_indextoname(index, name.as_mut_ptr()) };
+    if found.is_null() {
+        return None;
+    }
+    // SAFETY: on success the buffer holds a NUL-terminated name
+    let name = unsafe { std::ffi::CStr::from_ptr(name.as_ptr()) };
+    Some(name.to_string_lossy().into_owned())
+}
 
-                // Apply timeout to underlying TCP stream
-                if let (Some(ref _stream), ConnectionProperty::ConnTimeout(timeout_val)) =
-                    (&inner.tcp_stream, &value)
-                {
-                    match timeout_val {
-                        TimeoutValue::Duration(duration) => {
-                            // TCP connection timeout is handled at the application level
-                            // Store for future operations
-                            log::debug!("Connection timeout set to {duration:?}");
-                        }
-                        TimeoutValue::Disabled => {
-                            log::debug!("Connection timeout disabled");
-                        }
-                    }
-                }
-            }
-            "keepAliveTimeout" => {
-                // Configure keep-alive on TCP stream
-                #[allow(unused_variables)]
-                if let Some(ref stream) = inner.tcp_stream {
-                    #[allow(unused_variables)]
-                    if let ConnectionProperty::KeepAliveTimeout(timeout_val) = &value {
-                        // Get the raw socket to set keep-alive options
-                        #[cfg(unix)]
-                        {
-                            use socket2::{Socket, TcpKeepalive};
-                            use std::os::unix::io::{AsRawFd, FromRawFd};
-
-                            let fd = stream.as_raw_fd();
-                            let socket = unsafe { Socket::from_raw_fd(fd) };
-
-                            match timeout_val {
-                                TimeoutValue::Duration(duration) => {
-                                    // Enable keep-alive with the specified interval
-                                    let keepalive = TcpKeepalive::new()
-                                        .with_time(*duration)
-                                        .with_interval(*duration);
-
-                                    if let Err(e) = socket.set_tcp_keepalive(&keepalive) {
-                                        log::warn!("Failed to set TCP keep-alive: {e}");
-                                    } else {
-                                        log::debug!("TCP keep-alive set to {duration:?}");
-                                    }
-                                }
-                                TimeoutValue::Disabled => {
-                                    // Disable keep-alive
-                                    if let Err(e) = socket.set_keepalive(false) {
-                                        log::warn!("Failed to disable TCP keep-alive: {e}");
-                                    } else {
-                                        log::debug!("TCP keep-alive disabled");
-                                    }
-                                }
-                            }
+#[cfg(not(unix))]
+fn interface_name(_index: u32) -> Option<String> {
+    None
+}
 
-                            // Important: forget the socket to prevent double-close
-                            std::mem::forget(socket);
-                        }
+#[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+fn set_int_option(
+    socket: &socket2::SockRef<'_>,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: libc::c_int,
+    option: &str,
+) {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: the fd is valid for the lifetime of the borrowed socket and the
+    // option value points to a c_int of the length passed
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if result != 0 {
+        log::warn!(
+            "Failed to set {option}: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
 
-                        #[cfg(not(unix))]
-                        {
-                            log::warn!(
-                                "TCP keep-alive configuration not supported on this platform"
-                            );
-                        }
-                    }
-                }
-            }
-            "tcp.userTimeoutEnabled" => {
-                // Configure TCP User Timeout Option if supported
-                if let Some(ref _stream) = inner.tcp_stream {
-                    if let ConnectionProperty::TcpUserTimeoutEnabled(enabled) = &value {
-                        // Note: Actually setting TCP_USER_TIMEOUT socket option would require
-                        // platform-specific code and may not be available on all platforms
-                        log::debug!("TCP User Timeout enabled: {enabled}");
-                    }
-                }
-            }
-            "tcp.userTimeoutValue" => {
-                // Set the TCP User Timeout value
-                if let Some(ref _stream) = inner.tcp_stream {
-                    if let ConnectionProperty::TcpUserTimeoutValue(Some(duration)) = &value {
-                        // Note: Actually setting TCP_USER_TIMEOUT socket option would require
-                        // platform-specific code and may not be available on all platforms
-                        log::debug!("TCP User Timeout value set to: {duration:?}");
-                    }
-                }
-            }
-            _ => {}
-        }
+/// Fill in round-trip time and retransmissions from the kernel's TCP_INFO
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn read_tcp_info(stream: &TcpStream, stats: &mut ConnectionStats) {
+    use std::os::fd::AsRawFd;
+
+    let mut info: libc::tcp_info = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::tcp_info>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_INFO,
+            &mut info as *mut _ as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret != 0 {
+        log::debug!(
+            "Failed to read TCP_INFO: {}",
+            std::io::Error::last_os_error()
+        );
+        return;
+    }
+    stats.rtt = Some(Duration::from_micros(info.tcpi_rtt.into()));
+    stats.rtt_variance = Some(Duration::from_micros(info.tcpi_rttvar.into()));
+    stats.retransmits = Some(info.tcpi_total_retrans);
+}
 
-        Ok(())
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn read_tcp_info(_stream: &TcpStream, _stats: &mut ConnectionStats) {}
+
+/// Switch a stream to TCP-LP, the kernel's low-priority congestion control,
+/// returning the algorithm it replaces, or None if the kernel lacks it
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn enable_low_priority_congestion(stream: &TcpStream) -> Option<String> {
+    use std::os::fd::AsRawFd;
+
+    let mut name = [0u8; 16];
+    let mut len = name.len() as libc::socklen_t;
+    // SAFETY: the buffer has the length passed
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_CONGESTION,
+            name.as_mut_ptr() as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret != 0 {
+        return None;
     }
+    let previous = String::from_utf8_lossy(&name[..len as usize])
+        .trim_end_matches('\0')
+        .to_string();
+    set_tcp_congestion(stream, "lp").then_some(previous)
+}
 
-    /// Get all connection properties
-    /// RFC Section 8: ConnectionProperties := Connection.GetProperties()
-    pub async fn get_properties(&self) -> ConnectionProperties {
-        let inner = self.inner.read().await;
-        let mut props = inner.properties.clone();
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn enable_low_priority_congestion(_stream: &TcpStream) -> Option<String> {
+    None
+}
 
-        // Get the transport properties to check direction
-        let direction = inner.transport_properties.selection_properties.direction;
+/// Select a stream's congestion control algorithm by name
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn set_tcp_congestion(stream: &TcpStream, name: &str) -> bool {
+    use std::os::fd::AsRawFd;
+
+    // SAFETY: the option value points to the name's bytes, of the length passed
+    let ret = unsafe {
+        libc::setsockopt(
+            stream.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_CONGESTION,
+            name.as_ptr() as *const libc::c_void,
+            name.len() as libc::socklen_t,
+        )
+    };
+    if ret != 0 {
+        log::debug!(
+            "Failed to set TCP congestion control {name}: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+    ret == 0
+}
 
-        // RFC 8.1.11.2: Can Send Data
-        // Check against direction Selection Property and Final message state
-        let can_send = match inner.state {
-            ConnectionState::Established => {
-                // Can't send if:
-                // 1. Direction is unidirectional receive, or
-                // 2. A Final message was already sent
-                match direction {
-                    CommunicationDirection::UnidirectionalReceive => false,
-                    _ => !inner.final_message_sent,
-                }
-            }
-            ConnectionState::Establishing => false, // Could buffer, but say false for now
-            _ => false,
-        };
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn set_tcp_congestion(_stream: &TcpStream, _name: &str) -> bool {
+    false
+}
 
-        // RFC 8.1.11.3: Can Receive Data
-        // Check against direction Selection Property and Final message state
-        let can_receive = match inner.state {
-            ConnectionState::Established => {
-                // Can't receive if:
-                // 1. Direction is unidirectional send, or
-                // 2. A Final message was already received (implementation specific)
-                match direction {
-                    CommunicationDirection::UnidirectionalSend => false,
-                    _ => !inner.final_message_received,
-                }
-            }
-            _ => false,
+/// Check whether the peer of a stream is still acknowledging, from the
+/// kernel's TCP_INFO: a dead path leaves retransmissions or zero-window
+/// probes unanswered, while a peer with a closed window answers its probes
+///
+/// Where TCP_INFO is unavailable the peer is assumed to be acknowledging.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn ack_probe(stream: &TcpStream) -> impl Fn() -> bool {
+    use std::os::fd::AsRawFd;
+
+    let fd = stream.as_raw_fd();
+    move || {
+        let mut info: libc::tcp_info = unsafe { std::mem::zeroed() };
+        let mut len = std::mem::size_of::<libc::tcp_info>() as libc::socklen_t;
+        // SAFETY: the probe only runs while the write borrowing the stream
+        // is in flight, so the descriptor is open
+        let ret = unsafe {
+            libc::getsockopt(
+                fd,
+                libc::IPPROTO_TCP,
+                libc::TCP_INFO,
+                &mut info as *mut _ as *mut libc::c_void,
+                &mut len,
+            )
         };
+        ret != 0 || (info.tcpi_retransmits == 0 && info.tcpi_probes == 0)
+    }
+}
 
-        // Update the basic read-only properties
-        props.update_readonly(inner.state, can_send, can_receive);
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn ack_probe(_stream: &TcpStream) -> impl Fn() -> bool {
+    || true
+}
 
-        // Update MTU-related properties if we have a TCP stream
-        if let Some(ref stream) = inner.tcp_stream {
-            // RFC 8.1.11.4: Maximum Message Size Before Fragmentation
-            // Query actual MSS from socket
-            let mss = self.get_tcp_mss(stream).await.unwrap_or(1460); // Default to typical value if query fails
+/// Read from a TCP stream without blocking, with the kernel's receive timestamp
+fn try_read_timestamped(
+    stream: &TcpStream,
+    buf: &mut [u8],
+    timestamps: bool,
+) -> std::io::Result<(usize, Option<Instant>)> {
+    if !timestamps {
+        return stream.try_read(buf).map(|n| (n, None));
+    }
+    stream.try_io(Interest::READABLE, || {
+        recv_timestamped(&socket2::SockRef::from(stream), buf)
+            .map(|received| (received.length, received.timestamp))
+    })
+}
 
-            props.properties.insert(
-                "singularTransmissionMsgMaxLen".to_string(),
-                ConnectionProperty::SingularTransmissionMsgMaxLen(Some(mss)),
-            );
+/// Wait for data on a TCP stream and read it, with the kernel's receive timestamp
+async fn read_timestamped(
+    stream: &TcpStream,
+    buf: &mut [u8],
+    timestamps: bool,
+) -> std::io::Result<(usize, Option<Instant>)> {
+    loop {
+        stream.readable().await?;
+        match try_read_timestamped(stream, buf, timestamps) {
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => continue,
+            result => return result,
+        }
+    }
+}
 
-            // RFC 8.1.11.5: Maximum Message Size on Send
-            // For TCP, there's no inherent limit (streaming protocol)
-            // Return 0 if sending is not possible
-            let send_msg_max = if can_send {
-                None // No limit for TCP
-            } else {
-                Some(0) // Cannot send
-            };
-            props.properties.insert(
-                "sendMsgMaxLen".to_string(),
-                ConnectionProperty::SendMsgMaxLen(send_msg_max),
-            );
+/// What recvmsg reported besides the data
+pub(crate) struct ReceiveInfo {
+    /// Number of bytes read
+    pub(crate) length: usize,
+    pub(crate) source: Option<SocketAddr>,
+    /// Receive timestamp, if the kernel attached one
+    pub(crate) timestamp: Option<Instant>,
+    /// IP traffic class byte, if reception of it was enabled
+    pub(crate) traffic_class: Option<u8>,
+    /// Length of each datagram the kernel coalesced into this read, if any
+    pub(crate) segment_size: Option<usize>,
+    /// Where the datagram arrived, if reception of packet info was enabled
+    pub(crate) packet_info: Option<PacketInfo>,
+}
 
-            // RFC 8.1.11.6: Maximum Message Size on Receive
-            // For TCP, there's no inherent limit (streaming protocol)
-            // Return 0 if receiving is not possible
-            let recv_msg_max = if can_receive {
-                None // No limit for TCP
-            } else {
-                Some(0) // Cannot receive
+/// The address a datagram was sent to and the interface it arrived on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PacketInfo {
+    pub(crate) destination: IpAddr,
+    pub(crate) interface_index: u32,
+}
+
+/// Receive from a socket with recvmsg, along with the source address and
+/// whatever control messages the socket was asked to attach
+#[cfg(unix)]
+pub(crate) fn recv_timestamped(
+    socket: &socket2::SockRef<'_>,
+    buf: &mut [u8],
+) -> std::io::Result<ReceiveInfo> {
+    use std::os::unix::io::AsRawFd;
+
+    // Room for timestamp, traffic class, segment size and packet info
+    // control messages and their headers
+    let mut control = [0u64; 24];
+    let mut timestamp = None;
+    let mut traffic_class = None;
+    let mut segment_size = None;
+    let mut packet_info = None;
+
+    // SAFETY: msghdr points at buffers that outlive the call, with their
+    // true lengths, and control messages are only read within msg_controllen
+    let (n, source) = unsafe {
+        socket2::SockAddr::try_init(|storage, len| {
+            let mut iov = libc::iovec {
+                iov_base: buf.as_mut_ptr().cast(),
+                iov_len: buf.len(),
             };
-            props.properties.insert(
-                "recvMsgMaxLen".to_string(),
-                ConnectionProperty::RecvMsgMaxLen(recv_msg_max),
-            );
-        } else {
-            // No stream - set appropriate values based on connection state
-            if inner.state == ConnectionState::Establishing {
-                // Don't add properties for connections that haven't been established yet
-                // This matches the expectation of test_mss_property_not_set_before_connection
-            } else {
-                // For closed connections or other states, add properties with 0 values
-                props.properties.insert(
-                    "singularTransmissionMsgMaxLen".to_string(),
-                    ConnectionProperty::SingularTransmissionMsgMaxLen(None),
-                ); // Not applicable
-                props.properties.insert(
-                    "sendMsgMaxLen".to_string(),
-                    ConnectionProperty::SendMsgMaxLen(Some(0)),
-                ); // Cannot send without stream
-                props.properties.insert(
-                    "recvMsgMaxLen".to_string(),
-                    ConnectionProperty::RecvMsgMaxLen(Some(0)),
-                ); // Cannot receive without stream
+            let mut msg: libc::msghdr = std::mem::zeroed();
+            msg.msg_name = storage.cast();
+            msg.msg_namelen = *len;
+            msg.msg_iov = &mut iov;
+            msg.msg_iovlen = 1;
+            msg.msg_control = control.as_mut_ptr().cast();
+            msg.msg_controllen = std::mem::size_of_val(&control) as _;
+
+            let n = libc::recvmsg(socket.as_raw_fd(), &mut msg, 0);
+            if n < 0 {
+                return Err(std::io::Error::last_os_error());
             }
-        }
+            *len = msg.msg_namelen;
 
-        props
-    }
+            let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+            while !cmsg.is_null() {
+                if let Some(time) = control_timestamp(&*cmsg) {
+                    timestamp = Some(time);
+                }
+                if let Some(class) = control_traffic_class(&*cmsg) {
+                    traffic_class = Some(class);
+                }
+                if let Some(size) = udp_offload::control_segment_size(&*cmsg) {
+                    segment_size = Some(size);
+                }
+                if let Some(info) = control_packet_info(&*cmsg) {
+                    packet_info = Some(info);
+                }
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+            Ok(n as usize)
+        })?
+    };
+
+    Ok(ReceiveInfo {
+        length: n,
+        source: source.as_socket(),
+        timestamp: timestamp.map(system_time_to_instant),
+        traffic_class,
+        segment_size,
+        packet_info,
+    })
+}
 
-    /// Get a specific connection property value
-    pub async fn get_property(&self, key: &str) -> Option<ConnectionProperty> {
-        let props = self.get_properties().await;
-        props.get(key).cloned()
-    }
+#[cfg(not(unix))]
+pub(crate) fn recv_timestamped(
+    socket: &socket2::SockRef<'_>,
+    buf: &mut [u8],
+) -> std::io::Result<ReceiveInfo> {
+    // SAFETY: initialised bytes are valid MaybeUninit<u8> and recv only writes to them
+    let buf = unsafe { &mut *(buf as *mut [u8] as *mut [std::mem::MaybeUninit<u8>]) };
+    let (n, source) = socket.recv_from(buf)?;
+    Ok(ReceiveInfo {
+        length: n,
+        source: source.as_socket(),
+        timestamp: None,
+        traffic_class: None,
+        segment_size: None,
+        packet_info: None,
+    })
+}
 
-    /// Get the connection group ID if this connection is part of a group
-    pub async fn connection_group_id(&self) -> Option<ConnectionGroupId> {
-        let inner = self.inner.read().await;
-        inner.connection_group.as_ref().map(|g| g.id)
+/// Read the traffic class from an IP_TOS (IP_RECVTOS on Apple platforms) or
+/// IPV6_TCLASS control message
+///
+/// # Safety
+/// The control message must lie within a buffer filled in by recvmsg.
+#[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+unsafe fn control_traffic_class(cmsg: &libc::cmsghdr) -> Option<u8> {
+    #[cfg(target_vendor = "apple")]
+    const IPV4_TOS: libc::c_int = libc::IP_RECVTOS;
+    #[cfg(not(target_vendor = "apple"))]
+    const IPV4_TOS: libc::c_int = libc::IP_TOS;
+
+    let data = libc::CMSG_DATA(cmsg);
+    match (cmsg.cmsg_level, cmsg.cmsg_type) {
+        // A single byte
+        (libc::IPPROTO_IP, IPV4_TOS) => Some(*data),
+        // An int
+        (libc::IPPROTO_IPV6, libc::IPV6_TCLASS) => {
+            Some(std::ptr::read_unaligned(data as *const libc::c_int) as u8)
+        }
+        _ => None,
     }
+}
 
-    /// Check if this connection is part of a connection group
-    pub async fn is_grouped(&self) -> bool {
-        let inner = self.inner.read().await;
-        inner.connection_group.is_some()
-    }
+#[cfg(all(
+    unix,
+    not(any(target_os = "linux", target_os = "android", target_vendor = "apple"))
+))]
+unsafe fn control_traffic_class(_cmsg: &libc::cmsghdr) -> Option<u8> {
+    None
+}
 
-    /// Get the number of connections in this connection's group
-    pub async fn group_connection_count(&self) -> Option<u64> {
-        let inner = self.inner.read().await;
-        inner
-            .connection_group
-            .as_ref()
-            .map(|g| g.connection_count())
+/// Read the destination address and arrival interface from an IP_PKTINFO or
+/// IPV6_PKTINFO control message
+///
+/// # Safety
+/// The control message must lie within a buffer filled in by recvmsg.
+#[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+unsafe fn control_packet_info(cmsg: &libc::cmsghdr) -> Option<PacketInfo> {
+    let data = libc::CMSG_DATA(cmsg);
+    match (cmsg.cmsg_level, cmsg.cmsg_type) {
+        (libc::IPPROTO_IP, libc::IP_PKTINFO) => {
+            let info = std::ptr::read_unaligned(data as *const libc::in_pktinfo);
+            Some(PacketInfo {
+                destination: IpAddr::from(u32::from_be(info.ipi_addr.s_addr).to_be_bytes()),
+                interface_index: info.ipi_ifindex as u32,
+            })
+        }
+        (libc::IPPROTO_IPV6, libc::IPV6_PKTINFO) => {
+            let info = std::ptr::read_unaligned(data as *const libc::in6_pktinfo);
+            Some(PacketInfo {
+                destination: IpAddr::from(info.ipi6_addr.s6_addr),
+                interface_index: info.ipi6_ifindex as u32,
+            })
+        }
+        _ => None,
     }
+}
 
-    /// Close all connections in the group
-    /// RFC Section 10
-    pub async fn close_group(&self) -> Result<()> {
-        // Check if we have a group
-        let has_group = {
-            let inner = self.inner.read().await;
-            inner.connection_group.is_some()
-        };
+#[cfg(all(
+    unix,
+    not(any(target_os = "linux", target_os = "android", target_vendor = "apple"))
+))]
+unsafe fn control_packet_info(_cmsg: &libc::cmsghdr) -> Option<PacketInfo> {
+    None
+}
 
-        if has_group {
-            let inner = self.inner.read().await;
-            let group = inner.connection_group.as_ref().unwrap();
-            // Get all connections in the group
-            let connections = group.get_connections().await;
-            drop(inner); // Release lock before closing connections
+/// Read the receive timestamp from an SCM_TIMESTAMPNS control message
+///
+/// # Safety
+/// The control message must lie within a buffer filled in by recvmsg.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+unsafe fn control_timestamp(cmsg: &libc::cmsghdr) -> Option<std::time::SystemTime> {
+    if cmsg.cmsg_level != libc::SOL_SOCKET || cmsg.cmsg_type != libc::SCM_TIMESTAMPNS {
+        return None;
+    }
+    let ts = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::timespec);
+    Some(std::time::UNIX_EPOCH + Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}
 
-            // Close all connections in parallel
-            let mut close_tasks = Vec::new();
-            for conn_inner in connections {
-                let task = tokio::spawn(async move {
-                    let mut inner = conn_inner.write().await;
+/// Read the receive timestamp from an SCM_TIMESTAMP control message
+///
+/// # Safety
+/// The control message must lie within a buffer filled in by recvmsg.
+#[cfg(target_vendor = "apple")]
+unsafe fn control_timestamp(cmsg: &libc::cmsghdr) -> Option<std::time::SystemTime> {
+    if cmsg.cmsg_level != libc::SOL_SOCKET || cmsg.cmsg_type != libc::SCM_TIMESTAMP {
+        return None;
+    }
+    let tv = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::timeval);
+    Some(std::time::UNIX_EPOCH + Duration::new(tv.tv_sec as u64, tv.tv_usec as u32 * 1000))
+}
 
-                    match inner.state {
-                        ConnectionState::Established | ConnectionState::Establishing => {
-                            inner.state = ConnectionState::Closing;
+#[cfg(all(
+    unix,
+    not(any(target_os = "linux", target_os = "android", target_vendor = "apple"))
+))]
+unsafe fn control_timestamp(_cmsg: &libc::cmsghdr) -> Option<std::time::SystemTime> {
+    None
+}
 
-                            // Clear any pending batched messages before closing
-                            inner.batched_messages.clear();
+/// Place a wall-clock kernel timestamp on the monotonic clock
+///
+/// The kernel stamps packets with the realtime clock, so the timestamp is
+/// converted by its age, which is close enough for latency measurement.
+fn system_time_to_instant(time: std::time::SystemTime) -> Instant {
+    let age = std::time::SystemTime::now()
+        .duration_since(time)
+        .unwrap_or_default();
+    let now = Instant::now();
+    now.checked_sub(age).unwrap_or(now)
+}
 
-                            // Perform graceful close on TCP stream
-                            if let Some(ref mut stream) = inner.tcp_stream {
-                                let _ = stream.flush().await;
-                                let _ = stream.shutdown().await;
-                            }
+/// Apply keepAliveTimeout to a TCP socket (RFC Section 8.1.4)
+fn set_keep_alive(stream: &TcpStream, timeout: TimeoutValue) {
+    let socket = socket2::SockRef::from(stream);
+    let result = match timeout {
+        TimeoutValue::Duration(interval) => socket.set_tcp_keepalive(
+            &socket2::TcpKeepalive::new()
+                .with_time(interval)
+                .with_interval(interval),
+        ),
+        TimeoutValue::Disabled => socket.set_keepalive(false),
+    };
+    if let Err(e) = result {
+        log::warn!("Failed to set TCP keep-alive: {e}");
+    }
+}
 
-                            inner.state = ConnectionState::Closed;
-                            inner.pending_messages.clear();
-                            inner.receive_buffer.clear();
-                            inner.tcp_stream = None;
+/// Set TCP_USER_TIMEOUT, where the platform supports it; None restores the default
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "fuchsia"))]
+fn set_user_timeout(stream: &TcpStream, timeout: Option<Duration>) {
+    if let Err(e) = socket2::SockRef::from(stream).set_tcp_user_timeout(timeout) {
+        log::warn!("Failed to set TCP_USER_TIMEOUT: {e}");
+    }
+}
 
-                            // Note: We don't decrement connection count here as it's handled by each connection
-                        }
-                        _ => {} // Already closing or closed
-                    }
-                });
-                close_tasks.push(task);
-            }
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "fuchsia")))]
+fn set_user_timeout(_stream: &TcpStream, timeout: Option<Duration>) {
+    if timeout.is_some() {
+        log::debug!("TCP user timeout not supported on this platform");
+    }
+}
 
-            // Wait for all connections to close
-            for task in close_tasks {
-                let _ = task.await;
-            }
+/// Set the IP TTL, or the IPv6 hop limit, of a TCP stream's packets; None
+/// restores the system default
+fn set_hop_limit(stream: &TcpStream, hop_limit: Option<u8>) {
+    let socket = socket2::SockRef::from(stream);
+    let ipv6 = matches!(stream.local_addr(), Ok(SocketAddr::V6(_)));
+    let result = match (hop_limit, ipv6) {
+        (Some(hop_limit), false) => socket.set_ttl(hop_limit.into()),
+        (Some(hop_limit), true) => socket.set_unicast_hops_v6(hop_limit.into()),
+        (None, _) => restore_hop_limit(&socket, ipv6),
+    };
+    if let Err(e) = result {
+        log::warn!("Failed to set the hop limit: {e}");
+    }
+}
 
-            // Send Closed event for this connection
-            let _ = self.event_sender.send(ConnectionEvent::Closed);
-            Ok(())
-        } else {
-            // No group, just close this connection
-            self.close().await
+/// Return a socket to the system's default TTL or hop limit, which -1 selects
+///
+/// Only Linux accepts -1 for the IPv4 TTL; IPv6 takes it everywhere (RFC 3493).
+#[cfg(unix)]
+fn restore_hop_limit(socket: &socket2::SockRef<'_>, ipv6: bool) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let (level, name) = match ipv6 {
+        true => (libc::IPPROTO_IPV6, libc::IPV6_UNICAST_HOPS),
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        false => (libc::IPPROTO_IP, libc::IP_TTL),
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        false => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "Restoring the default TTL not supported on this platform",
+            ))
         }
+    };
+    let value: libc::c_int = -1;
+    // SAFETY: the fd is valid for the lifetime of the borrowed socket and the
+    // option value points to a c_int of the length passed
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error());
     }
+    Ok(())
+}
 
-    /// Abort all connections in the group
-    pub async fn abort_group(&self) -> Result<()> {
-        // Check if we have a group
-        let has_group = {
-            let inner = self.inner.read().await;
-            inner.connection_group.is_some()
-        };
+#[cfg(not(unix))]
+fn restore_hop_limit(_socket: &socket2::SockRef<'_>, _ipv6: bool) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "Restoring the default TTL not supported on this platform",
+    ))
+}
 
-        if has_group {
-            let inner = self.inner.read().await;
-            let group = inner.connection_group.as_ref().unwrap();
-            // Get all connections in the group
-            let connections = group.get_connections().await;
-            drop(inner); // Release lock before aborting connections
+/// Map connPriority and connCapacityProfile onto a TCP socket
+///
+/// RFC Section 8.1.2 leaves the effect of connPriority to the implementation;
+/// here it becomes SO_PRIORITY on Linux, which selects the egress queue band.
+/// The capacity profile sets the DSCP suggested by RFC Section 8.1.6.
+/// This crate has no QUIC or SCTP stack, so there are no stream priorities
+/// to map.
+fn apply_traffic_class(stream: &TcpStream, properties: &ConnectionProperties) {
+    let socket = socket2::SockRef::from(stream);
+
+    if let Some(ConnectionProperty::ConnPriority(priority)) = properties.get("connPriority") {
+        set_socket_priority(&socket, socket_priority(*priority));
+    }
 
-            // Abort all connections in parallel
-            let mut abort_tasks = Vec::new();
-            for conn_inner in connections {
-                let task = tokio::spawn(async move {
-                    let mut inner = conn_inner.write().await;
+    if let Some(ConnectionProperty::ConnCapacityProfile(profile)) =
+        properties.get("connCapacityProfile")
+    {
+        set_dscp(&socket, capacity_profile_dscp(*profile));
+    }
+}
 
-                    let was_not_closed = inner.state != ConnectionState::Closed;
-                    if was_not_closed {
-                        // Immediately set state to Closed
-                        inner.state = ConnectionState::Closed;
+/// Convert connPriority to a socket priority band
+///
+/// Lower connPriority values are more important. The default of 100 and
+/// anything above keeps band 0; values below it map linearly onto bands 6
+/// (priority 0) to 1. Bands above 6 need CAP_NET_ADMIN, so they are not used.
+pub(crate) fn socket_priority(conn_priority: u32) -> u32 {
+    if conn_priority >= 100 {
+        0
+    } else {
+        6 - conn_priority * 6 / 100
+    }
+}
 
-                        // Force close the TCP stream
-                        if let Some(stream) = inner.tcp_stream.take() {
-                            drop(stream); // This sends TCP RST
-                        }
+/// DSCP code point for a capacity profile (RFC Section 8.1.6)
+/// Scavenger traffic is marked Lower Effort (RFC 8622).
+pub(crate) fn capacity_profile_dscp(profile: CapacityProfile) -> u8 {
+    match profile {
+        CapacityProfile::Default => 0,                   // DF
+        CapacityProfile::Scavenger => 1,                 // LE
+        CapacityProfile::LowLatencyInteractive => 34,    // AF41
+        CapacityProfile::LowLatencyNonInteractive => 18, // AF21
+        CapacityProfile::ConstantRateStreaming => 26,    // AF31
+        CapacityProfile::CapacitySeeking => 10,          // AF11
+    }
+}
 
-                        // Clear all buffers
-                        inner.pending_messages.clear();
-                        inner.batched_messages.clear();
-                        inner.receive_buffer.clear();
-                    }
-                });
-                abort_tasks.push(task);
-            }
+/// DSCP code point for a per-message capacity profile (RFC Section 9.1.3.8)
+///
+/// Scavenger traffic is marked Lower Effort (RFC 8622).
+pub(crate) fn message_capacity_profile_dscp(profile: MessageCapacityProfile) -> u8 {
+    match profile {
+        MessageCapacityProfile::LowLatencyInteractive => 34, // AF41
+        MessageCapacityProfile::LowLatencyNonInteractive => 18, // AF21
+        MessageCapacityProfile::ConstantRate => 26,          // AF31
+        MessageCapacityProfile::Scavenger => 1,              // LE
+    }
+}
 
-            // Wait for all connections to abort
-            for task in abort_tasks {
-                let _ = task.await;
-            }
+/// Per-datagram IP header fields, which Listener flows sharing a socket
+/// cannot set as socket options
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct DatagramMarks {
+    dscp: Option<u8>,
+    hop_limit: Option<u8>,
+    source: Option<PacketInfo>,
+}
 
-            // Send ConnectionError event for this connection
-            let _ = self.event_sender.send(ConnectionEvent::ConnectionError(
-                "Connection group aborted".to_string(),
-            ));
-            Ok(())
-        } else {
-            // No group, just abort this connection
-            self.abort().await
-        }
+impl DatagramMarks {
+    fn is_unmarked(&self) -> bool {
+        self.dscp.is_none() && self.hop_limit.is_none() && self.source.is_none()
     }
+}
 
-    // Internal method to update state
-    #[allow(dead_code)]
-    pub(crate) async fn set_state(&self, state: ConnectionState) {
-        let mut inner = self.inner.write().await;
-        inner.state = state;
-
-        if state == ConnectionState::Established {
-            let _ = self.event_sender.send(ConnectionEvent::Ready);
+/// Send a datagram with its own traffic class, hop limit and source address,
+/// leaving the socket's defaults for other datagrams untouched
+///
+/// They are attached as IP_TOS and IP_TTL, or IPV6_TCLASS and IPV6_HOPLIMIT,
+/// ancillary data, and the source as IP_PKTINFO or IPV6_PKTINFO according to
+/// the socket's family. Apple platforms take no TTL for IPv4 datagrams this
+/// way, so those keep the socket's.
+#[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+async fn send_to_marked(
+    socket: &UdpSocket,
+    data: &[u8],
+    peer: SocketAddr,
+    marks: DatagramMarks,
+) -> std::io::Result<usize> {
+    use std::os::unix::io::AsRawFd;
+
+    // IPv4 peers of a dual-stack socket are addressed IPv4-mapped, and
+    // their datagrams take the IPv4 options
+    let ipv4 = match peer {
+        SocketAddr::V4(_) => true,
+        SocketAddr::V6(v6) => v6.ip().to_ipv4_mapped().is_some(),
+    };
+    let mut options = Vec::with_capacity(2);
+    if let Some(dscp) = marks.dscp {
+        let traffic_class = libc::c_int::from(dscp) << 2;
+        options.push(match ipv4 {
+            true => (libc::IPPROTO_IP, libc::IP_TOS, traffic_class),
+            false => (libc::IPPROTO_IPV6, libc::IPV6_TCLASS, traffic_class),
+        });
+    }
+    if let Some(hop_limit) = marks.hop_limit {
+        let hop_limit = libc::c_int::from(hop_limit);
+        match ipv4 {
+            #[cfg(target_vendor = "apple")]
+            true => log::debug!("Per-datagram TTL not supported for IPv4 on this platform"),
+            #[cfg(not(target_vendor = "apple"))]
+            true => options.push((libc::IPPROTO_IP, libc::IP_TTL, hop_limit)),
+            false => options.push((libc::IPPROTO_IPV6, libc::IPV6_HOPLIMIT, hop_limit)),
         }
     }
+    let local = socket.local_addr()?;
+    let peer = match (local, peer) {
+        (SocketAddr::V6(_), SocketAddr::V4(v4)) => {
+            SocketAddr::new(v4.ip().to_ipv6_mapped().into(), v4.port())
+        }
+        _ => peer,
+    };
+    let address = socket2::SockAddr::from(peer);
+    let source = marks.source.map(|source| source_packet_info(local, source));
+
+    socket
+        .async_io(Interest::WRITABLE, || {
+            let mut control = [0u64; 16];
+            let mut iov = libc::iovec {
+                iov_base: data.as_ptr() as *mut libc::c_void,
+                iov_len: data.len(),
+            };
+            // SAFETY: msghdr points at buffers that outlive the call, with
+            // their true lengths, and the control messages, at most two ints
+            // and a packet info, are written within the control buffer, which
+            // has room for them
+            let n = unsafe {
+                let int_space = libc::CMSG_SPACE(std::mem::size_of::<libc::c_int>() as u32);
+                let source_space = match &source {
+                    Some(source) => libc::CMSG_SPACE(source.len() as u32),
+                    None => 0,
+                };
+                let mut msg: libc::msghdr = std::mem::zeroed();
+                msg.msg_name = address.as_ptr() as *mut libc::c_void;
+                msg.msg_namelen = address.len();
+                msg.msg_iov = &mut iov;
+                msg.msg_iovlen = 1;
+                if !options.is_empty() || source.is_some() {
+                    msg.msg_control = control.as_mut_ptr().cast();
+                    msg.msg_controllen =
+                        (int_space as usize * options.len() + source_space as usize) as _;
+                }
 
-    // Internal method to set TCP stream (for listener)
-    pub(crate) async fn set_tcp_stream(&mut self, stream: TcpStream) {
-        let mut inner = self.inner.write().await;
-        inner.tcp_stream = Some(stream);
-        inner.state = ConnectionState::Established;
-        drop(inner);
+                let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+                for &(level, kind, value) in &options {
+                    (*cmsg).cmsg_level = level;
+                    (*cmsg).cmsg_type = kind;
+                    (*cmsg).cmsg_len =
+                        libc::CMSG_LEN(std::mem::size_of::<libc::c_int>() as u32) as _;
+                    std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut libc::c_int, value);
+                    cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+                }
+                if let Some(source) = &source {
+                    source.write(cmsg);
+                }
 
-        // Start background reading task
-        let _ = self.start_reading_task().await;
+                libc::sendmsg(socket.as_raw_fd(), &msg, 0)
+            };
+            if n < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(n as usize)
+        })
+        .await
+}
 
-        let _ = self.event_sender.send(ConnectionEvent::Ready);
+/// Source address control message for a datagram
+#[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+enum SourcePacketInfo {
+    V4(libc::in_pktinfo),
+    V6(libc::in6_pktinfo),
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+impl SourcePacketInfo {
+    fn len(&self) -> usize {
+        match self {
+            Self::V4(_) => std::mem::size_of::<libc::in_pktinfo>(),
+            Self::V6(_) => std::mem::size_of::<libc::in6_pktinfo>(),
+        }
     }
 
-    /// Emit a SoftError event
-    /// RFC Section 8.3.1 - Soft Errors
-    pub(crate) async fn emit_soft_error(&self, error_message: String) {
-        // Only emit if soft error notifications are requested
-        let inner = self.inner.read().await;
-        if inner
-            .transport_properties
-            .selection_properties
-            .soft_error_notify
-            == Preference::Require
-            || inner
-                .transport_properties
-                .selection_properties
-                .soft_error_notify
-                == Preference::Prefer
-        {
-            drop(inner);
-            let _ = self
-                .event_sender
-                .send(ConnectionEvent::SoftError(error_message));
+    /// Fill in the control message
+    ///
+    /// # Safety
+    /// The control message must have room for the packet info.
+    unsafe fn write(&self, cmsg: *mut libc::cmsghdr) {
+        (*cmsg).cmsg_len = libc::CMSG_LEN(self.len() as u32) as _;
+        match self {
+            Self::V4(info) => {
+                (*cmsg).cmsg_level = libc::IPPROTO_IP;
+                (*cmsg).cmsg_type = libc::IP_PKTINFO;
+                std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut libc::in_pktinfo, *info);
+            }
+            Self::V6(info) => {
+                (*cmsg).cmsg_level = libc::IPPROTO_IPV6;
+                (*cmsg).cmsg_type = libc::IPV6_PKTINFO;
+                std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut libc::in6_pktinfo, *info);
+            }
         }
     }
+}
 
-    /// Emit a PathChange event  
-    /// RFC Section 8.3.2 - Path Change
-    pub(crate) async fn emit_path_change(&self) {
-        let _ = self.event_sender.send(ConnectionEvent::PathChange);
+/// Packet info selecting the source address of a datagram sent on a socket
+/// bound to `local`
+///
+/// The kernel routes the datagram as usual; only link-local sources pin the
+/// interface, as their address is ambiguous without it. An IPv6 socket takes
+/// IPv4 sources IPv4-mapped.
+#[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+fn source_packet_info(local: SocketAddr, source: PacketInfo) -> SourcePacketInfo {
+    match (local, source.destination) {
+        (SocketAddr::V4(_), destination) => {
+            let destination = match destination.to_canonical() {
+                IpAddr::V4(v4) => v4,
+                IpAddr::V6(_) => std::net::Ipv4Addr::UNSPECIFIED,
+            };
+            SourcePacketInfo::V4(libc::in_pktinfo {
+                ipi_ifindex: 0,
+                ipi_spec_dst: libc::in_addr {
+                    s_addr: u32::from(destination).to_be(),
+                },
+                ipi_addr: libc::in_addr { s_addr: 0 },
+            })
+        }
+        (SocketAddr::V6(_), destination) => {
+            let destination = match destination {
+                IpAddr::V4(v4) => v4.to_ipv6_mapped(),
+                IpAddr::V6(v6) => v6,
+            };
+            let link_local = destination.is_unicast_link_local();
+            SourcePacketInfo::V6(libc::in6_pktinfo {
+                ipi6_addr: libc::in6_addr {
+                    s6_addr: destination.octets(),
+                },
+                ipi6_ifindex: if link_local {
+                    source.interface_index as _
+                } else {
+                    0
+                },
+            })
+        }
     }
+}
 
-    /// Get the TCP Maximum Segment Size (MSS) from a TcpStream
-    async fn get_tcp_mss(&self, #[allow(unused_variables)] stream: &TcpStream) -> Result<usize> {
-        #[cfg(unix)]
-        {
-            use std::os::unix::io::{AsRawFd, FromRawFd};
+#[cfg(not(any(target_os = "linux", target_os = "android", target_vendor = "apple")))]
+async fn send_to_marked(
+    socket: &UdpSocket,
+    data: &[u8],
+    peer: SocketAddr,
+    _marks: DatagramMarks,
+) -> std::io::Result<usize> {
+    log::debug!("Per-datagram traffic class, hop limit and source not supported on this platform");
+    socket.send_to(data, peer).await
+}
 
-            // Get the raw file descriptor from the TcpStream
-            let fd = stream.as_raw_fd();
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn set_socket_priority(socket: &socket2::SockRef<'_>, priority: u32) {
+    set_int_option(
+        socket,
+        libc::SOL_SOCKET,
+        libc::SO_PRIORITY,
+        priority as libc::c_int,
+        "SO_PRIORITY",
+    );
+}
 
-            // Create a Socket from the raw fd
-            // SAFETY: We're borrowing the fd from TcpStream, not taking ownership
-            let socket = unsafe { Socket::from_raw_fd(fd) };
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn set_socket_priority(_socket: &socket2::SockRef<'_>, priority: u32) {
+    if priority != 0 {
+        log::debug!("Socket priority not supported on this platform");
+    }
+}
 
-            // Get the MSS value
-            #[cfg(not(target_os = "redox"))]
-            let mss_result = socket.mss();
+fn set_dscp(socket: &socket2::SockRef<'_>, dscp: u8) {
+    let traffic_class = u32::from(dscp) << 2;
+    let is_ipv6 = matches!(socket.local_addr().map(|addr| addr.is_ipv6()), Ok(true));
 
-            #[cfg(target_os = "redox")]
-            let mss_result = Err(std::io::Error::new(
+    let result = if is_ipv6 {
+        #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+        {
+            socket.set_tclass_v6(traffic_class)
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+        {
+            Err(std::io::Error::new(
                 std::io::ErrorKind::Unsupported,
-                "TCP MSS query not supported on Redox",
-            ));
-
-            // Important: We must forget the socket to prevent it from closing the fd
-            // when it goes out of scope (since we don't own the fd)
-            std::mem::forget(socket);
-
-            match mss_result {
-                Ok(mss) => Ok(mss as usize),
-                Err(e) => {
-                    // Log the error but don't fail
-                    log::debug!("Failed to get TCP MSS: {e}");
-                    Err(TransportServicesError::NotSupported(format!(
-                        "Failed to get TCP MSS: {e}"
-                    )))
-                }
-            }
+                "IPv6 traffic class not supported on this platform",
+            ))
         }
-
-        #[cfg(not(unix))]
+    } else {
+        #[cfg(not(any(
+            target_os = "fuchsia",
+            target_os = "redox",
+            target_os = "solaris",
+            target_os = "illumos",
+            target_os = "haiku"
+        )))]
         {
-            // On non-Unix platforms, return a typical default value
-            Ok(1460)
+            socket.set_tos(traffic_class)
+        }
+        #[cfg(any(
+            target_os = "fuchsia",
+            target_os = "redox",
+            target_os = "solaris",
+            target_os = "illumos",
+            target_os = "haiku"
+        ))]
+        {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "IP TOS not supported on this platform",
+            ))
         }
+    };
+
+    if let Err(e) = result {
+        log::warn!("Failed to set DSCP {dscp}: {e}");
     }
+}
 
-    /// Start a background task to continuously read from the connection
-    /// This enables passive message reception via events
-    async fn start_reading_task(&self) -> Result<()> {
-        // Clone necessary handles for the background task
-        let inner_clone = Arc::clone(&self.inner);
-        let event_sender = self.event_sender.clone();
+/// Query the path MTU of a connected socket
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn path_mtu(socket: socket2::SockRef<'_>) -> Option<usize> {
+    use std::os::unix::io::AsRawFd;
+
+    let (level, name) = match socket.local_addr().ok()?.is_ipv6() {
+        true => (libc::IPPROTO_IPV6, libc::IPV6_MTU),
+        false => (libc::IPPROTO_IP, libc::IP_MTU),
+    };
+    let mut mtu: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    // SAFETY: the fd is valid for the lifetime of the borrowed socket and the
+    // option buffer is a c_int of the length passed
+    let result = unsafe {
+        libc::getsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            &mut mtu as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    // Unconnected sockets have no path and report ENOTCONN
+    (result == 0 && mtu > 0).then_some(mtu as usize)
+}
 
-        // Spawn the background reading task
-        tokio::spawn(async move {
-            let mut buffer = vec![0u8; 8192];
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn path_mtu(_socket: socket2::SockRef<'_>) -> Option<usize> {
+    None
+}
 
-            loop {
-                // Check if connection is still active
-                let should_continue = {
-                    let inner = inner_clone.read().await;
-                    matches!(inner.state, ConnectionState::Established)
-                };
+/// Error of an establishment ended by close or abort, which dispose of any
+/// queued messages themselves
+fn establishment_abandoned() -> TransportServicesError {
+    TransportServicesError::EstablishmentFailed(
+        "Connection closed during establishment".to_string(),
+    )
+}
 
-                if !should_continue {
-                    break;
-                }
+/// Abortively close a TCP stream
+///
+/// Dropping a stream normally sends a FIN; with SO_LINGER set to zero the
+/// kernel discards unsent data and sends a RST instead.
+fn reset_tcp_stream(stream: TcpStream) {
+    if let Err(e) = socket2::SockRef::from(&stream).set_linger(Some(Duration::ZERO)) {
+        log::warn!("Failed to set SO_LINGER for abort: {e}");
+    }
+    drop(stream);
+}
 
-                // Try to read data from the stream
-                let read_result = {
-                    let inner = inner_clone.read().await;
-                    if let Some(ref stream) = inner.tcp_stream {
-                        // Clone the stream handle for reading
-                        match stream.try_read(&mut buffer) {
-                            Ok(n) => Some(Ok(n)),
-                            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                                // No data available, continue
-                                None
-                            }
-                            Err(e) => Some(Err(e)),
-                        }
-                    } else {
-                        break; // No stream available
-                    }
-                };
+/// Messages collected to be sent together
+/// RFC Section 9.2.4
+///
+/// Created by Connection::batch(). Messages are held by the Batch until
+/// commit(), which hands them to the connection in order, with no other
+/// send in between. Dropping the Batch, or the commit() future before it
+/// starts sending, discards the messages.
+#[derive(Debug)]
+pub struct Batch {
+    connection: Connection,
+    messages: Vec<Message>,
+}
 
-                match read_result {
-                    Some(Ok(0)) => {
-                        // Connection closed by peer
-                        let mut inner = inner_clone.write().await;
-                        inner.state = ConnectionState::Closed;
-                        let _ = event_sender.send(ConnectionEvent::Closed);
-                        break;
-                    }
-                    Some(Ok(n)) => {
-                        // Add data to receive buffer and try to parse messages
-                        let mut inner = inner_clone.write().await;
-                        inner.receive_buffer.extend_from_slice(&buffer[..n]);
-
-                        // Try to parse complete messages from the buffer
-                        loop {
-                            let message_result = if !inner.framers.is_empty() {
-                                // Use framer to parse messages
-                                if inner.receive_buffer.len() >= 4 {
-                                    let len_bytes = &inner.receive_buffer[0..4];
-                                    let expected_len = u32::from_be_bytes([
-                                        len_bytes[0],
-                                        len_bytes[1],
-                                        len_bytes[2],
-                                        len_bytes[3],
-                                    ])
-                                        as usize;
-
-                                    if inner.receive_buffer.len() >= 4 + expected_len {
-                                        // We have a complete message
-                                        let message_data =
-                                            inner.receive_buffer[4..4 + expected_len].to_vec();
-                                        inner.receive_buffer.drain(..4 + expected_len);
-                                        Some(Message::from_bytes(&message_data))
-                                    } else {
-                                        None
-                                    }
-                                } else {
-                                    None
-                                }
-                            } else if !inner.receive_buffer.is_empty() {
-                                // No framers - treat all data as one message
-                                let message_data = inner.receive_buffer.clone();
-                                inner.receive_buffer.clear();
-                                Some(Message::from_bytes(&message_data))
-                            } else {
-                                None
-                            };
-
-                            if let Some(message) = message_result {
-                                // Create message context
-                                let mut context = MessageContext::new();
-                                context.remote_endpoint = inner.remote_endpoint.clone();
-
-                                // Check if this is a final message
-                                if message.properties().final_message {
-                                    inner.final_message_received = true;
-                                }
-
-                                // Send Received event
-                                let _ = event_sender.send(ConnectionEvent::Received {
-                                    message_data: message.data().to_vec(),
-                                    message_context: context,
-                                });
-                            } else {
-                                break; // No more complete messages
-                            }
-                        }
-                    }
-                    Some(Err(e)) => {
-                        let error_msg = e.to_string();
-                        let _ = event_sender.send(ConnectionEvent::ReceiveError {
-                            error: error_msg.clone(),
-                        });
+impl Batch {
+    /// Add a message to the batch
+    pub fn add(&mut self, message: impl Into<Message>) -> &mut Self {
+        self.messages.push(message.into());
+        self
+    }
 
-                        // Check if this is a fatal error
-                        if error_msg.contains("broken pipe")
-                            || error_msg.contains("connection reset")
-                        {
-                            let mut inner = inner_clone.write().await;
-                            inner.state = ConnectionState::Closed;
-                            let _ = event_sender.send(ConnectionEvent::Closed);
-                            break;
-                        }
-                    }
-                    None => {
-                        // WouldBlock - yield to allow other tasks to run
-                        tokio::time::sleep(Duration::from_millis(10)).await;
-                    }
-                }
-            }
-        });
+    /// Number of messages in the batch
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
 
-        Ok(())
+    /// Whether the batch holds no messages
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Send all messages in the batch
+    pub async fn commit(self) -> Result<()> {
+        self.connection.send_batch(self.messages).await
     }
 }
 
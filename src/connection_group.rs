@@ -1,10 +1,15 @@
 //! Connection Groups for Transport Services
 //! Based on RFC 9622 Section 7.4 (Connection Groups)
 
-use crate::{LocalEndpoint, RemoteEndpoint, TransportProperties};
+use crate::connection::{ConnectionInner, EventSender, HandleGuard, SendStall};
+use crate::memory::MemoryAccount;
+use crate::{ConnectionState, LocalEndpoint, RemoteEndpoint, TimedEvent, TransportProperties};
+use crate::{Result, TransportServicesError};
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Weak};
-use tokio::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Mutex, Notify, RwLock};
 use uuid::Uuid;
 
 /// Unique identifier for a connection group
@@ -33,6 +38,19 @@ impl std::fmt::Display for ConnectionGroupId {
 // Forward declaration to avoid circular dependency
 pub struct Connection;
 
+/// A connection registered with a group
+#[derive(Debug, Clone)]
+pub(crate) struct GroupMember {
+    pub(crate) inner: Weak<RwLock<ConnectionInner>>,
+    // Lets group-wide operations deliver events to every member's handles
+    pub(crate) event_sender: EventSender,
+    pub(crate) event_receiver: Weak<RwLock<mpsc::UnboundedReceiver<TimedEvent>>>,
+    pub(crate) send_stall: Weak<SendStall>,
+    pub(crate) memory: Weak<MemoryAccount>,
+    // None for members registered from internal handles
+    pub(crate) handle: Option<Weak<HandleGuard>>,
+}
+
 /// Represents a group of related connections that share properties
 /// RFC Section 7.4: Connection Groups
 #[derive(Debug)]
@@ -51,7 +69,11 @@ pub struct ConnectionGroup {
     pub multistreaming_capable: bool,
     /// Weak references to all connections in this group
     /// Using Weak to avoid circular references
-    pub(crate) connections: Arc<Mutex<Vec<Weak<RwLock<crate::connection::ConnectionInner>>>>>,
+    pub(crate) connections: Arc<Mutex<Vec<GroupMember>>>,
+    /// Orders members' sends under proportional-rate scheduling
+    pub(crate) scheduler: Arc<SendScheduler>,
+    /// Caps the rate members send at together
+    pub(crate) send_rate: Arc<std::sync::Mutex<RateLimiter>>,
 }
 
 impl ConnectionGroup {
@@ -69,6 +91,8 @@ impl ConnectionGroup {
             connection_count: Arc::new(AtomicU64::new(0)),
             multistreaming_capable: false, // Will be determined by protocol selection
             connections: Arc::new(Mutex::new(Vec::new())),
+            scheduler: Arc::new(SendScheduler::default()),
+            send_rate: Arc::new(std::sync::Mutex::new(RateLimiter::default())),
         }
     }
 
@@ -79,7 +103,11 @@ impl ConnectionGroup {
 
     /// Decrement the connection count
     pub fn remove_connection(&self) {
-        self.connection_count.fetch_sub(1, Ordering::Relaxed);
+        let _ = self
+            .connection_count
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                count.checked_sub(1)
+            });
     }
 
     /// Get the current number of connections in the group
@@ -93,26 +121,111 @@ impl ConnectionGroup {
     }
 
     /// Register a connection with this group
-    pub(crate) async fn register_connection(
-        &self,
-        conn_inner: Weak<RwLock<crate::connection::ConnectionInner>>,
-    ) {
+    pub(crate) async fn register_connection(&self, member: GroupMember) {
         let mut connections = self.connections.lock().await;
-        connections.push(conn_inner);
+        connections.push(member);
         // Clean up any dead weak references while we have the lock
-        connections.retain(|weak| weak.strong_count() > 0);
+        connections.retain(|member| member.inner.strong_count() > 0);
+    }
+
+    /// Get handles to all live connections in this group
+    /// RFC Section 7.4
+    ///
+    /// Closed members are pruned from the group.
+    pub async fn members(&self) -> Vec<crate::Connection> {
+        let candidates = {
+            let mut connections = self.connections.lock().await;
+            connections.retain(|member| member.inner.strong_count() > 0);
+            connections.clone()
+        };
+
+        let mut live = Vec::new();
+        let mut closed = Vec::new();
+        for member in &candidates {
+            let Some(conn) = crate::Connection::from_group_member(member) else {
+                continue;
+            };
+            if conn.state().await == ConnectionState::Closed {
+                closed.push(member.inner.clone());
+            } else {
+                live.push(conn);
+            }
+        }
+
+        if !closed.is_empty() {
+            let mut connections = self.connections.lock().await;
+            connections.retain(|member| !closed.iter().any(|c| c.ptr_eq(&member.inner)));
+        }
+        live
+    }
+
+    /// Set a member's weight for proportional-rate scheduling
+    /// RFC Section 8.1.5
+    ///
+    /// When connScheduler is ProportionalRate, members with messages to send
+    /// send bytes in the ratio of their weights. Members weigh 1 by default.
+    pub async fn set_weight(&self, connection: &crate::Connection, weight: u32) -> Result<()> {
+        if weight == 0 {
+            return Err(TransportServicesError::InvalidParameters(
+                "Scheduling weight must be positive".to_string(),
+            ));
+        }
+        if connection.connection_group_id().await != Some(self.id) {
+            return Err(TransportServicesError::InvalidParameters(
+                "Connection is not a member of this group".to_string(),
+            ));
+        }
+        connection.set_scheduling_weight(weight).await;
+        Ok(())
+    }
+
+    /// A member's weight for proportional-rate scheduling, if it belongs to
+    /// this group
+    pub async fn weight(&self, connection: &crate::Connection) -> Option<u32> {
+        if connection.connection_group_id().await != Some(self.id) {
+            return None;
+        }
+        Some(connection.scheduling_weight().await)
+    }
+
+    /// Cap the rate, in bits per second, at which the group's members send
+    /// in aggregate, or lift the cap with `None`
+    ///
+    /// This applies on top of each member's own maxSendRate: a send waits
+    /// until both allow it. Under proportional-rate scheduling the capped
+    /// rate is shared in the ratio of the members' weights.
+    pub fn set_max_send_rate(&self, rate: Option<u64>) -> Result<()> {
+        if rate == Some(0) {
+            return Err(TransportServicesError::InvalidParameters(
+                "Send rate must be positive".to_string(),
+            ));
+        }
+        self.send_rate.lock().unwrap().set_rate(rate);
+        Ok(())
+    }
+
+    /// The cap on the group's aggregate send rate in bits per second, if any
+    pub fn max_send_rate(&self) -> Option<u64> {
+        self.send_rate.lock().unwrap().rate()
     }
 
     /// Get all active connections in this group
-    pub(crate) async fn get_connections(
-        &self,
-    ) -> Vec<Arc<RwLock<crate::connection::ConnectionInner>>> {
+    pub(crate) async fn get_connections(&self) -> Vec<Arc<RwLock<ConnectionInner>>> {
+        self.get_members()
+            .await
+            .into_iter()
+            .map(|(inner, _)| inner)
+            .collect()
+    }
+
+    /// Get all active connections in this group with their event channels
+    pub(crate) async fn get_members(&self) -> Vec<(Arc<RwLock<ConnectionInner>>, EventSender)> {
         let mut connections = self.connections.lock().await;
         // Clean up dead references and collect strong references
         let mut active = Vec::new();
-        connections.retain(|weak| {
-            if let Some(strong) = weak.upgrade() {
-                active.push(strong);
+        connections.retain(|member| {
+            if let Some(strong) = member.inner.upgrade() {
+                active.push((strong, member.event_sender.clone()));
                 true
             } else {
                 false
@@ -132,6 +245,176 @@ impl Clone for ConnectionGroup {
             connection_count: Arc::clone(&self.connection_count),
             multistreaming_capable: self.multistreaming_capable,
             connections: Arc::clone(&self.connections),
+            scheduler: Arc::clone(&self.scheduler),
+            send_rate: Arc::clone(&self.send_rate),
+        }
+    }
+}
+
+/// Orders the sends of a group's members under proportional-rate scheduling
+///
+/// One member sends at a time. Each send is tagged with the virtual time at
+/// which it would finish if every member with data were served at a rate
+/// proportional to its weight, and the waiting send with the earliest tag
+/// goes next, so busy members send bytes in the ratio of their weights.
+/// The turn is only taken once the previous send is done, so a member that
+/// sends again straight away competes with those already waiting.
+#[derive(Debug, Default)]
+pub(crate) struct SendScheduler {
+    state: std::sync::Mutex<SchedulerState>,
+    // Signalled whenever the turn may have become free
+    turn: Notify,
+}
+
+#[derive(Debug, Default)]
+struct SchedulerState {
+    sending: bool,
+    virtual_time: f64,
+    // Finish tag of each member's latest send, keyed by member
+    finish: HashMap<usize, f64>,
+    waiting: Vec<WaitingSend>,
+    next_sequence: u64,
+}
+
+#[derive(Debug)]
+struct WaitingSend {
+    start: f64,
+    finish: f64,
+    // Breaks ties between equal tags in arrival order
+    sequence: u64,
+}
+
+impl SendScheduler {
+    /// Wait for `member`'s turn to send `bytes`
+    pub(crate) async fn admit(
+        self: &Arc<Self>,
+        member: usize,
+        weight: u32,
+        bytes: usize,
+    ) -> SendGrant {
+        let sequence = {
+            let mut state = self.state.lock().unwrap();
+            let previous = state.finish.get(&member).copied().unwrap_or_default();
+            let start = state.virtual_time.max(previous);
+            let finish = start + bytes.max(1) as f64 / f64::from(weight.max(1));
+            state.finish.insert(member, finish);
+            let sequence = state.next_sequence;
+            state.next_sequence += 1;
+            state.waiting.push(WaitingSend {
+                start,
+                finish,
+                sequence,
+            });
+            sequence
+        };
+
+        // The grant exists while waiting so a cancelled wait leaves the queue
+        let mut grant = SendGrant {
+            scheduler: Arc::clone(self),
+            sequence,
+            granted: false,
+        };
+        loop {
+            let notified = self.turn.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if self.take_turn(sequence) {
+                grant.granted = true;
+                return grant;
+            }
+            notified.await;
+        }
+    }
+
+    /// Take the turn if it is free and this send has the earliest tag
+    fn take_turn(&self, sequence: u64) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.sending {
+            return false;
+        }
+        let next = state
+            .waiting
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                a.finish
+                    .total_cmp(&b.finish)
+                    .then(a.sequence.cmp(&b.sequence))
+            })
+            .map(|(index, waiting)| (index, waiting.sequence));
+        match next {
+            Some((index, next)) if next == sequence => {
+                let waiting = state.waiting.swap_remove(index);
+                state.sending = true;
+                state.virtual_time = state.virtual_time.max(waiting.start);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A member's turn to send, or its place in the queue, given up when dropped
+pub(crate) struct SendGrant {
+    scheduler: Arc<SendScheduler>,
+    sequence: u64,
+    granted: bool,
+}
+
+impl Drop for SendGrant {
+    fn drop(&mut self) {
+        {
+            let mut state = self.scheduler.state.lock().unwrap();
+            if self.granted {
+                state.sending = false;
+                // Members whose sends have all finished start afresh at the virtual time
+                let virtual_time = state.virtual_time;
+                state.finish.retain(|_, finish| *finish > virtual_time);
+            } else {
+                state
+                    .waiting
+                    .retain(|waiting| waiting.sequence != self.sequence);
+            }
+        }
+        self.scheduler.turn.notify_waiters();
+    }
+}
+
+/// Spreads sends out at a rate in bits per second
+///
+/// Each send starts once those before it would have gone out at the rate,
+/// so an idle sender builds up no credit for a later burst.
+#[derive(Debug, Default)]
+pub(crate) struct RateLimiter {
+    rate: Option<u64>,
+    // When the next send may start, once one has been sent
+    next_send: Option<Instant>,
+}
+
+impl RateLimiter {
+    pub(crate) fn rate(&self) -> Option<u64> {
+        self.rate
+    }
+
+    /// Change the rate, starting afresh if it differs
+    pub(crate) fn set_rate(&mut self, rate: Option<u64>) {
+        if self.rate != rate {
+            self.rate = rate;
+            self.next_send = None;
+        }
+    }
+
+    /// When a send may start, if the rate limits it
+    pub(crate) fn ready_at(&self, now: Instant) -> Option<Instant> {
+        self.rate?;
+        Some(self.next_send.map_or(now, |next| next.max(now)))
+    }
+
+    /// Account for `bytes` sent at `start`
+    pub(crate) fn consume(&mut self, bytes: usize, start: Instant) {
+        if let Some(rate) = self.rate {
+            let duration = Duration::from_secs_f64(bytes as f64 * 8.0 / rate as f64);
+            self.next_send = Some(start + duration);
         }
     }
 }
@@ -1,10 +1,83 @@
 //! Connection Properties implementation for Transport Services
 //! Based on RFC 9622 Section 8.1
 
-use crate::ConnectionState;
+use crate::{CertificateChain, ConnectionState, MemoryUsage, ProxyKind, SecurityProtocol, Status};
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::time::Duration;
 
+/// Default time a write may make no progress before it counts as stalled
+pub const DEFAULT_SEND_STALL_THRESHOLD: Duration = Duration::from_secs(5);
+
+/// A layer of the protocol stack carrying a Connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackLayer {
+    /// TLS or DTLS, at the negotiated version
+    Security(SecurityProtocol),
+    /// Tunnel through a proxy
+    Proxy(ProxyKind),
+    Tcp,
+    Udp,
+    Ipv4,
+    Ipv6,
+}
+
+impl std::fmt::Display for StackLayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            StackLayer::Security(SecurityProtocol::TLS12) => "TLS1.2",
+            StackLayer::Security(SecurityProtocol::TLS13) => "TLS1.3",
+            StackLayer::Security(SecurityProtocol::DTLS12) => "DTLS1.2",
+            StackLayer::Security(SecurityProtocol::DTLS13) => "DTLS1.3",
+            StackLayer::Proxy(ProxyKind::Socks5) => "SOCKS5",
+            StackLayer::Proxy(ProxyKind::HttpConnect) => "HTTP-CONNECT",
+            StackLayer::Tcp => "TCP",
+            StackLayer::Udp => "UDP",
+            StackLayer::Ipv4 => "IPv4",
+            StackLayer::Ipv6 => "IPv6",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The protocol stack a Connection actually uses, outermost layer first,
+/// e.g. [TLS1.3, TCP, IPv6]
+///
+/// Empty while nothing carries the Connection, before it is established
+/// or once it is closed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProtocolStack {
+    pub layers: Vec<StackLayer>,
+}
+
+impl ProtocolStack {
+    /// Whether `layer` is part of the stack
+    pub fn contains(&self, layer: StackLayer) -> bool {
+        self.layers.contains(&layer)
+    }
+
+    /// The security protocol protecting the Connection, if any
+    pub fn security(&self) -> Option<SecurityProtocol> {
+        self.layers.iter().find_map(|layer| match layer {
+            StackLayer::Security(protocol) => Some(*protocol),
+            _ => None,
+        })
+    }
+}
+
+/// Formats as the layers joined by slashes, e.g. "TLS1.3/TCP/IPv6"
+impl std::fmt::Display for ProtocolStack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, layer) in self.layers.iter().enumerate() {
+            if i > 0 {
+                f.write_str("/")?;
+            }
+            write!(f, "{layer}")?;
+        }
+        Ok(())
+    }
+}
+
 /// Generic Connection Properties as defined in RFC 9622 Section 8.1
 #[derive(Debug, Clone)]
 pub enum ConnectionProperty {
@@ -25,6 +98,17 @@ pub enum ConnectionProperty {
     /// Maximum length of time an idle Connection waits before sending a keep-alive packet
     KeepAliveTimeout(TimeoutValue),
 
+    /// Timeout for Stalled Sends (implementation specific)
+    /// How long a write may block, e.g. on a zero-window peer, before the
+    /// messages fail with SendError and the Connection is aborted
+    SendTimeout(TimeoutValue),
+
+    /// Threshold for Flow-Control Stalls (implementation specific)
+    /// How long a write may make no progress while the peer keeps
+    /// acknowledging before it is reported as stalled, with a SoftError
+    /// event and the sendStall property
+    SendStallThreshold(TimeoutValue),
+
     /// Connection Group Transmission Scheduler (8.1.5)
     /// Which scheduler is used among Connections within a Connection Group
     ConnScheduler(SchedulerType),
@@ -53,6 +137,19 @@ pub enum ConnectionProperty {
     /// When true, use as little cached information as possible from previous Connections
     IsolateSession(bool),
 
+    /// IP TTL or Hop Limit (implementation specific)
+    /// TTL (IPv4) or hop limit (IPv6) of packets sent, e.g. for
+    /// traceroute-like probing or expanding-ring searches; None keeps the
+    /// system default. Datagrams carry it per packet, so flows sharing a
+    /// Listener's socket are unaffected
+    IpHopLimit(Option<u8>),
+
+    /// Path Pinning (implementation specific)
+    /// When true, the Connection never leaves the path it is using: losing
+    /// that path, or the peer's address changing, aborts it with a
+    /// ConnectionError instead of migrating
+    PathPinning(bool),
+
     // Read-only properties (8.1.11)
     /// Connection State (8.1.11.1)
     ConnState(ConnectionState),
@@ -72,6 +169,48 @@ pub enum ConnectionProperty {
     /// Maximum Message Size on Receive (8.1.11.6)
     RecvMsgMaxLen(Option<usize>),
 
+    /// How long sends have been stalled by the peer's flow control, e.g. a
+    /// zero receive window, or None if they are not; a path that stopped
+    /// acknowledging does not count (implementation specific)
+    SendStall(Option<Duration>),
+
+    /// Bytes the Connection holds in buffers, queues and undelivered
+    /// events, counted against the memory budget (implementation specific)
+    MemoryUsage(MemoryUsage),
+
+    /// Name of the interface the Connection is using
+    PathInterface(Option<String>),
+
+    /// Type of that interface, e.g. "wifi", "cellular" or "ethernet"
+    PathInterfaceType(Option<String>),
+
+    /// Local address of the current path
+    PathLocalAddress(Option<SocketAddr>),
+
+    /// Remote address of the current path
+    PathRemoteAddress(Option<SocketAddr>),
+
+    /// Path MTU in bytes, where the platform reports it
+    PathMtu(Option<usize>),
+
+    /// Protocol stack carrying the Connection, outermost layer first
+    /// (the transport stack instance)
+    ProtocolStack(ProtocolStack),
+
+    /// Security protocol protecting the Connection, None while in cleartext
+    SecurityProtocolInUse(Option<SecurityProtocol>),
+
+    /// Application protocol negotiated by the security handshake (ALPN)
+    SecurityAlpn(Option<String>),
+
+    /// Server name the client asked for (SNI), on connections a Listener
+    /// secured
+    SecurityServerName(Option<String>),
+
+    /// Certificate chain the client authenticated with, on connections a
+    /// Listener secured; None if the client presented none
+    SecurityClientCertificate(Option<CertificateChain>),
+
     // TCP-specific properties (8.2)
     /// Advertised User Timeout (8.2.1)
     TcpUserTimeoutValue(Option<Duration>),
@@ -81,6 +220,98 @@ pub enum ConnectionProperty {
 
     /// Timeout Changeable (8.2.3)
     TcpUserTimeoutChangeable(bool),
+
+    // QUIC-specific properties
+    /// Idle timeout advertised to the peer (RFC 9000 max_idle_timeout)
+    QuicMaxIdleTimeout(TimeoutValue),
+
+    /// Bidirectional streams the peer may open (RFC 9000 initial_max_streams_bidi)
+    QuicMaxStreamsBidi(u64),
+
+    /// Unidirectional streams the peer may open (RFC 9000 initial_max_streams_uni)
+    QuicMaxStreamsUni(u64),
+
+    /// Congestion control algorithm
+    QuicCongestionAlgorithm(CongestionAlgorithm),
+
+    // SCTP-specific properties
+    /// Outbound streams requested at association setup (RFC 9260 OS)
+    SctpNumOutboundStreams(u16),
+
+    /// Inbound streams accepted at association setup (RFC 9260 MIS)
+    SctpMaxInboundStreams(u16),
+
+    /// Interval between heartbeats on idle paths (RFC 9260 HB.interval)
+    SctpHeartbeatInterval(TimeoutValue),
+
+    /// Retransmissions before the association is aborted (RFC 9260 Association.Max.Retrans)
+    SctpMaxRetransmissions(u32),
+}
+
+/// Protocol whose stack a namespaced property tunes, e.g. "tcp.userTimeoutValue"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropertyNamespace {
+    /// tcp.* (RFC 9622 Section 8.2)
+    Tcp,
+    /// quic.*
+    Quic,
+    /// sctp.*
+    Sctp,
+}
+
+impl PropertyNamespace {
+    /// Namespace of a property name, or None for generic properties
+    pub fn of(key: &str) -> Option<Self> {
+        match key.split_once('.')?.0 {
+            "tcp" => Some(Self::Tcp),
+            "quic" => Some(Self::Quic),
+            "sctp" => Some(Self::Sctp),
+            _ => None,
+        }
+    }
+
+    /// Property name prefix
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Self::Tcp => "tcp",
+            Self::Quic => "quic",
+            Self::Sctp => "sctp",
+        }
+    }
+}
+
+impl ConnectionProperty {
+    /// The name a protocol-specific property is stored under
+    ///
+    /// None for generic properties, which can be stored under any key.
+    pub fn protocol_key(&self) -> Option<&'static str> {
+        match self {
+            Self::TcpUserTimeoutValue(_) => Some("tcp.userTimeoutValue"),
+            Self::TcpUserTimeoutEnabled(_) => Some("tcp.userTimeoutEnabled"),
+            Self::TcpUserTimeoutChangeable(_) => Some("tcp.userTimeoutChangeable"),
+            Self::QuicMaxIdleTimeout(_) => Some("quic.maxIdleTimeout"),
+            Self::QuicMaxStreamsBidi(_) => Some("quic.maxStreamsBidi"),
+            Self::QuicMaxStreamsUni(_) => Some("quic.maxStreamsUni"),
+            Self::QuicCongestionAlgorithm(_) => Some("quic.congestionAlgorithm"),
+            Self::SctpNumOutboundStreams(_) => Some("sctp.numOutboundStreams"),
+            Self::SctpMaxInboundStreams(_) => Some("sctp.maxInboundStreams"),
+            Self::SctpHeartbeatInterval(_) => Some("sctp.heartbeatInterval"),
+            Self::SctpMaxRetransmissions(_) => Some("sctp.maxRetransmissions"),
+            _ => None,
+        }
+    }
+}
+
+/// Congestion control algorithms a QUIC stack may offer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CongestionAlgorithm {
+    /// NewReno (RFC 9002)
+    NewReno,
+    /// CUBIC (RFC 9438)
+    #[default]
+    Cubic,
+    /// BBR
+    Bbr,
 }
 
 /// Checksum coverage specification
@@ -113,7 +344,8 @@ pub enum SchedulerType {
     Fifo,
     /// Round Robin
     RoundRobin,
-    /// Proportional Rate Reduction
+    /// Group members send at rates proportional to their weights,
+    /// set with `ConnectionGroup::set_weight`
     ProportionalRate,
 }
 
@@ -123,6 +355,8 @@ pub enum CapacityProfile {
     /// Default (Best Effort)
     #[default]
     Default,
+    /// Scavenger: yields to other traffic sharing the path
+    Scavenger,
     /// Low Latency/Interactive
     LowLatencyInteractive,
     /// Low Latency/Non-Interactive  
@@ -145,6 +379,34 @@ pub enum MultipathPolicy {
     Redundant,
 }
 
+/// The network path a Connection is currently using
+///
+/// Interface details come from the NetworkMonitor and are None when the
+/// local address does not belong to a known interface. On Apple platforms,
+/// Network.framework also reports the status, cost, constraints and DNS of
+/// the system path through that interface.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathInfo {
+    /// Interface name, e.g. "en0" or "eth0"
+    pub interface: Option<String>,
+    /// Interface type, e.g. "wifi", "cellular" or "ethernet"
+    pub interface_type: Option<String>,
+    /// Whether the interface is metered
+    pub is_expensive: bool,
+    /// Local address of the path
+    pub local_address: Option<SocketAddr>,
+    /// Remote address of the path
+    pub remote_address: Option<SocketAddr>,
+    /// Path MTU in bytes
+    pub path_mtu: Option<usize>,
+    /// Whether the path is in a low data mode, e.g. Low Data Mode on Apple platforms
+    pub is_constrained: bool,
+    /// Whether the system path is usable, as reported by the platform
+    pub status: Option<Status>,
+    /// Whether the path has DNS servers configured, as reported by the platform
+    pub has_dns: Option<bool>,
+}
+
 /// Storage for connection properties
 #[derive(Debug, Clone, Default)]
 pub struct ConnectionProperties {
@@ -172,6 +434,16 @@ impl ConnectionProperties {
             "keepAliveTimeout".to_string(),
             ConnectionProperty::KeepAliveTimeout(TimeoutValue::default()),
         );
+        properties.insert(
+            "sendTimeout".to_string(),
+            ConnectionProperty::SendTimeout(TimeoutValue::default()),
+        );
+        properties.insert(
+            "sendStallThreshold".to_string(),
+            ConnectionProperty::SendStallThreshold(TimeoutValue::Duration(
+                DEFAULT_SEND_STALL_THRESHOLD,
+            )),
+        );
         properties.insert(
             "connScheduler".to_string(),
             ConnectionProperty::ConnScheduler(SchedulerType::default()),
@@ -208,6 +480,14 @@ impl ConnectionProperties {
             "isolateSession".to_string(),
             ConnectionProperty::IsolateSession(false),
         ); // Default: false
+        properties.insert(
+            "ipHopLimit".to_string(),
+            ConnectionProperty::IpHopLimit(None),
+        ); // System default
+        properties.insert(
+            "pathPinning".to_string(),
+            ConnectionProperty::PathPinning(false),
+        ); // Default: false
 
         // TCP-specific defaults
         // tcp.userTimeoutValue defaults to None (use TCP default)
@@ -232,7 +512,19 @@ impl ConnectionProperties {
             | "canReceive"
             | "singularTransmissionMsgMaxLen"
             | "sendMsgMaxLen"
-            | "recvMsgMaxLen" => {
+            | "recvMsgMaxLen"
+            | "sendStall"
+            | "memoryUsage"
+            | "pathInterface"
+            | "pathInterfaceType"
+            | "pathLocalAddress"
+            | "pathRemoteAddress"
+            | "pathMtu"
+            | "protocolStack"
+            | "securityProtocol"
+            | "securityAlpn"
+            | "securityServerName"
+            | "securityClientCertificate" => {
                 return Err(crate::TransportServicesError::InvalidParameters(format!(
                     "Property '{key}' is read-only"
                 )));
@@ -240,6 +532,23 @@ impl ConnectionProperties {
             _ => {}
         }
 
+        // Protocol-specific properties must use their own name and value type
+        if let Some(namespace) = PropertyNamespace::of(key) {
+            if value.protocol_key() != Some(key) {
+                return Err(crate::TransportServicesError::InvalidParameters(format!(
+                    "'{key}' is not a known {} property for {value:?}",
+                    namespace.prefix()
+                )));
+            }
+        }
+        if let Some(expected) = value.protocol_key() {
+            if key != expected {
+                return Err(crate::TransportServicesError::InvalidParameters(format!(
+                    "{value:?} must be set as '{expected}'"
+                )));
+            }
+        }
+
         self.properties.insert(key.to_string(), value);
         Ok(())
     }
@@ -272,4 +581,57 @@ impl ConnectionProperties {
             ConnectionProperty::CanReceive(can_receive),
         );
     }
+
+    /// Update the read-only security properties
+    pub fn update_security(
+        &mut self,
+        protocol: Option<SecurityProtocol>,
+        alpn: Option<String>,
+        server_name: Option<String>,
+    ) {
+        self.properties.insert(
+            "securityProtocol".to_string(),
+            ConnectionProperty::SecurityProtocolInUse(protocol),
+        );
+        self.properties.insert(
+            "securityAlpn".to_string(),
+            ConnectionProperty::SecurityAlpn(alpn),
+        );
+        self.properties.insert(
+            "securityServerName".to_string(),
+            ConnectionProperty::SecurityServerName(server_name),
+        );
+    }
+
+    /// Update the read-only client certificate property
+    pub fn update_client_certificate(&mut self, chain: Option<CertificateChain>) {
+        self.properties.insert(
+            "securityClientCertificate".to_string(),
+            ConnectionProperty::SecurityClientCertificate(chain),
+        );
+    }
+
+    /// Update the read-only path properties
+    pub fn update_path(&mut self, path: &PathInfo) {
+        self.properties.insert(
+            "pathInterface".to_string(),
+            ConnectionProperty::PathInterface(path.interface.clone()),
+        );
+        self.properties.insert(
+            "pathInterfaceType".to_string(),
+            ConnectionProperty::PathInterfaceType(path.interface_type.clone()),
+        );
+        self.properties.insert(
+            "pathLocalAddress".to_string(),
+            ConnectionProperty::PathLocalAddress(path.local_address),
+        );
+        self.properties.insert(
+            "pathRemoteAddress".to_string(),
+            ConnectionProperty::PathRemoteAddress(path.remote_address),
+        );
+        self.properties.insert(
+            "pathMtu".to_string(),
+            ConnectionProperty::PathMtu(path.path_mtu),
+        );
+    }
 }
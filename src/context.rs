@@ -0,0 +1,308 @@
+//! Shared Transport Services context
+//!
+//! A context owns the state that Preconnections would otherwise take from
+//! process-wide defaults: the resolver and its cache, cached security
+//! sessions, the path monitor, the proxy, and a policy that can veto or reorder
+//! connection candidates for every Preconnection attached to it. It also
+//! tracks the listeners and connections created through it, so they can be
+//! shut down together.
+
+use crate::proxy::ProxyConfig;
+use crate::resolver::{ResolutionCache, ResolverConfig};
+use crate::shutdown::{Registry, ShutdownSummary};
+use crate::{NetworkMonitor, RemoteEndpoint};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Number of sessions a context caches by default
+pub const DEFAULT_MAX_CACHED_SESSIONS: usize = 256;
+
+/// How long a cached session may be resumed by default
+pub const DEFAULT_SESSION_LIFETIME: Duration = Duration::from_secs(2 * 60 * 60);
+
+/// A candidate address gathered for connection establishment
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    /// Address the connection attempt is made to
+    pub address: SocketAddr,
+    /// Remote endpoint the address was resolved from
+    pub endpoint: RemoteEndpoint,
+}
+
+/// Ordered candidates for racing, as presented to a candidate policy
+#[derive(Debug, Clone, Default)]
+pub struct CandidateSet {
+    /// Candidates in the order they are attempted
+    pub candidates: Vec<Candidate>,
+}
+
+impl CandidateSet {
+    /// Create a candidate set in the given order
+    pub fn new(candidates: Vec<Candidate>) -> Self {
+        Self { candidates }
+    }
+
+    /// Keep only the candidates matching the predicate, preserving order
+    pub fn filter(&self, predicate: impl Fn(&Candidate) -> bool) -> Self {
+        Self::new(
+            self.candidates
+                .iter()
+                .filter(|candidate| predicate(candidate))
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Iterate over the candidates in order
+    pub fn iter(&self) -> impl Iterator<Item = &Candidate> {
+        self.candidates.iter()
+    }
+
+    /// Number of candidates
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    /// Check whether no candidates remain
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+}
+
+/// Policy applied to the candidates of every connection initiated in a context
+///
+/// The returned set replaces the gathered one: candidates left out are never
+/// attempted, and the order given is the racing order.
+pub type CandidatePolicy = Arc<dyn Fn(&CandidateSet) -> CandidateSet + Send + Sync>;
+
+struct CachedSession {
+    data: Vec<u8>,
+    stored: Instant,
+}
+
+/// Cache of security sessions for resumption, keyed by server name
+pub struct SessionCache {
+    sessions: Mutex<HashMap<String, CachedSession>>,
+    max_sessions: usize,
+    lifetime: Duration,
+}
+
+impl SessionCache {
+    /// Create an empty cache with the default capacity and session lifetime
+    pub fn new() -> Self {
+        Self::with_limits(DEFAULT_MAX_CACHED_SESSIONS, DEFAULT_SESSION_LIFETIME)
+    }
+
+    /// Create an empty cache holding at most `max_sessions`, each for `lifetime`
+    pub fn with_limits(max_sessions: usize, lifetime: Duration) -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+            max_sessions,
+            lifetime,
+        }
+    }
+
+    /// Store session state for a server, evicting the oldest session when full
+    pub fn insert(&self, server_name: &str, data: Vec<u8>) {
+        if self.max_sessions == 0 {
+            return;
+        }
+        let mut sessions = self.sessions.lock().unwrap();
+        if !sessions.contains_key(server_name) && sessions.len() >= self.max_sessions {
+            let oldest = sessions
+                .iter()
+                .min_by_key(|(_, session)| session.stored)
+                .map(|(name, _)| name.clone());
+            if let Some(oldest) = oldest {
+                sessions.remove(&oldest);
+            }
+        }
+        sessions.insert(
+            server_name.to_string(),
+            CachedSession {
+                data,
+                stored: Instant::now(),
+            },
+        );
+    }
+
+    /// Get the session state for a server, unless it has expired
+    pub fn get(&self, server_name: &str) -> Option<Vec<u8>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let expired = sessions
+            .get(server_name)
+            .map(|session| session.stored.elapsed() >= self.lifetime)?;
+        if expired {
+            sessions.remove(server_name);
+            return None;
+        }
+        sessions
+            .get(server_name)
+            .map(|session| session.data.clone())
+    }
+
+    /// Forget the session for a server
+    pub fn remove(&self, server_name: &str) {
+        self.sessions.lock().unwrap().remove(server_name);
+    }
+
+    /// Forget all sessions
+    pub fn flush(&self) {
+        self.sessions.lock().unwrap().clear();
+    }
+
+    /// Number of cached sessions, including expired ones not yet evicted
+    pub fn len(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    /// Check whether the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for SessionCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Context shared by the Preconnections of an application or system integrator
+///
+/// Attach it with `Preconnection::set_context`; Preconnections without a
+/// context keep their own resolution cache and apply no candidate policy.
+#[derive(Clone)]
+pub struct TransportServices {
+    resolution_cache: Arc<ResolutionCache>,
+    resolver_config: ResolverConfig,
+    session_cache: Arc<SessionCache>,
+    path_monitor: Option<Arc<NetworkMonitor>>,
+    candidate_policy: Option<CandidatePolicy>,
+    proxy: Option<ProxyConfig>,
+    // Listeners and connections created from attached Preconnections
+    registry: Arc<Registry>,
+}
+
+impl TransportServices {
+    /// Create a context with its own caches, the system resolver and no policy
+    pub fn new() -> Self {
+        Self {
+            resolution_cache: Arc::new(ResolutionCache::new()),
+            resolver_config: ResolverConfig::default(),
+            session_cache: Arc::new(SessionCache::new()),
+            path_monitor: None,
+            candidate_policy: None,
+            proxy: None,
+            registry: Arc::new(Registry::new()),
+        }
+    }
+
+    /// Use the given resolution cache, e.g. `ResolutionCache::global()`
+    pub fn with_resolution_cache(mut self, cache: Arc<ResolutionCache>) -> Self {
+        self.resolution_cache = cache;
+        self
+    }
+
+    /// Use the given resolver configuration (DoT, DoH, DNSSEC)
+    pub fn with_resolver_config(mut self, config: ResolverConfig) -> Self {
+        self.resolver_config = config;
+        self
+    }
+
+    /// Use the given session cache
+    pub fn with_session_cache(mut self, cache: Arc<SessionCache>) -> Self {
+        self.session_cache = cache;
+        self
+    }
+
+    /// Own the given path monitor
+    ///
+    /// The monitor is created by the caller, as some platforms cannot create
+    /// or drop one from within an async context.
+    pub fn with_path_monitor(mut self, monitor: NetworkMonitor) -> Self {
+        self.path_monitor = Some(Arc::new(monitor));
+        self
+    }
+
+    /// Establish connections of every attached Preconnection through a proxy
+    pub fn with_proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Filter or reorder the candidates of every connection initiated in this context
+    pub fn with_candidate_policy<F>(mut self, policy: F) -> Self
+    where
+        F: Fn(&CandidateSet) -> CandidateSet + Send + Sync + 'static,
+    {
+        self.candidate_policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Get the resolution cache
+    pub fn resolution_cache(&self) -> Arc<ResolutionCache> {
+        Arc::clone(&self.resolution_cache)
+    }
+
+    /// Get the resolver configuration
+    pub fn resolver_config(&self) -> &ResolverConfig {
+        &self.resolver_config
+    }
+
+    /// Get the session cache
+    pub fn session_cache(&self) -> Arc<SessionCache> {
+        Arc::clone(&self.session_cache)
+    }
+
+    /// Get the proxy, if one was given
+    pub fn proxy(&self) -> Option<&ProxyConfig> {
+        self.proxy.as_ref()
+    }
+
+    /// Get the path monitor, if one was given
+    pub fn path_monitor(&self) -> Option<Arc<NetworkMonitor>> {
+        self.path_monitor.clone()
+    }
+
+    /// Apply the candidate policy, returning the candidates unchanged without one
+    pub fn apply_candidate_policy(&self, candidates: CandidateSet) -> CandidateSet {
+        match &self.candidate_policy {
+            Some(policy) => policy(&candidates),
+            None => candidates,
+        }
+    }
+
+    /// Stop the listeners and close the connections created through this context
+    ///
+    /// Connections still open when `timeout` passes are aborted. Unlike
+    /// `crate::shutdown`, listeners and connections of other contexts and of
+    /// Preconnections without one are left alone.
+    pub async fn shutdown(&self, timeout: Duration) -> ShutdownSummary {
+        self.registry.drain(timeout).await
+    }
+
+    pub(crate) fn registry(&self) -> &Registry {
+        &self.registry
+    }
+}
+
+impl Default for TransportServices {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for TransportServices {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TransportServices")
+            .field("resolver_config", &self.resolver_config)
+            .field("cached_sessions", &self.session_cache.len())
+            .field("has_path_monitor", &self.path_monitor.is_some())
+            .field("proxy", &self.proxy)
+            .field("has_candidate_policy", &self.candidate_policy.is_some())
+            .finish()
+    }
+}
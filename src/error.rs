@@ -54,6 +54,9 @@ pub enum TransportServicesError {
 
     /// A message was larger than the specified maximum length.
     MessageTooLarge(String),
+
+    /// The operation would have to wait, e.g. for the send queue to drain.
+    WouldBlock,
 }
 
 impl fmt::Display for TransportServicesError {
@@ -80,6 +83,7 @@ impl fmt::Display for TransportServicesError {
             }
             TransportServicesError::Timeout => write!(f, "Operation timed out"),
             TransportServicesError::MessageTooLarge(msg) => write!(f, "Message too large: {msg}"),
+            TransportServicesError::WouldBlock => write!(f, "Operation would block"),
         }
     }
 }
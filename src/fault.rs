@@ -0,0 +1,132 @@
+//! Fault injection for testing
+//! Perturbs the bytes and datagrams a Connection sends and receives so that
+//! framer resync, send error and abort paths can be exercised deterministically
+
+use crate::runtime;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// A fault applied to one unit of I/O: a stream write or read, or a datagram
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Hold the unit back for the given time
+    Delay(Duration),
+    /// Discard the unit
+    Drop,
+    /// Deliver the unit twice
+    Duplicate,
+    /// Flip the bits of the middle byte of the unit
+    Corrupt,
+    /// Deliver the unit after the next one
+    Reorder,
+}
+
+/// Direction of the I/O a fault applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultDirection {
+    Send,
+    Receive,
+}
+
+#[derive(Debug, Default)]
+struct FaultQueue {
+    faults: VecDeque<Fault>,
+    // Unit held back by a Reorder fault
+    held: Option<Vec<u8>>,
+}
+
+/// Programmatic fault injector attached to a Connection
+///
+/// Faults are queued per direction and each one applies to the next unit of
+/// I/O in that direction; units without a queued fault pass unchanged.
+#[derive(Debug, Default)]
+pub struct FaultInjector {
+    send: Mutex<FaultQueue>,
+    receive: Mutex<FaultQueue>,
+    reset: AtomicBool,
+}
+
+impl FaultInjector {
+    /// Create an injector with no faults queued
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a fault for the next unit in the given direction
+    pub fn inject(&self, direction: FaultDirection, fault: Fault) {
+        self.queue(direction)
+            .lock()
+            .unwrap()
+            .faults
+            .push_back(fault);
+    }
+
+    /// Reset the connection at its next I/O, as if the peer sent a TCP RST
+    pub fn reset_connection(&self) {
+        self.reset.store(true, Ordering::SeqCst);
+    }
+
+    /// Number of queued faults that have not been applied yet
+    pub fn pending(&self, direction: FaultDirection) -> usize {
+        self.queue(direction).lock().unwrap().faults.len()
+    }
+
+    /// Discard queued faults; a unit held for reordering is still delivered
+    pub fn clear(&self) {
+        self.send.lock().unwrap().faults.clear();
+        self.receive.lock().unwrap().faults.clear();
+        self.reset.store(false, Ordering::SeqCst);
+    }
+
+    pub(crate) fn take_reset(&self) -> bool {
+        self.reset.swap(false, Ordering::SeqCst)
+    }
+
+    /// Apply the next queued fault to a unit, returning the units to pass on
+    pub(crate) async fn perturb(&self, direction: FaultDirection, data: Vec<u8>) -> Vec<Vec<u8>> {
+        let (delay, units) = self.apply(direction, data);
+        if let Some(delay) = delay {
+            runtime::sleep(delay).await;
+        }
+        units
+    }
+
+    fn apply(&self, direction: FaultDirection, data: Vec<u8>) -> (Option<Duration>, Vec<Vec<u8>>) {
+        let mut queue = self.queue(direction).lock().unwrap();
+        let mut delay = None;
+
+        let mut units = match queue.faults.pop_front() {
+            Some(Fault::Reorder) => {
+                // A unit held earlier keeps its place ahead of this one
+                return (None, queue.held.replace(data).into_iter().collect());
+            }
+            Some(Fault::Delay(duration)) => {
+                delay = Some(duration);
+                vec![data]
+            }
+            Some(Fault::Drop) => Vec::new(),
+            Some(Fault::Duplicate) => vec![data.clone(), data],
+            Some(Fault::Corrupt) => {
+                let mut data = data;
+                let middle = data.len() / 2;
+                if let Some(byte) = data.get_mut(middle) {
+                    *byte ^= 0xFF;
+                }
+                vec![data]
+            }
+            None => vec![data],
+        };
+
+        units.extend(queue.held.take());
+        (delay, units)
+    }
+
+    fn queue(&self, direction: FaultDirection) -> &Mutex<FaultQueue> {
+        match direction {
+            FaultDirection::Send => &self.send,
+            FaultDirection::Receive => &self.receive,
+        }
+    }
+}
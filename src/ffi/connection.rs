@@ -1,11 +1,54 @@
 //! FFI bindings for Connection
 
+use super::message_context::ReceivedContext;
+use super::property_json::{property_from_json, property_to_json};
 use super::*;
-use crate::{Connection, ConnectionEvent, Message};
+use crate::{Connection, ConnectionEvent, Message, TransportServicesError};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::ffi::CStr;
 use std::os::raw::c_int;
 use std::slice;
+use std::sync::Mutex;
+use tokio::task::JoinHandle;
+
+/// Callback delivery task of each connection handle with callbacks set
+static CALLBACK_TASKS: Lazy<Mutex<HashMap<usize, JoinHandle<()>>>> = Lazy::new(Mutex::default);
+
+/// Make `task` the handle's callback delivery, cancelling the one before it
+fn register_callback_task(handle: *mut TransportServicesHandle, task: JoinHandle<()>) {
+    let previous = CALLBACK_TASKS.lock().unwrap().insert(handle as usize, task);
+    if let Some(previous) = previous {
+        cancel_callback_task(previous);
+    }
+}
+
+/// Stop a callback delivery task
+///
+/// Outside the runtime this waits for a callback in progress to return, so
+/// no callback runs once the caller goes on to free its user data. From a
+/// callback, which runs on the runtime, no further callback starts after the
+/// current one returns.
+fn cancel_callback_task(task: JoinHandle<()>) {
+    task.abort();
+    if tokio::runtime::Handle::try_current().is_err() {
+        let _ = futures::executor::block_on(task);
+    }
+}
+
+/// Stop delivering callbacks for a connection handle, if any were set
+fn clear_callback_task(handle: *mut TransportServicesHandle) {
+    let task = CALLBACK_TASKS.lock().unwrap().remove(&(handle as usize));
+    if let Some(task) = task {
+        cancel_callback_task(task);
+    }
+}
 
 /// Get the state of a connection
+///
+/// # Safety
+///
+/// `handle` must be null or a connection handle that has not been freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_connection_get_state(
     handle: *mut TransportServicesHandle,
@@ -24,6 +67,13 @@ pub unsafe extern "C" fn transport_services_connection_get_state(
 }
 
 /// Send a message on a connection
+///
+/// # Safety
+///
+/// `handle` must be null or a connection handle that has not been freed.
+/// `message` must be null or point to a valid `TransportServicesMessage` whose
+/// `data` holds `length` bytes. `user_data` is handed to the callbacks
+/// unchanged and must stay valid for as long as they may be called.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_connection_send(
     handle: *mut TransportServicesHandle,
@@ -105,7 +155,58 @@ pub unsafe extern "C" fn transport_services_connection_send(
     types::TransportServicesError::Success
 }
 
+/// Hand received data to a receive callback, with its context for the
+/// duration of the call; other events are ignored
+fn deliver_received(
+    event: ConnectionEvent,
+    callback: types::TransportServicesReceiveCallback,
+    user_data: *mut c_void,
+) {
+    let (message_data, received) = match event {
+        ConnectionEvent::Received {
+            message_data,
+            message_context,
+        } => (
+            message_data,
+            ReceivedContext {
+                context: message_context,
+                end_of_message: true,
+            },
+        ),
+        // The context tells the callback whether the message is complete
+        ConnectionEvent::ReceivedPartial {
+            message_data,
+            message_context,
+            end_of_message,
+        } => (
+            message_data,
+            ReceivedContext {
+                context: message_context,
+                end_of_message,
+            },
+        ),
+        _ => return,
+    };
+
+    // Lifetime, priority and idempotence are not available on received messages
+    let ffi_message = types::TransportServicesMessage {
+        data: message_data.as_ptr(),
+        length: message_data.len(),
+        lifetime_ms: 0,
+        priority: 0,
+        idempotent: false,
+        final_message: received.context.is_final(),
+    };
+    callback(&ffi_message, received.as_ptr(), user_data);
+}
+
 /// Receive messages asynchronously on a connection
+///
+/// # Safety
+///
+/// `handle` must be null or a connection handle that has not been freed.
+/// `user_data` is handed to the callbacks unchanged and must stay valid for as
+/// long as they may be called.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_connection_receive(
     handle: *mut TransportServicesHandle,
@@ -138,46 +239,13 @@ pub unsafe extern "C" fn transport_services_connection_receive(
         // Start receiving messages in a loop
         loop {
             match conn_clone.next_event().await {
-                Some(ConnectionEvent::Received {
-                    message_data,
-                    message_context: _,
-                }) => {
-                    // Convert message to FFI format
-                    let ffi_message = types::TransportServicesMessage {
-                        data: message_data.as_ptr(),
-                        length: message_data.len(),
-                        lifetime_ms: 0,    // Not available in received message context
-                        priority: 0,       // Not available in received message context
-                        idempotent: false, // Not available in received message context
-                        final_message: true, // Not available in received message context
-                    };
-
-                    // Call the message callback
-                    (callback_data.message_callback)(
-                        &ffi_message,
-                        std::ptr::null(), // context not implemented yet
-                        callback_data.user_data as *mut c_void,
-                    );
-                }
-                Some(ConnectionEvent::ReceivedPartial {
-                    message_data,
-                    end_of_message,
-                    ..
-                }) => {
-                    // For partial messages, accumulate or handle differently
-                    // For now, just treat as complete message
-                    let ffi_message = types::TransportServicesMessage {
-                        data: message_data.as_ptr(),
-                        length: message_data.len(),
-                        lifetime_ms: 0,
-                        priority: 0,
-                        idempotent: false,
-                        final_message: end_of_message,
-                    };
-
-                    (callback_data.message_callback)(
-                        &ffi_message,
-                        std::ptr::null(),
+                Some(
+                    event @ (ConnectionEvent::Received { .. }
+                    | ConnectionEvent::ReceivedPartial { .. }),
+                ) => {
+                    deliver_received(
+                        event,
+                        callback_data.message_callback,
                         callback_data.user_data as *mut c_void,
                     );
                 }
@@ -209,6 +277,12 @@ pub unsafe extern "C" fn transport_services_connection_receive(
 }
 
 /// Close a connection gracefully (async)
+///
+/// # Safety
+///
+/// `handle` must be null or a connection handle that has not been freed.
+/// `user_data` is handed to the callbacks unchanged and must stay valid for as
+/// long as they may be called.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_connection_close_async(
     handle: *mut TransportServicesHandle,
@@ -258,6 +332,10 @@ pub unsafe extern "C" fn transport_services_connection_close_async(
 }
 
 /// Close a connection gracefully (blocking version for backward compatibility)
+///
+/// # Safety
+///
+/// `handle` must be null or a connection handle that has not been freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_connection_close(
     handle: *mut TransportServicesHandle,
@@ -280,6 +358,10 @@ pub unsafe extern "C" fn transport_services_connection_close(
 }
 
 /// Abort a connection immediately
+///
+/// # Safety
+///
+/// `handle` must be null or a connection handle that has not been freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_connection_abort(
     handle: *mut TransportServicesHandle,
@@ -301,14 +383,121 @@ pub unsafe extern "C" fn transport_services_connection_abort(
     }
 }
 
+/// Set a connection property from its value as JSON text
+/// RFC Section 8: Connection.SetProperty(property, value)
+///
+/// Any property can be set this way, e.g. key "connTimeout" with value
+/// "30000", or "connScheduler" with "\"fifo\""; the JSON each property
+/// takes is documented with the property_json module.
+///
+/// # Safety
+///
+/// `handle` must be null or a connection handle that has not been freed. `key`
+/// must be null or point to a NUL-terminated string. `json_value` must be null
+/// or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_connection_set_property_json(
+    handle: *mut TransportServicesHandle,
+    key: *const c_char,
+    json_value: *const c_char,
+) -> types::TransportServicesError {
+    if handle.is_null() || key.is_null() || json_value.is_null() {
+        return types::TransportServicesError::InvalidParameters;
+    }
+    let (Ok(key), Ok(json_value)) = (
+        CStr::from_ptr(key).to_str(),
+        CStr::from_ptr(json_value).to_str(),
+    ) else {
+        return types::TransportServicesError::InvalidParameters;
+    };
+
+    let conn = handle_ref::<Connection>(handle);
+    let property = serde_json::from_str(json_value)
+        .map_err(|e| TransportServicesError::InvalidParameters(format!("Invalid JSON: {e}")))
+        .and_then(|value| property_from_json(key, &value));
+
+    // Use tokio runtime to execute async operation
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    match property.and_then(|property| rt.block_on(conn.set_property(key, property))) {
+        Ok(()) => types::TransportServicesError::Success,
+        Err(e) => {
+            error::set_last_error(&e);
+            types::TransportServicesError::from(e)
+        }
+    }
+}
+
+/// Get a connection property's value as JSON text
+/// RFC Section 8: Connection.GetProperties()
+///
+/// Returns null if the connection has no such property. The string must be
+/// freed with transport_services_free_string.
+///
+/// # Safety
+///
+/// `handle` must be null or a connection handle that has not been freed. `key`
+/// must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_connection_get_property_json(
+    handle: *mut TransportServicesHandle,
+    key: *const c_char,
+) -> *mut c_char {
+    if handle.is_null() || key.is_null() {
+        return std::ptr::null_mut();
+    }
+    let Ok(key) = CStr::from_ptr(key).to_str() else {
+        return std::ptr::null_mut();
+    };
+
+    let conn = handle_ref::<Connection>(handle);
+
+    // Use tokio runtime to execute async operation
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let Some(property) = rt.block_on(conn.get_property(key)) else {
+        return std::ptr::null_mut();
+    };
+    CString::new(property_to_json(&property).to_string())
+        .map_or(std::ptr::null_mut(), CString::into_raw)
+}
+
 /// Free a connection handle
+///
+/// Callbacks set on the connection are cleared first, as with
+/// `transport_services_connection_clear_callbacks`.
+///
+/// # Safety
+///
+/// `handle` must be null or a connection handle that has not already been
+/// freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_connection_free(handle: *mut TransportServicesHandle) {
     if !handle.is_null() {
+        clear_callback_task(handle);
         let _ = from_handle::<Connection>(handle);
     }
 }
 
+/// Stop calling the callbacks set on a connection
+///
+/// When called outside a callback this returns once no callback is running,
+/// after which their `user_data` may be freed. Called from a callback, no
+/// further callback starts once that one returns. Setting callbacks again
+/// replaces the previous ones in the same way.
+///
+/// # Safety
+///
+/// `handle` must be null or a connection handle that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_connection_clear_callbacks(
+    handle: *mut TransportServicesHandle,
+) -> types::TransportServicesError {
+    if handle.is_null() {
+        return types::TransportServicesError::InvalidParameters;
+    }
+    clear_callback_task(handle);
+    types::TransportServicesError::Success
+}
+
 /// FFI callback for connection events
 pub type TransportServicesConnectionEventCallback = extern "C" fn(
     connection: *mut TransportServicesHandle,
@@ -317,7 +506,80 @@ pub type TransportServicesConnectionEventCallback = extern "C" fn(
     user_data: *mut c_void,
 );
 
+/// The event type and description the event callback reports for an event,
+/// or None for received data
+fn event_description(
+    event: &ConnectionEvent,
+) -> Option<(types::TransportServicesConnectionEventType, &str)> {
+    Some(match event {
+        ConnectionEvent::Ready => (
+            types::TransportServicesConnectionEventType::Ready,
+            "Connection established",
+        ),
+        ConnectionEvent::EstablishmentError(ref m) => (
+            types::TransportServicesConnectionEventType::EstablishmentError,
+            m.as_str(),
+        ),
+        ConnectionEvent::ConnectionError(ref m) => (
+            types::TransportServicesConnectionEventType::ConnectionError,
+            m.as_str(),
+        ),
+        ConnectionEvent::PathChange => (
+            types::TransportServicesConnectionEventType::PathChange,
+            "Path changed",
+        ),
+        ConnectionEvent::SoftError(ref m) => (
+            types::TransportServicesConnectionEventType::SoftError,
+            m.as_str(),
+        ),
+        ConnectionEvent::Closed => (
+            types::TransportServicesConnectionEventType::Closed,
+            "Connection closed",
+        ),
+        ConnectionEvent::Sent { .. } => (
+            types::TransportServicesConnectionEventType::Sent,
+            "Message sent",
+        ),
+        ConnectionEvent::Expired { .. } => (
+            types::TransportServicesConnectionEventType::Expired,
+            "Message expired",
+        ),
+        ConnectionEvent::SendError { .. } => (
+            types::TransportServicesConnectionEventType::SendError,
+            "Send error",
+        ),
+        ConnectionEvent::Received { .. } | ConnectionEvent::ReceivedPartial { .. } => return None,
+        ConnectionEvent::ReceiveError { ref error, .. } => (
+            types::TransportServicesConnectionEventType::ReceiveError,
+            error.as_str(),
+        ),
+        ConnectionEvent::FinalReceived => (
+            types::TransportServicesConnectionEventType::FinalReceived,
+            "Final message received",
+        ),
+        ConnectionEvent::Stats(_) => (
+            types::TransportServicesConnectionEventType::Stats,
+            "Connection statistics",
+        ),
+        ConnectionEvent::Unresponsive { .. } => (
+            types::TransportServicesConnectionEventType::Unresponsive,
+            "Peer unresponsive",
+        ),
+    })
+}
+
 /// Set event callback for a connection
+///
+/// `user_data` must stay valid until the Closed event has been delivered, or
+/// until the callback is cleared with
+/// `transport_services_connection_clear_callbacks`, replaced, or the
+/// connection freed, whichever comes first.
+///
+/// # Safety
+///
+/// `handle` must be null or a connection handle that has not been freed.
+/// `user_data` is handed to the callbacks unchanged and must stay valid for as
+/// long as they may be called.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_connection_set_event_callback(
     handle: *mut TransportServicesHandle,
@@ -344,81 +606,104 @@ pub unsafe extern "C" fn transport_services_connection_set_event_callback(
 
     // Spawn async task to handle events using the global runtime
     match runtime::spawn(async move {
-        // Start receiving events in a loop
-        loop {
-            match conn_clone.next_event().await {
-                Some(event) => {
-                    let (evt_type, msg) = match event {
-                        ConnectionEvent::Ready => (
-                            types::TransportServicesConnectionEventType::Ready,
-                            "Connection established",
-                        ),
-                        ConnectionEvent::EstablishmentError(ref m) => (
-                            types::TransportServicesConnectionEventType::EstablishmentError,
-                            m.as_str(),
-                        ),
-                        ConnectionEvent::ConnectionError(ref m) => (
-                            types::TransportServicesConnectionEventType::ConnectionError,
-                            m.as_str(),
-                        ),
-                        ConnectionEvent::PathChange => (
-                            types::TransportServicesConnectionEventType::PathChange,
-                            "Path changed",
-                        ),
-                        ConnectionEvent::SoftError(ref m) => (
-                            types::TransportServicesConnectionEventType::SoftError,
-                            m.as_str(),
-                        ),
-                        ConnectionEvent::Closed => (
-                            types::TransportServicesConnectionEventType::Closed,
-                            "Connection closed",
-                        ),
-                        ConnectionEvent::Sent { .. } => (
-                            types::TransportServicesConnectionEventType::Sent,
-                            "Message sent",
-                        ),
-                        ConnectionEvent::Expired { .. } => (
-                            types::TransportServicesConnectionEventType::Expired,
-                            "Message expired",
-                        ),
-                        ConnectionEvent::SendError { .. } => (
-                            types::TransportServicesConnectionEventType::SendError,
-                            "Send error",
-                        ),
-                        ConnectionEvent::Received { .. }
-                        | ConnectionEvent::ReceivedPartial { .. } => {
-                            // Skip these events as they should be handled by receive callback
-                            continue;
-                        }
-                        ConnectionEvent::ReceiveError { ref error, .. } => (
-                            types::TransportServicesConnectionEventType::Received,
-                            error.as_str(),
-                        ),
-                    };
-
-                    // Convert message to C string
-                    let c_msg = CString::new(msg).unwrap_or_else(|_| CString::new("").unwrap());
-
-                    // Call the event callback
-                    (callback_data.event_callback)(
-                        evt_type,
-                        c_msg.as_ptr(),
-                        callback_data.user_data as *mut c_void,
-                    );
+        // Start receiving events in a loop, until there are no more
+        while let Some(event) = conn_clone.next_event().await {
+            // Received data is for the receive callback
+            let Some((evt_type, msg)) = event_description(&event) else {
+                continue;
+            };
 
-                    // Check if we should stop (closed event)
-                    if matches!(event, ConnectionEvent::Closed) {
-                        break;
-                    }
-                }
-                None => {
-                    // No more events
-                    break;
-                }
+            // Convert message to C string
+            let c_msg = CString::new(msg).unwrap_or_else(|_| CString::new("").unwrap());
+
+            // Call the event callback
+            (callback_data.event_callback)(
+                evt_type,
+                c_msg.as_ptr(),
+                callback_data.user_data as *mut c_void,
+            );
+
+            // Check if we should stop (closed event)
+            if matches!(event, ConnectionEvent::Closed) {
+                break;
             }
         }
     }) {
-        Ok(_) => types::TransportServicesError::Success,
+        Ok(task) => {
+            register_callback_task(handle, task);
+            types::TransportServicesError::Success
+        }
+        Err(e) => {
+            error::set_last_error_string(&e);
+            types::TransportServicesError::RuntimeError
+        }
+    }
+}
+
+/// Deliver received data and every other event on a connection from one task
+///
+/// Data goes to `message_callback` as with `transport_services_connection_receive`
+/// and everything else to `event_callback` as with
+/// `transport_services_connection_set_event_callback`. A connection has a
+/// single event queue, so registering those two separately splits events
+/// between them and each drops what the other needed; use this instead.
+/// Delivery stops after the Closed event. `user_data` must stay valid until
+/// then, or until the callbacks are cleared with
+/// `transport_services_connection_clear_callbacks`, replaced, or the
+/// connection freed.
+///
+/// # Safety
+///
+/// `handle` must be null or a connection handle that has not been freed.
+/// `user_data` is handed to the callbacks unchanged and must stay valid for as
+/// long as they may be called.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_connection_set_callbacks(
+    handle: *mut TransportServicesHandle,
+    message_callback: types::TransportServicesReceiveCallback,
+    event_callback: types::TransportServicesEventCallback,
+    user_data: *mut c_void,
+) -> types::TransportServicesError {
+    if handle.is_null() {
+        return types::TransportServicesError::InvalidParameters;
+    }
+
+    let conn = handle_ref::<Connection>(handle);
+    let conn_clone = conn.clone();
+
+    // Wrap user_data in a type that is Send
+    struct CallbackData {
+        message_callback: types::TransportServicesReceiveCallback,
+        event_callback: types::TransportServicesEventCallback,
+        user_data: usize,
+    }
+
+    let callback_data = CallbackData {
+        message_callback,
+        event_callback,
+        user_data: user_data as usize,
+    };
+
+    match runtime::spawn(async move {
+        while let Some(event) = conn_clone.next_event().await {
+            let user_data = callback_data.user_data as *mut c_void;
+            let Some((evt_type, msg)) = event_description(&event) else {
+                deliver_received(event, callback_data.message_callback, user_data);
+                continue;
+            };
+
+            let c_msg = CString::new(msg).unwrap_or_else(|_| CString::new("").unwrap());
+            (callback_data.event_callback)(evt_type, c_msg.as_ptr(), user_data);
+
+            if matches!(event, ConnectionEvent::Closed) {
+                break;
+            }
+        }
+    }) {
+        Ok(task) => {
+            register_callback_task(handle, task);
+            types::TransportServicesError::Success
+        }
         Err(e) => {
             error::set_last_error_string(&e);
             types::TransportServicesError::RuntimeError
@@ -427,6 +712,12 @@ pub unsafe extern "C" fn transport_services_connection_set_event_callback(
 }
 
 /// Poll for the next event on a connection (non-blocking) - DEPRECATED
+///
+/// # Safety
+///
+/// `handle` must be null or a connection handle that has not been freed.
+/// `event_type` must be null or valid for writes. `message_buffer` must be null
+/// or valid for writes of `message_buffer_size` bytes.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_connection_poll_event(
     handle: *mut TransportServicesHandle,
@@ -493,9 +784,21 @@ pub unsafe extern "C" fn transport_services_connection_poll_event(
                     "Partial message received",
                 ),
                 ConnectionEvent::ReceiveError { ref error, .. } => (
-                    types::TransportServicesConnectionEventType::Received,
+                    types::TransportServicesConnectionEventType::ReceiveError,
                     error.as_str(),
                 ),
+                ConnectionEvent::FinalReceived => (
+                    types::TransportServicesConnectionEventType::FinalReceived,
+                    "Final message received",
+                ),
+                ConnectionEvent::Stats(_) => (
+                    types::TransportServicesConnectionEventType::Stats,
+                    "Connection statistics",
+                ),
+                ConnectionEvent::Unresponsive { .. } => (
+                    types::TransportServicesConnectionEventType::Unresponsive,
+                    "Peer unresponsive",
+                ),
             };
 
             *event_type = evt_type;
@@ -4,7 +4,7 @@ use std::ffi::CString;
 use std::os::raw::c_char;
 
 thread_local! {
-    static LAST_ERROR: std::cell::RefCell<Option<CString>> = std::cell::RefCell::new(None);
+    static LAST_ERROR: std::cell::RefCell<Option<CString>> = const { std::cell::RefCell::new(None) };
 }
 
 /// Set the last error message
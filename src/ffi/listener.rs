@@ -5,6 +5,12 @@ use crate::{Listener, ListenerEvent};
 use std::ffi::CString;
 
 /// Set callbacks for a listener to handle incoming connections
+///
+/// # Safety
+///
+/// `handle` must be null or a listener handle that has not been freed.
+/// `user_data` is handed to the callbacks unchanged and must stay valid for as
+/// long as they may be called.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_listener_set_callbacks(
     handle: *mut TransportServicesHandle,
@@ -56,6 +62,23 @@ pub unsafe extern "C" fn transport_services_listener_set_callbacks(
                         callback_data.user_data as *mut c_void,
                     );
                 }
+                Some(ListenerEvent::EstablishmentError {
+                    remote, ref reason, ..
+                }) => {
+                    let text = match remote {
+                        Some(addr) => format!("{addr}: {reason}"),
+                        None => reason.clone(),
+                    };
+                    let c_msg = CString::new(text).unwrap_or_else(|_| CString::new("").unwrap());
+                    (callback_data.error_callback)(
+                        types::TransportServicesError::EstablishmentFailed,
+                        c_msg.as_ptr(),
+                        callback_data.user_data as *mut c_void,
+                    );
+                }
+                Some(ListenerEvent::AddressAdded(_) | ListenerEvent::AddressRemoved(_)) => {
+                    // Address changes have no C callback
+                }
                 Some(ListenerEvent::Stopped) => {
                     // Listener stopped, exit the loop
                     break;
@@ -76,6 +99,12 @@ pub unsafe extern "C" fn transport_services_listener_set_callbacks(
 }
 
 /// Stop a listener asynchronously
+///
+/// # Safety
+///
+/// `handle` must be null or a listener handle that has not been freed.
+/// `user_data` is handed to the callbacks unchanged and must stay valid for as
+/// long as they may be called.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_listener_stop_async(
     handle: *mut TransportServicesHandle,
@@ -125,6 +154,10 @@ pub unsafe extern "C" fn transport_services_listener_stop_async(
 }
 
 /// Stop a listener (blocking version for backward compatibility)
+///
+/// # Safety
+///
+/// `handle` must be null or a listener handle that has not been freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_listener_stop(
     handle: *mut TransportServicesHandle,
@@ -147,6 +180,10 @@ pub unsafe extern "C" fn transport_services_listener_stop(
 }
 
 /// Check if a listener is active
+///
+/// # Safety
+///
+/// `handle` must be null or a listener handle that has not been freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_listener_is_active(
     handle: *mut TransportServicesHandle,
@@ -163,6 +200,10 @@ pub unsafe extern "C" fn transport_services_listener_is_active(
 }
 
 /// Free a listener handle
+///
+/// # Safety
+///
+/// `handle` must be null or a listener handle that has not already been freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_listener_free(handle: *mut TransportServicesHandle) {
     if !handle.is_null() {
@@ -5,6 +5,10 @@ use crate::Message;
 use std::slice;
 
 /// Create a new message
+///
+/// # Safety
+///
+/// `data` must be null or point to `length` readable bytes.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_message_new(
     data: *const u8,
@@ -21,6 +25,11 @@ pub unsafe extern "C" fn transport_services_message_new(
 }
 
 /// Get message data
+///
+/// # Safety
+///
+/// `handle` must be null or a message handle that has not been freed. `length`
+/// must be null or valid for writes.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_message_get_data(
     handle: *mut TransportServicesHandle,
@@ -38,6 +47,10 @@ pub unsafe extern "C" fn transport_services_message_get_data(
 }
 
 /// Set message priority
+///
+/// # Safety
+///
+/// `handle` must be null or a message handle that has not been freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_message_set_priority(
     handle: *mut TransportServicesHandle,
@@ -54,6 +67,10 @@ pub unsafe extern "C" fn transport_services_message_set_priority(
 }
 
 /// Set message lifetime
+///
+/// # Safety
+///
+/// `handle` must be null or a message handle that has not been freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_message_set_lifetime(
     handle: *mut TransportServicesHandle,
@@ -70,6 +87,10 @@ pub unsafe extern "C" fn transport_services_message_set_lifetime(
 }
 
 /// Mark message as idempotent
+///
+/// # Safety
+///
+/// `handle` must be null or a message handle that has not been freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_message_set_idempotent(
     handle: *mut TransportServicesHandle,
@@ -86,6 +107,10 @@ pub unsafe extern "C" fn transport_services_message_set_idempotent(
 }
 
 /// Free a message handle
+///
+/// # Safety
+///
+/// `handle` must be null or a message handle that has not already been freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_message_free(handle: *mut TransportServicesHandle) {
     if !handle.is_null() {
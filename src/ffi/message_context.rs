@@ -0,0 +1,216 @@
+//! FFI bindings for MessageContext
+//!
+//! The receive callback's context argument points to one of these for the
+//! duration of the callback only; copy out anything needed later.
+
+use super::*;
+use crate::{EndpointIdentifier, MessageContext};
+use std::os::raw::c_int;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// What the receive callback's context argument points to
+pub(crate) struct ReceivedContext {
+    pub(crate) context: MessageContext,
+    /// Whether the data completes its message; partial deliveries before
+    /// the last carry false
+    pub(crate) end_of_message: bool,
+}
+
+impl ReceivedContext {
+    /// The pointer handed to the receive callback
+    pub(crate) fn as_ptr(&self) -> *const c_void {
+        self as *const Self as *const c_void
+    }
+}
+
+unsafe fn received_context<'a>(context: *const c_void) -> Option<&'a ReceivedContext> {
+    (context as *const ReceivedContext).as_ref()
+}
+
+/// Write an endpoint's address as `host:port`, or just the host without a port
+fn endpoint_address(identifiers: &[EndpointIdentifier]) -> Option<String> {
+    let mut host = None;
+    let mut port = None;
+    for identifier in identifiers {
+        match identifier {
+            EndpointIdentifier::SocketAddress(addr) => return Some(addr.to_string()),
+            EndpointIdentifier::IpAddress(ip) => host = Some(ip.to_string()),
+            EndpointIdentifier::HostName(name) => {
+                host.get_or_insert_with(|| name.clone());
+            }
+            EndpointIdentifier::Port(p) => port = Some(*p),
+            _ => {}
+        }
+    }
+    let host = host?;
+    Some(match port {
+        Some(port) if host.contains(':') => format!("[{host}]:{port}"),
+        Some(port) => format!("{host}:{port}"),
+        None => host,
+    })
+}
+
+fn into_c_string(value: Option<String>) -> *mut c_char {
+    value
+        .and_then(|value| CString::new(value).ok())
+        .map_or(std::ptr::null_mut(), CString::into_raw)
+}
+
+/// Microseconds since the Unix epoch at which `instant` occurred
+fn unix_micros(instant: Instant) -> u64 {
+    let time = SystemTime::now() - instant.elapsed();
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_micros() as u64)
+}
+
+/// Get the remote endpoint a message came from, as `host:port`
+/// The string must be freed with transport_services_free_string; null if unknown
+///
+/// # Safety
+///
+/// `context` must be null or the context passed to the receive callback, used
+/// before that callback returns.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_message_context_get_remote_endpoint(
+    context: *const c_void,
+) -> *mut c_char {
+    let Some(received) = received_context(context) else {
+        return std::ptr::null_mut();
+    };
+    let remote = received.context.remote_endpoint.as_ref();
+    into_c_string(remote.and_then(|endpoint| endpoint_address(&endpoint.identifiers)))
+}
+
+/// Get the local endpoint a message arrived at, as `host:port`
+/// The string must be freed with transport_services_free_string; null if unknown
+///
+/// # Safety
+///
+/// `context` must be null or the context passed to the receive callback, used
+/// before that callback returns.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_message_context_get_local_endpoint(
+    context: *const c_void,
+) -> *mut c_char {
+    let Some(received) = received_context(context) else {
+        return std::ptr::null_mut();
+    };
+    let local = received.context.local_endpoint.as_ref();
+    into_c_string(local.and_then(|endpoint| endpoint_address(&endpoint.identifiers)))
+}
+
+/// Get the ECN marking a message arrived with
+/// Returns 1 if the platform did not report it
+///
+/// # Safety
+///
+/// `context` must be null or the context passed to the receive callback, used
+/// before that callback returns. `ecn` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_message_context_get_ecn(
+    context: *const c_void,
+    ecn: *mut types::TransportServicesEcnMarking,
+) -> c_int {
+    let Some(received) = received_context(context) else {
+        return -1;
+    };
+    if ecn.is_null() {
+        return -1;
+    }
+    match received.context.ecn {
+        Some(marking) => {
+            *ecn = marking.into();
+            0
+        }
+        None => 1,
+    }
+}
+
+/// Whether a message was received as early data (0-RTT)
+///
+/// # Safety
+///
+/// `context` must be null or the context passed to the receive callback, used
+/// before that callback returns.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_message_context_is_early_data(
+    context: *const c_void,
+) -> bool {
+    received_context(context).is_some_and(|received| received.context.early_data)
+}
+
+/// Whether the peer marked a message as Final
+///
+/// # Safety
+///
+/// `context` must be null or the context passed to the receive callback, used
+/// before that callback returns.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_message_context_is_final(
+    context: *const c_void,
+) -> bool {
+    received_context(context).is_some_and(|received| received.context.is_final())
+}
+
+/// Whether the delivered data completes its message
+/// Always true for whole messages; false for all but the last partial delivery
+///
+/// # Safety
+///
+/// `context` must be null or the context passed to the receive callback, used
+/// before that callback returns.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_message_context_is_end_of_message(
+    context: *const c_void,
+) -> bool {
+    received_context(context).is_some_and(|received| received.end_of_message)
+}
+
+/// Get when a message was received, in microseconds since the Unix epoch
+///
+/// # Safety
+///
+/// `context` must be null or the context passed to the receive callback, used
+/// before that callback returns. `unix_us` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_message_context_get_received_time(
+    context: *const c_void,
+    unix_us: *mut u64,
+) -> c_int {
+    let Some(received) = received_context(context) else {
+        return -1;
+    };
+    if unix_us.is_null() {
+        return -1;
+    }
+    *unix_us = unix_micros(received.context.received_at);
+    0
+}
+
+/// Get when the network interface received a message, in microseconds
+/// since the Unix epoch
+/// Returns 1 unless receive timestamps are enabled and the platform reports them
+///
+/// # Safety
+///
+/// `context` must be null or the context passed to the receive callback, used
+/// before that callback returns. `unix_us` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_message_context_get_interface_timestamp(
+    context: *const c_void,
+    unix_us: *mut u64,
+) -> c_int {
+    let Some(received) = received_context(context) else {
+        return -1;
+    };
+    if unix_us.is_null() {
+        return -1;
+    }
+    match received.context.interface_timestamp {
+        Some(timestamp) => {
+            *unix_us = unix_micros(timestamp);
+            0
+        }
+        None => 1,
+    }
+}
@@ -5,8 +5,10 @@ pub mod connection;
 pub mod error;
 pub mod listener;
 pub mod message;
+pub mod message_context;
 pub mod path_monitor;
 pub mod preconnection;
+pub mod property_json;
 pub mod runtime;
 pub mod security_parameters;
 pub mod transport_properties;
@@ -59,7 +61,18 @@ pub extern "C" fn transport_services_version() -> *const c_char {
     version.into_raw()
 }
 
+/// Get the protocols and features this build provides on this platform
+#[no_mangle]
+pub extern "C" fn transport_services_capabilities() -> types::TransportServicesCapabilities {
+    crate::capabilities().into()
+}
+
 /// Free a string returned by the Transport Services library
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by this library that has not already
+/// been freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_free_string(s: *mut c_char) {
     if !s.is_null() {
@@ -79,16 +92,31 @@ pub fn to_handle<T>(obj: Box<T>) -> *mut TransportServicesHandle {
 }
 
 /// Convert an opaque handle back to a Rust object
+///
+/// # Safety
+///
+/// `handle` must come from `to_handle` for the same `T` and not have been
+/// freed.
 pub unsafe fn from_handle<T>(handle: *mut TransportServicesHandle) -> Box<T> {
     Box::from_raw(handle as *mut T)
 }
 
 /// Get a reference from an opaque handle
+///
+/// # Safety
+///
+/// `handle` must come from `to_handle` for the same `T` and not have been
+/// freed.
 pub unsafe fn handle_ref<'a, T>(handle: *const TransportServicesHandle) -> &'a T {
     &*(handle as *const T)
 }
 
 /// Get a mutable reference from an opaque handle
+///
+/// # Safety
+///
+/// `handle` must come from `to_handle` for the same `T` and not have been
+/// freed.
 pub unsafe fn handle_mut<'a, T>(handle: *mut TransportServicesHandle) -> &'a mut T {
     &mut *(handle as *mut T)
 }
@@ -89,6 +89,10 @@ pub extern "C" fn transport_services_path_monitor_create() -> *mut TransportServ
 }
 
 /// Destroy a network path monitor
+///
+/// # Safety
+///
+/// `handle` must be null or a path monitor handle that has not been freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_path_monitor_destroy(
     handle: *mut TransportServicesHandle,
@@ -99,6 +103,12 @@ pub unsafe extern "C" fn transport_services_path_monitor_destroy(
 }
 
 /// List all network interfaces
+///
+/// # Safety
+///
+/// `handle` must be null or a path monitor handle that has not been freed.
+/// `interfaces` must be null or valid for writes. `count` must be null or valid
+/// for writes.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_path_monitor_list_interfaces(
     handle: *mut TransportServicesHandle,
@@ -148,6 +158,10 @@ pub unsafe extern "C" fn transport_services_path_monitor_list_interfaces(
 }
 
 /// Free an array of interfaces returned by list_interfaces
+///
+/// # Safety
+///
+/// `interfaces` must be null or valid for writes.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_path_monitor_free_interfaces(
     interfaces: *mut *mut TransportServicesInterface,
@@ -170,6 +184,12 @@ pub unsafe extern "C" fn transport_services_path_monitor_free_interfaces(
 }
 
 /// Start watching for network changes
+///
+/// # Safety
+///
+/// `handle` must be null or a path monitor handle that has not been freed.
+/// `user_data` is handed to the callbacks unchanged and must stay valid for as
+/// long as they may be called.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_path_monitor_start_watching(
     handle: *mut TransportServicesHandle,
@@ -203,6 +223,10 @@ pub unsafe extern "C" fn transport_services_path_monitor_start_watching(
 }
 
 /// Stop watching for network changes
+///
+/// # Safety
+///
+/// `handle` must be null or a watch handle that has not been stopped.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_path_monitor_stop_watching(
     handle: *mut TransportServicesHandle,
@@ -19,6 +19,12 @@ pub extern "C" fn transport_services_preconnection_new() -> *mut TransportServic
 }
 
 /// Add a local endpoint to the preconnection
+///
+/// # Safety
+///
+/// `handle` must be null or a preconnection handle that has not been freed.
+/// `endpoint` must be null or point to a valid `TransportServicesEndpoint`
+/// whose strings are NUL-terminated or null.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_preconnection_add_local_endpoint(
     handle: *mut TransportServicesHandle,
@@ -33,12 +39,14 @@ pub unsafe extern "C" fn transport_services_preconnection_add_local_endpoint(
 
     let mut local = LocalEndpoint::default();
 
-    // Add hostname if provided
+    // Add hostname if provided; address literals name the address to bind
     if !endpoint.hostname.is_null() {
         if let Ok(hostname) = CStr::from_ptr(endpoint.hostname).to_str() {
-            local
-                .identifiers
-                .push(crate::EndpointIdentifier::HostName(hostname.to_string()));
+            let identifier = match hostname.parse::<std::net::IpAddr>() {
+                Ok(ip) => crate::EndpointIdentifier::IpAddress(ip),
+                Err(_) => crate::EndpointIdentifier::HostName(hostname.to_string()),
+            };
+            local.identifiers.push(identifier);
         }
     }
 
@@ -60,12 +68,22 @@ pub unsafe extern "C" fn transport_services_preconnection_add_local_endpoint(
 
     // Use tokio runtime to execute async operation
     let rt = tokio::runtime::Runtime::new().unwrap();
-    rt.block_on(preconn.add_local(local));
-
-    types::TransportServicesError::Success
+    match rt.block_on(preconn.add_local(local)) {
+        Ok(()) => types::TransportServicesError::Success,
+        Err(e) => {
+            error::set_last_error(&e);
+            types::TransportServicesError::from(e)
+        }
+    }
 }
 
 /// Add a remote endpoint to the preconnection
+///
+/// # Safety
+///
+/// `handle` must be null or a preconnection handle that has not been freed.
+/// `endpoint` must be null or point to a valid `TransportServicesEndpoint`
+/// whose strings are NUL-terminated or null.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_preconnection_add_remote_endpoint(
     handle: *mut TransportServicesHandle,
@@ -107,12 +125,21 @@ pub unsafe extern "C" fn transport_services_preconnection_add_remote_endpoint(
 
     // Use tokio runtime to execute async operation
     let rt = tokio::runtime::Runtime::new().unwrap();
-    rt.block_on(preconn.add_remote(remote));
-
-    types::TransportServicesError::Success
+    match rt.block_on(preconn.add_remote(remote)) {
+        Ok(()) => types::TransportServicesError::Success,
+        Err(e) => {
+            error::set_last_error(&e);
+            types::TransportServicesError::from(e)
+        }
+    }
 }
 
 /// Set transport properties on the preconnection
+///
+/// # Safety
+///
+/// `handle` must be null or a preconnection handle that has not been freed.
+/// `properties` must be null or point to a valid `TransportServicesProperties`.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_preconnection_set_transport_properties(
     handle: *mut TransportServicesHandle,
@@ -134,12 +161,88 @@ pub unsafe extern "C" fn transport_services_preconnection_set_transport_properti
 
     // Use tokio runtime to execute async operation
     let rt = tokio::runtime::Runtime::new().unwrap();
-    rt.block_on(preconn.set_transport_properties(transport_props));
+    match rt.block_on(preconn.set_transport_properties(transport_props)) {
+        Ok(()) => types::TransportServicesError::Success,
+        Err(e) => {
+            error::set_last_error(&e);
+            types::TransportServicesError::from(e)
+        }
+    }
+}
 
-    types::TransportServicesError::Success
+/// Replace the preconnection's transport properties with a copy of a
+/// TransportProperties object
+///
+/// Unlike `transport_services_preconnection_set_transport_properties`, this
+/// carries every property set on the object, including interface and PvD
+/// preferences for path selection.
+///
+/// # Safety
+///
+/// `handle` must be null or a preconnection handle that has not been freed.
+/// `properties` must be null or a transport properties handle that has not been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_preconnection_use_transport_properties(
+    handle: *mut TransportServicesHandle,
+    properties: *const TransportServicesHandle,
+) -> types::TransportServicesError {
+    if handle.is_null() || properties.is_null() {
+        return types::TransportServicesError::InvalidParameters;
+    }
+
+    let preconn = handle_mut::<Preconnection>(handle);
+    let properties = handle_ref::<TransportProperties>(properties).clone();
+
+    // Use tokio runtime to execute async operation
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    match rt.block_on(preconn.set_transport_properties(properties)) {
+        Ok(()) => types::TransportServicesError::Success,
+        Err(e) => {
+            error::set_last_error(&e);
+            types::TransportServicesError::from(e)
+        }
+    }
+}
+
+/// Replace the preconnection's security parameters with a copy of a
+/// SecurityParameters object
+///
+/// # Safety
+///
+/// `handle` must be null or a preconnection handle that has not been freed.
+/// `parameters` must be null or a security parameters handle that has not been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_preconnection_use_security_parameters(
+    handle: *mut TransportServicesHandle,
+    parameters: *const TransportServicesHandle,
+) -> types::TransportServicesError {
+    if handle.is_null() || parameters.is_null() {
+        return types::TransportServicesError::InvalidParameters;
+    }
+
+    let preconn = handle_mut::<Preconnection>(handle);
+    let parameters = handle_ref::<SecurityParameters>(parameters).clone();
+
+    // Use tokio runtime to execute async operation
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    match rt.block_on(preconn.set_security_parameters(parameters)) {
+        Ok(()) => types::TransportServicesError::Success,
+        Err(e) => {
+            error::set_last_error(&e);
+            types::TransportServicesError::from(e)
+        }
+    }
 }
 
 /// Initiate a connection
+///
+/// # Safety
+///
+/// `handle` must be null or a preconnection handle that has not been freed.
+/// `user_data` is handed to the callbacks unchanged and must stay valid for as
+/// long as they may be called.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_preconnection_initiate(
     handle: *mut TransportServicesHandle,
@@ -197,7 +300,45 @@ pub unsafe extern "C" fn transport_services_preconnection_initiate(
     types::TransportServicesError::Success
 }
 
+/// Listen for incoming connections on the preconnection's local endpoints
+///
+/// Returns a listener handle, or null with the reason in the last error.
+/// Pass the handle to `transport_services_listener_set_callbacks` to accept
+/// connections and free it with `transport_services_listener_free`.
+///
+/// # Safety
+///
+/// `handle` must be null or a preconnection handle that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_preconnection_listen(
+    handle: *mut TransportServicesHandle,
+) -> *mut TransportServicesHandle {
+    if handle.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    let preconn = handle_ref::<Preconnection>(handle);
+
+    // The listener's accept tasks live on the global runtime
+    match runtime::block_on(preconn.listen()) {
+        Ok(Ok(listener)) => to_handle(Box::new(listener)),
+        Ok(Err(e)) => {
+            error::set_last_error(&e);
+            std::ptr::null_mut()
+        }
+        Err(e) => {
+            error::set_last_error_string(&e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
 /// Free a preconnection handle
+///
+/// # Safety
+///
+/// `handle` must be null or a preconnection handle that has not already been
+/// freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_preconnection_free(
     handle: *mut TransportServicesHandle,
@@ -0,0 +1,352 @@
+//! JSON encoding of connection properties for FFI
+//!
+//! `transport_services_connection_set_property_json` and
+//! `transport_services_connection_get_property_json` take and return a
+//! property's value as a JSON text, so bindings reach every property through
+//! two entry points. Each value type has one encoding:
+//!
+//! | Type | JSON | Properties |
+//! |------|------|------------|
+//! | boolean | `true` / `false` | isolateSession, pathPinning, canSend, canReceive, tcp.userTimeoutEnabled, tcp.userTimeoutChangeable |
+//! | count | integer | connPriority, quic.maxStreamsBidi, quic.maxStreamsUni, sctp.numOutboundStreams, sctp.maxInboundStreams, sctp.maxRetransmissions |
+//! | limit | integer, or `null` for unlimited / system default | minSendRate, maxSendRate, minRecvRate, maxRecvRate (bits per second), groupConnLimit, ipHopLimit, singularTransmissionMsgMaxLen, sendMsgMaxLen, recvMsgMaxLen, pathMtu |
+//! | timeout | milliseconds, or `null` for disabled | connTimeout, keepAliveTimeout, sendTimeout, sendStallThreshold, quic.maxIdleTimeout, sctp.heartbeatInterval |
+//! | duration | milliseconds, or `null` if unset | tcp.userTimeoutValue, sendStall |
+//! | checksum coverage | `"full"`, or the minimum bytes covered | recvChecksumLen |
+//! | name | one of the strings listed below | connScheduler, connCapacityProfile, multipathPolicy, quic.congestionAlgorithm, connState, securityProtocol |
+//! | text | string, or `null` if unknown | pathInterface, pathInterfaceType, pathLocalAddress, pathRemoteAddress (`"ip:port"`), securityAlpn, securityServerName |
+//! | protocol stack | array of layer names, outermost first, e.g. `["TLS1.3", "TCP", "IPv6"]` | protocolStack |
+//! | memory usage | `{"receiveBuffer": n, "sendQueue": n, "eventQueue": n}` in bytes | memoryUsage |
+//! | certificate chain | array of DER certificates as lowercase hex, leaf first, or `null` | securityClientCertificate |
+//!
+//! Names are connScheduler `"weightedFairQueueing"`, `"fifo"`, `"roundRobin"`,
+//! `"proportionalRate"`; connCapacityProfile `"default"`, `"scavenger"`,
+//! `"lowLatencyInteractive"`, `"lowLatencyNonInteractive"`,
+//! `"constantRateStreaming"`, `"capacitySeeking"`; multipathPolicy
+//! `"handover"`, `"active"`, `"redundant"`; quic.congestionAlgorithm
+//! `"newReno"`, `"cubic"`, `"bbr"`; connState `"establishing"`,
+//! `"established"`, `"closing"`, `"closed"`; securityProtocol `"TLS1.2"`,
+//! `"TLS1.3"`, `"DTLS1.2"`, `"DTLS1.3"` or `null`.
+//!
+//! Read-only properties, such as connState, sendStall, memoryUsage and the
+//! path and security properties, are only returned by the getter.
+
+use crate::connection_properties::StackLayer;
+use crate::{
+    CapacityProfile, ChecksumCoverage, CongestionAlgorithm, ConnectionProperty, ConnectionState,
+    MultipathPolicy, Result, SchedulerType, TimeoutValue, TransportServicesError,
+};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+const SCHEDULERS: [(&str, SchedulerType); 4] = [
+    ("weightedFairQueueing", SchedulerType::WeightedFairQueueing),
+    ("fifo", SchedulerType::Fifo),
+    ("roundRobin", SchedulerType::RoundRobin),
+    ("proportionalRate", SchedulerType::ProportionalRate),
+];
+
+const CAPACITY_PROFILES: [(&str, CapacityProfile); 6] = [
+    ("default", CapacityProfile::Default),
+    ("scavenger", CapacityProfile::Scavenger),
+    (
+        "lowLatencyInteractive",
+        CapacityProfile::LowLatencyInteractive,
+    ),
+    (
+        "lowLatencyNonInteractive",
+        CapacityProfile::LowLatencyNonInteractive,
+    ),
+    (
+        "constantRateStreaming",
+        CapacityProfile::ConstantRateStreaming,
+    ),
+    ("capacitySeeking", CapacityProfile::CapacitySeeking),
+];
+
+const MULTIPATH_POLICIES: [(&str, MultipathPolicy); 3] = [
+    ("handover", MultipathPolicy::Handover),
+    ("active", MultipathPolicy::Active),
+    ("redundant", MultipathPolicy::Redundant),
+];
+
+const CONGESTION_ALGORITHMS: [(&str, CongestionAlgorithm); 3] = [
+    ("newReno", CongestionAlgorithm::NewReno),
+    ("cubic", CongestionAlgorithm::Cubic),
+    ("bbr", CongestionAlgorithm::Bbr),
+];
+
+const STATES: [(&str, ConnectionState); 4] = [
+    ("establishing", ConnectionState::Establishing),
+    ("established", ConnectionState::Established),
+    ("closing", ConnectionState::Closing),
+    ("closed", ConnectionState::Closed),
+];
+
+fn name_of<T: PartialEq>(names: &[(&'static str, T)], value: &T) -> Value {
+    names
+        .iter()
+        .find(|(_, named)| named == value)
+        .map_or(Value::Null, |(name, _)| json!(name))
+}
+
+fn named<T: Copy>(names: &[(&str, T)], value: &Value) -> Option<T> {
+    let name = value.as_str()?;
+    names
+        .iter()
+        .find(|(candidate, _)| *candidate == name)
+        .map(|(_, value)| *value)
+}
+
+fn millis(duration: Duration) -> Value {
+    json!(duration.as_millis() as u64)
+}
+
+fn timeout_json(timeout: &TimeoutValue) -> Value {
+    match timeout {
+        TimeoutValue::Disabled => Value::Null,
+        TimeoutValue::Duration(duration) => millis(*duration),
+    }
+}
+
+fn timeout(value: &Value) -> Option<TimeoutValue> {
+    match value {
+        Value::Null => Some(TimeoutValue::Disabled),
+        _ => Some(TimeoutValue::Duration(Duration::from_millis(
+            value.as_u64()?,
+        ))),
+    }
+}
+
+fn count<T: TryFrom<u64>>(value: &Value) -> Option<T> {
+    T::try_from(value.as_u64()?).ok()
+}
+
+/// A count, or None for `null`; the outer None rejects the value
+fn limit<T: TryFrom<u64>>(value: &Value) -> Option<Option<T>> {
+    match value {
+        Value::Null => Some(None),
+        _ => count(value).map(Some),
+    }
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Parse the JSON value of the writable property `key`
+pub(crate) fn property_from_json(key: &str, value: &Value) -> Result<ConnectionProperty> {
+    let (property, expected) = match key {
+        "recvChecksumLen" => (
+            match value {
+                Value::String(full) if full == "full" => Some(ConnectionProperty::RecvChecksumLen(
+                    ChecksumCoverage::FullCoverage,
+                )),
+                _ => count(value).map(|bytes| {
+                    ConnectionProperty::RecvChecksumLen(ChecksumCoverage::MinBytes(bytes))
+                }),
+            },
+            "\"full\" or a byte count",
+        ),
+        "connPriority" => (
+            count(value).map(ConnectionProperty::ConnPriority),
+            "an integer",
+        ),
+        "connTimeout" => (
+            timeout(value).map(ConnectionProperty::ConnTimeout),
+            "milliseconds or null",
+        ),
+        "keepAliveTimeout" => (
+            timeout(value).map(ConnectionProperty::KeepAliveTimeout),
+            "milliseconds or null",
+        ),
+        "sendTimeout" => (
+            timeout(value).map(ConnectionProperty::SendTimeout),
+            "milliseconds or null",
+        ),
+        "sendStallThreshold" => (
+            timeout(value).map(ConnectionProperty::SendStallThreshold),
+            "milliseconds or null",
+        ),
+        "connScheduler" => (
+            named(&SCHEDULERS, value).map(ConnectionProperty::ConnScheduler),
+            "a scheduler name",
+        ),
+        "connCapacityProfile" => (
+            named(&CAPACITY_PROFILES, value).map(ConnectionProperty::ConnCapacityProfile),
+            "a capacity profile name",
+        ),
+        "multipathPolicy" => (
+            named(&MULTIPATH_POLICIES, value).map(ConnectionProperty::MultipathPolicy),
+            "a multipath policy name",
+        ),
+        "minSendRate" => (
+            limit(value).map(ConnectionProperty::MinSendRate),
+            "bits per second or null",
+        ),
+        "maxSendRate" => (
+            limit(value).map(ConnectionProperty::MaxSendRate),
+            "bits per second or null",
+        ),
+        "minRecvRate" => (
+            limit(value).map(ConnectionProperty::MinRecvRate),
+            "bits per second or null",
+        ),
+        "maxRecvRate" => (
+            limit(value).map(ConnectionProperty::MaxRecvRate),
+            "bits per second or null",
+        ),
+        "groupConnLimit" => (
+            limit(value).map(ConnectionProperty::GroupConnLimit),
+            "an integer or null",
+        ),
+        "isolateSession" => (
+            value.as_bool().map(ConnectionProperty::IsolateSession),
+            "a boolean",
+        ),
+        "ipHopLimit" => (
+            limit(value).map(ConnectionProperty::IpHopLimit),
+            "0-255 or null",
+        ),
+        "pathPinning" => (
+            value.as_bool().map(ConnectionProperty::PathPinning),
+            "a boolean",
+        ),
+        "tcp.userTimeoutValue" => (
+            limit(value)
+                .map(|ms| ConnectionProperty::TcpUserTimeoutValue(ms.map(Duration::from_millis))),
+            "milliseconds or null",
+        ),
+        "tcp.userTimeoutEnabled" => (
+            value
+                .as_bool()
+                .map(ConnectionProperty::TcpUserTimeoutEnabled),
+            "a boolean",
+        ),
+        "tcp.userTimeoutChangeable" => (
+            value
+                .as_bool()
+                .map(ConnectionProperty::TcpUserTimeoutChangeable),
+            "a boolean",
+        ),
+        "quic.maxIdleTimeout" => (
+            timeout(value).map(ConnectionProperty::QuicMaxIdleTimeout),
+            "milliseconds or null",
+        ),
+        "quic.maxStreamsBidi" => (
+            count(value).map(ConnectionProperty::QuicMaxStreamsBidi),
+            "an integer",
+        ),
+        "quic.maxStreamsUni" => (
+            count(value).map(ConnectionProperty::QuicMaxStreamsUni),
+            "an integer",
+        ),
+        "quic.congestionAlgorithm" => (
+            named(&CONGESTION_ALGORITHMS, value).map(ConnectionProperty::QuicCongestionAlgorithm),
+            "a congestion algorithm name",
+        ),
+        "sctp.numOutboundStreams" => (
+            count(value).map(ConnectionProperty::SctpNumOutboundStreams),
+            "0-65535",
+        ),
+        "sctp.maxInboundStreams" => (
+            count(value).map(ConnectionProperty::SctpMaxInboundStreams),
+            "0-65535",
+        ),
+        "sctp.heartbeatInterval" => (
+            timeout(value).map(ConnectionProperty::SctpHeartbeatInterval),
+            "milliseconds or null",
+        ),
+        "sctp.maxRetransmissions" => (
+            count(value).map(ConnectionProperty::SctpMaxRetransmissions),
+            "an integer",
+        ),
+        _ => {
+            return Err(TransportServicesError::InvalidParameters(format!(
+                "Property '{key}' is unknown or read-only"
+            )))
+        }
+    };
+    property.ok_or_else(|| {
+        TransportServicesError::InvalidParameters(format!(
+            "Property '{key}' takes {expected}, not {value}"
+        ))
+    })
+}
+
+/// Encode a property's value as JSON
+pub(crate) fn property_to_json(property: &ConnectionProperty) -> Value {
+    match property {
+        ConnectionProperty::RecvChecksumLen(ChecksumCoverage::FullCoverage) => json!("full"),
+        ConnectionProperty::RecvChecksumLen(ChecksumCoverage::MinBytes(bytes)) => json!(bytes),
+        ConnectionProperty::ConnPriority(priority) => json!(priority),
+        ConnectionProperty::ConnTimeout(timeout)
+        | ConnectionProperty::KeepAliveTimeout(timeout)
+        | ConnectionProperty::SendTimeout(timeout)
+        | ConnectionProperty::SendStallThreshold(timeout)
+        | ConnectionProperty::QuicMaxIdleTimeout(timeout)
+        | ConnectionProperty::SctpHeartbeatInterval(timeout) => timeout_json(timeout),
+        ConnectionProperty::ConnScheduler(scheduler) => name_of(&SCHEDULERS, scheduler),
+        ConnectionProperty::ConnCapacityProfile(profile) => name_of(&CAPACITY_PROFILES, profile),
+        ConnectionProperty::MultipathPolicy(policy) => name_of(&MULTIPATH_POLICIES, policy),
+        ConnectionProperty::MinSendRate(rate)
+        | ConnectionProperty::MaxSendRate(rate)
+        | ConnectionProperty::MinRecvRate(rate)
+        | ConnectionProperty::MaxRecvRate(rate) => json!(rate),
+        ConnectionProperty::GroupConnLimit(limit) => json!(limit),
+        ConnectionProperty::IsolateSession(flag)
+        | ConnectionProperty::PathPinning(flag)
+        | ConnectionProperty::CanSend(flag)
+        | ConnectionProperty::CanReceive(flag)
+        | ConnectionProperty::TcpUserTimeoutEnabled(flag)
+        | ConnectionProperty::TcpUserTimeoutChangeable(flag) => json!(flag),
+        ConnectionProperty::IpHopLimit(hop_limit) => json!(hop_limit),
+        ConnectionProperty::ConnState(state) => name_of(&STATES, state),
+        ConnectionProperty::SingularTransmissionMsgMaxLen(size)
+        | ConnectionProperty::SendMsgMaxLen(size)
+        | ConnectionProperty::RecvMsgMaxLen(size)
+        | ConnectionProperty::PathMtu(size) => json!(size),
+        ConnectionProperty::SendStall(duration)
+        | ConnectionProperty::TcpUserTimeoutValue(duration) => duration.map_or(Value::Null, millis),
+        ConnectionProperty::MemoryUsage(usage) => json!({
+            "receiveBuffer": usage.receive_buffer,
+            "sendQueue": usage.send_queue,
+            "eventQueue": usage.event_queue,
+        }),
+        ConnectionProperty::PathInterface(text)
+        | ConnectionProperty::PathInterfaceType(text)
+        | ConnectionProperty::SecurityAlpn(text)
+        | ConnectionProperty::SecurityServerName(text) => json!(text),
+        ConnectionProperty::PathLocalAddress(addr)
+        | ConnectionProperty::PathRemoteAddress(addr) => {
+            json!(addr.map(|addr| addr.to_string()))
+        }
+        ConnectionProperty::ProtocolStack(stack) => {
+            json!(stack
+                .layers
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>())
+        }
+        ConnectionProperty::SecurityProtocolInUse(protocol) => {
+            json!(protocol.map(|protocol| StackLayer::Security(protocol).to_string()))
+        }
+        ConnectionProperty::SecurityClientCertificate(chain) => {
+            json!(chain.as_ref().map(|chain| {
+                chain
+                    .certificates
+                    .iter()
+                    .map(|certificate| hex(&certificate.data))
+                    .collect::<Vec<_>>()
+            }))
+        }
+        ConnectionProperty::QuicMaxStreamsBidi(count)
+        | ConnectionProperty::QuicMaxStreamsUni(count) => json!(count),
+        ConnectionProperty::QuicCongestionAlgorithm(algorithm) => {
+            name_of(&CONGESTION_ALGORITHMS, algorithm)
+        }
+        ConnectionProperty::SctpNumOutboundStreams(streams)
+        | ConnectionProperty::SctpMaxInboundStreams(streams) => json!(streams),
+        ConnectionProperty::SctpMaxRetransmissions(retransmissions) => json!(retransmissions),
+    }
+}
@@ -62,6 +62,11 @@ pub extern "C" fn transport_services_new_opportunistic_security_parameters(
 }
 
 /// Free a SecurityParameters object
+///
+/// # Safety
+///
+/// `handle` must be null or a security parameters handle that has not already
+/// been freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_free_security_parameters(
     handle: *mut TransportServicesHandle,
@@ -72,6 +77,11 @@ pub unsafe extern "C" fn transport_services_free_security_parameters(
 }
 
 /// Set allowed security protocols
+///
+/// # Safety
+///
+/// `handle` must be null or a security parameters handle that has not been
+/// freed. `protocols` must be null or point to `count` readable values.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_set_allowed_protocols(
     handle: *mut TransportServicesHandle,
@@ -105,6 +115,11 @@ pub unsafe extern "C" fn transport_services_set_allowed_protocols(
 }
 
 /// Set ALPN protocols
+///
+/// # Safety
+///
+/// `handle` must be null or a security parameters handle that has not been
+/// freed. `protocols` must be null or point to `count` NUL-terminated strings.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_set_alpn(
     handle: *mut TransportServicesHandle,
@@ -136,6 +151,12 @@ pub unsafe extern "C" fn transport_services_set_alpn(
 }
 
 /// Set ciphersuites
+///
+/// # Safety
+///
+/// `handle` must be null or a security parameters handle that has not been
+/// freed. `ciphersuites` must be null or point to `count` NUL-terminated
+/// strings.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_set_ciphersuites(
     handle: *mut TransportServicesHandle,
@@ -167,6 +188,11 @@ pub unsafe extern "C" fn transport_services_set_ciphersuites(
 }
 
 /// Set server certificate
+///
+/// # Safety
+///
+/// `handle` must be null or a security parameters handle that has not been
+/// freed. `cert_data` must be null or point to `cert_len` readable bytes.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_set_server_certificate(
     handle: *mut TransportServicesHandle,
@@ -192,6 +218,11 @@ pub unsafe extern "C" fn transport_services_set_server_certificate(
 }
 
 /// Set client certificate
+///
+/// # Safety
+///
+/// `handle` must be null or a security parameters handle that has not been
+/// freed. `cert_data` must be null or point to `cert_len` readable bytes.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_set_client_certificate(
     handle: *mut TransportServicesHandle,
@@ -217,6 +248,12 @@ pub unsafe extern "C" fn transport_services_set_client_certificate(
 }
 
 /// Set pre-shared key
+///
+/// # Safety
+///
+/// `handle` must be null or a security parameters handle that has not been
+/// freed. `key_data` must be null or point to `key_len` readable bytes.
+/// `identity` must be null or point to a NUL-terminated string.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_set_pre_shared_key(
     handle: *mut TransportServicesHandle,
@@ -249,6 +286,11 @@ pub unsafe extern "C" fn transport_services_set_pre_shared_key(
 }
 
 /// Set max cached sessions
+///
+/// # Safety
+///
+/// `handle` must be null or a security parameters handle that has not been
+/// freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_set_max_cached_sessions(
     handle: *mut TransportServicesHandle,
@@ -267,6 +309,11 @@ pub unsafe extern "C" fn transport_services_set_max_cached_sessions(
 }
 
 /// Set cached session lifetime in seconds
+///
+/// # Safety
+///
+/// `handle` must be null or a security parameters handle that has not been
+/// freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_set_cached_session_lifetime(
     handle: *mut TransportServicesHandle,
@@ -284,6 +331,126 @@ pub unsafe extern "C" fn transport_services_set_cached_session_lifetime(
     0
 }
 
+/// Trust verifier calling back into C with the presented chain, as the
+/// concatenated DER certificates, leaf first
+struct FfiTrustVerifier {
+    callback: TransportServicesTrustVerificationCallback,
+    // The C caller keeps user_data valid and usable from any thread
+    user_data: usize,
+}
+
+impl crate::TrustVerifier for FfiTrustVerifier {
+    fn verify(&self, chain: &crate::CertificateChain) -> bool {
+        let data = chain
+            .certificates
+            .iter()
+            .flat_map(|certificate| certificate.data.iter().copied())
+            .collect::<Vec<_>>();
+        (self.callback)(data.as_ptr(), data.len(), self.user_data as *mut c_void) == 1
+    }
+}
+
+/// Largest identity challenge response the C callback may write
+const MAX_CHALLENGE_RESPONSE: usize = 16 * 1024;
+
+/// Identity provider calling back into C
+struct FfiIdentityProvider {
+    callback: TransportServicesIdentityChallengeCallback,
+    user_data: usize,
+}
+
+impl crate::IdentityProvider for FfiIdentityProvider {
+    fn respond(&self, challenge: &[u8]) -> Vec<u8> {
+        let mut response = vec![0u8; MAX_CHALLENGE_RESPONSE];
+        let written = (self.callback)(
+            challenge.as_ptr(),
+            challenge.len(),
+            response.as_mut_ptr(),
+            response.len(),
+            self.user_data as *mut c_void,
+        );
+        response.truncate(usize::try_from(written).unwrap_or(0));
+        response
+    }
+}
+
+/// Set the callback deciding whether to trust peers
+/// The callback returns 1 to trust the chain
+///
+/// # Safety
+///
+/// `handle` must be null or a security parameters handle that has not been
+/// freed. `user_data` is handed to the callbacks unchanged and must stay valid
+/// for as long as they may be called.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_set_trust_verification_callback(
+    handle: *mut TransportServicesHandle,
+    callback: TransportServicesTrustVerificationCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    if handle.is_null() {
+        return -1;
+    }
+
+    let params = handle_mut::<SecurityParameters>(handle);
+    params.set_trust_verifier(std::sync::Arc::new(FfiTrustVerifier {
+        callback,
+        user_data: user_data as usize,
+    }));
+    0
+}
+
+/// Set the callback answering identity challenges
+///
+/// # Safety
+///
+/// `handle` must be null or a security parameters handle that has not been
+/// freed. `user_data` is handed to the callbacks unchanged and must stay valid
+/// for as long as they may be called.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_set_identity_challenge_callback(
+    handle: *mut TransportServicesHandle,
+    callback: TransportServicesIdentityChallengeCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    if handle.is_null() {
+        return -1;
+    }
+
+    let params = handle_mut::<SecurityParameters>(handle);
+    params.set_identity_provider(std::sync::Arc::new(FfiIdentityProvider {
+        callback,
+        user_data: user_data as usize,
+    }));
+    0
+}
+
+/// Add the trust verifier registered under a name, which peers must also pass
+///
+/// # Safety
+///
+/// `handle` must be null or a security parameters handle that has not been
+/// freed. `name` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_add_registered_trust_verifier(
+    handle: *mut TransportServicesHandle,
+    name: *const c_char,
+) -> c_int {
+    if handle.is_null() || name.is_null() {
+        return -1;
+    }
+
+    let params = handle_mut::<SecurityParameters>(handle);
+    let name = match CStr::from_ptr(name).to_str() {
+        Ok(name) => name,
+        Err(_) => return -1,
+    };
+    match params.add_registered_trust_verifier(name) {
+        Ok(_) => 0,
+        Err(_) => -1,
+    }
+}
+
 /// Security protocol constants for FFI
 pub mod security_protocol_constants {
     pub const TRANSPORT_SERVICES_SECURITY_PROTOCOL_TLS12: i32 = 0;
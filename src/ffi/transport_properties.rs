@@ -1,9 +1,10 @@
 //! FFI bindings for TransportProperties
 
+use self::property_constants::*;
 use super::*;
 use crate::{
-    CommunicationDirection, MultipathConfig, Preference, PropertyValue, TransportProperties,
-    TransportProperty,
+    AddressFamilyPreference, CommunicationDirection, MultipathConfig, Preference, PropertyValue,
+    TransportProperties, TransportProperty,
 };
 use std::ffi::CStr;
 use std::os::raw::{c_char, c_int};
@@ -17,6 +18,11 @@ pub extern "C" fn transport_services_new_transport_properties() -> *mut Transpor
 }
 
 /// Free a TransportProperties object
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not already
+/// been freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_free_transport_properties(
     handle: *mut TransportServicesHandle,
@@ -27,6 +33,11 @@ pub unsafe extern "C" fn transport_services_free_transport_properties(
 }
 
 /// Set a preference property
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_set_preference(
     handle: *mut TransportServicesHandle,
@@ -62,6 +73,11 @@ pub unsafe extern "C" fn transport_services_set_preference(
 }
 
 /// Set multipath configuration
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_set_multipath(
     handle: *mut TransportServicesHandle,
@@ -81,6 +97,11 @@ pub unsafe extern "C" fn transport_services_set_multipath(
 }
 
 /// Set communication direction
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_set_direction(
     handle: *mut TransportServicesHandle,
@@ -97,6 +118,11 @@ pub unsafe extern "C" fn transport_services_set_direction(
 }
 
 /// Set advertises alternate address
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_set_advertises_altaddr(
     handle: *mut TransportServicesHandle,
@@ -115,6 +141,11 @@ pub unsafe extern "C" fn transport_services_set_advertises_altaddr(
 }
 
 /// Set interface preference
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed. `interface` must be null or point to a NUL-terminated string.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_set_interface(
     handle: *mut TransportServicesHandle,
@@ -140,6 +171,11 @@ pub unsafe extern "C" fn transport_services_set_interface(
 }
 
 /// Set PVD preference
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed. `pvd` must be null or point to a NUL-terminated string.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_set_pvd(
     handle: *mut TransportServicesHandle,
@@ -165,6 +201,11 @@ pub unsafe extern "C" fn transport_services_set_pvd(
 }
 
 /// Set connection timeout in milliseconds
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_set_connection_timeout(
     handle: *mut TransportServicesHandle,
@@ -184,6 +225,11 @@ pub unsafe extern "C" fn transport_services_set_connection_timeout(
 }
 
 /// Set keep alive timeout in milliseconds
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_set_keep_alive_timeout(
     handle: *mut TransportServicesHandle,
@@ -203,6 +249,11 @@ pub unsafe extern "C" fn transport_services_set_keep_alive_timeout(
 }
 
 /// Set connection priority
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed.
 #[no_mangle]
 pub unsafe extern "C" fn transport_services_set_connection_priority(
     handle: *mut TransportServicesHandle,
@@ -220,6 +271,626 @@ pub unsafe extern "C" fn transport_services_set_connection_priority(
     0
 }
 
+/// Get a preference property
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed. `preference` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_get_preference(
+    handle: *const TransportServicesHandle,
+    property: c_int,
+    preference: *mut types::TransportServicesPreference,
+) -> c_int {
+    if handle.is_null() || preference.is_null() {
+        return -1;
+    }
+
+    let selection = &handle_ref::<TransportProperties>(handle).selection_properties;
+    let value = match property {
+        0 => selection.reliability,
+        1 => selection.preserve_msg_boundaries,
+        2 => selection.per_msg_reliability,
+        3 => selection.preserve_order,
+        4 => selection.zero_rtt_msg,
+        5 => selection.multistreaming,
+        6 => selection.full_checksum_send,
+        7 => selection.full_checksum_recv,
+        8 => selection.congestion_control,
+        9 => selection.keep_alive,
+        10 => selection.use_temporary_local_address,
+        11 => selection.soft_error_notify,
+        12 => selection.active_read_before_send,
+        _ => return -1,
+    };
+    *preference = value.into();
+    0
+}
+
+/// Get multipath configuration
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed. `config` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_get_multipath(
+    handle: *const TransportServicesHandle,
+    config: *mut types::TransportServicesMultipathConfig,
+) -> c_int {
+    if handle.is_null() || config.is_null() {
+        return -1;
+    }
+
+    let properties = handle_ref::<TransportProperties>(handle);
+    *config = properties.selection_properties.multipath.into();
+    0
+}
+
+/// Get communication direction
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed. `direction` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_get_direction(
+    handle: *const TransportServicesHandle,
+    direction: *mut types::TransportServicesCommunicationDirection,
+) -> c_int {
+    if handle.is_null() || direction.is_null() {
+        return -1;
+    }
+
+    let properties = handle_ref::<TransportProperties>(handle);
+    *direction = properties.selection_properties.direction.into();
+    0
+}
+
+/// Get advertises alternate address
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed. `value` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_get_advertises_altaddr(
+    handle: *const TransportServicesHandle,
+    value: *mut bool,
+) -> c_int {
+    if handle.is_null() || value.is_null() {
+        return -1;
+    }
+
+    let properties = handle_ref::<TransportProperties>(handle);
+    *value = properties.selection_properties.advertises_altaddr;
+    0
+}
+
+/// Get the number of interface preferences set
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_get_interface_count(
+    handle: *const TransportServicesHandle,
+) -> c_int {
+    if handle.is_null() {
+        return -1;
+    }
+
+    let properties = handle_ref::<TransportProperties>(handle);
+    properties.selection_properties.interface.len() as c_int
+}
+
+/// Get the interface preference at `index`, in the order they were set
+/// The name must be freed with transport_services_free_string
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed. `interface` must be null or valid for writes. `preference` must be
+/// null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_get_interface(
+    handle: *const TransportServicesHandle,
+    index: usize,
+    interface: *mut *mut c_char,
+    preference: *mut types::TransportServicesPreference,
+) -> c_int {
+    if handle.is_null() {
+        return -1;
+    }
+
+    let properties = handle_ref::<TransportProperties>(handle);
+    string_preference_at(
+        &properties.selection_properties.interface,
+        index,
+        interface,
+        preference,
+    )
+}
+
+/// Get the number of PVD preferences set
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_get_pvd_count(
+    handle: *const TransportServicesHandle,
+) -> c_int {
+    if handle.is_null() {
+        return -1;
+    }
+
+    let properties = handle_ref::<TransportProperties>(handle);
+    properties.selection_properties.pvd.len() as c_int
+}
+
+/// Get the PVD preference at `index`, in the order they were set
+/// The name must be freed with transport_services_free_string
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed. `pvd` must be null or valid for writes. `preference` must be null or
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_get_pvd(
+    handle: *const TransportServicesHandle,
+    index: usize,
+    pvd: *mut *mut c_char,
+    preference: *mut types::TransportServicesPreference,
+) -> c_int {
+    if handle.is_null() {
+        return -1;
+    }
+
+    let properties = handle_ref::<TransportProperties>(handle);
+    string_preference_at(&properties.selection_properties.pvd, index, pvd, preference)
+}
+
+unsafe fn string_preference_at(
+    list: &[(String, Preference)],
+    index: usize,
+    name: *mut *mut c_char,
+    preference: *mut types::TransportServicesPreference,
+) -> c_int {
+    if name.is_null() || preference.is_null() {
+        return -1;
+    }
+    let Some((value, pref)) = list.get(index) else {
+        return -1;
+    };
+    let Ok(c_value) = CString::new(value.as_str()) else {
+        return -1;
+    };
+    *name = c_value.into_raw();
+    *preference = (*pref).into();
+    0
+}
+
+/// Get connection priority
+/// Returns 1 if it is unset
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed. `priority` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_get_connection_priority(
+    handle: *const TransportServicesHandle,
+    priority: *mut i32,
+) -> c_int {
+    if handle.is_null() || priority.is_null() {
+        return -1;
+    }
+
+    let properties = handle_ref::<TransportProperties>(handle);
+    match properties.connection_properties.connection_priority {
+        Some(value) => {
+            *priority = value;
+            0
+        }
+        None => 1,
+    }
+}
+
+/// The connection property a duration constant names
+fn duration_property(property: c_int) -> Option<TransportProperty> {
+    Some(match property {
+        TRANSPORT_SERVICES_PROPERTY_CONNECTION_TIMEOUT => TransportProperty::ConnectionTimeout,
+        TRANSPORT_SERVICES_PROPERTY_KEEP_ALIVE_TIMEOUT => TransportProperty::KeepAliveTimeout,
+        TRANSPORT_SERVICES_PROPERTY_STATS_INTERVAL => TransportProperty::StatsInterval,
+        TRANSPORT_SERVICES_PROPERTY_NAT_KEEPALIVE_INTERVAL => {
+            TransportProperty::NatKeepaliveInterval
+        }
+        TRANSPORT_SERVICES_PROPERTY_CONNECTION_ATTEMPT_DELAY => {
+            TransportProperty::ConnectionAttemptDelay
+        }
+        TRANSPORT_SERVICES_PROPERTY_CANDIDATE_TIMEOUT => TransportProperty::CandidateTimeout,
+        _ => return None,
+    })
+}
+
+/// Set a connection property holding a duration, in milliseconds
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_set_duration_property(
+    handle: *mut TransportServicesHandle,
+    property: c_int,
+    value_ms: u64,
+) -> c_int {
+    if handle.is_null() {
+        return -1;
+    }
+    let Some(prop) = duration_property(property) else {
+        return -1;
+    };
+
+    let properties = handle_mut::<TransportProperties>(handle);
+    properties.set(
+        prop,
+        PropertyValue::Duration(Duration::from_millis(value_ms)),
+    );
+    0
+}
+
+/// Get a connection property holding a duration, in milliseconds
+/// Returns 1 if it is unset
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed. `value_ms` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_get_duration_property(
+    handle: *const TransportServicesHandle,
+    property: c_int,
+    value_ms: *mut u64,
+) -> c_int {
+    if handle.is_null() || value_ms.is_null() {
+        return -1;
+    }
+
+    let connection = &handle_ref::<TransportProperties>(handle).connection_properties;
+    let value = match property {
+        TRANSPORT_SERVICES_PROPERTY_CONNECTION_TIMEOUT => connection.connection_timeout,
+        TRANSPORT_SERVICES_PROPERTY_KEEP_ALIVE_TIMEOUT => connection.keep_alive_timeout,
+        TRANSPORT_SERVICES_PROPERTY_STATS_INTERVAL => connection.stats_interval,
+        TRANSPORT_SERVICES_PROPERTY_NAT_KEEPALIVE_INTERVAL => connection.nat_keepalive_interval,
+        TRANSPORT_SERVICES_PROPERTY_CONNECTION_ATTEMPT_DELAY => {
+            Some(connection.happy_eyeballs.connection_attempt_delay)
+        }
+        TRANSPORT_SERVICES_PROPERTY_CANDIDATE_TIMEOUT => {
+            connection.happy_eyeballs.candidate_timeout
+        }
+        _ => return -1,
+    };
+    match value {
+        Some(duration) => {
+            *value_ms = duration.as_millis() as u64;
+            0
+        }
+        None => 1,
+    }
+}
+
+/// The connection property a size constant names
+fn size_property(property: c_int) -> Option<TransportProperty> {
+    Some(match property {
+        TRANSPORT_SERVICES_PROPERTY_MAXIMUM_MESSAGE_SIZE_ON_SEND => {
+            TransportProperty::MaximumMessageSizeOnSend
+        }
+        TRANSPORT_SERVICES_PROPERTY_MAXIMUM_MESSAGE_SIZE_ON_RECEIVE => {
+            TransportProperty::MaximumMessageSizeOnReceive
+        }
+        TRANSPORT_SERVICES_PROPERTY_SEND_BUFFER_SIZE => TransportProperty::SendBufferSize,
+        TRANSPORT_SERVICES_PROPERTY_RECEIVE_BUFFER_SIZE => TransportProperty::ReceiveBufferSize,
+        TRANSPORT_SERVICES_PROPERTY_SEND_HIGH_WATERMARK => TransportProperty::SendHighWatermark,
+        TRANSPORT_SERVICES_PROPERTY_SEND_LOW_WATERMARK => TransportProperty::SendLowWatermark,
+        TRANSPORT_SERVICES_PROPERTY_MAX_PARALLEL_ATTEMPTS => TransportProperty::MaxParallelAttempts,
+        _ => return None,
+    })
+}
+
+/// Set a connection property holding a size or count
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_set_size_property(
+    handle: *mut TransportServicesHandle,
+    property: c_int,
+    value: usize,
+) -> c_int {
+    if handle.is_null() {
+        return -1;
+    }
+    let Some(prop) = size_property(property) else {
+        return -1;
+    };
+
+    let properties = handle_mut::<TransportProperties>(handle);
+    properties.set(prop, PropertyValue::Size(value));
+    0
+}
+
+/// Get a connection property holding a size or count
+/// Returns 1 if it is unset
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed. `value` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_get_size_property(
+    handle: *const TransportServicesHandle,
+    property: c_int,
+    value: *mut usize,
+) -> c_int {
+    if handle.is_null() || value.is_null() {
+        return -1;
+    }
+
+    let connection = &handle_ref::<TransportProperties>(handle).connection_properties;
+    let size = match property {
+        TRANSPORT_SERVICES_PROPERTY_MAXIMUM_MESSAGE_SIZE_ON_SEND => {
+            connection.maximum_message_size_on_send
+        }
+        TRANSPORT_SERVICES_PROPERTY_MAXIMUM_MESSAGE_SIZE_ON_RECEIVE => {
+            connection.maximum_message_size_on_receive
+        }
+        TRANSPORT_SERVICES_PROPERTY_SEND_BUFFER_SIZE => connection.send_buffer_size,
+        TRANSPORT_SERVICES_PROPERTY_RECEIVE_BUFFER_SIZE => connection.receive_buffer_size,
+        TRANSPORT_SERVICES_PROPERTY_SEND_HIGH_WATERMARK => connection.send_high_watermark,
+        TRANSPORT_SERVICES_PROPERTY_SEND_LOW_WATERMARK => connection.send_low_watermark,
+        TRANSPORT_SERVICES_PROPERTY_MAX_PARALLEL_ATTEMPTS => {
+            Some(connection.happy_eyeballs.max_parallel_attempts)
+        }
+        _ => return -1,
+    };
+    match size {
+        Some(size) => {
+            *value = size;
+            0
+        }
+        None => 1,
+    }
+}
+
+/// The connection property a boolean constant names
+fn bool_property(property: c_int) -> Option<TransportProperty> {
+    Some(match property {
+        TRANSPORT_SERVICES_PROPERTY_NO_DELAY => TransportProperty::NoDelay,
+        TRANSPORT_SERVICES_PROPERTY_RECEIVE_TIMESTAMPS => TransportProperty::ReceiveTimestamps,
+        TRANSPORT_SERVICES_PROPERTY_UDP_OFFLOAD => TransportProperty::UdpOffload,
+        TRANSPORT_SERVICES_PROPERTY_ABORT_ON_OVERSIZED_MESSAGE => {
+            TransportProperty::AbortOnOversizedMessage
+        }
+        TRANSPORT_SERVICES_PROPERTY_PATH_PINNING => TransportProperty::PathPinning,
+        _ => return None,
+    })
+}
+
+/// Set a boolean connection property
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_set_bool_property(
+    handle: *mut TransportServicesHandle,
+    property: c_int,
+    value: bool,
+) -> c_int {
+    if handle.is_null() {
+        return -1;
+    }
+    let Some(prop) = bool_property(property) else {
+        return -1;
+    };
+
+    let properties = handle_mut::<TransportProperties>(handle);
+    properties.set(prop, PropertyValue::Bool(value));
+    0
+}
+
+/// Get a boolean connection property
+/// Returns 1 if it is unset
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed. `value` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_get_bool_property(
+    handle: *const TransportServicesHandle,
+    property: c_int,
+    value: *mut bool,
+) -> c_int {
+    if handle.is_null() || value.is_null() {
+        return -1;
+    }
+
+    let connection = &handle_ref::<TransportProperties>(handle).connection_properties;
+    let flag = match property {
+        TRANSPORT_SERVICES_PROPERTY_NO_DELAY => connection.no_delay,
+        TRANSPORT_SERVICES_PROPERTY_RECEIVE_TIMESTAMPS => connection.receive_timestamps,
+        TRANSPORT_SERVICES_PROPERTY_UDP_OFFLOAD => connection.udp_offload,
+        TRANSPORT_SERVICES_PROPERTY_ABORT_ON_OVERSIZED_MESSAGE => {
+            connection.abort_on_oversized_message
+        }
+        TRANSPORT_SERVICES_PROPERTY_PATH_PINNING => connection.path_pinning,
+        _ => return -1,
+    };
+    match flag {
+        Some(flag) => {
+            *value = flag;
+            0
+        }
+        None => 1,
+    }
+}
+
+/// Set a rate cap in bits per second, 0 meaning unlimited
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_set_rate_property(
+    handle: *mut TransportServicesHandle,
+    property: c_int,
+    rate_bps: u64,
+) -> c_int {
+    if handle.is_null() {
+        return -1;
+    }
+
+    let connection = &mut handle_mut::<TransportProperties>(handle).connection_properties;
+    let rate = Some(rate_bps).filter(|rate| *rate > 0);
+    match property {
+        TRANSPORT_SERVICES_PROPERTY_MAX_SEND_RATE => connection.max_send_rate = rate,
+        TRANSPORT_SERVICES_PROPERTY_MAX_RECV_RATE => connection.max_recv_rate = rate,
+        _ => return -1,
+    }
+    0
+}
+
+/// Get a rate cap in bits per second, 0 meaning unlimited
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed. `rate_bps` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_get_rate_property(
+    handle: *const TransportServicesHandle,
+    property: c_int,
+    rate_bps: *mut u64,
+) -> c_int {
+    if handle.is_null() || rate_bps.is_null() {
+        return -1;
+    }
+
+    let connection = &handle_ref::<TransportProperties>(handle).connection_properties;
+    let rate = match property {
+        TRANSPORT_SERVICES_PROPERTY_MAX_SEND_RATE => connection.max_send_rate,
+        TRANSPORT_SERVICES_PROPERTY_MAX_RECV_RATE => connection.max_recv_rate,
+        _ => return -1,
+    };
+    *rate_bps = rate.unwrap_or(0);
+    0
+}
+
+/// Set IP TTL (IPv4) or hop limit (IPv6) of packets sent
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_set_hop_limit(
+    handle: *mut TransportServicesHandle,
+    hop_limit: u8,
+) -> c_int {
+    if handle.is_null() {
+        return -1;
+    }
+
+    let properties = handle_mut::<TransportProperties>(handle);
+    properties.connection_properties.hop_limit = Some(hop_limit);
+    0
+}
+
+/// Get IP TTL (IPv4) or hop limit (IPv6) of packets sent
+/// Returns 1 if it is unset, leaving the system default
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed. `hop_limit` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_get_hop_limit(
+    handle: *const TransportServicesHandle,
+    hop_limit: *mut u8,
+) -> c_int {
+    if handle.is_null() || hop_limit.is_null() {
+        return -1;
+    }
+
+    let properties = handle_ref::<TransportProperties>(handle);
+    match properties.connection_properties.hop_limit {
+        Some(value) => {
+            *hop_limit = value;
+            0
+        }
+        None => 1,
+    }
+}
+
+/// Set which address family candidate racing tries first, or only
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_set_address_family_preference(
+    handle: *mut TransportServicesHandle,
+    preference: types::TransportServicesAddressFamilyPreference,
+) -> c_int {
+    if handle.is_null() {
+        return -1;
+    }
+
+    let properties = handle_mut::<TransportProperties>(handle);
+    let family: AddressFamilyPreference = preference.into();
+    properties.set(
+        TransportProperty::AddressFamilyPreference,
+        PropertyValue::AddressFamily(family),
+    );
+    0
+}
+
+/// Get which address family candidate racing tries first, or only
+///
+/// # Safety
+///
+/// `handle` must be null or a transport properties handle that has not been
+/// freed. `preference` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn transport_services_get_address_family_preference(
+    handle: *const TransportServicesHandle,
+    preference: *mut types::TransportServicesAddressFamilyPreference,
+) -> c_int {
+    if handle.is_null() || preference.is_null() {
+        return -1;
+    }
+
+    let properties = handle_ref::<TransportProperties>(handle);
+    *preference = properties
+        .connection_properties
+        .happy_eyeballs
+        .address_family_preference
+        .into();
+    0
+}
+
 /// Property constants for FFI
 pub mod property_constants {
     pub const TRANSPORT_SERVICES_PROPERTY_RELIABILITY: i32 = 0;
@@ -235,4 +906,34 @@ pub mod property_constants {
     pub const TRANSPORT_SERVICES_PROPERTY_USE_TEMPORARY_LOCAL_ADDRESS: i32 = 10;
     pub const TRANSPORT_SERVICES_PROPERTY_SOFT_ERROR_NOTIFY: i32 = 11;
     pub const TRANSPORT_SERVICES_PROPERTY_ACTIVE_READ_BEFORE_SEND: i32 = 12;
+
+    // Durations, for transport_services_set_duration_property
+    pub const TRANSPORT_SERVICES_PROPERTY_CONNECTION_TIMEOUT: i32 = 13;
+    pub const TRANSPORT_SERVICES_PROPERTY_KEEP_ALIVE_TIMEOUT: i32 = 14;
+    pub const TRANSPORT_SERVICES_PROPERTY_STATS_INTERVAL: i32 = 15;
+    pub const TRANSPORT_SERVICES_PROPERTY_NAT_KEEPALIVE_INTERVAL: i32 = 16;
+    pub const TRANSPORT_SERVICES_PROPERTY_CONNECTION_ATTEMPT_DELAY: i32 = 17;
+    pub const TRANSPORT_SERVICES_PROPERTY_CANDIDATE_TIMEOUT: i32 = 18;
+
+    // Sizes and counts, for transport_services_set_size_property
+    pub const TRANSPORT_SERVICES_PROPERTY_MAXIMUM_MESSAGE_SIZE_ON_SEND: i32 = 19;
+    pub const TRANSPORT_SERVICES_PROPERTY_MAXIMUM_MESSAGE_SIZE_ON_RECEIVE: i32 = 20;
+    pub const TRANSPORT_SERVICES_PROPERTY_SEND_BUFFER_SIZE: i32 = 21;
+    pub const TRANSPORT_SERVICES_PROPERTY_RECEIVE_BUFFER_SIZE: i32 = 22;
+    pub const TRANSPORT_SERVICES_PROPERTY_SEND_HIGH_WATERMARK: i32 = 23;
+    pub const TRANSPORT_SERVICES_PROPERTY_SEND_LOW_WATERMARK: i32 = 24;
+    pub const TRANSPORT_SERVICES_PROPERTY_MAX_PARALLEL_ATTEMPTS: i32 = 25;
+
+    // Flags, for transport_services_set_bool_property
+    pub const TRANSPORT_SERVICES_PROPERTY_NO_DELAY: i32 = 26;
+    pub const TRANSPORT_SERVICES_PROPERTY_RECEIVE_TIMESTAMPS: i32 = 27;
+    pub const TRANSPORT_SERVICES_PROPERTY_UDP_OFFLOAD: i32 = 28;
+    pub const TRANSPORT_SERVICES_PROPERTY_ABORT_ON_OVERSIZED_MESSAGE: i32 = 29;
+
+    // Rate caps, for transport_services_set_rate_property
+    pub const TRANSPORT_SERVICES_PROPERTY_MAX_SEND_RATE: i32 = 30;
+    pub const TRANSPORT_SERVICES_PROPERTY_MAX_RECV_RATE: i32 = 31;
+
+    // Flags, for transport_services_set_bool_property
+    pub const TRANSPORT_SERVICES_PROPERTY_PATH_PINNING: i32 = 32;
 }
@@ -116,6 +116,54 @@ impl From<TransportServicesCommunicationDirection> for crate::CommunicationDirec
     }
 }
 
+/// FFI representation of address family preference when racing candidates
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub enum TransportServicesAddressFamilyPreference {
+    PreferIpv6 = 0,
+    PreferIpv4 = 1,
+    Ipv6Only = 2,
+    Ipv4Only = 3,
+}
+
+impl From<crate::AddressFamilyPreference> for TransportServicesAddressFamilyPreference {
+    fn from(preference: crate::AddressFamilyPreference) -> Self {
+        match preference {
+            crate::AddressFamilyPreference::PreferIpv6 => {
+                TransportServicesAddressFamilyPreference::PreferIpv6
+            }
+            crate::AddressFamilyPreference::PreferIpv4 => {
+                TransportServicesAddressFamilyPreference::PreferIpv4
+            }
+            crate::AddressFamilyPreference::Ipv6Only => {
+                TransportServicesAddressFamilyPreference::Ipv6Only
+            }
+            crate::AddressFamilyPreference::Ipv4Only => {
+                TransportServicesAddressFamilyPreference::Ipv4Only
+            }
+        }
+    }
+}
+
+impl From<TransportServicesAddressFamilyPreference> for crate::AddressFamilyPreference {
+    fn from(preference: TransportServicesAddressFamilyPreference) -> Self {
+        match preference {
+            TransportServicesAddressFamilyPreference::PreferIpv6 => {
+                crate::AddressFamilyPreference::PreferIpv6
+            }
+            TransportServicesAddressFamilyPreference::PreferIpv4 => {
+                crate::AddressFamilyPreference::PreferIpv4
+            }
+            TransportServicesAddressFamilyPreference::Ipv6Only => {
+                crate::AddressFamilyPreference::Ipv6Only
+            }
+            TransportServicesAddressFamilyPreference::Ipv4Only => {
+                crate::AddressFamilyPreference::Ipv4Only
+            }
+        }
+    }
+}
+
 /// FFI representation of transport properties
 #[repr(C)]
 pub struct TransportServicesProperties {
@@ -199,6 +247,7 @@ pub enum TransportServicesError {
     SecurityError = -9,
     IoError = -10,
     RuntimeError = -11,
+    WouldBlock = -12,
     Unknown = -99,
 }
 
@@ -225,11 +274,33 @@ impl From<crate::TransportServicesError> for TransportServicesError {
                 TransportServicesError::SecurityError
             }
             crate::TransportServicesError::Io(_) => TransportServicesError::IoError,
+            crate::TransportServicesError::WouldBlock => TransportServicesError::WouldBlock,
             _ => TransportServicesError::Unknown,
         }
     }
 }
 
+/// FFI representation of the ECN marking of a received message
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub enum TransportServicesEcnMarking {
+    NotEct = 0,
+    Ect0 = 1,
+    Ect1 = 2,
+    Ce = 3,
+}
+
+impl From<crate::message::EcnMarking> for TransportServicesEcnMarking {
+    fn from(ecn: crate::message::EcnMarking) -> Self {
+        match ecn {
+            crate::message::EcnMarking::NotEct => TransportServicesEcnMarking::NotEct,
+            crate::message::EcnMarking::Ect0 => TransportServicesEcnMarking::Ect0,
+            crate::message::EcnMarking::Ect1 => TransportServicesEcnMarking::Ect1,
+            crate::message::EcnMarking::Ce => TransportServicesEcnMarking::Ce,
+        }
+    }
+}
+
 /// Connection event types for FFI
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
@@ -245,6 +316,43 @@ pub enum TransportServicesConnectionEventType {
     SendError = 8,
     Received = 9,
     ReceivedPartial = 10,
+    FinalReceived = 11,
+    Stats = 12,
+    Unresponsive = 13,
+    ReceiveError = 14,
+}
+
+/// FFI representation of the protocols and features this build provides
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct TransportServicesCapabilities {
+    pub tcp: bool,
+    pub udp: bool,
+    pub tls: bool,
+    pub quic: bool,
+    pub sctp: bool,
+    pub udp_lite: bool,
+    pub mptcp: bool,
+    pub tcp_fast_open: bool,
+    pub tcp_fast_open_listen: bool,
+    pub ecn: bool,
+}
+
+impl From<crate::Capabilities> for TransportServicesCapabilities {
+    fn from(capabilities: crate::Capabilities) -> Self {
+        Self {
+            tcp: capabilities.tcp,
+            udp: capabilities.udp,
+            tls: capabilities.tls,
+            quic: capabilities.quic,
+            sctp: capabilities.sctp,
+            udp_lite: capabilities.udp_lite,
+            mptcp: capabilities.mptcp,
+            tcp_fast_open: capabilities.tcp_fast_open,
+            tcp_fast_open_listen: capabilities.tcp_fast_open_listen,
+            ecn: capabilities.ecn,
+        }
+    }
 }
 
 /// Callback function types
@@ -1,7 +1,20 @@
-use crate::{Message, MessageContext, Result};
+#[cfg(feature = "tls")]
+use crate::tls::TlsSession;
+use crate::{Message, MessageContext, Result, TransportServicesError};
 use async_trait::async_trait;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use bytes::Bytes;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Largest preamble a framer may read while looking for a delimiter
+pub const MAX_HANDSHAKE_READ: usize = 64 * 1024;
+
+/// Creates a framer for each Connection of a Preconnection
+///
+/// Framers keep per-connection parse state, so a Preconnection holds
+/// factories rather than framer instances.
+pub type FramerFactory = Arc<dyn Fn() -> Box<dyn Framer> + Send + Sync>;
 
 /// Message Framer trait as defined in RFC 9622 Section 9.1.2
 ///
@@ -13,12 +26,48 @@ pub trait Framer: Send + Sync {
     /// Frame outbound messages for transmission
     async fn frame_message(&self, message: &Message, context: &MessageContext) -> Result<Vec<u8>>;
 
+    /// Frame an outbound message as a list of buffers
+    ///
+    /// Framers that only add headers or trailers can override this to pass the
+    /// message's own segments through without concatenating them.
+    async fn frame_segments(
+        &self,
+        message: &Message,
+        context: &MessageContext,
+    ) -> Result<Vec<Bytes>> {
+        Ok(vec![Bytes::from(
+            self.frame_message(message, context).await?,
+        )])
+    }
+
     /// Parse inbound data into messages
     async fn parse_data(&self, data: &[u8]) -> Result<Vec<(Message, MessageContext)>>;
 
     /// Get the name of this framer for identification
     fn name(&self) -> &str;
 
+    /// Whether messages are delimited by a 4-byte big-endian length prefix
+    ///
+    /// When the outermost framer of a connection is length-prefixed, the
+    /// connection reassembles messages itself, so large messages can be
+    /// delivered in parts and oversized ones rejected before buffering.
+    fn length_prefixed(&self) -> bool {
+        false
+    }
+
+    /// Discard any partially parsed inbound data
+    ///
+    /// Called when the connection closes or stops delivering data.
+    fn reset(&self) {}
+
+    /// Take the inbound bytes buffered but not yet parsed into messages
+    ///
+    /// Called when the framer is replaced, so the replacement can parse them.
+    /// Framers that drop what they cannot parse may keep the default.
+    fn take_unparsed(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
     /// Called when the framer is attached to a connection
     async fn on_attach(&self) -> Result<()> {
         Ok(())
@@ -28,19 +77,140 @@ pub trait Framer: Send + Sync {
     async fn on_detach(&self) -> Result<()> {
         Ok(())
     }
+
+    /// Start event (RFC 9622 Section 9.1.2.2)
+    ///
+    /// Runs once the transport is connected and before the connection is
+    /// Ready, so the framer can exchange a preamble such as a protocol
+    /// upgrade. An error fails establishment.
+    async fn start(&self, _handshake: &mut FramerHandshake<'_>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Stop event (RFC 9622 Section 9.1.2.2)
+    ///
+    /// Runs when the connection closes gracefully, before the transport is
+    /// shut down, so the framer can send a closing preamble.
+    async fn stop(&self, _handshake: &mut FramerHandshake<'_>) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Raw access to the transport for framer Start and Stop events
+///
+/// Bytes read past what the framer consumes are kept and delivered to the
+/// next framer, then to the connection as received data. On a secured
+/// connection the bytes are carried over TLS.
+pub struct FramerHandshake<'a> {
+    stream: &'a mut TcpStream,
+    buffered: Vec<u8>,
+    #[cfg(feature = "tls")]
+    tls: Option<&'a mut TlsSession>,
+}
+
+impl<'a> FramerHandshake<'a> {
+    pub(crate) fn new(stream: &'a mut TcpStream, buffered: Vec<u8>) -> Self {
+        Self {
+            stream,
+            buffered,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    /// Carry the handshake over the connection's TLS session
+    #[cfg(feature = "tls")]
+    pub(crate) fn with_tls(mut self, tls: Option<&'a mut TlsSession>) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Bytes received but not yet consumed
+    pub(crate) fn into_buffered(self) -> Vec<u8> {
+        self.buffered
+    }
+
+    /// Send bytes to the peer, unframed
+    pub async fn send(&mut self, data: &[u8]) -> Result<()> {
+        #[cfg(feature = "tls")]
+        if let Some(tls) = self.tls.as_mut() {
+            let records = tls.encrypt(data)?;
+            return self
+                .stream
+                .write_all(&records)
+                .await
+                .map_err(|e| TransportServicesError::SendFailed(e.to_string()));
+        }
+        self.stream
+            .write_all(data)
+            .await
+            .map_err(|e| TransportServicesError::SendFailed(e.to_string()))
+    }
+
+    /// Receive exactly `len` bytes
+    pub async fn read_exact(&mut self, len: usize) -> Result<Vec<u8>> {
+        while self.buffered.len() < len {
+            self.fill().await?;
+        }
+        Ok(self.buffered.drain(..len).collect())
+    }
+
+    /// Receive up to and including `delimiter`, e.g. the blank line ending
+    /// an HTTP header
+    ///
+    /// Fails if the delimiter is not seen within MAX_HANDSHAKE_READ bytes.
+    pub async fn read_until(&mut self, delimiter: &[u8]) -> Result<Vec<u8>> {
+        loop {
+            if let Some(position) = self
+                .buffered
+                .windows(delimiter.len())
+                .position(|window| window == delimiter)
+            {
+                return Ok(self.buffered.drain(..position + delimiter.len()).collect());
+            }
+            if self.buffered.len() >= MAX_HANDSHAKE_READ {
+                return Err(TransportServicesError::ReceiveFailed(
+                    "Framer preamble exceeds the handshake read limit".to_string(),
+                ));
+            }
+            self.fill().await?;
+        }
+    }
+
+    async fn fill(&mut self) -> Result<()> {
+        let mut buffer = [0u8; 4096];
+        let n = self
+            .stream
+            .read(&mut buffer)
+            .await
+            .map_err(|e| TransportServicesError::ReceiveFailed(e.to_string()))?;
+        if n == 0 {
+            return Err(TransportServicesError::ReceiveFailed(
+                "Connection closed during framer handshake".to_string(),
+            ));
+        }
+        #[cfg(feature = "tls")]
+        if let Some(tls) = self.tls.as_mut() {
+            let plaintext = tls.decrypt(&buffer[..n])?;
+            self.buffered.extend(plaintext);
+            return Ok(());
+        }
+        self.buffered.extend_from_slice(&buffer[..n]);
+        Ok(())
+    }
 }
 
 /// Length-prefix framer implementation
 ///
 /// This framer adds a 4-byte length prefix to each message for framing
 pub struct LengthPrefixFramer {
-    buffer: Arc<RwLock<Vec<u8>>>,
+    buffer: Arc<Mutex<Vec<u8>>>,
 }
 
 impl LengthPrefixFramer {
     pub fn new() -> Self {
         Self {
-            buffer: Arc::new(RwLock::new(Vec::new())),
+            buffer: Arc::new(Mutex::new(Vec::new())),
         }
     }
 }
@@ -58,8 +228,21 @@ impl Framer for LengthPrefixFramer {
         Ok(framed)
     }
 
+    async fn frame_segments(
+        &self,
+        message: &Message,
+        _context: &MessageContext,
+    ) -> Result<Vec<Bytes>> {
+        let len = message.len() as u32;
+
+        let mut segments = vec![Bytes::copy_from_slice(&len.to_be_bytes())];
+        segments.extend(message.segments());
+
+        Ok(segments)
+    }
+
     async fn parse_data(&self, data: &[u8]) -> Result<Vec<(Message, MessageContext)>> {
-        let mut buffer = self.buffer.write().await;
+        let mut buffer = self.buffer.lock().unwrap();
         buffer.extend_from_slice(data);
 
         let mut messages = Vec::new();
@@ -96,6 +279,18 @@ impl Framer for LengthPrefixFramer {
     fn name(&self) -> &str {
         "length-prefix"
     }
+
+    fn length_prefixed(&self) -> bool {
+        true
+    }
+
+    fn reset(&self) {
+        self.buffer.lock().unwrap().clear();
+    }
+
+    fn take_unparsed(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.buffer.lock().unwrap())
+    }
 }
 
 impl Default for LengthPrefixFramer {
@@ -104,7 +299,115 @@ impl Default for LengthPrefixFramer {
     }
 }
 
+/// CBOR sequence framer (RFC 8742)
+///
+/// Each message is one CBOR data item, sent as-is; the receiver finds message
+/// boundaries by decoding the items, so no length prefix is added. Messages
+/// that are not exactly one well-formed item are rejected on send.
+#[cfg(feature = "cbor")]
+pub struct CborSequenceFramer {
+    buffer: Arc<Mutex<Vec<u8>>>,
+}
+
+#[cfg(feature = "cbor")]
+impl CborSequenceFramer {
+    pub fn new() -> Self {
+        Self {
+            buffer: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+/// Length of the CBOR data item at the start of `data`, or None if it is
+/// incomplete
+#[cfg(feature = "cbor")]
+fn cbor_item_length(data: &[u8]) -> std::result::Result<Option<usize>, String> {
+    let mut rest = data;
+    match ciborium::from_reader::<ciborium::Value, _>(&mut rest) {
+        Ok(_) => Ok(Some(data.len() - rest.len())),
+        Err(ciborium::de::Error::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+            Ok(None)
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(feature = "cbor")]
+#[async_trait]
+impl Framer for CborSequenceFramer {
+    async fn frame_message(&self, message: &Message, _context: &MessageContext) -> Result<Vec<u8>> {
+        let data = message.data();
+        match cbor_item_length(data) {
+            Ok(Some(length)) if length == data.len() => Ok(data.to_vec()),
+            Ok(Some(_)) => Err(TransportServicesError::SendFailed(
+                "Message holds more than one CBOR data item".to_string(),
+            )),
+            Ok(None) => Err(TransportServicesError::SendFailed(
+                "Message is not a complete CBOR data item".to_string(),
+            )),
+            Err(e) => Err(TransportServicesError::SendFailed(format!(
+                "Message is not well-formed CBOR: {e}"
+            ))),
+        }
+    }
+
+    async fn parse_data(&self, data: &[u8]) -> Result<Vec<(Message, MessageContext)>> {
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.extend_from_slice(data);
+
+        let mut messages = Vec::new();
+        let mut pos = 0;
+        while pos < buffer.len() {
+            match cbor_item_length(&buffer[pos..]) {
+                Ok(Some(length)) => {
+                    let message = Message::from_bytes(&buffer[pos..pos + length]);
+                    messages.push((message, MessageContext::new()));
+                    pos += length;
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    // Item boundaries are lost, so nothing after this can be parsed
+                    buffer.clear();
+                    return Err(TransportServicesError::ReceiveFailed(format!(
+                        "Malformed CBOR sequence: {e}"
+                    )));
+                }
+            }
+        }
+
+        if pos > 0 {
+            buffer.drain(..pos);
+        }
+
+        Ok(messages)
+    }
+
+    fn name(&self) -> &str {
+        "cbor-sequence"
+    }
+
+    fn reset(&self) {
+        self.buffer.lock().unwrap().clear();
+    }
+
+    fn take_unparsed(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.buffer.lock().unwrap())
+    }
+}
+
+#[cfg(feature = "cbor")]
+impl Default for CborSequenceFramer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Stack of framers that can be applied to a connection
+///
+/// The first framer added is the outermost, closest to the transport. On
+/// send, the last framer added encodes the message first and each earlier
+/// framer wraps the result; on receive, the outermost framer parses the
+/// inbound data and each message it yields is parsed by the next framer in.
 pub struct FramerStack {
     framers: Vec<Box<dyn Framer>>,
 }
@@ -125,37 +428,113 @@ impl FramerStack {
         message: &Message,
         context: &MessageContext,
     ) -> Result<Vec<u8>> {
-        let mut data = message.data().to_vec();
+        Ok(self.frame_segments(message, context).await?.concat())
+    }
+
+    /// Frame a message as a list of buffers suitable for vectored I/O
+    pub async fn frame_segments(
+        &self,
+        message: &Message,
+        context: &MessageContext,
+    ) -> Result<Vec<Bytes>> {
+        let mut segments = message.segments();
 
         // Apply framers in reverse order (last added runs first for outbound)
         for framer in self.framers.iter().rev() {
-            let temp_message = Message::from_bytes(&data);
-            data = framer.frame_message(&temp_message, context).await?;
+            let temp_message = Message::from_segments(segments);
+            segments = framer.frame_segments(&temp_message, context).await?;
         }
 
-        Ok(data)
+        Ok(segments)
     }
 
+    /// Parse inbound data through every framer, outermost first
     pub async fn parse_data(&self, data: &[u8]) -> Result<Vec<(Message, MessageContext)>> {
-        // For now, just use the first framer if available
-        if let Some(framer) = self.framers.first() {
-            framer.parse_data(data).await
-        } else {
+        if self.framers.is_empty() {
             // No framers, return original data as message if not empty
             if data.is_empty() {
-                Ok(Vec::new())
-            } else {
-                let message = Message::from_bytes(data);
-                let context = MessageContext::new();
-                Ok(vec![(message, context)])
+                return Ok(Vec::new());
             }
+            let message = Message::from_bytes(data);
+            let context = MessageContext::new();
+            return Ok(vec![(message, context)]);
+        }
+        parse_through(&self.framers, data).await
+    }
+
+    /// Parse the payload of an outermost message through the inner framers
+    pub(crate) async fn parse_inner(&self, data: &[u8]) -> Result<Vec<(Message, MessageContext)>> {
+        parse_through(self.framers.get(1..).unwrap_or_default(), data).await
+    }
+
+    /// Whether the outermost framer is length-prefixed
+    pub fn outermost_length_prefixed(&self) -> bool {
+        self.framers
+            .first()
+            .is_some_and(|framer| framer.length_prefixed())
+    }
+
+    /// Whether framers are stacked inside the outermost one
+    pub fn has_inner(&self) -> bool {
+        self.framers.len() > 1
+    }
+
+    /// Take the bytes the outermost framer received but has not parsed
+    ///
+    /// Data buffered by inner framers is payload of an outer message and
+    /// cannot be parsed again from the transport, so it is discarded.
+    pub(crate) fn take_unparsed(&self) -> Vec<u8> {
+        let unparsed = self
+            .framers
+            .first()
+            .map(|framer| framer.take_unparsed())
+            .unwrap_or_default();
+        self.reset();
+        unparsed
+    }
+
+    /// Discard the partially parsed inbound data of every framer
+    pub fn reset(&self) {
+        for framer in &self.framers {
+            framer.reset();
+        }
+    }
+
+    /// Deliver the Start event to every framer, outermost first
+    ///
+    /// Returns the bytes received past the framers' preambles, or a
+    /// description of the framer that failed.
+    pub(crate) async fn start(
+        &self,
+        stream: &mut TcpStream,
+    ) -> std::result::Result<Vec<u8>, String> {
+        let mut handshake = FramerHandshake::new(stream, Vec::new());
+        for framer in &self.framers {
+            framer
+                .start(&mut handshake)
+                .await
+                .map_err(|e| format!("Framer {} failed to start: {e}", framer.name()))?;
         }
+        Ok(handshake.into_buffered())
+    }
+
+    /// Deliver the Stop event to every framer, innermost first
+    pub(crate) async fn stop(&self, handshake: &mut FramerHandshake<'_>) -> Result<()> {
+        for framer in self.framers.iter().rev() {
+            framer.stop(handshake).await?;
+        }
+        Ok(())
     }
 
     pub fn is_empty(&self) -> bool {
         self.framers.is_empty()
     }
 
+    /// Number of framers in the stack
+    pub fn len(&self) -> usize {
+        self.framers.len()
+    }
+
     pub async fn on_attach(&self) -> Result<()> {
         for framer in &self.framers {
             framer.on_attach().await?;
@@ -171,6 +550,37 @@ impl FramerStack {
     }
 }
 
+/// Parse data through framers in order, each parsing the messages of the one before
+///
+/// Framer metadata from every layer is kept on the resulting message context.
+async fn parse_through(
+    framers: &[Box<dyn Framer>],
+    data: &[u8],
+) -> Result<Vec<(Message, MessageContext)>> {
+    let Some((outermost, inner)) = framers.split_first() else {
+        return Ok(vec![(Message::from_bytes(data), MessageContext::new())]);
+    };
+
+    let mut messages = outermost.parse_data(data).await?;
+    for framer in inner {
+        let mut parsed = Vec::new();
+        for (message, context) in messages {
+            for (inner_message, mut inner_context) in framer.parse_data(message.data()).await? {
+                for (key, value) in &context.message_properties.framer_metadata {
+                    inner_context
+                        .message_properties
+                        .framer_metadata
+                        .entry(key.clone())
+                        .or_insert_with(|| value.clone());
+                }
+                parsed.push((inner_message, inner_context));
+            }
+        }
+        messages = parsed;
+    }
+    Ok(messages)
+}
+
 impl Default for FramerStack {
     fn default() -> Self {
         Self::new()
@@ -0,0 +1,56 @@
+//! Application-level liveness detection
+//! Probes are sent as ordinary messages, through the connection's framers,
+//! so they work on any transport; any data the peer sends within the
+//! response window counts as an answer
+
+use crate::Message;
+use std::time::Duration;
+
+/// Probe a connection periodically and report when the peer stops answering
+///
+/// Unlike TCP keep-alive, probes are visible to the peer's application,
+/// which is expected to answer them, and a peer that keeps the transport
+/// up but stops responding is detected.
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    /// Message sent as each probe; an ID is assigned per probe if unset
+    pub probe: Message,
+    /// Time between probes
+    pub interval: Duration,
+    /// Time after a probe within which the peer must send something
+    pub response_window: Duration,
+    /// Consecutive unanswered probes before the connection is reported
+    /// Unresponsive
+    pub missed_probes: u32,
+}
+
+impl Heartbeat {
+    /// Probe with `probe` every 15s, reporting after 3 probes go
+    /// unanswered for 5s each
+    pub fn new(probe: impl Into<Message>) -> Self {
+        Self {
+            probe: probe.into(),
+            interval: Duration::from_secs(15),
+            response_window: Duration::from_secs(5),
+            missed_probes: 3,
+        }
+    }
+
+    /// Set the time between probes
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the time the peer has to answer a probe
+    pub fn with_response_window(mut self, window: Duration) -> Self {
+        self.response_window = window;
+        self
+    }
+
+    /// Set how many consecutive probes may go unanswered
+    pub fn with_missed_probes(mut self, missed: u32) -> Self {
+        self.missed_probes = missed.max(1);
+        self
+    }
+}
@@ -0,0 +1,69 @@
+//! Hostname validation and IDNA conversion
+//!
+//! Hostnames in endpoints may be internationalized; before they reach a
+//! resolver, a proxy or a TLS server name they are converted to their ASCII
+//! form with UTS #46 processing, each non-ASCII label encoded as punycode
+//! (RFC 3492) behind the `xn--` prefix (RFC 5890). Labels must otherwise
+//! follow the letter, digit and hyphen rule of RFC 1123, with underscores
+//! also allowed for names such as `_dmarc.example.com` (RFC 2782).
+
+use crate::{Result, TransportServicesError};
+
+/// Longest label, in ASCII form
+const MAX_LABEL_LENGTH: usize = 63;
+
+/// Longest hostname, in ASCII form and without a trailing dot
+const MAX_HOSTNAME_LENGTH: usize = 253;
+
+/// Convert a hostname to the ASCII form resolvers and servers expect
+///
+/// Labels are mapped and lowercased and non-ASCII labels punycode-encoded;
+/// ideographic and fullwidth full stops separate labels like `.`, and a
+/// trailing dot is dropped. IP address literals are returned unchanged.
+pub fn to_ascii(hostname: &str) -> Result<String> {
+    let invalid = |reason: &str| {
+        TransportServicesError::InvalidParameters(format!(
+            "Invalid hostname {hostname:?}: {reason}"
+        ))
+    };
+
+    if hostname.parse::<std::net::IpAddr>().is_ok() {
+        return Ok(hostname.to_string());
+    }
+    let is_separator = |c: char| matches!(c, '.' | '\u{3002}' | '\u{FF0E}' | '\u{FF61}');
+    let name = hostname.strip_suffix(is_separator).unwrap_or(hostname);
+    if name.is_empty() {
+        return Err(invalid("it is empty"));
+    }
+
+    let is_allowed = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.');
+    if let Some(c) = name.chars().find(|&c| c.is_ascii() && !is_allowed(c)) {
+        return Err(invalid(&format!("{c:?} is not allowed")));
+    }
+    let ascii = idna::domain_to_ascii(name)
+        .map_err(|_| invalid("it is not a valid internationalized domain name"))?;
+    // Mapping may turn other characters, e.g. fullwidth forms, into ASCII ones
+    if let Some(c) = ascii.chars().find(|&c| !is_allowed(c)) {
+        return Err(invalid(&format!("{c:?} is not allowed")));
+    }
+
+    for label in ascii.split('.') {
+        if label.is_empty() {
+            return Err(invalid("it has an empty label"));
+        }
+        if label.starts_with('-') || label.ends_with('-') {
+            return Err(invalid(&format!("label {label:?} starts or ends with '-'")));
+        }
+        if label.len() > MAX_LABEL_LENGTH {
+            return Err(invalid(&format!(
+                "label {label:?} is longer than {MAX_LABEL_LENGTH} bytes"
+            )));
+        }
+    }
+    if ascii.len() > MAX_HOSTNAME_LENGTH {
+        return Err(invalid(&format!(
+            "it is longer than {MAX_HOSTNAME_LENGTH} bytes"
+        )));
+    }
+    Ok(ascii)
+}
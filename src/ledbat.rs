@@ -0,0 +1,147 @@
+//! Application-level LEDBAT pacing for Scavenger connections
+//! Where the OS has no less-than-best-effort congestion control, the rate a
+//! Scavenger connection hands data to the transport is limited instead,
+//! backing off as round-trip times inflate, after LEDBAT++
+//! (draft-irtf-iccrg-ledbat-plus-plus)
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Queuing delay LEDBAT++ aims to stay under
+pub(crate) const TARGET_DELAY: Duration = Duration::from_millis(60);
+
+/// Segment size the window is counted in
+const MSS: f64 = 1448.0;
+
+/// Smallest window, so the connection keeps making progress
+const MIN_WINDOW: f64 = 2.0 * MSS;
+
+/// Window the connection starts with, as TCP's initial window
+const INITIAL_WINDOW: f64 = 10.0 * MSS;
+
+/// Base delay is the minimum over this many intervals, so a route change
+/// that lengthens the path is eventually accepted as the new base
+const BASE_HISTORY: usize = 10;
+const BASE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Round trip assumed for pacing before any is measured
+const MIN_ROUND_TRIP: Duration = Duration::from_millis(1);
+
+/// Paces a connection's sends to a window per round trip that LEDBAT
+/// adjusts from the queuing delay its round-trip samples show
+#[derive(Debug, Clone)]
+pub(crate) struct LedbatPacer {
+    /// Bytes that may be sent per round trip
+    window: f64,
+    slow_start: bool,
+    /// Minimum round trip of each recent interval, oldest first
+    base_history: VecDeque<(Instant, Duration)>,
+    /// Latest round-trip sample
+    current: Option<Duration>,
+    /// When the window was last adjusted, once per round trip
+    adjusted: Instant,
+    /// When the next send may start
+    next_send: Instant,
+}
+
+impl LedbatPacer {
+    pub(crate) fn new(now: Instant) -> Self {
+        Self {
+            window: INITIAL_WINDOW,
+            slow_start: true,
+            base_history: VecDeque::new(),
+            current: None,
+            adjusted: now,
+            next_send: now,
+        }
+    }
+
+    /// Bytes that may be sent per round trip
+    #[cfg(test)]
+    pub(crate) fn window(&self) -> usize {
+        self.window as usize
+    }
+
+    /// Lowest round trip seen recently, taken as the path's delay without
+    /// queuing
+    pub(crate) fn base_delay(&self) -> Option<Duration> {
+        self.base_history.iter().map(|(_, rtt)| *rtt).min()
+    }
+
+    /// Delay added by queues along the path, by the latest sample
+    pub(crate) fn queuing_delay(&self) -> Option<Duration> {
+        Some(self.current?.saturating_sub(self.base_delay()?))
+    }
+
+    /// Whether a new round-trip sample is due: one per round trip
+    pub(crate) fn needs_sample(&self, now: Instant) -> bool {
+        match self.current {
+            Some(rtt) => now.duration_since(self.adjusted) >= rtt.max(MIN_ROUND_TRIP),
+            None => true,
+        }
+    }
+
+    /// Take a round-trip sample, adjusting the window once per round trip
+    ///
+    /// Below the target delay the window grows, doubling in slow start and
+    /// then by a fraction of a segment per round trip in proportion to the
+    /// room left; above it, it shrinks in proportion to the excess, at most
+    /// by half, as LEDBAT++'s multiplicative decrease.
+    pub(crate) fn on_rtt(&mut self, rtt: Duration, now: Instant) {
+        match self.base_history.back_mut() {
+            Some((start, min)) if now.duration_since(*start) < BASE_INTERVAL => {
+                *min = (*min).min(rtt);
+            }
+            _ => {
+                self.base_history.push_back((now, rtt));
+                if self.base_history.len() > BASE_HISTORY {
+                    self.base_history.pop_front();
+                }
+            }
+        }
+        let first = self.current.replace(rtt).is_none();
+        self.adjusted = now;
+        let Some(queuing) = self.queuing_delay().filter(|_| !first) else {
+            return;
+        };
+        let target = TARGET_DELAY.as_secs_f64();
+        let ratio = queuing.as_secs_f64() / target;
+        if ratio > 1.0 {
+            self.slow_start = false;
+            self.window *= 1.0 - (ratio - 1.0).min(0.5);
+        } else if self.slow_start {
+            // LEDBAT++ leaves slow start at three quarters of the target
+            if ratio > 0.75 {
+                self.slow_start = false;
+            } else {
+                self.window *= 2.0;
+            }
+        } else {
+            self.window += self.gain() * (1.0 - ratio) * MSS;
+        }
+        self.window = self.window.max(MIN_WINDOW);
+    }
+
+    /// LEDBAT++'s dynamic gain: 1 for base delays near the target, down to
+    /// 1/16 for short paths, so it competes no harder there than on long ones
+    fn gain(&self) -> f64 {
+        let base = self.base_delay().unwrap_or_default().as_secs_f64();
+        let ratio = (2.0 * TARGET_DELAY.as_secs_f64() / base.max(f64::EPSILON)).ceil();
+        1.0 / ratio.clamp(1.0, 16.0)
+    }
+
+    /// Reserve the pacing budget for `bytes`, returning how long to wait
+    /// before sending them
+    ///
+    /// Sends are spread at a window per round trip; until a round trip is
+    /// measured nothing is held back.
+    pub(crate) fn reserve(&mut self, bytes: usize, now: Instant) -> Duration {
+        let Some(rtt) = self.current else {
+            return Duration::ZERO;
+        };
+        let rate = self.window / rtt.max(MIN_ROUND_TRIP).as_secs_f64();
+        let start = self.next_send.max(now);
+        self.next_send = start + Duration::from_secs_f64(bytes as f64 / rate);
+        start - now
+    }
+}
@@ -3,32 +3,81 @@
 //! This library provides an abstract API for transport protocols that enables
 //! the selection of transport protocols and network paths dynamically at runtime.
 
+pub mod admission;
+pub mod capabilities;
 pub mod connection;
 pub mod connection_group;
 pub mod connection_properties;
+pub mod context;
 pub mod error;
+pub mod fault;
 pub mod framer;
+pub mod heartbeat;
+pub mod hostname;
 pub mod listener;
+pub mod memory;
 pub mod message;
 pub mod path_monitor;
 pub mod preconnection;
+pub mod proxy;
+pub mod racing;
+pub mod reassembly;
+pub mod reconnect;
+pub mod resolver;
+pub mod runtime;
+pub mod selection;
+pub mod shaping;
+pub mod shutdown;
+pub mod simple;
+pub mod sniff;
+pub mod state_machine;
+pub mod trust;
 pub mod types;
 
+mod ledbat;
+#[cfg(feature = "tls")]
+mod tls;
+mod udp_offload;
+#[cfg(all(target_os = "linux", feature = "io-uring-writes"))]
+mod uring;
+mod url;
+
 #[cfg(feature = "ffi")]
 pub mod ffi;
 
-pub use connection::Connection;
+pub use admission::{AcceptRate, AdmissionPolicy, ListenerStats};
+pub use capabilities::{capabilities, Capabilities};
+pub use connection::{Batch, Connection, FilteredEvents};
 pub use connection_group::{ConnectionGroup, ConnectionGroupId};
 pub use connection_properties::{
-    CapacityProfile, ChecksumCoverage, ConnectionProperties, ConnectionProperty, MultipathPolicy,
-    SchedulerType, TimeoutValue,
+    CapacityProfile, ChecksumCoverage, CongestionAlgorithm, ConnectionProperties,
+    ConnectionProperty, MultipathPolicy, PathInfo, PropertyNamespace, ProtocolStack, SchedulerType,
+    StackLayer, TimeoutValue,
 };
+pub use context::{Candidate, CandidatePolicy, CandidateSet, SessionCache, TransportServices};
 pub use error::{Result, TransportServicesError};
-pub use framer::{Framer, FramerStack, LengthPrefixFramer};
-pub use listener::{Listener, ListenerEvent};
-pub use message::{Message, MessageContext};
+pub use fault::{Fault, FaultDirection, FaultInjector};
+#[cfg(feature = "cbor")]
+pub use framer::CborSequenceFramer;
+pub use framer::{Framer, FramerFactory, FramerHandshake, FramerStack, LengthPrefixFramer};
+pub use heartbeat::Heartbeat;
+pub use listener::{AcceptErrorClass, Listener, ListenerEvent};
+pub use memory::{MemoryBudget, MemoryPressurePolicy, MemoryUsage};
+pub use message::{Message, MessageContext, ReceivedMessageProperties};
 pub use path_monitor::{ChangeEvent, Interface, MonitorHandle, NetworkMonitor, Status};
 pub use preconnection::Preconnection;
+pub use proxy::{ProxyConfig, ProxyKind};
+pub use reconnect::{ReconnectHook, ReconnectPolicy, ReconnectingConnection};
+pub use resolver::{DnsResolver, DnssecPolicy, ResolutionCache, ResolverConfig, ResolverTransport};
+pub use runtime::{Executor, IoBackend};
+pub use selection::{
+    CandidateBranch, Decision, EndpointBranch, PruneReason, SelectionExplanation, StackBranch,
+};
+pub use shaping::NetworkConditions;
+pub use shutdown::{shutdown, ShutdownSummary};
+pub use simple::{connect, listen, Connect};
+pub use sniff::{ClientHelloInfo, ProtocolSniffer, SniffedData};
+pub use trust::{AllOf, AnyOf, CertificateSelector, IdentityProvider, ReplayPolicy, TrustVerifier};
 pub use types::*;
 
 #[cfg(test)]
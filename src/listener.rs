@@ -1,27 +1,110 @@
 //! Listener implementation for Transport Services
 //! Based on RFC 9622 Section 7.2 (Passive Open: Listen)
 
+use crate::admission::{
+    AdmissionControl, AdmissionPolicy, AdmissionTicket, ListenerStats, Rejection,
+};
+use crate::connection::{
+    enable_receive_packet_info, enable_receive_timestamps, enable_receive_traffic_class,
+    recv_timestamped, set_buffer_sizes, ReceiveInfo,
+};
+use crate::path_monitor::{
+    self, is_ipv6_link_local, ChangeEvent, Interface, NetworkMonitor, Status,
+};
+use crate::runtime;
+use crate::sniff::ProtocolSniffer;
+use crate::udp_offload;
 use crate::{
-    Connection, ConnectionState, EndpointIdentifier, LocalEndpoint, Preconnection, RemoteEndpoint,
-    Result, TransportServicesError,
+    Connection, ConnectionState, EndpointIdentifier, LocalEndpoint, Preconnection, Preference,
+    RemoteEndpoint, Result, SecurityParameters, TransportProperties, TransportServicesError,
 };
-use std::net::SocketAddr;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Arc;
-use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{mpsc, RwLock};
+use futures::Stream;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::io::Interest;
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::{mpsc, RwLock, Semaphore};
+
+/// How often idle datagram flows are swept
+const SWEEP_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Default time a per-peer datagram flow may stay idle before it is evicted
+const DEFAULT_PEER_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
 
 /// Event types that can be emitted by listeners
 #[derive(Debug)]
 pub enum ListenerEvent {
     /// A new connection was received
     ConnectionReceived(Connection),
+    /// An incoming connection attempt failed or was refused
+    /// RFC Section 7.2
+    EstablishmentError {
+        /// Address of the peer, if the attempt got far enough to know it
+        remote: Option<SocketAddr>,
+        /// What kind of failure this was
+        class: AcceptErrorClass,
+        /// Human-readable reason
+        reason: String,
+    },
+    /// The listener started accepting on a new address, after an interface
+    /// gained it
+    AddressAdded(SocketAddr),
+    /// The listener stopped accepting on an address its interface lost
+    AddressRemoved(SocketAddr),
     /// Listener stopped
     Stopped,
     /// Error occurred
     Error(String),
 }
 
+/// Classification of a failed incoming connection attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcceptErrorClass {
+    /// Out of file descriptors or memory (EMFILE, ENFILE, ENOBUFS, ENOMEM)
+    ResourceExhausted,
+    /// Refused by the connection limit or admission policy
+    Filtered,
+    /// The security handshake with the peer failed
+    HandshakeFailed,
+    /// Any other I/O error
+    Io,
+}
+
+impl AcceptErrorClass {
+    /// Classify an error returned by accept
+    pub fn from_io_error(error: &std::io::Error) -> Self {
+        #[cfg(unix)]
+        if matches!(
+            error.raw_os_error(),
+            Some(libc::EMFILE | libc::ENFILE | libc::ENOBUFS | libc::ENOMEM)
+        ) {
+            return AcceptErrorClass::ResourceExhausted;
+        }
+        if error.kind() == std::io::ErrorKind::OutOfMemory {
+            return AcceptErrorClass::ResourceExhausted;
+        }
+        AcceptErrorClass::Io
+    }
+}
+
+/// An accepted connection whose TLS handshake finished, or why it failed
+type HandshakeOutcome =
+    std::result::Result<(Connection, SocketAddr, AdmissionTicket), (SocketAddr, String)>;
+
+/// TLS settings of a Listener terminating TLS
+#[cfg(feature = "tls")]
+type ServerTls = crate::tls::ServerSettings;
+#[cfg(not(feature = "tls"))]
+type ServerTls = std::convert::Infallible;
+
+/// Pause after running out of descriptors so the accept loop does not spin
+const RESOURCE_EXHAUSTED_BACKOFF: Duration = Duration::from_millis(50);
+
 /// A Listener waits for incoming Connections from Remote Endpoints
 pub struct Listener {
     inner: Arc<RwLock<ListenerInner>>,
@@ -29,12 +112,79 @@ pub struct Listener {
     stop_sender: tokio::sync::broadcast::Sender<()>,
     active: Arc<AtomicBool>,
     connection_limit: Arc<AtomicUsize>,
+    // Datagram listeners: deliver each peer on its own Connection
+    demultiplex_peers: Arc<AtomicBool>,
+    // Datagram listeners: idle time (ms) before a peer flow is evicted, 0 disables eviction
+    peer_idle_timeout_ms: Arc<AtomicU64>,
+    admission_policy: Arc<Mutex<AdmissionPolicy>>,
+    stats: Arc<Mutex<ListenerStats>>,
+    // Peeks at incoming streams before delivering them, if set
+    sniffer: Arc<Mutex<Option<ProtocolSniffer>>>,
+    // TLS settings for new handshakes, None when connections stay in cleartext
+    #[cfg(feature = "tls")]
+    tls_config: Arc<Mutex<Option<crate::tls::ServerSettings>>>,
+}
+
+/// Stops a Listener without keeping it alive, for shutdown
+#[derive(Debug)]
+pub(crate) struct StopHandle {
+    active: Weak<AtomicBool>,
+    stop_sender: tokio::sync::broadcast::WeakSender<()>,
+}
+
+impl StopHandle {
+    /// Whether the listener still exists and has not been stopped
+    pub(crate) fn is_live(&self) -> bool {
+        self.active
+            .upgrade()
+            .is_some_and(|active| active.load(Ordering::Relaxed))
+    }
+
+    /// Stop the listener; returns whether it was still active
+    pub(crate) fn stop(&self) -> bool {
+        let (Some(active), Some(stop_sender)) = (self.active.upgrade(), self.stop_sender.upgrade())
+        else {
+            return false;
+        };
+        let stopped = active.swap(false, Ordering::Relaxed);
+        if stopped {
+            let _ = stop_sender.send(());
+        }
+        stopped
+    }
+}
+
+/// A per-peer flow on a datagram Listener
+struct PeerFlow {
+    connection: Connection,
+    last_activity: Instant,
 }
 
 struct ListenerInner {
     preconnection: Preconnection,
     event_sender: mpsc::UnboundedSender<ListenerEvent>,
-    local_addr: Option<SocketAddr>,
+    // Every address the listener is bound to, with kernel-assigned ports
+    local_addrs: Vec<SocketAddr>,
+    // Local endpoints given by interface name, bound to each of its addresses
+    interface_bindings: Vec<InterfaceBinding>,
+    // Wildcard addresses that could not be bound yet, e.g. IPv6 before the
+    // host has an IPv6 address
+    unbound_wildcards: Vec<SocketAddr>,
+    // Adds and removes sockets served by the accept loop
+    socket_changes: Option<mpsc::UnboundedSender<SocketChange>>,
+}
+
+/// A local endpoint given by interface name
+struct InterfaceBinding {
+    interface: String,
+    // Shared by all of the interface's addresses once the kernel picked it
+    port: u16,
+}
+
+/// A change to the sockets served by a stream listener's accept loop
+enum SocketChange {
+    Add(TcpListener, SocketAddr),
+    Remove(SocketAddr),
 }
 
 impl Clone for Listener {
@@ -45,6 +195,13 @@ impl Clone for Listener {
             stop_sender: self.stop_sender.clone(),
             active: Arc::clone(&self.active),
             connection_limit: Arc::clone(&self.connection_limit),
+            demultiplex_peers: Arc::clone(&self.demultiplex_peers),
+            peer_idle_timeout_ms: Arc::clone(&self.peer_idle_timeout_ms),
+            admission_policy: Arc::clone(&self.admission_policy),
+            stats: Arc::clone(&self.stats),
+            sniffer: Arc::clone(&self.sniffer),
+            #[cfg(feature = "tls")]
+            tls_config: Arc::clone(&self.tls_config),
         }
     }
 }
@@ -60,7 +217,10 @@ impl Listener {
         let inner = Arc::new(RwLock::new(ListenerInner {
             preconnection: preconnection.clone(),
             event_sender: event_sender.clone(),
-            local_addr: None,
+            local_addrs: Vec::new(),
+            interface_bindings: Vec::new(),
+            unbound_wildcards: Vec::new(),
+            socket_changes: None,
         }));
 
         Self {
@@ -69,12 +229,38 @@ impl Listener {
             stop_sender,
             active,
             connection_limit,
+            demultiplex_peers: Arc::new(AtomicBool::new(true)),
+            peer_idle_timeout_ms: Arc::new(AtomicU64::new(
+                DEFAULT_PEER_IDLE_TIMEOUT.as_millis() as u64
+            )),
+            admission_policy: Arc::new(Mutex::new(AdmissionPolicy::default())),
+            stats: Arc::new(Mutex::new(ListenerStats::default())),
+            sniffer: Arc::new(Mutex::new(None)),
+            #[cfg(feature = "tls")]
+            tls_config: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Handle that stops this listener once it is no longer needed elsewhere
+    pub(crate) fn stop_handle(&self) -> StopHandle {
+        StopHandle {
+            active: Arc::downgrade(&self.active),
+            stop_sender: self.stop_sender.downgrade(),
         }
     }
 
     /// Start listening on the configured endpoints
+    ///
+    /// Stream listeners bind every local endpoint and accept on all of them;
+    /// datagram listeners are served from the first one. Endpoints given by
+    /// interface name are bound to each address of the interface, and follow
+    /// its addresses as they come and go.
     pub(crate) async fn start(&self) -> Result<()> {
         let inner = self.inner.read().await;
+        crate::shutdown::register_listener(self.stop_handle());
+        if let Some(context) = inner.preconnection.context().await {
+            context.registry().add_listener(self.stop_handle());
+        }
         // Access preconnection data through public API
         let (local_endpoints, _) = inner.preconnection.resolve().await?;
 
@@ -85,22 +271,95 @@ impl Listener {
             )
         })?;
 
-        // Extract socket address to bind to
-        let bind_addr = self.extract_bind_address(local_endpoint)?;
+        // Connectionless transports are served from a single datagram socket
+        let transport_properties = inner.preconnection.transport_properties().await;
+        if transport_properties.selection_properties.reliability == Preference::Prohibit
+            || Self::has_multicast_group(local_endpoint)
+        {
+            let local_endpoint = local_endpoint.clone();
+            drop(inner);
+            return self.start_datagram(&local_endpoint).await;
+        }
 
-        // Start TCP listener
-        let tcp_listener = TcpListener::bind(bind_addr)
-            .await
-            .map_err(TransportServicesError::Io)?;
+        // Connections are secured before they are delivered when the
+        // security parameters give the Listener identities to present
+        let security_parameters = inner.preconnection.security_parameters().await;
+        drop(inner);
+        #[cfg(feature = "tls")]
+        {
+            *self.tls_config.lock().unwrap() = crate::tls::server_config(&security_parameters)?;
+        }
+        #[cfg(not(feature = "tls"))]
+        if !security_parameters.disabled && !security_parameters.server_identities.is_empty() {
+            return Err(TransportServicesError::NotSupported(
+                "Built without the tls feature".to_string(),
+            ));
+        }
 
-        let actual_addr = tcp_listener
-            .local_addr()
-            .map_err(TransportServicesError::Io)?;
+        // Start a TCP listener on each local endpoint; one that cannot be
+        // bound, e.g. IPv6 on a host without it, only fails if none can
+        let mut tcp_listeners = Vec::new();
+        let mut interface_bindings = Vec::new();
+        let mut unbound_wildcards = Vec::new();
+        let mut bind_error = None;
+        for endpoint in &local_endpoints {
+            if let Some(mut binding) = Self::interface_binding(endpoint) {
+                // An interface without addresses yet is bound once it gets one
+                if let Some(interface) = path_monitor::interface_by_name(&binding.interface).await {
+                    for ip in interface.ips {
+                        match binding.bind(ip, interface.index, &transport_properties) {
+                            Ok(bound) => tcp_listeners.push(bound),
+                            Err(e) => log::warn!("Failed to listen on {ip}: {e}"),
+                        }
+                    }
+                }
+                interface_bindings.push(binding);
+                continue;
+            }
 
-        // Update local address
-        drop(inner);
+            let bind_addr = self.extract_bind_address(endpoint).await?;
+            match Self::bind_stream_listener(bind_addr, &transport_properties) {
+                Ok(listener) => {
+                    let actual_addr = listener.local_addr().map_err(TransportServicesError::Io)?;
+                    tcp_listeners.push((listener, actual_addr));
+                }
+                Err(e) => {
+                    log::warn!("Failed to listen on {bind_addr}: {e}");
+                    if bind_addr.ip().is_unspecified() {
+                        unbound_wildcards.push(bind_addr);
+                    }
+                    bind_error.get_or_insert(e);
+                }
+            }
+        }
+        if tcp_listeners.is_empty() && interface_bindings.is_empty() {
+            return Err(bind_error.unwrap_or_else(|| {
+                TransportServicesError::InvalidParameters(
+                    "No local endpoint specified for listen".to_string(),
+                )
+            }));
+        }
+        let follow_interfaces = !interface_bindings.is_empty() || !unbound_wildcards.is_empty();
+
+        // Accepting data in the SYN is the application's opt-in to 0-RTT,
+        // and needs a replay policy to check that data with
+        let fast_open = matches!(
+            transport_properties.selection_properties.zero_rtt_msg,
+            Preference::Require | Preference::Prefer
+        ) && security_parameters.replay_policy.is_some();
+        if fast_open {
+            for (listener, _) in &tcp_listeners {
+                enable_fast_open(listener);
+            }
+        }
+
+        // Update local addresses
+        let (socket_changes, mut socket_change_receiver) = mpsc::unbounded_channel();
         let mut inner = self.inner.write().await;
-        inner.local_addr = Some(actual_addr);
+        inner.local_addrs = tcp_listeners.iter().map(|(_, addr)| *addr).collect();
+        inner.interface_bindings = interface_bindings;
+        inner.unbound_wildcards = unbound_wildcards;
+        inner.socket_changes = Some(socket_changes);
         let event_sender = inner.event_sender.clone();
         let preconnection = inner.preconnection.clone();
         drop(inner);
@@ -111,9 +370,17 @@ impl Listener {
         // Spawn accept loop
         let active = Arc::clone(&self.active);
         let connection_limit = Arc::clone(&self.connection_limit);
+        let mut admission =
+            AdmissionControl::new(Arc::clone(&self.admission_policy), Arc::clone(&self.stats));
         let mut stop_receiver = self.stop_sender.subscribe();
+        let sniffer = Arc::clone(&self.sniffer);
+        #[cfg(feature = "tls")]
+        let tls_config = Arc::clone(&self.tls_config);
+        // Connections still being sniffed or in their TLS handshake report back here
+        let (handshake_sender, mut handshake_receiver) =
+            mpsc::unbounded_channel::<HandshakeOutcome>();
 
-        tokio::spawn(async move {
+        runtime::spawn(async move {
             // Signal that we're ready to accept connections
             let _ = ready_tx.send(());
 
@@ -122,26 +389,118 @@ impl Listener {
                     break;
                 }
 
+                // An interface listener may have no sockets until an address appears
+                let accept = async {
+                    if tcp_listeners.is_empty() {
+                        return std::future::pending().await;
+                    }
+                    let accepts = tcp_listeners.iter().map(|(listener, addr)| {
+                        Box::pin(async move { (listener.accept().await, *addr) })
+                    });
+                    futures::future::select_all(accepts).await.0
+                };
+
                 tokio::select! {
                     _ = stop_receiver.recv() => {
                         break;
                     }
-                    result = tcp_listener.accept() => {
+                    Some(change) = socket_change_receiver.recv() => {
+                        match change {
+                            SocketChange::Add(listener, addr) => {
+                                if fast_open {
+                                    enable_fast_open(&listener);
+                                }
+                                tcp_listeners.push((listener, addr))
+                            }
+                            SocketChange::Remove(addr) => {
+                                tcp_listeners.retain(|(_, bound)| *bound != addr)
+                            }
+                        }
+                    }
+                    Some(outcome) = handshake_receiver.recv() => {
+                        match outcome {
+                            Ok((conn, peer_addr, ticket)) => {
+                                admission.admit(peer_addr.ip(), ticket, &conn).await;
+                                let _ = event_sender.send(ListenerEvent::ConnectionReceived(conn));
+                            }
+                            Err((peer_addr, reason)) => {
+                                // No connection was delivered, so the limit is not spent
+                                if connection_limit.load(Ordering::Relaxed) != usize::MAX {
+                                    connection_limit.fetch_add(1, Ordering::Relaxed);
+                                }
+                                let _ = event_sender.send(ListenerEvent::EstablishmentError {
+                                    remote: Some(peer_addr),
+                                    class: AcceptErrorClass::HandshakeFailed,
+                                    reason,
+                                });
+                            }
+                        }
+                    }
+                    (result, actual_addr) = accept => {
                         match result {
                             Ok((stream, peer_addr)) => {
                                 // Check connection limit
                                 let current = connection_limit.load(Ordering::Relaxed);
                                 if current == 0 {
                                     // Drop connection - limit reached
+                                    admission.reject(Rejection::NewConnections);
+                                    Self::report_rejection(
+                                        &event_sender,
+                                        peer_addr,
+                                        Rejection::NewConnections,
+                                    );
                                     drop(stream);
                                     continue;
                                 }
 
+                                // Apply per-source and global admission control
+                                let ticket = match admission.check(peer_addr.ip()) {
+                                    Ok(ticket) => ticket,
+                                    Err(rejection) => {
+                                        Self::report_rejection(&event_sender, peer_addr, rejection);
+                                        drop(stream);
+                                        continue;
+                                    }
+                                };
+
                                 // Decrement limit if not unlimited
                                 if current != usize::MAX {
                                     connection_limit.fetch_sub(1, Ordering::Relaxed);
                                 }
 
+                                // Sniffing and handshakes run aside so they do not
+                                // hold up the accept loop
+                                let sniffer = sniffer.lock().unwrap().clone();
+                                #[cfg(feature = "tls")]
+                                let tls = tls_config.lock().unwrap().clone();
+                                #[cfg(not(feature = "tls"))]
+                                let tls: Option<ServerTls> = None;
+                                // Data in a TLS client's SYN is its ClientHello
+                                let syn_data = match fast_open && tls.is_none() {
+                                    true => syn_data_length(&stream),
+                                    false => None,
+                                };
+                                if sniffer.is_some() || tls.is_some() || syn_data.is_some() {
+                                    let preconnection = preconnection.clone();
+                                    let handshake_sender = handshake_sender.clone();
+                                    runtime::spawn(async move {
+                                        let outcome = Self::prepare_stream(
+                                            stream,
+                                            peer_addr,
+                                            actual_addr,
+                                            &preconnection,
+                                            sniffer,
+                                            tls,
+                                            syn_data,
+                                        )
+                                        .await
+                                        .map(|conn| (conn, peer_addr, ticket))
+                                        .map_err(|e| (peer_addr, e.to_string()));
+                                        let _ = handshake_sender.send(outcome);
+                                    });
+                                    continue;
+                                }
+
                                 // Create connection from accepted stream
                                 let conn = Self::create_connection_from_stream(
                                     stream,
@@ -150,10 +509,19 @@ impl Listener {
                                     &preconnection
                                 ).await;
 
+                                admission.admit(peer_addr.ip(), ticket, &conn).await;
                                 let _ = event_sender.send(ListenerEvent::ConnectionReceived(conn));
                             }
                             Err(e) => {
-                                let _ = event_sender.send(ListenerEvent::Error(e.to_string()));
+                                let class = AcceptErrorClass::from_io_error(&e);
+                                let _ = event_sender.send(ListenerEvent::EstablishmentError {
+                                    remote: None,
+                                    class,
+                                    reason: e.to_string(),
+                                });
+                                if class == AcceptErrorClass::ResourceExhausted {
+                                    runtime::sleep(RESOURCE_EXHAUSTED_BACKOFF).await;
+                                }
                             }
                         }
                     }
@@ -167,27 +535,508 @@ impl Listener {
         // Wait for the accept loop to be ready
         let _ = ready_rx.await;
 
+        if follow_interfaces {
+            self.watch_interfaces();
+        }
+
         Ok(())
     }
 
+    /// Follow interface changes reported by the NetworkMonitor until the listener stops
+    fn watch_interfaces(&self) {
+        let (event_sender, mut event_receiver) = mpsc::unbounded_channel();
+        let (stop_monitor, monitor_stopped) = std::sync::mpsc::channel::<()>();
+
+        // Platform monitors may drive their own event loop, so the monitor
+        // lives on a separate thread rather than inside the caller's runtime
+        std::thread::spawn(move || {
+            let monitor = match NetworkMonitor::new() {
+                Ok(monitor) => monitor,
+                Err(e) => {
+                    log::debug!("Cannot follow interface changes: {e}");
+                    return;
+                }
+            };
+            let _handle = monitor.watch_changes(move |event| {
+                let _ = event_sender.send(event);
+            });
+            let _ = monitor_stopped.recv();
+        });
+
+        let listener = self.clone();
+        let mut stop_receiver = self.stop_sender.subscribe();
+        runtime::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = stop_receiver.recv() => break,
+                    event = event_receiver.recv() => match event {
+                        Some(event) => listener.handle_interface_change(&event).await,
+                        None => break,
+                    },
+                }
+            }
+            let _ = stop_monitor.send(());
+        });
+    }
+
+    /// Rebind the listener's sockets after an interface change
+    ///
+    /// Endpoints given by interface name gain a socket for each new address
+    /// of the interface and lose the sockets of its removed addresses.
+    /// Wildcard sockets accept on new addresses already; only a wildcard
+    /// that could not be bound, e.g. for lack of an address of its family,
+    /// is retried. Each socket added or removed is reported as a
+    /// ListenerEvent.
+    pub(crate) async fn handle_interface_change(&self, event: &ChangeEvent) {
+        fn usable_ips(interface: &Interface) -> Vec<IpAddr> {
+            match interface.status {
+                Status::Down => Vec::new(),
+                _ => interface.ips.clone(),
+            }
+        }
+
+        let (interface, removed, added) = match event {
+            ChangeEvent::Added(interface) => (interface, Vec::new(), usable_ips(interface)),
+            ChangeEvent::Removed(interface) => (interface, interface.ips.clone(), Vec::new()),
+            ChangeEvent::Modified { old, new } => {
+                let (old_ips, new_ips) = (usable_ips(old), usable_ips(new));
+                let removed = old_ips
+                    .iter()
+                    .filter(|ip| !new_ips.contains(ip))
+                    .copied()
+                    .collect();
+                let added = new_ips
+                    .iter()
+                    .filter(|ip| !old_ips.contains(ip))
+                    .copied()
+                    .collect();
+                (new, removed, added)
+            }
+            ChangeEvent::PathChanged { .. } => return,
+        };
+        if !self.active.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let mut inner = self.inner.write().await;
+        let Some(socket_changes) = inner.socket_changes.clone() else {
+            return;
+        };
+        let properties = inner.preconnection.transport_properties().await;
+        let ListenerInner {
+            local_addrs,
+            interface_bindings,
+            unbound_wildcards,
+            event_sender,
+            ..
+        } = &mut *inner;
+
+        for ip in removed {
+            for addr in local_addrs.iter().filter(|addr| addr.ip() == ip) {
+                let _ = socket_changes.send(SocketChange::Remove(*addr));
+                let _ = event_sender.send(ListenerEvent::AddressRemoved(*addr));
+            }
+            local_addrs.retain(|addr| addr.ip() != ip);
+        }
+
+        for ip in added {
+            let mut bound = Vec::new();
+            for binding in interface_bindings
+                .iter_mut()
+                .filter(|binding| binding.interface == interface.name)
+            {
+                if local_addrs.iter().any(|addr| addr.ip() == ip) {
+                    continue;
+                }
+                match binding.bind(ip, interface.index, &properties) {
+                    Ok(socket) => bound.push(socket),
+                    Err(e) => {
+                        let _ = event_sender.send(ListenerEvent::Error(format!(
+                            "Failed to listen on {ip}: {e}"
+                        )));
+                    }
+                }
+            }
+
+            let mut retried = Vec::new();
+            for wildcard in unbound_wildcards.iter() {
+                if wildcard.is_ipv4() == ip.is_ipv4() {
+                    if let Ok(listener) = Self::bind_stream_listener(*wildcard, &properties) {
+                        if let Ok(addr) = listener.local_addr() {
+                            bound.push((listener, addr));
+                            retried.push(*wildcard);
+                        }
+                    }
+                }
+            }
+            unbound_wildcards.retain(|wildcard| !retried.contains(wildcard));
+
+            for (listener, addr) in bound {
+                local_addrs.push(addr);
+                let _ = socket_changes.send(SocketChange::Add(listener, addr));
+                let _ = event_sender.send(ListenerEvent::AddressAdded(addr));
+            }
+        }
+    }
+
+    /// The interface binding for a local endpoint given only by interface name
+    fn interface_binding(endpoint: &LocalEndpoint) -> Option<InterfaceBinding> {
+        let mut interface = None;
+        let mut port = 0;
+        for identifier in &endpoint.identifiers {
+            match identifier {
+                EndpointIdentifier::Interface(name) => interface = Some(name.clone()),
+                EndpointIdentifier::Port(p) => port = *p,
+                EndpointIdentifier::IpAddress(_) | EndpointIdentifier::SocketAddress(_) => {
+                    return None
+                }
+                _ => {}
+            }
+        }
+        Some(InterfaceBinding {
+            interface: interface?,
+            port,
+        })
+    }
+
+    /// Start listening for datagrams on a connectionless transport (UDP)
+    ///
+    /// Datagrams are demultiplexed by source address into per-peer Connections,
+    /// unless demultiplexing is disabled, in which case all peers share one Connection.
+    async fn start_datagram(&self, local_endpoint: &LocalEndpoint) -> Result<()> {
+        let socket = Self::bind_datagram_socket(
+            local_endpoint,
+            self.extract_bind_address(local_endpoint).await?,
+        )
+        .await?;
+        let socket = Arc::new(socket);
+        let actual_addr = socket.local_addr().map_err(TransportServicesError::Io)?;
+
+        let mut inner = self.inner.write().await;
+        inner.local_addrs = vec![actual_addr];
+        let event_sender = inner.event_sender.clone();
+        let preconnection = inner.preconnection.clone();
+        drop(inner);
+
+        let timestamps = preconnection
+            .transport_properties()
+            .await
+            .connection_properties
+            .receive_timestamps
+            == Some(true);
+        if timestamps {
+            enable_receive_timestamps(&socket2::SockRef::from(socket.as_ref()));
+        }
+        enable_receive_traffic_class(&socket2::SockRef::from(socket.as_ref()));
+        enable_receive_packet_info(&socket2::SockRef::from(socket.as_ref()));
+        let offload = preconnection
+            .transport_properties()
+            .await
+            .connection_properties
+            .udp_offload
+            != Some(false);
+        if offload {
+            udp_offload::enable_gro(&socket2::SockRef::from(socket.as_ref()));
+        }
+
+        let active = Arc::clone(&self.active);
+        let connection_limit = Arc::clone(&self.connection_limit);
+        let demultiplex_peers = Arc::clone(&self.demultiplex_peers);
+        let peer_idle_timeout_ms = Arc::clone(&self.peer_idle_timeout_ms);
+        let mut admission =
+            AdmissionControl::new(Arc::clone(&self.admission_policy), Arc::clone(&self.stats));
+        let mut stop_receiver = self.stop_sender.subscribe();
+
+        runtime::spawn(async move {
+            // Flows keyed by peer address, or None for the shared flow
+            let mut flows: HashMap<Option<SocketAddr>, PeerFlow> = HashMap::new();
+            let mut buffer = vec![0u8; 65536];
+            let mut next_sweep = Instant::now();
+            let mut accepting = true;
+
+            loop {
+                if accepting && !active.load(Ordering::Relaxed) {
+                    // Existing flows keep being served after the Listener stops
+                    accepting = false;
+                    let _ = event_sender.send(ListenerEvent::Stopped);
+                }
+                if !accepting && flows.is_empty() {
+                    break;
+                }
+
+                tokio::select! {
+                    _ = stop_receiver.recv(), if accepting => {
+                        active.store(false, Ordering::Relaxed);
+                    }
+                    _ = runtime::sleep(next_sweep.saturating_duration_since(Instant::now())) => {
+                        next_sweep = Instant::now() + SWEEP_INTERVAL;
+                        let idle_timeout = peer_idle_timeout_ms.load(Ordering::Relaxed);
+                        let mut expired = Vec::new();
+                        for (key, flow) in &flows {
+                            let idle = idle_timeout != 0
+                                && flow.last_activity.elapsed() >= Duration::from_millis(idle_timeout);
+                            if idle || flow.connection.state().await == ConnectionState::Closed {
+                                expired.push(*key);
+                            }
+                        }
+                        for key in expired {
+                            if let Some(flow) = flows.remove(&key) {
+                                let _ = flow.connection.close().await;
+                            }
+                        }
+                    }
+                    result = Self::receive_datagram(&socket, &mut buffer) => {
+                        match result {
+                            Ok((peer_addr, received)) => {
+                                let key = if demultiplex_peers.load(Ordering::Relaxed) {
+                                    Some(peer_addr)
+                                } else {
+                                    None
+                                };
+
+                                let flow = match flows.entry(key) {
+                                    Entry::Occupied(entry) => entry.into_mut(),
+                                    Entry::Vacant(entry) => {
+                                        if !accepting {
+                                            continue;
+                                        }
+
+                                        // Check connection limit
+                                        let current = connection_limit.load(Ordering::Relaxed);
+                                        if current == 0 {
+                                            admission.reject(Rejection::NewConnections);
+                                            Self::report_rejection(
+                                                &event_sender,
+                                                peer_addr,
+                                                Rejection::NewConnections,
+                                            );
+                                            continue;
+                                        }
+
+                                        // Apply per-source and global admission control
+                                        let ticket = match admission.check(peer_addr.ip()) {
+                                            Ok(ticket) => ticket,
+                                            Err(rejection) => {
+                                                Self::report_rejection(
+                                                    &event_sender,
+                                                    peer_addr,
+                                                    rejection,
+                                                );
+                                                continue;
+                                            }
+                                        };
+                                        if current != usize::MAX {
+                                            connection_limit.fetch_sub(1, Ordering::Relaxed);
+                                        }
+
+                                        let conn = Self::create_connection_from_datagram_socket(
+                                            Arc::clone(&socket),
+                                            peer_addr,
+                                            actual_addr,
+                                            &preconnection,
+                                        )
+                                        .await;
+                                        // The flow must not keep the Connection alive
+                                        // once the application drops it
+                                        let connection = conn.internal_clone();
+                                        admission.admit(peer_addr.ip(), ticket, &conn).await;
+                                        let _ = event_sender
+                                            .send(ListenerEvent::ConnectionReceived(conn));
+                                        entry.insert(PeerFlow {
+                                            connection,
+                                            last_activity: Instant::now(),
+                                        })
+                                    }
+                                };
+
+                                flow.last_activity = Instant::now();
+                                // Datagrams the kernel coalesced are delivered one by one
+                                let data = &buffer[..received.length];
+                                let datagrams = match received.segment_size {
+                                    Some(size) => data.chunks(size).collect(),
+                                    None => vec![data],
+                                };
+                                for datagram in datagrams {
+                                    flow.connection
+                                        .deliver_datagram(datagram, peer_addr, &received)
+                                        .await;
+                                }
+                            }
+                            Err(e) => {
+                                let _ = event_sender.send(ListenerEvent::Error(e.to_string()));
+                            }
+                        }
+                    }
+                }
+            }
+
+            if accepting {
+                active.store(false, Ordering::Relaxed);
+                let _ = event_sender.send(ListenerEvent::Stopped);
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Tell the application that a peer was refused
+    fn report_rejection(
+        event_sender: &mpsc::UnboundedSender<ListenerEvent>,
+        peer_addr: SocketAddr,
+        rejection: Rejection,
+    ) {
+        let _ = event_sender.send(ListenerEvent::EstablishmentError {
+            remote: Some(peer_addr),
+            class: AcceptErrorClass::Filtered,
+            reason: rejection.to_string(),
+        });
+    }
+
+    /// Check whether the local endpoint asks to receive from a multicast group
+    fn has_multicast_group(endpoint: &LocalEndpoint) -> bool {
+        endpoint.identifiers.iter().any(|identifier| {
+            matches!(
+                identifier,
+                EndpointIdentifier::AnySourceMulticastGroupIP(_)
+                    | EndpointIdentifier::SingleSourceMulticastGroupIP { .. }
+            )
+        })
+    }
+
+    /// Receive a datagram, with the kernel's receive timestamp, traffic
+    /// class and packet info where enabled
+    async fn receive_datagram(
+        socket: &UdpSocket,
+        buffer: &mut [u8],
+    ) -> std::io::Result<(SocketAddr, ReceiveInfo)> {
+        socket
+            .async_io(Interest::READABLE, || {
+                let received = recv_timestamped(&socket2::SockRef::from(socket), buffer)?;
+                let peer_addr = received.source.ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "Datagram has no IP source address",
+                    )
+                })?;
+                Ok((peer_addr, received))
+            })
+            .await
+    }
+
+    /// Bind a stream listener socket
+    ///
+    /// Wildcard IPv6 sockets are IPv6-only, so they can share a port with an
+    /// IPv4 wildcard socket for the same endpoint. The buffer sizes are set
+    /// on the listening socket so accepted connections start with them.
+    fn bind_stream_listener(
+        bind_addr: SocketAddr,
+        properties: &TransportProperties,
+    ) -> Result<TcpListener> {
+        let socket = socket2::Socket::new(
+            socket2::Domain::for_address(bind_addr),
+            socket2::Type::STREAM,
+            Some(socket2::Protocol::TCP),
+        )
+        .map_err(TransportServicesError::Io)?;
+        if bind_addr.is_ipv6() {
+            socket
+                .set_only_v6(true)
+                .map_err(TransportServicesError::Io)?;
+        }
+        #[cfg(unix)]
+        socket
+            .set_reuse_address(true)
+            .map_err(TransportServicesError::Io)?;
+        set_buffer_sizes(
+            &socket2::SockRef::from(&socket),
+            properties.connection_properties.send_buffer_size,
+            properties.connection_properties.receive_buffer_size,
+        );
+        socket
+            .bind(&bind_addr.into())
+            .map_err(TransportServicesError::Io)?;
+        socket.listen(1024).map_err(TransportServicesError::Io)?;
+        socket
+            .set_nonblocking(true)
+            .map_err(TransportServicesError::Io)?;
+        TcpListener::from_std(socket.into()).map_err(TransportServicesError::Io)
+    }
+
+    /// Bind a datagram socket, joining any multicast groups on the local endpoint
+    async fn bind_datagram_socket(
+        endpoint: &LocalEndpoint,
+        bind_addr: SocketAddr,
+    ) -> Result<UdpSocket> {
+        let group = endpoint
+            .identifiers
+            .iter()
+            .find_map(|identifier| match identifier {
+                EndpointIdentifier::AnySourceMulticastGroupIP(group) => Some((*group, None)),
+                EndpointIdentifier::SingleSourceMulticastGroupIP { group, source } => {
+                    Some((*group, Some(*source)))
+                }
+                _ => None,
+            });
+
+        let Some((group, source)) = group else {
+            return UdpSocket::bind(bind_addr)
+                .await
+                .map_err(TransportServicesError::Io);
+        };
+
+        // Multicast receivers bind the wildcard address on the group's family
+        let wildcard = match group {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        let socket = UdpSocket::bind(SocketAddr::new(wildcard, bind_addr.port()))
+            .await
+            .map_err(TransportServicesError::Io)?;
+
+        match (group, source) {
+            (IpAddr::V4(group), None) => socket
+                .join_multicast_v4(group, Ipv4Addr::UNSPECIFIED)
+                .map_err(TransportServicesError::Io)?,
+            (IpAddr::V6(group), None) => socket
+                .join_multicast_v6(&group, 0)
+                .map_err(TransportServicesError::Io)?,
+            (IpAddr::V4(group), Some(IpAddr::V4(source))) => socket2::SockRef::from(&socket)
+                .join_ssm_v4(&source, &group, &Ipv4Addr::UNSPECIFIED)
+                .map_err(TransportServicesError::Io)?,
+            _ => {
+                return Err(TransportServicesError::NotSupported(
+                    "Source-specific multicast is only supported for IPv4".to_string(),
+                ))
+            }
+        }
+
+        Ok(socket)
+    }
+
     /// Extract bind address from local endpoint
-    fn extract_bind_address(&self, endpoint: &LocalEndpoint) -> Result<SocketAddr> {
+    async fn extract_bind_address(&self, endpoint: &LocalEndpoint) -> Result<SocketAddr> {
         let mut ip_addr = None;
         let mut port = None;
+        let mut socket_addr = None;
+        let mut interface = None;
 
         for identifier in &endpoint.identifiers {
             match identifier {
                 EndpointIdentifier::IpAddress(addr) => ip_addr = Some(*addr),
                 EndpointIdentifier::Port(p) => port = Some(*p),
-                EndpointIdentifier::SocketAddress(addr) => return Ok(*addr),
+                EndpointIdentifier::SocketAddress(addr) => socket_addr = Some(*addr),
+                EndpointIdentifier::Interface(name) => interface = Some(name.as_str()),
                 _ => {}
             }
         }
 
         // Default to 0.0.0.0:0 if not specified
-        let ip = ip_addr.unwrap_or_else(|| "0.0.0.0".parse().unwrap());
-        let p = port.unwrap_or(0);
-        Ok(SocketAddr::new(ip, p))
+        let addr = socket_addr.unwrap_or_else(|| {
+            let ip = ip_addr.unwrap_or_else(|| "0.0.0.0".parse().unwrap());
+            SocketAddr::new(ip, port.unwrap_or(0))
+        });
+        // Link-local addresses are bound in the zone of their interface
+        path_monitor::scope_link_local(addr, interface).await
     }
 
     /// Create a connection from an accepted TCP stream
@@ -196,6 +1045,114 @@ impl Listener {
         peer_addr: SocketAddr,
         local_addr: SocketAddr,
         preconnection: &Preconnection,
+    ) -> Connection {
+        let mut conn = Self::stream_connection(peer_addr, local_addr, preconnection).await;
+
+        // Set the TCP stream
+        conn.set_tcp_stream(stream).await;
+
+        conn
+    }
+
+    /// Sniff an accepted TCP stream and terminate TLS on it, as configured,
+    /// and create its connection
+    ///
+    /// Without TLS, data the client sent in its SYN is delivered as early
+    /// data, once the early data callback finds it replay-safe; with TLS the
+    /// SYN carries the ClientHello, and TLS decides on its own 0-RTT data.
+    async fn prepare_stream(
+        stream: TcpStream,
+        peer_addr: SocketAddr,
+        local_addr: SocketAddr,
+        preconnection: &Preconnection,
+        sniffer: Option<ProtocolSniffer>,
+        tls: Option<ServerTls>,
+        syn_data: Option<usize>,
+    ) -> Result<Connection> {
+        let sniffed = match sniffer {
+            Some(sniffer) => Some(sniffer.sniff(&stream).await?),
+            None => None,
+        };
+        match tls {
+            #[cfg(feature = "tls")]
+            Some(settings) => {
+                Self::terminate_tls(
+                    stream,
+                    peer_addr,
+                    local_addr,
+                    preconnection,
+                    settings,
+                    sniffed,
+                )
+                .await
+            }
+            #[cfg(not(feature = "tls"))]
+            Some(never) => match never {},
+            None => {
+                let early_data = syn_data.and_then(|length| take_syn_data(&stream, length));
+                if let Some(data) = &early_data {
+                    // Without a replay policy early data is refused
+                    let parameters = preconnection.security_parameters().await;
+                    let replay_safe = parameters
+                        .replay_policy
+                        .as_ref()
+                        .is_some_and(|policy| policy.is_replay_safe(data));
+                    if !replay_safe {
+                        return Err(TransportServicesError::SecurityError(
+                            "Early data is not replay-safe".to_string(),
+                        ));
+                    }
+                }
+                let mut conn = Self::stream_connection(peer_addr, local_addr, preconnection).await;
+                if let Some(sniffed) = sniffed {
+                    conn.set_sniffed(sniffed).await?;
+                }
+                if let Some(data) = early_data {
+                    conn.set_early_data(data).await;
+                }
+                conn.set_tcp_stream(stream).await;
+                Ok(conn)
+            }
+        }
+    }
+
+    /// Run the server side of the TLS handshake on an accepted TCP stream,
+    /// and create a connection secured by it
+    #[cfg(feature = "tls")]
+    async fn terminate_tls(
+        mut stream: TcpStream,
+        peer_addr: SocketAddr,
+        local_addr: SocketAddr,
+        preconnection: &Preconnection,
+        settings: crate::tls::ServerSettings,
+        sniffed: Option<crate::sniff::Sniffed>,
+    ) -> Result<Connection> {
+        let timeout = preconnection
+            .transport_properties()
+            .await
+            .connection_properties
+            .connection_timeout
+            .unwrap_or(Duration::from_secs(30));
+        let mut session = crate::tls::TlsSession::server(&settings)?;
+        let received = runtime::timeout(timeout, session.handshake(&mut stream))
+            .await
+            .map_err(|_| TransportServicesError::Timeout)??;
+
+        let mut conn = Self::stream_connection(peer_addr, local_addr, preconnection).await;
+        if let Some(sniffed) = sniffed {
+            conn.set_sniffed(sniffed).await?;
+        }
+        conn.set_tls_session(session, received).await;
+        conn.set_tcp_stream(stream).await;
+        Ok(conn)
+    }
+
+    /// Create the connection for an accepted TCP stream, before the stream
+    /// is attached
+    async fn stream_connection(
+        peer_addr: SocketAddr,
+        local_addr: SocketAddr,
+        preconnection: &Preconnection,
     ) -> Connection {
         let transport_properties = preconnection.transport_properties().await;
 
@@ -210,6 +1167,35 @@ impl Listener {
         };
 
         // Create connection with established state
+        let conn = Connection::new_with_data(
+            preconnection.clone(),
+            ConnectionState::Established,
+            Some(local_endpoint),
+            Some(remote_endpoint),
+            transport_properties,
+        );
+        preconnection.track_connection(&conn).await;
+        conn
+    }
+
+    /// Create a connection for a peer flow on a datagram socket
+    async fn create_connection_from_datagram_socket(
+        socket: Arc<UdpSocket>,
+        peer_addr: SocketAddr,
+        local_addr: SocketAddr,
+        preconnection: &Preconnection,
+    ) -> Connection {
+        let transport_properties = preconnection.transport_properties().await;
+
+        let local_endpoint = LocalEndpoint {
+            identifiers: vec![EndpointIdentifier::SocketAddress(local_addr)],
+        };
+
+        let remote_endpoint = RemoteEndpoint {
+            identifiers: vec![EndpointIdentifier::SocketAddress(peer_addr)],
+            protocol: None,
+        };
+
         let mut conn = Connection::new_with_data(
             preconnection.clone(),
             ConnectionState::Established,
@@ -217,9 +1203,9 @@ impl Listener {
             Some(remote_endpoint),
             transport_properties,
         );
+        preconnection.track_connection(&conn).await;
 
-        // Set the TCP stream
-        conn.set_tcp_stream(stream).await;
+        conn.set_udp_socket(socket).await;
 
         conn
     }
@@ -239,6 +1225,13 @@ impl Listener {
                     // Continue listening after non-fatal errors
                     eprintln!("Listener error: {e}");
                 }
+                Some(
+                    ListenerEvent::EstablishmentError { .. }
+                    | ListenerEvent::AddressAdded(_)
+                    | ListenerEvent::AddressRemoved(_),
+                ) => {
+                    // Failed attempts and address changes are reported through next_event
+                }
                 None => {
                     return Err(TransportServicesError::InvalidState(
                         "Listener closed".to_string(),
@@ -248,6 +1241,54 @@ impl Listener {
         }
     }
 
+    /// Incoming connections as a stream, which ends once the Listener stops
+    ///
+    /// Works with stream combinators, e.g. `for_each_concurrent` to bound
+    /// how many connections are handled at once.
+    pub fn incoming(&self) -> impl Stream<Item = Result<Connection>> + Send + 'static {
+        futures::stream::unfold(self.clone(), |listener| async move {
+            let connection = listener.accept().await.ok()?;
+            Some((Ok(connection), listener))
+        })
+    }
+
+    /// Handle each incoming connection on a task of its own until the
+    /// Listener stops
+    pub async fn serve<F, Fut>(&self, handler: F)
+    where
+        F: Fn(Connection) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        while let Ok(connection) = self.accept().await {
+            runtime::spawn(handler(connection));
+        }
+    }
+
+    /// Like `serve`, with at most `limit` handlers running at once
+    ///
+    /// Further connections are not accepted until a handler finishes; they
+    /// wait in the backlog or are refused by the connection limits.
+    pub async fn serve_with_limit<F, Fut>(&self, limit: usize, handler: F)
+    where
+        F: Fn(Connection) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let handlers = Arc::new(Semaphore::new(limit.max(1)));
+        loop {
+            let Ok(slot) = Arc::clone(&handlers).acquire_owned().await else {
+                break;
+            };
+            let Ok(connection) = self.accept().await else {
+                break;
+            };
+            let handled = handler(connection);
+            runtime::spawn(async move {
+                handled.await;
+                drop(slot);
+            });
+        }
+    }
+
     /// Get the next event without blocking
     pub async fn next_event(&self) -> Option<ListenerEvent> {
         let mut receiver = self.event_receiver.write().await;
@@ -272,19 +1313,170 @@ impl Listener {
         self.connection_limit.store(limit, Ordering::Relaxed);
     }
 
+    /// Replace the certificates and TLS settings of a Listener terminating TLS
+    ///
+    /// Takes effect for handshakes that start afterwards, e.g. to rotate
+    /// certificates without restarting; connections already accepted keep
+    /// their sessions. On error the current settings stay in effect.
+    pub fn reload_security(&self, parameters: &SecurityParameters) -> Result<()> {
+        #[cfg(feature = "tls")]
+        {
+            let mut tls_config = self.tls_config.lock().unwrap();
+            if tls_config.is_none() {
+                return Err(TransportServicesError::InvalidState(
+                    "Listener does not terminate TLS".to_string(),
+                ));
+            }
+            let config = crate::tls::server_config(parameters)?.ok_or_else(|| {
+                TransportServicesError::InvalidParameters(
+                    "Security parameters hold no server identities".to_string(),
+                )
+            })?;
+            *tls_config = Some(config);
+            Ok(())
+        }
+        #[cfg(not(feature = "tls"))]
+        {
+            let _ = parameters;
+            Err(TransportServicesError::NotSupported(
+                "Built without the tls feature".to_string(),
+            ))
+        }
+    }
+
+    /// Set the admission control policy for incoming connections
+    ///
+    /// Takes effect for the next connection attempt.
+    pub fn set_admission_policy(&self, policy: AdmissionPolicy) {
+        *self.admission_policy.lock().unwrap() = policy;
+    }
+
+    /// Peek at the start of incoming streams before delivering their
+    /// Connections, or stop with None
+    ///
+    /// What was peeked is available from `Connection::sniffed`, and may
+    /// choose the Connection's framers. The data is not consumed: the
+    /// Connection still receives it, and a TLS handshake still reads the
+    /// ClientHello.
+    pub fn set_protocol_sniffer(&self, sniffer: Option<ProtocolSniffer>) {
+        *self.sniffer.lock().unwrap() = sniffer;
+    }
+
+    /// Get the admission control policy for incoming connections
+    pub fn admission_policy(&self) -> AdmissionPolicy {
+        self.admission_policy.lock().unwrap().clone()
+    }
+
+    /// Get counters for admitted and rejected connection attempts
+    pub fn stats(&self) -> ListenerStats {
+        *self.stats.lock().unwrap()
+    }
+
+    /// Set whether datagrams from different peers are delivered on separate Connections
+    ///
+    /// Only applies to connectionless transports. When disabled, all peers share a
+    /// single Connection and replies go to the most recent sender. Defaults to enabled.
+    pub fn set_peer_demultiplexing(&self, enabled: bool) {
+        self.demultiplex_peers.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Set how long a per-peer datagram flow may stay idle before its Connection is closed
+    ///
+    /// A zero duration disables idle eviction.
+    pub fn set_peer_idle_timeout(&self, timeout: Duration) {
+        self.peer_idle_timeout_ms
+            .store(timeout.as_millis() as u64, Ordering::Relaxed);
+    }
+
     /// Get the local address the listener is bound to
+    ///
+    /// With several local endpoints this is the first; see local_addrs().
     pub async fn local_addr(&self) -> Option<SocketAddr> {
         let inner = self.inner.read().await;
-        inner.local_addr
+        inner.local_addrs.first().copied()
+    }
+
+    /// Get every address the listener is bound to, including the ports the
+    /// kernel chose for endpoints with port 0
+    pub async fn local_addrs(&self) -> Vec<SocketAddr> {
+        let inner = self.inner.read().await;
+        inner.local_addrs.clone()
     }
 
-    /// Get the preconnection this listener was created from
+    /// Remote Endpoints a peer can use to reach this listener
+    ///
+    /// Wildcard bindings are expanded to the addresses of the local
+    /// interfaces of the same family, so the result can be handed to a peer,
+    /// e.g. for rendezvous. IPv6 link-local addresses are left out, as they
+    /// are unusable without a scope.
+    pub async fn advertised_endpoints(&self) -> Vec<RemoteEndpoint> {
+        let local_addrs = self.local_addrs().await;
+        let interface_addrs = if local_addrs.iter().any(|addr| addr.ip().is_unspecified()) {
+            path_monitor::local_addresses().await
+        } else {
+            Vec::new()
+        };
+
+        let mut advertised = Vec::new();
+        for addr in local_addrs {
+            if !addr.ip().is_unspecified() {
+                advertised.push(addr);
+                continue;
+            }
+            let mut addrs = interface_addrs
+                .iter()
+                .filter(|ip| ip.is_ipv4() == addr.is_ipv4())
+                .filter(|ip| !is_ipv6_link_local(ip))
+                .map(|ip| SocketAddr::new(*ip, addr.port()))
+                .peekable();
+            if addrs.peek().is_none() {
+                // Without interface details, the peer can at least be local
+                let loopback = match addr.ip() {
+                    IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                    IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+                };
+                advertised.push(SocketAddr::new(loopback, addr.port()));
+            } else {
+                advertised.extend(addrs);
+            }
+        }
+
+        advertised.dedup();
+        advertised
+            .into_iter()
+            .map(|addr| RemoteEndpoint::builder().socket_address(addr).build())
+            .collect()
+    }
+
+    /// Get the snapshot of the preconnection this listener was created from
     pub async fn preconnection(&self) -> Preconnection {
         let inner = self.inner.read().await;
         inner.preconnection.clone()
     }
 }
 
+impl InterfaceBinding {
+    /// Bind a stream listener to one of the interface's addresses
+    fn bind(
+        &mut self,
+        ip: IpAddr,
+        interface_index: u32,
+        properties: &TransportProperties,
+    ) -> Result<(TcpListener, SocketAddr)> {
+        let bind_addr = match ip {
+            // Link-local addresses are only meaningful with the interface's scope
+            IpAddr::V6(v6) if is_ipv6_link_local(&ip) => {
+                SocketAddr::V6(SocketAddrV6::new(v6, self.port, 0, interface_index))
+            }
+            _ => SocketAddr::new(ip, self.port),
+        };
+        let listener = Listener::bind_stream_listener(bind_addr, properties)?;
+        let addr = listener.local_addr().map_err(TransportServicesError::Io)?;
+        self.port = addr.port();
+        Ok((listener, addr))
+    }
+}
+
 impl std::fmt::Debug for Listener {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Listener")
@@ -296,3 +1488,145 @@ impl std::fmt::Debug for Listener {
             .finish()
     }
 }
+
+/// Connections with data in their SYN a listening socket may hold before
+/// the kernel falls back to the regular handshake
+#[cfg(target_os = "linux")]
+const FAST_OPEN_QUEUE: libc::c_int = 256;
+
+/// Set on connections whose SYN carried data (linux/tcp.h)
+#[cfg(target_os = "linux")]
+const TCPI_OPT_SYN_DATA: u8 = 0x20;
+
+/// Accept data in the SYN (TCP Fast Open) on a listening socket
+///
+/// The kernel only does so when net.ipv4.tcp_fastopen enables the server side.
+#[cfg(target_os = "linux")]
+fn enable_fast_open(listener: &TcpListener) {
+    use std::os::fd::AsRawFd;
+
+    let ret = unsafe {
+        libc::setsockopt(
+            listener.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_FASTOPEN,
+            &FAST_OPEN_QUEUE as *const _ as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret != 0 {
+        log::debug!(
+            "Failed to enable TCP Fast Open: {}",
+            std::io::Error::last_os_error()
+        );
+        return;
+    }
+
+    // Keep each SYN's headers, which give the length of the data it carried
+    let save_syn: libc::c_int = 1;
+    let ret = unsafe {
+        libc::setsockopt(
+            listener.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_SAVE_SYN,
+            &save_syn as *const _ as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret != 0 {
+        log::debug!(
+            "Failed to save SYN headers: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn enable_fast_open(_listener: &TcpListener) {}
+
+/// Length of the data an accepted stream's SYN carried, if it carried any
+///
+/// Read from the SYN's headers, which the listening socket saves.
+#[cfg(target_os = "linux")]
+fn syn_data_length(stream: &TcpStream) -> Option<usize> {
+    use std::os::fd::AsRawFd;
+
+    let mut info: libc::tcp_info = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::tcp_info>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_INFO,
+            &mut info as *mut _ as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret != 0 || info.tcpi_options & TCPI_OPT_SYN_DATA == 0 {
+        return None;
+    }
+
+    let mut headers = [0u8; 512];
+    let mut len = headers.len() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_SAVED_SYN,
+            headers.as_mut_ptr() as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret != 0 {
+        return None;
+    }
+    syn_payload_length(&headers[..len as usize]).filter(|&length| length > 0)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn syn_data_length(_stream: &TcpStream) -> Option<usize> {
+    None
+}
+
+/// Payload length of a packet from its IP and TCP headers
+fn syn_payload_length(headers: &[u8]) -> Option<usize> {
+    let (total, tcp_offset) = match headers.first()? >> 4 {
+        4 => {
+            let total = u16::from_be_bytes([*headers.get(2)?, *headers.get(3)?]) as usize;
+            (total, usize::from(headers[0] & 0x0f) * 4)
+        }
+        // A SYN with extension headers before TCP is not expected
+        6 if *headers.get(6)? == libc::IPPROTO_TCP as u8 => {
+            let payload = u16::from_be_bytes([*headers.get(4)?, *headers.get(5)?]) as usize;
+            (40 + payload, 40)
+        }
+        _ => return None,
+    };
+    let tcp_header = usize::from(headers.get(tcp_offset + 12)? >> 4) * 4;
+    total.checked_sub(tcp_offset + tcp_header)
+}
+
+/// Take the `length` bytes an accepted stream's SYN carried
+///
+/// Anything the client sent after its SYN stays queued as ordinary data.
+#[cfg(target_os = "linux")]
+fn take_syn_data(stream: &TcpStream, length: usize) -> Option<Vec<u8>> {
+    // Read the socket directly: the runtime may not have seen it readable yet
+    use std::io::Read;
+    let socket = socket2::SockRef::from(stream);
+    let mut data = vec![0u8; length];
+    let mut taken = 0;
+    while taken < length {
+        match (&*socket).read(&mut data[taken..]) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => taken += n,
+        }
+    }
+    data.truncate(taken);
+    Some(data).filter(|data| !data.is_empty())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn take_syn_data(_stream: &TcpStream, _length: usize) -> Option<Vec<u8>> {
+    None
+}
@@ -0,0 +1,236 @@
+//! Memory accounting for connections
+//! Tracks the bytes each connection holds in receive buffers, queued sends
+//! and undelivered events, and enforces a process-wide budget on the total.
+
+use crate::connection_group::GroupMember;
+use crate::{runtime, Connection, ConnectionEvent};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+/// Bytes held by all connections
+static IN_USE: AtomicUsize = AtomicUsize::new(0);
+
+static BUDGET: Mutex<Option<MemoryBudget>> = Mutex::new(None);
+
+/// Live accounts, with what is needed to close their connection
+static ACCOUNTS: Mutex<Vec<(Weak<MemoryAccount>, GroupMember)>> = Mutex::new(Vec::new());
+
+/// What happens once connections hold more than the memory budget
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MemoryPressurePolicy {
+    /// Stop reading from the network until usage falls below the budget;
+    /// TCP peers are held back by flow control and datagrams are dropped
+    #[default]
+    Backpressure,
+    /// Abort the connection holding the most memory
+    CloseLargest,
+}
+
+/// Limit on the memory held by all connections together
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryBudget {
+    /// Bytes all connections may hold
+    pub limit: usize,
+    /// What to do when the limit is exceeded
+    pub policy: MemoryPressurePolicy,
+}
+
+impl MemoryBudget {
+    /// Create a budget that applies backpressure beyond `limit` bytes
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            policy: MemoryPressurePolicy::default(),
+        }
+    }
+
+    /// Set what happens when the limit is exceeded
+    pub fn with_policy(mut self, policy: MemoryPressurePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+/// Bytes a connection holds, by where they are held
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Received data not yet delivered as an event
+    pub receive_buffer: usize,
+    /// Messages held before establishment, for the peer, or in a batch
+    pub send_queue: usize,
+    /// Received message data in events the application has not taken
+    pub event_queue: usize,
+}
+
+impl MemoryUsage {
+    /// Total bytes held
+    pub fn total(&self) -> usize {
+        self.receive_buffer + self.send_queue + self.event_queue
+    }
+}
+
+/// Set the memory budget for all connections, or remove it with None
+pub fn set_memory_budget(budget: Option<MemoryBudget>) {
+    *BUDGET.lock().unwrap() = budget;
+    enforce();
+}
+
+/// The memory budget in force, if any
+pub fn memory_budget() -> Option<MemoryBudget> {
+    *BUDGET.lock().unwrap()
+}
+
+/// Bytes currently held by all connections
+pub fn memory_in_use() -> usize {
+    IN_USE.load(Ordering::Relaxed)
+}
+
+/// Whether reading should pause until the application catches up
+pub(crate) fn backpressure() -> bool {
+    memory_budget().is_some_and(|budget| {
+        budget.policy == MemoryPressurePolicy::Backpressure && memory_in_use() > budget.limit
+    })
+}
+
+/// Memory held by one connection, counted into the global total
+#[derive(Debug, Default)]
+pub(crate) struct MemoryAccount {
+    receive_buffer: AtomicUsize,
+    send_queue: AtomicUsize,
+    event_queue: AtomicUsize,
+    // Set once the connection is aborted to free memory
+    evicted: AtomicBool,
+}
+
+impl MemoryAccount {
+    /// Register the account of a new connection, which may be evicted
+    pub(crate) fn register(account: &Arc<Self>, member: GroupMember) {
+        let mut accounts = ACCOUNTS.lock().unwrap();
+        accounts.retain(|(account, _)| account.strong_count() > 0);
+        accounts.push((Arc::downgrade(account), member));
+    }
+
+    pub(crate) fn usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            receive_buffer: self.receive_buffer.load(Ordering::Relaxed),
+            send_queue: self.send_queue.load(Ordering::Relaxed),
+            event_queue: self.event_queue.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Record the bytes buffered for receiving and sending
+    pub(crate) fn set_buffered(&self, receive_buffer: usize, send_queue: usize) {
+        let grew = set(&self.receive_buffer, receive_buffer) | set(&self.send_queue, send_queue);
+        if grew {
+            enforce();
+        }
+    }
+
+    /// Count the data of an event queued for the application
+    pub(crate) fn event_queued(&self, event: &ConnectionEvent) {
+        let size = event_size(event);
+        if size > 0 {
+            self.event_queue.fetch_add(size, Ordering::Relaxed);
+            IN_USE.fetch_add(size, Ordering::Relaxed);
+            enforce();
+        }
+    }
+
+    /// Release the data of an event the application has taken
+    pub(crate) fn event_taken(&self, event: &ConnectionEvent) {
+        let size = event_size(event);
+        if size > 0 {
+            // Events queued before accounting began are not counted
+            let taken = self
+                .event_queue
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |queued| {
+                    Some(queued.saturating_sub(size))
+                })
+                .unwrap_or_else(|queued| queued)
+                .min(size);
+            IN_USE.fetch_sub(taken, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drop for MemoryAccount {
+    fn drop(&mut self) {
+        IN_USE.fetch_sub(self.usage().total(), Ordering::Relaxed);
+    }
+}
+
+/// Store a new value in a counter, updating the total; returns whether it grew
+fn set(counter: &AtomicUsize, value: usize) -> bool {
+    let previous = counter.swap(value, Ordering::Relaxed);
+    if value > previous {
+        IN_USE.fetch_add(value - previous, Ordering::Relaxed);
+    } else {
+        IN_USE.fetch_sub(previous - value, Ordering::Relaxed);
+    }
+    value > previous
+}
+
+/// Message data carried by an event
+fn event_size(event: &ConnectionEvent) -> usize {
+    match event {
+        ConnectionEvent::Received { message_data, .. }
+        | ConnectionEvent::ReceivedPartial { message_data, .. } => message_data.len(),
+        _ => 0,
+    }
+}
+
+/// Abort the largest connections until the rest fit the budget
+fn enforce() {
+    let Some(budget) = memory_budget() else {
+        return;
+    };
+    if budget.policy != MemoryPressurePolicy::CloseLargest || memory_in_use() <= budget.limit {
+        return;
+    }
+    // Aborting is async; without a runtime the budget is enforced later
+    let executor = runtime::executor();
+    if !executor.can_spawn() {
+        return;
+    }
+
+    let accounts = ACCOUNTS.lock().unwrap();
+    let live: Vec<_> = accounts
+        .iter()
+        .filter_map(|(account, member)| Some((account.upgrade()?, member)))
+        .collect();
+    // Evicted connections are already freeing their memory
+    let evicted: usize = live
+        .iter()
+        .filter(|(account, _)| account.evicted.load(Ordering::Relaxed))
+        .map(|(account, _)| account.usage().total())
+        .sum();
+    let mut remaining = memory_in_use().saturating_sub(evicted);
+    let mut candidates: Vec<_> = live
+        .iter()
+        .filter(|(account, _)| !account.evicted.load(Ordering::Relaxed))
+        .collect();
+    candidates.sort_by_key(|(account, _)| std::cmp::Reverse(account.usage().total()));
+
+    for (account, member) in candidates {
+        if remaining <= budget.limit {
+            break;
+        }
+        let Some(connection) = Connection::from_group_member(member) else {
+            continue;
+        };
+        let usage = account.usage().total();
+        if usage == 0 {
+            break;
+        }
+        account.evicted.store(true, Ordering::Relaxed);
+        remaining = remaining.saturating_sub(usage);
+        log::debug!("Aborting connection holding {usage} bytes over the memory budget");
+        executor.spawn(Box::pin(async move {
+            let _ = connection
+                .abort_internal(format!(
+                    "Connection aborted: holding {usage} bytes exceeds the memory budget"
+                ))
+                .await;
+        }));
+    }
+}
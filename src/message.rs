@@ -2,7 +2,9 @@
 //! Based on RFC 9622 Section 9.1 (Messages and Framers)
 
 use crate::{LocalEndpoint, MessageCapacityProfile, MessageProperties, RemoteEndpoint};
-use std::sync::Arc;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 
@@ -12,6 +14,13 @@ pub struct Message {
     /// The actual data payload
     data: Vec<u8>,
 
+    /// Payload segments for scatter/gather messages; when non-empty these
+    /// replace `data`, which is left empty
+    segments: Vec<Bytes>,
+
+    /// Contiguous copy of `segments`, built on first call to `data()`
+    flattened: OnceLock<Vec<u8>>,
+
     /// Properties specific to this message
     properties: MessageProperties,
 
@@ -32,7 +41,10 @@ pub struct SendContext {
     /// Expiry time for the message
     pub expiry: Option<Instant>,
 
-    /// Whether to bundle this message with others
+    /// Whether to bundle this message with the messages sent after it
+    ///
+    /// Bundled messages are held and written together with the next message
+    /// that is not bundled, or when a batch ends or the connection closes.
     pub bundle: bool,
 
     /// Event notifier for send completion
@@ -60,6 +72,8 @@ impl Message {
     pub fn new(data: Vec<u8>) -> Self {
         Self {
             data,
+            segments: Vec::new(),
+            flattened: OnceLock::new(),
             properties: MessageProperties::default(),
             id: None,
             end_of_message: true,
@@ -77,24 +91,97 @@ impl Message {
         Self::new(s.as_bytes().to_vec())
     }
 
+    /// Create a message holding `value` encoded as one CBOR data item
+    #[cfg(feature = "cbor")]
+    pub fn from_cbor<T: serde::Serialize + ?Sized>(value: &T) -> crate::Result<Self> {
+        let mut data = Vec::new();
+        ciborium::into_writer(value, &mut data).map_err(|e| {
+            crate::TransportServicesError::InvalidParameters(format!("Cannot encode as CBOR: {e}"))
+        })?;
+        Ok(Self::new(data))
+    }
+
+    /// Decode the message data as one CBOR data item
+    ///
+    /// Fails if the data is not well-formed CBOR, does not match `T`, or
+    /// holds anything after the item.
+    #[cfg(feature = "cbor")]
+    pub fn to_cbor<T: serde::de::DeserializeOwned>(&self) -> crate::Result<T> {
+        let mut data = self.data();
+        let value = ciborium::from_reader(&mut data).map_err(|e| {
+            crate::TransportServicesError::ReceiveFailed(format!("Invalid CBOR message: {e}"))
+        })?;
+        if !data.is_empty() {
+            return Err(crate::TransportServicesError::ReceiveFailed(format!(
+                "Invalid CBOR message: {} bytes follow the data item",
+                data.len()
+            )));
+        }
+        Ok(value)
+    }
+
+    /// Create a new message from multiple buffers (e.g. a header and a body)
+    ///
+    /// The segments are kept as-is and written with vectored I/O, so no
+    /// concatenation happens on the send path.
+    pub fn from_segments(segments: Vec<Bytes>) -> Self {
+        let mut message = Self::new(Vec::new());
+        message.segments = segments.into_iter().filter(|s| !s.is_empty()).collect();
+        message
+    }
+
     /// Get the message data
+    ///
+    /// For messages created with `from_segments` this concatenates the
+    /// segments on first access; use `segments()` to avoid the copy.
     pub fn data(&self) -> &[u8] {
-        &self.data
+        match self.segments.len() {
+            0 => &self.data,
+            1 => &self.segments[0],
+            _ => self.flattened.get_or_init(|| self.segments.concat()),
+        }
     }
 
     /// Get mutable access to the message data
+    ///
+    /// A segmented message is flattened into a single buffer first.
     pub fn data_mut(&mut self) -> &mut Vec<u8> {
+        if !self.segments.is_empty() {
+            self.data = self.segments.concat();
+            self.segments.clear();
+            self.flattened = OnceLock::new();
+        }
         &mut self.data
     }
 
+    /// Get the message payload as a list of buffers
+    ///
+    /// Segmented messages return their segments without copying; a
+    /// contiguous message is returned as a single segment.
+    pub fn segments(&self) -> Vec<Bytes> {
+        if self.segments.is_empty() {
+            if self.data.is_empty() {
+                Vec::new()
+            } else {
+                vec![Bytes::copy_from_slice(&self.data)]
+            }
+        } else {
+            self.segments.clone()
+        }
+    }
+
     /// Get the message length
     pub fn len(&self) -> usize {
-        self.data.len()
+        if self.segments.is_empty() {
+            self.data.len()
+        } else {
+            self.segments.iter().map(|s| s.len()).sum()
+        }
     }
 
     /// Check if the message is empty
     pub fn is_empty(&self) -> bool {
-        self.data.is_empty()
+        self.len() == 0
     }
 
     /// Set message properties
@@ -119,6 +206,18 @@ impl Message {
         self
     }
 
+    /// Set the time allowed for writing the message to the network
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.properties.deadline = Some(deadline);
+        self
+    }
+
+    /// Set the IP TTL or hop limit of the datagram carrying the message
+    pub fn with_hop_limit(mut self, hop_limit: u8) -> Self {
+        self.properties.hop_limit = Some(hop_limit);
+        self
+    }
+
     /// Set message priority
     pub fn with_priority(mut self, priority: i32) -> Self {
         self.properties.priority = Some(priority);
@@ -272,6 +371,18 @@ impl MessageBuilder {
         self
     }
 
+    /// Set the time allowed for writing the message to the network
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.message = self.message.with_deadline(deadline);
+        self
+    }
+
+    /// Set the IP TTL or hop limit of the datagram carrying the message
+    pub fn hop_limit(mut self, hop_limit: u8) -> Self {
+        self.message = self.message.with_hop_limit(hop_limit);
+        self
+    }
+
     /// Set message priority
     pub fn priority(mut self, priority: i32) -> Self {
         self.message = self.message.with_priority(priority);
@@ -348,6 +459,55 @@ impl MessageBuilder {
     }
 }
 
+impl From<MessageBuilder> for Message {
+    fn from(builder: MessageBuilder) -> Self {
+        builder.build()
+    }
+}
+
+impl From<&str> for Message {
+    fn from(s: &str) -> Self {
+        Self::from_string(s)
+    }
+}
+
+impl From<String> for Message {
+    fn from(s: String) -> Self {
+        Self::new(s.into_bytes())
+    }
+}
+
+impl From<&[u8]> for Message {
+    fn from(data: &[u8]) -> Self {
+        Self::from_bytes(data)
+    }
+}
+
+impl<const N: usize> From<&[u8; N]> for Message {
+    fn from(data: &[u8; N]) -> Self {
+        Self::from_bytes(data)
+    }
+}
+
+impl From<Vec<u8>> for Message {
+    fn from(data: Vec<u8>) -> Self {
+        Self::new(data)
+    }
+}
+
+impl From<Bytes> for Message {
+    fn from(data: Bytes) -> Self {
+        Self::from_segments(vec![data])
+    }
+}
+
+/// Data paired with the Message Properties to send it with
+impl<T: Into<Message>> From<(T, MessageProperties)> for Message {
+    fn from((data, properties): (T, MessageProperties)) -> Self {
+        data.into().with_properties(properties)
+    }
+}
+
 /// Context information about a received message
 /// RFC Section 9.1.1
 #[derive(Debug, Clone)]
@@ -372,6 +532,30 @@ pub struct MessageContext {
 
     /// Reception timestamp from the network interface
     pub interface_timestamp: Option<Instant>,
+
+    /// IP traffic class (IPv4 TOS) byte the message arrived with, if the
+    /// platform reports it; the upper six bits are the DSCP
+    pub traffic_class: Option<u8>,
+
+    /// Properties of the received message as signalled by the peer
+    pub message_properties: ReceivedMessageProperties,
+}
+
+/// Message Properties of a received message
+/// RFC Section 9.3.2.1: MessageContext.GetProperties()
+#[derive(Debug, Clone, Default)]
+pub struct ReceivedMessageProperties {
+    /// Whether the peer marked this message as Final
+    pub final_message: bool,
+
+    /// Whether the message was delivered in order, if known
+    pub ordered: Option<bool>,
+
+    /// Stream the message arrived on for multistreaming protocols (SCTP, QUIC)
+    pub stream_id: Option<u64>,
+
+    /// Metadata attached by Message Framers while parsing the message
+    pub framer_metadata: HashMap<String, Vec<u8>>,
 }
 
 impl MessageContext {
@@ -385,6 +569,8 @@ impl MessageContext {
             ecn: None,
             early_data: false,
             interface_timestamp: None,
+            traffic_class: None,
+            message_properties: ReceivedMessageProperties::default(),
         }
     }
 
@@ -406,11 +592,70 @@ impl MessageContext {
         self
     }
 
+    /// Set the reception timestamp from the network interface
+    pub fn with_interface_timestamp(mut self, timestamp: Instant) -> Self {
+        self.interface_timestamp = Some(timestamp);
+        self
+    }
+
+    /// Set the received traffic class byte, and the ECN marking it carries
+    pub fn with_traffic_class(mut self, traffic_class: u8) -> Self {
+        self.traffic_class = Some(traffic_class);
+        self.ecn = Some(match traffic_class & 0b11 {
+            0b00 => EcnMarking::NotEct,
+            0b10 => EcnMarking::Ect0,
+            0b01 => EcnMarking::Ect1,
+            _ => EcnMarking::Ce,
+        });
+        self
+    }
+
+    /// DSCP of the received message, if its traffic class is known
+    pub fn dscp(&self) -> Option<u8> {
+        self.traffic_class.map(|traffic_class| traffic_class >> 2)
+    }
+
     /// Mark as early data
     pub fn as_early_data(mut self) -> Self {
         self.early_data = true;
         self
     }
+
+    /// Mark the received message as Final
+    pub fn with_final(mut self, is_final: bool) -> Self {
+        self.message_properties.final_message = is_final;
+        self
+    }
+
+    /// Set whether the message was delivered in order
+    pub fn with_ordered(mut self, ordered: bool) -> Self {
+        self.message_properties.ordered = Some(ordered);
+        self
+    }
+
+    /// Set the stream the message arrived on
+    pub fn with_stream_id(mut self, stream_id: u64) -> Self {
+        self.message_properties.stream_id = Some(stream_id);
+        self
+    }
+
+    /// Attach framer metadata to the message
+    pub fn with_framer_metadata(mut self, key: impl Into<String>, value: Vec<u8>) -> Self {
+        self.message_properties
+            .framer_metadata
+            .insert(key.into(), value);
+        self
+    }
+
+    /// Check whether the peer marked the received message as Final
+    pub fn is_final(&self) -> bool {
+        self.message_properties.final_message
+    }
+
+    /// Get the properties of the received message
+    pub fn message_properties(&self) -> &ReceivedMessageProperties {
+        &self.message_properties
+    }
 }
 
 impl Default for MessageContext {
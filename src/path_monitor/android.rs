@@ -1,15 +1,28 @@
 //! Android platform implementation using JNI
 //!
-//! Uses ConnectivityManager for monitoring network changes.
+//! Uses ConnectivityManager for monitoring network changes. Interfaces are
+//! listed from the networks ConnectivityManager knows, and the Java class
+//! `com.transport_services.android.NetworkMonitorSupport` (shipped in
+//! `bindings/android`) registers the network callbacks that report changes.
 
 use super::*;
 use jni::{
-    objects::{GlobalRef, JObject, JValue},
+    objects::{GlobalRef, JByteArray, JClass, JObject, JObjectArray, JString, JValue},
+    sys::jboolean,
     JNIEnv, JavaVM,
 };
 use std::collections::HashMap;
+use std::ffi::CString;
+use std::net::{Ipv4Addr, Ipv6Addr};
 use std::sync::{Arc, Mutex, OnceLock};
 
+// android.net.NetworkCapabilities constants
+const TRANSPORT_CELLULAR: i32 = 0;
+const TRANSPORT_WIFI: i32 = 1;
+const TRANSPORT_ETHERNET: i32 = 3;
+const TRANSPORT_VPN: i32 = 4;
+const NET_CAPABILITY_NOT_METERED: i32 = 11;
+
 static STATE: OnceLock<Arc<Mutex<State>>> = OnceLock::new();
 
 struct State {
@@ -17,6 +30,8 @@ struct State {
     current_interfaces: Vec<Interface>,
     next_watcher_id: usize,
     java_support: Option<JavaSupport>,
+    // Last default network reported, to skip repeated callbacks
+    default_network: Option<String>,
 }
 
 struct JavaSupport {
@@ -52,19 +67,7 @@ impl Drop for AndroidWatchHandle {
 
 impl PlatformMonitor for AndroidMonitor {
     fn list_interfaces(&self) -> Result<Vec<Interface>, Error> {
-        // Get JVM and context from android_context
-        let (vm_ptr, _context_ptr) = android_context()
-            .ok_or_else(|| Error::PlatformError("Android context not set".into()))?;
-
-        let jvm = unsafe { JavaVM::from_raw(vm_ptr as *mut jni::sys::JavaVM) }
-            .map_err(|e| Error::PlatformError(format!("Failed to get JavaVM: {:?}", e)))?;
-
-        let mut env = jvm
-            .attach_current_thread()
-            .map_err(|e| Error::PlatformError(format!("Failed to attach thread: {:?}", e)))?;
-
-        // Call into Java to get network interfaces
-        list_interfaces_jni(&mut env)
+        get_current_interfaces()
     }
 
     fn start_watching(
@@ -77,6 +80,7 @@ impl PlatformMonitor for AndroidMonitor {
                 current_interfaces: Vec::new(),
                 next_watcher_id: 1,
                 java_support: None,
+                default_network: None,
             }))
         });
 
@@ -106,10 +110,142 @@ impl PlatformMonitor for AndroidMonitor {
     }
 }
 
-fn list_interfaces_jni(_env: &mut JNIEnv) -> Result<Vec<Interface>, Error> {
-    // This would call into Java code to get the list of network interfaces
-    // For now, return an empty list as this requires Java-side implementation
-    Ok(Vec::new())
+/// List the interfaces of the networks ConnectivityManager knows
+fn list_interfaces_jni(env: &mut JNIEnv, context: &JObject) -> Result<Vec<Interface>, Error> {
+    let interfaces = env
+        .with_local_frame(32, |env| {
+            let service = env.new_string("connectivity")?;
+            let manager = env
+                .call_method(
+                    context,
+                    "getSystemService",
+                    "(Ljava/lang/String;)Ljava/lang/Object;",
+                    &[(&service).into()],
+                )?
+                .l()?;
+            let networks = JObjectArray::from(
+                env.call_method(&manager, "getAllNetworks", "()[Landroid/net/Network;", &[])?
+                    .l()?,
+            );
+
+            let mut interfaces: Vec<Interface> = Vec::new();
+            for i in 0..env.get_array_length(&networks)? {
+                let network = env.get_object_array_element(&networks, i)?;
+                let interface =
+                    env.with_local_frame(32, |env| network_interface(env, &manager, &network))?;
+                // Stacked networks may share an interface; report it once
+                if let Some(interface) = interface {
+                    if !interfaces.iter().any(|known| known.name == interface.name) {
+                        interfaces.push(interface);
+                    }
+                }
+                env.delete_local_ref(network)?;
+            }
+            Ok::<_, jni::errors::Error>(interfaces)
+        })
+        .map_err(|e| {
+            // A Java exception stays pending until cleared
+            let _ = env.exception_clear();
+            Error::PlatformError(format!("Failed to list networks: {:?}", e))
+        })?;
+    Ok(interfaces)
+}
+
+/// Describe the interface of one network, None if it has no link
+fn network_interface(
+    env: &mut JNIEnv,
+    manager: &JObject,
+    network: &JObject,
+) -> jni::errors::Result<Option<Interface>> {
+    let link = env
+        .call_method(
+            manager,
+            "getLinkProperties",
+            "(Landroid/net/Network;)Landroid/net/LinkProperties;",
+            &[network.into()],
+        )?
+        .l()?;
+    if link.is_null() {
+        // The network was lost since it was listed
+        return Ok(None);
+    }
+    let name = env
+        .call_method(&link, "getInterfaceName", "()Ljava/lang/String;", &[])?
+        .l()?;
+    if name.is_null() {
+        return Ok(None);
+    }
+    let name: String = env.get_string(&JString::from(name))?.into();
+
+    let addresses = env
+        .call_method(&link, "getLinkAddresses", "()Ljava/util/List;", &[])?
+        .l()?;
+    let mut ips = Vec::new();
+    for i in 0..env.call_method(&addresses, "size", "()I", &[])?.i()? {
+        let link_address = env
+            .call_method(
+                &addresses,
+                "get",
+                "(I)Ljava/lang/Object;",
+                &[JValue::Int(i)],
+            )?
+            .l()?;
+        let address = env
+            .call_method(&link_address, "getAddress", "()Ljava/net/InetAddress;", &[])?
+            .l()?;
+        let bytes = JByteArray::from(env.call_method(&address, "getAddress", "()[B", &[])?.l()?);
+        let bytes = env.convert_byte_array(&bytes)?;
+        if let Ok(octets) = <[u8; 4]>::try_from(bytes.as_slice()) {
+            ips.push(IpAddr::V4(Ipv4Addr::from(octets)));
+        } else if let Ok(octets) = <[u8; 16]>::try_from(bytes.as_slice()) {
+            ips.push(IpAddr::V6(Ipv6Addr::from(octets)));
+        }
+    }
+
+    let capabilities = env
+        .call_method(
+            manager,
+            "getNetworkCapabilities",
+            "(Landroid/net/Network;)Landroid/net/NetworkCapabilities;",
+            &[network.into()],
+        )?
+        .l()?;
+    let (interface_type, is_expensive) = if capabilities.is_null() {
+        ("unknown".to_string(), false)
+    } else {
+        let mut has = |method: &str, value: i32| {
+            env.call_method(&capabilities, method, "(I)Z", &[JValue::Int(value)])
+                .and_then(|result| result.z())
+        };
+        // A VPN also reports the transports it runs over, so check it first
+        let interface_type = if has("hasTransport", TRANSPORT_VPN)? {
+            "vpn"
+        } else if has("hasTransport", TRANSPORT_WIFI)? {
+            "wifi"
+        } else if has("hasTransport", TRANSPORT_CELLULAR)? {
+            "cellular"
+        } else if has("hasTransport", TRANSPORT_ETHERNET)? {
+            "ethernet"
+        } else {
+            "unknown"
+        };
+        let metered = !has("hasCapability", NET_CAPABILITY_NOT_METERED)?;
+        (interface_type.to_string(), metered)
+    };
+
+    let index = CString::new(name.as_str())
+        .map(|c_name| unsafe { libc::if_nametoindex(c_name.as_ptr()) })
+        .unwrap_or(0);
+
+    Ok(Some(Interface {
+        name,
+        index,
+        ips,
+        // ConnectivityManager only lists connected networks
+        status: Status::Up,
+        interface_type,
+        is_expensive,
+    }))
 }
 
 fn start_java_watching(state: &mut State) -> Result<(), Error> {
@@ -141,15 +277,13 @@ fn start_java_watching(state: &mut State) -> Result<(), Error> {
             .new_global_ref(support_object)
             .map_err(|e| Error::PlatformError(format!("Failed to create global ref: {:?}", e)))?;
 
-        // Start watching with callback pointer
-        let callback_ptr = transport_services_network_changed as *const () as jni::sys::jlong;
-        env.call_method(
-            &global_ref,
-            "startNetworkWatch",
-            "(J)V",
-            &[JValue::Long(callback_ptr)],
-        )
-        .map_err(|e| Error::PlatformError(format!("Failed to start watch: {:?}", e)))?;
+        // Register the network callbacks, which call back into
+        // nativeNetworksChanged and nativeDefaultNetworkChanged
+        env.call_method(&global_ref, "startNetworkWatch", "()V", &[])
+            .map_err(|e| {
+                let _ = env.exception_clear();
+                Error::PlatformError(format!("Failed to start watch: {:?}", e))
+            })?;
 
         global_ref
     };
@@ -174,17 +308,81 @@ fn stop_java_watching(java_support: &JavaSupport) -> Result<(), Error> {
     Ok(())
 }
 
-/// Called from Java when network interfaces change
+/// Called when network interfaces change, to list them again
 #[no_mangle]
 pub extern "C" fn transport_services_network_changed() {
+    if let Ok(list) = get_current_interfaces() {
+        interfaces_changed(list);
+    }
+}
+
+/// Called by NetworkMonitorSupport when a network appears, is lost, or
+/// changes its capabilities (e.g. metered) or link properties (e.g. addresses)
+#[no_mangle]
+pub extern "system" fn Java_com_transport_1services_android_NetworkMonitorSupport_nativeNetworksChanged<
+    'local,
+>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+) {
+    let Some(context) = android_context_ref() else {
+        return;
+    };
+    match list_interfaces_jni(&mut env, context.as_obj()) {
+        Ok(list) => interfaces_changed(list),
+        Err(e) => log::debug!("Failed to list interfaces after a network change: {e}"),
+    }
+}
+
+/// Called by NetworkMonitorSupport when the default network changes
+///
+/// `interface_name` is null once no network is available.
+#[no_mangle]
+pub extern "system" fn Java_com_transport_1services_android_NetworkMonitorSupport_nativeDefaultNetworkChanged<
+    'local,
+>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    interface_name: JString<'local>,
+    metered: jboolean,
+    vpn: jboolean,
+) {
+    let description = if interface_name.is_null() {
+        "No default network".to_string()
+    } else {
+        let name: String = match env.get_string(&interface_name) {
+            Ok(name) => name.into(),
+            Err(_) => return,
+        };
+        format!(
+            "Default network changed to {name} (metered: {}, vpn: {})",
+            metered != 0,
+            vpn != 0
+        )
+    };
+    default_network_changed(description);
+}
+
+/// Report a new default network to the watchers, once per change
+fn default_network_changed(description: String) {
     let Some(state_ref) = STATE.get() else {
         return;
     };
+    let mut state = state_ref.lock().unwrap();
+    if state.default_network.as_ref() == Some(&description) {
+        return;
+    }
+    state.default_network = Some(description.clone());
+    let event = ChangeEvent::PathChanged { description };
+    for callback in state.watchers.values() {
+        callback(event.clone());
+    }
+}
 
-    // Get new interface list
-    let new_list = match get_current_interfaces() {
-        Ok(list) => list,
-        Err(_) => return,
+/// Report the differences to a new interface list to the watchers
+fn interfaces_changed(new_list: Vec<Interface>) {
+    let Some(state_ref) = STATE.get() else {
+        return;
     };
 
     let mut state = state_ref.lock().unwrap();
@@ -235,7 +433,9 @@ pub extern "C" fn transport_services_network_changed() {
 
 fn get_current_interfaces() -> Result<Vec<Interface>, Error> {
     let (vm_ptr, _) =
-        android_context().ok_or_else(|| Error::PlatformError("No Android context".into()))?;
+        android_context().ok_or_else(|| Error::PlatformError("Android context not set".into()))?;
+    let context = android_context_ref()
+        .ok_or_else(|| Error::PlatformError("Android context not set".into()))?;
 
     let jvm = unsafe { JavaVM::from_raw(vm_ptr as *mut jni::sys::JavaVM) }
         .map_err(|e| Error::PlatformError(format!("Failed to get JavaVM: {:?}", e)))?;
@@ -244,7 +444,7 @@ fn get_current_interfaces() -> Result<Vec<Interface>, Error> {
         .attach_current_thread()
         .map_err(|e| Error::PlatformError(format!("Failed to attach thread: {:?}", e)))?;
 
-    list_interfaces_jni(&mut env)
+    list_interfaces_jni(&mut env, context.as_obj())
 }
 
 fn interfaces_equal(a: &Interface, b: &Interface) -> bool {
@@ -325,6 +525,12 @@ fn android_context() -> Option<(*mut std::ffi::c_void, *mut std::ffi::c_void)> {
     None
 }
 
+/// The Android Context, kept alive while it is used
+fn android_context_ref() -> Option<GlobalRef> {
+    let ctx = ANDROID_CONTEXT.get()?.lock().unwrap();
+    ctx.as_ref().map(|android_ctx| android_ctx.context.clone())
+}
+
 pub fn create_platform_impl() -> Result<Box<dyn PlatformMonitor + Send + Sync>, Error> {
     Ok(Box::new(AndroidMonitor {
         _phantom: std::marker::PhantomData,
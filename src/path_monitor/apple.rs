@@ -1,13 +1,16 @@
 //! Apple platform implementation using direct Network.framework FFI
 //!
 //! Uses direct C bindings to Network.framework for monitoring network path changes.
+//! The latest nw_path is also kept as a snapshot, which refines interface types
+//! and costs and describes the path of each connection.
 
 use super::*;
 use libc::{c_void, freeifaddrs, getifaddrs, if_nametoindex, ifaddrs, AF_INET, AF_INET6};
 use std::collections::HashMap;
 use std::ffi::{CStr, CString};
 use std::ptr;
-use std::sync::Arc;
+use std::sync::{Arc, Condvar, OnceLock};
+use std::time::Duration;
 
 // Include network_sys as a submodule
 #[path = "network_sys.rs"]
@@ -16,6 +19,112 @@ use network_sys::*;
 
 type PathChangeCallback = Box<dyn Fn(ChangeEvent) + Send + 'static>;
 
+/// How long a first lookup waits for Network.framework to report the path
+const FIRST_PATH_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// The current path as last reported by Network.framework
+#[derive(Debug, Clone)]
+struct PathSnapshot {
+    status: Status,
+    is_expensive: bool,
+    is_constrained: bool,
+    has_dns: bool,
+    /// Name and type of the interfaces the path uses, in order of preference
+    interfaces: Vec<(String, Option<String>)>,
+}
+
+/// Latest path snapshot, and a signal for the first one
+static SNAPSHOT: (Mutex<Option<PathSnapshot>>, Condvar) = (Mutex::new(None), Condvar::new());
+
+/// Record what Network.framework reports about a path
+unsafe fn record_path(path: nw_path_t) {
+    let mut interfaces = Vec::new();
+    enumerate_interfaces(path, |interface| {
+        let name = nw_interface_get_name(interface);
+        if name.is_null() {
+            return;
+        }
+        let name = CStr::from_ptr(name).to_string_lossy().to_string();
+        let interface_type = match nw_interface_get_type(interface) {
+            NW_INTERFACE_TYPE_WIFI => Some("wifi".to_string()),
+            NW_INTERFACE_TYPE_CELLULAR => Some("cellular".to_string()),
+            NW_INTERFACE_TYPE_WIRED => Some("ethernet".to_string()),
+            NW_INTERFACE_TYPE_LOOPBACK => Some("loopback".to_string()),
+            // e.g. VPN tunnels, better named by the interface
+            _ => None,
+        };
+        interfaces.push((name, interface_type));
+    });
+
+    let snapshot = PathSnapshot {
+        status: match nw_path_get_status(path) {
+            NW_PATH_STATUS_SATISFIED => Status::Up,
+            NW_PATH_STATUS_UNSATISFIED => Status::Down,
+            _ => Status::Unknown,
+        },
+        is_expensive: nw_path_is_expensive(path),
+        is_constrained: nw_path_is_constrained(path),
+        has_dns: nw_path_has_dns(path),
+        interfaces,
+    };
+    let (current, first) = &SNAPSHOT;
+    *current.lock().unwrap() = Some(snapshot);
+    first.notify_all();
+}
+
+/// The latest path snapshot, starting a monitor that keeps it current on first use
+fn current_path() -> Option<PathSnapshot> {
+    static MONITOR: OnceLock<()> = OnceLock::new();
+    MONITOR.get_or_init(|| unsafe {
+        let monitor = nw_path_monitor_create();
+        if monitor.is_null() {
+            return;
+        }
+        let queue_name = CString::new("com.tapsrs.pathsnapshot").unwrap();
+        let queue = dispatch_queue_create(queue_name.as_ptr(), ptr::null());
+        if queue.is_null() {
+            nw_release(monitor as *mut c_void);
+            return;
+        }
+        let update_block = PathUpdateBlock::new(|path: nw_path_t| record_path(path));
+        nw_path_monitor_set_update_handler(monitor, update_block.as_ptr());
+        nw_path_monitor_set_queue(monitor, queue);
+        nw_path_monitor_start(monitor);
+        // The monitor runs for the rest of the process
+        std::mem::forget(update_block);
+    });
+
+    let (current, first) = &SNAPSHOT;
+    let guard = current.lock().unwrap();
+    let (guard, _) = first
+        .wait_timeout_while(guard, FIRST_PATH_TIMEOUT, |snapshot| snapshot.is_none())
+        .unwrap();
+    guard.clone()
+}
+
+/// Path details for connections through `interface`, None if the system
+/// path does not use it
+pub(crate) fn system_path(interface: &str) -> Option<SystemPath> {
+    let snapshot = current_path()?;
+    let position = snapshot
+        .interfaces
+        .iter()
+        .position(|(name, _)| name == interface)?;
+    // Cost and constraints describe the path's preferred interface
+    let preferred = position == 0;
+    Some(SystemPath {
+        interface_type: snapshot.interfaces[position].1.clone(),
+        status: snapshot.status,
+        is_expensive: if preferred {
+            snapshot.is_expensive
+        } else {
+            detect_expensive_interface(interface)
+        },
+        is_constrained: preferred && snapshot.is_constrained,
+        has_dns: snapshot.has_dns,
+    })
+}
+
 pub struct AppleDirectMonitor {
     monitor: Option<nw_path_monitor_t>,
     queue: Option<dispatch_queue_t>,
@@ -99,14 +208,22 @@ impl PlatformMonitor for AppleDirectMonitor {
                 current = ifa.ifa_next;
             }
 
-            // Enhance with Network.framework info if we have a monitor
-            if let Some(_monitor) = self.monitor {
-                // We can't easily get the current path synchronously without blocks
-                // This is a limitation of the API design
-                // In practice, the callback mechanism would keep this updated
+            freeifaddrs(ifap);
+
+            // Network.framework knows the types and cost better than the names
+            if let Some(snapshot) = SNAPSHOT.0.lock().unwrap().clone() {
+                for (position, (name, interface_type)) in snapshot.interfaces.iter().enumerate() {
+                    if let Some(interface) = interfaces_map.get_mut(name) {
+                        if let Some(interface_type) = interface_type {
+                            interface.interface_type = interface_type.clone();
+                        }
+                        if position == 0 {
+                            interface.is_expensive = snapshot.is_expensive;
+                        }
+                    }
+                }
             }
 
-            freeifaddrs(ifap);
             Ok(interfaces_map.into_values().collect())
         }
     }
@@ -138,6 +255,8 @@ impl PlatformMonitor for AppleDirectMonitor {
             // Set up path update handler
             let callback_holder = self.callback_holder.as_ref().unwrap().clone();
             let update_block = PathUpdateBlock::new(move |path: nw_path_t| {
+                record_path(path);
+
                 // Get path status
                 let status = nw_path_get_status(path);
                 let is_expensive = nw_path_is_expensive(path);
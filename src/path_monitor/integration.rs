@@ -57,11 +57,11 @@ impl PathAwareConnectionManager {
                             log::warn!("Interface {} removed", interface.name);
                             // TODO: Check if this affects the connection
                         }
-                        ChangeEvent::Modified { old, new } => {
-                            if old.status == Status::Up && new.status == Status::Down {
-                                log::warn!("Interface {} went down", new.name);
-                                // TODO: Trigger failover if this is the current path
-                            }
+                        ChangeEvent::Modified { old, new }
+                            if old.status == Status::Up && new.status == Status::Down =>
+                        {
+                            log::warn!("Interface {} went down", new.name);
+                            // TODO: Trigger failover if this is the current path
                         }
                         _ => {}
                     }
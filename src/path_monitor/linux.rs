@@ -171,9 +171,8 @@ pub fn create_platform_impl() -> Result<Box<dyn PlatformMonitor + Send + Sync>,
     );
 
     let (conn, handle, _) = runtime.block_on(async {
-        new_connection().map_err(|e| {
-            Error::PlatformError(format!("Failed to create netlink connection: {e}"))
-        })
+        new_connection()
+            .map_err(|e| Error::PlatformError(format!("Failed to create netlink connection: {e}")))
     })?;
 
     // Spawn connection handler
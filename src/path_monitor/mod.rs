@@ -3,10 +3,13 @@
 //! This module provides cross-platform network interface and path monitoring,
 //! allowing applications to track network changes and adapt connections accordingly.
 
+use std::collections::HashSet;
 use std::net::IpAddr;
 #[cfg(target_vendor = "apple")]
 use std::net::{Ipv4Addr, Ipv6Addr};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
 
 // Platform-specific implementations
 #[cfg(target_vendor = "apple")]
@@ -34,7 +37,7 @@ pub struct Interface {
     pub is_expensive: bool,     // e.g., metered like cellular
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Status {
     Up,
     Down,
@@ -81,6 +84,225 @@ impl NetworkMonitor {
     }
 }
 
+/// Find the interface that owns a local address
+///
+/// Platform monitors may drive their own event loop, so the lookup runs on a
+/// separate thread rather than inside the caller's runtime.
+pub(crate) async fn interface_for_address(address: IpAddr) -> Option<Interface> {
+    let address = address.to_canonical();
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+
+    std::thread::spawn(move || {
+        let interface = match NetworkMonitor::new().and_then(|m| m.list_interfaces()) {
+            Ok(interfaces) => interfaces
+                .into_iter()
+                .find(|iface| iface.ips.contains(&address)),
+            Err(e) => {
+                log::debug!("Interface lookup failed: {e}");
+                None
+            }
+        };
+        let _ = sender.send(interface);
+    });
+
+    receiver.await.ok().flatten()
+}
+
+/// Local addresses assigned to interfaces that are not down
+///
+/// None if the interfaces cannot be listed, so watchers can tell an unknown
+/// path from a lost one.
+pub(crate) type AvailableAddresses = Option<HashSet<IpAddr>>;
+
+/// How often the shared address watch lists interfaces without a change
+/// notification, as not every platform monitor reports changes
+const ADDRESS_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+static ADDRESS_WATCH: Mutex<Option<watch::Sender<AvailableAddresses>>> = Mutex::new(None);
+
+/// Follow the local addresses assigned to interfaces that are not down
+///
+/// All receivers share one monitor thread, which lists the interfaces when
+/// the platform monitor reports a change and at least every
+/// ADDRESS_POLL_INTERVAL. The thread stops once every receiver is dropped.
+pub(crate) fn watch_addresses() -> watch::Receiver<AvailableAddresses> {
+    let mut shared = ADDRESS_WATCH.lock().unwrap();
+    if let Some(sender) = shared.as_ref() {
+        return sender.subscribe();
+    }
+    let (sender, receiver) = watch::channel(None);
+    *shared = Some(sender.clone());
+
+    // Platform monitors may drive their own event loop, so the monitor lives
+    // on a separate thread rather than inside the caller's runtime
+    std::thread::spawn(move || run_address_watch(sender));
+    receiver
+}
+
+fn run_address_watch(sender: watch::Sender<AvailableAddresses>) {
+    let (wake, woken) = std::sync::mpsc::channel();
+    let monitor = NetworkMonitor::new();
+    let _handle = match &monitor {
+        Ok(monitor) => {
+            let wake = wake.clone();
+            Some(monitor.watch_changes(move |_| {
+                let _ = wake.send(());
+            }))
+        }
+        Err(e) => {
+            log::debug!("Cannot follow interface changes: {e}");
+            None
+        }
+    };
+
+    loop {
+        let addresses = match monitor.as_ref().map(|m| m.list_interfaces()) {
+            Ok(Ok(interfaces)) => Some(
+                interfaces
+                    .into_iter()
+                    .filter(|iface| iface.status != Status::Down)
+                    .flat_map(|iface| iface.ips)
+                    .collect(),
+            ),
+            Ok(Err(e)) => {
+                log::debug!("Interface lookup failed: {e}");
+                None
+            }
+            Err(_) => None,
+        };
+        sender.send_if_modified(|current| {
+            let changed = *current != addresses;
+            *current = addresses;
+            changed
+        });
+
+        let _ = woken.recv_timeout(ADDRESS_POLL_INTERVAL);
+
+        // Checked under the lock, so no receiver subscribes meanwhile
+        let mut shared = ADDRESS_WATCH.lock().unwrap();
+        if sender.receiver_count() == 0 {
+            *shared = None;
+            return;
+        }
+    }
+}
+
+/// What the platform reports about the system's path through an interface
+#[derive(Debug, Clone)]
+pub(crate) struct SystemPath {
+    pub(crate) interface_type: Option<String>,
+    pub(crate) status: Status,
+    pub(crate) is_expensive: bool,
+    pub(crate) is_constrained: bool,
+    pub(crate) has_dns: bool,
+}
+
+/// Describe the system's path through an interface
+///
+/// Only Apple platforms report paths (from Network.framework); elsewhere,
+/// and for interfaces the system path does not use, this is None.
+pub(crate) async fn system_path(interface: &str) -> Option<SystemPath> {
+    #[cfg(target_vendor = "apple")]
+    {
+        let interface = interface.to_string();
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        // The first lookup may wait for Network.framework to report the path
+        std::thread::spawn(move || {
+            let _ = sender.send(apple::system_path(&interface));
+        });
+        receiver.await.ok().flatten()
+    }
+    #[cfg(not(target_vendor = "apple"))]
+    {
+        let _ = interface;
+        None
+    }
+}
+
+/// Whether an address is an IPv6 link-local address (fe80::/10)
+pub(crate) fn is_ipv6_link_local(ip: &IpAddr) -> bool {
+    matches!(ip, IpAddr::V6(v6) if (v6.segments()[0] & 0xffc0) == 0xfe80)
+}
+
+/// Scope a link-local address to the zone given by `interface`, an
+/// interface name or index
+///
+/// Other addresses, and addresses already carrying a zone, are returned
+/// unchanged; a link-local address without one cannot be used.
+pub(crate) async fn scope_link_local(
+    addr: std::net::SocketAddr,
+    interface: Option<&str>,
+) -> crate::Result<std::net::SocketAddr> {
+    let std::net::SocketAddr::V6(mut v6) = addr else {
+        return Ok(addr);
+    };
+    if !is_ipv6_link_local(&addr.ip()) || v6.scope_id() != 0 {
+        return Ok(addr);
+    }
+    let Some(interface) = interface else {
+        return Err(crate::TransportServicesError::InvalidParameters(format!(
+            "Link-local address {} needs a zone, e.g. {}%en0 or an interface",
+            addr.ip(),
+            addr.ip()
+        )));
+    };
+    let index = match interface.parse::<u32>() {
+        Ok(index) => index,
+        Err(_) => interface_by_name(interface)
+            .await
+            .map(|interface| interface.index)
+            .ok_or_else(|| {
+                crate::TransportServicesError::InvalidParameters(format!(
+                    "No interface {interface} to scope {} to",
+                    addr.ip()
+                ))
+            })?,
+    };
+    v6.set_scope_id(index);
+    Ok(std::net::SocketAddr::V6(v6))
+}
+
+/// Find a local interface by name
+pub(crate) async fn interface_by_name(name: &str) -> Option<Interface> {
+    let name = name.to_string();
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+
+    std::thread::spawn(move || {
+        let interface = match NetworkMonitor::new().and_then(|m| m.list_interfaces()) {
+            Ok(interfaces) => interfaces.into_iter().find(|iface| iface.name == name),
+            Err(e) => {
+                log::debug!("Interface lookup failed: {e}");
+                None
+            }
+        };
+        let _ = sender.send(interface);
+    });
+
+    receiver.await.ok().flatten()
+}
+
+/// List the addresses of all local interfaces that are up
+pub(crate) async fn local_addresses() -> Vec<IpAddr> {
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+
+    std::thread::spawn(move || {
+        let addresses = match NetworkMonitor::new().and_then(|m| m.list_interfaces()) {
+            Ok(interfaces) => interfaces
+                .into_iter()
+                .filter(|iface| iface.status == Status::Up)
+                .flat_map(|iface| iface.ips)
+                .collect(),
+            Err(e) => {
+                log::debug!("Interface lookup failed: {e}");
+                Vec::new()
+            }
+        };
+        let _ = sender.send(addresses);
+    });
+
+    receiver.await.unwrap_or_default()
+}
+
 // Handle to stop monitoring (drops the watcher)
 pub struct MonitorHandle {
     _inner: PlatformHandle, // Platform-specific drop logic
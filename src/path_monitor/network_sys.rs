@@ -57,6 +57,7 @@ extern "C" {
     pub fn nw_path_get_status(path: nw_path_t) -> nw_path_status_t;
     pub fn nw_path_is_expensive(path: nw_path_t) -> bool;
     pub fn nw_path_is_constrained(path: nw_path_t) -> bool;
+    pub fn nw_path_has_dns(path: nw_path_t) -> bool;
     pub fn nw_path_uses_interface_type(
         path: nw_path_t,
         interface_type: nw_interface_type_t,
@@ -196,3 +197,51 @@ unsafe impl Send for PathUpdateBlock {}
 extern "C" {
     pub fn nw_path_monitor_set_update_handler(monitor: nw_path_monitor_t, handler: *mut c_void);
 }
+
+// Block passed to nw_path_enumerate_interfaces, which calls it synchronously,
+// so it can live on the stack and borrow its closure
+#[repr(C)]
+struct EnumerateBlock<'a> {
+    isa: *const c_void,
+    flags: c_int,
+    reserved: c_int,
+    invoke: unsafe extern "C" fn(*mut EnumerateBlock<'a>, nw_interface_t) -> bool,
+    descriptor: *const EnumerateDescriptor,
+    closure: &'a mut dyn FnMut(nw_interface_t),
+}
+
+#[repr(C)]
+struct EnumerateDescriptor {
+    reserved: c_ulong,
+    size: c_ulong,
+}
+
+static ENUMERATE_DESCRIPTOR: EnumerateDescriptor = EnumerateDescriptor {
+    reserved: 0,
+    size: mem::size_of::<EnumerateBlock<'static>>() as c_ulong,
+};
+
+unsafe extern "C" fn invoke_enumerate(
+    block: *mut EnumerateBlock<'_>,
+    interface: nw_interface_t,
+) -> bool {
+    ((*block).closure)(interface);
+    true
+}
+
+/// Call `f` with each interface the path uses, in order of preference
+///
+/// # Safety
+///
+/// `path` must be a valid path for the duration of the call.
+pub unsafe fn enumerate_interfaces(path: nw_path_t, mut f: impl FnMut(nw_interface_t)) {
+    let mut block = EnumerateBlock {
+        isa: &_NSConcreteStackBlock as *const _,
+        flags: 0,
+        reserved: 0,
+        invoke: invoke_enumerate,
+        descriptor: &ENUMERATE_DESCRIPTOR,
+        closure: &mut f,
+    };
+    nw_path_enumerate_interfaces(path, &mut block as *mut _ as dispatch_block_t);
+}
@@ -1,6 +1,7 @@
 //! Tests for the path monitor module
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
     use super::super::*;
     use std::sync::{Arc, Mutex};
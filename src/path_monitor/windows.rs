@@ -1,6 +1,8 @@
 //! Windows platform implementation using IP Helper API
 //!
-//! Uses NotifyUnicastIpAddressChange and GetAdaptersAddresses for monitoring.
+//! Uses NotifyUnicastIpAddressChange and GetAdaptersAddresses for monitoring,
+//! NotifyRouteChange2 to follow the default route, and the Network List
+//! Manager for connection cost and connectivity.
 
 use super::*;
 use std::collections::HashMap;
@@ -8,19 +10,29 @@ use std::ffi::c_void;
 use std::net::IpAddr;
 use std::sync::Mutex;
 
+use ::windows::core::Interface as _;
 use ::windows::Win32::Foundation::{
     BOOLEAN, ERROR_ADDRESS_NOT_ASSOCIATED, ERROR_BUFFER_OVERFLOW, ERROR_INVALID_PARAMETER,
     ERROR_NOT_ENOUGH_MEMORY, ERROR_NO_DATA, ERROR_SUCCESS, HANDLE, NO_ERROR, WIN32_ERROR,
 };
 use ::windows::Win32::NetworkManagement::IpHelper::{
-    CancelMibChangeNotify2, GetAdaptersAddresses, NotifyUnicastIpAddressChange,
-    GAA_FLAG_SKIP_ANYCAST, GAA_FLAG_SKIP_MULTICAST, IP_ADAPTER_ADDRESSES_LH, MIB_NOTIFICATION_TYPE,
-    MIB_UNICASTIPADDRESS_ROW,
+    CancelMibChangeNotify2, FreeMibTable, GetAdaptersAddresses, GetIpForwardTable2,
+    NotifyRouteChange2, NotifyUnicastIpAddressChange, GAA_FLAG_SKIP_ANYCAST,
+    GAA_FLAG_SKIP_MULTICAST, IP_ADAPTER_ADDRESSES_LH, MIB_IPFORWARD_ROW2, MIB_IPFORWARD_TABLE2,
+    MIB_NOTIFICATION_TYPE, MIB_UNICASTIPADDRESS_ROW,
 };
 use ::windows::Win32::NetworkManagement::Ndis::IfOperStatusDown;
+use ::windows::Win32::Networking::NetworkListManager::{
+    INetworkConnection, INetworkConnectionCost, INetworkListManager, NetworkListManager,
+    NLM_CONNECTION_COST_FIXED, NLM_CONNECTION_COST_OVERDATALIMIT, NLM_CONNECTION_COST_ROAMING,
+    NLM_CONNECTION_COST_VARIABLE, NLM_CONNECTIVITY_IPV4_INTERNET, NLM_CONNECTIVITY_IPV6_INTERNET,
+};
 use ::windows::Win32::Networking::WinSock::{
     AF_INET, AF_INET6, AF_UNSPEC, SOCKADDR_IN, SOCKADDR_IN6,
 };
+use ::windows::Win32::System::Com::{
+    CoCreateInstance, CoInitializeEx, CoUninitialize, CLSCTX_ALL, COINIT_MULTITHREADED,
+};
 
 // Interface type constants from Windows SDK
 const IF_TYPE_ETHERNET_CSMACD: u32 = 6;
@@ -29,10 +41,18 @@ const IF_TYPE_SOFTWARE_LOOPBACK: u32 = 24;
 const IF_TYPE_WWANPP: u32 = 243;
 const IF_TYPE_WWANPP2: u32 = 244;
 
+// Costs that make a connection expensive to use
+const EXPENSIVE_COSTS: i32 = NLM_CONNECTION_COST_FIXED.0
+    | NLM_CONNECTION_COST_VARIABLE.0
+    | NLM_CONNECTION_COST_ROAMING.0
+    | NLM_CONNECTION_COST_OVERDATALIMIT.0;
+
 /// State for tracking interface changes
 struct WatchState {
     /// The last known list of interfaces for diffing
     prev_interfaces: Vec<Interface>,
+    /// The last default route reported, for detecting transitions
+    default_route: Option<String>,
     /// User's callback wrapped for thread safety
     cb: Box<dyn Fn(ChangeEvent) + Send + 'static>,
 }
@@ -50,6 +70,7 @@ impl WindowsMonitor {
     /// List all network interfaces using GetAdaptersAddresses
     fn list_interfaces_internal() -> Result<Vec<Interface>, Error> {
         let mut interfaces = Vec::new();
+        let costs = connection_costs().unwrap_or_default();
 
         // Microsoft recommends a 15 KB initial buffer
         let start_size = 15 * 1024;
@@ -136,6 +157,14 @@ impl WindowsMonitor {
                     unicast_ptr = unicast.Next;
                 }
 
+                let interface_type = detect_interface_type(adapter.IfType);
+                // Adapters without a connection profile are costed by type
+                let is_expensive = match adapter.AdapterName.to_string() {
+                    Ok(adapter_name) => costs.get(&adapter_name.to_uppercase()).copied(),
+                    Err(_) => None,
+                }
+                .unwrap_or(interface_type == "cellular");
+
                 let interface = Interface {
                     name,
                     index: adapter.Ipv6IfIndex, // Use IPv6 index as it's more consistent
@@ -145,8 +174,8 @@ impl WindowsMonitor {
                     } else {
                         Status::Up
                     },
-                    interface_type: detect_interface_type(adapter.IfType),
-                    is_expensive: false, // TODO: Detect from connection profile API
+                    interface_type,
+                    is_expensive,
                 };
 
                 interfaces.push(interface);
@@ -173,6 +202,7 @@ impl PlatformMonitor for WindowsMonitor {
         // Create the watch state
         let state = Arc::new(Mutex::new(WatchState {
             prev_interfaces,
+            default_route: None,
             cb: callback,
         }));
 
@@ -182,9 +212,10 @@ impl PlatformMonitor for WindowsMonitor {
         // Store the state in self to keep it alive
         self.state = Some(state.clone());
 
-        let mut handle = HANDLE::default();
+        let mut handles = Vec::new();
 
         unsafe {
+            let mut handle = HANDLE::default();
             let res = NotifyUnicastIpAddressChange(
                 AF_UNSPEC,
                 Some(notif_callback),
@@ -192,34 +223,42 @@ impl PlatformMonitor for WindowsMonitor {
                 BOOLEAN(0), // Not initial notification
                 &mut handle,
             );
+            if res == NO_ERROR {
+                handles.push(handle);
+            }
 
-            match res {
-                NO_ERROR => {
-                    // Trigger an initial update to establish baseline
-                    if let Ok(new_list) = Self::list_interfaces_internal() {
-                        handle_notif(&mut state.lock().unwrap(), new_list);
-                    }
+            let mut handle = HANDLE::default();
+            let res = NotifyRouteChange2(
+                AF_UNSPEC,
+                Some(route_callback),
+                state_ptr,
+                BOOLEAN(0), // Not initial notification
+                &mut handle,
+            );
+            if res == NO_ERROR {
+                handles.push(handle);
+            }
+        }
 
-                    Box::new(WindowsWatchHandle {
-                        handle,
-                        _state: state,
-                    })
-                }
-                _ => {
-                    // Return a dummy handle that does nothing
-                    Box::new(WindowsWatchHandle {
-                        handle: HANDLE::default(),
-                        _state: state,
-                    })
-                }
+        // Trigger an initial update to establish baseline
+        {
+            let mut state = state.lock().unwrap();
+            if let Ok(new_list) = Self::list_interfaces_internal() {
+                handle_notif(&mut state, new_list);
             }
+            handle_default_route(&mut state);
         }
+
+        Box::new(WindowsWatchHandle {
+            handles,
+            _state: state,
+        })
     }
 }
 
 /// Handle for canceling the network change notifications
 struct WindowsWatchHandle {
-    handle: HANDLE,
+    handles: Vec<HANDLE>,
     _state: Arc<Mutex<WatchState>>, // Keep state alive
 }
 
@@ -227,9 +266,9 @@ unsafe impl Send for WindowsWatchHandle {}
 
 impl Drop for WindowsWatchHandle {
     fn drop(&mut self) {
-        unsafe {
-            if !self.handle.is_invalid() {
-                let _ = CancelMibChangeNotify2(self.handle);
+        for handle in &self.handles {
+            unsafe {
+                let _ = CancelMibChangeNotify2(*handle);
             }
         }
     }
@@ -255,6 +294,173 @@ unsafe extern "system" fn notif_callback(
     }
 }
 
+/// Callback invoked by Windows when a route is added, changed or deleted
+unsafe extern "system" fn route_callback(
+    ctx: *const c_void,
+    row: *const MIB_IPFORWARD_ROW2,
+    _notification_type: MIB_NOTIFICATION_TYPE,
+) {
+    if ctx.is_null() {
+        return;
+    }
+    // Only default routes matter; a null row means the table must be re-read
+    if !row.is_null() && (*row).DestinationPrefix.PrefixLength != 0 {
+        return;
+    }
+
+    let state_mutex = &*(ctx as *const Mutex<WatchState>);
+    if let Ok(mut state_guard) = state_mutex.lock() {
+        // A new default route usually comes with a new connection cost
+        if let Ok(new_list) = WindowsMonitor::list_interfaces_internal() {
+            handle_notif(&mut state_guard, new_list);
+        }
+        handle_default_route(&mut state_guard);
+    }
+}
+
+/// Report a transition of the default route as a path change
+fn handle_default_route(state: &mut WatchState) {
+    let description = describe_default_route(&state.prev_interfaces);
+    if state.default_route.as_ref() == Some(&description) {
+        return;
+    }
+    state.default_route = Some(description.clone());
+    (state.cb)(ChangeEvent::PathChanged { description });
+}
+
+/// Describe the preferred default route of each family and the connectivity
+fn describe_default_route(interfaces: &[Interface]) -> String {
+    let routes = default_routes();
+    let name_of = |index: u32| {
+        interfaces
+            .iter()
+            .find(|iface| iface.index == index)
+            .map(|iface| {
+                format!(
+                    "{} ({}, expensive: {})",
+                    iface.name, iface.interface_type, iface.is_expensive
+                )
+            })
+            .unwrap_or_else(|| format!("interface {index}"))
+    };
+    let ipv4 = routes.ipv4.map(name_of);
+    let ipv6 = routes.ipv6.map(name_of);
+
+    let internet = match connectivity() {
+        Some(true) => "internet",
+        Some(false) => "no internet",
+        None => "internet unknown",
+    };
+    match (ipv4, ipv6) {
+        (None, None) => format!("No default route ({internet})"),
+        (ipv4, ipv6) => format!(
+            "Default route changed (IPv4: {}, IPv6: {}, {internet})",
+            ipv4.as_deref().unwrap_or("none"),
+            ipv6.as_deref().unwrap_or("none")
+        ),
+    }
+}
+
+/// Interfaces of the default routes with the lowest metric
+#[derive(Default)]
+struct DefaultRoutes {
+    ipv4: Option<u32>,
+    ipv6: Option<u32>,
+}
+
+fn default_routes() -> DefaultRoutes {
+    let mut routes = DefaultRoutes::default();
+    let mut best: [Option<(u32, u32)>; 2] = [None, None];
+
+    unsafe {
+        let mut table: *mut MIB_IPFORWARD_TABLE2 = std::ptr::null_mut();
+        if GetIpForwardTable2(AF_UNSPEC, &mut table).is_err() || table.is_null() {
+            return routes;
+        }
+        let rows =
+            std::slice::from_raw_parts((*table).Table.as_ptr(), (*table).NumEntries as usize);
+        for row in rows {
+            if row.DestinationPrefix.PrefixLength != 0 {
+                continue;
+            }
+            let slot = match row.DestinationPrefix.Prefix.si_family {
+                AF_INET => 0,
+                AF_INET6 => 1,
+                _ => continue,
+            };
+            if best[slot].is_none_or(|(metric, _)| row.Metric < metric) {
+                best[slot] = Some((row.Metric, row.InterfaceIndex));
+            }
+        }
+        FreeMibTable(table as *const c_void);
+    }
+
+    routes.ipv4 = best[0].map(|(_, index)| index);
+    routes.ipv6 = best[1].map(|(_, index)| index);
+    routes
+}
+
+/// Run `f` with COM initialized on the calling thread
+fn with_com<T>(f: impl FnOnce() -> Option<T>) -> Option<T> {
+    unsafe {
+        // Fails if the thread already uses another apartment, which works too
+        let initialized = CoInitializeEx(None, COINIT_MULTITHREADED).is_ok();
+        let result = f();
+        if initialized {
+            CoUninitialize();
+        }
+        result
+    }
+}
+
+/// Whether each adapter's connection is expensive, keyed by the adapter GUID
+/// in the "{XXXXXXXX-...}" form of its adapter name
+fn connection_costs() -> Option<HashMap<String, bool>> {
+    with_com(|| unsafe {
+        let manager: INetworkListManager =
+            CoCreateInstance(&NetworkListManager, None, CLSCTX_ALL).ok()?;
+        let connections = manager.GetNetworkConnections().ok()?;
+
+        let mut costs = HashMap::new();
+        loop {
+            let mut next: [Option<INetworkConnection>; 1] = [None];
+            let mut fetched = 0;
+            if connections.Next(&mut next, Some(&mut fetched)).is_err() || fetched == 0 {
+                break;
+            }
+            let Some(connection) = next[0].take() else {
+                break;
+            };
+            let (Ok(adapter), Ok(cost)) = (
+                connection.GetAdapterId(),
+                connection
+                    .cast::<INetworkConnectionCost>()
+                    .and_then(|cost| cost.GetCost()),
+            ) else {
+                continue;
+            };
+            costs.insert(
+                format!("{{{adapter:?}}}"),
+                cost as i32 & EXPENSIVE_COSTS != 0,
+            );
+        }
+        Some(costs)
+    })
+}
+
+/// Whether the Network List Manager reports internet connectivity
+fn connectivity() -> Option<bool> {
+    with_com(|| unsafe {
+        let manager: INetworkListManager =
+            CoCreateInstance(&NetworkListManager, None, CLSCTX_ALL).ok()?;
+        let connectivity = manager.GetConnectivity().ok()?;
+        Some(
+            connectivity.0 & (NLM_CONNECTIVITY_IPV4_INTERNET.0 | NLM_CONNECTIVITY_IPV6_INTERNET.0)
+                != 0,
+        )
+    })
+}
+
 /// Handle a notification by comparing old and new interface lists
 fn handle_notif(state: &mut WatchState, new_interfaces: Vec<Interface>) {
     // Create maps for efficient comparison
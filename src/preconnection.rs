@@ -2,8 +2,19 @@
 //! Based on RFC 9622 Section 6 (Preestablishment Phase)
 
 use crate::{
-    Connection, EndpointIdentifier, Framer, FramerStack, Listener, LocalEndpoint, Message,
-    RemoteEndpoint, Result, SecurityParameters, TransportProperties, TransportServicesError,
+    context::{Candidate, CandidateSet, TransportServices},
+    path_monitor,
+    proxy::{ProxyConfig, ProxyTarget},
+    racing,
+    resolver::{ResolutionCache, ResolverConfig},
+    runtime,
+    selection::{
+        CandidateBranch, Decision, EndpointBranch, PruneReason, SelectionExplanation, StackBranch,
+    },
+    AdmissionPolicy, CommunicationDirection, Connection, EndpointIdentifier, Framer, FramerFactory,
+    FramerStack, Listener, LocalEndpoint, Message, Preference, PropertyConflict, Protocol,
+    RemoteEndpoint, Result, SecurityParameters, TransportProperties, TransportProperty,
+    TransportServicesError,
 };
 use std::sync::Arc;
 use std::time::Duration;
@@ -17,12 +28,26 @@ pub struct Preconnection {
     inner: Arc<RwLock<PreconnectionInner>>,
 }
 
+#[derive(Clone)]
 struct PreconnectionInner {
     local_endpoints: Vec<LocalEndpoint>,
     remote_endpoints: Vec<RemoteEndpoint>,
+    // Alias set of each remote endpoint; endpoints sharing one are aliases
+    // for the same host rather than alternatives
+    remote_alias_sets: Vec<usize>,
     transport_properties: TransportProperties,
     security_parameters: SecurityParameters,
-    framers: FramerStack,
+    framers: Vec<FramerFactory>,
+    resolution_cache: Arc<ResolutionCache>,
+    resolver_config: ResolverConfig,
+    context: Option<Arc<TransportServices>>,
+    proxy: Option<ProxyConfig>,
+    // TLS sessions resumed by the connections initiated from here
+    #[cfg(feature = "tls")]
+    tls_sessions: Option<Arc<crate::tls::ClientSessions>>,
+    // A snapshot taken for initiate, listen or rendezvous, which no longer
+    // accepts changes
+    frozen: bool,
 }
 
 impl Preconnection {
@@ -33,13 +58,22 @@ impl Preconnection {
         transport_properties: TransportProperties,
         security_parameters: SecurityParameters,
     ) -> Self {
+        warn_conflicts(&transport_properties);
         Self {
             inner: Arc::new(RwLock::new(PreconnectionInner {
                 local_endpoints,
+                remote_alias_sets: (0..remote_endpoints.len()).collect(),
                 remote_endpoints,
                 transport_properties,
                 security_parameters,
-                framers: FramerStack::new(),
+                framers: Vec::new(),
+                resolution_cache: Arc::new(ResolutionCache::new()),
+                resolver_config: ResolverConfig::default(),
+                context: None,
+                proxy: None,
+                #[cfg(feature = "tls")]
+                tls_sessions: None,
+                frozen: false,
             })),
         }
     }
@@ -64,35 +98,291 @@ impl Preconnection {
         )
     }
 
+    /// Create a Preconnection to the endpoint a URL names
+    ///
+    /// The URL is parsed as by `RemoteEndpoint::from_url`. Security is
+    /// enabled for the tls, dtls, https and quic schemes and disabled
+    /// otherwise, and udp and dtls prohibit reliability. The `alpn` query
+    /// parameter, comma-separated or repeated, sets the ALPN protocols
+    /// offered, as in `quic://example.com?alpn=h3`; other parameters are
+    /// rejected.
+    pub fn from_url(url: &str) -> Result<Self> {
+        let url = crate::url::parse(url)?;
+        let secure = matches!(
+            url.protocol,
+            Protocol::TLS | Protocol::DTLS | Protocol::QUIC
+        );
+
+        let mut alpn = Vec::new();
+        for (key, value) in &url.query {
+            match key.as_str() {
+                "alpn" => alpn.extend(
+                    value
+                        .split(',')
+                        .filter(|protocol| !protocol.is_empty())
+                        .map(str::to_string),
+                ),
+                _ => {
+                    return Err(TransportServicesError::InvalidParameters(format!(
+                        "Unknown URL parameter: {key}"
+                    )))
+                }
+            }
+        }
+        if !alpn.is_empty() && !secure {
+            return Err(TransportServicesError::InvalidParameters(
+                "ALPN needs a secure scheme such as tls or quic".to_string(),
+            ));
+        }
+
+        let mut security_parameters = if secure {
+            SecurityParameters::new()
+        } else {
+            SecurityParameters::new_disabled()
+        };
+        security_parameters.alpn = alpn;
+        let mut transport_properties = TransportProperties::default();
+        if matches!(url.protocol, Protocol::UDP | Protocol::DTLS) {
+            transport_properties.selection_properties.reliability = Preference::Prohibit;
+        }
+
+        Ok(Self::new(
+            vec![],
+            vec![url.endpoint],
+            transport_properties,
+            security_parameters,
+        ))
+    }
+
+    /// Take an immutable snapshot of the current configuration
+    /// RFC Section 7: a Preconnection is copied when it is used
+    ///
+    /// Initiate, Listen and Rendezvous each run on a snapshot, so changes
+    /// made afterwards do not reach the Connections and Listeners already
+    /// created. Changes to a snapshot itself fail with InvalidState. It
+    /// keeps sharing the resolution cache, context and TLS sessions of the
+    /// original.
+    pub async fn freeze(&self) -> Preconnection {
+        if self.is_frozen().await {
+            return self.clone();
+        }
+        // Create the TLS session store first, so every snapshot resumes
+        // the sessions of the others
+        #[cfg(feature = "tls")]
+        self.tls_sessions().await;
+        let inner = self.inner.read().await;
+        Self {
+            inner: Arc::new(RwLock::new(PreconnectionInner {
+                frozen: true,
+                ..inner.clone()
+            })),
+        }
+    }
+
+    /// Whether this Preconnection is a snapshot that refuses changes
+    pub async fn is_frozen(&self) -> bool {
+        self.inner.read().await.frozen
+    }
+
+    /// Copy the configuration into a new, independent Preconnection
+    ///
+    /// Use this to derive variants, e.g. with other transport properties;
+    /// a copy of a snapshot can be changed again. Unlike `clone()`, which
+    /// shares one configuration, changes to either do not affect the other.
+    pub async fn clone_config(&self) -> Preconnection {
+        let inner = self.inner.read().await;
+        Self {
+            inner: Arc::new(RwLock::new(PreconnectionInner {
+                frozen: false,
+                ..inner.clone()
+            })),
+        }
+    }
+
+    /// Lock the configuration for a change, unless this is a snapshot
+    async fn configure(&self) -> Result<tokio::sync::RwLockWriteGuard<'_, PreconnectionInner>> {
+        let inner = self.inner.write().await;
+        if inner.frozen {
+            return Err(TransportServicesError::InvalidState(
+                "Preconnection is a frozen snapshot; change a clone_config() copy instead"
+                    .to_string(),
+            ));
+        }
+        Ok(inner)
+    }
+
     /// Add a local endpoint
-    pub async fn add_local(&self, endpoint: LocalEndpoint) {
-        let mut inner = self.inner.write().await;
+    pub async fn add_local(&self, endpoint: LocalEndpoint) -> Result<()> {
+        let mut inner = self.configure().await?;
         inner.local_endpoints.push(endpoint);
+        Ok(())
     }
 
     /// Add a remote endpoint
-    pub async fn add_remote(&self, endpoint: RemoteEndpoint) {
-        let mut inner = self.inner.write().await;
+    ///
+    /// Endpoints added separately are alternatives, which may be different
+    /// hosts offering the same service.
+    pub async fn add_remote(&self, endpoint: RemoteEndpoint) -> Result<()> {
+        let mut inner = self.configure().await?;
+        let set = inner.next_alias_set();
         inner.remote_endpoints.push(endpoint);
+        inner.remote_alias_sets.push(set);
+        Ok(())
+    }
+
+    /// Add remote endpoints that are aliases for the same host
+    /// RFC Section 6.1.2: endpoint aliases
+    ///
+    /// An address that several aliases resolve to is attempted once, and a
+    /// connection to any alias is secured with the host name of the set, so
+    /// it is verified against, and resumes TLS sessions of, the same server.
+    pub async fn add_remote_associated(&self, endpoints: Vec<RemoteEndpoint>) -> Result<()> {
+        let mut inner = self.configure().await?;
+        let set = inner.next_alias_set();
+        for endpoint in endpoints {
+            inner.remote_endpoints.push(endpoint);
+            inner.remote_alias_sets.push(set);
+        }
+        Ok(())
+    }
+
+    /// Get the remote endpoints aliased with `endpoint`, excluding itself
+    pub async fn remote_aliases(&self, endpoint: &RemoteEndpoint) -> Vec<RemoteEndpoint> {
+        let inner = self.inner.read().await;
+        let Some(index) = inner.remote_endpoints.iter().position(|e| e == endpoint) else {
+            return Vec::new();
+        };
+        let set = inner.remote_alias_sets[index];
+        inner
+            .remote_endpoints
+            .iter()
+            .zip(&inner.remote_alias_sets)
+            .enumerate()
+            .filter(|(i, (_, s))| *i != index && **s == set)
+            .map(|(_, (alias, _))| alias.clone())
+            .collect()
     }
 
     /// Set transport properties
-    pub async fn set_transport_properties(&self, properties: TransportProperties) {
-        let mut inner = self.inner.write().await;
+    pub async fn set_transport_properties(&self, properties: TransportProperties) -> Result<()> {
+        let mut inner = self.configure().await?;
+        warn_conflicts(&properties);
         inner.transport_properties = properties;
+        Ok(())
+    }
+
+    /// Combinations of transport properties that no Connection can satisfy
+    ///
+    /// Initiate, Listen and Rendezvous fail with InvalidParameters while
+    /// any remain.
+    pub async fn property_conflicts(&self) -> Vec<PropertyConflict> {
+        self.inner.read().await.transport_properties.conflicts()
     }
 
     /// Set security parameters
-    pub async fn set_security_parameters(&self, parameters: SecurityParameters) {
-        let mut inner = self.inner.write().await;
+    pub async fn set_security_parameters(&self, parameters: SecurityParameters) -> Result<()> {
+        let mut inner = self.configure().await?;
         inner.security_parameters = parameters;
+        Ok(())
     }
 
     /// Add a Message Framer to this Preconnection
     /// RFC Section 9.1.2.1: Preconnection.AddFramer(framer)
-    pub async fn add_framer(&self, framer: Box<dyn Framer>) {
-        let mut inner = self.inner.write().await;
-        inner.framers.add_framer(framer);
+    ///
+    /// Each initiated Connection gets its own framer from the factory. The
+    /// first framer added is outermost, and its Start event runs first.
+    pub async fn add_framer<F>(&self, factory: F) -> Result<()>
+    where
+        F: Fn() -> Box<dyn Framer> + Send + Sync + 'static,
+    {
+        let mut inner = self.configure().await?;
+        inner.framers.push(Arc::new(factory));
+        Ok(())
+    }
+
+    /// Instantiate the framers for a new Connection
+    fn framer_stack(inner: &PreconnectionInner) -> FramerStack {
+        let mut stack = FramerStack::new();
+        for factory in &inner.framers {
+            stack.add_framer(factory());
+        }
+        stack
+    }
+
+    /// Share the process-wide resolution cache instead of a per-Preconnection one
+    pub async fn use_global_resolution_cache(&self) -> Result<()> {
+        self.set_resolution_cache(ResolutionCache::global()).await
+    }
+
+    /// Set the cache used to resolve hostnames for this Preconnection
+    pub async fn set_resolution_cache(&self, cache: Arc<ResolutionCache>) -> Result<()> {
+        let mut inner = self.configure().await?;
+        inner.resolution_cache = cache;
+        Ok(())
+    }
+
+    /// Configure the resolver used for endpoint resolution, including any
+    /// backend performing DoT, DoH or DNSSEC
+    pub async fn set_resolver_config(&self, config: ResolverConfig) -> Result<()> {
+        let mut inner = self.configure().await?;
+        inner.resolver_config = config;
+        Ok(())
+    }
+
+    /// Get the resolver configuration used for endpoint resolution
+    pub async fn resolver_config(&self) -> ResolverConfig {
+        let inner = self.inner.read().await;
+        inner.resolver_config.clone()
+    }
+
+    /// Attach this Preconnection to a shared context
+    ///
+    /// The context's resolution cache, resolver configuration and proxy, if
+    /// it has one, replace the Preconnection's own, and its candidate policy applies to every
+    /// connection initiated from now on.
+    pub async fn set_context(&self, context: Arc<TransportServices>) -> Result<()> {
+        let mut inner = self.configure().await?;
+        inner.resolution_cache = context.resolution_cache();
+        inner.resolver_config = context.resolver_config().clone();
+        if let Some(proxy) = context.proxy() {
+            inner.proxy = Some(proxy.clone());
+        }
+        inner.context = Some(context);
+        Ok(())
+    }
+
+    /// Get the context this Preconnection is attached to, if any
+    pub async fn context(&self) -> Option<Arc<TransportServices>> {
+        let inner = self.inner.read().await;
+        inner.context.clone()
+    }
+
+    /// Let the context, if any, shut down a connection created from here
+    pub(crate) async fn track_connection(&self, connection: &Connection) {
+        if let Some(context) = self.context().await {
+            context.registry().add_connection(connection.group_member());
+        }
+    }
+
+    /// Establish connections through a proxy, or directly with None
+    ///
+    /// Use `ProxyConfig::from_env()` to follow the system's proxy settings.
+    pub async fn set_proxy(&self, proxy: Option<ProxyConfig>) -> Result<()> {
+        let mut inner = self.configure().await?;
+        inner.proxy = proxy;
+        Ok(())
+    }
+
+    /// Get the proxy connections are established through, if any
+    pub async fn proxy(&self) -> Option<ProxyConfig> {
+        let inner = self.inner.read().await;
+        inner.proxy.clone()
+    }
+
+    /// Drop cached resolutions, e.g. after a path change
+    pub async fn flush_resolution_cache(&self) {
+        let inner = self.inner.read().await;
+        inner.resolution_cache.flush();
     }
 
     /// Initiate an active connection (client mode)
@@ -104,27 +394,27 @@ impl Preconnection {
     /// Initiate an active connection with timeout
     /// RFC Section 7.1: Connection := Preconnection.Initiate(timeout?)
     pub async fn initiate_with_timeout(&self, timeout: Option<Duration>) -> Result<Connection> {
-        let inner = self.inner.read().await;
+        runtime::require_executor()?;
+        let snapshot = self.freeze().await;
+        let inner = snapshot.inner.read().await;
 
-        // Validate that we have at least one remote endpoint
-        if inner.remote_endpoints.is_empty() {
-            return Err(TransportServicesError::InvalidParameters(
-                "No remote endpoints specified for initiate".to_string(),
-            ));
-        }
+        let mut explanation = SelectionExplanation::default();
+        let selected = Self::select(&inner, &mut explanation).await;
+        log::debug!("Candidate selection:\n{explanation}");
+        let (candidates, tunnel) = selected?;
 
         // Create the connection object
         let connection = Connection::new_with_data(
-            self.clone(),
+            snapshot.clone(),
             crate::ConnectionState::Establishing,
             inner.local_endpoints.first().cloned(),
             inner.remote_endpoints.first().cloned(),
             inner.transport_properties.clone(),
         );
-
-        // Extract remote endpoint information
-        let remote_endpoint = &inner.remote_endpoints[0];
-        let socket_addr = self.extract_socket_address(remote_endpoint)?;
+        if let Some(context) = &inner.context {
+            context.registry().add_connection(connection.group_member());
+        }
+        connection.set_framers(Self::framer_stack(&inner)).await?;
 
         // Get connection timeout from transport properties if not specified
         let connection_timeout = timeout.or(inner
@@ -136,9 +426,9 @@ impl Preconnection {
         let conn_clone = connection.clone();
 
         // Spawn the connection establishment task
-        tokio::spawn(async move {
+        runtime::spawn(async move {
             let _ = conn_clone
-                .establish_tcp(socket_addr, connection_timeout)
+                .establish_tcp(candidates, connection_timeout, tunnel)
                 .await;
         });
 
@@ -147,22 +437,42 @@ impl Preconnection {
 
     /// Initiate an active connection and send a message
     /// RFC Section 9.2.5: Send on Active Open: InitiateWithSend
-    pub async fn initiate_with_send(&self, message: Message) -> Result<Connection> {
-        let connection = self.initiate().await?;
-
-        // Queue the message to be sent once established
-        connection.send(message).await?;
-
-        Ok(connection)
+    pub async fn initiate_with_send(&self, message: impl Into<Message>) -> Result<Connection> {
+        self.initiate_with_send_timeout(message, None).await
     }
 
     /// Initiate an active connection with timeout and send a message
     /// RFC Section 9.2.5: Send on Active Open: InitiateWithSend
+    ///
+    /// Only safely replayable messages may be sent as 0-RTT data. Other
+    /// messages are sent after the handshake completes, unless zeroRttMsg is
+    /// required, in which case the call fails.
     pub async fn initiate_with_send_timeout(
         &self,
-        message: Message,
+        message: impl Into<Message>,
         timeout: Option<Duration>,
     ) -> Result<Connection> {
+        let message = message.into();
+        {
+            let inner = self.inner.read().await;
+            let selection = &inner.transport_properties.selection_properties;
+            if !message.properties().safely_replayable
+                && selection.zero_rtt_msg == Preference::Require
+            {
+                return Err(TransportServicesError::InvalidParameters(
+                    "Only safely replayable messages can be sent as 0-RTT data".to_string(),
+                ));
+            }
+            if selection.direction == CommunicationDirection::UnidirectionalReceive {
+                let mut conflicts = inner.transport_properties.conflicts();
+                conflicts.push(PropertyConflict::new(
+                    vec![TransportProperty::Direction],
+                    "InitiateWithSend sends on a receive-only connection",
+                ));
+                reject_conflicts(conflicts)?;
+            }
+        }
+
         let connection = self.initiate_with_timeout(timeout).await?;
 
         // Queue the message to be sent once established
@@ -171,14 +481,222 @@ impl Preconnection {
         Ok(connection)
     }
 
-    /// Extract a socket address from an endpoint
-    fn extract_socket_address(&self, endpoint: &RemoteEndpoint) -> Result<std::net::SocketAddr> {
+    /// Explain which protocol stacks and candidates initiate would race
+    ///
+    /// Endpoints are resolved as for initiate, but nothing is established
+    /// and the Preconnection stays open to changes. When initiate would fail
+    /// before racing, the explanation says why.
+    pub async fn explain_selection(&self) -> SelectionExplanation {
+        let inner = self.inner.read().await;
+        let mut explanation = SelectionExplanation::default();
+        let _ = Self::select(&inner, &mut explanation).await;
+        explanation
+    }
+
+    /// Choose the protocol stack and its candidates in racing order,
+    /// recording every decision in `explanation`
+    async fn select(
+        inner: &PreconnectionInner,
+        explanation: &mut SelectionExplanation,
+    ) -> Result<Selected> {
+        let selected = Self::gather_candidates(inner, explanation).await;
+        if let Err(e) = &selected {
+            explanation.failure = Some(e.to_string());
+        }
+        selected
+    }
+
+    async fn gather_candidates(
+        inner: &PreconnectionInner,
+        explanation: &mut SelectionExplanation,
+    ) -> Result<Selected> {
+        // Validate that we have at least one remote endpoint
+        if inner.remote_endpoints.is_empty() {
+            return Err(TransportServicesError::InvalidParameters(
+                "No remote endpoints specified for initiate".to_string(),
+            ));
+        }
+        reject_conflicts(inner.transport_properties.conflicts())?;
+
+        // Endpoints may name protocols this build cannot provide
+        let unavailable = unavailable_protocol(&inner.remote_endpoints);
+
+        // TCP is the only protocol available for initiate, and it cannot
+        // vary reliability per message
+        let tcp = if inner
+            .transport_properties
+            .selection_properties
+            .per_msg_reliability
+            == Preference::Require
+        {
+            Decision::Pruned(PruneReason::Property(TransportProperty::PerMsgReliability))
+        } else {
+            Decision::Selected { rank: 0 }
+        };
+        let tcp_selected = tcp.is_selected();
+        let mut others = vec![Protocol::QUIC, Protocol::SCTP, Protocol::UDP];
+        others.extend(unavailable.filter(|protocol| !others.contains(protocol)));
+        explanation.stacks = std::iter::once((Protocol::TCP, tcp))
+            .chain(
+                others
+                    .into_iter()
+                    .map(|protocol| (protocol, Decision::Pruned(PruneReason::Unavailable))),
+            )
+            .map(|(protocol, decision)| StackBranch {
+                protocol,
+                decision,
+                endpoints: Vec::new(),
+            })
+            .collect();
+
+        if let Some(protocol) = unavailable {
+            return Err(protocol_unavailable(protocol));
+        }
+        if !tcp_selected {
+            return Err(TransportServicesError::NotSupported(
+                "No available protocol supports per-message reliability".to_string(),
+            ));
+        }
+
+        // Through a proxy, the candidates are the proxy's addresses and the
+        // proxy resolves the first remote endpoint that is not bypassed
+        let tunnel = inner.proxy.as_ref().and_then(|proxy| {
+            inner.remote_endpoints.iter().find_map(|endpoint| {
+                let target = ProxyTarget::from_endpoint(endpoint)?;
+                (!proxy.bypasses(&target.host())).then(|| (proxy.clone(), target, endpoint))
+            })
+        });
+        let sources = match &tunnel {
+            Some((proxy, _, endpoint)) => vec![(proxy.endpoint(), (*endpoint).clone(), 0)],
+            None => inner
+                .remote_endpoints
+                .iter()
+                .zip(&inner.remote_alias_sets)
+                .map(|(endpoint, set)| (endpoint.clone(), endpoint.clone(), *set))
+                .collect(),
+        };
+        let tunnel = tunnel.map(|(proxy, target, _)| (proxy, target));
+        explanation.proxy = tunnel.as_ref().map(|(proxy, _)| proxy.endpoint());
+
+        // Gather candidate addresses from all remote endpoints; an address
+        // reached through several aliases is attempted once
+        let endpoints = &mut explanation.stacks[0].endpoints;
+        let mut origins = Vec::new();
+        let mut gathered = std::collections::HashSet::new();
+        let mut last_error = None;
+        for (resolved, remote_endpoint, set) in &sources {
+            let mut branch = EndpointBranch {
+                endpoint: remote_endpoint.clone(),
+                decision: Decision::Selected {
+                    rank: endpoints
+                        .iter()
+                        .filter(|e| e.decision.is_selected())
+                        .count(),
+                },
+                candidates: Vec::new(),
+            };
+            match Self::extract_socket_addresses(
+                &inner.resolution_cache,
+                &inner.resolver_config,
+                resolved,
+            )
+            .await
+            {
+                Ok(endpoint_addrs) => {
+                    let interface = endpoint_interface(resolved);
+                    for address in endpoint_addrs {
+                        // Kept candidates are ranked once the racing order is known
+                        let decision = if gathered.insert((address, *set)) {
+                            origins.push((address, remote_endpoint.clone()));
+                            Decision::Selected { rank: 0 }
+                        } else {
+                            Decision::Pruned(PruneReason::DuplicateAlias)
+                        };
+                        branch.candidates.push(CandidateBranch {
+                            address,
+                            interface: interface.clone(),
+                            decision,
+                        });
+                    }
+                }
+                Err(e) => {
+                    branch.decision =
+                        Decision::Pruned(PruneReason::ResolutionFailed(e.to_string()));
+                    last_error = Some(e);
+                }
+            }
+            endpoints.push(branch);
+        }
+        if origins.is_empty() {
+            return Err(last_error.unwrap_or_else(|| {
+                TransportServicesError::InvalidParameters(
+                    "No valid socket address could be extracted from endpoints".to_string(),
+                )
+            }));
+        }
+
+        // Order candidates for racing (RFC 8305)
+        let happy_eyeballs = inner
+            .transport_properties
+            .connection_properties
+            .happy_eyeballs;
+        let sorted = racing::sort_candidates(
+            origins.iter().map(|(address, _)| *address).collect(),
+            happy_eyeballs.address_family_preference,
+        );
+        let mut excluded = origins;
+        let mut candidates = Vec::new();
+        for address in sorted {
+            if let Some(i) = excluded.iter().position(|(a, _)| *a == address) {
+                candidates.push(excluded.remove(i));
+            }
+        }
+
+        // The context's policy may veto or reorder candidates
+        let matched = !candidates.is_empty();
+        if let (Some(context), true) = (&inner.context, matched) {
+            let set = CandidateSet::new(
+                candidates
+                    .into_iter()
+                    .map(|(address, endpoint)| Candidate { address, endpoint })
+                    .collect(),
+            );
+            candidates = context
+                .apply_candidate_policy(set)
+                .candidates
+                .into_iter()
+                .map(|candidate| (candidate.address, candidate.endpoint))
+                .collect();
+        }
+        rank_candidates(endpoints, &candidates, &excluded);
+        if !matched {
+            return Err(TransportServicesError::InvalidParameters(
+                "No candidate addresses match the address family preference".to_string(),
+            ));
+        }
+        if candidates.is_empty() {
+            return Err(TransportServicesError::EstablishmentFailed(
+                "All candidates were rejected by the context policy".to_string(),
+            ));
+        }
+
+        Ok((candidates, tunnel))
+    }
+
+    /// Extract candidate socket addresses from an endpoint
+    async fn extract_socket_addresses(
+        cache: &Arc<ResolutionCache>,
+        resolver_config: &ResolverConfig,
+        endpoint: &RemoteEndpoint,
+    ) -> Result<Vec<std::net::SocketAddr>> {
         use crate::EndpointIdentifier;
         use std::net::{IpAddr, SocketAddr};
 
         let mut ip_addr: Option<IpAddr> = None;
         let mut port: Option<u16> = None;
         let mut hostname: Option<String> = None;
+        let mut socket_addr: Option<SocketAddr> = None;
+        let mut interface: Option<&str> = None;
 
         // Extract components from identifiers
         for identifier in &endpoint.identifiers {
@@ -186,35 +704,26 @@ impl Preconnection {
                 EndpointIdentifier::IpAddress(addr) => ip_addr = Some(*addr),
                 EndpointIdentifier::Port(p) => port = Some(*p),
                 EndpointIdentifier::HostName(h) => hostname = Some(h.clone()),
-                EndpointIdentifier::SocketAddress(addr) => return Ok(*addr),
+                EndpointIdentifier::SocketAddress(addr) => socket_addr = Some(*addr),
+                EndpointIdentifier::Interface(name) => interface = Some(name.as_str()),
                 _ => {}
             }
         }
 
-        // Try to construct socket address
-        if let (Some(ip), Some(p)) = (ip_addr, port) {
-            return Ok(SocketAddr::new(ip, p));
+        // Try to construct socket address; link-local addresses are only
+        // reachable in the zone of an interface
+        let addr = match (socket_addr, ip_addr, port) {
+            (Some(addr), _, _) => Some(addr),
+            (None, Some(ip), Some(p)) => Some(SocketAddr::new(ip, p)),
+            _ => None,
+        };
+        if let Some(addr) = addr {
+            return Ok(vec![path_monitor::scope_link_local(addr, interface).await?]);
         }
 
         // Try hostname resolution
         if let (Some(host), Some(p)) = (hostname, port) {
-            // For now, use blocking resolver in a spawn_blocking task
-            // In production, we'd want to use trust-dns or similar async resolver
-            use std::net::ToSocketAddrs;
-            let addr_string = format!("{host}:{p}");
-
-            match addr_string.to_socket_addrs() {
-                Ok(mut addrs) => {
-                    if let Some(addr) = addrs.next() {
-                        return Ok(addr);
-                    }
-                }
-                Err(e) => {
-                    return Err(TransportServicesError::InvalidParameters(format!(
-                        "Failed to resolve hostname: {e}"
-                    )));
-                }
-            }
+            return cache.resolve_with(&host, p, resolver_config).await;
         }
 
         Err(TransportServicesError::InvalidParameters(
@@ -225,7 +734,23 @@ impl Preconnection {
     /// Listen for incoming connections (server mode)
     /// RFC Section 7.2
     pub async fn listen(&self) -> Result<Listener> {
-        let inner = self.inner.read().await;
+        self.listen_with_policy(AdmissionPolicy::default()).await
+    }
+
+    /// Listen, keeping at most `limit` accepted connections open at once
+    ///
+    /// Attempts beyond the limit are refused until connections close; the
+    /// Listener's `incoming` stream and `serve` methods then hand out
+    /// connections without further bookkeeping.
+    pub async fn listen_with_limit(&self, limit: usize) -> Result<Listener> {
+        self.listen_with_policy(AdmissionPolicy::new().with_max_concurrent_connections(limit))
+            .await
+    }
+
+    async fn listen_with_policy(&self, policy: AdmissionPolicy) -> Result<Listener> {
+        runtime::require_executor()?;
+        let snapshot = self.freeze().await;
+        let inner = snapshot.inner.read().await;
 
         // Validate that we have at least one local endpoint
         if inner.local_endpoints.is_empty() {
@@ -233,9 +758,15 @@ impl Preconnection {
                 "No local endpoints specified for listen".to_string(),
             ));
         }
+        reject_conflicts(inner.transport_properties.conflicts())?;
+        if let Some(protocol) = unavailable_protocol(&inner.remote_endpoints) {
+            return Err(protocol_unavailable(protocol));
+        }
 
         // Create and start the listener
-        let listener = Listener::new(self.clone());
+        drop(inner);
+        let listener = Listener::new(snapshot);
+        listener.set_admission_policy(policy);
         listener.start().await?;
 
         Ok(listener)
@@ -244,7 +775,9 @@ impl Preconnection {
     /// Rendezvous for peer-to-peer connections
     /// RFC Section 7.3
     pub async fn rendezvous(&self) -> Result<(Connection, Listener)> {
-        let inner = self.inner.read().await;
+        runtime::require_executor()?;
+        let snapshot = self.freeze().await;
+        let inner = snapshot.inner.read().await;
 
         // Validate that we have both local and remote endpoints
         if inner.local_endpoints.is_empty() {
@@ -257,23 +790,32 @@ impl Preconnection {
                 "No remote endpoints specified for rendezvous".to_string(),
             ));
         }
+        reject_conflicts(inner.transport_properties.conflicts())?;
 
         // Resolve endpoints to get all candidates
         drop(inner); // Release lock before calling resolve
-        let (local_candidates, remote_candidates) = self.resolve().await?;
+        let (local_candidates, remote_candidates) = snapshot.resolve().await?;
 
         // Create listener on local endpoints
-        let listener = Listener::new(self.clone());
+        let listener = Listener::new(snapshot.clone());
         listener.start().await?;
 
         // Create connection that will attempt to connect to remote endpoints
+        let transport_properties = snapshot.transport_properties().await;
+        let buffer_sizes = (
+            transport_properties.connection_properties.send_buffer_size,
+            transport_properties
+                .connection_properties
+                .receive_buffer_size,
+        );
         let connection = Connection::new_with_data(
-            self.clone(),
+            snapshot.clone(),
             crate::ConnectionState::Establishing,
             local_candidates.first().cloned(),
             remote_candidates.first().cloned(),
-            self.transport_properties().await,
+            transport_properties,
         );
+        snapshot.track_connection(&connection).await;
 
         // Get the listener's actual bound address
         let _listen_addr = listener.local_addr().await;
@@ -282,14 +824,14 @@ impl Preconnection {
         let conn_clone = connection.clone();
         let remote_endpoints = remote_candidates.clone();
 
-        tokio::spawn(async move {
+        runtime::spawn(async move {
             // Try to connect to each remote endpoint
             for remote in remote_endpoints {
-                if let Some(socket_addr) = extract_socket_addr(&remote) {
+                if let Some(socket_addr) = extract_socket_addr(&remote).await {
                     // Attempt connection with short timeout for rendezvous
-                    match tokio::time::timeout(
+                    match runtime::timeout(
                         Duration::from_secs(5),
-                        tokio::net::TcpStream::connect(socket_addr),
+                        crate::racing::connect(socket_addr, buffer_sizes),
                     )
                     .await
                     {
@@ -375,10 +917,11 @@ impl Preconnection {
                     });
 
                     if let Some(port) = port {
-                        use std::net::ToSocketAddrs;
-                        let addr_string = format!("{hostname}:{port}");
-
-                        if let Ok(addrs) = addr_string.to_socket_addrs() {
+                        if let Ok(addrs) = inner
+                            .resolution_cache
+                            .resolve_with(hostname, port, &inner.resolver_config)
+                            .await
+                        {
                             for addr in addrs {
                                 let mut new_identifiers = resolved.identifiers.clone();
                                 new_identifiers
@@ -423,28 +966,160 @@ impl Preconnection {
         let inner = self.inner.read().await;
         inner.transport_properties.clone()
     }
+
+    /// Get security parameters (for internal use)
+    pub(crate) async fn security_parameters(&self) -> SecurityParameters {
+        let inner = self.inner.read().await;
+        inner.security_parameters.clone()
+    }
+
+    /// Host name a connection to `endpoint` is secured with: its own, or
+    /// that of one of its aliases, in ASCII form
+    #[cfg(feature = "tls")]
+    pub(crate) async fn host_name(&self, endpoint: &RemoteEndpoint) -> Option<String> {
+        let host_name = |endpoint: &RemoteEndpoint| {
+            endpoint.identifiers.iter().find_map(|id| match id {
+                EndpointIdentifier::HostName(name) => {
+                    Some(crate::hostname::to_ascii(name).unwrap_or_else(|_| name.clone()))
+                }
+                _ => None,
+            })
+        };
+        match host_name(endpoint) {
+            Some(name) => Some(name),
+            None => self
+                .remote_aliases(endpoint)
+                .await
+                .iter()
+                .find_map(host_name),
+        }
+    }
+
+    /// TLS sessions shared by the connections initiated from here
+    #[cfg(feature = "tls")]
+    pub(crate) async fn tls_sessions(&self) -> Arc<crate::tls::ClientSessions> {
+        let mut inner = self.inner.write().await;
+        let capacity = inner
+            .security_parameters
+            .max_cached_sessions
+            .unwrap_or(crate::context::DEFAULT_MAX_CACHED_SESSIONS);
+        inner
+            .tls_sessions
+            .get_or_insert_with(|| Arc::new(crate::tls::ClientSessions::new(capacity)))
+            .clone()
+    }
+}
+
+impl PreconnectionInner {
+    fn next_alias_set(&self) -> usize {
+        self.remote_alias_sets.iter().max().map_or(0, |set| set + 1)
+    }
+}
+
+/// Candidates in racing order, and the proxy tunnel they are reached through
+type Selected = (
+    Vec<(std::net::SocketAddr, RemoteEndpoint)>,
+    Option<(ProxyConfig, ProxyTarget)>,
+);
+
+/// Interface an endpoint's addresses are scoped to, if it names one
+fn endpoint_interface(endpoint: &RemoteEndpoint) -> Option<String> {
+    endpoint.identifiers.iter().find_map(|id| match id {
+        EndpointIdentifier::Interface(name) => Some(name.clone()),
+        _ => None,
+    })
+}
+
+/// Rank the gathered candidates by their position in `ranked`; those not
+/// there were excluded by the address family preference when in `excluded`,
+/// and by the context policy otherwise
+fn rank_candidates(
+    endpoints: &mut [EndpointBranch],
+    ranked: &[(std::net::SocketAddr, RemoteEndpoint)],
+    excluded: &[(std::net::SocketAddr, RemoteEndpoint)],
+) {
+    let mut taken = vec![false; ranked.len()];
+    for endpoint in endpoints {
+        for candidate in &mut endpoint.candidates {
+            if candidate.decision == Decision::Pruned(PruneReason::DuplicateAlias) {
+                continue;
+            }
+            let origin = |(address, remote): &(std::net::SocketAddr, RemoteEndpoint)| {
+                *address == candidate.address && *remote == endpoint.endpoint
+            };
+            let rank = ranked
+                .iter()
+                .enumerate()
+                .position(|(i, entry)| !taken[i] && origin(entry));
+            candidate.decision = match rank {
+                Some(rank) => {
+                    taken[rank] = true;
+                    Decision::Selected { rank }
+                }
+                None if excluded.iter().any(origin) => Decision::Pruned(PruneReason::AddressFamily),
+                None => Decision::Pruned(PruneReason::ContextPolicy),
+            };
+        }
+    }
+}
+
+/// Log contradictory properties when they are set, long before they are used
+fn warn_conflicts(properties: &TransportProperties) {
+    for conflict in properties.conflicts() {
+        log::warn!("Conflicting transport properties: {conflict}");
+    }
+}
+
+/// Fail with every conflict listed, if there are any
+fn reject_conflicts(conflicts: Vec<PropertyConflict>) -> Result<()> {
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+    let conflicts: Vec<_> = conflicts.iter().map(ToString::to_string).collect();
+    Err(TransportServicesError::InvalidParameters(format!(
+        "Conflicting transport properties: {}",
+        conflicts.join("; ")
+    )))
+}
+
+/// The first protocol named by an endpoint that this build cannot provide
+fn unavailable_protocol(endpoints: &[RemoteEndpoint]) -> Option<Protocol> {
+    let capabilities = crate::capabilities();
+    endpoints
+        .iter()
+        .filter_map(|endpoint| endpoint.protocol)
+        .find(|protocol| !capabilities.supports(*protocol))
+}
+
+/// Error for a protocol this build cannot provide
+fn protocol_unavailable(protocol: Protocol) -> TransportServicesError {
+    TransportServicesError::NotSupported(format!("{protocol:?} is not available in this build"))
 }
 
 /// Helper function to extract socket address from remote endpoint
-fn extract_socket_addr(endpoint: &RemoteEndpoint) -> Option<std::net::SocketAddr> {
+async fn extract_socket_addr(endpoint: &RemoteEndpoint) -> Option<std::net::SocketAddr> {
     use std::net::{IpAddr, SocketAddr};
 
     let mut ip_addr: Option<IpAddr> = None;
     let mut port: Option<u16> = None;
+    let mut socket_addr: Option<SocketAddr> = None;
+    let mut interface: Option<&str> = None;
 
     for identifier in &endpoint.identifiers {
         match identifier {
             EndpointIdentifier::IpAddress(addr) => ip_addr = Some(*addr),
             EndpointIdentifier::Port(p) => port = Some(*p),
-            EndpointIdentifier::SocketAddress(addr) => return Some(*addr),
+            EndpointIdentifier::SocketAddress(addr) => socket_addr = Some(*addr),
+            EndpointIdentifier::Interface(name) => interface = Some(name.as_str()),
             _ => {}
         }
     }
 
     // Try to construct socket address from IP and port
-    if let (Some(ip), Some(p)) = (ip_addr, port) {
-        return Some(SocketAddr::new(ip, p));
-    }
-
-    None
+    let addr = match (socket_addr, ip_addr, port) {
+        (Some(addr), _, _) => addr,
+        (None, Some(ip), Some(p)) => SocketAddr::new(ip, p),
+        _ => return None,
+    };
+    path_monitor::scope_link_local(addr, interface).await.ok()
 }
@@ -0,0 +1,176 @@
+//! Candidate racing for connection establishment
+//! Based on RFC 9623 Section 4.2 (Racing Candidates) and RFC 8305 (Happy Eyeballs v2)
+
+use crate::connection::set_buffer_sizes;
+use crate::runtime::{self, BoxFuture};
+use crate::{AddressFamilyPreference, HappyEyeballsConfig};
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::{TcpSocket, TcpStream};
+
+/// Lower bound on the connection attempt delay (RFC 8305 Section 5)
+const MIN_ATTEMPT_DELAY: Duration = Duration::from_millis(10);
+
+/// Upper bound on the connection attempt delay (RFC 8305 Section 5)
+const MAX_ATTEMPT_DELAY: Duration = Duration::from_secs(2);
+
+/// Order candidate addresses for racing
+///
+/// Filters by the address family preference and interleaves the families,
+/// starting with the preferred one (RFC 8305 Section 4).
+pub fn sort_candidates(
+    addrs: Vec<SocketAddr>,
+    preference: AddressFamilyPreference,
+) -> Vec<SocketAddr> {
+    let (v6, v4): (Vec<_>, Vec<_>) = addrs.into_iter().partition(|addr| addr.is_ipv6());
+
+    let (first, second) = match preference {
+        AddressFamilyPreference::PreferIpv6 => (v6, v4),
+        AddressFamilyPreference::PreferIpv4 => (v4, v6),
+        AddressFamilyPreference::Ipv6Only => (v6, Vec::new()),
+        AddressFamilyPreference::Ipv4Only => (v4, Vec::new()),
+    };
+
+    let mut ordered = Vec::with_capacity(first.len() + second.len());
+    let mut first = first.into_iter();
+    let mut second = second.into_iter();
+    loop {
+        match (first.next(), second.next()) {
+            (None, None) => break,
+            (a, b) => ordered.extend(a.into_iter().chain(b)),
+        }
+    }
+    ordered
+}
+
+/// Race TCP connection attempts to the candidates, returning the first to connect
+///
+/// A new attempt starts whenever the attempt delay elapses or an earlier attempt
+/// fails, up to `max_parallel_attempts` at once. Losing attempts are dropped.
+/// Each attempt's socket gets the send and receive buffer sizes before it
+/// connects.
+pub(crate) async fn race_tcp(
+    candidates: Vec<SocketAddr>,
+    config: &HappyEyeballsConfig,
+    buffer_sizes: (Option<usize>, Option<usize>),
+) -> io::Result<TcpStream> {
+    let attempt_delay = config
+        .connection_attempt_delay
+        .clamp(MIN_ATTEMPT_DELAY, MAX_ATTEMPT_DELAY);
+    let max_parallel = config.max_parallel_attempts.max(1);
+
+    let mut pending = candidates.into_iter();
+    let mut attempts: FuturesUnordered<BoxFuture<io::Result<TcpStream>>> = FuturesUnordered::new();
+    let mut last_error = None;
+
+    loop {
+        if attempts.len() < max_parallel {
+            if let Some(addr) = pending.next() {
+                attempts.push(Box::pin(connect_candidate(
+                    addr,
+                    config.candidate_timeout,
+                    buffer_sizes,
+                )));
+            }
+        }
+
+        if attempts.is_empty() {
+            return Err(last_error.unwrap_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "No candidates to connect to")
+            }));
+        }
+
+        let can_start_more = pending.len() > 0 && attempts.len() < max_parallel;
+
+        tokio::select! {
+            Some(result) = attempts.next() => {
+                match result {
+                    // Dropping the set cancels the losing attempts
+                    Ok(stream) => return Ok(stream),
+                    Err(e) => {
+                        log::debug!("Connection attempt failed: {e}");
+                        last_error = Some(e);
+                    }
+                }
+            }
+            _ = runtime::sleep(attempt_delay), if can_start_more => {}
+        }
+    }
+}
+
+/// Make a single connection attempt, bounded by the candidate timeout
+///
+/// The attempt's socket lives inside the returned future, so dropping the
+/// future closes it and no further SYNs are sent for the candidate.
+async fn connect_candidate(
+    addr: SocketAddr,
+    candidate_timeout: Option<Duration>,
+    buffer_sizes: (Option<usize>, Option<usize>),
+) -> io::Result<TcpStream> {
+    #[cfg(test)]
+    let _attempt = audit::Attempt::open(addr);
+    let connect = connect(addr, buffer_sizes);
+    match candidate_timeout {
+        Some(limit) => runtime::timeout(limit, connect).await.map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("Connection attempt to {addr} timed out"),
+            )
+        })?,
+        None => connect.await,
+    }
+}
+
+/// Connect a TCP socket to `addr`, with the send and receive buffer sizes
+/// set before it connects
+pub(crate) async fn connect(
+    addr: SocketAddr,
+    (send_buffer_size, receive_buffer_size): (Option<usize>, Option<usize>),
+) -> io::Result<TcpStream> {
+    let socket = match addr {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => TcpSocket::new_v6()?,
+    };
+    set_buffer_sizes(
+        &socket2::SockRef::from(&socket),
+        send_buffer_size,
+        receive_buffer_size,
+    );
+    socket.connect(addr).await
+}
+
+/// Sockets of connection attempts still in flight, for auditing that losing
+/// candidates are closed
+#[cfg(test)]
+pub(crate) mod audit {
+    use std::net::SocketAddr;
+    use std::sync::Mutex;
+
+    static LIVE: Mutex<Vec<SocketAddr>> = Mutex::new(Vec::new());
+
+    /// An attempt counted from its start until its future completes or is dropped
+    pub(crate) struct Attempt(SocketAddr);
+
+    impl Attempt {
+        pub(crate) fn open(addr: SocketAddr) -> Self {
+            LIVE.lock().unwrap().push(addr);
+            Self(addr)
+        }
+    }
+
+    impl Drop for Attempt {
+        fn drop(&mut self) {
+            let mut live = LIVE.lock().unwrap();
+            if let Some(i) = live.iter().position(|addr| *addr == self.0) {
+                live.swap_remove(i);
+            }
+        }
+    }
+
+    /// Number of attempts to `addr` whose sockets are still open
+    pub(crate) fn live_sockets(addr: SocketAddr) -> usize {
+        LIVE.lock().unwrap().iter().filter(|a| **a == addr).count()
+    }
+}
@@ -0,0 +1,170 @@
+//! Receive-side message reassembly
+//! Collects the bytes of length-prefixed messages until they are complete,
+//! switching to partial delivery (RFC Section 9.3.2.2) for messages larger
+//! than the receiver's maxLength or the connection's reassembly limit.
+//! Messages declared larger than the maximum message size on receive are
+//! discarded as they arrive rather than delivered.
+
+/// Largest message buffered whole before it is delivered in parts
+pub const DEFAULT_REASSEMBLY_LIMIT: usize = 1024 * 1024;
+
+const LENGTH_PREFIX: usize = 4;
+
+/// Received data ready to be delivered to the application
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Delivery {
+    pub(crate) data: Vec<u8>,
+    /// Length of the whole message as declared by the framer
+    pub(crate) message_length: usize,
+    /// Whether this is part of a message rather than all of it
+    pub(crate) partial: bool,
+    /// Whether this delivery completes the message
+    pub(crate) end_of_message: bool,
+}
+
+/// Per-connection reassembly state for framed messages
+#[derive(Debug)]
+pub(crate) struct Reassembler {
+    limit: usize,
+    max_message_size: Option<usize>,
+    // Message being delivered in parts: (declared length, bytes still to come)
+    in_progress: Option<(usize, usize)>,
+    // Bytes of a rejected message still to be discarded
+    discarding: usize,
+    // Declared length of a message rejected since the last take_rejected
+    rejected: Option<usize>,
+}
+
+impl Reassembler {
+    pub(crate) fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            max_message_size: None,
+            in_progress: None,
+            discarding: 0,
+            rejected: None,
+        }
+    }
+
+    pub(crate) fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub(crate) fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    pub(crate) fn max_message_size(&self) -> Option<usize> {
+        self.max_message_size
+    }
+
+    /// Reject messages declared larger than `max`; None accepts any size
+    pub(crate) fn set_max_message_size(&mut self, max: Option<usize>) {
+        self.max_message_size = max;
+    }
+
+    /// Whether a message is partly delivered or being discarded
+    pub(crate) fn in_progress(&self) -> bool {
+        self.in_progress.is_some() || self.discarding > 0
+    }
+
+    /// Declared length of the last message rejected for its size, if any
+    /// since the previous call
+    pub(crate) fn take_rejected(&mut self) -> Option<usize> {
+        self.rejected.take()
+    }
+
+    /// Forget any message in progress, e.g. when the receive buffer is dropped
+    pub(crate) fn reset(&mut self) {
+        self.in_progress = None;
+        self.discarding = 0;
+        self.rejected = None;
+    }
+
+    /// Take the next deliverable unit of framed data from the buffer
+    ///
+    /// `max_length` and `min_incomplete_length` are the parameters of the
+    /// Receive call (RFC Section 9.3.1); a message larger than either
+    /// `max_length` or the reassembly limit is delivered in parts of at most
+    /// `max_length` bytes, each holding at least `min_incomplete_length`
+    /// bytes unless it ends the message.
+    ///
+    /// A message declared larger than the maximum message size is never
+    /// buffered: its bytes are dropped as they arrive, and it is reported
+    /// through `take_rejected`.
+    pub(crate) fn next(
+        &mut self,
+        buffer: &mut Vec<u8>,
+        max_length: Option<usize>,
+        min_incomplete_length: Option<usize>,
+    ) -> Option<Delivery> {
+        if self.discarding > 0 {
+            let dropped = self.discarding.min(buffer.len());
+            buffer.drain(..dropped);
+            self.discarding -= dropped;
+            if self.discarding > 0 {
+                return None;
+            }
+        }
+
+        let (message_length, remaining) = match self.in_progress {
+            Some(progress) => progress,
+            None => {
+                let header = buffer.get(..LENGTH_PREFIX)?;
+                let length =
+                    u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+
+                if self.max_message_size.is_some_and(|max| length > max) {
+                    buffer.drain(..LENGTH_PREFIX);
+                    let dropped = length.min(buffer.len());
+                    buffer.drain(..dropped);
+                    self.discarding = length - dropped;
+                    self.rejected = Some(length);
+                    return None;
+                }
+
+                let cap = max_length.map_or(self.limit, |max| max.min(self.limit));
+                if length <= cap {
+                    if buffer.len() < LENGTH_PREFIX + length {
+                        return None;
+                    }
+                    let data = buffer[LENGTH_PREFIX..LENGTH_PREFIX + length].to_vec();
+                    buffer.drain(..LENGTH_PREFIX + length);
+                    return Some(Delivery {
+                        data,
+                        message_length: length,
+                        partial: false,
+                        end_of_message: true,
+                    });
+                }
+
+                buffer.drain(..LENGTH_PREFIX);
+                self.in_progress = Some((length, length));
+                (length, length)
+            }
+        };
+
+        let part = remaining.min(max_length.unwrap_or(usize::MAX).max(1));
+        let wanted = part.min(min_incomplete_length.unwrap_or(1).max(1));
+        if buffer.len() < wanted {
+            return None;
+        }
+
+        let data: Vec<u8> = buffer.drain(..part.min(buffer.len())).collect();
+        let remaining = remaining - data.len();
+        self.in_progress = (remaining > 0).then_some((message_length, remaining));
+
+        Some(Delivery {
+            data,
+            message_length,
+            partial: true,
+            end_of_message: remaining == 0,
+        })
+    }
+}
+
+impl Default for Reassembler {
+    fn default() -> Self {
+        Self::new(DEFAULT_REASSEMBLY_LIMIT)
+    }
+}
@@ -0,0 +1,310 @@
+//! Automatic re-establishment of connections
+//! A ReconnectingConnection initiates its Preconnection again with
+//! exponential backoff whenever the current Connection fails or closes,
+//! delivering the events of every generation through one stream
+
+use crate::runtime::{self, BoxFuture};
+use crate::{Connection, ConnectionEvent, Message, Preconnection, Result, TransportServicesError};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Called with each newly established Connection, e.g. to authenticate or
+/// resubscribe before the application sends on it
+///
+/// The hook must not consume the Connection's events. An error aborts the
+/// Connection and counts as a failed attempt.
+pub type ReconnectHook = Arc<dyn Fn(Connection) -> BoxFuture<Result<()>> + Send + Sync>;
+
+/// When and how often to re-initiate
+#[derive(Clone)]
+pub struct ReconnectPolicy {
+    /// Delay before the first attempt after a connection is lost
+    pub initial_delay: Duration,
+    /// Upper bound for the delay between attempts
+    pub max_delay: Duration,
+    /// Factor the delay grows by after each failed attempt
+    pub multiplier: f64,
+    /// Fraction of each delay, from 0.0 to 1.0, drawn at random and taken
+    /// off so that many clients do not reconnect in lockstep
+    pub jitter: f64,
+    /// Consecutive failed attempts after which to give up, or None to
+    /// keep trying
+    pub max_attempts: Option<u32>,
+    on_reconnect: Option<ReconnectHook>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.2,
+            max_attempts: None,
+            on_reconnect: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for ReconnectPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReconnectPolicy")
+            .field("initial_delay", &self.initial_delay)
+            .field("max_delay", &self.max_delay)
+            .field("multiplier", &self.multiplier)
+            .field("jitter", &self.jitter)
+            .field("max_attempts", &self.max_attempts)
+            .field("on_reconnect", &self.on_reconnect.is_some())
+            .finish()
+    }
+}
+
+impl ReconnectPolicy {
+    /// The default policy: 100ms doubling up to 30s, with 20% jitter
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the delay before the first attempt
+    pub fn with_initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    /// Set the upper bound for the delay
+    pub fn with_max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Set the factor the delay grows by
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// Set the jitter fraction
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Give up after this many consecutive failed attempts
+    pub fn with_max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = Some(attempts);
+        self
+    }
+
+    /// Set the hook run on every newly established Connection
+    pub fn on_reconnect<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(Connection) -> BoxFuture<Result<()>> + Send + Sync + 'static,
+    {
+        self.on_reconnect = Some(Arc::new(hook));
+        self
+    }
+
+    /// Delay before attempt `attempt`, counted from 0 after a loss
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let growth = self
+            .multiplier
+            .max(1.0)
+            .powi(attempt.min(i32::MAX as u32) as i32);
+        let delay = self
+            .initial_delay
+            .mul_f64(growth.min(u32::MAX as f64))
+            .min(self.max_delay);
+        delay.mul_f64(1.0 - self.jitter.clamp(0.0, 1.0) * random_fraction())
+    }
+}
+
+/// Uniform random value in [0, 1)
+fn random_fraction() -> f64 {
+    // The low 53 bits of a v4 UUID are all random
+    let bits = uuid::Uuid::new_v4().as_u128() as u64 & ((1 << 53) - 1);
+    bits as f64 / (1u64 << 53) as f64
+}
+
+/// State shared with the task driving the generations
+#[derive(Default)]
+struct Shared {
+    current: Mutex<Option<Connection>>,
+    generation: AtomicU64,
+    closing: AtomicBool,
+}
+
+/// A connection that re-initiates itself after ConnectionError or Closed
+///
+/// Each Connection established is a generation. Its events, including the
+/// ConnectionError or Closed ending it, are delivered through `next_event`
+/// in order; the next generation's Ready follows once it is established.
+/// After giving up, a final ConnectionError is delivered and the event
+/// stream ends.
+pub struct ReconnectingConnection {
+    shared: Arc<Shared>,
+    events: tokio::sync::Mutex<mpsc::UnboundedReceiver<ConnectionEvent>>,
+}
+
+impl ReconnectingConnection {
+    /// Start initiating `preconnection` under `policy`
+    ///
+    /// Every generation uses the Preconnection's configuration at this call.
+    pub async fn initiate(preconnection: &Preconnection, policy: ReconnectPolicy) -> Self {
+        let preconnection = preconnection.freeze().await;
+        let shared = Arc::new(Shared::default());
+        let (sender, receiver) = mpsc::unbounded_channel();
+        runtime::spawn(run(preconnection, policy, Arc::clone(&shared), sender));
+        Self {
+            shared,
+            events: tokio::sync::Mutex::new(receiver),
+        }
+    }
+
+    /// The Connection of the current generation, while there is one
+    pub fn current(&self) -> Option<Connection> {
+        self.shared.current.lock().unwrap().clone()
+    }
+
+    /// Number of Connections initiated so far, including failed attempts
+    pub fn generation(&self) -> u64 {
+        self.shared.generation.load(Ordering::Relaxed)
+    }
+
+    /// Send on the current Connection
+    ///
+    /// Fails while reconnecting; messages are not held across generations.
+    pub async fn send(&self, message: impl Into<Message>) -> Result<()> {
+        match self.current() {
+            Some(connection) => connection.send(message).await,
+            None => Err(TransportServicesError::InvalidState(
+                "Reconnecting; no connection to send on".to_string(),
+            )),
+        }
+    }
+
+    /// Get the next event of any generation
+    ///
+    /// Returns None once closed or after giving up.
+    pub async fn next_event(&self) -> Option<ConnectionEvent> {
+        self.events.lock().await.recv().await
+    }
+
+    /// Close the current Connection and stop reconnecting
+    pub async fn close(&self) -> Result<()> {
+        self.shared.closing.store(true, Ordering::Relaxed);
+        match self.current() {
+            Some(connection) => connection.close().await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for ReconnectingConnection {
+    fn drop(&mut self) {
+        self.shared.closing.store(true, Ordering::Relaxed);
+        let current = self.shared.current.lock().unwrap().take();
+        if let (Some(connection), true) = (current, runtime::executor().can_spawn()) {
+            runtime::spawn(async move {
+                let _ = connection.close().await;
+            });
+        }
+    }
+}
+
+/// Drive generations until closed or out of attempts
+async fn run(
+    preconnection: Preconnection,
+    policy: ReconnectPolicy,
+    shared: Arc<Shared>,
+    events: mpsc::UnboundedSender<ConnectionEvent>,
+) {
+    // Consecutive failed attempts, and delays waited since the last success
+    let mut failures = 0u32;
+    let mut retries = 0u32;
+    let mut first = true;
+
+    while !shared.closing.load(Ordering::Relaxed) {
+        if !std::mem::take(&mut first) {
+            if policy.max_attempts.is_some_and(|max| failures >= max) {
+                let _ = events.send(ConnectionEvent::ConnectionError(format!(
+                    "Gave up reconnecting after {failures} attempts"
+                )));
+                break;
+            }
+            runtime::sleep(policy.backoff(retries)).await;
+            retries = retries.saturating_add(1);
+            if shared.closing.load(Ordering::Relaxed) {
+                break;
+            }
+        }
+
+        shared.generation.fetch_add(1, Ordering::Relaxed);
+        let established = match preconnection.initiate().await {
+            Ok(connection) => run_generation(connection, &policy, &shared, &events).await,
+            Err(e) => {
+                let _ = events.send(ConnectionEvent::EstablishmentError(e.to_string()));
+                false
+            }
+        };
+        shared.current.lock().unwrap().take();
+        if events.is_closed() {
+            break;
+        }
+        // A loss after establishment starts the backoff over
+        if established {
+            failures = 0;
+            retries = 0;
+        } else {
+            failures += 1;
+        }
+    }
+}
+
+/// Forward one generation's events until it ends
+///
+/// Returns whether the Connection was established and passed the hook.
+async fn run_generation(
+    connection: Connection,
+    policy: &ReconnectPolicy,
+    shared: &Shared,
+    events: &mpsc::UnboundedSender<ConnectionEvent>,
+) -> bool {
+    *shared.current.lock().unwrap() = Some(connection.clone());
+    if shared.closing.load(Ordering::Relaxed) {
+        // Closed while this generation was being initiated
+        let _ = connection.close().await;
+        return false;
+    }
+    let mut established = false;
+
+    while let Some(event) = connection.next_event().await {
+        let ready = matches!(event, ConnectionEvent::Ready);
+        let ended = matches!(
+            event,
+            ConnectionEvent::EstablishmentError(_)
+                | ConnectionEvent::ConnectionError(_)
+                | ConnectionEvent::Closed
+        );
+        if events.send(event).is_err() {
+            break;
+        }
+        if ended {
+            break;
+        }
+        if ready {
+            if let Some(hook) = &policy.on_reconnect {
+                if let Err(e) = hook(connection.clone()).await {
+                    let _ = connection.abort().await;
+                    let _ = events.send(ConnectionEvent::ConnectionError(format!(
+                        "Reconnect hook failed: {e}"
+                    )));
+                    return false;
+                }
+            }
+            established = true;
+        }
+    }
+    established
+}
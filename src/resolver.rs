@@ -0,0 +1,381 @@
+//! Name resolution for Transport Services
+//! Based on RFC 9623 Section 4.1 (Candidate Gathering) and RFC 9622 Section 7.1
+
+use crate::{runtime, Result, TransportServicesError};
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// TTL used when the resolver does not report one
+///
+/// The system resolver (getaddrinfo) does not expose record TTLs.
+pub const DEFAULT_RESOLUTION_TTL: Duration = Duration::from_secs(60);
+
+/// How long an expired entry may still be served while it is refreshed
+pub const DEFAULT_STALE_WINDOW: Duration = Duration::from_secs(30);
+
+/// Longest a resolved answer is cached, whatever TTL the resolver reports
+///
+/// RFC 2181 Section 8 caps TTLs at 2^31 - 1 seconds; caches commonly cap
+/// them far lower, as this one does at a day.
+pub const MAX_RESOLUTION_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+static GLOBAL_CACHE: Lazy<Arc<ResolutionCache>> = Lazy::new(|| Arc::new(ResolutionCache::new()));
+
+/// Transport a resolver backend is asked to use
+///
+/// This crate implements no encrypted DNS transport itself; anything other
+/// than `System` is a hint for the `DnsResolver` backend set with
+/// `ResolverConfig::with_backend`, and resolution fails without one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum ResolverTransport {
+    /// Platform resolver (getaddrinfo)
+    #[default]
+    System,
+    /// DNS-over-TLS (RFC 7858), performed by the backend
+    Tls {
+        server: SocketAddr,
+        server_name: String,
+    },
+    /// DNS-over-HTTPS (RFC 8484), performed by the backend
+    Https { url: String },
+}
+
+/// DNSSEC validation policy for resolution
+///
+/// Validation is left to the `DnsResolver` backend, as with encrypted transports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DnssecPolicy {
+    /// Accept unvalidated answers
+    #[default]
+    Disabled,
+    /// Only accept answers that pass DNSSEC validation
+    Required,
+}
+
+/// Resolver backend for secure resolution
+///
+/// The system resolver cannot use encrypted transports or validate DNSSEC;
+/// a backend implementing those is plugged in through `ResolverConfig::with_backend`.
+#[async_trait]
+pub trait DnsResolver: Send + Sync {
+    /// Resolve a hostname and port, returning the addresses and record TTL if known
+    async fn lookup(
+        &self,
+        host: &str,
+        port: u16,
+        config: &ResolverConfig,
+    ) -> Result<(Vec<SocketAddr>, Option<Duration>)>;
+}
+
+/// Configuration of the resolver backing endpoint resolution
+#[derive(Clone, Default)]
+pub struct ResolverConfig {
+    /// Transport used to reach the resolver
+    pub transport: ResolverTransport,
+    /// DNSSEC validation policy
+    pub dnssec: DnssecPolicy,
+    backend: Option<Arc<dyn DnsResolver>>,
+}
+
+impl ResolverConfig {
+    /// Use the platform resolver
+    pub fn system() -> Self {
+        Self::default()
+    }
+
+    /// Ask the backend to use DNS-over-TLS to the given server
+    ///
+    /// Only a hint: lookups fail with NotSupported until a backend
+    /// implementing DNS-over-TLS is set with `with_backend`.
+    pub fn dns_over_tls_hint(server: SocketAddr, server_name: impl Into<String>) -> Self {
+        Self {
+            transport: ResolverTransport::Tls {
+                server,
+                server_name: server_name.into(),
+            },
+            ..Self::default()
+        }
+    }
+
+    /// Ask the backend to use DNS-over-HTTPS with the given URI template
+    ///
+    /// Only a hint: lookups fail with NotSupported until a backend
+    /// implementing DNS-over-HTTPS is set with `with_backend`.
+    pub fn dns_over_https_hint(url: impl Into<String>) -> Self {
+        Self {
+            transport: ResolverTransport::Https { url: url.into() },
+            ..Self::default()
+        }
+    }
+
+    /// Set the DNSSEC validation policy the backend must enforce
+    pub fn with_dnssec(mut self, policy: DnssecPolicy) -> Self {
+        self.dnssec = policy;
+        self
+    }
+
+    /// Set the backend that performs secure resolution
+    pub fn with_backend(mut self, backend: Arc<dyn DnsResolver>) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Check whether this configuration needs a backend beyond the system resolver
+    pub fn requires_backend(&self) -> bool {
+        self.transport != ResolverTransport::System || self.dnssec == DnssecPolicy::Required
+    }
+
+    fn cache_key(&self, host: String, port: u16) -> CacheKey {
+        (
+            host,
+            port,
+            self.transport.clone(),
+            self.dnssec,
+            self.backend.clone().map(BackendKey),
+        )
+    }
+}
+
+impl std::fmt::Debug for ResolverConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResolverConfig")
+            .field("transport", &self.transport)
+            .field("dnssec", &self.dnssec)
+            .field("has_backend", &self.backend.is_some())
+            .finish()
+    }
+}
+
+/// Hostname, port and the transport, DNSSEC policy and backend the answer
+/// was obtained with, so an answer only satisfies lookups configured alike
+type CacheKey = (
+    String,
+    u16,
+    ResolverTransport,
+    DnssecPolicy,
+    Option<BackendKey>,
+);
+
+/// A backend compared by identity
+///
+/// Holding the backend keeps its address from being reused by another
+/// while answers it gave are cached.
+#[derive(Clone)]
+struct BackendKey(Arc<dyn DnsResolver>);
+
+impl PartialEq for BackendKey {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::addr_eq(Arc::as_ptr(&self.0), Arc::as_ptr(&other.0))
+    }
+}
+
+impl Eq for BackendKey {}
+
+impl std::hash::Hash for BackendKey {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        (Arc::as_ptr(&self.0) as *const ()).hash(state);
+    }
+}
+
+struct CacheEntry {
+    addrs: Vec<SocketAddr>,
+    expires: Instant,
+    refreshing: bool,
+}
+
+/// Cache of resolved hostnames honouring record TTLs
+///
+/// Expired entries are served for a further stale window while a background
+/// lookup refreshes them (stale-while-revalidate).
+pub struct ResolutionCache {
+    entries: Mutex<HashMap<CacheKey, CacheEntry>>,
+    default_ttl: Duration,
+    stale_window: Duration,
+}
+
+impl ResolutionCache {
+    /// Create an empty cache with the default TTL and stale window
+    pub fn new() -> Self {
+        Self::with_ttl(DEFAULT_RESOLUTION_TTL, DEFAULT_STALE_WINDOW)
+    }
+
+    /// Create an empty cache with a custom default TTL and stale window
+    ///
+    /// Both are clamped to `MAX_RESOLUTION_TTL`.
+    pub fn with_ttl(default_ttl: Duration, stale_window: Duration) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            default_ttl,
+            stale_window: stale_window.min(MAX_RESOLUTION_TTL),
+        }
+    }
+
+    /// Get the process-wide shared cache
+    pub fn global() -> Arc<ResolutionCache> {
+        Arc::clone(&GLOBAL_CACHE)
+    }
+
+    /// Resolve a hostname and port with the system resolver, consulting the cache first
+    pub async fn resolve(self: &Arc<Self>, host: &str, port: u16) -> Result<Vec<SocketAddr>> {
+        self.resolve_with(host, port, &ResolverConfig::default())
+            .await
+    }
+
+    /// Resolve a hostname and port with the given resolver configuration
+    ///
+    /// Answers are only reused for the same transport, DNSSEC policy and
+    /// backend, so one from the system resolver, another transport or another
+    /// backend never satisfies it.
+    /// Internationalized hostnames are resolved in their ASCII form, and
+    /// malformed ones fail before any lookup.
+    pub async fn resolve_with(
+        self: &Arc<Self>,
+        host: &str,
+        port: u16,
+        config: &ResolverConfig,
+    ) -> Result<Vec<SocketAddr>> {
+        runtime::require_executor()?;
+        let key = config.cache_key(crate::hostname::to_ascii(host)?, port);
+        let now = Instant::now();
+
+        let stale = {
+            let mut entries = self.entries.lock().unwrap();
+            match entries.get_mut(&key) {
+                Some(entry) if now < entry.expires => return Ok(entry.addrs.clone()),
+                Some(entry) if now < entry.expires + self.stale_window => {
+                    let refresh = !entry.refreshing;
+                    entry.refreshing = true;
+                    Some((entry.addrs.clone(), refresh))
+                }
+                _ => None,
+            }
+        };
+
+        match stale {
+            Some((addrs, refresh)) => {
+                if refresh {
+                    let cache = Arc::clone(self);
+                    let config = config.clone();
+                    runtime::spawn(async move {
+                        if cache.lookup(&key.0, key.1, &config).await.is_err() {
+                            // Allow another refresh attempt on the next resolve
+                            if let Some(entry) = cache.entries.lock().unwrap().get_mut(&key) {
+                                entry.refreshing = false;
+                            }
+                        }
+                    });
+                }
+                Ok(addrs)
+            }
+            None => self.lookup(&key.0, key.1, config).await,
+        }
+    }
+
+    /// Insert resolved addresses with an explicit TTL
+    ///
+    /// Resolvers that see record TTLs use this to feed the cache. TTLs
+    /// beyond `MAX_RESOLUTION_TTL` are clamped to it.
+    pub fn insert(&self, host: &str, port: u16, addrs: Vec<SocketAddr>, ttl: Duration) {
+        self.insert_entry(host, port, &ResolverConfig::default(), addrs, ttl);
+    }
+
+    fn insert_entry(
+        &self,
+        host: &str,
+        port: u16,
+        config: &ResolverConfig,
+        addrs: Vec<SocketAddr>,
+        ttl: Duration,
+    ) {
+        let host = crate::hostname::to_ascii(host).unwrap_or_else(|_| host.to_ascii_lowercase());
+        let now = Instant::now();
+        let expires = now.checked_add(ttl.min(MAX_RESOLUTION_TTL)).unwrap_or(now);
+        self.entries.lock().unwrap().insert(
+            config.cache_key(host, port),
+            CacheEntry {
+                addrs,
+                expires,
+                refreshing: false,
+            },
+        );
+    }
+
+    /// Drop all cached entries, e.g. after a path change
+    pub fn flush(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// Number of cached entries, including stale ones
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Check whether the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Look up a hostname and cache the result
+    async fn lookup(
+        &self,
+        host: &str,
+        port: u16,
+        config: &ResolverConfig,
+    ) -> Result<Vec<SocketAddr>> {
+        let (addrs, ttl) = match &config.backend {
+            Some(backend) => backend.lookup(host, port, config).await?,
+            None if config.requires_backend() => {
+                return Err(TransportServicesError::NotSupported(
+                    "DNS-over-TLS, DNS-over-HTTPS and DNSSEC require a DnsResolver backend"
+                        .to_string(),
+                ))
+            }
+            None => {
+                let addrs = tokio::net::lookup_host((host, port))
+                    .await
+                    .map_err(|e| {
+                        TransportServicesError::InvalidParameters(format!(
+                            "Failed to resolve hostname: {e}"
+                        ))
+                    })?
+                    .collect();
+                (addrs, None)
+            }
+        };
+
+        if addrs.is_empty() {
+            return Err(TransportServicesError::InvalidParameters(format!(
+                "No addresses found for {host}"
+            )));
+        }
+
+        self.insert_entry(
+            host,
+            port,
+            config,
+            addrs.clone(),
+            ttl.unwrap_or(self.default_ttl),
+        );
+        Ok(addrs)
+    }
+}
+
+impl Default for ResolutionCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for ResolutionCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResolutionCache")
+            .field("entries", &self.len())
+            .field("default_ttl", &self.default_ttl)
+            .field("stale_window", &self.stale_window)
+            .finish()
+    }
+}
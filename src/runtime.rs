@@ -0,0 +1,152 @@
+//! Executor abstraction for the Transport Services core
+//! Background tasks and timers are driven through an `Executor` so the
+//! connection state machines are not tied to one async runtime
+
+use crate::{Result, TransportServicesError};
+use futures::future::{self, Either};
+use once_cell::sync::OnceCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Boxed future run by an `Executor`
+pub type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send + 'static>>;
+
+/// Spawns background tasks and provides timers
+///
+/// Synchronization uses runtime-independent primitives; socket I/O still
+/// requires a tokio reactor (e.g. through a compatibility layer).
+pub trait Executor: Send + Sync {
+    /// Run a future to completion in the background
+    fn spawn(&self, future: BoxFuture<()>);
+
+    /// Complete after the given duration
+    fn sleep(&self, duration: Duration) -> BoxFuture<()>;
+
+    /// Check whether tasks can be spawned from the current context
+    ///
+    /// Used where spawning is best effort, such as teardown on drop.
+    fn can_spawn(&self) -> bool {
+        true
+    }
+}
+
+/// Executor backed by the tokio runtime
+#[cfg(feature = "runtime-tokio")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioExecutor;
+
+#[cfg(feature = "runtime-tokio")]
+impl Executor for TokioExecutor {
+    fn spawn(&self, future: BoxFuture<()>) {
+        tokio::spawn(future);
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+
+    fn can_spawn(&self) -> bool {
+        tokio::runtime::Handle::try_current().is_ok()
+    }
+}
+
+static EXECUTOR: OnceCell<Arc<dyn Executor>> = OnceCell::new();
+
+/// Install the executor used by the library
+///
+/// Must be called before any Transport Services object is used; the executor
+/// cannot be replaced once set.
+pub fn set_executor(executor: Arc<dyn Executor>) -> Result<()> {
+    EXECUTOR.set(executor).map_err(|_| {
+        TransportServicesError::InvalidState("An executor is already installed".to_string())
+    })
+}
+
+/// Get the executor used by the library
+///
+/// Defaults to `TokioExecutor` when the `runtime-tokio` feature is enabled.
+#[cfg(feature = "runtime-tokio")]
+pub fn executor() -> Arc<dyn Executor> {
+    Arc::clone(EXECUTOR.get_or_init(|| Arc::new(TokioExecutor)))
+}
+
+/// Get the executor used by the library
+///
+/// With `runtime-custom` alone this is the one installed with
+/// `set_executor`, which must come before the library is used: Initiate,
+/// Listen, Rendezvous and resolution fail with InvalidState until then.
+#[cfg(not(feature = "runtime-tokio"))]
+pub fn executor() -> Arc<dyn Executor> {
+    Arc::clone(
+        EXECUTOR
+            .get()
+            .expect("runtime-custom needs runtime::set_executor before the library is used"),
+    )
+}
+
+#[cfg(not(any(feature = "runtime-tokio", feature = "runtime-custom")))]
+compile_error!(
+    "Enable the runtime-tokio feature, or runtime-custom and install an executor \
+     with runtime::set_executor"
+);
+
+/// Check that background tasks have an executor to run on
+pub(crate) fn require_executor() -> Result<()> {
+    if cfg!(feature = "runtime-tokio") || EXECUTOR.get().is_some() {
+        return Ok(());
+    }
+    Err(TransportServicesError::InvalidState(
+        "No executor installed; call runtime::set_executor first".to_string(),
+    ))
+}
+
+/// Mechanism driving socket I/O
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoBackend {
+    /// Readiness notification through the tokio reactor (epoll, kqueue)
+    Readiness,
+    /// Writes are completed through one io_uring shared by all connections,
+    /// batching many into each system call; reads and accepts still use
+    /// readiness notification
+    IoUringWrites,
+}
+
+/// The I/O backend in use
+///
+/// io_uring is used for writes with the `io-uring-writes` feature on Linux
+/// kernels that provide it, which is checked when first asked; otherwise,
+/// including where seccomp filters forbid io_uring, readiness notification
+/// is used. Reads and accepts always use readiness notification.
+pub fn io_backend() -> IoBackend {
+    #[cfg(all(target_os = "linux", feature = "io-uring-writes"))]
+    if crate::uring::supported() {
+        return IoBackend::IoUringWrites;
+    }
+    IoBackend::Readiness
+}
+
+/// Error returned when `timeout` elapses before the future completes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Elapsed;
+
+pub(crate) fn spawn(future: impl Future<Output = ()> + Send + 'static) {
+    executor().spawn(Box::pin(future));
+}
+
+pub(crate) async fn sleep(duration: Duration) {
+    executor().sleep(duration).await
+}
+
+/// Run a future, giving up once `duration` has elapsed
+pub(crate) async fn timeout<F: Future>(
+    duration: Duration,
+    future: F,
+) -> std::result::Result<F::Output, Elapsed> {
+    let future = std::pin::pin!(future);
+    match future::select(future, executor().sleep(duration)).await {
+        Either::Left((output, _)) => Ok(output),
+        Either::Right(_) => Err(Elapsed),
+    }
+}
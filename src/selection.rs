@@ -0,0 +1,164 @@
+//! Explanation of candidate selection
+//! Based on RFC 9623 Section 4.1 (Candidate Gathering) and 4.2 (Candidate Racing)
+//!
+//! `Preconnection::explain_selection` derives the candidate tree an initiate
+//! would race, protocol stacks first, then the remote endpoints, then the
+//! addresses and paths they resolve to, and records why each branch was
+//! pruned or where it was ranked, without establishing anything. The same
+//! tree is logged at debug level when a Connection is initiated.
+
+use crate::{Protocol, RemoteEndpoint, TransportProperty};
+use std::fmt;
+use std::net::SocketAddr;
+
+/// Why a branch of the candidate tree is never attempted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PruneReason {
+    /// This build cannot initiate connections over the protocol, even
+    /// where it can listen with it
+    Unavailable,
+    /// The protocol stack cannot honor the property as set
+    Property(TransportProperty),
+    /// The endpoint could not be resolved to any address
+    ResolutionFailed(String),
+    /// The address was already gathered from an alias of the endpoint
+    DuplicateAlias,
+    /// The address family preference excludes the address
+    AddressFamily,
+    /// The candidate policy of the Preconnection's context left it out
+    ContextPolicy,
+}
+
+impl fmt::Display for PruneReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PruneReason::Unavailable => write!(f, "not available for initiate"),
+            PruneReason::Property(property) => write!(f, "cannot honor {property:?}"),
+            PruneReason::ResolutionFailed(e) => write!(f, "resolution failed: {e}"),
+            PruneReason::DuplicateAlias => write!(f, "already gathered from an alias"),
+            PruneReason::AddressFamily => write!(f, "excluded by address family preference"),
+            PruneReason::ContextPolicy => write!(f, "rejected by the context policy"),
+        }
+    }
+}
+
+/// What became of a branch of the candidate tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    /// Kept; stacks and endpoints are ranked among their siblings, candidates
+    /// by the order in which they are raced
+    Selected { rank: usize },
+    /// Left out, for the given reason
+    Pruned(PruneReason),
+}
+
+impl Decision {
+    /// Whether the branch was kept
+    pub fn is_selected(&self) -> bool {
+        matches!(self, Decision::Selected { .. })
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Decision::Selected { rank } => write!(f, "selected #{}", rank + 1),
+            Decision::Pruned(reason) => write!(f, "pruned: {reason}"),
+        }
+    }
+}
+
+/// An address a connection attempt would be made to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CandidateBranch {
+    pub address: SocketAddr,
+    /// Interface the attempt is scoped to, or None for the system's route
+    pub interface: Option<String>,
+    pub decision: Decision,
+}
+
+/// A remote endpoint and the candidates gathered from it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EndpointBranch {
+    pub endpoint: RemoteEndpoint,
+    pub decision: Decision,
+    pub candidates: Vec<CandidateBranch>,
+}
+
+/// A protocol stack and, once selected, the endpoints it would reach
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackBranch {
+    pub protocol: Protocol,
+    pub decision: Decision,
+    pub endpoints: Vec<EndpointBranch>,
+}
+
+/// The candidate tree of a Preconnection, with the reasons behind it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SelectionExplanation {
+    pub stacks: Vec<StackBranch>,
+    /// Proxy whose addresses the candidates are, when tunneling
+    pub proxy: Option<RemoteEndpoint>,
+    /// Why initiate would fail before racing, if it would
+    pub failure: Option<String>,
+}
+
+impl SelectionExplanation {
+    /// The protocol stack initiate would use
+    pub fn selected_stack(&self) -> Option<Protocol> {
+        self.stacks
+            .iter()
+            .find(|stack| stack.decision == Decision::Selected { rank: 0 })
+            .map(|stack| stack.protocol)
+    }
+
+    /// Every candidate gathered, kept or not
+    pub fn candidates(&self) -> impl Iterator<Item = &CandidateBranch> {
+        self.stacks
+            .iter()
+            .flat_map(|stack| &stack.endpoints)
+            .flat_map(|endpoint| &endpoint.candidates)
+    }
+
+    /// The kept candidates, in racing order
+    pub fn attempts(&self) -> Vec<&CandidateBranch> {
+        let mut attempts: Vec<_> = self
+            .candidates()
+            .filter_map(|candidate| match candidate.decision {
+                Decision::Selected { rank } => Some((rank, candidate)),
+                Decision::Pruned(_) => None,
+            })
+            .collect();
+        attempts.sort_by_key(|(rank, _)| *rank);
+        attempts
+            .into_iter()
+            .map(|(_, candidate)| candidate)
+            .collect()
+    }
+}
+
+/// Formats as an indented tree, one branch per line
+impl fmt::Display for SelectionExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(proxy) = &self.proxy {
+            writeln!(f, "through proxy {proxy:?}")?;
+        }
+        for stack in &self.stacks {
+            writeln!(f, "{:?}: {}", stack.protocol, stack.decision)?;
+            for endpoint in &stack.endpoints {
+                writeln!(f, "  {:?}: {}", endpoint.endpoint, endpoint.decision)?;
+                for candidate in &endpoint.candidates {
+                    write!(f, "    {}", candidate.address)?;
+                    if let Some(interface) = &candidate.interface {
+                        write!(f, " via {interface}")?;
+                    }
+                    writeln!(f, ": {}", candidate.decision)?;
+                }
+            }
+        }
+        if let Some(failure) = &self.failure {
+            writeln!(f, "fails: {failure}")?;
+        }
+        Ok(())
+    }
+}
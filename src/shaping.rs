@@ -0,0 +1,256 @@
+//! Network condition simulation
+//! Shapes the data a Connection sends with a token bucket and a delay queue
+//! to emulate links such as 3G, satellite or Wi-Fi in tests and demos
+
+use crate::runtime::{self, BoxFuture};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch};
+
+/// Token bucket depth used when none is configured
+pub const DEFAULT_BURST_BYTES: usize = 16 * 1024;
+
+/// Link characteristics to emulate
+///
+/// Shaping applies to the data a connection sends, like an egress queue
+/// discipline; shape both ends to emulate a bidirectional link.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkConditions {
+    /// One-way delay added to every unit sent
+    pub latency: Duration,
+    /// Maximum extra delay, drawn uniformly for each unit
+    pub jitter: Duration,
+    /// Link rate in bits per second, or None for unlimited
+    pub bandwidth_bps: Option<u64>,
+    /// Bytes that may be sent back to back before the link rate applies
+    pub burst_bytes: usize,
+    /// Fraction of units lost, from 0.0 to 1.0
+    ///
+    /// Lost datagrams are discarded; on streams a lost unit is retransmitted
+    /// and arrives one round trip late.
+    pub loss: f64,
+    /// Seed for jitter and loss, so runs are reproducible
+    pub seed: u64,
+}
+
+impl Default for NetworkConditions {
+    fn default() -> Self {
+        Self {
+            latency: Duration::ZERO,
+            jitter: Duration::ZERO,
+            bandwidth_bps: None,
+            burst_bytes: DEFAULT_BURST_BYTES,
+            loss: 0.0,
+            seed: 0,
+        }
+    }
+}
+
+impl NetworkConditions {
+    /// An unimpaired link
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A typical 3G mobile uplink
+    pub fn three_g() -> Self {
+        Self::new()
+            .with_latency(Duration::from_millis(100))
+            .with_jitter(Duration::from_millis(20))
+            .with_bandwidth(750_000)
+            .with_loss(0.01)
+    }
+
+    /// A geostationary satellite link
+    pub fn satellite() -> Self {
+        Self::new()
+            .with_latency(Duration::from_millis(300))
+            .with_jitter(Duration::from_millis(10))
+            .with_bandwidth(5_000_000)
+            .with_loss(0.005)
+    }
+
+    /// A home Wi-Fi link
+    pub fn wifi() -> Self {
+        Self::new()
+            .with_latency(Duration::from_millis(5))
+            .with_jitter(Duration::from_millis(5))
+            .with_bandwidth(50_000_000)
+            .with_loss(0.001)
+    }
+
+    /// Set the one-way latency
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Set the maximum jitter
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Limit the link rate in bits per second
+    pub fn with_bandwidth(mut self, bits_per_second: u64) -> Self {
+        self.bandwidth_bps = Some(bits_per_second);
+        self
+    }
+
+    /// Set the token bucket depth in bytes
+    pub fn with_burst(mut self, bytes: usize) -> Self {
+        self.burst_bytes = bytes;
+        self
+    }
+
+    /// Set the fraction of units lost
+    pub fn with_loss(mut self, loss: f64) -> Self {
+        self.loss = loss.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the seed for jitter and loss
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+struct ShaperState {
+    tokens: f64,
+    last_refill: Instant,
+    // Release time of the last queued unit; the link never reorders
+    last_release: Instant,
+    rng: u64,
+}
+
+impl ShaperState {
+    /// Pick the release time of a unit, or None if it is lost
+    fn schedule(
+        &mut self,
+        conditions: &NetworkConditions,
+        len: usize,
+        reliable: bool,
+    ) -> Option<Instant> {
+        let now = Instant::now();
+        let mut departure = now;
+
+        if let Some(bps) = conditions.bandwidth_bps.filter(|bps| *bps > 0) {
+            let rate = bps as f64 / 8.0;
+            let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * rate).min(conditions.burst_bytes as f64);
+            self.last_refill = now;
+
+            // Tokens go negative while the link is busy sending earlier units
+            self.tokens -= len as f64;
+            if self.tokens < 0.0 {
+                departure += Duration::from_secs_f64(-self.tokens / rate);
+            }
+        }
+
+        let mut delay = conditions.latency;
+        if !conditions.jitter.is_zero() {
+            delay += conditions.jitter.mul_f64(self.next_random());
+        }
+        if conditions.loss > 0.0 && self.next_random() < conditions.loss {
+            if !reliable {
+                return None;
+            }
+            delay += conditions.latency * 2;
+        }
+
+        let release = (departure + delay).max(self.last_release);
+        self.last_release = release;
+        Some(release)
+    }
+
+    /// Uniform value in [0, 1) from a splitmix64 sequence
+    fn next_random(&mut self) -> f64 {
+        self.rng = self.rng.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Per-connection shaper feeding a delay queue
+pub(crate) struct TrafficShaper {
+    conditions: NetworkConditions,
+    state: Mutex<ShaperState>,
+    queue: mpsc::UnboundedSender<(Instant, Vec<u8>)>,
+    in_flight: watch::Sender<usize>,
+}
+
+impl TrafficShaper {
+    /// Create a shaper whose delay queue hands released units to `deliver`
+    pub(crate) fn new<F>(conditions: NetworkConditions, deliver: F) -> Self
+    where
+        F: Fn(Vec<u8>) -> BoxFuture<()> + Send + Sync + 'static,
+    {
+        let (queue, mut released) = mpsc::unbounded_channel::<(Instant, Vec<u8>)>();
+        let in_flight = watch::Sender::new(0usize);
+        let delivered = in_flight.clone();
+
+        runtime::spawn(async move {
+            while let Some((release, unit)) = released.recv().await {
+                let wait = release.saturating_duration_since(Instant::now());
+                if !wait.is_zero() {
+                    runtime::sleep(wait).await;
+                }
+                deliver(unit).await;
+                delivered.send_modify(|count| *count -= 1);
+            }
+        });
+
+        let now = Instant::now();
+        Self {
+            state: Mutex::new(ShaperState {
+                tokens: conditions.burst_bytes as f64,
+                last_refill: now,
+                last_release: now,
+                rng: conditions.seed,
+            }),
+            conditions,
+            queue,
+            in_flight,
+        }
+    }
+
+    pub(crate) fn conditions(&self) -> &NetworkConditions {
+        &self.conditions
+    }
+
+    /// Queue a unit for delivery, returning false if the link lost it
+    pub(crate) fn enqueue(&self, unit: Vec<u8>, reliable: bool) -> bool {
+        let release = {
+            let mut state = self.state.lock().unwrap();
+            match state.schedule(&self.conditions, unit.len(), reliable) {
+                Some(release) => release,
+                None => return false,
+            }
+        };
+
+        self.in_flight.send_modify(|count| *count += 1);
+        if self.queue.send((release, unit)).is_err() {
+            self.in_flight.send_modify(|count| *count -= 1);
+        }
+        true
+    }
+
+    /// Wait until every queued unit has been delivered
+    pub(crate) async fn drained(&self) {
+        let mut in_flight = self.in_flight.subscribe();
+        let _ = in_flight.wait_for(|count| *count == 0).await;
+    }
+}
+
+impl std::fmt::Debug for TrafficShaper {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TrafficShaper")
+            .field("conditions", &self.conditions)
+            .field("in_flight", &*self.in_flight.borrow())
+            .finish()
+    }
+}
@@ -0,0 +1,109 @@
+//! Graceful shutdown of all live connections
+//! Tracks every listener and connection so a process can drain them on exit,
+//! e.g. on SIGTERM, without keeping handles to connections spread across tasks.
+
+use crate::connection_group::GroupMember;
+use crate::listener::StopHandle;
+use crate::{runtime, Connection, ConnectionState};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Every listener and connection created in the process
+static GLOBAL: Registry = Registry::new();
+
+/// What a shutdown did
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShutdownSummary {
+    /// Listeners that were stopped
+    pub listeners_stopped: usize,
+    /// Connections that closed gracefully before the deadline
+    pub closed: usize,
+    /// Connections aborted because they were still open at the deadline
+    pub aborted: usize,
+}
+
+/// Stop all listeners and close all connections in the process
+///
+/// Listeners are stopped first so no new connections are accepted. Every open
+/// connection then closes gracefully, delivering outstanding data, and those
+/// still open when `timeout` passes are aborted. Connections whose handles
+/// were all dropped are left to their drop policy.
+pub async fn shutdown(timeout: Duration) -> ShutdownSummary {
+    GLOBAL.drain(timeout).await
+}
+
+/// Register a new connection with the process-wide registry
+pub(crate) fn register_connection(member: GroupMember) {
+    GLOBAL.add_connection(member);
+}
+
+/// Register a new listener with the process-wide registry
+pub(crate) fn register_listener(listener: StopHandle) {
+    GLOBAL.add_listener(listener);
+}
+
+/// Listeners and connections that are drained together
+#[derive(Debug, Default)]
+pub(crate) struct Registry {
+    connections: Mutex<Vec<GroupMember>>,
+    listeners: Mutex<Vec<StopHandle>>,
+}
+
+impl Registry {
+    pub(crate) const fn new() -> Self {
+        Self {
+            connections: Mutex::new(Vec::new()),
+            listeners: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn add_connection(&self, member: GroupMember) {
+        let mut connections = self.connections.lock().unwrap();
+        connections.retain(|member| member.inner.strong_count() > 0);
+        connections.push(member);
+    }
+
+    pub(crate) fn add_listener(&self, listener: StopHandle) {
+        let mut listeners = self.listeners.lock().unwrap();
+        listeners.retain(StopHandle::is_live);
+        listeners.push(listener);
+    }
+
+    /// Stop the listeners, then close the connections within `timeout`
+    pub(crate) async fn drain(&self, timeout: Duration) -> ShutdownSummary {
+        let mut summary = ShutdownSummary::default();
+
+        let listeners = std::mem::take(&mut *self.listeners.lock().unwrap());
+        summary.listeners_stopped = listeners.iter().filter(|listener| listener.stop()).count();
+
+        let members = self.connections.lock().unwrap().clone();
+        let mut open = Vec::new();
+        for connection in members.iter().filter_map(Connection::from_group_member) {
+            if connection.state().await != ConnectionState::Closed {
+                open.push(connection);
+            }
+        }
+
+        // Connections already closing elsewhere are waited for, not closed again
+        let closing = futures::future::join_all(open.iter().map(|connection| async move {
+            let _ = connection.close().await;
+            let _ = connection.wait_for_closed(None).await;
+        }));
+        if !open.is_empty() {
+            let _ = runtime::timeout(timeout, closing).await;
+        }
+
+        for connection in &open {
+            if connection.state().await == ConnectionState::Closed {
+                summary.closed += 1;
+            } else {
+                let _ = connection
+                    .abort_internal("Shutdown deadline passed".to_string())
+                    .await;
+                summary.aborted += 1;
+            }
+        }
+        log::debug!("Shutdown finished: {summary:?}");
+        summary
+    }
+}
@@ -0,0 +1,177 @@
+//! One-line connect and listen
+//! A facade over Preconnection for the common cases: connect to
+//! `host:port`, optionally over TLS, or listen on a local address. The
+//! Connections and Listeners returned are the full RFC 9622 objects.
+
+use crate::{
+    runtime, Connection, Listener, LocalEndpoint, Preconnection, RemoteEndpoint, Result,
+    SecurityParameters, TransportProperties, TransportServicesError,
+};
+use std::future::{Future, IntoFuture};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+/// How long `connect` waits for establishment unless told otherwise
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Connect to `target`, given as `host:port` or as a URL such as
+/// `tls://example.com:443`
+///
+/// The connection is TCP unless the URL names another protocol. Await the
+/// result to get an established Connection:
+///
+/// ```no_run
+/// # async fn example() -> transport_services::Result<()> {
+/// let connection = transport_services::connect("example.com:443").secure().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn connect(target: impl Into<String>) -> Connect {
+    Connect {
+        target: target.into(),
+        security: Security::Default,
+        timeout: DEFAULT_CONNECT_TIMEOUT,
+    }
+}
+
+/// Listen on `address`, such as `0.0.0.0:8080` or `[::]:0`
+pub async fn listen(address: &str) -> Result<Listener> {
+    let address: SocketAddr = address.parse().map_err(|_| {
+        TransportServicesError::InvalidParameters(format!("Invalid listen address: {address}"))
+    })?;
+    let local = LocalEndpoint::builder()
+        .ip_address(address.ip())
+        .port(address.port())
+        .build();
+    Preconnection::new(
+        vec![local],
+        vec![],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    )
+    .listen()
+    .await
+}
+
+/// A connection attempt being configured; await it to connect
+pub struct Connect {
+    target: String,
+    security: Security,
+    timeout: Duration,
+}
+
+enum Security {
+    // As the target implies: secured for tls and https URLs
+    Default,
+    // Secured, trusting the system's root certificates unless told otherwise
+    Secure,
+    Parameters(Box<SecurityParameters>),
+}
+
+impl Connect {
+    /// Secure the connection with TLS once established
+    ///
+    /// The server's certificate is verified against the system's trusted
+    /// roots and the target's host name.
+    pub fn secure(mut self) -> Self {
+        self.security = Security::Secure;
+        self
+    }
+
+    /// Secure the connection with TLS using `parameters`, e.g. with pinned
+    /// certificates or ALPN protocols
+    pub fn security(mut self, parameters: SecurityParameters) -> Self {
+        self.security = Security::Parameters(Box::new(parameters));
+        self
+    }
+
+    /// Give up if the connection is not established and secured within
+    /// `timeout`; 30 seconds by default
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    async fn run(self) -> Result<Connection> {
+        let preconnection = if self.target.contains("://") {
+            Preconnection::from_url(&self.target)?
+        } else {
+            Preconnection::new(
+                vec![],
+                vec![remote_endpoint(&self.target)?],
+                TransportProperties::default(),
+                SecurityParameters::new_disabled(),
+            )
+        };
+        let parameters = match self.security {
+            Security::Default => preconnection.security_parameters().await,
+            Security::Secure => SecurityParameters {
+                disabled: false,
+                ..preconnection.security_parameters().await
+            },
+            Security::Parameters(parameters) => *parameters,
+        };
+        let parameters = if parameters.disabled {
+            None
+        } else {
+            Some(with_default_trust(parameters))
+        };
+
+        let deadline = Instant::now() + self.timeout;
+        let connection = preconnection
+            .initiate_with_timeout(Some(self.timeout))
+            .await?;
+        connection.wait_for_established(Some(self.timeout)).await?;
+        if let Some(parameters) = parameters {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match runtime::timeout(remaining, connection.start_security(parameters)).await {
+                Ok(result) => result?,
+                Err(_) => {
+                    connection.abort().await?;
+                    return Err(TransportServicesError::Timeout);
+                }
+            }
+        }
+        Ok(connection)
+    }
+}
+
+impl IntoFuture for Connect {
+    type Output = Result<Connection>;
+    type IntoFuture = Pin<Box<dyn Future<Output = Result<Connection>> + Send>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.run())
+    }
+}
+
+/// Parse `host:port`, with IPv6 addresses in brackets
+fn remote_endpoint(target: &str) -> Result<RemoteEndpoint> {
+    if let Ok(address) = target.parse::<SocketAddr>() {
+        return Ok(RemoteEndpoint::builder().socket_address(address).build());
+    }
+    let invalid = || {
+        TransportServicesError::InvalidParameters(format!(
+            "Invalid target {target:?}: expected host:port"
+        ))
+    };
+    let (host, port) = target.rsplit_once(':').ok_or_else(invalid)?;
+    let port = port.parse::<u16>().map_err(|_| invalid())?;
+    if host.is_empty() || host.contains(':') {
+        return Err(invalid());
+    }
+    Ok(RemoteEndpoint::builder().hostname(host).port(port).build())
+}
+
+/// Trust the system's roots when no other trust is configured
+fn with_default_trust(parameters: SecurityParameters) -> SecurityParameters {
+    #[cfg(feature = "tls")]
+    if parameters.pinned_server_certificate.is_empty() && parameters.pinned_peers.is_empty() {
+        return SecurityParameters {
+            pinned_server_certificate: vec![crate::tls::system_trust_anchors()],
+            ..parameters
+        };
+    }
+    parameters
+}
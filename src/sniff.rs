@@ -0,0 +1,241 @@
+//! Protocol sniffing for Listeners serving several protocols on one port
+//! The start of each incoming stream is peeked, without consuming it, before
+//! the Connection is delivered, so the application can pick framers and a
+//! handler from what the peer sent first
+
+use crate::FramerStack;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+
+/// Largest TLS record a ClientHello is read from
+const MAX_CLIENT_HELLO: usize = 16 * 1024 + 5;
+
+/// Pause between peeks while waiting for more of the peer's first bytes
+const PEEK_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Chooses the framers for a Connection from what its peer sent first
+pub type FramerSelector = Arc<dyn Fn(&SniffedData) -> FramerStack + Send + Sync>;
+
+/// How a Listener peeks at incoming streams
+#[derive(Clone)]
+pub struct ProtocolSniffer {
+    /// Bytes to peek before delivering the Connection
+    ///
+    /// A TLS ClientHello is peeked whole even if longer.
+    pub peek_bytes: usize,
+    /// Longest time to wait for them; the Connection is then delivered with
+    /// whatever arrived, e.g. nothing for protocols where the server speaks first
+    pub timeout: Duration,
+    framer_selector: Option<FramerSelector>,
+}
+
+impl std::fmt::Debug for ProtocolSniffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProtocolSniffer")
+            .field("peek_bytes", &self.peek_bytes)
+            .field("timeout", &self.timeout)
+            .field("framer_selector", &self.framer_selector.is_some())
+            .finish()
+    }
+}
+
+impl ProtocolSniffer {
+    /// Peek up to `peek_bytes`, waiting at most one second for them
+    pub fn new(peek_bytes: usize) -> Self {
+        Self {
+            peek_bytes,
+            timeout: Duration::from_secs(1),
+            framer_selector: None,
+        }
+    }
+
+    /// Set the longest time to wait for the peer's first bytes
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the framers of each Connection from what its peer sent first
+    pub fn with_framer_selector<F>(mut self, selector: F) -> Self
+    where
+        F: Fn(&SniffedData) -> FramerStack + Send + Sync + 'static,
+    {
+        self.framer_selector = Some(Arc::new(selector));
+        self
+    }
+
+    /// Peek at the start of an accepted stream and choose its framers
+    pub(crate) async fn sniff(&self, stream: &TcpStream) -> std::io::Result<Sniffed> {
+        let data = self.peek(stream).await?;
+        let framers = self
+            .framer_selector
+            .as_ref()
+            .map(|selector| selector(&data));
+        Ok(Sniffed { data, framers })
+    }
+
+    async fn peek(&self, stream: &TcpStream) -> std::io::Result<SniffedData> {
+        let deadline = Instant::now() + self.timeout;
+        let mut buffer = vec![0u8; self.peek_bytes.max(5)];
+        let mut peeked = 0;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let peek = crate::runtime::timeout(remaining, stream.peek(&mut buffer)).await;
+            let n = match peek {
+                Ok(result) => result?,
+                Err(_) => break,
+            };
+            // Nothing arrived since the previous peek
+            let stalled = n == peeked;
+            peeked = n;
+
+            let wanted = match tls_record_length(&buffer[..n]) {
+                Some(length) => (length + 5).min(MAX_CLIENT_HELLO).max(self.peek_bytes),
+                None => self.peek_bytes,
+            };
+            if wanted > buffer.len() {
+                buffer.resize(wanted, 0);
+                continue;
+            }
+            // No data means the peer closed without sending any
+            if n >= wanted || n == 0 || Instant::now() >= deadline {
+                break;
+            }
+            // Peeking returns at once while any data is queued, so wait
+            // before looking for more
+            if stalled {
+                crate::runtime::sleep(PEEK_INTERVAL.min(remaining)).await;
+            }
+        }
+
+        let client_hello = parse_client_hello(&buffer[..peeked]);
+        buffer.truncate(peeked.min(self.peek_bytes));
+        Ok(SniffedData {
+            data: buffer,
+            client_hello,
+        })
+    }
+}
+
+/// A sniffed stream's data and the framers chosen for it
+pub(crate) struct Sniffed {
+    pub(crate) data: SniffedData,
+    pub(crate) framers: Option<FramerStack>,
+}
+
+/// What a Listener learned from the start of an incoming stream
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SniffedData {
+    /// The first bytes the peer sent, at most the requested number
+    pub data: Vec<u8>,
+    /// The TLS ClientHello, if the peer started a TLS handshake
+    pub client_hello: Option<ClientHelloInfo>,
+}
+
+/// What a TLS ClientHello asks for
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientHelloInfo {
+    /// Host name from the server_name extension (SNI)
+    pub server_name: Option<String>,
+    /// Protocols offered through ALPN, in the client's order of preference
+    pub alpn: Vec<String>,
+}
+
+/// Length of the TLS handshake record starting `data`, if it starts one
+fn tls_record_length(data: &[u8]) -> Option<usize> {
+    match data {
+        [0x16, 0x03, _, high, low, ..] => Some(u16::from_be_bytes([*high, *low]) as usize),
+        _ => None,
+    }
+}
+
+/// Parse the ClientHello in the first TLS record of `data`
+///
+/// Returns None unless the record is a complete ClientHello.
+pub(crate) fn parse_client_hello(data: &[u8]) -> Option<ClientHelloInfo> {
+    let length = tls_record_length(data)?;
+    let mut reader = Reader(data.get(5..5 + length)?);
+
+    // Handshake header: type 1 is ClientHello
+    if reader.u8()? != 1 {
+        return None;
+    }
+    let hello_length = reader.u24()?;
+    let mut hello = Reader(reader.take(hello_length)?);
+    hello.take(2 + 32)?; // legacy_version, random
+    let session_id = hello.u8()? as usize;
+    hello.take(session_id)?;
+    let cipher_suites = hello.u16()? as usize;
+    hello.take(cipher_suites)?;
+    let compression_methods = hello.u8()? as usize;
+    hello.take(compression_methods)?;
+
+    let mut info = ClientHelloInfo::default();
+    if hello.0.is_empty() {
+        return Some(info);
+    }
+    let extensions_length = hello.u16()? as usize;
+    let mut extensions = Reader(hello.take(extensions_length)?);
+    while !extensions.0.is_empty() {
+        let kind = extensions.u16()?;
+        let length = extensions.u16()? as usize;
+        let mut extension = Reader(extensions.take(length)?);
+        match kind {
+            // server_name: a list of typed names, of which host_name is 0
+            0 => {
+                let list_length = extension.u16()? as usize;
+                let mut names = Reader(extension.take(list_length)?);
+                while !names.0.is_empty() {
+                    let name_type = names.u8()?;
+                    let name_length = names.u16()? as usize;
+                    let name = names.take(name_length)?;
+                    if name_type == 0 {
+                        info.server_name = Some(String::from_utf8_lossy(name).into_owned());
+                    }
+                }
+            }
+            // application_layer_protocol_negotiation
+            16 => {
+                let list_length = extension.u16()? as usize;
+                let mut protocols = Reader(extension.take(list_length)?);
+                while !protocols.0.is_empty() {
+                    let protocol_length = protocols.u8()? as usize;
+                    let protocol = protocols.take(protocol_length)?;
+                    info.alpn
+                        .push(String::from_utf8_lossy(protocol).into_owned());
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(info)
+}
+
+/// Reads big-endian fields from the front of a slice
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u24(&mut self) -> Option<usize> {
+        self.take(3)
+            .map(|b| u32::from_be_bytes([0, b[0], b[1], b[2]]) as usize)
+    }
+}
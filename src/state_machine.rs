@@ -0,0 +1,28 @@
+//! Connection state machine
+//! Based on RFC 9622 Section 11 (Connection State and Ordering of Operations and Events)
+//!
+//! The transition rules are kept free of I/O and locking so they can be
+//! checked exhaustively; `Connection` consults them on every state change.
+
+use crate::ConnectionState;
+
+/// Check whether a connection may move from one state to another
+///
+/// States only move forward: Establishing, Established, Closing, Closed.
+/// Closing may be skipped (abort, failure or peer close) and so may
+/// Established (close or failure during establishment). Staying in the same
+/// state is not a transition.
+pub fn is_valid_transition(from: ConnectionState, to: ConnectionState) -> bool {
+    use ConnectionState::*;
+    matches!(
+        (from, to),
+        (Establishing, Established | Closing | Closed)
+            | (Established, Closing | Closed)
+            | (Closing, Closed)
+    )
+}
+
+/// Check whether a state is terminal
+pub fn is_terminal(state: ConnectionState) -> bool {
+    state == ConnectionState::Closed
+}
@@ -128,7 +128,19 @@ async fn test_background_reading_with_framing() {
     let start = std::time::Instant::now();
     while received_messages.len() < 2 && start.elapsed() < Duration::from_secs(2) {
         match tokio::time::timeout(Duration::from_millis(500), conn.next_event()).await {
-            Ok(Some(ConnectionEvent::Received { message_data, .. })) => {
+            Ok(Some(ConnectionEvent::Received {
+                message_data,
+                message_context,
+            })) => {
+                // Received message properties are exposed on the context
+                let properties = message_context.message_properties();
+                assert_eq!(properties.ordered, Some(true));
+                assert!(!properties.final_message);
+                assert_eq!(
+                    properties.framer_metadata.get("length"),
+                    Some(&(message_data.len() as u32).to_be_bytes().to_vec())
+                );
+                assert!(message_context.remote_endpoint.is_some());
                 received_messages.push(String::from_utf8(message_data).unwrap());
             }
             Ok(Some(_)) => {} // Ignore other events
@@ -289,3 +301,123 @@ async fn test_background_reading_concurrent_with_receive() {
     conn.close().await.unwrap();
     let _ = server_task.await;
 }
+
+#[tokio::test]
+async fn test_background_reading_peer_fin_closes_read_side() {
+    // Start a TCP listener
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // Accept connection, send data and then shut down the write side
+    let server_task = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        sleep(Duration::from_millis(100)).await;
+        stream.write_all(b"Last words").await.unwrap();
+        stream.shutdown().await.unwrap();
+        sleep(Duration::from_millis(500)).await;
+    });
+
+    // Create connection
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+
+    let conn = preconn.initiate().await.expect("Should connect");
+
+    // Wait for ready event
+    match conn.next_event().await {
+        Some(ConnectionEvent::Ready) => {}
+        other => panic!("Expected Ready event, got {other:?}"),
+    }
+
+    // Collect events in order
+    let mut events = Vec::new();
+    let start = std::time::Instant::now();
+    while start.elapsed() < Duration::from_secs(2) {
+        match tokio::time::timeout(Duration::from_millis(500), conn.next_event()).await {
+            Ok(Some(ConnectionEvent::Received { message_data, .. })) => {
+                assert_eq!(message_data, b"Last words");
+                events.push("received");
+            }
+            Ok(Some(ConnectionEvent::FinalReceived)) => events.push("final"),
+            Ok(Some(ConnectionEvent::Closed)) => {
+                events.push("closed");
+                break;
+            }
+            Ok(Some(_)) => {} // Ignore other events
+            Ok(None) => break,
+            Err(_) => {} // Timeout, continue
+        }
+    }
+
+    // The FIN is reported as the end of the peer's data before the close
+    assert_eq!(events, vec!["received", "final", "closed"]);
+
+    // The read side is closed
+    assert!(matches!(
+        conn.get_property("canReceive").await,
+        Some(ConnectionProperty::CanReceive(false))
+    ));
+    assert!(conn.receive().await.is_err());
+
+    let _ = server_task.await;
+}
+
+#[tokio::test]
+async fn test_receive_timestamps_only_when_requested() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server_task = tokio::spawn(async move {
+        for _ in 0..2 {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            sleep(Duration::from_millis(100)).await;
+            stream.write_all(b"stamped").await.unwrap();
+            sleep(Duration::from_millis(500)).await;
+        }
+    });
+
+    for requested in [false, true] {
+        let properties = TransportProperties::builder()
+            .receive_timestamps(requested)
+            .build();
+        let preconn = Preconnection::new(
+            vec![],
+            vec![RemoteEndpoint::builder().socket_address(addr).build()],
+            properties,
+            SecurityParameters::new_disabled(),
+        );
+        let conn = preconn.initiate().await.expect("Should connect");
+
+        let context = loop {
+            match tokio::time::timeout(Duration::from_secs(2), conn.next_event()).await {
+                Ok(Some(ConnectionEvent::Received {
+                    message_data,
+                    message_context,
+                })) => {
+                    assert_eq!(message_data, b"stamped");
+                    break message_context;
+                }
+                Ok(Some(_)) => {}
+                other => panic!("Expected Received event, got {other:?}"),
+            }
+        };
+
+        if !requested {
+            assert!(context.interface_timestamp.is_none());
+        } else if cfg!(target_os = "linux") {
+            // The kernel stamped the data before it was read
+            let timestamp = context.interface_timestamp.expect("Should be timestamped");
+            assert!(timestamp <= context.received_at);
+            assert!(context.received_at - timestamp < Duration::from_secs(1));
+        }
+
+        conn.close().await.unwrap();
+    }
+
+    let _ = server_task.await;
+}
@@ -0,0 +1,48 @@
+//! Tests for protocol stack capability introspection
+
+use crate::*;
+
+#[test]
+fn test_capabilities_reflect_build() {
+    let capabilities = capabilities();
+    assert!(capabilities.tcp);
+    assert!(capabilities.udp);
+    assert_eq!(capabilities.tls, cfg!(feature = "tls"));
+    assert_eq!(capabilities.tcp_fast_open_listen, cfg!(target_os = "linux"));
+    assert_eq!(
+        capabilities.ecn,
+        cfg!(any(
+            target_os = "linux",
+            target_os = "android",
+            target_vendor = "apple"
+        ))
+    );
+}
+
+#[test]
+fn test_capabilities_supports_protocols() {
+    let capabilities = capabilities();
+    assert!(capabilities.supports(Protocol::TCP));
+    assert!(capabilities.supports(Protocol::UDP));
+    assert_eq!(capabilities.supports(Protocol::TLS), capabilities.tls);
+    assert_eq!(capabilities.supports(Protocol::QUIC), capabilities.quic);
+    assert!(!capabilities.supports(Protocol::DTLS));
+    assert_eq!(capabilities.supports(Protocol::SCTP), capabilities.sctp);
+}
+
+#[tokio::test]
+async fn test_unsupported_protocol_fails_initiate() {
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .socket_address("127.0.0.1:443".parse().unwrap())
+            .protocol(Protocol::DTLS)
+            .build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    assert!(matches!(
+        preconn.initiate().await,
+        Err(TransportServicesError::NotSupported(_))
+    ));
+}
@@ -0,0 +1,158 @@
+//! Tests for CBOR message helpers and the CBOR sequence framer (RFC 8742)
+
+use crate::*;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+fn reading() -> BTreeMap<String, i64> {
+    BTreeMap::from([("sensor".to_string(), 7), ("value".to_string(), -40)])
+}
+
+#[test]
+fn test_message_cbor_round_trip() {
+    let message = Message::from_cbor(&(1u8, "a")).unwrap();
+    assert_eq!(message.data(), [0x82, 0x01, 0x61, b'a']);
+    assert_eq!(
+        message.to_cbor::<(u8, String)>().unwrap(),
+        (1, "a".to_string())
+    );
+
+    let message = Message::from_cbor(&reading()).unwrap();
+    assert_eq!(
+        message.to_cbor::<BTreeMap<String, i64>>().unwrap(),
+        reading()
+    );
+}
+
+#[test]
+fn test_message_to_cbor_rejects_invalid_data() {
+    // Trailing bytes after the data item
+    let message = Message::from_bytes(&[0x01, 0x02]);
+    let error = message.to_cbor::<u8>().unwrap_err();
+    assert!(
+        error.to_string().contains("follow the data item"),
+        "{error}"
+    );
+
+    // Truncated item
+    assert!(Message::from_bytes(&[0x82, 0x01])
+        .to_cbor::<Vec<u8>>()
+        .is_err());
+
+    // Well-formed but of the wrong type
+    let message = Message::from_cbor("text").unwrap();
+    assert!(message.to_cbor::<u32>().is_err());
+}
+
+#[tokio::test]
+async fn test_cbor_sequence_framer_splits_items() {
+    let framer = CborSequenceFramer::new();
+    let mut sequence = Message::from_cbor(&reading()).unwrap().data().to_vec();
+    sequence.extend(Message::from_cbor(&[1u16, 500]).unwrap().data());
+    sequence.extend(Message::from_cbor("end").unwrap().data());
+
+    // Items arrive split at arbitrary points
+    let mut messages = Vec::new();
+    for chunk in sequence.chunks(3) {
+        messages.extend(framer.parse_data(chunk).await.unwrap());
+    }
+    assert_eq!(messages.len(), 3);
+    assert_eq!(
+        messages[0].0.to_cbor::<BTreeMap<String, i64>>().unwrap(),
+        reading()
+    );
+    assert_eq!(messages[1].0.to_cbor::<Vec<u16>>().unwrap(), vec![1, 500]);
+    assert_eq!(messages[2].0.to_cbor::<String>().unwrap(), "end");
+    assert!(framer.take_unparsed().is_empty());
+
+    // A reserved additional-information value is malformed
+    let error = framer.parse_data(&[0x1c]).await.unwrap_err();
+    assert!(matches!(error, TransportServicesError::ReceiveFailed(_)));
+}
+
+#[tokio::test]
+async fn test_cbor_sequence_framer_rejects_non_items() {
+    let framer = CborSequenceFramer::new();
+    let context = MessageContext::new();
+
+    let item = Message::from_cbor(&42u32).unwrap();
+    assert_eq!(
+        framer.frame_message(&item, &context).await.unwrap(),
+        item.data()
+    );
+
+    for data in [&[0x01, 0x02][..], &[0x82, 0x01][..], &[][..]] {
+        let message = Message::from_bytes(data);
+        assert!(
+            matches!(
+                framer.frame_message(&message, &context).await,
+                Err(TransportServicesError::SendFailed(_))
+            ),
+            "{data:?} was framed"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_cbor_sequence_over_tcp() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let mut sequence = Message::from_cbor(&reading()).unwrap().data().to_vec();
+    sequence.extend(Message::from_cbor(&true).unwrap().data());
+    let reply = Message::from_cbor(&("ack", 2u8)).unwrap().data().to_vec();
+    let expected = reply.len();
+
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        // The first item is written in two parts
+        let (first, rest) = sequence.split_at(5);
+        stream.write_all(first).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        stream.write_all(rest).await.unwrap();
+
+        let mut buf = vec![0u8; expected];
+        stream.read_exact(&mut buf).await.unwrap();
+        buf
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    preconn
+        .add_framer(|| Box::new(CborSequenceFramer::new()))
+        .await;
+    let conn = preconn.initiate().await.unwrap();
+
+    let mut received = Vec::new();
+    while received.len() < 2 {
+        match tokio::time::timeout(Duration::from_secs(2), conn.next_event()).await {
+            Ok(Some(ConnectionEvent::Received { message_data, .. })) => {
+                received.push(Message::new(message_data))
+            }
+            Ok(Some(_)) => {}
+            other => panic!("Expected Received event, got {other:?}"),
+        }
+    }
+    assert_eq!(
+        received[0].to_cbor::<BTreeMap<String, i64>>().unwrap(),
+        reading()
+    );
+    assert!(received[1].to_cbor::<bool>().unwrap());
+
+    conn.send(Message::from_cbor(&("ack", 2u8)).unwrap())
+        .await
+        .unwrap();
+    let written = tokio::time::timeout(Duration::from_secs(2), server)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(written, reply);
+
+    conn.close().await.unwrap();
+}
@@ -333,3 +333,275 @@ async fn test_connection_group_creation() {
     assert_eq!(group.connection_count(), 0);
     assert!(!group.has_connections());
 }
+
+#[tokio::test]
+async fn test_connection_group_members_and_count() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buf = [0; 1024];
+                let _ = tokio::io::AsyncReadExt::read(&mut stream, &mut buf).await;
+            });
+        }
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .socket_address(server_addr)
+            .build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+
+    let conn1 = preconn.initiate().await.unwrap();
+    while conn1.state().await == ConnectionState::Establishing {
+        sleep(Duration::from_millis(10)).await;
+    }
+    let conn2 = conn1.clone_connection().await.unwrap();
+    let conn3 = conn1.clone_connection().await.unwrap();
+    for conn in [&conn2, &conn3] {
+        while conn.state().await == ConnectionState::Establishing {
+            sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    let group = conn1.connection_group().await.unwrap();
+    assert_eq!(group.members().await.len(), 3);
+    assert_eq!(group.connection_count(), 3);
+
+    // Members are live handles sharing state with the originals
+    for member in group.members().await {
+        assert_eq!(member.state().await, ConnectionState::Established);
+        assert_eq!(member.connection_group_id().await, Some(group.id));
+    }
+
+    // Aborting outside the group APIs still keeps the count consistent
+    conn2.abort().await.unwrap();
+    assert_eq!(group.connection_count(), 2);
+    assert_eq!(group.members().await.len(), 2);
+
+    // Closing twice does not double-count
+    conn3.close().await.unwrap();
+    conn3.close().await.unwrap();
+    assert_eq!(group.connection_count(), 1);
+
+    // Dropped members are pruned
+    drop(conn2);
+    drop(conn3);
+    sleep(Duration::from_millis(50)).await;
+    assert_eq!(group.members().await.len(), 1);
+
+    // Group-wide termination updates the count too
+    conn1.close_group().await.unwrap();
+    assert_eq!(group.connection_count(), 0);
+    assert!(group.members().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_proportional_rate_scheduling_follows_weights() {
+    use crate::{ConnectionProperty, Message, SchedulerType};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    // The peer drains everything it is sent
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buf = [0; 65536];
+                while let Ok(n) = tokio::io::AsyncReadExt::read(&mut stream, &mut buf).await {
+                    if n == 0 {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .socket_address(server_addr)
+            .build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let light = preconn.initiate().await.unwrap();
+    light
+        .wait_for_established(Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+    let heavy = light.clone_connection().await.unwrap();
+    heavy
+        .wait_for_established(Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+
+    let group = light.connection_group().await.unwrap();
+    light
+        .set_property(
+            "connScheduler",
+            ConnectionProperty::ConnScheduler(SchedulerType::ProportionalRate),
+        )
+        .await
+        .unwrap();
+    group.set_weight(&heavy, 3).await.unwrap();
+    assert_eq!(group.weight(&light).await, Some(1));
+    assert_eq!(group.weight(&heavy).await, Some(3));
+    assert!(group.set_weight(&heavy, 0).await.is_err());
+
+    // Both members keep sending until stopped
+    let stop = Arc::new(AtomicBool::new(false));
+    let senders = [light.clone(), heavy.clone()].map(|conn| {
+        let stop = Arc::clone(&stop);
+        tokio::spawn(async move {
+            while !stop.load(Ordering::Relaxed) {
+                conn.send(Message::new(vec![0u8; 1024])).await.unwrap();
+            }
+        })
+    });
+    sleep(Duration::from_millis(300)).await;
+    stop.store(true, Ordering::Relaxed);
+    for sender in senders {
+        sender.await.unwrap();
+    }
+
+    let light_bytes = light.stats().await.bytes_sent as f64;
+    let heavy_bytes = heavy.stats().await.bytes_sent as f64;
+    let ratio = heavy_bytes / light_bytes;
+    assert!(
+        (2.5..=3.5).contains(&ratio),
+        "Sent {heavy_bytes} and {light_bytes} bytes, a ratio of {ratio:.2}"
+    );
+}
+
+#[tokio::test]
+async fn test_set_weight_rejects_other_connections() {
+    let group = ConnectionGroup::new(TransportProperties::default(), vec![], vec![]);
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .socket_address("127.0.0.1:9".parse().unwrap())
+            .build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.unwrap();
+    assert!(group.set_weight(&conn, 2).await.is_err());
+    assert_eq!(group.weight(&conn).await, None);
+}
+
+/// Listen on loopback, draining everything each connection is sent
+async fn draining_peer() -> std::net::SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buf = [0; 65536];
+                while let Ok(n) = tokio::io::AsyncReadExt::read(&mut stream, &mut buf).await {
+                    if n == 0 {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    addr
+}
+
+/// Establish two connections to `addr` in one group
+async fn grouped_pair(addr: std::net::SocketAddr) -> (crate::Connection, crate::Connection) {
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let first = preconn.initiate().await.unwrap();
+    first
+        .wait_for_established(Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+    let second = first.clone_connection().await.unwrap();
+    second
+        .wait_for_established(Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+    (first, second)
+}
+
+#[tokio::test]
+async fn test_group_send_rate_caps_members_together() {
+    use crate::Message;
+
+    let (first, second) = grouped_pair(draining_peer().await).await;
+    let group = first.connection_group().await.unwrap();
+    assert_eq!(group.max_send_rate(), None);
+    assert!(group.set_max_send_rate(Some(0)).is_err());
+
+    // 200 kB/s shared by both members
+    group.set_max_send_rate(Some(1_600_000)).unwrap();
+    assert_eq!(group.max_send_rate(), Some(1_600_000));
+
+    // 100 kB between them takes about half a second, the first send free
+    let started = std::time::Instant::now();
+    let senders = [first.clone(), second.clone()].map(|conn| {
+        tokio::spawn(async move {
+            for _ in 0..10 {
+                conn.send(Message::new(vec![0u8; 5000])).await.unwrap();
+            }
+        })
+    });
+    for sender in senders {
+        sender.await.unwrap();
+    }
+    let elapsed = started.elapsed();
+    assert!(
+        elapsed >= Duration::from_millis(400) && elapsed < Duration::from_secs(3),
+        "Sent in {elapsed:?}"
+    );
+    assert_eq!(
+        first.stats().await.bytes_sent + second.stats().await.bytes_sent,
+        100_000
+    );
+
+    // Lifting the cap lets them send freely again
+    group.set_max_send_rate(None).unwrap();
+    let started = std::time::Instant::now();
+    for _ in 0..10 {
+        first.send(Message::new(vec![0u8; 5000])).await.unwrap();
+    }
+    assert!(started.elapsed() < Duration::from_millis(200));
+}
+
+#[tokio::test]
+async fn test_member_send_rate_applies_under_group_cap() {
+    use crate::{ConnectionProperty, Message};
+
+    let (first, _second) = grouped_pair(draining_peer().await).await;
+    let group = first.connection_group().await.unwrap();
+    group.set_max_send_rate(Some(80_000_000)).unwrap();
+
+    // The member's own 100 kB/s is the tighter limit
+    first
+        .set_property(
+            "maxSendRate",
+            ConnectionProperty::MaxSendRate(Some(800_000)),
+        )
+        .await
+        .unwrap();
+    let started = std::time::Instant::now();
+    for _ in 0..10 {
+        first.send(Message::new(vec![0u8; 5000])).await.unwrap();
+    }
+    let elapsed = started.elapsed();
+    assert!(
+        elapsed >= Duration::from_millis(400) && elapsed < Duration::from_secs(3),
+        "Sent in {elapsed:?}"
+    );
+}
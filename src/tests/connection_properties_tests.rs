@@ -283,3 +283,105 @@ async fn test_rate_limits() {
         panic!("Min send rate not found");
     }
 }
+
+#[test]
+fn test_protocol_property_namespaces() {
+    assert_eq!(
+        PropertyNamespace::of("tcp.userTimeoutValue"),
+        Some(PropertyNamespace::Tcp)
+    );
+    assert_eq!(
+        PropertyNamespace::of("quic.maxIdleTimeout"),
+        Some(PropertyNamespace::Quic)
+    );
+    assert_eq!(
+        PropertyNamespace::of("sctp.heartbeatInterval"),
+        Some(PropertyNamespace::Sctp)
+    );
+    assert_eq!(PropertyNamespace::of("connPriority"), None);
+
+    let mut props = ConnectionProperties::new();
+    props
+        .set(
+            "quic.maxIdleTimeout",
+            ConnectionProperty::QuicMaxIdleTimeout(TimeoutValue::Duration(Duration::from_secs(30))),
+        )
+        .expect("Should set QUIC property");
+    props
+        .set(
+            "quic.congestionAlgorithm",
+            ConnectionProperty::QuicCongestionAlgorithm(CongestionAlgorithm::Bbr),
+        )
+        .expect("Should set QUIC property");
+    props
+        .set(
+            "sctp.numOutboundStreams",
+            ConnectionProperty::SctpNumOutboundStreams(16),
+        )
+        .expect("Should set SCTP property");
+    assert!(matches!(
+        props.get("quic.congestionAlgorithm"),
+        Some(ConnectionProperty::QuicCongestionAlgorithm(
+            CongestionAlgorithm::Bbr
+        ))
+    ));
+}
+
+#[test]
+fn test_protocol_property_validation() {
+    let mut props = ConnectionProperties::new();
+
+    // Unknown name in a protocol namespace
+    assert!(matches!(
+        props.set("quic.maxStreams", ConnectionProperty::QuicMaxStreamsBidi(8)),
+        Err(TransportServicesError::InvalidParameters(_))
+    ));
+
+    // Value stored under another property's name
+    assert!(matches!(
+        props.set(
+            "sctp.maxInboundStreams",
+            ConnectionProperty::SctpNumOutboundStreams(8)
+        ),
+        Err(TransportServicesError::InvalidParameters(_))
+    ));
+
+    // Protocol-specific value stored under a generic name
+    assert!(matches!(
+        props.set("connPriority", ConnectionProperty::QuicMaxStreamsUni(8)),
+        Err(TransportServicesError::InvalidParameters(_))
+    ));
+    assert!(!props.has("quic.maxStreams"));
+}
+
+#[tokio::test]
+async fn test_initial_rate_limits_from_transport_properties() {
+    let mut properties = TransportProperties::default();
+    properties.connection_properties.max_send_rate = Some(2_000_000);
+    properties.connection_properties.max_recv_rate = Some(4_000_000);
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .hostname("example.com")
+            .port(443)
+            .build()],
+        properties.clone(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = Connection::new_with_data(
+        preconn,
+        ConnectionState::Established,
+        None,
+        None,
+        properties,
+    );
+
+    assert!(matches!(
+        conn.get_property("maxSendRate").await,
+        Some(ConnectionProperty::MaxSendRate(Some(2_000_000)))
+    ));
+    assert!(matches!(
+        conn.get_property("maxRecvRate").await,
+        Some(ConnectionProperty::MaxRecvRate(Some(4_000_000)))
+    ));
+}
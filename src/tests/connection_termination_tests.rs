@@ -383,3 +383,130 @@ async fn test_termination_events_are_terminal() {
     .await
     .expect("Test should complete within timeout");
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_drop_last_handle_closes_connection() {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let preconn = Preconnection::new(
+            vec![],
+            vec![RemoteEndpoint::builder().socket_address(addr).build()],
+            TransportProperties::default(),
+            SecurityParameters::new_disabled(),
+        );
+
+        let conn = preconn.initiate().await.expect("Should connect");
+        let (mut server, _) = listener.accept().await.unwrap();
+        match conn.next_event().await {
+            Some(ConnectionEvent::Ready) => {}
+            other => panic!("Expected Ready event, got {other:?}"),
+        }
+        assert_eq!(conn.drop_policy().await, DropPolicy::Close);
+
+        // Dropping one of several handles keeps the connection open
+        let other = conn.clone();
+        drop(conn);
+        let mut buf = [0u8; 16];
+        assert!(
+            tokio::time::timeout(Duration::from_millis(200), server.read(&mut buf))
+                .await
+                .is_err(),
+            "Connection should stay open while a handle remains"
+        );
+        assert_eq!(other.state().await, ConnectionState::Established);
+
+        // Dropping the last handle closes it gracefully
+        drop(other);
+        let n = server.read(&mut buf).await.expect("Should see FIN");
+        assert_eq!(n, 0);
+    })
+    .await
+    .expect("Test should complete within timeout");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_drop_policy_abort_and_linger() {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        for policy in [
+            DropPolicy::Abort,
+            DropPolicy::Linger(Duration::from_millis(100)),
+        ] {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+
+            let preconn = Preconnection::new(
+                vec![],
+                vec![RemoteEndpoint::builder().socket_address(addr).build()],
+                TransportProperties::default(),
+                SecurityParameters::new_disabled(),
+            );
+
+            let conn = preconn.initiate().await.expect("Should connect");
+            let (mut server, _) = listener.accept().await.unwrap();
+            match conn.next_event().await {
+                Some(ConnectionEvent::Ready) => {}
+                other => panic!("Expected Ready event, got {other:?}"),
+            }
+
+            conn.set_drop_policy(policy).await;
+            assert_eq!(conn.drop_policy().await, policy);
+            drop(conn);
+
+            // The peer observes the connection going away either way
+            let mut buf = [0u8; 16];
+            match server.read(&mut buf).await {
+                Ok(0) | Err(_) => {}
+                Ok(n) => panic!("Unexpected {n} bytes after drop with {policy:?}"),
+            }
+        }
+    })
+    .await
+    .expect("Test should complete within timeout");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_abort_resets_tcp_connection() {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let preconn = Preconnection::new(
+            vec![],
+            vec![RemoteEndpoint::builder().socket_address(addr).build()],
+            TransportProperties::default(),
+            SecurityParameters::new_disabled(),
+        );
+
+        let conn = preconn.initiate().await.expect("Should connect");
+        let (mut server, _) = listener.accept().await.unwrap();
+        match conn.next_event().await {
+            Some(ConnectionEvent::Ready) => {}
+            other => panic!("Expected Ready event, got {other:?}"),
+        }
+
+        conn.abort_with_code(42, "shutting down")
+            .await
+            .expect("Should abort");
+
+        match conn.next_event().await {
+            Some(ConnectionEvent::ConnectionError(msg)) => {
+                assert!(msg.contains("aborted"));
+                assert!(msg.contains("42"));
+                assert!(msg.contains("shutting down"));
+            }
+            other => panic!("Expected ConnectionError event, got {other:?}"),
+        }
+
+        // The peer sees a reset rather than an orderly FIN
+        let mut buf = [0u8; 16];
+        let err = server
+            .read(&mut buf)
+            .await
+            .expect_err("Abort should reset the connection");
+        assert_eq!(err.kind(), std::io::ErrorKind::ConnectionReset);
+    })
+    .await
+    .expect("Test should complete within timeout");
+}
@@ -243,9 +243,7 @@ async fn test_queued_messages() {
         connection.send(msg2).await.unwrap();
 
         // Wait for establishment
-        while connection.state().await == ConnectionState::Establishing {
-            tokio::time::sleep(Duration::from_millis(50)).await;
-        }
+        connection.wait_for_established(None).await.unwrap();
 
         // Messages should have been sent automatically
         // (We can't verify this without receive, but at least no errors)
@@ -257,3 +255,103 @@ async fn test_queued_messages() {
         .await
         .expect("Test timed out");
 }
+
+#[tokio::test]
+async fn test_state_watch_and_wait_helpers() {
+    let addr = start_echo_server().await;
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+
+    let connection = preconn.initiate().await.unwrap();
+    let mut watch = connection.state_watch().await;
+
+    connection
+        .wait_for_established(Some(Duration::from_secs(5)))
+        .await
+        .unwrap();
+    assert_eq!(*watch.borrow_and_update(), ConnectionState::Established);
+
+    // Still open, so waiting for closure times out
+    let result = connection
+        .wait_for_closed(Some(Duration::from_millis(50)))
+        .await;
+    assert!(matches!(result, Err(TransportServicesError::Timeout)));
+
+    connection.close().await.unwrap();
+    watch.changed().await.unwrap();
+    connection
+        .wait_for_closed(Some(Duration::from_secs(1)))
+        .await
+        .unwrap();
+    assert_eq!(*watch.borrow(), ConnectionState::Closed);
+}
+
+#[tokio::test]
+async fn test_wait_for_established_fails_when_unreachable() {
+    // Bind and drop a listener so the port refuses connections
+    let addr = {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap()
+    };
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+
+    let connection = preconn.initiate().await.unwrap();
+    let result = connection
+        .wait_for_established(Some(Duration::from_secs(5)))
+        .await;
+    assert!(matches!(
+        result,
+        Err(TransportServicesError::EstablishmentFailed(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_receive_timeout_and_try_receive() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (send_now, send_signal) = tokio::sync::oneshot::channel::<()>();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let _ = send_signal.await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        stream.write_all(b"eventually").await.unwrap();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.unwrap();
+    conn.wait_for_established(Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+
+    // Nothing has been sent yet
+    assert!(conn.try_receive().await.unwrap().is_none());
+    let started = std::time::Instant::now();
+    assert!(matches!(
+        conn.receive_timeout(Duration::from_millis(100)).await,
+        Err(TransportServicesError::Timeout)
+    ));
+    assert!(started.elapsed() >= Duration::from_millis(100));
+
+    // A message arriving before the deadline is delivered
+    send_now.send(()).unwrap();
+    let (message, _) = conn.receive_timeout(Duration::from_secs(2)).await.unwrap();
+    assert_eq!(message.data(), b"eventually");
+
+    conn.abort().await.unwrap();
+    assert!(conn.try_receive().await.is_err());
+}
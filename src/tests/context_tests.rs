@@ -0,0 +1,114 @@
+//! Tests for the shared Transport Services context
+
+use crate::*;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+fn preconnection_to(addrs: &[SocketAddr]) -> Preconnection {
+    Preconnection::new(
+        vec![],
+        addrs
+            .iter()
+            .map(|addr| RemoteEndpoint::builder().socket_address(*addr).build())
+            .collect(),
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    )
+}
+
+#[tokio::test]
+async fn test_candidate_policy_vetoes_candidates() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let context = TransportServices::new()
+        .with_candidate_policy(|candidates| candidates.filter(|c| !c.address.ip().is_loopback()));
+    let preconn = preconnection_to(&[addr]);
+    preconn.set_context(Arc::new(context)).await.unwrap();
+
+    match preconn.initiate().await {
+        Err(TransportServicesError::EstablishmentFailed(_)) => {}
+        other => panic!("Expected the policy to reject all candidates, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_candidate_policy_selects_candidates() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let live = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let _ = listener.accept().await;
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    });
+    let closed = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let seen = Arc::new(AtomicUsize::new(0));
+    let policy_seen = seen.clone();
+    let context = TransportServices::new().with_candidate_policy(move |candidates| {
+        policy_seen.store(candidates.len(), Ordering::SeqCst);
+        candidates.filter(|c| c.address == live)
+    });
+    let preconn = preconnection_to(&[closed, live]);
+    preconn.set_context(Arc::new(context)).await.unwrap();
+
+    let conn = preconn.initiate().await.unwrap();
+    conn.wait_for_established(Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+    assert_eq!(seen.load(Ordering::SeqCst), 2);
+    assert_eq!(
+        conn.remote_endpoint().await.unwrap().identifiers,
+        vec![EndpointIdentifier::SocketAddress(live)]
+    );
+}
+
+#[tokio::test]
+async fn test_context_shares_resolution_cache() {
+    let context = Arc::new(TransportServices::new());
+    let addr: SocketAddr = "192.0.2.1:443".parse().unwrap();
+    context
+        .resolution_cache()
+        .insert("service.test", 443, vec![addr], Duration::from_secs(60));
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .hostname("service.test")
+            .port(443)
+            .build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    preconn.set_context(context.clone()).await.unwrap();
+    assert!(Arc::ptr_eq(&preconn.context().await.unwrap(), &context));
+
+    let (_, remotes) = preconn.resolve().await.unwrap();
+    assert!(remotes.iter().any(|remote| remote
+        .identifiers
+        .contains(&EndpointIdentifier::SocketAddress(addr))));
+}
+
+#[test]
+fn test_session_cache_evicts_oldest_and_expired() {
+    let cache = SessionCache::with_limits(2, Duration::from_secs(60));
+    cache.insert("a.test", vec![1]);
+    std::thread::sleep(Duration::from_millis(5));
+    cache.insert("b.test", vec![2]);
+    std::thread::sleep(Duration::from_millis(5));
+    cache.insert("c.test", vec![3]);
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.get("a.test"), None);
+    assert_eq!(cache.get("c.test"), Some(vec![3]));
+
+    let expiring = SessionCache::with_limits(2, Duration::ZERO);
+    expiring.insert("a.test", vec![1]);
+    assert_eq!(expiring.get("a.test"), None);
+    assert!(expiring.is_empty());
+}
@@ -203,3 +203,134 @@ async fn test_add_local_to_closed_connection() {
         }
     }
 }
+
+#[test]
+fn test_scoped_ip_address_parsing() {
+    let endpoint = RemoteEndpoint::new()
+        .with_scoped_ip_address("fe80::1%en0")
+        .unwrap();
+    assert_eq!(
+        endpoint.identifiers,
+        vec![
+            EndpointIdentifier::IpAddress("fe80::1".parse().unwrap()),
+            EndpointIdentifier::Interface("en0".to_string()),
+        ]
+    );
+
+    let endpoint = LocalEndpoint::new()
+        .with_scoped_ip_address("fe80::1%3")
+        .unwrap();
+    assert!(endpoint
+        .identifiers
+        .contains(&EndpointIdentifier::Interface("3".to_string())));
+
+    // Without a zone only the address is added
+    let endpoint = RemoteEndpoint::new()
+        .with_scoped_ip_address("192.0.2.1")
+        .unwrap();
+    assert_eq!(
+        endpoint.identifiers,
+        vec![EndpointIdentifier::IpAddress("192.0.2.1".parse().unwrap())]
+    );
+
+    // Zones are only meaningful for IPv6, and must not be empty
+    assert!(RemoteEndpoint::new()
+        .with_scoped_ip_address("192.0.2.1%en0")
+        .is_err());
+    assert!(RemoteEndpoint::new()
+        .with_scoped_ip_address("fe80::1%")
+        .is_err());
+    assert!(RemoteEndpoint::new()
+        .with_scoped_ip_address("not-an-address")
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_link_local_requires_zone() {
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .ip_address("fe80::1".parse().unwrap())
+            .port(9)
+            .build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    assert!(matches!(
+        preconn.initiate().await,
+        Err(TransportServicesError::InvalidParameters(_))
+    ));
+
+    // An unknown interface cannot scope the address either
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::new()
+            .with_scoped_ip_address("fe80::1%no-such-interface0")
+            .unwrap()
+            .with_port(9)],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    assert!(matches!(
+        preconn.initiate().await,
+        Err(TransportServicesError::InvalidParameters(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_link_local_listen_and_connect() {
+    use crate::path_monitor::{is_ipv6_link_local, NetworkMonitor};
+    use std::time::Duration;
+
+    // Needs an interface with a link-local address, which not every host has
+    let interfaces = std::thread::spawn(|| {
+        NetworkMonitor::new()
+            .and_then(|monitor| monitor.list_interfaces())
+            .unwrap_or_default()
+    })
+    .join()
+    .unwrap();
+    let Some((interface, address)) = interfaces.into_iter().find_map(|interface| {
+        let address = interface.ips.iter().copied().find(is_ipv6_link_local)?;
+        Some((interface.name, address))
+    }) else {
+        return;
+    };
+
+    let listener = Preconnection::new(
+        vec![LocalEndpoint::new()
+            .with_scoped_ip_address(&format!("{address}%{interface}"))
+            .unwrap()
+            .with_port(0)],
+        vec![],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    )
+    .listen()
+    .await
+    .unwrap();
+    let bound = listener.local_addr().await.unwrap();
+    let std::net::SocketAddr::V6(bound_v6) = bound else {
+        panic!("Expected an IPv6 address");
+    };
+    assert_ne!(bound_v6.scope_id(), 0);
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::new()
+            .with_ip_address(address)
+            .with_port(bound.port())
+            .with_interface(&interface)],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.unwrap();
+    let event = tokio::time::timeout(Duration::from_secs(2), listener.next_event()).await;
+    assert!(matches!(
+        event,
+        Ok(Some(crate::listener::ListenerEvent::ConnectionReceived(_)))
+    ));
+
+    conn.close().await.unwrap();
+    listener.stop().await.unwrap();
+}
@@ -0,0 +1,202 @@
+//! Tests for TCP Fast Open on Listeners
+//!
+//! The kernel only accepts data in the SYN when net.ipv4.tcp_fastopen enables
+//! both sides, so tests needing it pass trivially on systems where it does not.
+
+use crate::*;
+use std::io::Write;
+use std::net::SocketAddr;
+use tokio::time::Duration;
+
+/// Whether this host sends and accepts data in the SYN
+fn fast_open_enabled() -> bool {
+    std::fs::read_to_string("/proc/sys/net/ipv4/tcp_fastopen")
+        .ok()
+        .and_then(|value| value.trim().parse::<u32>().ok())
+        .is_some_and(|flags| flags & 0x3 == 0x3)
+}
+
+async fn fast_open_listener(
+    zero_rtt: Preference,
+    parameters: SecurityParameters,
+) -> (Listener, SocketAddr) {
+    let mut properties = TransportProperties::default();
+    properties.selection_properties.zero_rtt_msg = zero_rtt;
+    let preconn = Preconnection::new(
+        vec![LocalEndpoint::builder()
+            .ip_address("127.0.0.1".parse().unwrap())
+            .port(0)
+            .build()],
+        vec![],
+        properties,
+        parameters,
+    );
+    let listener = preconn.listen().await.unwrap();
+    let addr = listener.local_addr().await.unwrap();
+    (listener, addr)
+}
+
+/// Connect with TCP Fast Open, sending `data` in the SYN once the client
+/// holds a cookie for the server
+async fn fast_open_send(addr: SocketAddr, data: &'static [u8]) -> socket2::Socket {
+    tokio::task::spawn_blocking(move || {
+        let socket = socket2::Socket::new(
+            socket2::Domain::IPV4,
+            socket2::Type::STREAM,
+            Some(socket2::Protocol::TCP),
+        )
+        .unwrap();
+        socket
+            .send_to_with_flags(data, &addr.into(), libc::MSG_FASTOPEN)
+            .unwrap();
+        socket
+    })
+    .await
+    .unwrap()
+}
+
+async fn first_received(conn: &Connection) -> (Vec<u8>, MessageContext) {
+    tokio::time::timeout(Duration::from_secs(2), async {
+        loop {
+            match conn.next_event().await {
+                Some(ConnectionEvent::Received {
+                    message_data,
+                    message_context,
+                }) => break (message_data, message_context),
+                Some(_) => continue,
+                None => panic!("Event stream ended"),
+            }
+        }
+    })
+    .await
+    .expect("Should receive data")
+}
+
+async fn accept(listener: &Listener) -> Connection {
+    tokio::time::timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .expect("Should accept a connection")
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_listener_delivers_syn_data_as_early_data() {
+    if !fast_open_enabled() {
+        return;
+    }
+    let mut parameters = SecurityParameters::new_disabled();
+    parameters.set_early_data_callback(|_| true);
+    let (listener, addr) = fast_open_listener(Preference::Prefer, parameters).await;
+
+    // Without a cookie cached from earlier runs, the first connection only
+    // fetches one and its data follows the handshake
+    let _first = fast_open_send(addr, b"cookie please").await;
+    let conn = accept(&listener).await;
+    let (data, _) = first_received(&conn).await;
+    assert_eq!(data, b"cookie please");
+
+    let client = fast_open_send(addr, b"GET /index.html").await;
+    let conn = accept(&listener).await;
+    let (data, context) = first_received(&conn).await;
+    assert_eq!(data, b"GET /index.html");
+    assert!(context.early_data);
+
+    // Later data is not early
+    (&client).write_all(b"more").unwrap();
+    let (data, context) = first_received(&conn).await;
+    assert_eq!(data, b"more");
+    assert!(!context.early_data);
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_listener_takes_only_the_syn_payload_as_early_data() {
+    if !fast_open_enabled() {
+        return;
+    }
+    let mut parameters = SecurityParameters::new_disabled();
+    parameters.set_early_data_callback(|_| true);
+    let (listener, addr) = fast_open_listener(Preference::Prefer, parameters).await;
+
+    let _first = fast_open_send(addr, b"cookie please").await;
+    accept(&listener).await;
+
+    // Data sent right after the SYN is queued before the connection is
+    // accepted, but did not arrive with the SYN
+    let client = fast_open_send(addr, b"GET /").await;
+    (&client).write_all(b"after").unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let conn = accept(&listener).await;
+    let (data, context) = first_received(&conn).await;
+    assert_eq!(data, b"GET /");
+    assert!(context.early_data);
+    let (data, context) = first_received(&conn).await;
+    assert_eq!(data, b"after");
+    assert!(!context.early_data);
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_listener_refuses_syn_data_not_replay_safe() {
+    if !fast_open_enabled() {
+        return;
+    }
+    let mut parameters = SecurityParameters::new_disabled();
+    parameters.set_early_data_callback(|data| data.starts_with(b"GET "));
+    let (listener, addr) = fast_open_listener(Preference::Require, parameters).await;
+
+    let _first = fast_open_send(addr, b"GET /").await;
+    accept(&listener).await;
+
+    let _client = fast_open_send(addr, b"POST /orders").await;
+    let reason = tokio::time::timeout(Duration::from_secs(2), async {
+        loop {
+            match listener.next_event().await {
+                Some(ListenerEvent::EstablishmentError {
+                    class: AcceptErrorClass::HandshakeFailed,
+                    reason,
+                    ..
+                }) => break reason,
+                Some(ListenerEvent::ConnectionReceived(_)) => {
+                    panic!("Replay-unsafe early data should not be delivered")
+                }
+                Some(_) => continue,
+                None => panic!("Listener event stream ended"),
+            }
+        }
+    })
+    .await
+    .expect("Should report the refused connection");
+    assert!(reason.contains("replay"), "{reason}");
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_listener_without_zero_rtt_takes_no_syn_data() {
+    let (listener, addr) =
+        fast_open_listener(Preference::NoPreference, SecurityParameters::new_disabled()).await;
+
+    for _ in 0..2 {
+        let _client = fast_open_send(addr, b"hello").await;
+        let conn = accept(&listener).await;
+        let (data, context) = first_received(&conn).await;
+        assert_eq!(data, b"hello");
+        assert!(!context.early_data);
+    }
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_listener_without_replay_policy_takes_no_syn_data() {
+    let (listener, addr) =
+        fast_open_listener(Preference::Prefer, SecurityParameters::new_disabled()).await;
+
+    for _ in 0..2 {
+        let _client = fast_open_send(addr, b"hello").await;
+        let conn = accept(&listener).await;
+        let (data, context) = first_received(&conn).await;
+        assert_eq!(data, b"hello");
+        assert!(!context.early_data);
+    }
+    listener.stop().await.unwrap();
+}
@@ -0,0 +1,150 @@
+//! Tests for the fault injection layer
+
+use crate::*;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+#[tokio::test]
+async fn test_faults_apply_to_units_in_order() {
+    let injector = FaultInjector::new();
+    injector.inject(FaultDirection::Send, Fault::Drop);
+    injector.inject(FaultDirection::Send, Fault::Duplicate);
+    injector.inject(FaultDirection::Send, Fault::Corrupt);
+    injector.inject(FaultDirection::Send, Fault::Reorder);
+    assert_eq!(injector.pending(FaultDirection::Send), 4);
+    assert_eq!(injector.pending(FaultDirection::Receive), 0);
+
+    let send = |data: &[u8]| injector.perturb(FaultDirection::Send, data.to_vec());
+    assert!(send(b"a").await.is_empty());
+    assert_eq!(send(b"b").await, vec![b"b".to_vec(), b"b".to_vec()]);
+    assert_eq!(
+        send(&[0x00, 0x00, 0x00]).await,
+        vec![vec![0x00, 0xFF, 0x00]]
+    );
+    assert!(send(b"c").await.is_empty());
+    // The held unit follows the next one
+    assert_eq!(send(b"d").await, vec![b"d".to_vec(), b"c".to_vec()]);
+    assert_eq!(send(b"e").await, vec![b"e".to_vec()]);
+}
+
+/// Connect to a server that reports what it reads and writes what it is given
+async fn connect_to_scripted_server() -> (Connection, mpsc::Receiver<Vec<u8>>, mpsc::Sender<Vec<u8>>)
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr: SocketAddr = listener.local_addr().unwrap();
+    let (read_tx, read_rx) = mpsc::channel(16);
+    let (write_tx, mut write_rx) = mpsc::channel::<Vec<u8>>(16);
+
+    tokio::spawn(async move {
+        let Ok((stream, _)) = listener.accept().await else {
+            return;
+        };
+        let (mut reader, mut writer) = stream.into_split();
+        tokio::spawn(async move {
+            while let Some(data) = write_rx.recv().await {
+                let _ = writer.write_all(&data).await;
+            }
+        });
+        let mut buf = [0; 1024];
+        while let Ok(n) = reader.read(&mut buf).await {
+            if n == 0 || read_tx.send(buf[..n].to_vec()).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.unwrap();
+    conn.wait_for_established(Some(Duration::from_secs(5)))
+        .await
+        .unwrap();
+    (conn, read_rx, write_tx)
+}
+
+async fn read_exactly(rx: &mut mpsc::Receiver<Vec<u8>>, len: usize) -> Vec<u8> {
+    let mut received = Vec::new();
+    while received.len() < len {
+        let chunk = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+            .await
+            .expect("timed out waiting for data")
+            .expect("server closed");
+        received.extend(chunk);
+    }
+    received
+}
+
+#[tokio::test]
+async fn test_send_faults_on_tcp() {
+    let (conn, mut server_rx, _server_tx) = connect_to_scripted_server().await;
+    let injector = Arc::new(FaultInjector::new());
+    conn.set_fault_injector(Arc::clone(&injector)).await;
+
+    injector.inject(FaultDirection::Send, Fault::Drop);
+    injector.inject(FaultDirection::Send, Fault::Duplicate);
+    conn.send("lost").await.unwrap();
+    conn.send("twice").await.unwrap();
+    conn.send("!").await.unwrap();
+
+    assert_eq!(read_exactly(&mut server_rx, 11).await, b"twicetwice!");
+    conn.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_receive_corruption_on_tcp() {
+    let (conn, _server_rx, server_tx) = connect_to_scripted_server().await;
+    let injector = Arc::new(FaultInjector::new());
+    conn.set_fault_injector(Arc::clone(&injector)).await;
+    injector.inject(FaultDirection::Receive, Fault::Corrupt);
+
+    server_tx.send(b"abc".to_vec()).await.unwrap();
+    loop {
+        match tokio::time::timeout(Duration::from_secs(2), conn.next_event())
+            .await
+            .unwrap()
+        {
+            Some(ConnectionEvent::Received { message_data, .. }) => {
+                assert_eq!(message_data, vec![b'a', !b'b', b'c']);
+                break;
+            }
+            Some(_) => continue,
+            None => panic!("event channel closed"),
+        }
+    }
+    conn.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_injected_reset_aborts_connection() {
+    let (conn, mut server_rx, _server_tx) = connect_to_scripted_server().await;
+    let injector = Arc::new(FaultInjector::new());
+    conn.set_fault_injector(Arc::clone(&injector)).await;
+
+    injector.reset_connection();
+    conn.wait_for_closed(Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+
+    let mut saw_error = false;
+    while let Ok(Some(event)) =
+        tokio::time::timeout(Duration::from_millis(100), conn.next_event()).await
+    {
+        saw_error |= matches!(event, ConnectionEvent::ConnectionError(_));
+    }
+    assert!(saw_error);
+
+    // The peer sees the connection go away
+    let end = tokio::time::timeout(Duration::from_secs(2), server_rx.recv())
+        .await
+        .unwrap();
+    assert!(end.is_none());
+    assert!(conn.send("late").await.is_err());
+}
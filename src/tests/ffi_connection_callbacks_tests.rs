@@ -0,0 +1,229 @@
+//! Tests for how connection events and received data reach FFI callbacks
+
+use crate::ffi::connection::{
+    transport_services_connection_clear_callbacks, transport_services_connection_free,
+    transport_services_connection_set_callbacks, transport_services_connection_set_event_callback,
+};
+use crate::ffi::runtime;
+use crate::ffi::types::{
+    TransportServicesConnectionEventType as EventType, TransportServicesError as FfiError,
+    TransportServicesMessage,
+};
+use crate::ffi::{handle_ref, to_handle};
+use crate::*;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// What the callbacks were handed
+#[derive(Default)]
+struct Recorder {
+    messages: Mutex<Vec<Vec<u8>>>,
+    events: Mutex<Vec<(EventType, String)>>,
+}
+
+extern "C" fn on_message(
+    message: *const TransportServicesMessage,
+    _context: *const c_void,
+    user_data: *mut c_void,
+) {
+    let recorder = unsafe { &*(user_data as *const Recorder) };
+    let message = unsafe { &*message };
+    let data = unsafe { std::slice::from_raw_parts(message.data, message.length) };
+    recorder.messages.lock().unwrap().push(data.to_vec());
+}
+
+extern "C" fn on_event(event_type: EventType, message: *const c_char, user_data: *mut c_void) {
+    let recorder = unsafe { &*(user_data as *const Recorder) };
+    let message = unsafe { CStr::from_ptr(message) }
+        .to_string_lossy()
+        .into_owned();
+    recorder.events.lock().unwrap().push((event_type, message));
+}
+
+fn closed(recorder: &Recorder) -> bool {
+    recorder
+        .events
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|e| matches!(e, (EventType::Closed, _)))
+}
+
+fn wait_for(recorder: &Recorder, done: impl Fn(&Recorder) -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !done(recorder) {
+        assert!(
+            Instant::now() < deadline,
+            "callbacks were not called in time"
+        );
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// An established, length-prefix framed connection whose peer sends `data`
+fn framed_connection(data: Vec<u8>, properties: TransportProperties) -> Connection {
+    let _ = runtime::init_runtime();
+    runtime::block_on(async {
+        let peer = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = peer.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = peer.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            stream.write_all(&data).await.unwrap();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+
+        let preconn = Preconnection::new(
+            vec![],
+            vec![RemoteEndpoint::builder().socket_address(addr).build()],
+            properties,
+            SecurityParameters::new_disabled(),
+        );
+        let conn = preconn.initiate().await.unwrap();
+        conn.wait_for_established(Some(Duration::from_secs(2)))
+            .await
+            .unwrap();
+        conn.use_length_prefix_framer().await.unwrap();
+        conn
+    })
+    .unwrap()
+}
+
+#[test]
+fn test_receive_error_has_its_own_event_type() {
+    let mut data = 64u32.to_be_bytes().to_vec();
+    data.extend_from_slice(&[0u8; 64]);
+    let conn = framed_connection(
+        data,
+        TransportProperties::builder()
+            .maximum_message_size_on_receive(16)
+            .build(),
+    );
+
+    let recorder = Box::new(Recorder::default());
+    let user_data = &*recorder as *const Recorder as *mut c_void;
+    let handle = to_handle(Box::new(conn));
+    unsafe {
+        assert!(matches!(
+            transport_services_connection_set_event_callback(handle, on_event, user_data),
+            FfiError::Success
+        ));
+    }
+
+    // The oversized message is reported as a receive error, not as data
+    wait_for(&recorder, |r| {
+        r.events
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(e, _)| matches!(e, EventType::ReceiveError))
+    });
+    let events = recorder.events.lock().unwrap();
+    assert!(events
+        .iter()
+        .all(|(e, _)| !matches!(e, EventType::Received)));
+    let (_, message) = events
+        .iter()
+        .find(|(e, _)| matches!(e, EventType::ReceiveError))
+        .unwrap();
+    assert!(message.contains("64 bytes"));
+    drop(events);
+
+    // The recorder outlives the Closed event, the last callback made
+    let conn = unsafe { handle_ref::<Connection>(handle) };
+    runtime::block_on(conn.close()).unwrap().unwrap();
+    wait_for(&recorder, closed);
+
+    unsafe {
+        transport_services_connection_free(handle);
+    }
+}
+
+#[test]
+fn test_set_callbacks_delivers_data_and_events() {
+    let _ = runtime::init_runtime();
+    let conn = runtime::block_on(async {
+        let peer = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = peer.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = peer.accept().await.unwrap();
+            stream.write_all(b"hello").await.unwrap();
+            let mut rest = Vec::new();
+            let _ = stream.read_to_end(&mut rest).await;
+        });
+
+        let preconn = Preconnection::new(
+            vec![],
+            vec![RemoteEndpoint::builder().socket_address(addr).build()],
+            TransportProperties::default(),
+            SecurityParameters::new_disabled(),
+        );
+        preconn.initiate().await.unwrap()
+    })
+    .unwrap();
+
+    let recorder = Box::new(Recorder::default());
+    let user_data = &*recorder as *const Recorder as *mut c_void;
+    let handle = to_handle(Box::new(conn));
+    unsafe {
+        assert!(matches!(
+            transport_services_connection_set_callbacks(handle, on_message, on_event, user_data),
+            FfiError::Success
+        ));
+    }
+
+    // The data and the Ready event both arrive, whichever was queued first
+    wait_for(&recorder, |r| {
+        r.messages.lock().unwrap().concat() == b"hello"
+            && r.events
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|e| matches!(e, (EventType::Ready, _)))
+    });
+
+    let conn = unsafe { handle_ref::<Connection>(handle) };
+    runtime::block_on(conn.close()).unwrap().unwrap();
+    wait_for(&recorder, closed);
+
+    unsafe {
+        transport_services_connection_free(handle);
+    }
+}
+
+#[test]
+fn test_cleared_callbacks_are_not_called() {
+    let conn = framed_connection(Vec::new(), TransportProperties::default());
+
+    let recorder = Box::new(Recorder::default());
+    let user_data = &*recorder as *const Recorder as *mut c_void;
+    let handle = to_handle(Box::new(conn));
+    unsafe {
+        assert!(matches!(
+            transport_services_connection_set_event_callback(handle, on_event, user_data),
+            FfiError::Success
+        ));
+        assert!(matches!(
+            transport_services_connection_clear_callbacks(handle),
+            FfiError::Success
+        ));
+    }
+
+    // Once cleared the recorder may go, and closing reaches no callback
+    drop(recorder);
+    let conn = unsafe { handle_ref::<Connection>(handle) };
+    runtime::block_on(conn.close()).unwrap().unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+
+    unsafe {
+        assert!(matches!(
+            transport_services_connection_clear_callbacks(std::ptr::null_mut()),
+            FfiError::InvalidParameters
+        ));
+        transport_services_connection_free(handle);
+    }
+}
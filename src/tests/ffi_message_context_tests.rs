@@ -0,0 +1,164 @@
+//! Tests for reading a received message's context through the C ABI
+
+use crate::ffi::message_context::*;
+use crate::ffi::transport_services_free_string;
+use crate::ffi::types::TransportServicesEcnMarking;
+use crate::message::EcnMarking;
+use crate::{LocalEndpoint, MessageContext, RemoteEndpoint};
+use std::ffi::CStr;
+use std::ptr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Take a string returned over FFI, freeing it
+unsafe fn take_string(value: *mut std::os::raw::c_char) -> Option<String> {
+    if value.is_null() {
+        return None;
+    }
+    let string = CStr::from_ptr(value).to_str().unwrap().to_string();
+    transport_services_free_string(value);
+    Some(string)
+}
+
+#[test]
+fn test_context_endpoints() {
+    let context = MessageContext::new()
+        .with_remote_endpoint(
+            RemoteEndpoint::builder()
+                .socket_address("[2001:db8::1]:4433".parse().unwrap())
+                .build(),
+        )
+        .with_local_endpoint(
+            LocalEndpoint::builder()
+                .ip_address("192.0.2.7".parse().unwrap())
+                .port(5000)
+                .build(),
+        );
+    let received = ReceivedContext {
+        context,
+        end_of_message: true,
+    };
+
+    unsafe {
+        assert_eq!(
+            take_string(transport_services_message_context_get_remote_endpoint(
+                received.as_ptr()
+            ))
+            .as_deref(),
+            Some("[2001:db8::1]:4433")
+        );
+        assert_eq!(
+            take_string(transport_services_message_context_get_local_endpoint(
+                received.as_ptr()
+            ))
+            .as_deref(),
+            Some("192.0.2.7:5000")
+        );
+    }
+
+    let unknown = ReceivedContext {
+        context: MessageContext::new(),
+        end_of_message: true,
+    };
+    unsafe {
+        assert!(transport_services_message_context_get_remote_endpoint(unknown.as_ptr()).is_null());
+        assert!(transport_services_message_context_get_local_endpoint(ptr::null()).is_null());
+    }
+}
+
+#[test]
+fn test_context_ecn_and_flags() {
+    let received = ReceivedContext {
+        context: MessageContext::new()
+            .with_ecn(EcnMarking::Ce)
+            .as_early_data()
+            .with_final(true),
+        end_of_message: false,
+    };
+
+    unsafe {
+        let mut ecn = TransportServicesEcnMarking::NotEct;
+        assert_eq!(
+            transport_services_message_context_get_ecn(received.as_ptr(), &mut ecn),
+            0
+        );
+        assert!(matches!(ecn, TransportServicesEcnMarking::Ce));
+        assert!(transport_services_message_context_is_early_data(
+            received.as_ptr()
+        ));
+        assert!(transport_services_message_context_is_final(
+            received.as_ptr()
+        ));
+        assert!(!transport_services_message_context_is_end_of_message(
+            received.as_ptr()
+        ));
+
+        let plain = ReceivedContext {
+            context: MessageContext::new(),
+            end_of_message: true,
+        };
+        assert_eq!(
+            transport_services_message_context_get_ecn(plain.as_ptr(), &mut ecn),
+            1
+        );
+        assert!(!transport_services_message_context_is_early_data(
+            plain.as_ptr()
+        ));
+        assert!(transport_services_message_context_is_end_of_message(
+            plain.as_ptr()
+        ));
+        assert_eq!(
+            transport_services_message_context_get_ecn(ptr::null(), &mut ecn),
+            -1
+        );
+    }
+}
+
+#[test]
+fn test_context_timestamps() {
+    let now_us = |time: SystemTime| time.duration_since(UNIX_EPOCH).unwrap().as_micros() as u64;
+    let before = now_us(SystemTime::now());
+    let received = ReceivedContext {
+        context: MessageContext::new()
+            .with_interface_timestamp(Instant::now() - Duration::from_millis(50)),
+        end_of_message: true,
+    };
+    let after = now_us(SystemTime::now());
+
+    unsafe {
+        let mut received_us = 0;
+        assert_eq!(
+            transport_services_message_context_get_received_time(
+                received.as_ptr(),
+                &mut received_us
+            ),
+            0
+        );
+        assert!(received_us >= before.saturating_sub(1_000) && received_us <= after + 1_000);
+
+        let mut interface_us = 0;
+        assert_eq!(
+            transport_services_message_context_get_interface_timestamp(
+                received.as_ptr(),
+                &mut interface_us
+            ),
+            0
+        );
+        let earlier = received_us - interface_us;
+        assert!(
+            (45_000..60_000).contains(&earlier),
+            "Interface timestamp {earlier}us earlier"
+        );
+
+        let plain = ReceivedContext {
+            context: MessageContext::new(),
+            end_of_message: true,
+        };
+        assert_eq!(
+            transport_services_message_context_get_interface_timestamp(
+                plain.as_ptr(),
+                &mut interface_us
+            ),
+            1
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Tests for configuring preconnections and listening through the C ABI
+
+use crate::ffi::listener::{
+    transport_services_listener_free, transport_services_listener_is_active,
+    transport_services_listener_stop,
+};
+use crate::ffi::preconnection::{
+    transport_services_preconnection_add_local_endpoint, transport_services_preconnection_free,
+    transport_services_preconnection_listen, transport_services_preconnection_new,
+    transport_services_preconnection_use_security_parameters,
+    transport_services_preconnection_use_transport_properties,
+};
+use crate::ffi::runtime;
+use crate::ffi::security_parameters::{
+    transport_services_free_security_parameters,
+    transport_services_new_disabled_security_parameters,
+};
+use crate::ffi::transport_properties::{
+    transport_services_free_transport_properties, transport_services_new_transport_properties,
+    transport_services_set_preference,
+};
+use crate::ffi::types::TransportServicesPreference;
+use crate::ffi::types::{TransportServicesEndpoint, TransportServicesError as FfiError};
+use crate::{EndpointIdentifier, Preconnection};
+use std::ffi::CString;
+
+/// Identifiers of the preconnection's local endpoints
+fn local_identifiers(preconn: *mut crate::ffi::TransportServicesHandle) -> Vec<EndpointIdentifier> {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let preconn = unsafe { crate::ffi::handle_ref::<Preconnection>(preconn) };
+    let (locals, _) = rt.block_on(preconn.resolve()).unwrap();
+    locals.into_iter().flat_map(|l| l.identifiers).collect()
+}
+
+#[test]
+fn test_local_address_literal_is_an_ip_address() {
+    let host = CString::new("127.0.0.1").unwrap();
+    let name = CString::new("localhost").unwrap();
+
+    unsafe {
+        for (hostname, expected) in [
+            (
+                &host,
+                EndpointIdentifier::IpAddress("127.0.0.1".parse().unwrap()),
+            ),
+            (&name, EndpointIdentifier::HostName("localhost".to_string())),
+        ] {
+            let endpoint = TransportServicesEndpoint {
+                hostname: hostname.as_ptr(),
+                port: 8080,
+                service: std::ptr::null(),
+                interface: std::ptr::null(),
+            };
+            let preconn = transport_services_preconnection_new();
+            assert!(matches!(
+                transport_services_preconnection_add_local_endpoint(preconn, &endpoint),
+                FfiError::Success
+            ));
+
+            let identifiers = local_identifiers(preconn);
+            assert!(identifiers.contains(&expected));
+            assert!(identifiers.contains(&EndpointIdentifier::Port(8080)));
+            transport_services_preconnection_free(preconn);
+        }
+    }
+}
+
+#[test]
+fn test_listen_on_local_endpoint() {
+    let _ = runtime::init_runtime();
+    let host = CString::new("127.0.0.1").unwrap();
+    let endpoint = TransportServicesEndpoint {
+        hostname: host.as_ptr(),
+        port: 0,
+        service: std::ptr::null(),
+        interface: std::ptr::null(),
+    };
+
+    unsafe {
+        let preconn = transport_services_preconnection_new();
+        assert!(matches!(
+            transport_services_preconnection_add_local_endpoint(preconn, &endpoint),
+            FfiError::Success
+        ));
+
+        let listener = transport_services_preconnection_listen(preconn);
+        assert!(!listener.is_null());
+        assert!(transport_services_listener_is_active(listener));
+
+        assert!(matches!(
+            transport_services_listener_stop(listener),
+            FfiError::Success
+        ));
+        assert!(!transport_services_listener_is_active(listener));
+
+        transport_services_listener_free(listener);
+        transport_services_preconnection_free(preconn);
+    }
+}
+
+#[test]
+fn test_listen_without_local_endpoint_fails() {
+    let _ = runtime::init_runtime();
+    unsafe {
+        let preconn = transport_services_preconnection_new();
+        assert!(transport_services_preconnection_listen(preconn).is_null());
+        assert!(!crate::ffi::error::transport_services_get_last_error().is_null());
+        transport_services_preconnection_free(preconn);
+    }
+}
+
+#[test]
+fn test_use_transport_properties_copies_every_property() {
+    unsafe {
+        let preconn = transport_services_preconnection_new();
+        let properties = transport_services_new_transport_properties();
+        // Prohibiting reliability while requiring it per message conflicts
+        transport_services_set_preference(properties, 0, TransportServicesPreference::Prohibit);
+        transport_services_set_preference(properties, 2, TransportServicesPreference::Require);
+
+        assert!(matches!(
+            transport_services_preconnection_use_transport_properties(preconn, properties),
+            FfiError::Success
+        ));
+        transport_services_free_transport_properties(properties);
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let conflicts = rt
+            .block_on(crate::ffi::handle_ref::<crate::Preconnection>(preconn).property_conflicts());
+        assert!(!conflicts.is_empty());
+
+        let security = transport_services_new_disabled_security_parameters();
+        assert!(matches!(
+            transport_services_preconnection_use_security_parameters(preconn, security),
+            FfiError::Success
+        ));
+        assert!(matches!(
+            transport_services_preconnection_use_security_parameters(preconn, std::ptr::null()),
+            FfiError::InvalidParameters
+        ));
+        transport_services_free_security_parameters(security);
+        transport_services_preconnection_free(preconn);
+    }
+}
@@ -0,0 +1,148 @@
+//! Tests for getting and setting connection properties as JSON through the C ABI
+
+use crate::ffi::connection::{
+    transport_services_connection_get_property_json,
+    transport_services_connection_set_property_json,
+};
+use crate::ffi::property_json::{property_from_json, property_to_json};
+use crate::ffi::types::TransportServicesError as FfiError;
+use crate::ffi::{from_handle, to_handle, transport_services_free_string};
+use crate::{
+    Connection, ConnectionState, Preconnection, RemoteEndpoint, SecurityParameters,
+    TransportProperties,
+};
+use serde_json::{json, Value};
+use std::ffi::{CStr, CString};
+
+/// An established connection behind an FFI handle
+fn connection_handle() -> *mut crate::ffi::TransportServicesHandle {
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .hostname("example.com")
+            .port(443)
+            .build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    to_handle(Box::new(Connection::new_with_data(
+        preconn,
+        ConnectionState::Established,
+        None,
+        None,
+        TransportProperties::default(),
+    )))
+}
+
+fn set(handle: *mut crate::ffi::TransportServicesHandle, key: &str, value: &str) -> FfiError {
+    let key = CString::new(key).unwrap();
+    let value = CString::new(value).unwrap();
+    unsafe { transport_services_connection_set_property_json(handle, key.as_ptr(), value.as_ptr()) }
+}
+
+fn get(handle: *mut crate::ffi::TransportServicesHandle, key: &str) -> Option<Value> {
+    let key = CString::new(key).unwrap();
+    unsafe {
+        let text = transport_services_connection_get_property_json(handle, key.as_ptr());
+        if text.is_null() {
+            return None;
+        }
+        let value = serde_json::from_str(CStr::from_ptr(text).to_str().unwrap()).unwrap();
+        transport_services_free_string(text);
+        Some(value)
+    }
+}
+
+#[test]
+fn test_every_writable_property_round_trips() {
+    for (key, value) in [
+        ("recvChecksumLen", json!("full")),
+        ("recvChecksumLen", json!(8)),
+        ("connPriority", json!(5)),
+        ("connTimeout", json!(30000)),
+        ("keepAliveTimeout", Value::Null),
+        ("sendTimeout", json!(250)),
+        ("sendStallThreshold", json!(1000)),
+        ("connScheduler", json!("proportionalRate")),
+        ("connCapacityProfile", json!("scavenger")),
+        ("multipathPolicy", json!("redundant")),
+        ("minSendRate", json!(1000)),
+        ("maxSendRate", Value::Null),
+        ("minRecvRate", json!(2000)),
+        ("maxRecvRate", json!(4_000_000)),
+        ("groupConnLimit", json!(3)),
+        ("isolateSession", json!(true)),
+        ("ipHopLimit", json!(64)),
+        ("tcp.userTimeoutValue", json!(5000)),
+        ("tcp.userTimeoutEnabled", json!(true)),
+        ("tcp.userTimeoutChangeable", json!(false)),
+        ("quic.maxIdleTimeout", Value::Null),
+        ("quic.maxStreamsBidi", json!(100)),
+        ("quic.maxStreamsUni", json!(3)),
+        ("quic.congestionAlgorithm", json!("bbr")),
+        ("sctp.numOutboundStreams", json!(10)),
+        ("sctp.maxInboundStreams", json!(10)),
+        ("sctp.heartbeatInterval", json!(30000)),
+        ("sctp.maxRetransmissions", json!(5)),
+    ] {
+        let property = property_from_json(key, &value).unwrap();
+        assert_eq!(property_to_json(&property), value, "{key}");
+    }
+}
+
+#[test]
+fn test_invalid_values_are_rejected() {
+    assert!(property_from_json("connPriority", &json!("high")).is_err());
+    assert!(property_from_json("connPriority", &json!(-1)).is_err());
+    assert!(property_from_json("ipHopLimit", &json!(256)).is_err());
+    assert!(property_from_json("connScheduler", &json!("lottery")).is_err());
+    assert!(property_from_json("isolateSession", &json!(1)).is_err());
+    assert!(property_from_json("connState", &json!("closed")).is_err());
+    assert!(property_from_json("noSuchProperty", &json!(1)).is_err());
+}
+
+#[test]
+fn test_set_and_get_property_json() {
+    let handle = connection_handle();
+
+    assert!(matches!(
+        set(handle, "connTimeout", "30000"),
+        FfiError::Success
+    ));
+    assert_eq!(get(handle, "connTimeout"), Some(json!(30000)));
+    assert!(matches!(
+        set(handle, "connCapacityProfile", "\"lowLatencyInteractive\""),
+        FfiError::Success
+    ));
+    assert_eq!(
+        get(handle, "connCapacityProfile"),
+        Some(json!("lowLatencyInteractive"))
+    );
+    assert!(matches!(
+        set(handle, "quic.maxStreamsBidi", "16"),
+        FfiError::Success
+    ));
+    assert_eq!(get(handle, "quic.maxStreamsBidi"), Some(json!(16)));
+
+    // Read-only properties can be read but not set
+    assert_eq!(get(handle, "connState"), Some(json!("established")));
+    assert!(matches!(
+        set(handle, "connState", "\"closed\""),
+        FfiError::InvalidParameters
+    ));
+
+    // Malformed JSON and unknown keys fail
+    assert!(matches!(
+        set(handle, "connTimeout", "30 seconds"),
+        FfiError::InvalidParameters
+    ));
+    assert!(matches!(
+        set(handle, "connTimeout", "\"30000\""),
+        FfiError::InvalidParameters
+    ));
+    assert_eq!(get(handle, "noSuchProperty"), None);
+
+    unsafe {
+        let _ = from_handle::<Connection>(handle);
+    }
+}
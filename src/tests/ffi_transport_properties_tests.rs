@@ -0,0 +1,296 @@
+//! Tests for setting and reading back transport properties through the C ABI
+
+use crate::ffi::transport_properties::property_constants::*;
+use crate::ffi::transport_properties::*;
+use crate::ffi::types::{
+    TransportServicesAddressFamilyPreference, TransportServicesCommunicationDirection,
+    TransportServicesMultipathConfig, TransportServicesPreference,
+};
+use crate::ffi::TransportServicesHandle;
+use crate::ffi::{handle_ref, transport_services_free_string};
+use crate::TransportProperties;
+use crate::{AddressFamilyPreference, CommunicationDirection, MultipathConfig, Preference};
+use std::ffi::{CStr, CString};
+use std::ptr;
+
+/// Run `test` against a fresh properties handle, freeing it afterwards
+fn with_properties(test: impl FnOnce(*mut TransportServicesHandle)) {
+    let handle = transport_services_new_transport_properties();
+    test(handle);
+    unsafe { transport_services_free_transport_properties(handle) };
+}
+
+#[test]
+fn test_preferences_round_trip() {
+    with_properties(|handle| unsafe {
+        for property in 0..=TRANSPORT_SERVICES_PROPERTY_ACTIVE_READ_BEFORE_SEND {
+            assert_eq!(
+                transport_services_set_preference(
+                    handle,
+                    property,
+                    TransportServicesPreference::Avoid
+                ),
+                0
+            );
+            let mut preference = TransportServicesPreference::Require;
+            assert_eq!(
+                transport_services_get_preference(handle, property, &mut preference),
+                0
+            );
+            assert_eq!(Preference::from(preference), Preference::Avoid);
+        }
+
+        let mut preference = TransportServicesPreference::Require;
+        assert_eq!(
+            transport_services_get_preference(
+                handle,
+                TRANSPORT_SERVICES_PROPERTY_CONNECTION_TIMEOUT,
+                &mut preference
+            ),
+            -1
+        );
+        let selection = &handle_ref::<TransportProperties>(handle).selection_properties;
+        assert_eq!(selection.preserve_order, Preference::Avoid);
+        assert_eq!(selection.active_read_before_send, Preference::Avoid);
+    });
+}
+
+#[test]
+fn test_selection_properties_round_trip() {
+    with_properties(|handle| unsafe {
+        transport_services_set_multipath(handle, TransportServicesMultipathConfig::Passive);
+        let mut config = TransportServicesMultipathConfig::Disabled;
+        assert_eq!(transport_services_get_multipath(handle, &mut config), 0);
+        assert_eq!(MultipathConfig::from(config), MultipathConfig::Passive);
+
+        transport_services_set_direction(
+            handle,
+            TransportServicesCommunicationDirection::UnidirectionalReceive,
+        );
+        let mut direction = TransportServicesCommunicationDirection::Bidirectional;
+        assert_eq!(transport_services_get_direction(handle, &mut direction), 0);
+        assert_eq!(
+            CommunicationDirection::from(direction),
+            CommunicationDirection::UnidirectionalReceive
+        );
+
+        transport_services_set_advertises_altaddr(handle, true);
+        let mut advertises = false;
+        assert_eq!(
+            transport_services_get_advertises_altaddr(handle, &mut advertises),
+            0
+        );
+        assert!(advertises);
+    });
+}
+
+#[test]
+fn test_interface_and_pvd_preferences_round_trip() {
+    with_properties(|handle| unsafe {
+        assert_eq!(transport_services_get_interface_count(handle), 0);
+        for (name, preference) in [
+            ("eth0", TransportServicesPreference::Prefer),
+            ("wlan0", TransportServicesPreference::Prohibit),
+        ] {
+            let name = CString::new(name).unwrap();
+            assert_eq!(
+                transport_services_set_interface(handle, name.as_ptr(), preference),
+                0
+            );
+        }
+        let pvd = CString::new("pvd.example.com").unwrap();
+        transport_services_set_pvd(handle, pvd.as_ptr(), TransportServicesPreference::Require);
+
+        assert_eq!(transport_services_get_interface_count(handle), 2);
+        let mut name = ptr::null_mut();
+        let mut preference = TransportServicesPreference::NoPreference;
+        assert_eq!(
+            transport_services_get_interface(handle, 1, &mut name, &mut preference),
+            0
+        );
+        assert_eq!(CStr::from_ptr(name).to_str().unwrap(), "wlan0");
+        assert_eq!(Preference::from(preference), Preference::Prohibit);
+        transport_services_free_string(name);
+        assert_eq!(
+            transport_services_get_interface(handle, 2, &mut name, &mut preference),
+            -1
+        );
+
+        assert_eq!(transport_services_get_pvd_count(handle), 1);
+        assert_eq!(
+            transport_services_get_pvd(handle, 0, &mut name, &mut preference),
+            0
+        );
+        assert_eq!(CStr::from_ptr(name).to_str().unwrap(), "pvd.example.com");
+        assert_eq!(Preference::from(preference), Preference::Require);
+        transport_services_free_string(name);
+    });
+}
+
+#[test]
+fn test_connection_properties_round_trip() {
+    with_properties(|handle| unsafe {
+        // Unset properties read back as such
+        let mut ms = 0;
+        assert_eq!(
+            transport_services_get_duration_property(
+                handle,
+                TRANSPORT_SERVICES_PROPERTY_STATS_INTERVAL,
+                &mut ms
+            ),
+            1
+        );
+        let mut priority = 0;
+        assert_eq!(
+            transport_services_get_connection_priority(handle, &mut priority),
+            1
+        );
+
+        for property in TRANSPORT_SERVICES_PROPERTY_CONNECTION_TIMEOUT
+            ..=TRANSPORT_SERVICES_PROPERTY_CANDIDATE_TIMEOUT
+        {
+            assert_eq!(
+                transport_services_set_duration_property(handle, property, 1500),
+                0
+            );
+            assert_eq!(
+                transport_services_get_duration_property(handle, property, &mut ms),
+                0
+            );
+            assert_eq!(ms, 1500);
+        }
+        for property in TRANSPORT_SERVICES_PROPERTY_MAXIMUM_MESSAGE_SIZE_ON_SEND
+            ..=TRANSPORT_SERVICES_PROPERTY_MAX_PARALLEL_ATTEMPTS
+        {
+            let mut size = 0;
+            assert_eq!(transport_services_set_size_property(handle, property, 4), 0);
+            assert_eq!(
+                transport_services_get_size_property(handle, property, &mut size),
+                0
+            );
+            assert_eq!(size, 4);
+        }
+        for property in (TRANSPORT_SERVICES_PROPERTY_NO_DELAY
+            ..=TRANSPORT_SERVICES_PROPERTY_ABORT_ON_OVERSIZED_MESSAGE)
+            .chain([TRANSPORT_SERVICES_PROPERTY_PATH_PINNING])
+        {
+            let mut flag = false;
+            assert_eq!(
+                transport_services_set_bool_property(handle, property, true),
+                0
+            );
+            assert_eq!(
+                transport_services_get_bool_property(handle, property, &mut flag),
+                0
+            );
+            assert!(flag);
+        }
+
+        // Each family only takes its own properties
+        assert_eq!(
+            transport_services_set_size_property(handle, TRANSPORT_SERVICES_PROPERTY_NO_DELAY, 1),
+            -1
+        );
+        assert_eq!(
+            transport_services_set_bool_property(
+                handle,
+                TRANSPORT_SERVICES_PROPERTY_RELIABILITY,
+                true
+            ),
+            -1
+        );
+
+        transport_services_set_connection_priority(handle, 7);
+        assert_eq!(
+            transport_services_get_connection_priority(handle, &mut priority),
+            0
+        );
+        assert_eq!(priority, 7);
+
+        let connection = &handle_ref::<TransportProperties>(handle).connection_properties;
+        assert_eq!(connection.send_low_watermark, Some(4));
+        assert_eq!(connection.happy_eyeballs.max_parallel_attempts, 4);
+        assert_eq!(connection.udp_offload, Some(true));
+    });
+}
+
+#[test]
+fn test_rate_hop_limit_and_address_family_round_trip() {
+    with_properties(|handle| unsafe {
+        let mut rate = 1;
+        assert_eq!(
+            transport_services_get_rate_property(
+                handle,
+                TRANSPORT_SERVICES_PROPERTY_MAX_SEND_RATE,
+                &mut rate
+            ),
+            0
+        );
+        assert_eq!(rate, 0);
+        transport_services_set_rate_property(
+            handle,
+            TRANSPORT_SERVICES_PROPERTY_MAX_SEND_RATE,
+            1_000_000,
+        );
+        transport_services_set_rate_property(
+            handle,
+            TRANSPORT_SERVICES_PROPERTY_MAX_RECV_RATE,
+            2_000_000,
+        );
+        transport_services_get_rate_property(
+            handle,
+            TRANSPORT_SERVICES_PROPERTY_MAX_RECV_RATE,
+            &mut rate,
+        );
+        assert_eq!(rate, 2_000_000);
+        let connection = &handle_ref::<TransportProperties>(handle).connection_properties;
+        assert_eq!(connection.max_send_rate, Some(1_000_000));
+
+        // Zero lifts the cap
+        transport_services_set_rate_property(handle, TRANSPORT_SERVICES_PROPERTY_MAX_SEND_RATE, 0);
+        let connection = &handle_ref::<TransportProperties>(handle).connection_properties;
+        assert_eq!(connection.max_send_rate, None);
+
+        let mut hop_limit = 0;
+        assert_eq!(transport_services_get_hop_limit(handle, &mut hop_limit), 1);
+        transport_services_set_hop_limit(handle, 32);
+        assert_eq!(transport_services_get_hop_limit(handle, &mut hop_limit), 0);
+        assert_eq!(hop_limit, 32);
+
+        let mut family = TransportServicesAddressFamilyPreference::PreferIpv6;
+        transport_services_set_address_family_preference(
+            handle,
+            TransportServicesAddressFamilyPreference::Ipv4Only,
+        );
+        assert_eq!(
+            transport_services_get_address_family_preference(handle, &mut family),
+            0
+        );
+        assert_eq!(
+            AddressFamilyPreference::from(family),
+            AddressFamilyPreference::Ipv4Only
+        );
+    });
+}
+
+#[test]
+fn test_getters_reject_null_handles() {
+    unsafe {
+        let mut ms = 0;
+        assert_eq!(
+            transport_services_get_duration_property(
+                ptr::null(),
+                TRANSPORT_SERVICES_PROPERTY_CONNECTION_TIMEOUT,
+                &mut ms
+            ),
+            -1
+        );
+        assert_eq!(transport_services_get_interface_count(ptr::null()), -1);
+        with_properties(|handle| {
+            assert_eq!(
+                transport_services_get_hop_limit(handle, ptr::null_mut()),
+                -1
+            );
+        });
+    }
+}
@@ -0,0 +1,487 @@
+//! Tests for stacked Message Framers (RFC 9.1.2)
+
+use crate::*;
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::time::{sleep, Duration};
+
+/// Type-length-value framer: 1-byte type, 2-byte big-endian length, value
+struct TlvFramer {
+    message_type: u8,
+    buffer: Arc<Mutex<Vec<u8>>>,
+}
+
+impl TlvFramer {
+    fn new(message_type: u8) -> Self {
+        Self {
+            message_type,
+            buffer: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+#[async_trait]
+impl Framer for TlvFramer {
+    async fn frame_message(&self, message: &Message, _context: &MessageContext) -> Result<Vec<u8>> {
+        let mut framed = vec![self.message_type];
+        framed.extend_from_slice(&(message.len() as u16).to_be_bytes());
+        framed.extend_from_slice(message.data());
+        Ok(framed)
+    }
+
+    async fn parse_data(&self, data: &[u8]) -> Result<Vec<(Message, MessageContext)>> {
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.extend_from_slice(data);
+
+        let mut messages = Vec::new();
+        while buffer.len() >= 3 {
+            let length = u16::from_be_bytes([buffer[1], buffer[2]]) as usize;
+            if buffer.len() < 3 + length {
+                break;
+            }
+            let context = MessageContext::new().with_framer_metadata("type", vec![buffer[0]]);
+            messages.push((Message::from_bytes(&buffer[3..3 + length]), context));
+            buffer.drain(..3 + length);
+        }
+        Ok(messages)
+    }
+
+    fn name(&self) -> &str {
+        "tlv"
+    }
+
+    fn reset(&self) {
+        self.buffer.lock().unwrap().clear();
+    }
+}
+
+fn tlv(message_type: u8, value: &[u8]) -> Vec<u8> {
+    let mut frame = vec![message_type];
+    frame.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    frame.extend_from_slice(value);
+    frame
+}
+
+fn length_prefixed(data: &[u8]) -> Vec<u8> {
+    let mut frame = (data.len() as u32).to_be_bytes().to_vec();
+    frame.extend_from_slice(data);
+    frame
+}
+
+/// TLV framing inside length-prefix framing
+fn nested_stack() -> FramerStack {
+    let mut stack = FramerStack::new();
+    stack.add_framer(Box::new(LengthPrefixFramer::new()));
+    stack.add_framer(Box::new(TlvFramer::new(7)));
+    stack
+}
+
+#[tokio::test]
+async fn test_stack_frames_innermost_first() {
+    let stack = nested_stack();
+    let framed = stack
+        .frame_message(&Message::from_string("hi"), &MessageContext::new())
+        .await
+        .unwrap();
+
+    assert_eq!(framed, length_prefixed(&tlv(7, b"hi")));
+}
+
+#[tokio::test]
+async fn test_stack_parses_outermost_first() {
+    let stack = nested_stack();
+    let mut wire = length_prefixed(&tlv(7, b"first"));
+    wire.extend(length_prefixed(&tlv(9, b"second")));
+
+    // Data arrives in arbitrary pieces
+    let mut parsed = Vec::new();
+    for piece in wire.chunks(5) {
+        parsed.extend(stack.parse_data(piece).await.unwrap());
+    }
+
+    assert_eq!(parsed.len(), 2);
+    assert_eq!(parsed[0].0.data(), b"first");
+    assert_eq!(
+        parsed[0].1.message_properties().framer_metadata.get("type"),
+        Some(&vec![7])
+    );
+    assert_eq!(parsed[1].0.data(), b"second");
+    assert_eq!(
+        parsed[1].1.message_properties().framer_metadata.get("type"),
+        Some(&vec![9])
+    );
+}
+
+#[tokio::test]
+async fn test_stack_reset_discards_partial_data() {
+    let stack = nested_stack();
+    let stale = length_prefixed(&tlv(7, b"stale"));
+    assert!(stack.parse_data(&stale[..6]).await.unwrap().is_empty());
+
+    stack.reset();
+
+    let parsed = stack
+        .parse_data(&length_prefixed(&tlv(7, b"fresh")))
+        .await
+        .unwrap();
+    assert_eq!(parsed.len(), 1);
+    assert_eq!(parsed[0].0.data(), b"fresh");
+}
+
+/// Connect to a peer that writes `peer_writes` and returns what it reads
+async fn framed_peer(
+    peer_writes: Vec<Vec<u8>>,
+) -> (Connection, tokio::sync::oneshot::Receiver<Vec<u8>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = tokio::sync::oneshot::channel();
+
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        sleep(Duration::from_millis(100)).await;
+        for chunk in peer_writes {
+            stream.write_all(&chunk).await.unwrap();
+            sleep(Duration::from_millis(20)).await;
+        }
+
+        let mut read = Vec::new();
+        let mut buf = [0u8; 1024];
+        while let Ok(Ok(n)) =
+            tokio::time::timeout(Duration::from_millis(300), stream.read(&mut buf)).await
+        {
+            if n == 0 {
+                break;
+            }
+            read.extend_from_slice(&buf[..n]);
+        }
+        let _ = tx.send(read);
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.expect("Should connect");
+    conn.wait_for_established(Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+    (conn, rx)
+}
+
+async fn next_received(conn: &Connection) -> (Vec<u8>, MessageContext) {
+    tokio::time::timeout(Duration::from_secs(2), async {
+        loop {
+            match conn.next_event().await {
+                Some(ConnectionEvent::Received {
+                    message_data,
+                    message_context,
+                }) => break (message_data, message_context),
+                Some(ConnectionEvent::ReceivedPartial { .. }) => {
+                    panic!("Inner framers deliver whole messages")
+                }
+                Some(_) => continue,
+                None => panic!("Event stream ended"),
+            }
+        }
+    })
+    .await
+    .expect("Should receive a message")
+}
+
+#[tokio::test]
+async fn test_connection_nested_framers_round_trip() {
+    let mut wire = length_prefixed(&tlv(1, b"hello"));
+    wire.extend(length_prefixed(&tlv(2, b"world")));
+    let (conn, peer_read) = framed_peer(vec![wire[..7].to_vec(), wire[7..].to_vec()]).await;
+
+    conn.use_length_prefix_framer().await.unwrap();
+    conn.add_framer(Box::new(TlvFramer::new(3))).await.unwrap();
+
+    let (data, context) = next_received(&conn).await;
+    assert_eq!(data, b"hello");
+    let metadata = &context.message_properties().framer_metadata;
+    assert_eq!(metadata.get("type"), Some(&vec![1]));
+    assert_eq!(metadata.get("length"), Some(&8u32.to_be_bytes().to_vec()));
+
+    let (data, context) = next_received(&conn).await;
+    assert_eq!(data, b"world");
+    assert_eq!(
+        context.message_properties().framer_metadata.get("type"),
+        Some(&vec![2])
+    );
+
+    conn.send(Message::from_string("ping")).await.unwrap();
+    assert_eq!(peer_read.await.unwrap(), length_prefixed(&tlv(3, b"ping")));
+}
+
+#[tokio::test]
+async fn test_connection_inner_framers_decode_partial_deliveries() {
+    // The outer message exceeds the reassembly limit, so it arrives in parts
+    let body = vec![b'x'; 40];
+    let wire = length_prefixed(&tlv(5, &body));
+    let (conn, _) = framed_peer(vec![wire[..20].to_vec(), wire[20..].to_vec()]).await;
+
+    conn.set_reassembly_limit(16).await;
+    conn.use_length_prefix_framer().await.unwrap();
+    conn.add_framer(Box::new(TlvFramer::new(5))).await.unwrap();
+
+    let (data, _) = next_received(&conn).await;
+    assert_eq!(data, body);
+}
+
+#[tokio::test]
+async fn test_connection_custom_outermost_framer() {
+    let mut wire = tlv(4, b"one");
+    wire.extend(tlv(4, b"two"));
+    let (conn, _) = framed_peer(vec![wire[..5].to_vec(), wire[5..].to_vec()]).await;
+
+    conn.add_framer(Box::new(TlvFramer::new(4))).await.unwrap();
+
+    assert_eq!(next_received(&conn).await.0, b"one");
+    assert_eq!(next_received(&conn).await.0, b"two");
+}
+
+/// Negotiates a protocol version before the connection is Ready, then passes
+/// messages through unchanged
+struct VersionFramer;
+
+#[async_trait]
+impl Framer for VersionFramer {
+    async fn frame_message(&self, message: &Message, _context: &MessageContext) -> Result<Vec<u8>> {
+        Ok(message.data().to_vec())
+    }
+
+    async fn parse_data(&self, data: &[u8]) -> Result<Vec<(Message, MessageContext)>> {
+        Ok(vec![(Message::from_bytes(data), MessageContext::new())])
+    }
+
+    fn name(&self) -> &str {
+        "version"
+    }
+
+    async fn start(&self, handshake: &mut FramerHandshake<'_>) -> Result<()> {
+        handshake.send(b"HELLO v1\r\n").await?;
+        let reply = handshake.read_until(b"\r\n").await?;
+        if reply != b"OK v1\r\n" {
+            return Err(TransportServicesError::EstablishmentFailed(format!(
+                "Peer refused version: {}",
+                String::from_utf8_lossy(&reply).trim_end()
+            )));
+        }
+        Ok(())
+    }
+
+    async fn stop(&self, handshake: &mut FramerHandshake<'_>) -> Result<()> {
+        handshake.send(b"BYE\r\n").await
+    }
+}
+
+/// Peer that answers the version preamble with `reply`, then records
+/// everything it reads until the client closes
+async fn version_peer(
+    reply: Vec<u8>,
+) -> (
+    std::net::SocketAddr,
+    tokio::sync::oneshot::Receiver<Vec<u8>>,
+) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = tokio::sync::oneshot::channel();
+
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut read = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !read.ends_with(b"\r\n") {
+            let n = stream.read(&mut buf).await.unwrap();
+            read.extend_from_slice(&buf[..n]);
+        }
+        stream.write_all(&reply).await.unwrap();
+
+        while let Ok(Ok(n)) =
+            tokio::time::timeout(Duration::from_secs(2), stream.read(&mut buf)).await
+        {
+            if n == 0 {
+                break;
+            }
+            read.extend_from_slice(&buf[..n]);
+        }
+        let _ = tx.send(read);
+    });
+
+    (addr, rx)
+}
+
+fn version_preconnection(addr: std::net::SocketAddr) -> Preconnection {
+    Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    )
+}
+
+#[tokio::test]
+async fn test_framer_start_and_stop_exchange_preambles() {
+    // The first message follows the reply in the same write
+    let mut reply = b"OK v1\r\n".to_vec();
+    reply.extend(length_prefixed(b"welcome"));
+    let (addr, peer_read) = version_peer(reply).await;
+
+    let preconn = version_preconnection(addr);
+    preconn
+        .add_framer(|| Box::new(VersionFramer))
+        .await
+        .unwrap();
+    preconn
+        .add_framer(|| Box::new(LengthPrefixFramer::new()))
+        .await
+        .unwrap();
+
+    let conn = preconn.initiate().await.unwrap();
+    assert!(matches!(
+        conn.next_event().await,
+        Some(ConnectionEvent::Ready)
+    ));
+
+    let (data, _) = next_received(&conn).await;
+    assert_eq!(data, b"welcome");
+
+    conn.send(Message::from_string("ping")).await.unwrap();
+    conn.close().await.unwrap();
+
+    let mut expected = b"HELLO v1\r\n".to_vec();
+    expected.extend(length_prefixed(b"ping"));
+    expected.extend(b"BYE\r\n");
+    assert_eq!(peer_read.await.unwrap(), expected);
+}
+
+#[tokio::test]
+async fn test_framer_start_failure_is_establishment_error() {
+    let (addr, _) = version_peer(b"NO v2\r\n".to_vec()).await;
+
+    let preconn = version_preconnection(addr);
+    preconn
+        .add_framer(|| Box::new(VersionFramer))
+        .await
+        .unwrap();
+
+    let conn = preconn.initiate().await.unwrap();
+    let event = tokio::time::timeout(Duration::from_secs(2), conn.next_event())
+        .await
+        .expect("Should report the failure");
+    match event {
+        Some(ConnectionEvent::EstablishmentError(error)) => {
+            assert!(error.contains("Framer version failed to start"), "{error}");
+            assert!(error.contains("NO v2"), "{error}");
+        }
+        other => panic!("Expected EstablishmentError, got {other:?}"),
+    }
+    assert_eq!(conn.state().await, ConnectionState::Closed);
+}
+
+/// Newline-delimited text framer
+struct LineFramer {
+    buffer: Arc<Mutex<Vec<u8>>>,
+}
+
+impl LineFramer {
+    fn new() -> Self {
+        Self {
+            buffer: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+#[async_trait]
+impl Framer for LineFramer {
+    async fn frame_message(&self, message: &Message, _context: &MessageContext) -> Result<Vec<u8>> {
+        let mut framed = message.data().to_vec();
+        framed.push(b'\n');
+        Ok(framed)
+    }
+
+    async fn parse_data(&self, data: &[u8]) -> Result<Vec<(Message, MessageContext)>> {
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.extend_from_slice(data);
+
+        let mut messages = Vec::new();
+        while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=end).collect();
+            messages.push((
+                Message::from_bytes(&line[..line.len() - 1]),
+                MessageContext::new(),
+            ));
+        }
+        Ok(messages)
+    }
+
+    fn name(&self) -> &str {
+        "line"
+    }
+
+    fn reset(&self) {
+        self.buffer.lock().unwrap().clear();
+    }
+
+    fn take_unparsed(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.buffer.lock().unwrap())
+    }
+}
+
+fn length_prefix_stack() -> FramerStack {
+    let mut stack = FramerStack::new();
+    stack.add_framer(Box::new(LengthPrefixFramer::new()));
+    stack
+}
+
+#[tokio::test]
+async fn test_replace_framers_upgrades_protocol() {
+    // The first binary message arrives with the line that announces it
+    let mut wire = b"HELLO\nSTARTBIN\n".to_vec();
+    wire.extend(length_prefixed(b"bin1"));
+    let (conn, peer_read) = framed_peer(vec![wire, length_prefixed(b"bin2")]).await;
+    conn.add_framer(Box::new(LineFramer::new())).await.unwrap();
+
+    assert_eq!(next_received(&conn).await.0, b"HELLO");
+    assert_eq!(next_received(&conn).await.0, b"STARTBIN");
+    conn.send(Message::from_string("OK")).await.unwrap();
+
+    // The bytes the line framer held back are parsed by the new framer
+    conn.replace_framers(length_prefix_stack()).await.unwrap();
+    assert_eq!(next_received(&conn).await.0, b"bin1");
+    assert_eq!(next_received(&conn).await.0, b"bin2");
+
+    conn.send(Message::from_string("done")).await.unwrap();
+    let mut expected = b"OK\n".to_vec();
+    expected.extend(length_prefixed(b"done"));
+    assert_eq!(peer_read.await.unwrap(), expected);
+}
+
+#[tokio::test]
+async fn test_replace_framers_refused_mid_message() {
+    // Only part of an oversized message has arrived
+    let wire = length_prefixed(&[b'x'; 64]);
+    let (conn, _) = framed_peer(vec![wire[..20].to_vec()]).await;
+    conn.set_reassembly_limit(8).await;
+    conn.use_length_prefix_framer().await.unwrap();
+
+    tokio::time::timeout(Duration::from_secs(2), async {
+        loop {
+            if let Some(ConnectionEvent::ReceivedPartial { .. }) = conn.next_event().await {
+                break;
+            }
+        }
+    })
+    .await
+    .expect("Should receive part of the message");
+
+    assert!(matches!(
+        conn.replace_framers(FramerStack::new()).await,
+        Err(TransportServicesError::InvalidState(_))
+    ));
+}
@@ -5,6 +5,17 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpListener;
 use tokio::time::{sleep, Duration};
 
+/// Wait for an event matching `predicate`, skipping any others
+async fn expect_event(conn: &Connection, predicate: impl Fn(&ConnectionEvent) -> bool) {
+    loop {
+        match tokio::time::timeout(Duration::from_secs(2), conn.next_event()).await {
+            Ok(Some(event)) if predicate(&event) => return,
+            Ok(Some(_)) => continue,
+            other => panic!("Expected event not received, got {other:?}"),
+        }
+    }
+}
+
 #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
 async fn test_close_group_closes_all_connections() {
     // Start a TCP listener
@@ -84,6 +95,11 @@ async fn test_close_group_closes_all_connections() {
     assert_eq!(conn2.state().await, ConnectionState::Closed);
     assert_eq!(conn3.state().await, ConnectionState::Closed);
 
+    // Every member observes the closure, not just the caller
+    for conn in [&conn1, &conn2, &conn3] {
+        expect_event(conn, |e| matches!(e, ConnectionEvent::Closed)).await;
+    }
+
     // Verify we can't send on any connection
     let msg = Message::from_bytes(b"test");
     assert!(conn1.send(msg.clone()).await.is_err());
@@ -148,6 +164,13 @@ async fn test_abort_group_aborts_all_connections() {
         other => panic!("Expected ConnectionError event, got {other:?}"),
     }
 
+    // The other member observes the abort as well
+    expect_event(
+        &conn2,
+        |e| matches!(e, ConnectionEvent::ConnectionError(msg) if msg.contains("aborted")),
+    )
+    .await;
+
     // Verify all connections are closed
     assert_eq!(conn1.state().await, ConnectionState::Closed);
     assert_eq!(conn2.state().await, ConnectionState::Closed);
@@ -277,3 +300,111 @@ async fn test_abort_group_on_ungrouped_connection() {
     .await
     .expect("Test should complete within timeout");
 }
+
+/// Connect a group of `size` connections to a server that discards all data
+async fn create_discarding_group(size: usize) -> Vec<Connection> {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buf = [0u8; 1024];
+                while let Ok(n) = stream.read(&mut buf).await {
+                    if n == 0 {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let first = preconn.initiate().await.unwrap();
+    expect_event(&first, |e| matches!(e, ConnectionEvent::Ready)).await;
+
+    let mut group = vec![first];
+    for _ in 1..size {
+        let member = group[0].clone_connection().await.unwrap();
+        expect_event(&member, |e| matches!(e, ConnectionEvent::Ready)).await;
+        group.push(member);
+    }
+    group
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_close_group_with_drain_in_priority_order() {
+    let group = create_discarding_group(3).await;
+    let (urgent, background, normal) = (&group[0], &group[1], &group[2]);
+    urgent
+        .set_property("connPriority", ConnectionProperty::ConnPriority(1))
+        .await
+        .unwrap();
+    background
+        .set_property("connPriority", ConnectionProperty::ConnPriority(200))
+        .await
+        .unwrap();
+
+    // Data still held by the link delays each member's drain
+    for (conn, latency) in [(urgent, 200), (normal, 400), (background, 50)] {
+        conn.set_network_conditions(
+            NetworkConditions::new().with_latency(Duration::from_millis(latency)),
+        )
+        .await;
+        conn.send(Message::from_bytes(b"outstanding"))
+            .await
+            .unwrap();
+    }
+
+    let drain = {
+        let conn = urgent.clone();
+        tokio::spawn(async move { conn.close_group_with_drain(Duration::from_secs(5)).await })
+    };
+
+    // New sends fail on every member while the group drains
+    sleep(Duration::from_millis(25)).await;
+    assert!(background.send(Message::from_bytes(b"late")).await.is_err());
+
+    // The urgent member has closed; the background member waits behind the
+    // default one even though its own data was delivered long ago
+    sleep(Duration::from_millis(275)).await;
+    assert_eq!(urgent.state().await, ConnectionState::Closed);
+    assert_eq!(normal.state().await, ConnectionState::Closing);
+    assert_eq!(background.state().await, ConnectionState::Closing);
+
+    drain.await.unwrap().unwrap();
+    for conn in &group {
+        assert_eq!(conn.state().await, ConnectionState::Closed);
+        expect_event(conn, |e| matches!(e, ConnectionEvent::Closed)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_close_group_with_drain_aborts_at_deadline() {
+    let group = create_discarding_group(2).await;
+    group[1]
+        .set_network_conditions(NetworkConditions::new().with_latency(Duration::from_secs(10)))
+        .await;
+    group[1].send(Message::from_bytes(b"stuck")).await.unwrap();
+
+    let started = std::time::Instant::now();
+    group[0]
+        .close_group_with_drain(Duration::from_millis(200))
+        .await
+        .unwrap();
+    assert!(started.elapsed() < Duration::from_secs(2));
+
+    // The member that drained in time closed; the other was aborted
+    assert_eq!(group[0].state().await, ConnectionState::Closed);
+    expect_event(&group[0], |e| matches!(e, ConnectionEvent::Closed)).await;
+    assert_eq!(group[1].state().await, ConnectionState::Closed);
+    expect_event(
+        &group[1],
+        |e| matches!(e, ConnectionEvent::ConnectionError(error) if error.contains("deadline")),
+    )
+    .await;
+}
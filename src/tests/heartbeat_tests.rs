@@ -0,0 +1,112 @@
+//! Tests for heartbeat-based liveness detection
+
+use crate::*;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Peer that answers each probe while `answering` is set, and counts them
+async fn probed_peer(
+    answering: Arc<AtomicBool>,
+    probes: Arc<AtomicUsize>,
+) -> (std::net::SocketAddr, tokio::task::JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let peer = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut probe = [0u8; 4];
+        while stream.read_exact(&mut probe).await.is_ok() {
+            probes.fetch_add(1, Ordering::Relaxed);
+            if answering.load(Ordering::Relaxed) {
+                stream.write_all(b"pong").await.unwrap();
+            }
+        }
+    });
+    (addr, peer)
+}
+
+async fn connect(addr: std::net::SocketAddr) -> Connection {
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.unwrap();
+    conn.wait_for_established(Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+    conn
+}
+
+fn heartbeat() -> Heartbeat {
+    Heartbeat::new("ping")
+        .with_interval(Duration::from_millis(50))
+        .with_response_window(Duration::from_millis(50))
+        .with_missed_probes(2)
+}
+
+/// Wait for an Unresponsive event, or None if there is none within `wait`
+async fn unresponsive(conn: &Connection, wait: Duration) -> Option<u32> {
+    tokio::time::timeout(wait, async {
+        loop {
+            match conn.next_event().await {
+                Some(ConnectionEvent::Unresponsive { missed_probes }) => return missed_probes,
+                Some(_) => {}
+                None => panic!("Event stream ended"),
+            }
+        }
+    })
+    .await
+    .ok()
+}
+
+#[tokio::test]
+async fn test_unanswered_probes_report_unresponsive() {
+    let answering = Arc::new(AtomicBool::new(true));
+    let probes = Arc::new(AtomicUsize::new(0));
+    let (addr, peer) = probed_peer(Arc::clone(&answering), Arc::clone(&probes)).await;
+    let conn = connect(addr).await;
+    conn.start_heartbeat(heartbeat()).await.unwrap();
+
+    // An answering peer is live
+    assert_eq!(unresponsive(&conn, Duration::from_millis(500)).await, None);
+    assert!(probes.load(Ordering::Relaxed) >= 3);
+
+    // A peer that keeps the transport up but stops answering is not
+    answering.store(false, Ordering::Relaxed);
+    assert_eq!(unresponsive(&conn, Duration::from_secs(2)).await, Some(2));
+    assert_eq!(conn.state().await, ConnectionState::Established);
+
+    peer.abort();
+}
+
+#[tokio::test]
+async fn test_stop_heartbeat() {
+    let answering = Arc::new(AtomicBool::new(false));
+    let probes = Arc::new(AtomicUsize::new(0));
+    let (addr, peer) = probed_peer(answering, Arc::clone(&probes)).await;
+    let conn = connect(addr).await;
+
+    conn.start_heartbeat(heartbeat()).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    conn.stop_heartbeat().await;
+    tokio::time::sleep(Duration::from_millis(150)).await;
+
+    let sent = probes.load(Ordering::Relaxed);
+    assert!(sent >= 1);
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(probes.load(Ordering::Relaxed), sent);
+    assert_eq!(unresponsive(&conn, Duration::from_millis(100)).await, None);
+
+    // Probing needs a positive interval
+    assert!(matches!(
+        conn.start_heartbeat(Heartbeat::new("ping").with_interval(Duration::ZERO))
+            .await,
+        Err(TransportServicesError::InvalidParameters(_))
+    ));
+
+    peer.abort();
+}
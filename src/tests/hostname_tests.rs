@@ -0,0 +1,127 @@
+//! Tests for hostname validation and IDNA conversion
+
+use crate::hostname::to_ascii;
+use crate::resolver::ResolutionCache;
+use crate::*;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+fn test_ascii_hostnames_are_lowercased() {
+    assert_eq!(to_ascii("Example.COM").unwrap(), "example.com");
+    assert_eq!(to_ascii("example.com.").unwrap(), "example.com");
+    assert_eq!(to_ascii("a-1.b2").unwrap(), "a-1.b2");
+    assert_eq!(
+        to_ascii("xn--bcher-kva.test").unwrap(),
+        "xn--bcher-kva.test"
+    );
+    assert_eq!(to_ascii("192.0.2.1").unwrap(), "192.0.2.1");
+    assert_eq!(to_ascii("2001:db8::1").unwrap(), "2001:db8::1");
+}
+
+#[test]
+fn test_internationalized_hostnames_use_punycode() {
+    // RFC 3492 Section 7.1 and common IDNs
+    assert_eq!(to_ascii("bücher.test").unwrap(), "xn--bcher-kva.test");
+    assert_eq!(to_ascii("MÜNCHEN.de").unwrap(), "xn--mnchen-3ya.de");
+    assert_eq!(to_ascii("日本語.jp").unwrap(), "xn--wgv71a119e.jp");
+    assert_eq!(
+        to_ascii("ليهمابتكلموشعربي؟").unwrap(),
+        "xn--egbpdaj6bu4bxfgehfvwxn"
+    );
+    assert_eq!(
+        to_ascii("他们为什么不说中文").unwrap(),
+        "xn--ihqwcrb4cv8a8dqg056pqjye"
+    );
+    // Ideographic full stops separate labels
+    assert_eq!(to_ascii("例え。テスト").unwrap(), "xn--r8jz45g.xn--zckzah");
+}
+
+#[test]
+fn test_hostnames_are_mapped_per_uts46() {
+    // Fullwidth forms map to ASCII, and ß is kept rather than folded to ss
+    assert_eq!(to_ascii("ＥＸＡＭＰＬＥ.com").unwrap(), "example.com");
+    assert_eq!(to_ascii("faß.de").unwrap(), "xn--fa-hia.de");
+    // Characters that map to disallowed ASCII are still refused
+    assert!(to_ascii("a／b.test").is_err());
+}
+
+#[test]
+fn test_underscore_labels_are_allowed() {
+    // Service and policy records, e.g. DMARC (RFC 7489) and SRV (RFC 2782)
+    assert_eq!(
+        to_ascii("_dmarc.Example.com").unwrap(),
+        "_dmarc.example.com"
+    );
+    assert_eq!(
+        to_ascii("_sip._tcp.bücher.test").unwrap(),
+        "_sip._tcp.xn--bcher-kva.test"
+    );
+}
+
+#[test]
+fn test_malformed_hostnames_are_rejected() {
+    let long_label = "a".repeat(64);
+    let long_name = vec!["a".repeat(63); 4].join(".");
+    for (hostname, reason) in [
+        ("", "empty"),
+        (".", "empty"),
+        ("a..b", "empty label"),
+        ("exa mple.com", "' '"),
+        ("example.com/path", "'/'"),
+        ("user@example.com", "'@'"),
+        ("-example.com", "'-'"),
+        ("example-.com", "'-'"),
+        ("bang!.test", "'!'"),
+        ("tab\u{9}ü.test", "'\\t'"),
+        (long_label.as_str(), "63"),
+        (long_name.as_str(), "253"),
+    ] {
+        match to_ascii(hostname) {
+            Err(TransportServicesError::InvalidParameters(error)) => {
+                assert!(error.contains(reason), "{hostname:?}: {error}")
+            }
+            other => panic!("Expected {hostname:?} to be rejected, got {other:?}"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_resolution_uses_ascii_hostnames() {
+    let cache = Arc::new(ResolutionCache::new());
+    let addr: SocketAddr = "192.0.2.1:443".parse().unwrap();
+    cache.insert(
+        "xn--bcher-kva.test",
+        443,
+        vec![addr],
+        Duration::from_secs(60),
+    );
+
+    // Unicode and ASCII forms share the cache entry
+    assert_eq!(cache.resolve("Bücher.test", 443).await.unwrap(), vec![addr]);
+    cache.insert("日本語.jp", 443, vec![addr], Duration::from_secs(60));
+    assert_eq!(
+        cache.resolve("xn--wgv71a119e.jp", 443).await.unwrap(),
+        vec![addr]
+    );
+}
+
+#[tokio::test]
+async fn test_initiate_rejects_malformed_hostname() {
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .hostname("bad host.example")
+            .port(443)
+            .build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    match preconn.initiate().await {
+        Err(TransportServicesError::InvalidParameters(error)) => {
+            assert!(error.contains("Invalid hostname"), "{error}")
+        }
+        other => panic!("Expected an invalid hostname error, got {other:?}"),
+    }
+}
@@ -0,0 +1,91 @@
+//! Tests for LEDBAT pacing of Scavenger connections
+
+use crate::ledbat::{LedbatPacer, TARGET_DELAY};
+use std::time::{Duration, Instant};
+
+const MSS: usize = 1448;
+
+/// Feed a pacer one sample per round trip
+fn sample(pacer: &mut LedbatPacer, now: &mut Instant, rtt: Duration) {
+    *now += rtt;
+    assert!(pacer.needs_sample(*now));
+    pacer.on_rtt(rtt, *now);
+}
+
+#[test]
+fn test_pacer_holds_nothing_back_before_a_sample() {
+    let now = Instant::now();
+    let mut pacer = LedbatPacer::new(now);
+    assert!(pacer.needs_sample(now));
+    assert_eq!(pacer.reserve(1_000_000, now), Duration::ZERO);
+    assert_eq!(pacer.queuing_delay(), None);
+}
+
+#[test]
+fn test_pacer_slow_starts_without_queuing() {
+    let mut now = Instant::now();
+    let mut pacer = LedbatPacer::new(now);
+    let initial = pacer.window();
+
+    sample(&mut pacer, &mut now, Duration::from_millis(20));
+    assert_eq!(pacer.window(), initial);
+    assert!(!pacer.needs_sample(now));
+
+    sample(&mut pacer, &mut now, Duration::from_millis(20));
+    assert_eq!(pacer.queuing_delay(), Some(Duration::ZERO));
+    assert_eq!(pacer.window(), 2 * initial);
+    sample(&mut pacer, &mut now, Duration::from_millis(20));
+    assert_eq!(pacer.window(), 4 * initial);
+}
+
+#[test]
+fn test_pacer_backs_off_as_delay_inflates() {
+    let mut now = Instant::now();
+    let mut pacer = LedbatPacer::new(now);
+    let base = Duration::from_millis(20);
+    for _ in 0..5 {
+        sample(&mut pacer, &mut now, base);
+    }
+    let grown = pacer.window();
+
+    // Queuing at one and a half times the target cuts the window by half
+    // the excess
+    sample(&mut pacer, &mut now, base + TARGET_DELAY * 3 / 2);
+    assert_eq!(pacer.queuing_delay(), Some(TARGET_DELAY * 3 / 2));
+    assert_eq!(pacer.window(), grown / 2);
+
+    // Far above the target it at most halves, down to the minimum window
+    for _ in 0..20 {
+        sample(&mut pacer, &mut now, base + TARGET_DELAY * 4);
+    }
+    assert_eq!(pacer.window(), 2 * MSS);
+
+    // Once queues drain it grows again, additively outside slow start
+    let floor = pacer.window();
+    now += base + TARGET_DELAY * 4;
+    sample(&mut pacer, &mut now, base);
+    assert!(pacer.window() > floor);
+    assert!(pacer.window() < 2 * floor);
+}
+
+#[test]
+fn test_pacer_spreads_sends_over_the_round_trip() {
+    let mut now = Instant::now();
+    let mut pacer = LedbatPacer::new(now);
+    let rtt = Duration::from_millis(100);
+    sample(&mut pacer, &mut now, rtt);
+
+    // A window's worth of data takes a round trip to send
+    let window = pacer.window();
+    assert_eq!(pacer.reserve(window / 2, now), Duration::ZERO);
+    let wait = pacer.reserve(window / 2, now);
+    assert!(
+        wait.abs_diff(rtt / 2) < Duration::from_millis(1),
+        "{wait:?}"
+    );
+
+    // An idle pacer builds up no credit
+    now += rtt * 10;
+    assert_eq!(pacer.reserve(window, now), Duration::ZERO);
+    assert!(pacer.reserve(1, now) > Duration::ZERO);
+}
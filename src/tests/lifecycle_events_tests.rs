@@ -347,3 +347,245 @@ async fn test_multiple_lifecycle_events() {
     .await
     .expect("Test should complete within timeout");
 }
+
+#[tokio::test]
+async fn test_stats_events_report_traffic() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buf = [0u8; 5];
+        stream.read_exact(&mut buf).await.unwrap();
+        stream.write_all(b"0123456789").await.unwrap();
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    });
+
+    let properties = TransportProperties::builder()
+        .stats_interval(Duration::from_millis(50))
+        .build();
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        properties,
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.expect("Should connect");
+    conn.send(Message::from_string("hello")).await.unwrap();
+
+    // Stats keep arriving until they account for the exchanged data
+    let stats = tokio::time::timeout(Duration::from_secs(2), async {
+        loop {
+            match conn.next_event().await {
+                Some(ConnectionEvent::Stats(stats)) if stats.bytes_received == 10 => break stats,
+                Some(_) => {}
+                None => panic!("Event channel closed"),
+            }
+        }
+    })
+    .await
+    .expect("Expected a Stats event with the received data");
+
+    assert_eq!(stats.bytes_sent, 5);
+    assert_eq!(stats.send_queue, 0);
+    #[cfg(target_os = "linux")]
+    {
+        assert!(stats.rtt.is_some());
+        assert_eq!(stats.retransmits, Some(0));
+    }
+    assert_eq!(conn.stats().await.bytes_sent, 5);
+}
+
+#[tokio::test]
+async fn test_no_stats_events_without_interval() {
+    let conn = create_test_connection().await;
+
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(200);
+    while let Ok(event) = tokio::time::timeout_at(deadline, conn.next_event()).await {
+        assert!(!matches!(event, Some(ConnectionEvent::Stats(_))));
+    }
+}
+
+#[tokio::test]
+async fn test_timed_events_report_send_and_receive_times() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buf = [0u8; 5];
+        stream.read_exact(&mut buf).await.unwrap();
+        stream.write_all(b"world").await.unwrap();
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.expect("Should connect");
+    let ready = conn.next_timed_event().await.unwrap();
+    assert!(matches!(ready.event, ConnectionEvent::Ready));
+    assert_eq!(ready.timestamps.send_started, None);
+    assert_eq!(ready.timestamps.received, None);
+
+    conn.send(Message::from_string("hello")).await.unwrap();
+    // Leave the events queued for a while
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let (mut sent, mut received) = (None, None);
+    while sent.is_none() || received.is_none() {
+        let timed = tokio::time::timeout(Duration::from_secs(2), conn.next_timed_event())
+            .await
+            .expect("Expected Sent and Received events")
+            .unwrap();
+        match timed.event {
+            ConnectionEvent::Sent { .. } => sent = Some(timed),
+            ConnectionEvent::Received { .. } => received = Some(timed),
+            _ => {}
+        }
+    }
+
+    let sent = sent.unwrap();
+    let started = sent.timestamps.send_started.unwrap();
+    let completed = sent.timestamps.send_completed.unwrap();
+    assert!(started <= completed && completed <= sent.timestamps.enqueued);
+    assert!(sent.send_latency().is_some());
+    assert!(sent.queue_latency() >= Duration::from_millis(50));
+
+    let received = received.unwrap();
+    assert!(received.timestamps.received.unwrap() <= received.timestamps.enqueued);
+    assert!(received.receive_latency().unwrap() >= received.queue_latency());
+    assert!(received.timestamps.send_started.is_none());
+}
+
+#[test]
+fn test_event_classes() {
+    let classes = EventClass::Data | EventClass::Lifecycle;
+    assert!(classes.contains(EventClass::Data));
+    assert!(classes.contains(EventClass::Lifecycle));
+    assert!(!classes.contains(EventClass::Send));
+    assert_eq!(
+        classes.iter().collect::<Vec<_>>(),
+        vec![EventClass::Lifecycle, EventClass::Data]
+    );
+    assert!(EventClasses::empty().is_empty());
+    assert!(EventClass::ALL
+        .iter()
+        .all(|c| EventClasses::all().contains(*c)));
+
+    assert_eq!(ConnectionEvent::Closed.class(), EventClass::Lifecycle);
+    assert_eq!(ConnectionEvent::FinalReceived.class(), EventClass::Data);
+    assert_eq!(
+        ConnectionEvent::Sent { message_id: None }.class(),
+        EventClass::Send
+    );
+    assert_eq!(ConnectionEvent::PathChange.class(), EventClass::Path);
+    assert_eq!(
+        ConnectionEvent::Stats(ConnectionStats::default()).class(),
+        EventClass::Stats
+    );
+}
+
+#[tokio::test]
+async fn test_filtered_events_leave_other_classes_queued() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buf = [0u8; 5];
+        stream.read_exact(&mut buf).await.unwrap();
+        stream.write_all(b"world").await.unwrap();
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.expect("Should connect");
+    let mut data = conn.events_filtered(EventClass::Data);
+    assert_eq!(data.classes(), EventClasses::from(EventClass::Data));
+    let event = tokio::time::timeout(Duration::from_secs(2), conn.next_event())
+        .await
+        .unwrap();
+    assert!(matches!(event, Some(ConnectionEvent::Ready)));
+
+    conn.send(Message::from_string("hello")).await.unwrap();
+    let event = tokio::time::timeout(Duration::from_secs(2), data.next_event())
+        .await
+        .unwrap();
+    match event {
+        Some(ConnectionEvent::Received { message_data, .. }) => {
+            assert_eq!(message_data, b"world")
+        }
+        other => panic!("Expected Received event, got {other:?}"),
+    }
+
+    // The Sent event stays in the connection's own queue
+    let event = tokio::time::timeout(Duration::from_secs(2), conn.next_event())
+        .await
+        .unwrap();
+    assert!(matches!(event, Some(ConnectionEvent::Sent { .. })));
+
+    // Once the subscription is dropped, data is queued as before
+    drop(data);
+    conn.send(Message::from_string("again")).await.unwrap();
+    let event = tokio::time::timeout(Duration::from_secs(2), conn.next_event())
+        .await
+        .unwrap();
+    assert!(matches!(event, Some(ConnectionEvent::Sent { .. })));
+}
+
+#[tokio::test]
+async fn test_slow_data_consumer_does_not_delay_closed() {
+    use tokio::io::AsyncWriteExt;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        for _ in 0..10 {
+            stream.write_all(b"chunk").await.unwrap();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.expect("Should connect");
+    let mut events =
+        conn.events_filtered_with_capacity(EventClass::Data | EventClass::Lifecycle, 2);
+
+    // Let data pile up past the buffer without taking any
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    conn.close().await.unwrap();
+
+    let mut lifecycle = Vec::new();
+    while let Ok(Some(event)) =
+        tokio::time::timeout(Duration::from_millis(200), events.next_event()).await
+    {
+        match event {
+            ConnectionEvent::Ready | ConnectionEvent::Closed => lifecycle.push(event),
+            _ => break,
+        }
+    }
+    assert!(matches!(
+        lifecycle.as_slice(),
+        [ConnectionEvent::Ready, ConnectionEvent::Closed]
+    ));
+    assert!(events.dropped() > 0);
+}
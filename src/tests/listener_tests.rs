@@ -1,9 +1,11 @@
 //! Unit tests for Listener implementation
 
+use crate::path_monitor::{ChangeEvent, Interface, Status};
 use crate::{
     listener::ListenerEvent, EndpointIdentifier, LocalEndpoint, Preconnection, SecurityParameters,
     TransportProperties,
 };
+use std::net::{IpAddr, SocketAddr};
 use std::time::Duration;
 use tokio::time::{sleep, timeout};
 
@@ -341,3 +343,1150 @@ async fn test_listener_bind_any_address() {
 
     listener.stop().await.unwrap();
 }
+
+/// Create a datagram (UDP) listener on an OS-chosen loopback port
+async fn create_datagram_listener() -> crate::Listener {
+    let preconn = Preconnection::new(
+        vec![LocalEndpoint {
+            identifiers: vec![
+                EndpointIdentifier::IpAddress("127.0.0.1".parse().unwrap()),
+                EndpointIdentifier::Port(0),
+            ],
+        }],
+        vec![],
+        TransportProperties::builder()
+            .reliability(crate::Preference::Prohibit)
+            .build(),
+        SecurityParameters::new_disabled(),
+    );
+
+    preconn.listen().await.unwrap()
+}
+
+/// Wait for the next Received event on a connection
+async fn next_datagram(conn: &crate::Connection) -> (Vec<u8>, crate::MessageContext) {
+    loop {
+        match timeout(Duration::from_secs(2), conn.next_event()).await {
+            Ok(Some(crate::ConnectionEvent::Received {
+                message_data,
+                message_context,
+            })) => return (message_data, message_context),
+            Ok(Some(_)) => continue,
+            other => panic!("Expected Received event, got {other:?}"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_datagram_listener_demultiplexes_peers() {
+    let listener = create_datagram_listener().await;
+    let addr = listener.local_addr().await.unwrap();
+
+    let peer_a = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let peer_b = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+    peer_a.send_to(b"from a", addr).await.unwrap();
+    let conn_a = timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+    peer_b.send_to(b"from b", addr).await.unwrap();
+    let conn_b = timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Each datagram carries its actual source on the MessageContext
+    let (data, context) = next_datagram(&conn_a).await;
+    assert_eq!(data, b"from a");
+    assert_eq!(context.message_properties().ordered, Some(false));
+    assert_eq!(
+        context.remote_endpoint.unwrap().identifiers,
+        vec![EndpointIdentifier::SocketAddress(
+            peer_a.local_addr().unwrap()
+        )]
+    );
+
+    let (data, context) = next_datagram(&conn_b).await;
+    assert_eq!(data, b"from b");
+    assert_eq!(
+        context.remote_endpoint.unwrap().identifiers,
+        vec![EndpointIdentifier::SocketAddress(
+            peer_b.local_addr().unwrap()
+        )]
+    );
+
+    // A second datagram from a known peer stays on its flow
+    peer_a.send_to(b"again", addr).await.unwrap();
+    let (data, _) = next_datagram(&conn_a).await;
+    assert_eq!(data, b"again");
+
+    // Replies go back to the flow's peer
+    conn_b
+        .send(crate::Message::from_bytes(b"reply"))
+        .await
+        .unwrap();
+    let mut buf = [0u8; 64];
+    let (n, from) = timeout(Duration::from_secs(2), peer_b.recv_from(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(&buf[..n], b"reply");
+    assert_eq!(from, addr);
+
+    // UDP cannot honour per-message requests for ordered or reliable delivery
+    let result = conn_b
+        .send(crate::Message::from_bytes(b"ordered").with_ordered(true))
+        .await;
+    assert!(matches!(
+        result,
+        Err(crate::TransportServicesError::NotSupported(_))
+    ));
+    let result = conn_b
+        .send(crate::Message::from_bytes(b"reliable").with_reliable(true))
+        .await;
+    assert!(matches!(
+        result,
+        Err(crate::TransportServicesError::NotSupported(_))
+    ));
+
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_datagram_listener_shared_flow() {
+    let listener = create_datagram_listener().await;
+    listener.set_peer_demultiplexing(false);
+    let addr = listener.local_addr().await.unwrap();
+
+    let peer_a = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let peer_b = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+    peer_a.send_to(b"from a", addr).await.unwrap();
+    let conn = timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+    let (_, context) = next_datagram(&conn).await;
+    assert_eq!(
+        context.remote_endpoint.unwrap().identifiers,
+        vec![EndpointIdentifier::SocketAddress(
+            peer_a.local_addr().unwrap()
+        )]
+    );
+
+    // The second peer arrives on the same Connection
+    peer_b.send_to(b"from b", addr).await.unwrap();
+    let (data, context) = next_datagram(&conn).await;
+    assert_eq!(data, b"from b");
+    assert_eq!(
+        context.remote_endpoint.unwrap().identifiers,
+        vec![EndpointIdentifier::SocketAddress(
+            peer_b.local_addr().unwrap()
+        )]
+    );
+    assert!(timeout(Duration::from_millis(200), listener.accept())
+        .await
+        .is_err());
+
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_datagram_listener_idle_eviction() {
+    let listener = create_datagram_listener().await;
+    listener.set_peer_idle_timeout(Duration::from_millis(200));
+    let addr = listener.local_addr().await.unwrap();
+
+    let peer = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    peer.send_to(b"hello", addr).await.unwrap();
+    let conn = timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+
+    // The idle flow is closed
+    let closed = timeout(Duration::from_secs(2), async {
+        loop {
+            match conn.next_event().await {
+                Some(crate::ConnectionEvent::Closed) | None => break,
+                Some(_) => {}
+            }
+        }
+    })
+    .await;
+    assert!(closed.is_ok(), "Idle flow should be evicted");
+    assert_eq!(conn.state().await, crate::ConnectionState::Closed);
+
+    // A new datagram from the same peer starts a fresh flow
+    peer.send_to(b"back again", addr).await.unwrap();
+    let conn = timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+    let (data, _) = next_datagram(&conn).await;
+    assert_eq!(data, b"back again");
+
+    listener.stop().await.unwrap();
+}
+
+async fn create_tcp_listener() -> crate::Listener {
+    let preconn = Preconnection::new(
+        vec![LocalEndpoint {
+            identifiers: vec![
+                EndpointIdentifier::IpAddress("127.0.0.1".parse().unwrap()),
+                EndpointIdentifier::Port(0),
+            ],
+        }],
+        vec![],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+
+    preconn.listen().await.unwrap()
+}
+
+/// Connect a raw client and report whether the listener kept the connection open
+async fn connect_and_probe(addr: std::net::SocketAddr) -> (tokio::net::TcpStream, bool) {
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let mut buf = [0u8; 1];
+    // A rejected connection is closed immediately
+    let rejected = matches!(
+        timeout(
+            Duration::from_millis(200),
+            tokio::io::AsyncReadExt::read(&mut stream, &mut buf)
+        )
+        .await,
+        Ok(Ok(0)) | Ok(Err(_))
+    );
+    (stream, !rejected)
+}
+
+#[tokio::test]
+async fn test_listener_per_ip_and_global_limits() {
+    let listener = create_tcp_listener().await;
+    let addr = listener.local_addr().await.unwrap();
+    listener.set_admission_policy(
+        crate::AdmissionPolicy::new()
+            .with_max_connections_per_ip(1)
+            .with_max_concurrent_connections(1),
+    );
+
+    let (_first, admitted) = connect_and_probe(addr).await;
+    assert!(admitted);
+    let conn = timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+
+    // A second concurrent connection from the same source is refused
+    let (_second, admitted) = connect_and_probe(addr).await;
+    assert!(!admitted);
+    let stats = listener.stats();
+    assert_eq!(stats.accepted, 1);
+    assert_eq!(stats.rejected(), 1);
+
+    // Once the first connection closes there is room again
+    conn.close().await.unwrap();
+    let (_third, admitted) = connect_and_probe(addr).await;
+    assert!(admitted);
+    assert_eq!(listener.stats().accepted, 2);
+
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_listener_accept_rate_per_ip() {
+    let listener = create_tcp_listener().await;
+    let addr = listener.local_addr().await.unwrap();
+    listener.set_admission_policy(crate::AdmissionPolicy::new().with_accept_rate_per_ip(0.01, 2));
+
+    let mut clients = Vec::new();
+    for expected in [true, true, false] {
+        let (stream, admitted) = connect_and_probe(addr).await;
+        assert_eq!(admitted, expected);
+        clients.push(stream);
+    }
+
+    let stats = listener.stats();
+    assert_eq!(stats.accepted, 2);
+    assert_eq!(stats.rejected_rate_limit, 1);
+
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_admission_releases_closed_connections_under_default_policy() {
+    use crate::admission::{AdmissionControl, AdmissionPolicy, ListenerStats};
+    use std::sync::{Arc, Mutex};
+
+    let listener = create_tcp_listener().await;
+    let addr = listener.local_addr().await.unwrap();
+    let mut admission = AdmissionControl::new(
+        Arc::new(Mutex::new(AdmissionPolicy::default())),
+        Arc::new(Mutex::new(ListenerStats::default())),
+    );
+    let source: IpAddr = "127.0.0.1".parse().unwrap();
+
+    let mut clients = Vec::new();
+    for _ in 0..3 {
+        clients.push(tokio::net::TcpStream::connect(addr).await.unwrap());
+        let conn = timeout(Duration::from_secs(2), listener.accept())
+            .await
+            .unwrap()
+            .unwrap();
+        let ticket = admission.check(source).unwrap();
+        admission.admit(source, ticket, &conn).await;
+        assert_eq!(admission.tracked(), 1);
+
+        // Closed connections are released before the next one is admitted
+        conn.close().await.unwrap();
+    }
+    drop(admission.check(source).unwrap());
+    assert_eq!(admission.tracked(), 1);
+
+    listener.stop().await.unwrap();
+}
+
+#[test]
+fn test_admission_counts_handshakes_until_they_fail() {
+    use crate::admission::{AdmissionControl, AdmissionPolicy, ListenerStats};
+    use std::sync::{Arc, Mutex};
+
+    let mut admission = AdmissionControl::new(
+        Arc::new(Mutex::new(
+            AdmissionPolicy::new()
+                .with_max_connections_per_ip(1)
+                .with_max_concurrent_connections(2),
+        )),
+        Arc::new(Mutex::new(ListenerStats::default())),
+    );
+    let source: IpAddr = "127.0.0.1".parse().unwrap();
+    let other: IpAddr = "127.0.0.2".parse().unwrap();
+
+    // A connection still in its handshake holds its source's place
+    let handshake = admission.check(source).unwrap();
+    assert!(admission.check(source).is_err());
+    let _other = admission.check(other).unwrap();
+    assert_eq!(admission.tracked(), 2);
+
+    // and the global one, until the handshake fails
+    assert!(admission.check("127.0.0.3".parse().unwrap()).is_err());
+    drop(handshake);
+    assert!(admission.check(source).is_ok());
+}
+
+#[tokio::test]
+async fn test_listener_counts_connections_in_their_handshake() {
+    use crate::ProtocolSniffer;
+
+    let listener = create_tcp_listener().await;
+    let addr = listener.local_addr().await.unwrap();
+    listener.set_admission_policy(crate::AdmissionPolicy::new().with_max_connections_per_ip(1));
+    listener.set_protocol_sniffer(Some(
+        ProtocolSniffer::new(4).with_timeout(Duration::from_secs(5)),
+    ));
+
+    // The first client sends nothing, so it stays in sniffing
+    let _first = tokio::net::TcpStream::connect(addr).await.unwrap();
+    sleep(Duration::from_millis(100)).await;
+    let (_second, admitted) = connect_and_probe(addr).await;
+    assert!(!admitted);
+    assert_eq!(listener.stats().rejected_per_ip_limit, 1);
+
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_listener_reports_refused_attempts() {
+    let listener = create_tcp_listener().await;
+    let addr = listener.local_addr().await.unwrap();
+    listener.set_new_connection_limit(0);
+
+    let (client, admitted) = connect_and_probe(addr).await;
+    assert!(!admitted);
+
+    match timeout(Duration::from_secs(2), listener.next_event()).await {
+        Ok(Some(ListenerEvent::EstablishmentError {
+            remote,
+            class,
+            reason,
+        })) => {
+            assert_eq!(remote, Some(client.local_addr().unwrap()));
+            assert_eq!(class, crate::AcceptErrorClass::Filtered);
+            assert!(reason.contains("limit"));
+        }
+        other => panic!("Expected EstablishmentError, got {other:?}"),
+    }
+    assert_eq!(listener.stats().rejected_connection_limit, 1);
+
+    listener.stop().await.unwrap();
+}
+
+#[test]
+fn test_accept_error_classification() {
+    use crate::AcceptErrorClass;
+
+    #[cfg(unix)]
+    for code in [libc::EMFILE, libc::ENFILE] {
+        assert_eq!(
+            AcceptErrorClass::from_io_error(&std::io::Error::from_raw_os_error(code)),
+            AcceptErrorClass::ResourceExhausted
+        );
+    }
+    assert_eq!(
+        AcceptErrorClass::from_io_error(&std::io::Error::from(
+            std::io::ErrorKind::ConnectionAborted
+        )),
+        AcceptErrorClass::Io
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_datagram_listener_receive_timestamps() {
+    let preconn = Preconnection::new(
+        vec![LocalEndpoint {
+            identifiers: vec![
+                EndpointIdentifier::IpAddress("127.0.0.1".parse().unwrap()),
+                EndpointIdentifier::Port(0),
+            ],
+        }],
+        vec![],
+        TransportProperties::builder()
+            .reliability(crate::Preference::Prohibit)
+            .receive_timestamps(true)
+            .build(),
+        SecurityParameters::new_disabled(),
+    );
+    let listener = preconn.listen().await.unwrap();
+    let addr = listener.local_addr().await.unwrap();
+
+    let peer = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    peer.send_to(b"stamped", addr).await.unwrap();
+    let conn = timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+
+    // The source address still comes through alongside the timestamp
+    let (data, context) = next_datagram(&conn).await;
+    assert_eq!(data, b"stamped");
+    assert_eq!(
+        context.remote_endpoint.unwrap().identifiers,
+        vec![EndpointIdentifier::SocketAddress(
+            peer.local_addr().unwrap()
+        )]
+    );
+    let timestamp = context.interface_timestamp.expect("Should be timestamped");
+    assert!(timestamp <= context.received_at);
+
+    listener.stop().await.unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_datagram_traffic_class_per_message() {
+    use crate::connection::{enable_receive_traffic_class, recv_timestamped};
+    use crate::{Message, MessageCapacityProfile};
+
+    let listener = create_datagram_listener().await;
+    let addr = listener.local_addr().await.unwrap();
+
+    // The peer marks its datagrams AF41 and reads back the marking of replies
+    let peer = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let peer_ref = socket2::SockRef::from(&peer);
+    peer_ref.set_tos(34 << 2).unwrap();
+    enable_receive_traffic_class(&peer_ref);
+    peer.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    peer.send_to(b"marked", addr).unwrap();
+
+    let conn = timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+    let (data, context) = next_datagram(&conn).await;
+    assert_eq!(data, b"marked");
+    assert_eq!(context.traffic_class, Some(34 << 2));
+    assert_eq!(context.dscp(), Some(34));
+    assert_eq!(context.ecn, Some(crate::message::EcnMarking::NotEct));
+
+    // Each reply carries the traffic class of its own capacity profile
+    conn.send(
+        Message::from_bytes(b"bulk").with_capacity_profile(MessageCapacityProfile::Scavenger),
+    )
+    .await
+    .unwrap();
+    conn.send(Message::from_bytes(b"plain")).await.unwrap();
+
+    let received = tokio::task::spawn_blocking(move || {
+        let mut buffer = [0u8; 64];
+        (0..2)
+            .map(|_| {
+                let received =
+                    recv_timestamped(&socket2::SockRef::from(&peer), &mut buffer).unwrap();
+                (buffer[..received.length].to_vec(), received.traffic_class)
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap();
+    assert_eq!(received[0], (b"bulk".to_vec(), Some(1 << 2)));
+    assert_eq!(received[1], (b"plain".to_vec(), Some(0)));
+
+    listener.stop().await.unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_datagram_packet_info_on_wildcard_listener() {
+    use crate::Message;
+
+    let preconn = Preconnection::new(
+        vec![LocalEndpoint {
+            identifiers: vec![
+                EndpointIdentifier::IpAddress("0.0.0.0".parse().unwrap()),
+                EndpointIdentifier::Port(0),
+            ],
+        }],
+        vec![],
+        TransportProperties::builder()
+            .reliability(crate::Preference::Prohibit)
+            .build(),
+        SecurityParameters::new_disabled(),
+    );
+    let listener = preconn.listen().await.unwrap();
+    let port = listener.local_addr().await.unwrap().port();
+
+    // 127.0.0.2 is not the source the kernel would pick for replies to the
+    // peer at 127.0.0.1
+    let local = SocketAddr::new("127.0.0.2".parse().unwrap(), port);
+    let peer = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    peer.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    peer.send_to(b"hello", local).unwrap();
+
+    let conn = timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+    let (data, context) = next_datagram(&conn).await;
+    assert_eq!(data, b"hello");
+    let identifiers = context.local_endpoint.unwrap().identifiers;
+    assert!(identifiers.contains(&EndpointIdentifier::SocketAddress(local)));
+    assert!(identifiers.contains(&EndpointIdentifier::Interface("lo".to_string())));
+
+    // The reply comes from the address the peer sent to
+    conn.send(Message::from_bytes(b"reply")).await.unwrap();
+    let (n, source) = tokio::task::spawn_blocking(move || {
+        let mut buffer = [0u8; 64];
+        peer.recv_from(&mut buffer).unwrap()
+    })
+    .await
+    .unwrap();
+    assert_eq!(n, 5);
+    assert_eq!(source, local);
+
+    listener.stop().await.unwrap();
+}
+
+/// Receive a datagram along with the TTL and traffic class it arrived with
+#[cfg(target_os = "linux")]
+fn recv_marked(socket: &std::net::UdpSocket) -> (Vec<u8>, Option<u8>, Option<u8>) {
+    use std::os::unix::io::AsRawFd;
+
+    let mut buffer = [0u8; 64];
+    let mut control = [0u64; 8];
+    let mut iov = libc::iovec {
+        iov_base: buffer.as_mut_ptr().cast(),
+        iov_len: buffer.len(),
+    };
+    let mut ttl = None;
+    let mut tos = None;
+    let n = unsafe {
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr().cast();
+        msg.msg_controllen = std::mem::size_of_val(&control) as _;
+        let n = libc::recvmsg(socket.as_raw_fd(), &mut msg, 0);
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::IPPROTO_IP && (*cmsg).cmsg_type == libc::IP_TTL {
+                let value: libc::c_int =
+                    std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::c_int);
+                ttl = Some(value as u8);
+            }
+            if (*cmsg).cmsg_level == libc::IPPROTO_IP && (*cmsg).cmsg_type == libc::IP_TOS {
+                tos = Some(*libc::CMSG_DATA(cmsg));
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+        n
+    };
+    assert!(n >= 0, "{}", std::io::Error::last_os_error());
+    (buffer[..n as usize].to_vec(), ttl, tos)
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_datagram_hop_limit() {
+    use crate::connection::enable_receive_traffic_class;
+    use crate::{ConnectionProperty, Message, MessageCapacityProfile};
+    use std::os::unix::io::AsRawFd;
+
+    let listener = create_datagram_listener().await;
+    let addr = listener.local_addr().await.unwrap();
+
+    // The peer reads back the TTL and traffic class of replies
+    let peer = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let enable: libc::c_int = 1;
+    let result = unsafe {
+        libc::setsockopt(
+            peer.as_raw_fd(),
+            libc::IPPROTO_IP,
+            libc::IP_RECVTTL,
+            &enable as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    assert_eq!(result, 0);
+    enable_receive_traffic_class(&socket2::SockRef::from(&peer));
+    peer.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    peer.send_to(b"hello", addr).unwrap();
+
+    let conn = timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+    next_datagram(&conn).await;
+
+    // Messages override the connection's hop limit, which the rest take
+    conn.set_property("ipHopLimit", ConnectionProperty::IpHopLimit(Some(9)))
+        .await
+        .unwrap();
+    conn.send(Message::from_bytes(b"probe").with_hop_limit(2))
+        .await
+        .unwrap();
+    conn.send(Message::from_bytes(b"plain")).await.unwrap();
+    // A hop limit goes along with a traffic class
+    conn.send(
+        Message::from_bytes(b"both")
+            .with_capacity_profile(MessageCapacityProfile::Scavenger)
+            .with_hop_limit(4),
+    )
+    .await
+    .unwrap();
+
+    let received =
+        tokio::task::spawn_blocking(move || (0..3).map(|_| recv_marked(&peer)).collect::<Vec<_>>())
+            .await
+            .unwrap();
+    assert_eq!(received[0], (b"probe".to_vec(), Some(2), Some(0)));
+    assert_eq!(received[1], (b"plain".to_vec(), Some(9), Some(0)));
+    assert_eq!(received[2], (b"both".to_vec(), Some(4), Some(1 << 2)));
+
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_listener_binds_every_local_endpoint() {
+    let endpoint = |ip: &str| LocalEndpoint {
+        identifiers: vec![
+            EndpointIdentifier::IpAddress(ip.parse().unwrap()),
+            EndpointIdentifier::Port(0),
+        ],
+    };
+    let preconn = Preconnection::new(
+        vec![endpoint("127.0.0.1"), endpoint("127.0.0.1")],
+        vec![],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let listener = preconn.listen().await.unwrap();
+
+    // Each endpoint gets its own kernel-assigned port
+    let addrs = listener.local_addrs().await;
+    assert_eq!(addrs.len(), 2);
+    assert!(addrs.iter().all(|addr| addr.port() > 0));
+    assert_ne!(addrs[0].port(), addrs[1].port());
+    assert_eq!(listener.local_addr().await, Some(addrs[0]));
+
+    // Connections are accepted on every bound address
+    for addr in &addrs {
+        let _client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let conn = timeout(Duration::from_secs(2), listener.accept())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            conn.local_endpoint().await.unwrap().identifiers,
+            vec![EndpointIdentifier::SocketAddress(*addr)]
+        );
+    }
+
+    // Specific addresses are advertised as bound
+    let advertised = listener.advertised_endpoints().await;
+    assert_eq!(
+        advertised
+            .iter()
+            .map(|remote| remote.identifiers.clone())
+            .collect::<Vec<_>>(),
+        addrs
+            .iter()
+            .map(|addr| vec![EndpointIdentifier::SocketAddress(*addr)])
+            .collect::<Vec<_>>()
+    );
+
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_listener_advertises_wildcard_as_interface_addresses() {
+    let preconn = Preconnection::new(
+        vec![LocalEndpoint {
+            identifiers: vec![
+                EndpointIdentifier::IpAddress("0.0.0.0".parse().unwrap()),
+                EndpointIdentifier::Port(0),
+            ],
+        }],
+        vec![],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let listener = preconn.listen().await.unwrap();
+    let port = listener.local_addr().await.unwrap().port();
+
+    let advertised = listener.advertised_endpoints().await;
+    assert!(!advertised.is_empty());
+    for remote in &advertised {
+        match remote.identifiers.as_slice() {
+            [EndpointIdentifier::SocketAddress(addr)] => {
+                assert!(addr.is_ipv4());
+                assert!(!addr.ip().is_unspecified());
+                assert_eq!(addr.port(), port);
+            }
+            other => panic!("Expected a socket address, got {other:?}"),
+        }
+    }
+
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_interface_listener_follows_address_changes() {
+    let preconn = Preconnection::new(
+        vec![LocalEndpoint {
+            identifiers: vec![
+                EndpointIdentifier::Interface("tapsrs-test0".to_string()),
+                EndpointIdentifier::Port(0),
+            ],
+        }],
+        vec![],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+
+    // The interface does not exist yet, so nothing is bound
+    let listener = preconn.listen().await.unwrap();
+    assert!(listener.local_addrs().await.is_empty());
+
+    let interface = |ips: Vec<IpAddr>| Interface {
+        name: "tapsrs-test0".to_string(),
+        index: 0,
+        ips,
+        status: Status::Up,
+        interface_type: "unknown".to_string(),
+        is_expensive: false,
+    };
+    let ip: IpAddr = "127.0.0.2".parse().unwrap();
+    listener
+        .handle_interface_change(&ChangeEvent::Added(interface(vec![ip])))
+        .await;
+
+    let addr = match timeout(Duration::from_secs(1), listener.next_event()).await {
+        Ok(Some(ListenerEvent::AddressAdded(addr))) => addr,
+        other => panic!("Expected AddressAdded, got {other:?}"),
+    };
+    assert_eq!(addr.ip(), ip);
+    assert_ne!(addr.port(), 0);
+    assert_eq!(listener.local_addrs().await, vec![addr]);
+
+    // Connections are accepted on the new address
+    let _client = tokio::net::TcpStream::connect(addr).await.unwrap();
+    timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Changes to other interfaces are ignored
+    let mut other = interface(vec!["127.0.0.3".parse().unwrap()]);
+    other.name = "tapsrs-test1".to_string();
+    listener
+        .handle_interface_change(&ChangeEvent::Added(other))
+        .await;
+    assert_eq!(listener.local_addrs().await, vec![addr]);
+
+    listener
+        .handle_interface_change(&ChangeEvent::Modified {
+            old: interface(vec![ip]),
+            new: interface(vec![]),
+        })
+        .await;
+    match timeout(Duration::from_secs(1), listener.next_event()).await {
+        Ok(Some(ListenerEvent::AddressRemoved(removed))) => assert_eq!(removed, addr),
+        other => panic!("Expected AddressRemoved, got {other:?}"),
+    }
+    assert!(listener.local_addrs().await.is_empty());
+
+    // The socket is closed once the accept loop drops it
+    sleep(Duration::from_millis(50)).await;
+    assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_datagram_nat_keepalive_when_idle() {
+    let preconn = Preconnection::new(
+        vec![LocalEndpoint {
+            identifiers: vec![
+                EndpointIdentifier::IpAddress("127.0.0.1".parse().unwrap()),
+                EndpointIdentifier::Port(0),
+            ],
+        }],
+        vec![],
+        TransportProperties::builder()
+            .reliability(crate::Preference::Prohibit)
+            .nat_keepalive_interval(Duration::from_millis(100))
+            .build(),
+        SecurityParameters::new_disabled(),
+    );
+    let listener = preconn.listen().await.unwrap();
+    let addr = listener.local_addr().await.unwrap();
+
+    let peer = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    peer.send_to(b"hello", addr).await.unwrap();
+    let conn = timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+    next_datagram(&conn).await;
+
+    // Traffic from the peer suppresses keepalives
+    let mut buf = [0u8; 16];
+    for _ in 0..6 {
+        peer.send_to(b"tick", addr).await.unwrap();
+        assert!(timeout(Duration::from_millis(40), peer.recv_from(&mut buf))
+            .await
+            .is_err());
+    }
+
+    // Once idle, an empty datagram keeps the binding alive
+    let (n, from) = timeout(Duration::from_secs(1), peer.recv_from(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!((n, from), (0, addr));
+
+    // Keepalives from the peer are delivered as empty messages
+    peer.send_to(b"", addr).await.unwrap();
+    peer.send_to(b"after", addr).await.unwrap();
+    let mut data = next_datagram(&conn).await.0;
+    while data == b"tick" {
+        data = next_datagram(&conn).await.0;
+    }
+    assert!(data.is_empty());
+    assert_eq!(next_datagram(&conn).await.0, b"after");
+
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_datagram_empty_messages_are_zero_length_datagrams() {
+    let listener = create_datagram_listener().await;
+    let addr = listener.local_addr().await.unwrap();
+
+    let peer = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    peer.send_to(b"", addr).await.unwrap();
+    let conn = timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+
+    // The datagram that opened the flow is an empty message
+    let (data, _) = next_datagram(&conn).await;
+    assert!(data.is_empty());
+
+    // An empty message is sent as a zero-length datagram
+    conn.send(crate::Message::new(Vec::new())).await.unwrap();
+    let mut buf = [0u8; 16];
+    let (n, from) = timeout(Duration::from_secs(2), peer.recv_from(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!((n, from), (0, addr));
+    loop {
+        match timeout(Duration::from_secs(2), conn.next_event()).await {
+            Ok(Some(crate::ConnectionEvent::Sent { .. })) => break,
+            Ok(Some(_)) => continue,
+            other => panic!("Expected Sent event, got {other:?}"),
+        }
+    }
+
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_datagram_rebinding_emits_path_change() {
+    let listener = create_datagram_listener().await;
+    listener.set_peer_demultiplexing(false);
+    let addr = listener.local_addr().await.unwrap();
+
+    let before = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    before.send_to(b"first", addr).await.unwrap();
+    let conn = timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+    next_datagram(&conn).await;
+
+    // The peer reappears from a new port, as after a NAT rebinding
+    let after = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    after.send_to(b"second", addr).await.unwrap();
+    match timeout(Duration::from_secs(2), conn.next_event()).await {
+        Ok(Some(crate::ConnectionEvent::PathChange)) => {}
+        other => panic!("Expected PathChange, got {other:?}"),
+    }
+    let (data, _) = next_datagram(&conn).await;
+    assert_eq!(data, b"second");
+    assert_eq!(
+        conn.path_info().await.remote_address,
+        Some(after.local_addr().unwrap())
+    );
+
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_listener_sniffs_protocol() {
+    use crate::{ConnectionEvent, FramerStack, LengthPrefixFramer, ProtocolSniffer};
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpStream;
+
+    let preconn = Preconnection::new(
+        vec![LocalEndpoint::builder()
+            .ip_address("127.0.0.1".parse().unwrap())
+            .port(0)
+            .build()],
+        vec![],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let listener = preconn.listen().await.unwrap();
+    let addr = listener.local_addr().await.unwrap();
+
+    // Text requests are left unframed; anything else is length-prefixed
+    listener.set_protocol_sniffer(Some(
+        ProtocolSniffer::new(4)
+            .with_timeout(Duration::from_millis(200))
+            .with_framer_selector(|sniffed| {
+                let mut framers = FramerStack::new();
+                if !sniffed.data.starts_with(b"GET ") {
+                    framers.add_framer(Box::new(LengthPrefixFramer::new()));
+                }
+                framers
+            }),
+    ));
+
+    let received = |conn: crate::Connection| async move {
+        timeout(Duration::from_secs(2), async {
+            loop {
+                match conn.next_event().await {
+                    Some(ConnectionEvent::Received { message_data, .. }) => break message_data,
+                    Some(_) => continue,
+                    None => panic!("Event stream ended"),
+                }
+            }
+        })
+        .await
+        .expect("Should receive data")
+    };
+
+    // The peeked bytes are still delivered
+    let mut text = TcpStream::connect(addr).await.unwrap();
+    text.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+    let conn = timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+    let sniffed = conn
+        .sniffed()
+        .await
+        .expect("Should have sniffed the stream");
+    assert_eq!(sniffed.data, b"GET ");
+    assert!(sniffed.client_hello.is_none());
+    assert_eq!(received(conn).await, b"GET / HTTP/1.1\r\n");
+
+    // The first bytes may arrive in pieces
+    let mut binary = TcpStream::connect(addr).await.unwrap();
+    binary.write_all(&[0, 0]).await.unwrap();
+    sleep(Duration::from_millis(30)).await;
+    binary.write_all(&[0, 5]).await.unwrap();
+    binary.write_all(b"hello").await.unwrap();
+    let conn = timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(conn.sniffed().await.unwrap().data, [0, 0, 0, 5]);
+    assert_eq!(received(conn).await, b"hello");
+
+    // A silent peer is delivered once the wait is over
+    let _silent = TcpStream::connect(addr).await.unwrap();
+    let conn = timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(conn.sniffed().await.unwrap().data, b"");
+
+    listener.stop().await.unwrap();
+}
+
+fn loopback_preconnection() -> Preconnection {
+    Preconnection::new(
+        vec![LocalEndpoint {
+            identifiers: vec![
+                EndpointIdentifier::IpAddress("127.0.0.1".parse().unwrap()),
+                EndpointIdentifier::Port(0),
+            ],
+        }],
+        vec![],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    )
+}
+
+#[tokio::test]
+async fn test_incoming_stream_ends_when_stopped() {
+    use futures::StreamExt;
+
+    let listener = loopback_preconnection().listen().await.unwrap();
+    let addr = listener.local_addr().await.unwrap();
+    let mut incoming = Box::pin(listener.incoming());
+
+    let _clients = [
+        tokio::net::TcpStream::connect(addr).await.unwrap(),
+        tokio::net::TcpStream::connect(addr).await.unwrap(),
+    ];
+    for _ in 0..2 {
+        let connection = timeout(Duration::from_secs(2), incoming.next())
+            .await
+            .expect("Expected a connection")
+            .expect("Stream ended early");
+        assert!(connection.is_ok());
+    }
+
+    listener.stop().await.unwrap();
+    let end = timeout(Duration::from_secs(2), incoming.next()).await;
+    assert!(matches!(end, Ok(None)));
+}
+
+#[tokio::test]
+async fn test_serve_with_limit_bounds_running_handlers() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::AsyncReadExt;
+
+    let listener = loopback_preconnection().listen().await.unwrap();
+    let addr = listener.local_addr().await.unwrap();
+
+    let running = Arc::new(AtomicUsize::new(0));
+    let most_running = Arc::new(AtomicUsize::new(0));
+    let server = {
+        let listener = listener.clone();
+        let (running, most_running) = (Arc::clone(&running), Arc::clone(&most_running));
+        tokio::spawn(async move {
+            listener
+                .serve_with_limit(2, move |connection| {
+                    let (running, most_running) = (Arc::clone(&running), Arc::clone(&most_running));
+                    async move {
+                        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                        most_running.fetch_max(now, Ordering::SeqCst);
+                        sleep(Duration::from_millis(100)).await;
+                        running.fetch_sub(1, Ordering::SeqCst);
+                        connection.close().await.unwrap();
+                    }
+                })
+                .await
+        })
+    };
+
+    // Every client is served, two at a time
+    let mut clients = Vec::new();
+    for _ in 0..5 {
+        clients.push(tokio::net::TcpStream::connect(addr).await.unwrap());
+    }
+    for mut client in clients {
+        let mut buf = [0u8; 1];
+        let read = timeout(Duration::from_secs(5), client.read(&mut buf)).await;
+        assert!(matches!(read, Ok(Ok(0))), "Client not served: {read:?}");
+    }
+    assert_eq!(most_running.load(Ordering::SeqCst), 2);
+
+    listener.stop().await.unwrap();
+    timeout(Duration::from_secs(2), server)
+        .await
+        .expect("serve should return once the listener stops")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_serve_spawns_a_handler_per_connection() {
+    use crate::Message;
+    use tokio::io::AsyncReadExt;
+
+    let listener = loopback_preconnection().listen().await.unwrap();
+    let addr = listener.local_addr().await.unwrap();
+    let server = {
+        let listener = listener.clone();
+        tokio::spawn(async move {
+            listener
+                .serve(|connection| async move {
+                    connection.send(Message::from_string("hi")).await.unwrap();
+                    // Handlers run side by side
+                    sleep(Duration::from_millis(200)).await;
+                })
+                .await
+        })
+    };
+
+    let mut clients = Vec::new();
+    for _ in 0..3 {
+        clients.push(tokio::net::TcpStream::connect(addr).await.unwrap());
+    }
+    let started = tokio::time::Instant::now();
+    for mut client in clients {
+        let mut buf = [0u8; 2];
+        timeout(Duration::from_secs(2), client.read_exact(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&buf, b"hi");
+    }
+    assert!(started.elapsed() < Duration::from_millis(200));
+
+    listener.stop().await.unwrap();
+    timeout(Duration::from_secs(2), server)
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_listen_with_limit_sets_concurrency_limit() {
+    let listener = loopback_preconnection().listen_with_limit(3).await.unwrap();
+    assert_eq!(
+        listener.admission_policy().max_concurrent_connections,
+        Some(3)
+    );
+    listener.stop().await.unwrap();
+}
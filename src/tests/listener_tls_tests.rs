@@ -0,0 +1,762 @@
+//! Tests for TLS termination on Listeners, with certificates chosen by SNI
+
+use crate::*;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::Duration;
+use tokio_rustls::rustls::pki_types::{
+    CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName,
+};
+use tokio_rustls::rustls::{self, ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+// Test PKI: every certificate is issued by ca.der
+const CA: &[u8] = include_bytes!("certs/ca.der");
+// localhost and 127.0.0.1
+const DEFAULT_CERT: &[u8] = include_bytes!("certs/server.der");
+const DEFAULT_KEY: &[u8] = include_bytes!("certs/server.key.der");
+// alpha.test
+const ALPHA_CERT: &[u8] = include_bytes!("certs/alpha.der");
+const ALPHA_KEY: &[u8] = include_bytes!("certs/alpha.key.der");
+// *.beta.test
+const BETA_CERT: &[u8] = include_bytes!("certs/beta.der");
+const BETA_KEY: &[u8] = include_bytes!("certs/beta.key.der");
+// localhost and 127.0.0.1, issued later to replace DEFAULT_CERT
+const ROTATED_CERT: &[u8] = include_bytes!("certs/rotated.der");
+const ROTATED_KEY: &[u8] = include_bytes!("certs/rotated.key.der");
+// Client certificates; the rogue one is issued by untrusted_ca.der
+const CLIENT_CERT: &[u8] = include_bytes!("certs/client.der");
+const CLIENT_KEY: &[u8] = include_bytes!("certs/client.key.der");
+const ROGUE_CLIENT_CERT: &[u8] = include_bytes!("certs/rogue_client.der");
+const ROGUE_CLIENT_KEY: &[u8] = include_bytes!("certs/rogue_client.key.der");
+
+fn identity(names: &[&str], certificate: &[u8], key: &[u8]) -> ServerIdentity {
+    ServerIdentity::new(
+        names.iter().map(|name| name.to_string()).collect(),
+        CertificateChain {
+            certificates: vec![Certificate {
+                data: certificate.to_vec(),
+            }],
+        },
+        key.to_vec(),
+    )
+}
+
+fn multi_tenant_parameters() -> SecurityParameters {
+    let mut parameters = SecurityParameters::new();
+    parameters
+        .add_server_identity(identity(&["localhost"], DEFAULT_CERT, DEFAULT_KEY))
+        .add_server_identity(identity(&["alpha.test"], ALPHA_CERT, ALPHA_KEY))
+        .add_server_identity(identity(&["*.beta.test"], BETA_CERT, BETA_KEY));
+    parameters.alpn = vec!["h2".to_string(), "http/1.1".to_string()];
+    parameters
+}
+
+async fn tls_listener(parameters: SecurityParameters) -> (Listener, std::net::SocketAddr) {
+    let preconn = Preconnection::new(
+        vec![LocalEndpoint {
+            identifiers: vec![
+                EndpointIdentifier::IpAddress("127.0.0.1".parse().unwrap()),
+                EndpointIdentifier::Port(0),
+            ],
+        }],
+        vec![],
+        TransportProperties::default(),
+        parameters,
+    );
+    let listener = preconn.listen().await.unwrap();
+    let addr = listener.local_addr().await.unwrap();
+    (listener, addr)
+}
+
+/// Connect with a plain rustls client, asking for `server_name`
+async fn connect(
+    addr: std::net::SocketAddr,
+    server_name: &str,
+    alpn: &[&str],
+) -> std::io::Result<tokio_rustls::client::TlsStream<TcpStream>> {
+    connect_with(addr, server_name, alpn, None).await
+}
+
+/// Connect presenting `client_identity`, a certificate and PKCS#8 key
+async fn connect_with(
+    addr: std::net::SocketAddr,
+    server_name: &str,
+    alpn: &[&str],
+    client_identity: Option<(&[u8], &[u8])>,
+) -> std::io::Result<tokio_rustls::client::TlsStream<TcpStream>> {
+    let mut roots = RootCertStore::empty();
+    roots.add(CertificateDer::from(CA.to_vec())).unwrap();
+    let builder = ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::aws_lc_rs::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_root_certificates(roots);
+    let mut config = match client_identity {
+        Some((certificate, key)) => builder
+            .with_client_auth_cert(
+                vec![CertificateDer::from(certificate.to_vec())],
+                PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.to_vec())),
+            )
+            .unwrap(),
+        None => builder.with_no_client_auth(),
+    };
+    config.alpn_protocols = alpn.iter().map(|p| p.as_bytes().to_vec()).collect();
+
+    let stream = TcpStream::connect(addr).await?;
+    TlsConnector::from(Arc::new(config))
+        .connect(
+            ServerName::try_from(server_name.to_string()).unwrap(),
+            stream,
+        )
+        .await
+}
+
+fn presented_certificate(stream: &tokio_rustls::client::TlsStream<TcpStream>) -> Vec<u8> {
+    stream.get_ref().1.peer_certificates().unwrap()[0].to_vec()
+}
+
+async fn accept(listener: &Listener) -> Connection {
+    tokio::time::timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .expect("Should accept a connection")
+        .unwrap()
+}
+
+async fn received(conn: &Connection) -> Vec<u8> {
+    tokio::time::timeout(Duration::from_secs(2), async {
+        loop {
+            match conn.next_event().await {
+                Some(ConnectionEvent::Received { message_data, .. }) => break message_data,
+                Some(_) => continue,
+                None => panic!("Event stream ended"),
+            }
+        }
+    })
+    .await
+    .expect("Should receive data")
+}
+
+#[tokio::test]
+async fn test_listener_selects_certificate_by_sni() {
+    let (listener, addr) = tls_listener(multi_tenant_parameters()).await;
+
+    let mut client = connect(addr, "alpha.test", &["h2"]).await.unwrap();
+    assert_eq!(presented_certificate(&client), ALPHA_CERT);
+    client.write_all(b"hello alpha").await.unwrap();
+
+    let conn = accept(&listener).await;
+    assert_eq!(received(&conn).await, b"hello alpha");
+    match conn.get_property("securityServerName").await {
+        Some(ConnectionProperty::SecurityServerName(Some(name))) => assert_eq!(name, "alpha.test"),
+        other => panic!("Expected the requested server name, got {other:?}"),
+    }
+    match conn.get_property("securityAlpn").await {
+        Some(ConnectionProperty::SecurityAlpn(Some(alpn))) => assert_eq!(alpn, "h2"),
+        other => panic!("Expected the negotiated ALPN, got {other:?}"),
+    }
+
+    // Replies are encrypted for the client
+    conn.send(Message::from_bytes(b"hello client"))
+        .await
+        .unwrap();
+    let mut reply = [0u8; 12];
+    client.read_exact(&mut reply).await.unwrap();
+    assert_eq!(&reply, b"hello client");
+
+    // A wildcard identity serves any single label below its domain
+    let client = connect(addr, "tenant.beta.test", &["http/1.1"])
+        .await
+        .unwrap();
+    assert_eq!(presented_certificate(&client), BETA_CERT);
+    let conn = accept(&listener).await;
+    match conn.get_property("securityAlpn").await {
+        Some(ConnectionProperty::SecurityAlpn(Some(alpn))) => assert_eq!(alpn, "http/1.1"),
+        other => panic!("Expected the negotiated ALPN, got {other:?}"),
+    }
+
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_listener_presents_first_identity_without_sni_match() {
+    let (listener, addr) = tls_listener(multi_tenant_parameters()).await;
+
+    // Clients connecting by address send no SNI
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let client = preconn.initiate().await.unwrap();
+    client
+        .wait_for_established(Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+    let mut parameters = SecurityParameters::new();
+    parameters.pinned_server_certificate = vec![CertificateChain {
+        certificates: vec![Certificate { data: CA.to_vec() }],
+    }];
+    client.start_security(parameters).await.unwrap();
+
+    let conn = accept(&listener).await;
+    match conn.get_property("securityServerName").await {
+        Some(ConnectionProperty::SecurityServerName(None)) => {}
+        other => panic!("Expected no server name, got {other:?}"),
+    }
+    match conn.get_property("securityAlpn").await {
+        Some(ConnectionProperty::SecurityAlpn(None)) => {}
+        other => panic!("Expected no ALPN, got {other:?}"),
+    }
+
+    client.send(Message::from_bytes(b"ping")).await.unwrap();
+    assert_eq!(received(&conn).await, b"ping");
+    conn.send(Message::from_bytes(b"pong")).await.unwrap();
+    assert_eq!(received(&client).await, b"pong");
+
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_listener_certificate_selection_callback() {
+    let mut parameters = multi_tenant_parameters();
+    parameters.set_certificate_selection_callback(|server_name| match server_name {
+        Some("blocked.test") => None,
+        // Every other tenant is served by alpha's certificate
+        _ => Some(1),
+    });
+    let (listener, addr) = tls_listener(parameters).await;
+
+    // The callback overrides SNI matching
+    let client = connect(addr, "localhost", &[]).await;
+    assert!(client.is_err(), "localhost is not on alpha's certificate");
+    let client = connect(addr, "alpha.test", &[]).await.unwrap();
+    assert_eq!(presented_certificate(&client), ALPHA_CERT);
+    accept(&listener).await;
+
+    // A refused handshake is reported, and no connection is delivered
+    assert!(connect(addr, "blocked.test", &[]).await.is_err());
+    let reason = tokio::time::timeout(Duration::from_secs(2), async {
+        loop {
+            match listener.next_event().await {
+                Some(ListenerEvent::EstablishmentError {
+                    remote,
+                    class: AcceptErrorClass::HandshakeFailed,
+                    reason,
+                }) => {
+                    assert_eq!(remote.map(|r| r.ip()), Some(addr.ip()));
+                    break reason;
+                }
+                Some(_) => continue,
+                None => panic!("Listener event stream ended"),
+            }
+        }
+    })
+    .await
+    .expect("Should report the failed handshake");
+    assert!(!reason.is_empty());
+
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_listener_rejects_invalid_server_identity() {
+    let mut parameters = SecurityParameters::new();
+    parameters.add_server_identity(identity(&["alpha.test"], ALPHA_CERT, b"not a key"));
+    let preconn = Preconnection::new(
+        vec![LocalEndpoint {
+            identifiers: vec![EndpointIdentifier::Port(0)],
+        }],
+        vec![],
+        TransportProperties::default(),
+        parameters,
+    );
+    assert!(matches!(
+        preconn.listen().await,
+        Err(TransportServicesError::InvalidParameters(_))
+    ));
+}
+
+fn mtls_parameters(policy: ClientCertificatePolicy) -> SecurityParameters {
+    let mut parameters = multi_tenant_parameters();
+    parameters.set_client_certificate_policy(policy);
+    parameters.pinned_client_certificate = vec![CertificateChain {
+        certificates: vec![Certificate { data: CA.to_vec() }],
+    }];
+    parameters
+}
+
+async fn next_handshake_failure(listener: &Listener) -> String {
+    tokio::time::timeout(Duration::from_secs(2), async {
+        loop {
+            match listener.next_event().await {
+                Some(ListenerEvent::EstablishmentError {
+                    class: AcceptErrorClass::HandshakeFailed,
+                    reason,
+                    ..
+                }) => break reason,
+                Some(ListenerEvent::ConnectionReceived(_)) => {
+                    panic!("Unauthenticated client should not be delivered")
+                }
+                Some(_) => continue,
+                None => panic!("Listener event stream ended"),
+            }
+        }
+    })
+    .await
+    .expect("Should report the failed handshake")
+}
+
+/// Wait for the client to see the server end the session
+async fn assert_refused(mut client: tokio_rustls::client::TlsStream<TcpStream>) {
+    let mut buf = [0u8; 16];
+    let result = tokio::time::timeout(Duration::from_secs(2), client.read(&mut buf))
+        .await
+        .expect("Server should end the session");
+    assert!(matches!(result, Err(_) | Ok(0)), "{result:?}");
+}
+
+#[tokio::test]
+async fn test_listener_requires_client_certificate() {
+    let (listener, addr) = tls_listener(mtls_parameters(ClientCertificatePolicy::Require)).await;
+
+    let mut client = connect_with(addr, "localhost", &[], Some((CLIENT_CERT, CLIENT_KEY)))
+        .await
+        .unwrap();
+    client.write_all(b"authenticated").await.unwrap();
+    let conn = accept(&listener).await;
+    assert_eq!(received(&conn).await, b"authenticated");
+    match conn.get_property("securityClientCertificate").await {
+        Some(ConnectionProperty::SecurityClientCertificate(Some(chain))) => {
+            assert_eq!(chain.certificates[0].data, CLIENT_CERT)
+        }
+        other => panic!("Expected the client's certificate, got {other:?}"),
+    }
+
+    // Clients without a certificate, or with one from another CA, are refused
+    // (in TLS 1.3 the client finishes its handshake before the server decides)
+    if let Ok(client) = connect(addr, "localhost", &[]).await {
+        assert_refused(client).await;
+    }
+    next_handshake_failure(&listener).await;
+
+    if let Ok(client) = connect_with(
+        addr,
+        "localhost",
+        &[],
+        Some((ROGUE_CLIENT_CERT, ROGUE_CLIENT_KEY)),
+    )
+    .await
+    {
+        assert_refused(client).await;
+    }
+    next_handshake_failure(&listener).await;
+
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_listener_requests_client_certificate() {
+    let (listener, addr) = tls_listener(mtls_parameters(ClientCertificatePolicy::Request)).await;
+
+    // A client may connect without a certificate
+    let _anonymous = connect(addr, "localhost", &[]).await.unwrap();
+    let conn = accept(&listener).await;
+    match conn.get_property("securityClientCertificate").await {
+        Some(ConnectionProperty::SecurityClientCertificate(None)) => {}
+        other => panic!("Expected no client certificate, got {other:?}"),
+    }
+
+    // but one it presents must still be trusted
+    let _authenticated = connect_with(addr, "localhost", &[], Some((CLIENT_CERT, CLIENT_KEY)))
+        .await
+        .unwrap();
+    let conn = accept(&listener).await;
+    assert!(matches!(
+        conn.get_property("securityClientCertificate").await,
+        Some(ConnectionProperty::SecurityClientCertificate(Some(_)))
+    ));
+
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_listener_trust_callback_checks_client_chain() {
+    // Without trust anchors, the callback alone decides, e.g. by pinning
+    let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut parameters = multi_tenant_parameters();
+    let chains = Arc::clone(&seen);
+    parameters
+        .set_client_certificate_policy(ClientCertificatePolicy::Require)
+        .set_trust_verification_callback(move |chain| {
+            chains.lock().unwrap().push(chain.certificates.len());
+            chain.certificates[0].data == CLIENT_CERT
+        });
+    let (listener, addr) = tls_listener(parameters).await;
+
+    let _client = connect_with(addr, "localhost", &[], Some((CLIENT_CERT, CLIENT_KEY)))
+        .await
+        .unwrap();
+    accept(&listener).await;
+
+    if let Ok(client) = connect_with(
+        addr,
+        "localhost",
+        &[],
+        Some((ROGUE_CLIENT_CERT, ROGUE_CLIENT_KEY)),
+    )
+    .await
+    {
+        assert_refused(client).await;
+    }
+    next_handshake_failure(&listener).await;
+    assert_eq!(*seen.lock().unwrap(), vec![1, 1]);
+
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_listener_client_certificates_need_trust() {
+    let mut parameters = multi_tenant_parameters();
+    parameters.set_client_certificate_policy(ClientCertificatePolicy::Require);
+    let preconn = Preconnection::new(
+        vec![LocalEndpoint {
+            identifiers: vec![EndpointIdentifier::Port(0)],
+        }],
+        vec![],
+        TransportProperties::default(),
+        parameters,
+    );
+    assert!(matches!(
+        preconn.listen().await,
+        Err(TransportServicesError::InvalidParameters(_))
+    ));
+}
+
+#[cfg(feature = "keylog")]
+#[tokio::test]
+async fn test_key_log_file_records_session_secrets() {
+    let path = std::env::temp_dir().join(format!("tapsrs-keylog-{}.txt", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut parameters = multi_tenant_parameters();
+    parameters.set_key_log_file(&path);
+    let (listener, addr) = tls_listener(parameters).await;
+
+    let _client = connect(addr, "localhost", &[]).await.unwrap();
+    accept(&listener).await;
+    listener.stop().await.unwrap();
+
+    // NSS key log lines: label, client random and secret, all hex
+    let log = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let labels = log
+        .lines()
+        .map(|line| {
+            let fields = line.split(' ').collect::<Vec<_>>();
+            assert_eq!(fields.len(), 3, "{line}");
+            assert_eq!(fields[1].len(), 64, "{line}");
+            fields[0].to_string()
+        })
+        .collect::<Vec<_>>();
+    assert!(labels.contains(&"CLIENT_HANDSHAKE_TRAFFIC_SECRET".to_string()));
+    assert!(labels.contains(&"SERVER_TRAFFIC_SECRET_0".to_string()));
+}
+
+#[tokio::test]
+async fn test_listener_rotates_certificates_mid_run() {
+    let (listener, addr) = tls_listener(multi_tenant_parameters()).await;
+
+    let mut before = connect(addr, "localhost", &[]).await.unwrap();
+    assert_eq!(presented_certificate(&before), DEFAULT_CERT);
+    let accepted_before = accept(&listener).await;
+
+    let mut rotated = multi_tenant_parameters();
+    rotated.server_identities[0] = identity(&["localhost"], ROTATED_CERT, ROTATED_KEY);
+    listener.reload_security(&rotated).unwrap();
+
+    // New handshakes present the new certificate
+    let mut after = connect(addr, "localhost", &[]).await.unwrap();
+    assert_eq!(presented_certificate(&after), ROTATED_CERT);
+    let accepted_after = accept(&listener).await;
+
+    // Sessions from before the rotation carry on
+    before.write_all(b"old session").await.unwrap();
+    assert_eq!(received(&accepted_before).await, b"old session");
+    accepted_before
+        .send(Message::from_bytes(b"still here"))
+        .await
+        .unwrap();
+    let mut reply = [0u8; 10];
+    before.read_exact(&mut reply).await.unwrap();
+    assert_eq!(&reply, b"still here");
+
+    after.write_all(b"new session").await.unwrap();
+    assert_eq!(received(&accepted_after).await, b"new session");
+
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_listener_reload_keeps_settings_on_error() {
+    let (listener, addr) = tls_listener(multi_tenant_parameters()).await;
+
+    let mut broken = multi_tenant_parameters();
+    broken.server_identities[0] = identity(&["localhost"], ROTATED_CERT, b"not a key");
+    assert!(matches!(
+        listener.reload_security(&broken),
+        Err(TransportServicesError::InvalidParameters(_))
+    ));
+    assert!(matches!(
+        listener.reload_security(&SecurityParameters::new()),
+        Err(TransportServicesError::InvalidParameters(_))
+    ));
+
+    let client = connect(addr, "localhost", &[]).await.unwrap();
+    assert_eq!(presented_certificate(&client), DEFAULT_CERT);
+    accept(&listener).await;
+    listener.stop().await.unwrap();
+
+    // A cleartext Listener has no TLS settings to replace
+    let (cleartext, _) = tls_listener(SecurityParameters::new_disabled()).await;
+    assert!(matches!(
+        cleartext.reload_security(&multi_tenant_parameters()),
+        Err(TransportServicesError::InvalidState(_))
+    ));
+    cleartext.stop().await.unwrap();
+}
+
+#[test]
+fn test_server_identity_matches_names() {
+    let identity = identity(&["Alpha.test", "*.beta.test"], ALPHA_CERT, ALPHA_KEY);
+    assert!(identity.matches("alpha.test"));
+    assert!(identity.matches("x.beta.test"));
+    assert!(!identity.matches("beta.test"));
+    assert!(!identity.matches("x.y.beta.test"));
+    assert!(!identity.matches("gamma.test"));
+}
+
+/// Client configuration whose session cache is shared by every connection
+/// made with it, so later ones can resume
+fn resuming_client() -> Arc<ClientConfig> {
+    let mut roots = RootCertStore::empty();
+    roots.add(CertificateDer::from(CA.to_vec())).unwrap();
+    let mut config = ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::aws_lc_rs::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    config.enable_early_data = true;
+    Arc::new(config)
+}
+
+/// Connect, then read a reply from the accepted connection so the client
+/// has processed the session tickets sent after the handshake
+async fn connect_resuming(
+    listener: &Listener,
+    addr: std::net::SocketAddr,
+    config: &Arc<ClientConfig>,
+) -> rustls::HandshakeKind {
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut client = TlsConnector::from(Arc::clone(config))
+        .connect(ServerName::try_from("localhost").unwrap(), stream)
+        .await
+        .unwrap();
+    let accepted = accept(listener).await;
+    accepted.send(Message::from_bytes(b"hi")).await.unwrap();
+    let mut reply = [0u8; 2];
+    client.read_exact(&mut reply).await.unwrap();
+    client.get_ref().1.handshake_kind().unwrap()
+}
+
+/// Resume a session with a blocking rustls client, sending `request` as
+/// 0-RTT data; returns whether the server accepted it
+async fn send_early_data(
+    addr: std::net::SocketAddr,
+    config: &Arc<ClientConfig>,
+    request: &'static [u8],
+) -> bool {
+    let config = Arc::clone(config);
+    tokio::task::spawn_blocking(move || {
+        use std::io::Write;
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(std::time::Duration::from_secs(2)))
+            .unwrap();
+        let mut client =
+            rustls::ClientConnection::new(config, ServerName::try_from("localhost").unwrap())
+                .unwrap();
+        client
+            .early_data()
+            .expect("Session should allow 0-RTT data")
+            .write_all(request)
+            .unwrap();
+        while client.is_handshaking() {
+            if client.complete_io(&mut stream).is_err() {
+                return false;
+            }
+        }
+        while client.wants_write() {
+            client.write_tls(&mut stream).unwrap();
+        }
+        client.is_early_data_accepted()
+    })
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_listener_resumes_sessions() {
+    for stateless in [false, true] {
+        let mut parameters = multi_tenant_parameters();
+        parameters.stateless_session_tickets = stateless;
+        let (listener, addr) = tls_listener(parameters).await;
+        let client = resuming_client();
+
+        assert_eq!(
+            connect_resuming(&listener, addr, &client).await,
+            rustls::HandshakeKind::Full
+        );
+        assert_eq!(
+            connect_resuming(&listener, addr, &client).await,
+            rustls::HandshakeKind::Resumed,
+            "stateless: {stateless}"
+        );
+        listener.stop().await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_listener_without_session_tickets_does_full_handshakes() {
+    let mut parameters = multi_tenant_parameters();
+    parameters.session_tickets = 0;
+    let (listener, addr) = tls_listener(parameters).await;
+    let client = resuming_client();
+
+    for _ in 0..2 {
+        assert_eq!(
+            connect_resuming(&listener, addr, &client).await,
+            rustls::HandshakeKind::Full
+        );
+    }
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_listener_expires_cached_sessions() {
+    let mut parameters = multi_tenant_parameters();
+    parameters.cached_session_lifetime_seconds = Some(1);
+    let (listener, addr) = tls_listener(parameters).await;
+    let client = resuming_client();
+
+    connect_resuming(&listener, addr, &client).await;
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert_eq!(
+        connect_resuming(&listener, addr, &client).await,
+        rustls::HandshakeKind::Full
+    );
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_listener_accepts_replay_safe_early_data() {
+    let checked = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut parameters = multi_tenant_parameters();
+    let seen = Arc::clone(&checked);
+    parameters.set_early_data_callback(move |data| {
+        seen.lock().unwrap().extend_from_slice(data);
+        data.starts_with(b"GET ")
+    });
+    let (listener, addr) = tls_listener(parameters).await;
+    let client = resuming_client();
+
+    connect_resuming(&listener, addr, &client).await;
+    assert!(send_early_data(addr, &client, b"GET /index.html").await);
+
+    // The early data reaches the application ahead of anything sent later
+    let accepted = accept(&listener).await;
+    assert_eq!(received(&accepted).await, b"GET /index.html");
+    assert_eq!(*checked.lock().unwrap(), b"GET /index.html");
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_listener_refuses_early_data_not_replay_safe() {
+    let mut parameters = multi_tenant_parameters();
+    parameters.set_early_data_callback(|data| data.starts_with(b"GET "));
+    let (listener, addr) = tls_listener(parameters).await;
+    let client = resuming_client();
+
+    connect_resuming(&listener, addr, &client).await;
+    send_early_data(addr, &client, b"POST /orders").await;
+    let reason = next_handshake_failure(&listener).await;
+    assert!(reason.contains("replay"), "{reason}");
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_listener_refuses_early_data_without_replay_check() {
+    let mut parameters = multi_tenant_parameters();
+    parameters.accept_early_data = true;
+    let (listener, addr) = tls_listener(parameters).await;
+    let client = resuming_client();
+
+    // Tickets the listener issues do not offer 0-RTT
+    connect_resuming(&listener, addr, &client).await;
+    let mut connection =
+        rustls::ClientConnection::new(client, ServerName::try_from("localhost").unwrap()).unwrap();
+    assert!(connection.early_data().is_none());
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_listener_early_data_needs_session_cache() {
+    let mut parameters = multi_tenant_parameters();
+    parameters.accept_early_data = true;
+    parameters.stateless_session_tickets = true;
+    let preconn = Preconnection::new(
+        vec![LocalEndpoint {
+            identifiers: vec![
+                EndpointIdentifier::IpAddress("127.0.0.1".parse().unwrap()),
+                EndpointIdentifier::Port(0),
+            ],
+        }],
+        vec![],
+        TransportProperties::default(),
+        parameters,
+    );
+    assert!(matches!(
+        preconn.listen().await,
+        Err(TransportServicesError::InvalidParameters(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_listener_sniffs_client_hello() {
+    let (listener, addr) = tls_listener(multi_tenant_parameters()).await;
+    listener.set_protocol_sniffer(Some(ProtocolSniffer::new(1)));
+
+    let mut client = connect(addr, "alpha.test", &["h2", "http/1.1"])
+        .await
+        .unwrap();
+    client.write_all(b"hello alpha").await.unwrap();
+
+    // The handshake still reads the peeked ClientHello
+    let conn = accept(&listener).await;
+    assert_eq!(received(&conn).await, b"hello alpha");
+    let sniffed = conn.sniffed().await.unwrap();
+    assert_eq!(sniffed.data, [0x16]);
+    assert_eq!(
+        sniffed.client_hello,
+        Some(ClientHelloInfo {
+            server_name: Some("alpha.test".to_string()),
+            alpn: vec!["h2".to_string(), "http/1.1".to_string()],
+        })
+    );
+
+    listener.stop().await.unwrap();
+}
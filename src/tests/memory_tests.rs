@@ -0,0 +1,135 @@
+//! Tests for per-connection memory accounting and the global memory budget
+
+use crate::memory::{memory_in_use, set_memory_budget};
+use crate::*;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+
+/// The budget is process-wide, so tests that set one take turns
+static BUDGET_TURN: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Peer that writes `length` bytes once connected, then holds the stream open
+async fn writing_peer(length: usize) -> std::net::SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        // The write fails once the connection is aborted
+        let _ = stream.write_all(&vec![7u8; length]).await;
+        tokio::time::sleep(Duration::from_secs(10)).await;
+    });
+    addr
+}
+
+async fn connect(addr: std::net::SocketAddr) -> Connection {
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.unwrap();
+    conn.wait_for_established(Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+    conn
+}
+
+/// Take Received events until `length` bytes have arrived
+async fn drain(conn: &Connection, length: usize) {
+    let mut received = 0;
+    while received < length {
+        match tokio::time::timeout(Duration::from_secs(5), conn.next_event()).await {
+            Ok(Some(ConnectionEvent::Received { message_data, .. })) => {
+                received += message_data.len()
+            }
+            Ok(Some(_)) => {}
+            other => panic!("Received {received} of {length} bytes, then {other:?}"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_received_data_counts_until_taken() {
+    let conn = connect(writing_peer(10_000).await).await;
+
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(2);
+    while conn.memory_usage().event_queue < 10_000 {
+        assert!(tokio::time::Instant::now() < deadline, "Data not received");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let usage = conn.memory_usage();
+    assert_eq!(usage.event_queue, 10_000);
+    assert_eq!(usage.receive_buffer, 0);
+    assert_eq!(conn.stats().await.memory, usage);
+    assert!(matches!(
+        conn.get_property("memoryUsage").await,
+        Some(ConnectionProperty::MemoryUsage(property)) if property == usage
+    ));
+
+    drain(&conn, 10_000).await;
+    assert_eq!(conn.memory_usage().total(), 0);
+}
+
+#[tokio::test]
+async fn test_batched_messages_count_as_send_queue() {
+    let conn = connect(writing_peer(0).await).await;
+
+    conn.start_batch().await.unwrap();
+    for _ in 0..3 {
+        conn.send(Message::new(vec![1u8; 100])).await.unwrap();
+    }
+    assert_eq!(conn.memory_usage().send_queue, 300);
+
+    conn.end_batch().await.unwrap();
+    assert_eq!(conn.memory_usage().send_queue, 0);
+}
+
+#[tokio::test]
+async fn test_memory_budget_backpressure_bounds_reading() {
+    let _turn = BUDGET_TURN.lock().await;
+    let length = 4 * 1024 * 1024;
+    set_memory_budget(Some(MemoryBudget::new(memory_in_use() + 256 * 1024)));
+
+    let conn = connect(writing_peer(length).await).await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let held = conn.memory_usage().total();
+    set_memory_budget(None);
+
+    // Reading paused with most of the data still with the transport
+    assert!(held > 0, "Nothing was read");
+    assert!(held < length / 4, "{held} bytes read despite the budget");
+
+    // Lifting the budget resumes delivery
+    drain(&conn, length).await;
+}
+
+#[tokio::test]
+async fn test_memory_budget_closes_largest_connection() {
+    let _turn = BUDGET_TURN.lock().await;
+    set_memory_budget(Some(
+        MemoryBudget::new(memory_in_use() + 256 * 1024)
+            .with_policy(MemoryPressurePolicy::CloseLargest),
+    ));
+
+    // Received data piles up while the application takes no events
+    let conn = connect(writing_peer(4 * 1024 * 1024).await).await;
+    let mut state = conn.state_watch().await;
+    let closed = tokio::time::timeout(
+        Duration::from_secs(5),
+        state.wait_for(|state| *state == ConnectionState::Closed),
+    )
+    .await;
+    set_memory_budget(None);
+    assert!(closed.is_ok(), "Connection was not closed");
+
+    let error = loop {
+        match conn.next_event().await {
+            Some(ConnectionEvent::ConnectionError(error)) => break error,
+            Some(_) => {}
+            None => panic!("Event stream ended"),
+        }
+    };
+    assert!(error.contains("memory budget"), "{error}");
+}
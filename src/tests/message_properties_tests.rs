@@ -244,3 +244,74 @@ fn test_message_properties_clone() {
         original.properties().capacity_profile
     );
 }
+
+#[test]
+fn test_message_from_segments() {
+    use bytes::Bytes;
+
+    let msg = Message::from_segments(vec![
+        Bytes::from_static(b"HEADER:"),
+        Bytes::new(),
+        Bytes::from_static(b"body"),
+    ]);
+
+    assert_eq!(msg.len(), 11);
+    assert!(!msg.is_empty());
+    // Empty segments are dropped
+    assert_eq!(msg.segments().len(), 2);
+    assert_eq!(msg.data(), b"HEADER:body");
+
+    // Mutable access flattens the message into a single buffer
+    let mut msg = msg;
+    msg.data_mut().extend_from_slice(b"!");
+    assert_eq!(msg.data(), b"HEADER:body!");
+    assert_eq!(msg.segments().len(), 1);
+}
+
+#[test]
+fn test_received_message_properties() {
+    use crate::MessageContext;
+
+    let context = MessageContext::new()
+        .with_final(true)
+        .with_stream_id(4)
+        .with_framer_metadata("length", vec![0, 0, 0, 5]);
+
+    assert!(context.is_final());
+    assert_eq!(context.message_properties().stream_id, Some(4));
+    assert_eq!(context.message_properties().ordered, None);
+    assert_eq!(
+        context.message_properties().framer_metadata.get("length"),
+        Some(&vec![0, 0, 0, 5])
+    );
+
+    // Defaults for a fresh context
+    let context = MessageContext::new();
+    assert!(!context.is_final());
+    assert!(context.message_properties().framer_metadata.is_empty());
+}
+
+#[test]
+fn test_message_conversions() {
+    use crate::MessageProperties;
+    use bytes::Bytes;
+
+    assert_eq!(Message::from("text").data(), b"text");
+    assert_eq!(Message::from(String::from("owned")).data(), b"owned");
+    assert_eq!(Message::from(b"array").data(), b"array");
+    assert_eq!(Message::from(&b"slice"[..]).data(), b"slice");
+    assert_eq!(Message::from(vec![1u8, 2, 3]).data(), &[1, 2, 3]);
+    assert_eq!(Message::from(Bytes::from_static(b"bytes")).data(), b"bytes");
+
+    // Data paired with properties keeps both
+    let properties = MessageProperties {
+        priority: Some(7),
+        ..Default::default()
+    };
+    let msg = Message::from(("prioritised", properties));
+    assert_eq!(msg.data(), b"prioritised");
+    assert_eq!(msg.properties().priority, Some(7));
+
+    let msg: Message = Message::builder(b"built".to_vec()).priority(3).into();
+    assert_eq!(msg.properties().priority, Some(3));
+}
@@ -1,8 +1,8 @@
 //! Unit tests for Message Sending functionality
 
 use crate::{
-    message::SendContext, ConnectionEvent, ConnectionState, Message, Preconnection, RemoteEndpoint,
-    SecurityParameters, TransportProperties,
+    message::SendContext, ConnectionEvent, ConnectionState, Message, Preconnection, Preference,
+    RemoteEndpoint, SecurityParameters, TransportProperties, TransportServicesError,
 };
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -360,6 +360,98 @@ async fn test_initiate_with_send() {
     conn.close().await.unwrap();
 }
 
+#[tokio::test]
+async fn test_initiate_with_send_zero_rtt_requires_replayable() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buf = [0; 1024];
+                let _ = tokio::io::AsyncReadExt::read(&mut stream, &mut buf).await;
+            });
+        }
+    });
+
+    let properties = TransportProperties::builder()
+        .zero_rtt_msg(Preference::Require)
+        .build();
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .socket_address(server_addr)
+            .build()],
+        properties,
+        SecurityParameters::new_disabled(),
+    );
+
+    // A non-replayable message cannot be sent as 0-RTT data
+    let result = preconn
+        .initiate_with_send(Message::from_string("POST /order"))
+        .await;
+    assert!(matches!(
+        result,
+        Err(TransportServicesError::InvalidParameters(_))
+    ));
+
+    // A safely replayable message is accepted
+    let message = Message::from_string("GET /").safely_replayable();
+    let conn = preconn.initiate_with_send(message).await.unwrap();
+    loop {
+        match tokio::time::timeout(Duration::from_secs(2), conn.next_event()).await {
+            Ok(Some(ConnectionEvent::Sent { .. })) => break,
+            Ok(Some(ConnectionEvent::Ready)) => continue,
+            other => panic!("Expected Sent event, got: {other:?}"),
+        }
+    }
+
+    conn.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_initiate_with_send_downgrades_non_replayable() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_addr = listener.local_addr().unwrap();
+
+    let (tx, mut rx) = mpsc::channel(1);
+    tokio::spawn(async move {
+        if let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = [0; 1024];
+            if let Ok(n) = tokio::io::AsyncReadExt::read(&mut stream, &mut buf).await {
+                let _ = tx.send(buf[..n].to_vec()).await;
+            }
+        }
+    });
+
+    // With 0-RTT only preferred, a non-replayable message is sent after the handshake
+    let properties = TransportProperties::builder()
+        .zero_rtt_msg(Preference::Prefer)
+        .build();
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .socket_address(server_addr)
+            .build()],
+        properties,
+        SecurityParameters::new_disabled(),
+    );
+
+    let conn = preconn
+        .initiate_with_send(Message::from_string("POST /order"))
+        .await
+        .unwrap();
+
+    let received = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(received, b"POST /order");
+    assert_eq!(conn.state().await, ConnectionState::Established);
+
+    conn.close().await.unwrap();
+}
+
 #[tokio::test]
 async fn test_send_on_closed_connection() {
     // Start a test server
@@ -464,3 +556,1083 @@ async fn test_send_with_event_notifier() {
 
     conn.close().await.unwrap();
 }
+
+#[tokio::test]
+async fn test_segmented_message_send_with_framer() {
+    use bytes::Bytes;
+    use tokio::io::AsyncReadExt;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_addr = listener.local_addr().unwrap();
+
+    // Server reads exactly one framed message and reports what it saw
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buf = vec![0u8; 4 + 11];
+        stream.read_exact(&mut buf).await.unwrap();
+        let _ = tx.send(buf);
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .socket_address(server_addr)
+            .build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+
+    let conn = preconn.initiate().await.unwrap();
+    let ready_event = conn.next_event().await;
+    assert!(matches!(ready_event, Some(ConnectionEvent::Ready)));
+    conn.use_length_prefix_framer().await.unwrap();
+
+    let message = Message::from_segments(vec![
+        Bytes::from_static(b"HEADER:"),
+        Bytes::from_static(b"body"),
+    ]);
+    conn.send(message).await.unwrap();
+
+    let received = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+        .await
+        .expect("server should receive the message")
+        .unwrap();
+    assert_eq!(&received[..4], &11u32.to_be_bytes());
+    assert_eq!(&received[4..], b"HEADER:body");
+
+    conn.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_empty_message_with_framer_round_trips() {
+    use crate::LengthPrefixFramer;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_addr = listener.local_addr().unwrap();
+
+    // Server sends an empty frame and a non-empty one, then reads two frames
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        stream.write_all(&[0, 0, 0, 0, 0, 0, 0, 2]).await.unwrap();
+        stream.write_all(b"hi").await.unwrap();
+        let mut buf = vec![0u8; 4 + 4 + 3];
+        stream.read_exact(&mut buf).await.unwrap();
+        buf
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .socket_address(server_addr)
+            .build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    preconn
+        .add_framer(|| Box::new(LengthPrefixFramer::new()))
+        .await
+        .unwrap();
+    let conn = preconn.initiate().await.unwrap();
+
+    let mut received = Vec::new();
+    while received.len() < 2 {
+        match tokio::time::timeout(Duration::from_secs(2), conn.next_event()).await {
+            Ok(Some(ConnectionEvent::Received { message_data, .. })) => received.push(message_data),
+            Ok(Some(_)) => {}
+            other => panic!("Expected Received event, got {other:?}"),
+        }
+    }
+    assert_eq!(received, vec![Vec::new(), b"hi".to_vec()]);
+
+    conn.send(Message::new(Vec::new())).await.unwrap();
+    conn.send(Message::from_string("bye")).await.unwrap();
+    let written = tokio::time::timeout(Duration::from_secs(2), server)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(written, b"\0\0\0\0\0\0\0\x03bye");
+
+    conn.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_empty_message_without_framer_writes_nothing() {
+    use tokio::io::AsyncReadExt;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_addr = listener.local_addr().unwrap();
+
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).await.unwrap();
+        buf
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .socket_address(server_addr)
+            .build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.unwrap();
+    assert!(matches!(
+        conn.next_event().await,
+        Some(ConnectionEvent::Ready)
+    ));
+
+    // A byte stream has no boundaries, so an empty message is sent as no bytes
+    conn.send(Message::new(Vec::new())).await.unwrap();
+    assert!(matches!(
+        conn.next_event().await,
+        Some(ConnectionEvent::Sent { .. })
+    ));
+    conn.send(Message::from_string("data")).await.unwrap();
+    conn.close().await.unwrap();
+
+    let written = tokio::time::timeout(Duration::from_secs(2), server)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(written, b"data");
+}
+
+#[tokio::test]
+async fn test_bundled_messages_are_coalesced() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let (tx, mut rx) = mpsc::channel(8);
+    tokio::spawn(async move {
+        if let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = [0; 1024];
+            while let Ok(n) = tokio::io::AsyncReadExt::read(&mut stream, &mut buf).await {
+                if n == 0 || tx.send(buf[..n].to_vec()).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.unwrap();
+    while conn.state().await == ConnectionState::Establishing {
+        sleep(Duration::from_millis(10)).await;
+    }
+    assert!(matches!(
+        conn.next_event().await,
+        Some(ConnectionEvent::Ready)
+    ));
+
+    let bundled = || SendContext {
+        expiry: None,
+        bundle: true,
+        completion_notifier: None,
+    };
+
+    conn.send(Message::from_string("one,").with_send_context(bundled()))
+        .await
+        .unwrap();
+    conn.send(Message::from_string("two,").with_send_context(bundled()))
+        .await
+        .unwrap();
+
+    // Bundled messages are held until the bundle completes
+    assert!(tokio::time::timeout(Duration::from_millis(100), rx.recv())
+        .await
+        .is_err());
+
+    conn.send(Message::from_string("three")).await.unwrap();
+
+    let mut received = Vec::new();
+    while received.len() < 13 {
+        let chunk = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        received.extend(chunk);
+    }
+    assert_eq!(received, b"one,two,three");
+
+    // Every message in the bundle is reported as sent
+    for _ in 0..3 {
+        assert!(matches!(
+            conn.next_event().await,
+            Some(ConnectionEvent::Sent { .. })
+        ));
+    }
+
+    // A trailing bundle is flushed when the batch ends
+    conn.start_batch().await.unwrap();
+    conn.send(Message::from_string("four").with_send_context(bundled()))
+        .await
+        .unwrap();
+    conn.end_batch().await.unwrap();
+    let chunk = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(chunk, b"four");
+
+    conn.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_send_accepts_data_convertible_to_messages() {
+    use crate::MessageProperties;
+    use bytes::Bytes;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let (tx, mut rx) = mpsc::channel(8);
+    tokio::spawn(async move {
+        if let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = [0; 1024];
+            while let Ok(n) = tokio::io::AsyncReadExt::read(&mut stream, &mut buf).await {
+                if n == 0 || tx.send(buf[..n].to_vec()).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.unwrap();
+    while conn.state().await == ConnectionState::Establishing {
+        sleep(Duration::from_millis(10)).await;
+    }
+
+    conn.send("str,").await.unwrap();
+    conn.send(String::from("string,")).await.unwrap();
+    conn.send(b"array,").await.unwrap();
+    conn.send(&b"slice,"[..]).await.unwrap();
+    conn.send(b"vec,".to_vec()).await.unwrap();
+    conn.send(Bytes::from_static(b"bytes,")).await.unwrap();
+    let properties = MessageProperties {
+        priority: Some(7),
+        ..Default::default()
+    };
+    conn.send(("with properties", properties)).await.unwrap();
+
+    let expected = b"str,string,array,slice,vec,bytes,with properties";
+    let mut received = Vec::new();
+    while received.len() < expected.len() {
+        let chunk = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        received.extend(chunk);
+    }
+    assert_eq!(received, expected);
+}
+
+#[tokio::test]
+async fn test_per_message_reliability_on_tcp() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                sleep(Duration::from_secs(1)).await;
+                drop(stream);
+            });
+        }
+    });
+
+    let connect = |pref: Preference| {
+        Preconnection::new(
+            vec![],
+            vec![RemoteEndpoint::builder().socket_address(addr).build()],
+            TransportProperties::builder()
+                .per_msg_reliability(pref)
+                .build(),
+            SecurityParameters::new_disabled(),
+        )
+    };
+
+    // TCP cannot satisfy a requirement for per-message reliability
+    let result = connect(Preference::Require).initiate().await;
+    assert!(matches!(
+        result,
+        Err(TransportServicesError::NotSupported(_))
+    ));
+
+    // Without perMsgReliability, unreliable messages are rejected
+    let conn = connect(Preference::NoPreference).initiate().await.unwrap();
+    let result = conn
+        .send(Message::from_string("unreliable").with_reliable(false))
+        .await;
+    assert!(matches!(
+        result,
+        Err(TransportServicesError::InvalidParameters(_))
+    ));
+    conn.send(Message::from_string("reliable").with_reliable(true))
+        .await
+        .unwrap();
+    conn.close().await.unwrap();
+
+    // When only preferred, unreliable messages fall back to reliable delivery
+    let conn = connect(Preference::Prefer).initiate().await.unwrap();
+    while conn.state().await == ConnectionState::Establishing {
+        sleep(Duration::from_millis(10)).await;
+    }
+    conn.send(Message::from_string("unreliable").with_reliable(false))
+        .await
+        .unwrap();
+    conn.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_unordered_messages_on_tcp() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let (tx, mut rx) = mpsc::channel(8);
+    tokio::spawn(async move {
+        if let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = [0; 1024];
+            while let Ok(n) = tokio::io::AsyncReadExt::read(&mut stream, &mut buf).await {
+                if n == 0 || tx.send(buf[..n].to_vec()).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::builder()
+            .preserve_order(Preference::Avoid)
+            .build(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.unwrap();
+    while conn.state().await == ConnectionState::Establishing {
+        sleep(Duration::from_millis(10)).await;
+    }
+
+    // TCP delivers unordered messages in order, which satisfies them
+    conn.send(Message::from_string("a").with_ordered(false))
+        .await
+        .unwrap();
+    conn.send(Message::from_string("b").with_ordered(true))
+        .await
+        .unwrap();
+
+    let mut received = Vec::new();
+    while received.len() < 2 {
+        let chunk = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        received.extend(chunk);
+    }
+    assert_eq!(received, b"ab");
+
+    conn.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_request_returns_response() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // Echo server that answers each request once
+    tokio::spawn(async move {
+        if let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = [0; 1024];
+            while let Ok(n) = tokio::io::AsyncReadExt::read(&mut stream, &mut buf).await {
+                if n == 0 {
+                    break;
+                }
+                let mut reply = b"re:".to_vec();
+                reply.extend_from_slice(&buf[..n]);
+                let _ = tokio::io::AsyncWriteExt::write_all(&mut stream, &reply).await;
+            }
+        }
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.unwrap();
+
+    let (response, _) = conn
+        .request_with_timeout("ping", Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+    assert_eq!(response.data(), b"re:ping");
+
+    conn.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_request_times_out_without_response() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // Server that reads but never answers
+    tokio::spawn(async move {
+        if let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = [0; 1024];
+            while let Ok(n) = tokio::io::AsyncReadExt::read(&mut stream, &mut buf).await {
+                if n == 0 {
+                    break;
+                }
+            }
+        }
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.unwrap();
+
+    let result = conn
+        .request_with_timeout("ping", Some(Duration::from_millis(200)))
+        .await;
+    assert!(matches!(result, Err(TransportServicesError::Timeout)));
+
+    conn.close().await.unwrap();
+}
+
+/// Connect to a peer that accepts but never reads, so its window closes
+async fn connect_to_stalled_peer() -> (crate::Connection, tokio::task::JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let peer = tokio::spawn(async move {
+        let (_stream, _) = listener.accept().await.unwrap();
+        sleep(Duration::from_secs(10)).await;
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::builder()
+            .send_buffer_size(16 * 1024)
+            .soft_error_notify(Preference::Prefer)
+            .build(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.unwrap();
+    conn.wait_for_established(Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+    (conn, peer)
+}
+
+#[tokio::test]
+async fn test_flow_control_stall_reported() {
+    use crate::{ConnectionProperty, TimeoutValue};
+
+    let (conn, peer) = connect_to_stalled_peer().await;
+    conn.set_property(
+        "sendStallThreshold",
+        ConnectionProperty::SendStallThreshold(TimeoutValue::Duration(Duration::from_millis(300))),
+    )
+    .await
+    .unwrap();
+    assert!(matches!(
+        conn.get_property("sendStall").await,
+        Some(ConnectionProperty::SendStall(None))
+    ));
+    assert!(conn
+        .set_property("sendStall", ConnectionProperty::SendStall(None))
+        .await
+        .is_err());
+
+    // Far more than the socket buffers on both ends can hold
+    let sender = conn.clone();
+    let send = tokio::spawn(async move {
+        let _ = sender.send(Message::new(vec![0u8; 64 * 1024 * 1024])).await;
+    });
+
+    // The property can be read while the write is stalled
+    let started = Instant::now();
+    let stalled = loop {
+        if let Some(ConnectionProperty::SendStall(Some(stalled))) =
+            conn.get_property("sendStall").await
+        {
+            break stalled;
+        }
+        assert!(
+            started.elapsed() < Duration::from_secs(5),
+            "stall not reported"
+        );
+        sleep(Duration::from_millis(50)).await;
+    };
+    assert!(stalled >= Duration::from_millis(300));
+
+    let soft_error = loop {
+        match tokio::time::timeout(Duration::from_secs(2), conn.next_event()).await {
+            Ok(Some(ConnectionEvent::SoftError(error))) => break error,
+            Ok(Some(_)) => {}
+            other => panic!("Expected SoftError, got {other:?}"),
+        }
+    };
+    assert!(soft_error.contains("flow control"));
+
+    send.abort();
+    peer.abort();
+}
+
+#[tokio::test]
+async fn test_send_timeout_fails_stalled_write() {
+    use crate::{ConnectionProperty, TimeoutValue};
+
+    let (conn, peer) = connect_to_stalled_peer().await;
+    conn.set_property(
+        "sendTimeout",
+        ConnectionProperty::SendTimeout(TimeoutValue::Duration(Duration::from_millis(200))),
+    )
+    .await
+    .unwrap();
+
+    // Far more than the socket buffers on both ends can hold
+    let message = Message::new(vec![0u8; 64 * 1024 * 1024]).with_id(7);
+    let started = Instant::now();
+    let result = tokio::time::timeout(Duration::from_secs(5), conn.send(message))
+        .await
+        .expect("send should not hang");
+    assert!(matches!(result, Err(TransportServicesError::Timeout)));
+    assert!(started.elapsed() < Duration::from_secs(2));
+
+    assert!(matches!(
+        conn.next_event().await,
+        Some(ConnectionEvent::Ready)
+    ));
+    match conn.next_event().await {
+        Some(ConnectionEvent::SendError { message_id, .. }) => assert_eq!(message_id, Some(7)),
+        other => panic!("Expected SendError, got {other:?}"),
+    }
+    assert!(matches!(
+        conn.next_event().await,
+        Some(ConnectionEvent::ConnectionError(_))
+    ));
+    assert_eq!(conn.state().await, ConnectionState::Closed);
+
+    peer.abort();
+}
+
+#[tokio::test]
+async fn test_failed_write_reports_partial_progress() {
+    use crate::{ConnectionProperty, TimeoutValue};
+
+    let (conn, peer) = connect_to_stalled_peer().await;
+    conn.set_property(
+        "sendTimeout",
+        ConnectionProperty::SendTimeout(TimeoutValue::Duration(Duration::from_millis(200))),
+    )
+    .await
+    .unwrap();
+
+    // A small message and one far larger than the socket buffers, written together
+    let bundled = SendContext {
+        expiry: None,
+        bundle: true,
+        completion_notifier: None,
+    };
+    conn.send(
+        Message::from_string("head")
+            .with_id(1)
+            .with_send_context(bundled),
+    )
+    .await
+    .unwrap();
+    let result = tokio::time::timeout(
+        Duration::from_secs(5),
+        conn.send(Message::new(vec![0u8; 64 * 1024 * 1024]).with_id(2)),
+    )
+    .await
+    .expect("send should not hang");
+    assert!(matches!(result, Err(TransportServicesError::Timeout)));
+
+    assert!(matches!(
+        conn.next_event().await,
+        Some(ConnectionEvent::Ready)
+    ));
+    // The message written in full before the failure was sent
+    assert!(matches!(
+        conn.next_event().await,
+        Some(ConnectionEvent::Sent {
+            message_id: Some(1)
+        })
+    ));
+    match conn.next_event().await {
+        Some(ConnectionEvent::SendError {
+            message_id: Some(2),
+            error,
+        }) => assert!(
+            error.contains(&format!("of {} bytes", 64 * 1024 * 1024)),
+            "{error}"
+        ),
+        other => panic!("Expected SendError, got {other:?}"),
+    }
+    assert!(matches!(
+        conn.next_event().await,
+        Some(ConnectionEvent::ConnectionError(_))
+    ));
+
+    peer.abort();
+}
+
+#[tokio::test]
+async fn test_fatal_write_error_fails_connection() {
+    let (conn, peer) = connect_to_stalled_peer().await;
+    // Writes on the socket now fail with a broken pipe
+    conn.inspect_tcp_socket(|socket| socket.shutdown(std::net::Shutdown::Write))
+        .await
+        .expect("Should have a TCP stream")
+        .unwrap();
+
+    let result = conn.send(Message::from_string("lost").with_id(1)).await;
+    assert!(matches!(result, Err(TransportServicesError::SendFailed(_))));
+    assert_eq!(conn.state().await, ConnectionState::Closed);
+
+    let mut send_error = false;
+    let connection_error = loop {
+        match tokio::time::timeout(Duration::from_secs(2), conn.next_event()).await {
+            Ok(Some(ConnectionEvent::SendError {
+                message_id: Some(1),
+                ..
+            })) => send_error = true,
+            Ok(Some(ConnectionEvent::ConnectionError(error))) => break error,
+            Ok(Some(_)) => {}
+            other => panic!("Expected ConnectionError, got {other:?}"),
+        }
+    };
+    assert!(send_error);
+    assert!(
+        connection_error.contains("0 of 4 bytes"),
+        "{connection_error}"
+    );
+
+    // Later sends fail without touching the broken socket
+    assert!(matches!(
+        conn.send("again").await,
+        Err(TransportServicesError::InvalidState(_))
+    ));
+
+    peer.abort();
+}
+
+#[tokio::test]
+async fn test_message_deadline_fails_stalled_write() {
+    let (conn, peer) = connect_to_stalled_peer().await;
+
+    // A message within its deadline is sent as usual
+    conn.send(Message::from_string("small").with_deadline(Duration::from_millis(200)))
+        .await
+        .unwrap();
+
+    let message = Message::builder(vec![0u8; 64 * 1024 * 1024])
+        .deadline(Duration::from_millis(200))
+        .build();
+    let result = tokio::time::timeout(Duration::from_secs(5), conn.send(message))
+        .await
+        .expect("send should not hang");
+    assert!(matches!(result, Err(TransportServicesError::Timeout)));
+    assert_eq!(conn.state().await, ConnectionState::Closed);
+
+    peer.abort();
+}
+
+#[tokio::test]
+async fn test_scoped_batches_do_not_interleave() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let (tx, mut rx) = mpsc::channel(8);
+    tokio::spawn(async move {
+        if let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = [0; 1024];
+            while let Ok(n) = tokio::io::AsyncReadExt::read(&mut stream, &mut buf).await {
+                if n == 0 || tx.send(buf[..n].to_vec()).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.unwrap();
+    conn.wait_for_established(Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+
+    // Two users build batches at the same time on different tasks
+    let mut first = conn.batch();
+    let mut second = conn.batch();
+    first.add("a1,").add("a2,");
+    second.add("b1,");
+    let handle = tokio::spawn(async move {
+        second.add("b2,");
+        second.commit().await
+    });
+    handle.await.unwrap().unwrap();
+    first.add("a3,");
+    assert_eq!(first.len(), 3);
+
+    // A direct send is not swept into an open batch
+    conn.send("direct,").await.unwrap();
+    first.commit().await.unwrap();
+
+    let expected = b"b1,b2,direct,a1,a2,a3,";
+    let mut received = Vec::new();
+    while received.len() < expected.len() {
+        let chunk = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        received.extend(chunk);
+    }
+    assert_eq!(received, expected);
+
+    conn.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_dropped_batch_is_discarded() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let (tx, mut rx) = mpsc::channel(8);
+    tokio::spawn(async move {
+        if let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = [0; 1024];
+            while let Ok(n) = tokio::io::AsyncReadExt::read(&mut stream, &mut buf).await {
+                if n == 0 || tx.send(buf[..n].to_vec()).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.unwrap();
+    conn.wait_for_established(Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+    assert!(matches!(
+        conn.next_event().await,
+        Some(ConnectionEvent::Ready)
+    ));
+
+    let mut abandoned = conn.batch();
+    abandoned.add("never sent");
+    drop(abandoned);
+
+    // An expired message fails the whole batch, and nothing is sent
+    let mut expired = conn.batch();
+    expired
+        .add("also never sent")
+        .add(
+            Message::from_string("stale").with_send_context(SendContext {
+                expiry: Some(Instant::now() - Duration::from_secs(1)),
+                bundle: false,
+                completion_notifier: None,
+            }),
+        );
+    assert!(matches!(
+        expired.commit().await,
+        Err(TransportServicesError::MessageExpired)
+    ));
+    assert!(matches!(
+        conn.next_event().await,
+        Some(ConnectionEvent::Expired { .. })
+    ));
+
+    conn.send("kept").await.unwrap();
+    let chunk = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(chunk, b"kept");
+    assert!(matches!(
+        conn.next_event().await,
+        Some(ConnectionEvent::Sent { .. })
+    ));
+
+    // Batches committed on a closed connection are rejected
+    conn.close().await.unwrap();
+    let mut late = conn.batch();
+    late.add("late");
+    assert!(late.commit().await.is_err());
+}
+
+/// Start a server that speaks first, reporting what the client sent before
+/// its banner and what it sent after
+async fn banner_server(
+    banner_delay: Duration,
+) -> (
+    std::net::SocketAddr,
+    tokio::sync::oneshot::Receiver<(Vec<u8>, Vec<u8>)>,
+) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_addr = listener.local_addr().unwrap();
+    let (tx, rx) = tokio::sync::oneshot::channel();
+
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buf = [0u8; 1024];
+
+        let mut before = Vec::new();
+        if let Ok(Ok(n)) = tokio::time::timeout(banner_delay, stream.read(&mut buf)).await {
+            before.extend_from_slice(&buf[..n]);
+        }
+
+        stream.write_all(b"220 ready\r\n").await.unwrap();
+
+        let mut after = Vec::new();
+        while let Ok(Ok(n)) =
+            tokio::time::timeout(Duration::from_millis(500), stream.read(&mut buf)).await
+        {
+            if n == 0 {
+                break;
+            }
+            after.extend_from_slice(&buf[..n]);
+        }
+        let _ = tx.send((before, after));
+    });
+
+    (server_addr, rx)
+}
+
+#[tokio::test]
+async fn test_active_read_before_send_waits_for_peer() {
+    let (server_addr, result) = banner_server(Duration::from_millis(200)).await;
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .socket_address(server_addr)
+            .build()],
+        TransportProperties::builder()
+            .active_read_before_send(Preference::Require)
+            .build(),
+        SecurityParameters::default(),
+    );
+
+    let conn = preconn
+        .initiate_with_send(Message::from_string("EHLO client\r\n"))
+        .await
+        .unwrap();
+    conn.send(Message::from_string("NOOP\r\n")).await.unwrap();
+
+    // The background reader delivers the banner, releasing the held messages
+    loop {
+        match conn.next_event().await {
+            Some(ConnectionEvent::Received { message_data, .. }) => {
+                assert_eq!(message_data, b"220 ready\r\n");
+                break;
+            }
+            Some(_) => continue,
+            None => panic!("Event stream ended before the banner"),
+        }
+    }
+    let (before, after) = result.await.unwrap();
+    assert!(
+        before.is_empty(),
+        "client sent {before:?} before the banner"
+    );
+    assert_eq!(after, b"EHLO client\r\nNOOP\r\n");
+
+    conn.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_without_active_read_before_send_initiator_writes_first() {
+    let (server_addr, result) = banner_server(Duration::from_millis(200)).await;
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .socket_address(server_addr)
+            .build()],
+        TransportProperties::default(),
+        SecurityParameters::default(),
+    );
+
+    let conn = preconn
+        .initiate_with_send(Message::from_string("EHLO client\r\n"))
+        .await
+        .unwrap();
+
+    let (before, _) = result.await.unwrap();
+    assert_eq!(before, b"EHLO client\r\n");
+
+    conn.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_active_read_before_send_close_sends_held_messages() {
+    let (server_addr, result) = banner_server(Duration::from_millis(500)).await;
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .socket_address(server_addr)
+            .build()],
+        TransportProperties::builder()
+            .active_read_before_send(Preference::Prefer)
+            .build(),
+        SecurityParameters::default(),
+    );
+
+    let conn = preconn.initiate().await.unwrap();
+    while conn.state().await == ConnectionState::Establishing {
+        sleep(Duration::from_millis(10)).await;
+    }
+    conn.send(Message::from_string("QUIT\r\n")).await.unwrap();
+    conn.close().await.unwrap();
+
+    let (before, _) = result.await.unwrap();
+    assert_eq!(before, b"QUIT\r\n");
+}
+
+#[tokio::test]
+async fn test_send_waits_below_low_watermark() {
+    let (server_addr, result) = banner_server(Duration::from_millis(300)).await;
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .socket_address(server_addr)
+            .build()],
+        TransportProperties::builder()
+            .active_read_before_send(Preference::Require)
+            .send_watermarks(10, 4)
+            .build(),
+        SecurityParameters::default(),
+    );
+
+    let conn = preconn.initiate().await.unwrap();
+    while conn.state().await == ConnectionState::Establishing {
+        sleep(Duration::from_millis(10)).await;
+    }
+
+    // Held until the banner arrives, filling the queue past its high watermark
+    conn.send(Message::from_string("HELO client\r\n"))
+        .await
+        .unwrap();
+    assert_eq!(conn.send_queue_len().await, 13);
+    assert!(matches!(
+        conn.try_send(Message::from_string("NOOP\r\n")).await,
+        Err(TransportServicesError::WouldBlock)
+    ));
+
+    let sender = conn.clone();
+    let send = tokio::spawn(async move { sender.send(Message::from_string("QUIT\r\n")).await });
+    sleep(Duration::from_millis(100)).await;
+    assert!(!send.is_finished(), "send() did not wait for the queue");
+
+    // The banner releases the held message, draining the queue
+    tokio::time::timeout(Duration::from_secs(2), send)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let (before, after) = result.await.unwrap();
+    assert!(before.is_empty());
+    assert_eq!(after, b"HELO client\r\nQUIT\r\n");
+    assert_eq!(conn.send_queue_len().await, 0);
+
+    conn.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_send_queue_unbounded_without_watermarks() {
+    let (server_addr, _result) = banner_server(Duration::from_millis(300)).await;
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .socket_address(server_addr)
+            .build()],
+        TransportProperties::builder()
+            .active_read_before_send(Preference::Require)
+            .build(),
+        SecurityParameters::default(),
+    );
+
+    let conn = preconn.initiate().await.unwrap();
+    while conn.state().await == ConnectionState::Establishing {
+        sleep(Duration::from_millis(10)).await;
+    }
+    for _ in 0..10 {
+        conn.try_send(Message::from_bytes(&[0u8; 1024]))
+            .await
+            .unwrap();
+    }
+    assert_eq!(conn.send_queue_len().await, 10 * 1024);
+
+    conn.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_closing_releases_waiting_send() {
+    let (server_addr, _result) = banner_server(Duration::from_secs(2)).await;
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .socket_address(server_addr)
+            .build()],
+        TransportProperties::builder()
+            .active_read_before_send(Preference::Require)
+            .send_watermarks(4, 0)
+            .build(),
+        SecurityParameters::default(),
+    );
+
+    let conn = preconn.initiate().await.unwrap();
+    while conn.state().await == ConnectionState::Establishing {
+        sleep(Duration::from_millis(10)).await;
+    }
+    conn.send(Message::from_string("HELO\r\n")).await.unwrap();
+
+    let sender = conn.clone();
+    let send = tokio::spawn(async move { sender.send(Message::from_string("QUIT\r\n")).await });
+    sleep(Duration::from_millis(50)).await;
+    conn.abort().await.unwrap();
+
+    let result = tokio::time::timeout(Duration::from_secs(1), send)
+        .await
+        .expect("abort should release the waiting send")
+        .unwrap();
+    assert!(result.is_err());
+}
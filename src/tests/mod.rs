@@ -6,6 +6,8 @@ mod connection_tests;
 #[cfg(test)]
 mod connection_group_tests;
 
+#[cfg(test)]
+mod path_pinning_tests;
 #[cfg(test)]
 mod preconnection_tests;
 
@@ -56,3 +58,101 @@ mod integration_tests;
 
 #[cfg(test)]
 mod background_reading_tests;
+
+#[cfg(test)]
+mod racing_tests;
+#[cfg(test)]
+mod selection_tests;
+
+#[cfg(test)]
+mod resolver_tests;
+
+#[cfg(test)]
+mod runtime_tests;
+
+#[cfg(test)]
+mod fault_injection_tests;
+
+#[cfg(test)]
+mod shaping_tests;
+
+#[cfg(test)]
+mod state_machine_tests;
+
+#[cfg(test)]
+mod reassembly_tests;
+
+#[cfg(test)]
+mod context_tests;
+
+#[cfg(test)]
+mod proxy_tests;
+
+#[cfg(test)]
+mod framer_tests;
+
+#[cfg(test)]
+mod heartbeat_tests;
+
+#[cfg(test)]
+mod reconnect_tests;
+
+#[cfg(test)]
+mod trust_tests;
+
+#[cfg(all(test, feature = "tls"))]
+mod security_upgrade_tests;
+
+#[cfg(all(test, feature = "tls"))]
+mod listener_tls_tests;
+
+#[cfg(all(test, feature = "tls"))]
+mod p2p_security_tests;
+
+#[cfg(test)]
+mod hostname_tests;
+
+#[cfg(test)]
+mod udp_offload_tests;
+
+#[cfg(all(test, target_os = "linux", feature = "io-uring-writes"))]
+mod uring_tests;
+
+#[cfg(test)]
+mod memory_tests;
+
+#[cfg(all(test, feature = "cbor"))]
+mod cbor_tests;
+
+#[cfg(test)]
+mod capabilities_tests;
+
+#[cfg(test)]
+mod url_tests;
+
+#[cfg(test)]
+mod simple_tests;
+
+#[cfg(test)]
+mod shutdown_tests;
+
+#[cfg(all(test, target_os = "linux"))]
+mod fast_open_tests;
+
+#[cfg(test)]
+mod ledbat_tests;
+
+#[cfg(all(test, feature = "ffi"))]
+mod ffi_transport_properties_tests;
+
+#[cfg(all(test, feature = "ffi"))]
+mod ffi_message_context_tests;
+
+#[cfg(all(test, feature = "ffi"))]
+mod ffi_property_json_tests;
+
+#[cfg(all(test, feature = "ffi"))]
+mod ffi_preconnection_tests;
+
+#[cfg(all(test, feature = "ffi"))]
+mod ffi_connection_callbacks_tests;
@@ -0,0 +1,262 @@
+//! Tests for authenticating peers by pinned fingerprint or raw public key
+
+use crate::*;
+use tokio::time::Duration;
+
+// Self-signed identities, as peers without a certificate authority use
+const PEER_A_CERT: &[u8] = include_bytes!("certs/peer_a.der");
+const PEER_A_KEY: &[u8] = include_bytes!("certs/peer_a.key.der");
+const PEER_B_CERT: &[u8] = include_bytes!("certs/peer_b.der");
+const PEER_B_KEY: &[u8] = include_bytes!("certs/peer_b.key.der");
+
+// openssl x509 -in peer_a.pem -noout -fingerprint -sha256
+const PEER_A_FINGERPRINT: &str = "sha-256 C3:33:36:69:C1:2A:53:3C:96:8D:A8:2B:AA:93:D6:32:\
+                                  A1:37:F0:DD:21:43:95:54:35:C4:DA:76:CA:0E:9F:C9";
+
+fn peer(certificate: &[u8], key: &[u8]) -> ServerIdentity {
+    ServerIdentity::new(
+        vec![],
+        CertificateChain {
+            certificates: vec![Certificate {
+                data: certificate.to_vec(),
+            }],
+        },
+        key.to_vec(),
+    )
+}
+
+fn peer_a() -> ServerIdentity {
+    peer(PEER_A_CERT, PEER_A_KEY)
+}
+
+fn peer_b() -> ServerIdentity {
+    peer(PEER_B_CERT, PEER_B_KEY)
+}
+
+/// Peer A listening, accepting only peer B
+fn listening_side(raw_public_keys: bool) -> SecurityParameters {
+    let mut parameters = SecurityParameters::new();
+    parameters.raw_public_keys = raw_public_keys;
+    parameters.add_server_identity(peer_a());
+    let pin = match raw_public_keys {
+        true => PeerPin::PublicKey(peer_b().public_key().unwrap()),
+        false => PeerPin::from_fingerprint(&peer_b().fingerprint().unwrap()).unwrap(),
+    };
+    parameters.pin_peer(pin);
+    parameters
+}
+
+/// Peer B connecting, expecting peer A
+fn connecting_side(raw_public_keys: bool) -> SecurityParameters {
+    let mut parameters = SecurityParameters::new();
+    parameters.raw_public_keys = raw_public_keys;
+    parameters.set_client_identity(peer_b());
+    let pin = match raw_public_keys {
+        true => PeerPin::PublicKey(peer_a().public_key().unwrap()),
+        false => PeerPin::from_fingerprint(PEER_A_FINGERPRINT).unwrap(),
+    };
+    parameters.pin_peer(pin);
+    parameters
+}
+
+async fn listen(parameters: SecurityParameters) -> (Listener, std::net::SocketAddr) {
+    let preconn = Preconnection::new(
+        vec![LocalEndpoint {
+            identifiers: vec![
+                EndpointIdentifier::IpAddress("127.0.0.1".parse().unwrap()),
+                EndpointIdentifier::Port(0),
+            ],
+        }],
+        vec![],
+        TransportProperties::default(),
+        parameters,
+    );
+    let listener = preconn.listen().await.unwrap();
+    let addr = listener.local_addr().await.unwrap();
+    (listener, addr)
+}
+
+/// Connect in cleartext, then secure the connection with `parameters`
+async fn connect(addr: std::net::SocketAddr, parameters: SecurityParameters) -> Result<Connection> {
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await?;
+    conn.wait_for_established(Some(Duration::from_secs(2)))
+        .await?;
+    conn.start_security(parameters).await?;
+    Ok(conn)
+}
+
+async fn accept(listener: &Listener) -> Connection {
+    tokio::time::timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .expect("Should accept a connection")
+        .unwrap()
+}
+
+async fn received(conn: &Connection) -> Vec<u8> {
+    tokio::time::timeout(Duration::from_secs(2), async {
+        loop {
+            match conn.next_event().await {
+                Some(ConnectionEvent::Received { message_data, .. }) => break message_data,
+                Some(_) => continue,
+                None => panic!("Event stream ended"),
+            }
+        }
+    })
+    .await
+    .expect("Should receive data")
+}
+
+async fn next_handshake_failure(listener: &Listener) -> String {
+    tokio::time::timeout(Duration::from_secs(2), async {
+        loop {
+            match listener.next_event().await {
+                Some(ListenerEvent::EstablishmentError {
+                    class: AcceptErrorClass::HandshakeFailed,
+                    reason,
+                    ..
+                }) => break reason,
+                Some(ListenerEvent::ConnectionReceived(_)) => {
+                    panic!("Unpinned peer should not be delivered")
+                }
+                Some(_) => continue,
+                None => panic!("Listener event stream ended"),
+            }
+        }
+    })
+    .await
+    .expect("Should report the failed handshake")
+}
+
+async fn presented_by_client(conn: &Connection) -> Vec<u8> {
+    match conn.get_property("securityClientCertificate").await {
+        Some(ConnectionProperty::SecurityClientCertificate(Some(chain))) => {
+            chain.certificates[0].data.clone()
+        }
+        other => panic!("Expected the client's identity, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_peers_authenticate_by_certificate_fingerprint() {
+    let (listener, addr) = listen(listening_side(false)).await;
+    let client = connect(addr, connecting_side(false)).await.unwrap();
+    let server = accept(&listener).await;
+    assert_eq!(presented_by_client(&server).await, PEER_B_CERT);
+
+    client.send(Message::from_bytes(b"hello A")).await.unwrap();
+    assert_eq!(received(&server).await, b"hello A");
+    server.send(Message::from_bytes(b"hello B")).await.unwrap();
+    assert_eq!(received(&client).await, b"hello B");
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_peers_authenticate_by_raw_public_key() {
+    let (listener, addr) = listen(listening_side(true)).await;
+    let client = connect(addr, connecting_side(true)).await.unwrap();
+    let server = accept(&listener).await;
+
+    // The client presented its key rather than its certificate
+    assert_eq!(
+        presented_by_client(&server).await,
+        peer_b().public_key().unwrap()
+    );
+    client.send(Message::from_bytes(b"raw")).await.unwrap();
+    assert_eq!(received(&server).await, b"raw");
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_unpinned_server_is_refused() {
+    let (listener, addr) = listen(listening_side(false)).await;
+
+    // Pinning the client's own fingerprint instead of the server's
+    let mut parameters = connecting_side(false);
+    parameters.pinned_peers =
+        vec![PeerPin::from_fingerprint(&peer_b().fingerprint().unwrap()).unwrap()];
+    match connect(addr, parameters).await {
+        Err(TransportServicesError::SecurityError(error)) => {
+            assert!(error.contains("TLS handshake failed"), "{error}")
+        }
+        other => panic!("Expected a security error, got {other:?}"),
+    }
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_listener_refuses_unpinned_peers() {
+    let (listener, addr) = listen(listening_side(false)).await;
+
+    // Peer A's identity is not pinned by the Listener
+    let mut parameters = connecting_side(false);
+    parameters.set_client_identity(peer_a());
+    let _ = connect(addr, parameters).await;
+    next_handshake_failure(&listener).await;
+
+    // Nor is a peer presenting nothing
+    let mut parameters = connecting_side(false);
+    parameters.client_identity = None;
+    let _ = connect(addr, parameters).await;
+    next_handshake_failure(&listener).await;
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_pins_must_match_identity_mode() {
+    let (listener, addr) = listen(listening_side(true)).await;
+
+    // A certificate fingerprint cannot pin a raw public key
+    let mut parameters = connecting_side(true);
+    parameters.pinned_peers = vec![PeerPin::from_fingerprint(PEER_A_FINGERPRINT).unwrap()];
+    assert!(matches!(
+        connect(addr, parameters).await,
+        Err(TransportServicesError::InvalidParameters(_))
+    ));
+    listener.stop().await.unwrap();
+
+    let mut parameters = listening_side(false);
+    parameters.pin_peer(PeerPin::PublicKey(peer_b().public_key().unwrap()));
+    let preconn = Preconnection::new(
+        vec![LocalEndpoint {
+            identifiers: vec![EndpointIdentifier::Port(0)],
+        }],
+        vec![],
+        TransportProperties::default(),
+        parameters,
+    );
+    assert!(matches!(
+        preconn.listen().await,
+        Err(TransportServicesError::InvalidParameters(_))
+    ));
+}
+
+#[test]
+fn test_peer_fingerprints() {
+    assert_eq!(peer_a().fingerprint().unwrap(), PEER_A_FINGERPRINT);
+    assert_eq!(
+        PeerPin::from_fingerprint(&PEER_A_FINGERPRINT.to_lowercase()).unwrap(),
+        PeerPin::from_fingerprint(PEER_A_FINGERPRINT).unwrap()
+    );
+
+    for invalid in ["", "sha-256", "sha-256 C3:33", "sha-256 XX:33", "C3:33:36"] {
+        assert!(
+            matches!(
+                PeerPin::from_fingerprint(invalid),
+                Err(TransportServicesError::InvalidParameters(_))
+            ),
+            "{invalid}"
+        );
+    }
+    assert!(matches!(
+        PeerPin::from_fingerprint(
+            "sha-1 C3:33:36:69:C1:2A:53:3C:96:8D:A8:2B:AA:93:D6:32:A1:37:F0:DD"
+        ),
+        Err(TransportServicesError::NotSupported(_))
+    ));
+}
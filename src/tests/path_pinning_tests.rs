@@ -0,0 +1,166 @@
+//! Tests for pinning a connection to its path
+
+use crate::*;
+use std::time::Duration;
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::time::timeout;
+
+fn pinned(properties: &ConnectionProperties) -> Option<bool> {
+    match properties.get("pathPinning") {
+        Some(ConnectionProperty::PathPinning(pinned)) => Some(*pinned),
+        _ => None,
+    }
+}
+
+#[tokio::test]
+async fn test_path_pinning_property() {
+    let properties = TransportProperties::builder().path_pinning(true).build();
+    assert_eq!(properties.connection_properties.path_pinning, Some(true));
+    assert_eq!(
+        TransportProperties::default()
+            .connection_properties
+            .path_pinning,
+        None
+    );
+
+    // The connection starts with the Preconnection's setting, unpinned by default
+    let conn = Preconnection::new(
+        vec![],
+        vec![],
+        properties,
+        SecurityParameters::new_disabled(),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    conn.add_remote(
+        RemoteEndpoint::builder()
+            .socket_address(listener.local_addr().unwrap())
+            .build(),
+    )
+    .await
+    .unwrap();
+    let conn = conn.initiate().await.unwrap();
+    assert_eq!(pinned(&conn.get_properties().await), Some(true));
+    assert_eq!(pinned(&ConnectionProperties::new()), Some(false));
+
+    conn.set_property("pathPinning", ConnectionProperty::PathPinning(false))
+        .await
+        .unwrap();
+    assert_eq!(pinned(&conn.get_properties().await), Some(false));
+}
+
+#[tokio::test]
+async fn test_path_pinning_conflicts_with_multipath() {
+    let properties = TransportProperties::builder()
+        .multipath(MultipathConfig::Active)
+        .path_pinning(true)
+        .build();
+    let conflicts = properties.conflicts();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(
+        conflicts[0].properties,
+        vec![TransportProperty::Multipath, TransportProperty::PathPinning]
+    );
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .socket_address("127.0.0.1:9".parse().unwrap())
+            .build()],
+        properties,
+        SecurityParameters::new_disabled(),
+    );
+    assert!(matches!(
+        preconn.initiate().await,
+        Err(TransportServicesError::InvalidParameters(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_pinned_connection_keeps_a_present_path() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let _peer = listener.accept().await;
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::builder().path_pinning(true).build(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.unwrap();
+    conn.wait_for_established(Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+
+    // The loopback address stays assigned, so the watch leaves the connection be
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    assert_eq!(conn.state().await, ConnectionState::Established);
+}
+
+#[tokio::test]
+async fn test_pinned_connections_share_one_address_watch() {
+    let first = crate::path_monitor::watch_addresses();
+    let mut second = crate::path_monitor::watch_addresses();
+    assert!(first.same_channel(&second));
+
+    // The shared watch lists the loopback address once interfaces are read
+    let addresses = timeout(
+        Duration::from_secs(5),
+        second.wait_for(|addresses| addresses.is_some()),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert!(addresses
+        .as_ref()
+        .unwrap()
+        .contains(&"127.0.0.1".parse().unwrap()));
+}
+
+#[tokio::test]
+async fn test_pinned_datagram_flow_aborts_when_the_peer_moves() {
+    let preconn = Preconnection::new(
+        vec![LocalEndpoint::builder()
+            .ip_address("127.0.0.1".parse().unwrap())
+            .port(0)
+            .build()],
+        vec![],
+        TransportProperties::builder()
+            .reliability(Preference::Prohibit)
+            .build(),
+        SecurityParameters::new_disabled(),
+    );
+    let listener = preconn.listen().await.unwrap();
+    listener.set_peer_demultiplexing(false);
+    let addr = listener.local_addr().await.unwrap();
+
+    let before = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    before.send_to(b"first", addr).await.unwrap();
+    let conn = timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+    conn.set_property("pathPinning", ConnectionProperty::PathPinning(true))
+        .await
+        .unwrap();
+
+    // A NAT rebinding would migrate an unpinned flow; this one is aborted
+    let after = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    after.send_to(b"second", addr).await.unwrap();
+    loop {
+        match timeout(Duration::from_secs(2), conn.next_event()).await {
+            Ok(Some(ConnectionEvent::ConnectionError(_))) => break,
+            Ok(Some(ConnectionEvent::Received { message_data, .. })) => {
+                assert_eq!(message_data, b"first")
+            }
+            Ok(Some(ConnectionEvent::Ready)) => {}
+            other => panic!("Expected ConnectionError, got {other:?}"),
+        }
+    }
+    assert_eq!(conn.state().await, ConnectionState::Closed);
+
+    listener.stop().await.unwrap();
+}
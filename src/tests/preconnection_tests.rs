@@ -71,7 +71,8 @@ async fn test_preconnection_builder_pattern() {
     // Add local endpoint
     preconn
         .add_local(LocalEndpoint::builder().interface("en0").build())
-        .await;
+        .await
+        .unwrap();
 
     // Set transport properties
     let props = TransportProperties::builder()
@@ -80,7 +81,7 @@ async fn test_preconnection_builder_pattern() {
         .congestion_control(Preference::Require)
         .build();
 
-    preconn.set_transport_properties(props).await;
+    preconn.set_transport_properties(props).await.unwrap();
 
     // Verify we can resolve endpoints
     let (locals, remotes) = preconn.resolve().await.unwrap();
@@ -133,3 +134,377 @@ async fn test_security_parameters() {
     );
     assert!(preconn.resolve().await.is_ok());
 }
+
+#[tokio::test]
+async fn test_listen_unavailable_protocol_not_supported() {
+    let preconn = Preconnection::new(
+        vec![LocalEndpoint::builder()
+            .ip_address("127.0.0.1".parse().unwrap())
+            .port(0)
+            .build()],
+        vec![RemoteEndpoint::builder()
+            .hostname("example.com")
+            .port(443)
+            .protocol(Protocol::DTLS)
+            .build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+
+    match preconn.listen().await {
+        Err(TransportServicesError::NotSupported(msg)) => assert!(msg.contains("DTLS")),
+        Err(e) => panic!("Expected NotSupported error, got {e:?}"),
+        Ok(_) => panic!("Listening for DTLS should fail"),
+    }
+}
+
+#[tokio::test]
+async fn test_remote_aliases() {
+    let primary = RemoteEndpoint::builder()
+        .hostname("service.test")
+        .port(443)
+        .build();
+    let alias = RemoteEndpoint::builder()
+        .ip_address("192.0.2.1".parse().unwrap())
+        .port(443)
+        .build();
+    let alternative = RemoteEndpoint::builder()
+        .ip_address("192.0.2.2".parse().unwrap())
+        .port(443)
+        .build();
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![alternative.clone()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    preconn
+        .add_remote_associated(vec![primary.clone(), alias.clone()])
+        .await
+        .unwrap();
+
+    assert_eq!(preconn.remote_aliases(&alias).await, vec![primary.clone()]);
+    assert_eq!(preconn.remote_aliases(&primary).await, vec![alias]);
+    assert!(preconn.remote_aliases(&alternative).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_aliases_share_candidates() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            std::mem::forget(stream);
+        }
+    });
+    let by_address = RemoteEndpoint::builder().socket_address(addr).build();
+    let by_parts = RemoteEndpoint::builder()
+        .ip_address(addr.ip())
+        .port(addr.port())
+        .build();
+
+    // Alternatives reaching the same address are each attempted, aliases once
+    for aliased in [false, true] {
+        let seen = std::sync::Arc::new(AtomicUsize::new(0));
+        let policy_seen = seen.clone();
+        let context = TransportServices::new().with_candidate_policy(move |candidates| {
+            policy_seen.store(candidates.len(), Ordering::SeqCst);
+            candidates.clone()
+        });
+        let preconn = Preconnection::new(
+            vec![],
+            vec![],
+            TransportProperties::default(),
+            SecurityParameters::new_disabled(),
+        );
+        preconn
+            .set_context(std::sync::Arc::new(context))
+            .await
+            .unwrap();
+        if aliased {
+            preconn
+                .add_remote_associated(vec![by_address.clone(), by_parts.clone()])
+                .await
+                .unwrap();
+        } else {
+            preconn.add_remote(by_address.clone()).await.unwrap();
+            preconn.add_remote(by_parts.clone()).await.unwrap();
+        }
+
+        let conn = preconn.initiate().await.unwrap();
+        conn.wait_for_established(Some(std::time::Duration::from_secs(2)))
+            .await
+            .unwrap();
+        assert_eq!(seen.load(Ordering::SeqCst), if aliased { 1 } else { 2 });
+    }
+}
+
+#[tokio::test]
+async fn test_freeze_snapshots_configuration() {
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .ip_address("192.0.2.1".parse().unwrap())
+            .port(443)
+            .build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let snapshot = preconn.freeze().await;
+    assert!(snapshot.is_frozen().await);
+    assert!(!preconn.is_frozen().await);
+
+    // Changes to the original do not reach the snapshot
+    preconn
+        .add_remote(
+            RemoteEndpoint::builder()
+                .ip_address("192.0.2.2".parse().unwrap())
+                .port(443)
+                .build(),
+        )
+        .await
+        .unwrap();
+    preconn
+        .set_proxy(Some(ProxyConfig::socks5("127.0.0.1", 1080)))
+        .await
+        .unwrap();
+    let (_, remotes) = snapshot.resolve().await.unwrap();
+    assert_eq!(remotes.len(), 1);
+    let (_, remotes) = preconn.resolve().await.unwrap();
+    assert_eq!(remotes.len(), 2);
+    assert!(snapshot.proxy().await.is_none());
+
+    // and the snapshot itself refuses changes
+    assert!(matches!(
+        snapshot
+            .set_proxy(Some(ProxyConfig::socks5("127.0.0.1", 1080)))
+            .await,
+        Err(TransportServicesError::InvalidState(_))
+    ));
+    assert!(snapshot.proxy().await.is_none());
+    assert!(snapshot.freeze().await.is_frozen().await);
+}
+
+#[tokio::test]
+async fn test_frozen_preconnection_refuses_every_change() {
+    let snapshot = Preconnection::new(
+        vec![],
+        vec![],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    )
+    .freeze()
+    .await;
+    let remote = RemoteEndpoint::builder()
+        .ip_address("192.0.2.1".parse().unwrap())
+        .port(443)
+        .build();
+
+    let results = [
+        snapshot.add_local(LocalEndpoint::default()).await,
+        snapshot.add_remote(remote.clone()).await,
+        snapshot.add_remote_associated(vec![remote]).await,
+        snapshot
+            .set_transport_properties(TransportProperties::default())
+            .await,
+        snapshot
+            .set_security_parameters(SecurityParameters::new_disabled())
+            .await,
+        snapshot
+            .add_framer(|| Box::new(crate::LengthPrefixFramer::new()) as Box<dyn crate::Framer>)
+            .await,
+        snapshot.use_global_resolution_cache().await,
+        snapshot
+            .set_resolver_config(crate::ResolverConfig::default())
+            .await,
+        snapshot
+            .set_context(std::sync::Arc::new(TransportServices::new()))
+            .await,
+        snapshot.set_proxy(None).await,
+    ];
+    for result in results {
+        assert!(
+            matches!(result, Err(TransportServicesError::InvalidState(_))),
+            "{result:?}"
+        );
+    }
+    let (locals, remotes) = snapshot.resolve().await.unwrap();
+    assert!(locals.is_empty() && remotes.is_empty());
+    assert!(snapshot.context().await.is_none());
+}
+
+#[tokio::test]
+async fn test_clone_config_is_independent() {
+    let preconn = Preconnection::new(
+        vec![],
+        vec![],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let variant = preconn.clone_config().await;
+    variant
+        .set_proxy(Some(ProxyConfig::socks5("127.0.0.1", 1080)))
+        .await
+        .unwrap();
+    assert!(preconn.proxy().await.is_none());
+    assert!(variant.proxy().await.is_some());
+
+    // A plain clone shares the configuration
+    let shared = preconn.clone();
+    shared
+        .set_proxy(Some(ProxyConfig::socks5("127.0.0.1", 1080)))
+        .await
+        .unwrap();
+    assert!(preconn.proxy().await.is_some());
+
+    // A copy of a snapshot can be changed again
+    let copy = preconn.freeze().await.clone_config().await;
+    assert!(!copy.is_frozen().await);
+    copy.set_proxy(None).await.unwrap();
+    assert!(copy.proxy().await.is_none());
+}
+
+#[tokio::test]
+async fn test_listen_uses_snapshot() {
+    let preconn = Preconnection::new(
+        vec![LocalEndpoint::builder()
+            .ip_address("127.0.0.1".parse().unwrap())
+            .port(0)
+            .build()],
+        vec![],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let listener = preconn.listen().await.unwrap();
+
+    let mut properties = TransportProperties::default();
+    properties.connection_properties.connection_priority = Some(7);
+    preconn.set_transport_properties(properties).await.unwrap();
+
+    let used = listener.preconnection().await;
+    assert!(used.is_frozen().await);
+    assert_ne!(
+        used.transport_properties()
+            .await
+            .connection_properties
+            .connection_priority,
+        Some(7)
+    );
+    listener.stop().await.unwrap();
+}
+
+#[test]
+fn test_property_conflicts_are_listed() {
+    assert!(TransportProperties::default().conflicts().is_empty());
+
+    let properties = TransportProperties::builder()
+        .reliability(Preference::Prohibit)
+        .per_msg_reliability(Preference::Require)
+        .direction(CommunicationDirection::UnidirectionalSend)
+        .active_read_before_send(Preference::Require)
+        .interface("eth0", Preference::Require)
+        .interface("eth0", Preference::Prohibit)
+        .build();
+    let conflicts = properties.conflicts();
+    assert_eq!(conflicts.len(), 3, "{conflicts:?}");
+    assert_eq!(
+        conflicts[0].properties,
+        vec![
+            TransportProperty::Reliability,
+            TransportProperty::PerMsgReliability
+        ]
+    );
+    assert_eq!(
+        conflicts[1].properties,
+        vec![
+            TransportProperty::Direction,
+            TransportProperty::ActiveReadBeforeSend
+        ]
+    );
+    assert_eq!(conflicts[2].properties, vec![TransportProperty::Interface]);
+    assert!(conflicts[2].reason.contains("eth0"));
+
+    // Reliable transports cannot skip checksums
+    let properties = TransportProperties::builder()
+        .full_checksum_recv(Preference::Prohibit)
+        .build();
+    assert_eq!(
+        properties.conflicts()[0].properties,
+        vec![
+            TransportProperty::Reliability,
+            TransportProperty::FullChecksumRecv
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_conflicting_properties_fail_before_establishment() {
+    let remote = RemoteEndpoint::builder()
+        .socket_address("127.0.0.1:9".parse().unwrap())
+        .build();
+    let local = LocalEndpoint::builder()
+        .ip_address("127.0.0.1".parse().unwrap())
+        .port(0)
+        .build();
+    let preconn = Preconnection::new(
+        vec![local],
+        vec![remote],
+        TransportProperties::builder()
+            .reliability(Preference::Prohibit)
+            .per_msg_reliability(Preference::Require)
+            .build(),
+        SecurityParameters::new_disabled(),
+    );
+    assert_eq!(preconn.property_conflicts().await.len(), 1);
+
+    for result in [
+        preconn.initiate().await.map(|_| ()),
+        preconn.listen().await.map(|_| ()),
+        preconn.rendezvous().await.map(|_| ()),
+    ] {
+        match result {
+            Err(TransportServicesError::InvalidParameters(error)) => {
+                assert!(error.contains("PerMsgReliability"), "{error}")
+            }
+            other => panic!("Expected InvalidParameters, got {other:?}"),
+        }
+    }
+
+    // Resolving the conflict lets the Preconnection be used
+    preconn
+        .set_transport_properties(
+            TransportProperties::builder()
+                .reliability(Preference::Prohibit)
+                .build(),
+        )
+        .await
+        .unwrap();
+    assert!(preconn.property_conflicts().await.is_empty());
+    preconn.listen().await.unwrap().stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_initiate_with_send_on_receive_only_connection_fails() {
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .socket_address("127.0.0.1:9".parse().unwrap())
+            .build()],
+        TransportProperties::builder()
+            .direction(CommunicationDirection::UnidirectionalReceive)
+            .build(),
+        SecurityParameters::new_disabled(),
+    );
+    match preconn
+        .initiate_with_send(Message::from_string("hello"))
+        .await
+    {
+        Err(TransportServicesError::InvalidParameters(error)) => {
+            assert!(error.contains("receive-only"), "{error}")
+        }
+        other => panic!("Expected InvalidParameters, got {:?}", other.map(|_| ())),
+    }
+}
@@ -269,3 +269,142 @@ async fn test_connection_timeout_setting() {
         panic!("Connection timeout property not found");
     }
 }
+
+/// Establish a connection and a clone of it in the same group
+async fn create_group_pair() -> (Connection, Connection) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        for _ in 0..2 {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                drop(stream);
+            });
+        }
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn1 = preconn.initiate().await.expect("Should connect");
+    conn1
+        .wait_for_established(Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+    let conn2 = conn1.clone_connection().await.expect("Should clone");
+    conn2
+        .wait_for_established(Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+    (conn1, conn2)
+}
+
+#[tokio::test]
+async fn test_group_keepalive_applied_to_member_sockets() {
+    let (conn1, conn2) = create_group_pair().await;
+
+    conn1
+        .set_property(
+            "keepAliveTimeout",
+            ConnectionProperty::KeepAliveTimeout(TimeoutValue::Duration(Duration::from_secs(42))),
+        )
+        .await
+        .expect("Should set keep-alive");
+
+    for conn in [&conn1, &conn2] {
+        let keepalive = conn
+            .inspect_tcp_socket(|socket| socket.keepalive().unwrap())
+            .await
+            .expect("Should have a TCP stream");
+        assert!(keepalive);
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            let time = conn
+                .inspect_tcp_socket(|socket| socket.keepalive_time().unwrap())
+                .await
+                .unwrap();
+            assert_eq!(time, Duration::from_secs(42));
+        }
+    }
+
+    conn2
+        .set_property(
+            "keepAliveTimeout",
+            ConnectionProperty::KeepAliveTimeout(TimeoutValue::Disabled),
+        )
+        .await
+        .expect("Should disable keep-alive");
+
+    for conn in [&conn1, &conn2] {
+        let keepalive = conn
+            .inspect_tcp_socket(|socket| socket.keepalive().unwrap())
+            .await
+            .unwrap();
+        assert!(!keepalive);
+    }
+}
+
+#[tokio::test]
+async fn test_group_capacity_profile_applied_to_member_sockets() {
+    let (conn1, conn2) = create_group_pair().await;
+
+    conn2
+        .set_property(
+            "connCapacityProfile",
+            ConnectionProperty::ConnCapacityProfile(CapacityProfile::LowLatencyInteractive),
+        )
+        .await
+        .expect("Should set capacity profile");
+
+    // AF41 on both sockets, not just in both property maps
+    for conn in [&conn1, &conn2] {
+        let tos = conn
+            .inspect_tcp_socket(|socket| socket.tos().unwrap())
+            .await
+            .expect("Should have a TCP stream");
+        assert_eq!(tos, 34 << 2);
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_group_user_timeout_applied_to_member_sockets() {
+    let (conn1, conn2) = create_group_pair().await;
+
+    conn1
+        .set_property(
+            "tcp.userTimeoutValue",
+            ConnectionProperty::TcpUserTimeoutValue(Some(Duration::from_secs(20))),
+        )
+        .await
+        .unwrap();
+
+    // The value only takes effect once the user timeout is enabled
+    let timeout = conn2
+        .inspect_tcp_socket(|socket| socket.tcp_user_timeout().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(timeout, None);
+
+    conn1
+        .set_property(
+            "tcp.userTimeoutEnabled",
+            ConnectionProperty::TcpUserTimeoutEnabled(true),
+        )
+        .await
+        .unwrap();
+
+    for conn in [&conn1, &conn2] {
+        let timeout = conn
+            .inspect_tcp_socket(|socket| socket.tcp_user_timeout().unwrap())
+            .await
+            .expect("Should have a TCP stream");
+        assert_eq!(timeout, Some(Duration::from_secs(20)));
+    }
+}
@@ -0,0 +1,301 @@
+//! Tests for establishing connections through SOCKS5 and HTTP CONNECT proxies
+
+use crate::*;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+
+/// Server that answers every read with the same bytes
+async fn echo_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buf = [0u8; 1024];
+                while let Ok(n) = stream.read(&mut buf).await {
+                    if n == 0 || stream.write_all(&buf[..n]).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    addr
+}
+
+/// SOCKS5 proxy accepting one connection, reporting the requested target
+async fn socks5_proxy(
+    credentials: Option<(&'static str, &'static str)>,
+) -> (SocketAddr, oneshot::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (target_sender, target_receiver) = oneshot::channel();
+    tokio::spawn(async move {
+        let (mut client, _) = listener.accept().await.unwrap();
+
+        let mut header = [0u8; 2];
+        client.read_exact(&mut header).await.unwrap();
+        let mut methods = vec![0u8; header[1] as usize];
+        client.read_exact(&mut methods).await.unwrap();
+        match credentials {
+            Some((username, password)) => {
+                assert!(methods.contains(&2));
+                client.write_all(&[5, 2]).await.unwrap();
+                let mut version_len = [0u8; 2];
+                client.read_exact(&mut version_len).await.unwrap();
+                let mut user = vec![0u8; version_len[1] as usize];
+                client.read_exact(&mut user).await.unwrap();
+                let mut len = [0u8; 1];
+                client.read_exact(&mut len).await.unwrap();
+                let mut pass = vec![0u8; len[0] as usize];
+                client.read_exact(&mut pass).await.unwrap();
+                let ok = user == username.as_bytes() && pass == password.as_bytes();
+                client
+                    .write_all(&[1, if ok { 0 } else { 1 }])
+                    .await
+                    .unwrap();
+                if !ok {
+                    return;
+                }
+            }
+            None => client.write_all(&[5, 0]).await.unwrap(),
+        }
+
+        let mut request = [0u8; 4];
+        client.read_exact(&mut request).await.unwrap();
+        let host = match request[3] {
+            1 => {
+                let mut ip = [0u8; 4];
+                client.read_exact(&mut ip).await.unwrap();
+                std::net::Ipv4Addr::from(ip).to_string()
+            }
+            3 => {
+                let mut len = [0u8; 1];
+                client.read_exact(&mut len).await.unwrap();
+                let mut name = vec![0u8; len[0] as usize];
+                client.read_exact(&mut name).await.unwrap();
+                String::from_utf8(name).unwrap()
+            }
+            other => panic!("Unexpected address type {other}"),
+        };
+        let mut port = [0u8; 2];
+        client.read_exact(&mut port).await.unwrap();
+        let target = format!("{host}:{}", u16::from_be_bytes(port));
+        let _ = target_sender.send(target.clone());
+
+        match TcpStream::connect(&target).await {
+            Ok(mut upstream) => {
+                client
+                    .write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0, 0])
+                    .await
+                    .unwrap();
+                let _ = tokio::io::copy_bidirectional(&mut client, &mut upstream).await;
+            }
+            Err(_) => {
+                client
+                    .write_all(&[5, 5, 0, 1, 0, 0, 0, 0, 0, 0])
+                    .await
+                    .unwrap();
+            }
+        }
+    });
+    (addr, target_receiver)
+}
+
+/// HTTP CONNECT proxy accepting one connection, reporting the request header
+async fn http_proxy() -> (SocketAddr, oneshot::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (request_sender, request_receiver) = oneshot::channel();
+    tokio::spawn(async move {
+        let (mut client, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut byte = [0u8; 1];
+        while !request.ends_with(b"\r\n\r\n") {
+            client.read_exact(&mut byte).await.unwrap();
+            request.push(byte[0]);
+        }
+        let request = String::from_utf8(request).unwrap();
+        let target = request.split_whitespace().nth(1).unwrap().to_string();
+        let _ = request_sender.send(request);
+
+        let mut upstream = TcpStream::connect(&target).await.unwrap();
+        client
+            .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+            .await
+            .unwrap();
+        let _ = tokio::io::copy_bidirectional(&mut client, &mut upstream).await;
+    });
+    (addr, request_receiver)
+}
+
+async fn connect_through(proxy: ProxyConfig, remote: RemoteEndpoint) -> Result<Connection> {
+    let preconn = Preconnection::new(
+        vec![],
+        vec![remote],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    preconn.set_proxy(Some(proxy)).await.unwrap();
+    let conn = preconn.initiate().await?;
+    conn.wait_for_established(Some(Duration::from_secs(2)))
+        .await?;
+    Ok(conn)
+}
+
+async fn assert_echo(conn: &Connection) {
+    conn.send(Message::from_string("through the tunnel"))
+        .await
+        .unwrap();
+    let (message, _) = tokio::time::timeout(Duration::from_secs(2), conn.receive())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(message.data(), b"through the tunnel");
+}
+
+#[tokio::test]
+async fn test_socks5_proxy_resolves_hostnames() {
+    let target = echo_server().await;
+    let (proxy, requested) = socks5_proxy(None).await;
+
+    let remote = RemoteEndpoint::builder()
+        .hostname("localhost")
+        .port(target.port())
+        .build();
+    let conn = connect_through(ProxyConfig::socks5("127.0.0.1", proxy.port()), remote)
+        .await
+        .unwrap();
+
+    // The hostname is passed to the proxy rather than resolved locally
+    assert_eq!(
+        requested.await.unwrap(),
+        format!("localhost:{}", target.port())
+    );
+    assert_echo(&conn).await;
+}
+
+#[tokio::test]
+async fn test_socks5_proxy_with_credentials() {
+    let target = echo_server().await;
+    let (proxy, requested) = socks5_proxy(Some(("user", "secret"))).await;
+
+    let remote = RemoteEndpoint::builder().socket_address(target).build();
+    let config = ProxyConfig::socks5("127.0.0.1", proxy.port()).with_credentials("user", "secret");
+    let conn = connect_through(config, remote).await.unwrap();
+
+    assert_eq!(requested.await.unwrap(), target.to_string());
+    assert_echo(&conn).await;
+}
+
+#[tokio::test]
+async fn test_socks5_proxy_failure_is_establishment_error() {
+    let closed = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let (proxy, _requested) = socks5_proxy(None).await;
+
+    let remote = RemoteEndpoint::builder().socket_address(closed).build();
+    let preconn = Preconnection::new(
+        vec![],
+        vec![remote],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    preconn
+        .set_proxy(Some(ProxyConfig::socks5("127.0.0.1", proxy.port())))
+        .await
+        .unwrap();
+    let conn = preconn.initiate().await.unwrap();
+
+    match tokio::time::timeout(Duration::from_secs(2), conn.next_event()).await {
+        Ok(Some(ConnectionEvent::EstablishmentError(error))) => {
+            assert!(error.contains("connection refused"), "{error}");
+        }
+        other => panic!("Expected EstablishmentError, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_http_connect_proxy() {
+    let target = echo_server().await;
+    let (proxy, request) = http_proxy().await;
+
+    let remote = RemoteEndpoint::builder().socket_address(target).build();
+    let config =
+        ProxyConfig::http_connect("127.0.0.1", proxy.port()).with_credentials("user", "pass");
+    let conn = connect_through(config, remote).await.unwrap();
+
+    let request = request.await.unwrap();
+    assert!(request.starts_with(&format!("CONNECT {target} HTTP/1.1\r\n")));
+    assert!(request.contains("Proxy-Authorization: Basic dXNlcjpwYXNz\r\n"));
+    assert_echo(&conn).await;
+    assert_eq!(
+        conn.protocol_stack().await.layers,
+        vec![
+            StackLayer::Proxy(ProxyKind::HttpConnect),
+            StackLayer::Tcp,
+            StackLayer::Ipv4
+        ]
+    );
+
+    // The remote endpoint is the target, not the proxy
+    assert_eq!(
+        conn.remote_endpoint().await.unwrap().identifiers,
+        vec![EndpointIdentifier::SocketAddress(target)]
+    );
+}
+
+#[tokio::test]
+async fn test_bypassed_hosts_connect_directly() {
+    let target = echo_server().await;
+
+    // Nothing listens on the proxy port, so only a direct connection succeeds
+    let unused = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let config = ProxyConfig::socks5("127.0.0.1", unused.port()).with_bypass(["127.0.0.1"]);
+    let remote = RemoteEndpoint::builder().socket_address(target).build();
+    let conn = connect_through(config, remote).await.unwrap();
+    assert_echo(&conn).await;
+}
+
+#[test]
+fn test_proxy_url_parsing() {
+    let config = ProxyConfig::from_url("socks5h://alice:pw@proxy.example:1081").unwrap();
+    assert_eq!(config.kind, ProxyKind::Socks5);
+    assert_eq!(config.host, "proxy.example");
+    assert_eq!(config.port, 1081);
+    assert_eq!(
+        config.credentials,
+        Some(("alice".to_string(), "pw".to_string()))
+    );
+
+    let config = ProxyConfig::from_url("http://[::1]/").unwrap();
+    assert_eq!(config.kind, ProxyKind::HttpConnect);
+    assert_eq!(config.host, "::1");
+    assert_eq!(config.port, 80);
+
+    assert!(ProxyConfig::from_url("ftp://proxy").is_err());
+    assert!(ProxyConfig::from_url("proxy:8080").is_err());
+}
+
+#[test]
+fn test_proxy_bypass_matching() {
+    let config = ProxyConfig::socks5("proxy", 1080).with_bypass(["example.com", ".internal"]);
+    assert!(config.bypasses("example.com"));
+    assert!(config.bypasses("www.example.com"));
+    assert!(config.bypasses("db.internal"));
+    assert!(!config.bypasses("notexample.com"));
+    assert!(!config.bypasses("internal.example.org"));
+    assert!(ProxyConfig::socks5("proxy", 1080)
+        .with_bypass(["*"])
+        .bypasses("anything"));
+}
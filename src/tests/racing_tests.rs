@@ -0,0 +1,408 @@
+//! Tests for Happy Eyeballs candidate racing (RFC 8305)
+
+use crate::racing::sort_candidates;
+use crate::*;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+fn addr(s: &str) -> SocketAddr {
+    s.parse().unwrap()
+}
+
+#[test]
+fn test_happy_eyeballs_defaults() {
+    let config = HappyEyeballsConfig::default();
+    assert_eq!(config.connection_attempt_delay, Duration::from_millis(250));
+    assert_eq!(
+        config.address_family_preference,
+        AddressFamilyPreference::PreferIpv6
+    );
+    assert_eq!(config.candidate_timeout, None);
+    assert!(config.max_parallel_attempts > 1);
+}
+
+#[test]
+fn test_happy_eyeballs_builder() {
+    let props = TransportProperties::builder()
+        .connection_attempt_delay(Duration::from_millis(100))
+        .address_family_preference(AddressFamilyPreference::PreferIpv4)
+        .candidate_timeout(Duration::from_secs(2))
+        .max_parallel_attempts(2)
+        .build();
+
+    let config = props.connection_properties.happy_eyeballs;
+    assert_eq!(config.connection_attempt_delay, Duration::from_millis(100));
+    assert_eq!(
+        config.address_family_preference,
+        AddressFamilyPreference::PreferIpv4
+    );
+    assert_eq!(config.candidate_timeout, Some(Duration::from_secs(2)));
+    assert_eq!(config.max_parallel_attempts, 2);
+}
+
+#[test]
+fn test_sort_candidates_interleaves_families() {
+    let addrs = vec![
+        addr("192.0.2.1:80"),
+        addr("192.0.2.2:80"),
+        addr("192.0.2.3:80"),
+        addr("[2001:db8::1]:80"),
+        addr("[2001:db8::2]:80"),
+    ];
+
+    assert_eq!(
+        sort_candidates(addrs.clone(), AddressFamilyPreference::PreferIpv6),
+        vec![
+            addr("[2001:db8::1]:80"),
+            addr("192.0.2.1:80"),
+            addr("[2001:db8::2]:80"),
+            addr("192.0.2.2:80"),
+            addr("192.0.2.3:80"),
+        ]
+    );
+    assert_eq!(
+        sort_candidates(addrs.clone(), AddressFamilyPreference::PreferIpv4),
+        vec![
+            addr("192.0.2.1:80"),
+            addr("[2001:db8::1]:80"),
+            addr("192.0.2.2:80"),
+            addr("[2001:db8::2]:80"),
+            addr("192.0.2.3:80"),
+        ]
+    );
+    assert_eq!(
+        sort_candidates(addrs.clone(), AddressFamilyPreference::Ipv4Only).len(),
+        3
+    );
+    assert!(sort_candidates(addrs, AddressFamilyPreference::Ipv6Only)
+        .iter()
+        .all(|a| a.is_ipv6()));
+}
+
+#[tokio::test]
+async fn test_racing_falls_back_to_next_candidate() {
+    // A port with nothing listening refuses the first attempt
+    let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let closed_addr = closed.local_addr().unwrap();
+    drop(closed);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let open_addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let _ = listener.accept().await;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![
+            RemoteEndpoint::builder()
+                .socket_address(closed_addr)
+                .build(),
+            RemoteEndpoint::builder().socket_address(open_addr).build(),
+        ],
+        TransportProperties::builder()
+            .connection_attempt_delay(Duration::from_secs(2))
+            .max_parallel_attempts(1)
+            .build(),
+        SecurityParameters::new_disabled(),
+    );
+
+    let conn = preconn.initiate().await.unwrap();
+
+    // A failed attempt starts the next one without waiting for the attempt delay
+    let event = tokio::time::timeout(Duration::from_secs(1), conn.next_event())
+        .await
+        .expect("Should not wait for the attempt delay");
+    assert!(matches!(event, Some(ConnectionEvent::Ready)));
+    assert_eq!(
+        conn.remote_endpoint().await.unwrap().identifiers,
+        vec![EndpointIdentifier::SocketAddress(open_addr)]
+    );
+}
+
+#[tokio::test]
+async fn test_racing_rejects_filtered_candidates() {
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .socket_address(addr("127.0.0.1:80"))
+            .build()],
+        TransportProperties::builder()
+            .address_family_preference(AddressFamilyPreference::Ipv6Only)
+            .build(),
+        SecurityParameters::new_disabled(),
+    );
+
+    assert!(matches!(
+        preconn.initiate().await,
+        Err(TransportServicesError::InvalidParameters(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_pending_messages_delivered_once_in_order() {
+    let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let closed_addr = closed.local_addr().unwrap();
+    drop(closed);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let open_addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut received = Vec::new();
+        let _ = tokio::io::AsyncReadExt::read_to_end(&mut stream, &mut received).await;
+        received
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![
+            RemoteEndpoint::builder()
+                .socket_address(closed_addr)
+                .build(),
+            RemoteEndpoint::builder().socket_address(open_addr).build(),
+        ],
+        TransportProperties::builder()
+            .max_parallel_attempts(1)
+            .build(),
+        SecurityParameters::new_disabled(),
+    );
+
+    // Messages sent while the race is running, and while the queue drains,
+    // must reach the winning candidate exactly once and in order
+    let conn = preconn.initiate().await.unwrap();
+    let mut expected = Vec::new();
+    for i in 0..50 {
+        let text = format!("{i:03},");
+        expected.extend_from_slice(text.as_bytes());
+        conn.send(Message::from_string(&text)).await.unwrap();
+        if i % 10 == 0 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    while conn.state().await == ConnectionState::Establishing {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    conn.close().await.unwrap();
+
+    let received = tokio::time::timeout(Duration::from_secs(2), server)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(received, expected);
+}
+
+#[tokio::test]
+async fn test_pending_messages_fail_when_all_candidates_fail() {
+    let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let closed_addr = closed.local_addr().unwrap();
+    drop(closed);
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .socket_address(closed_addr)
+            .build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+
+    let conn = preconn.initiate().await.unwrap();
+    conn.send(Message::from_string("first").with_id(1))
+        .await
+        .unwrap();
+    conn.send(Message::from_string("second").with_id(2))
+        .await
+        .unwrap();
+
+    // Each queued message is failed before the establishment error
+    let mut failed = Vec::new();
+    loop {
+        match tokio::time::timeout(Duration::from_secs(2), conn.next_event()).await {
+            Ok(Some(ConnectionEvent::SendError { message_id, .. })) => failed.push(message_id),
+            Ok(Some(ConnectionEvent::EstablishmentError(_))) => break,
+            other => panic!("Unexpected event: {other:?}"),
+        }
+    }
+    assert_eq!(failed, vec![Some(1), Some(2)]);
+    assert_eq!(conn.state().await, ConnectionState::Closed);
+}
+
+#[tokio::test]
+async fn test_send_waits_for_earlier_send_in_progress() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut received = Vec::new();
+        let _ = tokio::io::AsyncReadExt::read_to_end(&mut stream, &mut received).await;
+        received
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.unwrap();
+    conn.wait_for_established(Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+
+    // The first write is held back while the connection lock is free
+    let injector = std::sync::Arc::new(FaultInjector::new());
+    injector.inject(
+        FaultDirection::Send,
+        Fault::Delay(Duration::from_millis(200)),
+    );
+    conn.set_fault_injector(injector).await;
+
+    let first = {
+        let conn = conn.clone();
+        tokio::spawn(async move { conn.send("first,").await })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    conn.send("second").await.unwrap();
+    first.await.unwrap().unwrap();
+    conn.close().await.unwrap();
+
+    let received = tokio::time::timeout(Duration::from_secs(2), server)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(received, b"first,second");
+}
+
+#[tokio::test]
+async fn test_concurrent_senders_keep_order_across_establishment() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut received = Vec::new();
+        let _ = tokio::io::AsyncReadExt::read_to_end(&mut stream, &mut received).await;
+        received
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.unwrap();
+
+    // Senders start while Establishing and keep going after Established
+    let senders = (0..4)
+        .map(|sender| {
+            let conn = conn.clone();
+            tokio::spawn(async move {
+                for i in 0..50 {
+                    conn.send(format!("{sender}:{i:02};")).await.unwrap();
+                    tokio::task::yield_now().await;
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for sender in senders {
+        sender.await.unwrap();
+    }
+    conn.close().await.unwrap();
+
+    let received = tokio::time::timeout(Duration::from_secs(2), server)
+        .await
+        .unwrap()
+        .unwrap();
+    let received = String::from_utf8(received).unwrap();
+
+    let mut next = [0; 4];
+    for record in received.split_terminator(';') {
+        let (sender, i) = record.split_once(':').unwrap();
+        let sender: usize = sender.parse().unwrap();
+        assert_eq!(i.parse::<usize>().unwrap(), next[sender], "in {received}");
+        next[sender] += 1;
+    }
+    assert_eq!(next, [50; 4]);
+}
+
+/// A listener whose accept queue is full, so further connection attempts stall
+fn stalled_listener() -> (socket2::Socket, Vec<std::net::TcpStream>, SocketAddr) {
+    use socket2::{Domain, Socket, Type};
+
+    let socket = Socket::new(Domain::IPV4, Type::STREAM, None).unwrap();
+    socket.bind(&addr("127.0.0.1:0").into()).unwrap();
+    socket.listen(0).unwrap();
+    let address = socket.local_addr().unwrap().as_socket().unwrap();
+    let queued = (0..4)
+        .filter_map(|_| {
+            std::net::TcpStream::connect_timeout(&address, Duration::from_millis(100)).ok()
+        })
+        .collect();
+    (socket, queued, address)
+}
+
+/// Wait for the attempts to `address` to reach `count`
+async fn live_sockets_reach(address: SocketAddr, count: usize) -> bool {
+    for _ in 0..100 {
+        if crate::racing::audit::live_sockets(address) == count {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    false
+}
+
+#[tokio::test]
+async fn test_losing_candidates_are_closed_once_a_winner_connects() {
+    let (_stalled, _queued, stalled) = stalled_listener();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let live = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let _ = listener.accept().await;
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    });
+
+    let config = HappyEyeballsConfig {
+        connection_attempt_delay: Duration::from_millis(300),
+        ..Default::default()
+    };
+    let race = tokio::spawn(async move {
+        crate::racing::race_tcp(vec![stalled, live], &config, (None, None)).await
+    });
+
+    // The stalled attempt is in flight until the second candidate wins
+    assert!(live_sockets_reach(stalled, 1).await);
+    let stream = race.await.unwrap().unwrap();
+    assert_eq!(stream.peer_addr().unwrap(), live);
+    assert_eq!(crate::racing::audit::live_sockets(stalled), 0);
+}
+
+#[tokio::test]
+async fn test_abort_during_establishment_abandons_the_race() {
+    let (_stalled, _queued, stalled) = stalled_listener();
+
+    for graceful in [false, true] {
+        let preconn = Preconnection::new(
+            vec![],
+            vec![RemoteEndpoint::builder().socket_address(stalled).build()],
+            TransportProperties::default(),
+            SecurityParameters::new_disabled(),
+        );
+        let conn = preconn.initiate().await.unwrap();
+        assert!(live_sockets_reach(stalled, 1).await);
+
+        if graceful {
+            conn.close().await.unwrap();
+        } else {
+            conn.abort().await.unwrap();
+        }
+
+        // No attempt outlives the connection
+        assert!(live_sockets_reach(stalled, 0).await);
+        assert_eq!(conn.state().await, ConnectionState::Closed);
+    }
+}
@@ -395,6 +395,12 @@ async fn test_readonly_properties_cannot_be_set() {
                 "recvMsgMaxLen",
                 ConnectionProperty::RecvMsgMaxLen(Some(3000)),
             ),
+            ("pathInterface", ConnectionProperty::PathInterface(None)),
+            ("pathMtu", ConnectionProperty::PathMtu(Some(1500))),
+            (
+                "protocolStack",
+                ConnectionProperty::ProtocolStack(ProtocolStack::default()),
+            ),
         ];
 
         for (key, value) in readonly_props {
@@ -419,3 +425,111 @@ async fn test_readonly_properties_cannot_be_set() {
     .await
     .expect("Test should complete within timeout");
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_path_info() {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        let conn = create_test_connection().await;
+
+        let path = conn.path_info().await;
+        let local = path.local_address.expect("Should know the local address");
+        let remote = path.remote_address.expect("Should know the remote address");
+        assert!(local.ip().is_loopback());
+        assert!(remote.ip().is_loopback());
+
+        // Loopback belongs to "lo" when the NetworkMonitor can see it
+        if let Some(interface_type) = &path.interface_type {
+            assert_eq!(interface_type, "loopback");
+            assert!(path.interface.is_some());
+        }
+
+        #[cfg(target_os = "linux")]
+        assert!(path.path_mtu.is_some_and(|mtu| mtu > 0));
+
+        // Only Network.framework reports the system path, and it does not use loopback
+        assert_eq!(path.status, None);
+        assert_eq!(path.has_dns, None);
+        assert!(!path.is_constrained);
+
+        // The same information is exposed as properties
+        let props = conn.get_properties().await;
+        match props.get("pathLocalAddress") {
+            Some(ConnectionProperty::PathLocalAddress(addr)) => assert_eq!(*addr, Some(local)),
+            other => panic!("Unexpected pathLocalAddress: {other:?}"),
+        }
+        match props.get("pathRemoteAddress") {
+            Some(ConnectionProperty::PathRemoteAddress(addr)) => assert_eq!(*addr, Some(remote)),
+            other => panic!("Unexpected pathRemoteAddress: {other:?}"),
+        }
+        match props.get("pathMtu") {
+            Some(ConnectionProperty::PathMtu(mtu)) => assert_eq!(*mtu, path.path_mtu),
+            other => panic!("Unexpected pathMtu: {other:?}"),
+        }
+        match props.get("pathInterface") {
+            Some(ConnectionProperty::PathInterface(name)) => assert_eq!(*name, path.interface),
+            other => panic!("Unexpected pathInterface: {other:?}"),
+        }
+    })
+    .await
+    .expect("Test should complete within timeout");
+}
+
+#[tokio::test]
+async fn test_path_info_empty_before_establishment() {
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .hostname("example.com")
+            .port(443)
+            .build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = Connection::new_with_data(
+        preconn,
+        ConnectionState::Establishing,
+        None,
+        None,
+        TransportProperties::default(),
+    );
+
+    assert_eq!(conn.path_info().await, PathInfo::default());
+    assert_eq!(conn.protocol_stack().await, ProtocolStack::default());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_protocol_stack() {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        let conn = create_test_connection().await;
+
+        let stack = conn.protocol_stack().await;
+        assert_eq!(stack.layers, vec![StackLayer::Tcp, StackLayer::Ipv4]);
+        assert_eq!(stack.to_string(), "TCP/IPv4");
+        assert_eq!(stack.security(), None);
+        match conn.get_property("protocolStack").await {
+            Some(ConnectionProperty::ProtocolStack(property)) => assert_eq!(property, stack),
+            other => panic!("Unexpected protocolStack: {other:?}"),
+        }
+
+        // Nothing carries the connection once it is closed
+        conn.abort().await.unwrap();
+        assert!(conn.protocol_stack().await.layers.is_empty());
+    })
+    .await
+    .expect("Test should complete within timeout");
+}
+
+#[test]
+fn test_protocol_stack_over_ipv6_and_datagrams() {
+    let stack = ProtocolStack {
+        layers: vec![
+            StackLayer::Security(SecurityProtocol::DTLS13),
+            StackLayer::Udp,
+            StackLayer::Ipv6,
+        ],
+    };
+    assert_eq!(stack.to_string(), "DTLS1.3/UDP/IPv6");
+    assert!(stack.contains(StackLayer::Udp));
+    assert!(!stack.contains(StackLayer::Tcp));
+    assert_eq!(stack.security(), Some(SecurityProtocol::DTLS13));
+}
@@ -0,0 +1,295 @@
+//! Tests for receive-side message reassembly (RFC 9.3.2)
+
+use crate::reassembly::Reassembler;
+use crate::*;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::time::{sleep, Duration};
+
+fn framed(data: &[u8]) -> Vec<u8> {
+    let mut frame = (data.len() as u32).to_be_bytes().to_vec();
+    frame.extend_from_slice(data);
+    frame
+}
+
+#[test]
+fn test_message_reassembled_across_arrivals() {
+    let mut reassembler = Reassembler::default();
+    let frame = framed(b"hello world");
+    let mut buffer = Vec::new();
+
+    // Header and body arrive in pieces; nothing is delivered until the end
+    for piece in frame.chunks(3) {
+        assert!(reassembler.next(&mut buffer, None, None).is_none());
+        buffer.extend_from_slice(piece);
+    }
+
+    let delivery = reassembler.next(&mut buffer, None, None).unwrap();
+    assert_eq!(delivery.data, b"hello world");
+    assert!(!delivery.partial);
+    assert!(delivery.end_of_message);
+    assert!(buffer.is_empty());
+}
+
+#[test]
+fn test_max_length_switches_to_partial_delivery() {
+    let mut reassembler = Reassembler::default();
+    let mut buffer = framed(b"abcdefghij");
+    buffer.extend(framed(b"xy"));
+
+    let mut parts = Vec::new();
+    while let Some(delivery) = reassembler.next(&mut buffer, Some(4), None) {
+        assert!(delivery.partial);
+        assert_eq!(delivery.message_length, 10);
+        parts.push((delivery.data, delivery.end_of_message));
+        if parts.last().unwrap().1 {
+            break;
+        }
+    }
+    assert_eq!(
+        parts,
+        vec![
+            (b"abcd".to_vec(), false),
+            (b"efgh".to_vec(), false),
+            (b"ij".to_vec(), true),
+        ]
+    );
+
+    // The next message fits and is delivered whole
+    let delivery = reassembler.next(&mut buffer, Some(4), None).unwrap();
+    assert_eq!(delivery.data, b"xy");
+    assert!(!delivery.partial);
+}
+
+#[test]
+fn test_limit_streams_large_messages() {
+    let mut reassembler = Reassembler::new(8);
+    let frame = framed(&[7u8; 20]);
+    let mut buffer = frame[..10].to_vec();
+
+    // Bytes are handed on as they arrive instead of being buffered
+    let delivery = reassembler.next(&mut buffer, None, None).unwrap();
+    assert!(delivery.partial);
+    assert_eq!(delivery.data.len(), 6);
+    assert!(!delivery.end_of_message);
+    assert!(buffer.is_empty());
+
+    buffer.extend_from_slice(&frame[10..]);
+    let delivery = reassembler.next(&mut buffer, None, None).unwrap();
+    assert_eq!(delivery.data.len(), 14);
+    assert!(delivery.end_of_message);
+}
+
+#[test]
+fn test_min_incomplete_length_holds_small_parts() {
+    let mut reassembler = Reassembler::new(0);
+    let frame = framed(b"0123456789");
+    let mut buffer = frame[..6].to_vec();
+
+    assert!(reassembler.next(&mut buffer, None, Some(4)).is_none());
+    buffer.extend_from_slice(&frame[6..8]);
+    let delivery = reassembler.next(&mut buffer, None, Some(4)).unwrap();
+    assert_eq!(delivery.data, b"0123");
+
+    // The end of the message is delivered even when shorter
+    buffer.extend_from_slice(&frame[8..]);
+    let delivery = reassembler.next(&mut buffer, None, Some(4)).unwrap();
+    assert_eq!(delivery.data, b"456789");
+    assert!(delivery.end_of_message);
+}
+
+async fn framed_connection(peer_writes: Vec<Vec<u8>>) -> Connection {
+    framed_connection_with(peer_writes, TransportProperties::default()).await
+}
+
+async fn framed_connection_with(
+    peer_writes: Vec<Vec<u8>>,
+    properties: TransportProperties,
+) -> Connection {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        sleep(Duration::from_millis(100)).await;
+        for chunk in peer_writes {
+            stream.write_all(&chunk).await.unwrap();
+            stream.flush().await.unwrap();
+            sleep(Duration::from_millis(30)).await;
+        }
+        sleep(Duration::from_secs(1)).await;
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        properties,
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.expect("Should connect");
+    conn.wait_for_established(Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+    conn.use_length_prefix_framer().await.unwrap();
+    conn
+}
+
+#[test]
+fn test_oversized_message_discarded_without_buffering() {
+    let mut reassembler = Reassembler::default();
+    reassembler.set_max_message_size(Some(8));
+
+    // The oversized body arrives in pieces and is dropped as it comes
+    let oversized = framed(&[7u8; 20]);
+    let mut buffer = oversized[..10].to_vec();
+    assert!(reassembler.next(&mut buffer, None, None).is_none());
+    assert_eq!(reassembler.take_rejected(), Some(20));
+    assert!(buffer.is_empty());
+
+    buffer.extend_from_slice(&oversized[10..]);
+    buffer.extend(framed(b"small"));
+    let delivery = reassembler.next(&mut buffer, None, None).unwrap();
+    assert_eq!(delivery.data, b"small");
+    assert_eq!(reassembler.take_rejected(), None);
+}
+
+#[tokio::test]
+async fn test_partial_sends_delivered_as_one_message() {
+    let frame = framed(b"one logical message");
+    let conn = framed_connection(vec![
+        frame[..2].to_vec(),
+        frame[2..9].to_vec(),
+        frame[9..].to_vec(),
+    ])
+    .await;
+
+    let event = tokio::time::timeout(Duration::from_secs(2), async {
+        loop {
+            match conn.next_event().await {
+                Some(ConnectionEvent::Ready) => continue,
+                other => break other,
+            }
+        }
+    })
+    .await
+    .expect("Should receive the message");
+
+    match event {
+        Some(ConnectionEvent::Received { message_data, .. }) => {
+            assert_eq!(message_data, b"one logical message");
+        }
+        other => panic!("Expected Received, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_messages_over_limit_delivered_in_parts() {
+    let body: Vec<u8> = (0..64u8).collect();
+    let frame = framed(&body);
+    let conn = framed_connection(vec![frame[..24].to_vec(), frame[24..].to_vec()]).await;
+    conn.set_reassembly_limit(16).await;
+    assert_eq!(conn.reassembly_limit().await, 16);
+
+    let mut reassembled = Vec::new();
+    let mut parts = 0;
+    tokio::time::timeout(Duration::from_secs(2), async {
+        loop {
+            match conn.next_event().await {
+                Some(ConnectionEvent::ReceivedPartial {
+                    message_data,
+                    end_of_message,
+                    ..
+                }) => {
+                    parts += 1;
+                    reassembled.extend(message_data);
+                    if end_of_message {
+                        break;
+                    }
+                }
+                Some(ConnectionEvent::Received { .. }) => panic!("Should not buffer whole"),
+                Some(_) => {}
+                None => panic!("Event stream ended"),
+            }
+        }
+    })
+    .await
+    .expect("Should receive every part");
+
+    assert!(parts >= 2);
+    assert_eq!(reassembled, body);
+}
+
+#[tokio::test]
+async fn test_oversized_message_reports_receive_error() {
+    let mut writes = framed(&[0u8; 64]);
+    writes.extend(framed(b"fits"));
+    let conn = framed_connection_with(
+        vec![writes],
+        TransportProperties::builder()
+            .maximum_message_size_on_receive(16)
+            .build(),
+    )
+    .await;
+    assert!(matches!(
+        conn.get_property("recvMsgMaxLen").await,
+        Some(ConnectionProperty::RecvMsgMaxLen(Some(16)))
+    ));
+
+    let mut errors = 0;
+    tokio::time::timeout(Duration::from_secs(2), async {
+        loop {
+            match conn.next_event().await {
+                Some(ConnectionEvent::ReceiveError { error }) => {
+                    assert!(error.contains("64 bytes"));
+                    errors += 1;
+                }
+                Some(ConnectionEvent::Received { message_data, .. }) => {
+                    assert_eq!(message_data, b"fits");
+                    break;
+                }
+                Some(ConnectionEvent::ReceivedPartial { .. }) => {
+                    panic!("Oversized message should not be delivered")
+                }
+                Some(_) => {}
+                None => panic!("Event stream ended"),
+            }
+        }
+    })
+    .await
+    .expect("Should receive the message after the oversized one");
+
+    assert_eq!(errors, 1);
+    assert_eq!(conn.state().await, ConnectionState::Established);
+}
+
+#[tokio::test]
+async fn test_oversized_message_aborts_connection() {
+    let conn = framed_connection_with(
+        vec![framed(&[0u8; 64])],
+        TransportProperties::builder()
+            .maximum_message_size_on_receive(16)
+            .abort_on_oversized_message(true)
+            .build(),
+    )
+    .await;
+
+    let mut receive_error = false;
+    tokio::time::timeout(Duration::from_secs(2), async {
+        loop {
+            match conn.next_event().await {
+                Some(ConnectionEvent::ReceiveError { .. }) => receive_error = true,
+                Some(ConnectionEvent::ConnectionError(error)) => {
+                    assert!(error.contains("maximum message size"));
+                    break;
+                }
+                Some(_) => {}
+                None => panic!("Event stream ended"),
+            }
+        }
+    })
+    .await
+    .expect("Should abort the connection");
+
+    assert!(receive_error);
+    assert_eq!(conn.state().await, ConnectionState::Closed);
+}
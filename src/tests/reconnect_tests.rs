@@ -0,0 +1,151 @@
+//! Tests for automatic reconnection
+
+use crate::*;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+fn preconnection(addr: std::net::SocketAddr) -> Preconnection {
+    Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    )
+}
+
+async fn next_event(conn: &ReconnectingConnection) -> Option<ConnectionEvent> {
+    tokio::time::timeout(Duration::from_secs(5), conn.next_event())
+        .await
+        .expect("Should deliver an event")
+}
+
+#[test]
+fn test_backoff_grows_within_bounds() {
+    let policy = ReconnectPolicy::new()
+        .with_initial_delay(Duration::from_millis(100))
+        .with_max_delay(Duration::from_secs(1))
+        .with_multiplier(2.0)
+        .with_jitter(0.0);
+    assert_eq!(policy.backoff(0), Duration::from_millis(100));
+    assert_eq!(policy.backoff(3), Duration::from_millis(800));
+    assert_eq!(policy.backoff(10), Duration::from_secs(1));
+    assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(1));
+
+    // Jitter only ever shortens the delay
+    let policy = policy.with_jitter(0.5);
+    for _ in 0..100 {
+        let delay = policy.backoff(2);
+        assert!(delay > Duration::from_millis(200) && delay <= Duration::from_millis(400));
+    }
+}
+
+#[tokio::test]
+async fn test_reconnects_after_peer_closes() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // Each connection reads the hook's greeting, answers and closes
+    let (greetings, mut greeted) = mpsc::unbounded_channel();
+    let server = tokio::spawn(async move {
+        for generation in 0..2u8 {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut greeting = [0u8; 5];
+            stream.read_exact(&mut greeting).await.unwrap();
+            greetings.send(greeting).unwrap();
+            stream.write_all(&[b'0' + generation]).await.unwrap();
+        }
+        // Keep the listener until the test ends
+        std::future::pending::<()>().await;
+    });
+
+    let policy = ReconnectPolicy::new()
+        .with_initial_delay(Duration::from_millis(10))
+        .on_reconnect(|connection: Connection| -> runtime::BoxFuture<Result<()>> {
+            Box::pin(async move { connection.send("hello").await })
+        });
+    let conn = ReconnectingConnection::initiate(&preconnection(addr), policy).await;
+
+    let mut received = Vec::new();
+    let mut readies = 0;
+    while received.len() < 2 {
+        match next_event(&conn).await {
+            Some(ConnectionEvent::Ready) => readies += 1,
+            Some(ConnectionEvent::Received { message_data, .. }) => received.extend(message_data),
+            Some(_) => {}
+            None => panic!("Event stream ended"),
+        }
+    }
+
+    // Both generations ran the hook and delivered through the one stream
+    assert_eq!(received, b"01");
+    assert_eq!(readies, 2);
+    assert_eq!(greeted.recv().await.unwrap(), *b"hello");
+    assert_eq!(greeted.recv().await.unwrap(), *b"hello");
+    assert!(conn.generation() >= 2);
+
+    conn.close().await.unwrap();
+    server.abort();
+}
+
+#[tokio::test]
+async fn test_gives_up_after_max_attempts() {
+    // Nothing listens on the port once the listener is gone
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let policy = ReconnectPolicy::new()
+        .with_initial_delay(Duration::from_millis(10))
+        .with_max_attempts(2);
+    let conn = ReconnectingConnection::initiate(&preconnection(addr), policy).await;
+
+    let mut failures = 0;
+    let gave_up = loop {
+        match next_event(&conn).await {
+            Some(ConnectionEvent::EstablishmentError(_)) => failures += 1,
+            Some(ConnectionEvent::ConnectionError(error)) => break error,
+            Some(_) => {}
+            None => panic!("Expected a final ConnectionError"),
+        }
+    };
+    assert_eq!(failures, 2);
+    assert!(gave_up.contains("2 attempts"), "{gave_up}");
+    assert!(next_event(&conn).await.is_none());
+    assert_eq!(conn.generation(), 2);
+    assert!(matches!(
+        conn.send("lost").await,
+        Err(TransportServicesError::InvalidState(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_close_stops_reconnecting() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+        let mut streams = Vec::new();
+        while let Ok((stream, _)) = listener.accept().await {
+            streams.push(stream);
+        }
+    });
+
+    let policy = ReconnectPolicy::new().with_initial_delay(Duration::from_millis(10));
+    let conn = ReconnectingConnection::initiate(&preconnection(addr), policy).await;
+    assert!(matches!(
+        next_event(&conn).await,
+        Some(ConnectionEvent::Ready)
+    ));
+    assert!(conn.current().is_some());
+
+    conn.close().await.unwrap();
+    // The stream ends after the Closed event instead of reconnecting
+    while next_event(&conn).await.is_some() {}
+    assert_eq!(conn.generation(), 1);
+    assert!(conn.current().is_none());
+
+    server.abort();
+}
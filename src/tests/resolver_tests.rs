@@ -0,0 +1,301 @@
+//! Tests for the TTL-aware resolution cache and resolver configuration
+
+use crate::*;
+use async_trait::async_trait;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+#[tokio::test]
+async fn test_resolution_cache_serves_fresh_entries() {
+    let cache = Arc::new(ResolutionCache::new());
+    let addr: SocketAddr = "192.0.2.1:443".parse().unwrap();
+
+    cache.insert("Example.invalid", 443, vec![addr], Duration::from_secs(60));
+
+    // Served from the cache without a lookup; names are case-insensitive
+    let addrs = cache.resolve("example.invalid", 443).await.unwrap();
+    assert_eq!(addrs, vec![addr]);
+    assert_eq!(cache.len(), 1);
+
+    cache.flush();
+    assert!(cache.is_empty());
+}
+
+#[tokio::test]
+async fn test_resolution_cache_serves_stale_entries() {
+    let cache = Arc::new(ResolutionCache::with_ttl(
+        Duration::from_secs(60),
+        Duration::from_secs(30),
+    ));
+    let addr: SocketAddr = "192.0.2.2:80".parse().unwrap();
+
+    // An expired entry within the stale window is still served
+    cache.insert("stale.invalid", 80, vec![addr], Duration::ZERO);
+    let addrs = cache.resolve("stale.invalid", 80).await.unwrap();
+    assert_eq!(addrs, vec![addr]);
+}
+
+#[tokio::test]
+async fn test_resolution_cache_populated_by_lookup() {
+    let cache = Arc::new(ResolutionCache::new());
+
+    let addrs = cache.resolve("localhost", 8080).await.unwrap();
+    assert!(!addrs.is_empty());
+    assert!(addrs.iter().all(|a| a.port() == 8080));
+    assert_eq!(cache.len(), 1);
+}
+
+#[tokio::test]
+async fn test_preconnection_uses_resolution_cache() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let _ = listener.accept().await;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    });
+
+    // The cached entry is the only way this name can resolve
+    let cache = Arc::new(ResolutionCache::new());
+    cache.insert(
+        "service.invalid",
+        addr.port(),
+        vec![addr],
+        Duration::from_secs(60),
+    );
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .hostname("service.invalid")
+            .port(addr.port())
+            .build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    preconn
+        .set_resolution_cache(Arc::clone(&cache))
+        .await
+        .unwrap();
+
+    let conn = preconn.initiate().await.unwrap();
+    match tokio::time::timeout(Duration::from_secs(2), conn.next_event()).await {
+        Ok(Some(ConnectionEvent::Ready)) => {}
+        other => panic!("Expected Ready event, got {other:?}"),
+    }
+
+    // Flushing clears the shared cache
+    preconn.flush_resolution_cache().await;
+    assert!(cache.is_empty());
+}
+
+struct StaticResolver(SocketAddr);
+
+#[async_trait]
+impl DnsResolver for StaticResolver {
+    async fn lookup(
+        &self,
+        _host: &str,
+        _port: u16,
+        config: &ResolverConfig,
+    ) -> Result<(Vec<SocketAddr>, Option<Duration>)> {
+        assert_eq!(config.dnssec, DnssecPolicy::Required);
+        Ok((vec![self.0], Some(Duration::from_secs(300))))
+    }
+}
+
+#[tokio::test]
+async fn test_secure_resolution_requires_backend() {
+    let cache = Arc::new(ResolutionCache::new());
+    let addr: SocketAddr = "192.0.2.3:443".parse().unwrap();
+
+    // Entries from the system resolver never satisfy a secure configuration
+    cache.insert("secure.invalid", 443, vec![addr], Duration::from_secs(60));
+    let config = ResolverConfig::dns_over_https_hint("https://dns.example/dns-query");
+    assert!(config.requires_backend());
+    let result = cache.resolve_with("secure.invalid", 443, &config).await;
+    assert!(matches!(
+        result,
+        Err(TransportServicesError::NotSupported(_))
+    ));
+
+    // A backend performs the lookup and its answers are cached separately
+    let secure_addr: SocketAddr = "192.0.2.4:443".parse().unwrap();
+    let config =
+        ResolverConfig::dns_over_tls_hint("192.0.2.53:853".parse().unwrap(), "dns.example")
+            .with_dnssec(DnssecPolicy::Required)
+            .with_backend(Arc::new(StaticResolver(secure_addr)));
+    let addrs = cache
+        .resolve_with("secure.invalid", 443, &config)
+        .await
+        .unwrap();
+    assert_eq!(addrs, vec![secure_addr]);
+    assert_eq!(cache.len(), 2);
+    assert_eq!(
+        cache.resolve("secure.invalid", 443).await.unwrap(),
+        vec![addr]
+    );
+}
+
+/// Answers every lookup with one address, whatever the configuration
+struct FixedResolver(SocketAddr);
+
+#[async_trait]
+impl DnsResolver for FixedResolver {
+    async fn lookup(
+        &self,
+        _host: &str,
+        _port: u16,
+        _config: &ResolverConfig,
+    ) -> Result<(Vec<SocketAddr>, Option<Duration>)> {
+        Ok((vec![self.0], None))
+    }
+}
+
+#[tokio::test]
+async fn test_cached_answers_are_kept_per_transport_and_dnssec_policy() {
+    let cache = Arc::new(ResolutionCache::new());
+    let https_addr: SocketAddr = "192.0.2.6:443".parse().unwrap();
+    let tls_addr: SocketAddr = "192.0.2.7:443".parse().unwrap();
+    let dnssec_addr: SocketAddr = "192.0.2.8:443".parse().unwrap();
+
+    let https = ResolverConfig::dns_over_https_hint("https://dns.example/dns-query")
+        .with_backend(Arc::new(FixedResolver(https_addr)));
+    let tls = ResolverConfig::dns_over_tls_hint("192.0.2.53:853".parse().unwrap(), "dns.example")
+        .with_backend(Arc::new(FixedResolver(tls_addr)));
+    let dnssec = ResolverConfig::dns_over_https_hint("https://dns.example/dns-query")
+        .with_dnssec(DnssecPolicy::Required)
+        .with_backend(Arc::new(FixedResolver(dnssec_addr)));
+
+    // An answer fetched over one transport or policy never satisfies another
+    for (config, expected) in [
+        (&https, https_addr),
+        (&tls, tls_addr),
+        (&dnssec, dnssec_addr),
+    ] {
+        let addrs = cache
+            .resolve_with("split.invalid", 443, config)
+            .await
+            .unwrap();
+        assert_eq!(addrs, vec![expected]);
+    }
+    assert_eq!(cache.len(), 3);
+
+    // The same configuration is served from the cache
+    assert_eq!(
+        cache
+            .resolve_with("split.invalid", 443, &https)
+            .await
+            .unwrap(),
+        vec![https_addr]
+    );
+    assert_eq!(cache.len(), 3);
+}
+
+#[tokio::test]
+async fn test_cached_answers_are_kept_per_backend() {
+    let cache = Arc::new(ResolutionCache::new());
+    let first_addr: SocketAddr = "192.0.2.9:443".parse().unwrap();
+    let second_addr: SocketAddr = "192.0.2.10:443".parse().unwrap();
+
+    // Alike but for their backends, e.g. two tenants' resolvers
+    let first = ResolverConfig::dns_over_https_hint("https://dns.example/dns-query")
+        .with_backend(Arc::new(FixedResolver(first_addr)));
+    let second = ResolverConfig::dns_over_https_hint("https://dns.example/dns-query")
+        .with_backend(Arc::new(FixedResolver(second_addr)));
+
+    for (config, expected) in [(&first, first_addr), (&second, second_addr)] {
+        let addrs = cache
+            .resolve_with("tenant.invalid", 443, config)
+            .await
+            .unwrap();
+        assert_eq!(addrs, vec![expected]);
+    }
+    assert_eq!(cache.len(), 2);
+}
+
+/// Reports the largest TTL a lookup can carry
+struct LongLivedResolver(SocketAddr);
+
+#[async_trait]
+impl DnsResolver for LongLivedResolver {
+    async fn lookup(
+        &self,
+        _host: &str,
+        _port: u16,
+        _config: &ResolverConfig,
+    ) -> Result<(Vec<SocketAddr>, Option<Duration>)> {
+        Ok((vec![self.0], Some(Duration::MAX)))
+    }
+}
+
+#[tokio::test]
+async fn test_resolution_cache_clamps_huge_ttls() {
+    let cache = Arc::new(ResolutionCache::new());
+    let addr: SocketAddr = "192.0.2.11:443".parse().unwrap();
+
+    cache.insert("forever.invalid", 443, vec![addr], Duration::MAX);
+    assert_eq!(
+        cache.resolve("forever.invalid", 443).await.unwrap(),
+        vec![addr]
+    );
+
+    let config = ResolverConfig::dns_over_https_hint("https://dns.example/dns-query")
+        .with_backend(Arc::new(LongLivedResolver(addr)));
+    for _ in 0..2 {
+        let addrs = cache
+            .resolve_with("forever.invalid", 443, &config)
+            .await
+            .unwrap();
+        assert_eq!(addrs, vec![addr]);
+    }
+    assert_eq!(cache.len(), 2);
+
+    // So is the stale window
+    let cache = Arc::new(ResolutionCache::with_ttl(Duration::MAX, Duration::MAX));
+    cache.insert("stale.invalid", 443, vec![addr], Duration::ZERO);
+    assert_eq!(
+        cache.resolve("stale.invalid", 443).await.unwrap(),
+        vec![addr]
+    );
+}
+
+#[tokio::test]
+async fn test_preconnection_resolver_config() {
+    let secure_addr: SocketAddr = "192.0.2.5:443".parse().unwrap();
+    let preconnection = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .hostname("secure.invalid")
+            .port(443)
+            .build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+
+    preconnection
+        .set_resolver_config(ResolverConfig::system().with_dnssec(DnssecPolicy::Required))
+        .await
+        .unwrap();
+    assert!(preconnection.resolver_config().await.requires_backend());
+    // Without a backend the name stays unresolved
+    let (_, remotes) = preconnection.resolve().await.unwrap();
+    assert!(!remotes.iter().any(|r| r
+        .identifiers
+        .iter()
+        .any(|id| matches!(id, EndpointIdentifier::SocketAddress(_)))));
+
+    preconnection
+        .set_resolver_config(
+            ResolverConfig::system()
+                .with_dnssec(DnssecPolicy::Required)
+                .with_backend(Arc::new(StaticResolver(secure_addr))),
+        )
+        .await
+        .unwrap();
+    let (_, remotes) = preconnection.resolve().await.unwrap();
+    assert!(remotes.iter().any(|r| r
+        .identifiers
+        .contains(&EndpointIdentifier::SocketAddress(secure_addr))));
+}
@@ -0,0 +1,63 @@
+//! Tests for the executor abstraction
+
+use crate::runtime::{self, BoxFuture, Executor};
+use crate::TransportServicesError;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Executor that counts spawned tasks and delegates to tokio
+#[derive(Default)]
+struct CountingExecutor {
+    spawned: AtomicUsize,
+}
+
+impl Executor for CountingExecutor {
+    fn spawn(&self, future: BoxFuture<()>) {
+        self.spawned.fetch_add(1, Ordering::SeqCst);
+        tokio::spawn(future);
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+#[tokio::test]
+async fn test_custom_executor_drives_tasks() {
+    let executor = CountingExecutor::default();
+    let (tx, rx) = oneshot::channel();
+    executor.spawn(Box::pin(async move {
+        runtime::sleep(Duration::from_millis(10)).await;
+        let _ = tx.send(());
+    }));
+
+    rx.await.unwrap();
+    assert_eq!(executor.spawned.load(Ordering::SeqCst), 1);
+    assert!(executor.can_spawn());
+}
+
+#[tokio::test]
+async fn test_executor_cannot_be_replaced() {
+    // Using the library installs the default executor
+    let _ = runtime::executor();
+    let result = runtime::set_executor(Arc::new(CountingExecutor::default()));
+    assert!(matches!(
+        result,
+        Err(TransportServicesError::InvalidState(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_timeout_uses_executor_timer() {
+    let done = runtime::timeout(Duration::from_secs(1), async { 7 }).await;
+    assert_eq!(done, Ok(7));
+
+    let elapsed = runtime::timeout(
+        Duration::from_millis(20),
+        runtime::sleep(Duration::from_secs(5)),
+    )
+    .await;
+    assert!(elapsed.is_err());
+}
@@ -0,0 +1,366 @@
+//! Tests for upgrading an established connection to TLS in-band (STARTTLS)
+
+use crate::*;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::time::Duration;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use tokio_rustls::rustls::{self, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+// Test PKI: the server certificate is issued by ca.der for localhost and 127.0.0.1
+const CA: &[u8] = include_bytes!("certs/ca.der");
+const UNTRUSTED_CA: &[u8] = include_bytes!("certs/untrusted_ca.der");
+const SERVER_CERT: &[u8] = include_bytes!("certs/server.der");
+const SERVER_KEY: &[u8] = include_bytes!("certs/server.key.der");
+// Issued by ca.der for alpha.test only
+const ALPHA_CERT: &[u8] = include_bytes!("certs/alpha.der");
+const ALPHA_KEY: &[u8] = include_bytes!("certs/alpha.key.der");
+
+const ALPN: &str = "x-starttls-test";
+
+fn acceptor() -> TlsAcceptor {
+    acceptor_for(SERVER_CERT, SERVER_KEY)
+}
+
+fn acceptor_for(certificate: &[u8], key: &[u8]) -> TlsAcceptor {
+    let mut config = ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::aws_lc_rs::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_no_client_auth()
+    .with_single_cert(
+        vec![CertificateDer::from(certificate.to_vec())],
+        PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.to_vec())),
+    )
+    .unwrap();
+    config.alpn_protocols = vec![ALPN.as_bytes().to_vec()];
+    TlsAcceptor::from(Arc::new(config))
+}
+
+fn trusting(ca: &[u8]) -> SecurityParameters {
+    let mut parameters = SecurityParameters::new();
+    parameters.pinned_server_certificate = vec![CertificateChain {
+        certificates: vec![Certificate { data: ca.to_vec() }],
+    }];
+    parameters.alpn = vec![ALPN.to_string()];
+    parameters
+}
+
+/// Server that answers STARTTLS in cleartext, then echoes lines over TLS
+///
+/// Reports everything it read over TLS once the client closes.
+async fn starttls_server() -> (
+    std::net::SocketAddr,
+    tokio::sync::oneshot::Receiver<std::io::Result<Vec<u8>>>,
+) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = tokio::sync::oneshot::channel();
+
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut command = [0u8; 10];
+        stream.read_exact(&mut command).await.unwrap();
+        assert_eq!(&command, b"STARTTLS\r\n");
+        stream.write_all(b"220 ready\r\n").await.unwrap();
+
+        let result = async {
+            let tls = acceptor().accept(stream).await?;
+            let mut tls = BufReader::new(tls);
+            let mut read = Vec::new();
+            loop {
+                let mut line = Vec::new();
+                if tls.read_until(b'\n', &mut line).await? == 0 {
+                    break;
+                }
+                read.extend_from_slice(&line);
+                let mut reply = b"echo: ".to_vec();
+                reply.extend_from_slice(&line);
+                tls.get_mut().write_all(&reply).await?;
+            }
+            Ok(read)
+        }
+        .await;
+        let _ = tx.send(result);
+    });
+
+    (addr, rx)
+}
+
+async fn cleartext_connection(addr: std::net::SocketAddr) -> Connection {
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.expect("Should connect");
+    conn.wait_for_established(Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+    conn
+}
+
+/// Collect received data until `expected` bytes have arrived
+async fn receive_bytes(conn: &Connection, expected: usize) -> Vec<u8> {
+    tokio::time::timeout(Duration::from_secs(2), async {
+        let mut data = Vec::new();
+        while data.len() < expected {
+            match conn.next_event().await {
+                Some(ConnectionEvent::Received { message_data, .. }) => data.extend(message_data),
+                Some(_) => continue,
+                None => panic!("Event stream ended"),
+            }
+        }
+        data
+    })
+    .await
+    .expect("Should receive data")
+}
+
+async fn negotiate_starttls(conn: &Connection) {
+    conn.send(Message::from_bytes(b"STARTTLS\r\n"))
+        .await
+        .unwrap();
+    assert_eq!(receive_bytes(conn, 11).await, b"220 ready\r\n");
+}
+
+#[tokio::test]
+async fn test_start_security_upgrades_connection() {
+    let (addr, rx) = starttls_server().await;
+    let conn = cleartext_connection(addr).await;
+    assert!(conn.get_property("securityProtocol").await.is_none());
+    assert_eq!(conn.protocol_stack().await.to_string(), "TCP/IPv4");
+
+    negotiate_starttls(&conn).await;
+    conn.start_security(trusting(CA)).await.unwrap();
+
+    let stack = conn.protocol_stack().await;
+    assert_eq!(stack.to_string(), "TLS1.3/TCP/IPv4");
+    assert_eq!(stack.security(), Some(SecurityProtocol::TLS13));
+
+    match conn.get_property("securityProtocol").await {
+        Some(ConnectionProperty::SecurityProtocolInUse(Some(SecurityProtocol::TLS13))) => {}
+        other => panic!("Expected TLS 1.3, got {other:?}"),
+    }
+    match conn.get_property("securityAlpn").await {
+        Some(ConnectionProperty::SecurityAlpn(Some(alpn))) => assert_eq!(alpn, ALPN),
+        other => panic!("Expected the negotiated ALPN, got {other:?}"),
+    }
+
+    // Sends and receives now go through the session
+    conn.send(Message::from_bytes(b"hello\r\n")).await.unwrap();
+    assert_eq!(receive_bytes(&conn, 13).await, b"echo: hello\r\n");
+
+    // Closing ends the session cleanly; the server sees no truncation
+    conn.close().await.unwrap();
+    let read = tokio::time::timeout(Duration::from_secs(2), rx)
+        .await
+        .unwrap()
+        .unwrap()
+        .expect("Session should end with close_notify");
+    assert_eq!(read, b"hello\r\n");
+}
+
+#[tokio::test]
+async fn test_start_security_with_untrusted_server_fails() {
+    let (addr, _rx) = starttls_server().await;
+    let conn = cleartext_connection(addr).await;
+    negotiate_starttls(&conn).await;
+
+    match conn.start_security(trusting(UNTRUSTED_CA)).await {
+        Err(TransportServicesError::SecurityError(error)) => {
+            assert!(error.contains("TLS handshake failed"), "{error}")
+        }
+        other => panic!("Expected a security error, got {other:?}"),
+    }
+
+    // Nothing usable is left on the stream
+    assert_eq!(conn.state().await, ConnectionState::Closed);
+    assert!(conn.send(Message::from_bytes(b"hello")).await.is_err());
+}
+
+#[tokio::test]
+async fn test_start_security_rejects_invalid_parameters() {
+    let (addr, _rx) = starttls_server().await;
+    let conn = cleartext_connection(addr).await;
+
+    // No trust anchors to verify the server with
+    let result = conn.start_security(SecurityParameters::new()).await;
+    assert!(matches!(
+        result,
+        Err(TransportServicesError::InvalidParameters(_))
+    ));
+
+    let result = conn
+        .start_security(SecurityParameters::new_disabled())
+        .await;
+    assert!(matches!(
+        result,
+        Err(TransportServicesError::InvalidParameters(_))
+    ));
+
+    // DTLS cannot secure a byte stream
+    let mut parameters = trusting(CA);
+    parameters.allowed_protocols = vec![SecurityProtocol::DTLS13];
+    let result = conn.start_security(parameters).await;
+    assert!(matches!(
+        result,
+        Err(TransportServicesError::NotSupported(_))
+    ));
+
+    // The connection is untouched and still usable in cleartext
+    assert_eq!(conn.state().await, ConnectionState::Established);
+    negotiate_starttls(&conn).await;
+}
+
+#[tokio::test]
+async fn test_start_security_requires_established_connection() {
+    let (addr, _rx) = starttls_server().await;
+    let conn = cleartext_connection(addr).await;
+    conn.close().await.unwrap();
+
+    let result = conn.start_security(trusting(CA)).await;
+    assert!(matches!(
+        result,
+        Err(TransportServicesError::InvalidState(_))
+    ));
+}
+
+/// TLS server for alpha.test, reporting how each session was established
+///
+/// Each session gets a greeting, so clients process the session tickets
+/// sent before it.
+async fn alpha_server() -> (
+    std::net::SocketAddr,
+    tokio::sync::mpsc::UnboundedReceiver<rustls::HandshakeKind>,
+) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    let acceptor = acceptor_for(ALPHA_CERT, ALPHA_KEY);
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let Ok(mut tls) = acceptor.accept(stream).await else {
+                continue;
+            };
+            let _ = tx.send(tls.get_ref().1.handshake_kind().unwrap());
+            tls.write_all(b"hello").await.unwrap();
+            tokio::spawn(async move {
+                let mut buf = [0u8; 64];
+                while matches!(tls.read(&mut buf).await, Ok(n) if n > 0) {}
+            });
+        }
+    });
+
+    (addr, rx)
+}
+
+/// Preconnection reaching `addr` through an IP endpoint, with alpha.test as
+/// an alias or as an alternative
+async fn alpha_preconnection(addr: std::net::SocketAddr, aliased: bool) -> Preconnection {
+    let preconn = Preconnection::new(
+        vec![],
+        vec![],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    // alpha.test resolves to a closed port, so the IP endpoint always wins
+    let cache = Arc::new(crate::resolver::ResolutionCache::new());
+    cache.insert(
+        "alpha.test",
+        addr.port(),
+        vec!["127.0.0.2:9".parse().unwrap()],
+        Duration::from_secs(60),
+    );
+    preconn.set_resolution_cache(cache).await.unwrap();
+    let by_address = RemoteEndpoint::builder().socket_address(addr).build();
+    let by_name = RemoteEndpoint::builder()
+        .hostname("alpha.test")
+        .port(addr.port())
+        .build();
+    if aliased {
+        preconn
+            .add_remote_associated(vec![by_address, by_name])
+            .await
+            .unwrap();
+    } else {
+        preconn.add_remote(by_address).await.unwrap();
+        preconn.add_remote(by_name).await.unwrap();
+    }
+    preconn
+}
+
+async fn secure_alpha(preconn: &Preconnection) -> Result<Connection> {
+    let conn = preconn.initiate().await?;
+    conn.wait_for_established(Some(Duration::from_secs(2)))
+        .await?;
+    let mut parameters = trusting(CA);
+    parameters.alpn.clear();
+    conn.start_security(parameters).await?;
+    Ok(conn)
+}
+
+#[tokio::test]
+async fn test_aliases_secure_with_host_name() {
+    let (addr, _rx) = alpha_server().await;
+
+    // As an alternative, the IP endpoint is verified by its address
+    let preconn = alpha_preconnection(addr, false).await;
+    assert!(matches!(
+        secure_alpha(&preconn).await,
+        Err(TransportServicesError::SecurityError(_))
+    ));
+
+    // As an alias, it is verified as alpha.test
+    let preconn = alpha_preconnection(addr, true).await;
+    let conn = secure_alpha(&preconn).await.unwrap();
+    assert_eq!(
+        conn.remote_endpoint().await.unwrap().identifiers,
+        vec![EndpointIdentifier::SocketAddress(addr)]
+    );
+    assert_eq!(receive_bytes(&conn, 5).await, b"hello");
+}
+
+#[tokio::test]
+async fn test_connections_resume_sessions() {
+    let (addr, mut kinds) = alpha_server().await;
+    let preconn = alpha_preconnection(addr, true).await;
+
+    for expected in [rustls::HandshakeKind::Full, rustls::HandshakeKind::Resumed] {
+        let conn = secure_alpha(&preconn).await.unwrap();
+        assert_eq!(receive_bytes(&conn, 5).await, b"hello");
+        assert_eq!(kinds.recv().await.unwrap(), expected);
+        conn.close().await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_trust_verifier_applies_on_top_of_anchors() {
+    // The server passes the anchors but not the added verifier
+    let (addr, _rx) = starttls_server().await;
+    let conn = cleartext_connection(addr).await;
+    negotiate_starttls(&conn).await;
+
+    let mut parameters = trusting(CA);
+    parameters.add_trust_verifier(Arc::new(PeerPin::CertificateSha256(vec![0u8; 32])));
+    assert!(matches!(
+        conn.start_security(parameters).await,
+        Err(TransportServicesError::SecurityError(_))
+    ));
+
+    // A verifier accepting the presented chain leaves the handshake to succeed
+    let (addr, _rx) = starttls_server().await;
+    let conn = cleartext_connection(addr).await;
+    negotiate_starttls(&conn).await;
+
+    let mut parameters = trusting(CA);
+    parameters.set_trust_verification_callback(|chain: &CertificateChain| {
+        chain.certificates.first().map(|leaf| leaf.data.as_slice()) == Some(SERVER_CERT)
+    });
+    conn.start_security(parameters).await.unwrap();
+}
@@ -0,0 +1,146 @@
+//! Tests for explaining protocol stack and candidate selection
+
+use crate::*;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+fn addr(s: &str) -> SocketAddr {
+    s.parse().unwrap()
+}
+
+fn endpoint(addr: SocketAddr) -> RemoteEndpoint {
+    RemoteEndpoint::builder().socket_address(addr).build()
+}
+
+fn preconnection(properties: TransportProperties) -> Preconnection {
+    Preconnection::new(
+        vec![],
+        vec![],
+        properties,
+        SecurityParameters::new_disabled(),
+    )
+}
+
+#[tokio::test]
+async fn test_explain_selection_ranks_candidates() {
+    let v4 = addr("192.0.2.1:443");
+    let v6 = addr("[2001:db8::1]:443");
+    let preconn = preconnection(TransportProperties::default());
+    preconn.add_remote(endpoint(v4)).await.unwrap();
+    preconn.add_remote(endpoint(v6)).await.unwrap();
+
+    let explanation = preconn.explain_selection().await;
+    assert_eq!(explanation.failure, None);
+    assert_eq!(explanation.selected_stack(), Some(Protocol::TCP));
+    let quic = explanation
+        .stacks
+        .iter()
+        .find(|stack| stack.protocol == Protocol::QUIC)
+        .unwrap();
+    assert_eq!(quic.decision, Decision::Pruned(PruneReason::Unavailable));
+
+    // IPv6 is preferred by default, so it is raced first
+    let attempts: Vec<_> = explanation
+        .attempts()
+        .iter()
+        .map(|candidate| candidate.address)
+        .collect();
+    assert_eq!(attempts, vec![v6, v4]);
+    let endpoints = &explanation.stacks[0].endpoints;
+    assert_eq!(endpoints[0].decision, Decision::Selected { rank: 0 });
+    assert_eq!(endpoints[1].decision, Decision::Selected { rank: 1 });
+
+    // Explaining neither establishes nor freezes anything
+    assert!(!preconn.is_frozen().await);
+    preconn
+        .add_remote(endpoint(addr("192.0.2.2:443")))
+        .await
+        .unwrap();
+    assert_eq!(preconn.explain_selection().await.attempts().len(), 3);
+}
+
+#[tokio::test]
+async fn test_explain_selection_records_pruned_candidates() {
+    let kept = addr("192.0.2.1:443");
+    let vetoed = addr("192.0.2.2:443");
+    let v6 = addr("[2001:db8::1]:443");
+    let properties = TransportProperties::builder()
+        .address_family_preference(AddressFamilyPreference::Ipv4Only)
+        .build();
+    let preconn = preconnection(properties);
+    let context = TransportServices::new()
+        .with_candidate_policy(move |candidates| candidates.filter(|c| c.address != vetoed));
+    preconn.set_context(Arc::new(context)).await.unwrap();
+    preconn
+        .add_remote_associated(vec![
+            endpoint(kept),
+            RemoteEndpoint::builder()
+                .ip_address(kept.ip())
+                .port(kept.port())
+                .build(),
+        ])
+        .await
+        .unwrap();
+    preconn.add_remote(endpoint(vetoed)).await.unwrap();
+    preconn.add_remote(endpoint(v6)).await.unwrap();
+    preconn
+        .add_remote(RemoteEndpoint::builder().hostname("example.test").build())
+        .await
+        .unwrap();
+
+    let explanation = preconn.explain_selection().await;
+    assert_eq!(explanation.failure, None);
+    let decisions: Vec<_> = explanation
+        .candidates()
+        .map(|candidate| (candidate.address, candidate.decision.clone()))
+        .collect();
+    assert_eq!(
+        decisions,
+        vec![
+            (kept, Decision::Selected { rank: 0 }),
+            (kept, Decision::Pruned(PruneReason::DuplicateAlias)),
+            (vetoed, Decision::Pruned(PruneReason::ContextPolicy)),
+            (v6, Decision::Pruned(PruneReason::AddressFamily)),
+        ]
+    );
+
+    // The endpoint without a port resolves to nothing
+    let unresolved = explanation.stacks[0].endpoints.last().unwrap();
+    assert!(unresolved.candidates.is_empty());
+    assert!(matches!(
+        unresolved.decision,
+        Decision::Pruned(PruneReason::ResolutionFailed(_))
+    ));
+
+    let tree = explanation.to_string();
+    assert!(tree.contains("192.0.2.2:443: pruned: rejected by the context policy"));
+}
+
+#[tokio::test]
+async fn test_explain_selection_reports_why_initiate_fails() {
+    let mut properties = TransportProperties::default();
+    properties.selection_properties.per_msg_reliability = Preference::Require;
+    let preconn = preconnection(properties);
+    preconn
+        .add_remote(endpoint(addr("192.0.2.1:443")))
+        .await
+        .unwrap();
+
+    let explanation = preconn.explain_selection().await;
+    assert_eq!(explanation.selected_stack(), None);
+    assert_eq!(
+        explanation.stacks[0].decision,
+        Decision::Pruned(PruneReason::Property(TransportProperty::PerMsgReliability))
+    );
+    assert!(explanation.stacks[0].endpoints.is_empty());
+
+    let error = preconn.initiate().await.unwrap_err();
+    assert_eq!(explanation.failure, Some(error.to_string()));
+
+    // Without remote endpoints there is nothing to select from
+    let explanation = preconnection(TransportProperties::default())
+        .explain_selection()
+        .await;
+    assert!(explanation.stacks.is_empty());
+    assert!(explanation.failure.is_some());
+}
@@ -0,0 +1,137 @@
+//! Tests for network condition simulation
+
+use crate::shaping::TrafficShaper;
+use crate::*;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+/// Connect to a server that reports each chunk it reads with its arrival time
+async fn connect_to_timing_server() -> (Connection, mpsc::UnboundedReceiver<(Instant, Vec<u8>)>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr: SocketAddr = listener.local_addr().unwrap();
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        if let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = vec![0; 65536];
+            while let Ok(n) = stream.read(&mut buf).await {
+                if n == 0 || tx.send((Instant::now(), buf[..n].to_vec())).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.unwrap();
+    conn.wait_for_established(Some(Duration::from_secs(5)))
+        .await
+        .unwrap();
+    (conn, rx)
+}
+
+/// Collect bytes until `len` have arrived, returning the time the last one did
+async fn arrival_of(
+    rx: &mut mpsc::UnboundedReceiver<(Instant, Vec<u8>)>,
+    len: usize,
+) -> (Instant, Vec<u8>) {
+    let mut received = Vec::new();
+    let mut last = Instant::now();
+    while received.len() < len {
+        let (at, chunk) = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("timed out waiting for data")
+            .expect("server closed");
+        last = at;
+        received.extend(chunk);
+    }
+    (last, received)
+}
+
+#[tokio::test]
+async fn test_latency_delays_delivery() {
+    let (conn, mut rx) = connect_to_timing_server().await;
+    conn.set_network_conditions(NetworkConditions::new().with_latency(Duration::from_millis(200)))
+        .await;
+
+    let sent_at = Instant::now();
+    conn.send("delayed").await.unwrap();
+    let (arrived_at, data) = arrival_of(&mut rx, 7).await;
+
+    assert_eq!(data, b"delayed");
+    assert!(arrived_at - sent_at >= Duration::from_millis(190));
+    conn.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_bandwidth_limits_throughput() {
+    let (conn, mut rx) = connect_to_timing_server().await;
+    // 10 KB/s with a 1 KB burst: 5 KB takes about 400 ms
+    conn.set_network_conditions(
+        NetworkConditions::new()
+            .with_bandwidth(80_000)
+            .with_burst(1000),
+    )
+    .await;
+
+    let sent_at = Instant::now();
+    for _ in 0..5 {
+        conn.send(vec![7u8; 1000]).await.unwrap();
+    }
+    let (arrived_at, data) = arrival_of(&mut rx, 5000).await;
+
+    assert_eq!(data.len(), 5000);
+    let elapsed = arrived_at - sent_at;
+    assert!(elapsed >= Duration::from_millis(350), "took {elapsed:?}");
+    assert!(elapsed < Duration::from_secs(2), "took {elapsed:?}");
+    conn.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_close_delivers_shaped_data() {
+    let (conn, mut rx) = connect_to_timing_server().await;
+    conn.set_network_conditions(NetworkConditions::satellite().with_loss(0.0))
+        .await;
+    assert_eq!(
+        conn.network_conditions().await.unwrap().latency,
+        Duration::from_millis(300)
+    );
+
+    conn.send("last words").await.unwrap();
+    conn.close().await.unwrap();
+
+    let (_, data) = arrival_of(&mut rx, 10).await;
+    assert_eq!(data, b"last words");
+}
+
+#[tokio::test]
+async fn test_loss_is_reproducible_per_seed() {
+    let conditions = NetworkConditions::new().with_loss(0.3).with_seed(42);
+    let pattern = |conditions: NetworkConditions| {
+        let shaper = TrafficShaper::new(conditions, |_| Box::pin(async {}));
+        (0..200)
+            .map(|_| shaper.enqueue(vec![0; 10], false))
+            .collect::<Vec<_>>()
+    };
+
+    let first = pattern(conditions.clone());
+    assert_eq!(first, pattern(conditions.clone()));
+    assert_ne!(first, pattern(conditions.with_seed(7)));
+
+    let lost = first.iter().filter(|delivered| !**delivered).count();
+    assert!((30..90).contains(&lost), "lost {lost} of 200");
+
+    // Streams never lose data
+    let shaper = TrafficShaper::new(NetworkConditions::new().with_loss(1.0), |_| {
+        Box::pin(async {})
+    });
+    assert!(shaper.enqueue(vec![0; 10], true));
+}
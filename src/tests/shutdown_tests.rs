@@ -0,0 +1,156 @@
+//! Tests for draining listeners and connections on shutdown
+//!
+//! The process-wide shutdown would close the connections of tests running
+//! alongside, so these drain a context, which shares its implementation.
+
+use crate::*;
+use std::sync::Arc;
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpListener;
+use tokio::time::Duration;
+
+/// Peer that reports everything it read once the connection is closed
+async fn reading_peer() -> (
+    std::net::SocketAddr,
+    tokio::sync::oneshot::Receiver<Vec<u8>>,
+) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut received = Vec::new();
+        let _ = stream.read_to_end(&mut received).await;
+        let _ = tx.send(received);
+    });
+    (addr, rx)
+}
+
+async fn preconnection_to(
+    addr: std::net::SocketAddr,
+    context: Option<&Arc<TransportServices>>,
+) -> Preconnection {
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    if let Some(context) = context {
+        preconn.set_context(Arc::clone(context)).await.unwrap();
+    }
+    preconn
+}
+
+async fn established(preconn: &Preconnection) -> Connection {
+    let conn = preconn.initiate().await.unwrap();
+    conn.wait_for_established(Some(Duration::from_secs(5)))
+        .await
+        .unwrap();
+    conn
+}
+
+#[tokio::test]
+async fn test_shutdown_closes_initiated_and_accepted_connections() {
+    let context = Arc::new(TransportServices::new());
+    let server = Preconnection::new(
+        vec![LocalEndpoint::builder()
+            .ip_address("127.0.0.1".parse().unwrap())
+            .port(0)
+            .build()],
+        vec![],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    server.set_context(Arc::clone(&context)).await.unwrap();
+    let listener = server.listen().await.unwrap();
+    let addr = listener.local_addr().await.unwrap();
+
+    let client = established(&preconnection_to(addr, Some(&context)).await).await;
+    let accepted = tokio::time::timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+
+    let summary = context.shutdown(Duration::from_secs(5)).await;
+    assert_eq!(
+        summary,
+        ShutdownSummary {
+            listeners_stopped: 1,
+            closed: 2,
+            aborted: 0,
+        }
+    );
+    assert!(!listener.is_active().await);
+    assert_eq!(client.state().await, ConnectionState::Closed);
+    assert_eq!(accepted.state().await, ConnectionState::Closed);
+
+    // Nothing is left to drain a second time
+    assert_eq!(
+        context.shutdown(Duration::from_secs(1)).await,
+        ShutdownSummary::default()
+    );
+}
+
+#[tokio::test]
+async fn test_shutdown_delivers_outstanding_data() {
+    let context = Arc::new(TransportServices::new());
+    let (addr, read) = reading_peer().await;
+    let conn = established(&preconnection_to(addr, Some(&context)).await).await;
+    conn.set_network_conditions(NetworkConditions::new().with_latency(Duration::from_millis(100)))
+        .await;
+    conn.send(Message::from_string("last words")).await.unwrap();
+
+    let summary = context.shutdown(Duration::from_secs(5)).await;
+    assert_eq!(summary.closed, 1);
+    let received = tokio::time::timeout(Duration::from_secs(2), read)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(received, b"last words");
+}
+
+#[tokio::test]
+async fn test_shutdown_aborts_connections_open_at_deadline() {
+    let context = Arc::new(TransportServices::new());
+    let (addr, _read) = reading_peer().await;
+    let conn = established(&preconnection_to(addr, Some(&context)).await).await;
+    conn.set_network_conditions(NetworkConditions::new().with_latency(Duration::from_secs(10)))
+        .await;
+    conn.send(Message::from_string("stuck")).await.unwrap();
+
+    let started = std::time::Instant::now();
+    let summary = context.shutdown(Duration::from_millis(200)).await;
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert_eq!(summary.aborted, 1);
+    assert_eq!(summary.closed, 0);
+    assert_eq!(conn.state().await, ConnectionState::Closed);
+}
+
+#[tokio::test]
+async fn test_shutdown_leaves_other_contexts_alone() {
+    let context = Arc::new(TransportServices::new());
+    let (addr, _read) = reading_peer().await;
+    let outside = established(&preconnection_to(addr, None).await).await;
+    let (addr, _read) = reading_peer().await;
+    let other = Arc::new(TransportServices::new());
+    let elsewhere = established(&preconnection_to(addr, Some(&other)).await).await;
+
+    let summary = context.shutdown(Duration::from_secs(1)).await;
+    assert_eq!(summary, ShutdownSummary::default());
+    assert_eq!(outside.state().await, ConnectionState::Established);
+    assert_eq!(elsewhere.state().await, ConnectionState::Established);
+}
+
+#[tokio::test]
+async fn test_shutdown_skips_connections_without_handles() {
+    let context = Arc::new(TransportServices::new());
+    let (addr, _read) = reading_peer().await;
+    let conn = established(&preconnection_to(addr, Some(&context)).await).await;
+    conn.set_drop_policy(DropPolicy::Abort).await;
+    drop(conn);
+
+    // The drop policy already ended it
+    let summary = context.shutdown(Duration::from_secs(1)).await;
+    assert_eq!(summary.closed + summary.aborted, 0);
+}
@@ -0,0 +1,190 @@
+//! Tests for the one-line connect and listen facade
+
+use crate::*;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpListener;
+
+/// Peer that reports the first bytes it reads
+async fn reading_peer() -> (
+    std::net::SocketAddr,
+    tokio::sync::oneshot::Receiver<Vec<u8>>,
+) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buf = vec![0u8; 64];
+        let n = stream.read(&mut buf).await.unwrap();
+        let _ = tx.send(buf[..n].to_vec());
+    });
+    (addr, rx)
+}
+
+#[tokio::test]
+async fn test_connect_returns_established_connection() {
+    let (addr, read) = reading_peer().await;
+
+    let connection = connect(addr.to_string()).await.unwrap();
+    assert_eq!(connection.state().await, ConnectionState::Established);
+    connection
+        .send(Message::from_string("hello"))
+        .await
+        .unwrap();
+    assert_eq!(read.await.unwrap(), b"hello");
+}
+
+#[tokio::test]
+async fn test_connect_by_host_name_and_url() {
+    let (addr, _read) = reading_peer().await;
+    let connection = connect(format!("localhost:{}", addr.port())).await.unwrap();
+    assert_eq!(connection.state().await, ConnectionState::Established);
+
+    let (addr, _read) = reading_peer().await;
+    let connection = connect(format!("tcp://{addr}")).await.unwrap();
+    assert_eq!(connection.state().await, ConnectionState::Established);
+}
+
+#[tokio::test]
+async fn test_listen_accepts_connect() {
+    let listener = listen("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().await.unwrap();
+
+    let client = connect(addr.to_string()).await.unwrap();
+    let server = tokio::time::timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+    client.send(Message::from_string("ping")).await.unwrap();
+    let (message, _) = tokio::time::timeout(Duration::from_secs(2), server.receive())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(message.data(), b"ping");
+
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_invalid_targets_are_rejected() {
+    for target in ["example.com", "example.com:https", "::1:80", ":80"] {
+        assert!(
+            matches!(
+                connect(target).await,
+                Err(TransportServicesError::InvalidParameters(_))
+            ),
+            "{target} was accepted"
+        );
+    }
+    assert!(matches!(
+        listen("localhost:80").await,
+        Err(TransportServicesError::InvalidParameters(_))
+    ));
+}
+
+#[cfg(feature = "tls")]
+mod secure {
+    use super::*;
+    use std::sync::Arc;
+    use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+    use tokio_rustls::rustls::{self, ServerConfig};
+    use tokio_rustls::TlsAcceptor;
+
+    // Issued by ca.der for localhost and 127.0.0.1
+    const CA: &[u8] = include_bytes!("certs/ca.der");
+    const SERVER_CERT: &[u8] = include_bytes!("certs/server.der");
+    const SERVER_KEY: &[u8] = include_bytes!("certs/server.key.der");
+
+    /// TLS server that reports the first bytes it reads
+    async fn tls_peer() -> (
+        std::net::SocketAddr,
+        tokio::sync::oneshot::Receiver<Vec<u8>>,
+    ) {
+        let config = ServerConfig::builder_with_provider(Arc::new(
+            rustls::crypto::aws_lc_rs::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![CertificateDer::from(SERVER_CERT.to_vec())],
+            PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(SERVER_KEY.to_vec())),
+        )
+        .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let Ok(mut tls) = acceptor.accept(stream).await else {
+                return;
+            };
+            let mut buf = vec![0u8; 64];
+            let n = tls.read(&mut buf).await.unwrap();
+            let _ = tx.send(buf[..n].to_vec());
+        });
+        (addr, rx)
+    }
+
+    fn trusting_ca() -> SecurityParameters {
+        let mut parameters = SecurityParameters::new();
+        parameters.pinned_server_certificate = vec![CertificateChain {
+            certificates: vec![Certificate { data: CA.to_vec() }],
+        }];
+        parameters
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_security_parameters() {
+        let (addr, read) = tls_peer().await;
+
+        let connection = connect(format!("localhost:{}", addr.port()))
+            .security(trusting_ca())
+            .await
+            .unwrap();
+        connection
+            .send(Message::from_string("secret"))
+            .await
+            .unwrap();
+        assert_eq!(read.await.unwrap(), b"secret");
+    }
+
+    #[tokio::test]
+    async fn test_secure_trusts_system_roots_only() {
+        let (addr, _read) = tls_peer().await;
+
+        // The test CA is not among the system's roots, if there are any
+        let result = connect(format!("localhost:{}", addr.port())).secure().await;
+        assert!(
+            matches!(
+                result,
+                Err(TransportServicesError::SecurityError(_)
+                    | TransportServicesError::InvalidParameters(_))
+            ),
+            "{:?}",
+            result.map(|_| ())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_secure_handshake_times_out() {
+        // A peer that accepts but never answers the handshake
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (_stream, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+
+        let started = std::time::Instant::now();
+        let result = connect(addr.to_string())
+            .security(trusting_ca())
+            .timeout(Duration::from_millis(300))
+            .await;
+        assert!(matches!(result, Err(TransportServicesError::Timeout)));
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
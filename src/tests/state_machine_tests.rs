@@ -0,0 +1,179 @@
+//! Tests for the connection state machine and races between operations
+//!
+//! The race tests run on a single-threaded runtime and step each operation
+//! through a fixed number of yields, so every schedule in the grid is replayed
+//! the same way on each run.
+
+use crate::state_machine::{is_terminal, is_valid_transition};
+use crate::*;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::yield_now;
+
+const STATES: [ConnectionState; 4] = [
+    ConnectionState::Establishing,
+    ConnectionState::Established,
+    ConnectionState::Closing,
+    ConnectionState::Closed,
+];
+
+#[test]
+fn test_transitions_only_move_forward() {
+    let rank = |state| STATES.iter().position(|s| *s == state).unwrap();
+    for from in STATES {
+        for to in STATES {
+            assert_eq!(
+                is_valid_transition(from, to),
+                rank(to) > rank(from),
+                "{from:?} -> {to:?}"
+            );
+        }
+    }
+    assert!(is_terminal(ConnectionState::Closed));
+    assert!(!is_terminal(ConnectionState::Closing));
+}
+
+/// Start an echo server and connect to it
+async fn echo_connection() -> Connection {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        if let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = [0; 1024];
+            while let Ok(n) = stream.read(&mut buf).await {
+                if n == 0 || stream.write_all(&buf[..n]).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.unwrap();
+    conn.wait_for_established(Some(Duration::from_secs(5)))
+        .await
+        .unwrap();
+    conn
+}
+
+async fn yields(count: usize) {
+    for _ in 0..count {
+        yield_now().await;
+    }
+}
+
+/// Drain the events delivered so far
+async fn drain_events(conn: &Connection) -> Vec<ConnectionEvent> {
+    let mut events = Vec::new();
+    while let Ok(Some(event)) =
+        tokio::time::timeout(Duration::from_millis(20), conn.next_event()).await
+    {
+        events.push(event);
+    }
+    events
+}
+
+/// Check the invariants every schedule must keep
+fn check_invariants(schedule: &str, state: ConnectionState, events: &[ConnectionEvent]) {
+    assert_eq!(state, ConnectionState::Closed, "{schedule}: not closed");
+
+    let terminal = events
+        .iter()
+        .filter(|e| {
+            matches!(
+                e,
+                ConnectionEvent::Closed | ConnectionEvent::ConnectionError(_)
+            )
+        })
+        .count();
+    assert_eq!(
+        terminal, 1,
+        "{schedule}: {terminal} terminal events in {events:?}"
+    );
+
+    let mut outcomes: HashMap<Option<u64>, usize> = HashMap::new();
+    for event in events {
+        if let ConnectionEvent::Sent { message_id }
+        | ConnectionEvent::SendError { message_id, .. } = event
+        {
+            *outcomes.entry(*message_id).or_default() += 1;
+        }
+    }
+    assert!(
+        outcomes.values().all(|count| *count == 1),
+        "{schedule}: message reported more than once in {events:?}"
+    );
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_send_close_abort_races() {
+    for send_at in 0..3 {
+        for close_at in 0..3 {
+            for abort_at in 0..4 {
+                let schedule = format!("send@{send_at} close@{close_at} abort@{abort_at}");
+                let conn = echo_connection().await;
+                let _ = drain_events(&conn).await;
+                // A batched message makes close write before it shuts down
+                conn.start_batch().await.unwrap();
+                conn.send("batched").await.unwrap();
+
+                let sender = conn.clone();
+                let closer = conn.clone();
+                let aborter = conn.clone();
+                let _ = tokio::join!(
+                    async move {
+                        yields(send_at).await;
+                        sender.send("racing").await
+                    },
+                    async move {
+                        yields(close_at).await;
+                        closer.close().await
+                    },
+                    async move {
+                        yields(abort_at).await;
+                        aborter.abort().await
+                    },
+                );
+
+                let events = drain_events(&conn).await;
+                check_invariants(&schedule, conn.state().await, &events);
+                assert!(conn.send("late").await.is_err(), "{schedule}");
+            }
+        }
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_receive_close_races() {
+    for close_at in 0..4 {
+        let schedule = format!("receive close@{close_at}");
+        let conn = echo_connection().await;
+        let _ = drain_events(&conn).await;
+
+        // The echo arrives through the background reader while closing
+        conn.send("echo").await.unwrap();
+        let closer = conn.clone();
+        let _ = tokio::join!(
+            async {
+                yields(close_at).await;
+                closer.close().await
+            },
+            async {
+                for _ in 0..4 {
+                    yield_now().await;
+                    let _ = conn.state().await;
+                }
+            },
+        );
+
+        let events = drain_events(&conn).await;
+        check_invariants(&schedule, conn.state().await, &events);
+    }
+}
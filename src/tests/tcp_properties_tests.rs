@@ -359,3 +359,456 @@ async fn test_tcp_properties_group_synchronization() {
     .await
     .expect("Test should complete within timeout");
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_socket_options_applied_on_both_sides() {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        let properties = TransportProperties::builder()
+            .keep_alive(Preference::Require)
+            .keep_alive_timeout(Duration::from_secs(60))
+            .no_delay(true)
+            .send_buffer_size(64 * 1024)
+            .receive_buffer_size(64 * 1024)
+            .build();
+
+        // Server applies its properties at accept time
+        let server_preconn = Preconnection::new(
+            vec![LocalEndpoint::builder()
+                .ip_address("127.0.0.1".parse().unwrap())
+                .port(0)
+                .build()],
+            vec![],
+            properties.clone(),
+            SecurityParameters::new_disabled(),
+        );
+        let listener = server_preconn.listen().await.unwrap();
+        let addr = listener.local_addr().await.unwrap();
+
+        let client_preconn = Preconnection::new(
+            vec![],
+            vec![RemoteEndpoint::builder().socket_address(addr).build()],
+            properties,
+            SecurityParameters::new_disabled(),
+        );
+        let client = client_preconn.initiate().await.expect("Should connect");
+        let server = listener.accept().await.expect("Should accept");
+
+        match client.next_event().await {
+            Some(ConnectionEvent::Ready) => {}
+            other => panic!("Expected Ready event, got {other:?}"),
+        }
+
+        for conn in [&client, &server] {
+            let (keepalive, nodelay, send_buf, recv_buf) = conn
+                .inspect_tcp_socket(|socket| {
+                    (
+                        socket.keepalive().unwrap(),
+                        socket.nodelay().unwrap(),
+                        socket.send_buffer_size().unwrap(),
+                        socket.recv_buffer_size().unwrap(),
+                    )
+                })
+                .await
+                .expect("Should have a TCP stream");
+
+            assert!(keepalive);
+            assert!(nodelay);
+            // The kernel may round buffer sizes up (Linux doubles them)
+            assert!(send_buf >= 64 * 1024);
+            assert!(recv_buf >= 64 * 1024);
+        }
+
+        listener.stop().await.unwrap();
+    })
+    .await
+    .expect("Test should complete within timeout");
+}
+
+#[test]
+fn test_conn_priority_socket_priority_mapping() {
+    use crate::connection::socket_priority;
+
+    assert_eq!(socket_priority(100), 0);
+    assert_eq!(socket_priority(u32::MAX), 0);
+    assert_eq!(socket_priority(0), 6);
+    assert_eq!(socket_priority(50), 3);
+    assert_eq!(socket_priority(99), 1);
+}
+
+#[test]
+fn test_capacity_profile_dscp_mapping() {
+    use crate::connection::capacity_profile_dscp;
+
+    assert_eq!(capacity_profile_dscp(CapacityProfile::Default), 0);
+    assert_eq!(capacity_profile_dscp(CapacityProfile::Scavenger), 1);
+    assert_eq!(
+        capacity_profile_dscp(CapacityProfile::LowLatencyInteractive),
+        34
+    );
+    assert_eq!(
+        capacity_profile_dscp(CapacityProfile::LowLatencyNonInteractive),
+        18
+    );
+    assert_eq!(
+        capacity_profile_dscp(CapacityProfile::ConstantRateStreaming),
+        26
+    );
+    assert_eq!(capacity_profile_dscp(CapacityProfile::CapacitySeeking), 10);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_capacity_profile_sets_tos() {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        let conn = create_test_connection().await;
+
+        conn.set_property(
+            "connCapacityProfile",
+            ConnectionProperty::ConnCapacityProfile(CapacityProfile::LowLatencyInteractive),
+        )
+        .await
+        .unwrap();
+
+        let tos = conn
+            .inspect_tcp_socket(|socket| socket.tos().unwrap())
+            .await
+            .expect("Should have a TCP stream");
+        assert_eq!(tos, 34 << 2);
+    })
+    .await
+    .expect("Test should complete within timeout");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_scavenger_profile_paces_sends() {
+    use tokio::io::AsyncReadExt;
+
+    tokio::time::timeout(Duration::from_secs(10), async {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut received = Vec::new();
+            stream.read_to_end(&mut received).await.unwrap();
+            received.len()
+        });
+
+        let preconn = Preconnection::new(
+            vec![],
+            vec![RemoteEndpoint::builder().socket_address(addr).build()],
+            TransportProperties::default(),
+            SecurityParameters::new_disabled(),
+        );
+        let conn = preconn.initiate().await.unwrap();
+        conn.wait_for_established(Some(Duration::from_secs(2)))
+            .await
+            .unwrap();
+        conn.set_property(
+            "connCapacityProfile",
+            ConnectionProperty::ConnCapacityProfile(CapacityProfile::Scavenger),
+        )
+        .await
+        .unwrap();
+
+        let tos = conn
+            .inspect_tcp_socket(|socket| socket.tos().unwrap())
+            .await
+            .expect("Should have a TCP stream");
+        assert_eq!(tos, 1 << 2);
+
+        // Paced or not, everything is delivered
+        for _ in 0..200 {
+            conn.send(Message::from_bytes(&[0u8; 1024])).await.unwrap();
+        }
+        conn.close().await.unwrap();
+        assert_eq!(server.await.unwrap(), 200 * 1024);
+    })
+    .await
+    .expect("Test should complete within timeout");
+}
+
+#[cfg(target_os = "linux")]
+fn read_socket_priority(socket: socket2::SockRef<'_>) -> i32 {
+    use std::os::unix::io::AsRawFd;
+
+    let mut value: libc::c_int = -1;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PRIORITY,
+            &mut value as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    assert_eq!(result, 0);
+    value
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_conn_priority_sets_so_priority() {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        let conn = create_test_connection().await;
+
+        conn.set_property("connPriority", ConnectionProperty::ConnPriority(0))
+            .await
+            .unwrap();
+        let priority = conn.inspect_tcp_socket(read_socket_priority).await;
+        assert_eq!(priority, Some(6));
+
+        conn.set_property("connPriority", ConnectionProperty::ConnPriority(100))
+            .await
+            .unwrap();
+        let priority = conn.inspect_tcp_socket(read_socket_priority).await;
+        assert_eq!(priority, Some(0));
+    })
+    .await
+    .expect("Test should complete within timeout");
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_preconnection_priority_applied_at_establishment() {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (_stream, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+
+        let preconn = Preconnection::new(
+            vec![],
+            vec![RemoteEndpoint::builder().socket_address(addr).build()],
+            TransportProperties::builder()
+                .connection_priority(50)
+                .build(),
+            SecurityParameters::new_disabled(),
+        );
+        let conn = preconn.initiate().await.expect("Should connect");
+        conn.wait_for_established(None).await.unwrap();
+
+        let priority = conn.inspect_tcp_socket(read_socket_priority).await;
+        assert_eq!(priority, Some(3));
+    })
+    .await
+    .expect("Test should complete within timeout");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_other_protocol_properties_rejected_on_tcp() {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        let conn = create_test_connection().await;
+
+        let result = conn
+            .set_property(
+                "quic.maxStreamsBidi",
+                ConnectionProperty::QuicMaxStreamsBidi(100),
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(TransportServicesError::NotSupported(_))
+        ));
+
+        let result = conn
+            .set_property(
+                "sctp.maxRetransmissions",
+                ConnectionProperty::SctpMaxRetransmissions(5),
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(TransportServicesError::NotSupported(_))
+        ));
+        assert!(conn.get_property("quic.maxStreamsBidi").await.is_none());
+
+        // TCP properties still apply
+        conn.set_property(
+            "tcp.userTimeoutEnabled",
+            ConnectionProperty::TcpUserTimeoutEnabled(true),
+        )
+        .await
+        .expect("TCP property should apply to a TCP connection");
+    })
+    .await
+    .expect("Test should not time out");
+}
+
+#[tokio::test]
+async fn test_protocol_properties_accepted_before_establishment() {
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder()
+            .hostname("example.com")
+            .port(443)
+            .build()],
+        TransportProperties::default(),
+        SecurityParameters::new_disabled(),
+    );
+    let conn = Connection::new_with_data(
+        preconn,
+        ConnectionState::Establishing,
+        None,
+        None,
+        TransportProperties::default(),
+    );
+
+    conn.set_property(
+        "quic.maxIdleTimeout",
+        ConnectionProperty::QuicMaxIdleTimeout(TimeoutValue::Disabled),
+    )
+    .await
+    .expect("The stack is not selected yet");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_hop_limit_sets_ttl() {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        let conn = create_test_connection().await;
+        let default_ttl = conn
+            .inspect_tcp_socket(|socket| socket.ttl().unwrap())
+            .await
+            .expect("Should have a TCP stream");
+
+        conn.set_property("ipHopLimit", ConnectionProperty::IpHopLimit(Some(3)))
+            .await
+            .unwrap();
+        let ttl = conn
+            .inspect_tcp_socket(|socket| socket.ttl().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(ttl, 3);
+
+        // Unset restores the system default
+        #[cfg(target_os = "linux")]
+        {
+            conn.set_property("ipHopLimit", ConnectionProperty::IpHopLimit(None))
+                .await
+                .unwrap();
+            let ttl = conn
+                .inspect_tcp_socket(|socket| socket.ttl().unwrap())
+                .await
+                .unwrap();
+            assert_eq!(ttl, default_ttl);
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = default_ttl;
+    })
+    .await
+    .expect("Test should complete within timeout");
+}
+
+#[tokio::test]
+async fn test_hop_limit_from_preconnection() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let _accepted = listener.accept().await;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    });
+
+    let mut properties = TransportProperties::default();
+    properties.connection_properties.hop_limit = Some(12);
+    let preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        properties,
+        SecurityParameters::new_disabled(),
+    );
+    let conn = preconn.initiate().await.unwrap();
+    conn.wait_for_established(Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+
+    match conn.get_property("ipHopLimit").await {
+        Some(ConnectionProperty::IpHopLimit(Some(12))) => {}
+        other => panic!("Expected ipHopLimit 12, got {other:?}"),
+    }
+    let ttl = conn
+        .inspect_tcp_socket(|socket| socket.ttl().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(ttl, 12);
+}
+
+/// A connected pair of client and accepted server connections
+#[cfg(target_os = "linux")]
+async fn connected_pair(properties: TransportProperties) -> (Connection, Connection, Listener) {
+    let server_preconn = Preconnection::new(
+        vec![LocalEndpoint::builder()
+            .ip_address("127.0.0.1".parse().unwrap())
+            .port(0)
+            .build()],
+        vec![],
+        properties.clone(),
+        SecurityParameters::new_disabled(),
+    );
+    let listener = server_preconn.listen().await.unwrap();
+    let addr = listener.local_addr().await.unwrap();
+
+    let client_preconn = Preconnection::new(
+        vec![],
+        vec![RemoteEndpoint::builder().socket_address(addr).build()],
+        properties,
+        SecurityParameters::new_disabled(),
+    );
+    let client = client_preconn.initiate().await.expect("Should connect");
+    let server = listener.accept().await.expect("Should accept");
+    client
+        .wait_for_established(Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+    (client, server, listener)
+}
+
+/// Receive window scale the kernel negotiated in the handshake
+#[cfg(target_os = "linux")]
+async fn receive_window_scale(conn: &Connection) -> u8 {
+    use std::os::fd::AsRawFd;
+
+    conn.inspect_tcp_socket(|socket| {
+        let mut info: libc::tcp_info = unsafe { std::mem::zeroed() };
+        let mut len = std::mem::size_of::<libc::tcp_info>() as libc::socklen_t;
+        let ret = unsafe {
+            libc::getsockopt(
+                socket.as_raw_fd(),
+                libc::IPPROTO_TCP,
+                libc::TCP_INFO,
+                &mut info as *mut _ as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        assert_eq!(ret, 0);
+        // The receive scale is the upper half of the bitfield
+        info.tcpi_snd_rcv_wscale >> 4
+    })
+    .await
+    .expect("Should have a TCP stream")
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_receive_buffer_size_applies_before_the_handshake() {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        let (client, server, listener) = connected_pair(TransportProperties::default()).await;
+        let default_scales = (
+            receive_window_scale(&client).await,
+            receive_window_scale(&server).await,
+        );
+        listener.stop().await.unwrap();
+
+        // A small buffer set before connect and listen shrinks the window
+        // scale both sides offer; set afterwards, it could not
+        let small = TransportProperties::builder()
+            .receive_buffer_size(4096)
+            .build();
+        let (client, server, listener) = connected_pair(small).await;
+        assert!(receive_window_scale(&client).await < default_scales.0);
+        assert!(receive_window_scale(&server).await < default_scales.1);
+        listener.stop().await.unwrap();
+    })
+    .await
+    .expect("Test should complete within timeout");
+}
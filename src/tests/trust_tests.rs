@@ -0,0 +1,130 @@
+//! Tests for composable and registered trust policies
+
+use crate::trust::{register_trust_verifier, registered_trust_verifier, unregister_trust_verifier};
+use crate::*;
+use std::sync::Arc;
+
+fn chain(leaf: &[u8]) -> CertificateChain {
+    CertificateChain {
+        certificates: vec![Certificate {
+            data: leaf.to_vec(),
+        }],
+    }
+}
+
+fn leaf_is(expected: &'static [u8]) -> Arc<dyn TrustVerifier> {
+    Arc::new(move |chain: &CertificateChain| {
+        chain
+            .certificates
+            .first()
+            .is_some_and(|leaf| leaf.data == expected)
+    })
+}
+
+#[test]
+fn test_all_of_and_any_of() {
+    let a = chain(b"a");
+    let b = chain(b"b");
+
+    let both = AllOf(vec![leaf_is(b"a"), Arc::new(|_: &CertificateChain| true)]);
+    assert!(both.verify(&a));
+    assert!(!both.verify(&b));
+
+    let either = AnyOf(vec![leaf_is(b"a"), leaf_is(b"b")]);
+    assert!(either.verify(&a));
+    assert!(either.verify(&b));
+    assert!(!either.verify(&chain(b"c")));
+
+    // Empty compositions are the identities of their operator
+    assert!(AllOf::default().verify(&a));
+    assert!(!AnyOf::default().verify(&a));
+}
+
+#[test]
+fn test_trust_policy_survives_clone() {
+    let mut params = SecurityParameters::new();
+    params
+        .set_trust_verifier(leaf_is(b"a"))
+        .set_identity_challenge_callback(|challenge: &[u8]| {
+            challenge.iter().rev().copied().collect()
+        });
+
+    let cloned = params.clone();
+    let verifier = cloned.trust_verifier.expect("Verifier should be cloned");
+    assert!(verifier.verify(&chain(b"a")));
+    assert!(!verifier.verify(&chain(b"b")));
+    let provider = cloned.identity_provider.expect("Provider should be cloned");
+    assert_eq!(provider.respond(b"abc"), b"cba");
+}
+
+/// Accepts early data that only reads
+struct SafeMethods;
+
+impl ReplayPolicy for SafeMethods {
+    fn is_replay_safe(&self, data: &[u8]) -> bool {
+        data.starts_with(b"GET ") || data.starts_with(b"HEAD ")
+    }
+}
+
+#[test]
+fn test_listener_policies_are_trait_objects() {
+    let mut params = SecurityParameters::new();
+    params
+        .set_replay_policy(Arc::new(SafeMethods))
+        .set_certificate_selection_callback(|server_name| match server_name {
+            Some("b.example") => Some(1),
+            _ => Some(0),
+        });
+    assert!(params.accept_early_data);
+
+    let cloned = params.clone();
+    let policy = cloned.replay_policy.expect("Policy should be cloned");
+    assert!(policy.is_replay_safe(b"GET /"));
+    assert!(!policy.is_replay_safe(b"POST /"));
+    let selector = cloned
+        .certificate_selector
+        .expect("Selector should be cloned");
+    assert_eq!(selector.select(Some("b.example")), Some(1));
+    assert_eq!(selector.select(None), Some(0));
+}
+
+#[test]
+fn test_add_trust_verifier_requires_all() {
+    let mut params = SecurityParameters::new();
+    params
+        .add_trust_verifier(leaf_is(b"a"))
+        .add_trust_verifier(Arc::new(|chain: &CertificateChain| {
+            chain.certificates.len() == 1
+        }));
+
+    let verifier = params.trust_verifier.unwrap();
+    assert!(verifier.verify(&chain(b"a")));
+    assert!(!verifier.verify(&chain(b"b")));
+    let mut longer = chain(b"a");
+    longer.certificates.push(Certificate {
+        data: b"ca".to_vec(),
+    });
+    assert!(!verifier.verify(&longer));
+}
+
+#[test]
+fn test_registered_trust_verifier() {
+    register_trust_verifier("trust-tests-a", leaf_is(b"a"));
+    assert!(registered_trust_verifier("trust-tests-a")
+        .unwrap()
+        .verify(&chain(b"a")));
+
+    let mut params = SecurityParameters::new();
+    params
+        .add_registered_trust_verifier("trust-tests-a")
+        .unwrap();
+    assert!(matches!(
+        params.add_registered_trust_verifier("trust-tests-missing"),
+        Err(TransportServicesError::InvalidParameters(_))
+    ));
+
+    // Parameters already using a verifier keep it once unregistered
+    assert!(unregister_trust_verifier("trust-tests-a").is_some());
+    assert!(registered_trust_verifier("trust-tests-a").is_err());
+    assert!(params.trust_verifier.unwrap().verify(&chain(b"a")));
+}
@@ -0,0 +1,166 @@
+//! Tests for UDP segmentation and receive offload
+
+use crate::udp_offload::{self, MAX_SEGMENTS};
+use crate::*;
+use std::time::Duration;
+use tokio::time::timeout;
+
+async fn datagram_listener(offload: bool) -> Listener {
+    let preconn = Preconnection::new(
+        vec![LocalEndpoint {
+            identifiers: vec![
+                EndpointIdentifier::IpAddress("127.0.0.1".parse().unwrap()),
+                EndpointIdentifier::Port(0),
+            ],
+        }],
+        vec![],
+        TransportProperties::builder()
+            .reliability(Preference::Prohibit)
+            .udp_offload(offload)
+            .build(),
+        SecurityParameters::new_disabled(),
+    );
+    preconn.listen().await.unwrap()
+}
+
+async fn next_datagram(conn: &Connection) -> Vec<u8> {
+    loop {
+        match timeout(Duration::from_secs(2), conn.next_event()).await {
+            Ok(Some(ConnectionEvent::Received { message_data, .. })) => return message_data,
+            Ok(Some(_)) => continue,
+            other => panic!("Expected Received event, got {other:?}"),
+        }
+    }
+}
+
+/// Accept the flow of a peer, which sends a first datagram to open it
+async fn accept_peer(listener: &Listener, peer: &tokio::net::UdpSocket) -> Connection {
+    let addr = listener.local_addr().await.unwrap();
+    peer.send_to(b"hello", addr).await.unwrap();
+    let conn = timeout(Duration::from_secs(2), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(next_datagram(&conn).await, b"hello");
+    conn
+}
+
+#[test]
+fn test_grouping_follows_segment_rules() {
+    let lengths = |groups: Vec<Vec<usize>>| {
+        groups
+            .into_iter()
+            .map(|group| group.len())
+            .collect::<Vec<_>>()
+    };
+
+    // Equal datagrams share a group, which a shorter one ends
+    let groups = udp_offload::group(vec![100, 100, 100, 40, 100], |n| *n, |_| ());
+    assert_eq!(lengths(groups), vec![4, 1]);
+
+    // A longer datagram cannot follow
+    let groups = udp_offload::group(vec![50, 50, 80, 80], |n| *n, |_| ());
+    assert_eq!(lengths(groups), vec![2, 2]);
+
+    // Datagrams with different keys go apart
+    let groups = udp_offload::group(vec![10, 10, 10], |n| *n, |n| *n == 10);
+    assert_eq!(lengths(groups), vec![3]);
+    let datagrams = vec![(10, 'a'), (10, 'a'), (10, 'b')];
+    let groups = udp_offload::group(datagrams, |(n, _)| *n, |(_, key)| *key);
+    assert_eq!(groups.iter().map(Vec::len).collect::<Vec<_>>(), vec![2, 1]);
+
+    // Groups stay within the segment count and payload limits
+    let groups = udp_offload::group(vec![10; MAX_SEGMENTS + 1], |n| *n, |_| ());
+    assert_eq!(lengths(groups), vec![MAX_SEGMENTS, 1]);
+    let groups = udp_offload::group(vec![30000; 3], |n| *n, |_| ());
+    assert_eq!(lengths(groups), vec![2, 1]);
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_batched_datagrams_share_a_send_call() {
+    let listener = datagram_listener(true).await;
+    let peer = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let conn = accept_peer(&listener, &peer).await;
+
+    let mut batch = conn.batch();
+    for i in 0..8u8 {
+        batch.add(vec![i; 200]);
+    }
+    batch.add(vec![8u8; 20]);
+    batch.commit().await.unwrap();
+
+    // The peer still sees every datagram on its own, in order
+    let mut buffer = [0u8; 1024];
+    for i in 0..9u8 {
+        let (n, _) = timeout(Duration::from_secs(2), peer.recv_from(&mut buffer))
+            .await
+            .unwrap()
+            .unwrap();
+        let expected = if i < 8 { vec![i; 200] } else { vec![8; 20] };
+        assert_eq!(&buffer[..n], expected.as_slice());
+    }
+
+    let stats = conn.stats().await;
+    assert_eq!(stats.datagrams_sent, 9);
+    assert_eq!(stats.bytes_sent, 8 * 200 + 20);
+    // Kernels or devices without segmentation offload fall back to one call each
+    assert!(
+        stats.datagram_send_calls == 1 || stats.datagram_send_calls == 9,
+        "{stats:?}"
+    );
+
+    listener.stop().await.unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_offload_can_be_disabled() {
+    let listener = datagram_listener(false).await;
+    let peer = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let conn = accept_peer(&listener, &peer).await;
+
+    let mut batch = conn.batch();
+    for _ in 0..4 {
+        batch.add(vec![1u8; 100]);
+    }
+    batch.commit().await.unwrap();
+
+    let stats = conn.stats().await;
+    assert_eq!(stats.datagrams_sent, 4);
+    assert_eq!(stats.datagram_send_calls, 4);
+
+    listener.stop().await.unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_coalesced_datagrams_are_split_on_receive() {
+    let listener = datagram_listener(true).await;
+    let addr = listener.local_addr().await.unwrap();
+    let peer = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let conn = accept_peer(&listener, &peer).await;
+
+    // A segmented send arrives coalesced where the kernel supports GRO
+    let mut buffer = Vec::new();
+    for i in 0..4u8 {
+        buffer.extend_from_slice(&[i; 300]);
+    }
+    buffer.extend_from_slice(&[4u8; 10]);
+    match udp_offload::send_segmented(&peer, &buffer, 300, addr).await {
+        Ok(_) => {}
+        Err(e) if udp_offload::is_unsupported(&e) => {
+            for chunk in buffer.chunks(300) {
+                peer.send_to(chunk, addr).await.unwrap();
+            }
+        }
+        Err(e) => panic!("Segmented send failed: {e}"),
+    }
+
+    for i in 0..4u8 {
+        assert_eq!(next_datagram(&conn).await, vec![i; 300]);
+    }
+    assert_eq!(next_datagram(&conn).await, vec![4u8; 10]);
+
+    listener.stop().await.unwrap();
+}
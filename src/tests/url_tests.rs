@@ -0,0 +1,137 @@
+//! Tests for converting between URLs and endpoints
+
+use crate::*;
+
+#[test]
+fn test_remote_endpoint_from_url() {
+    let endpoint = RemoteEndpoint::from_url("quic://example.com?alpn=h3").unwrap();
+    assert_eq!(endpoint.protocol, Some(Protocol::QUIC));
+    assert_eq!(
+        endpoint.identifiers,
+        vec![
+            EndpointIdentifier::HostName("example.com".to_string()),
+            EndpointIdentifier::Port(443),
+        ]
+    );
+
+    let endpoint = RemoteEndpoint::from_url("TCP://192.0.2.1:8080/ignored").unwrap();
+    assert_eq!(endpoint.protocol, Some(Protocol::TCP));
+    assert_eq!(
+        endpoint.identifiers,
+        vec![
+            EndpointIdentifier::IpAddress("192.0.2.1".parse().unwrap()),
+            EndpointIdentifier::Port(8080),
+        ]
+    );
+
+    let endpoint = RemoteEndpoint::from_url("udp://[fe80::1%25en0]:5353").unwrap();
+    assert_eq!(
+        endpoint.identifiers,
+        vec![
+            EndpointIdentifier::IpAddress("fe80::1".parse().unwrap()),
+            EndpointIdentifier::Interface("en0".to_string()),
+            EndpointIdentifier::Port(5353),
+        ]
+    );
+
+    let endpoint = RemoteEndpoint::from_url("https://example.com").unwrap();
+    assert_eq!(endpoint.protocol, Some(Protocol::TLS));
+    assert_eq!(endpoint.identifiers[1], EndpointIdentifier::Port(443));
+}
+
+#[test]
+fn test_remote_endpoint_from_invalid_url() {
+    for url in [
+        "example.com:443",
+        "tcp://example.com",
+        "tcp://:80",
+        "tcp://example.com:http",
+        "tcp://::1:80",
+        "tcp://[192.0.2.1]:80",
+        "tcp://[::1:80",
+        "tcp://user@example.com:80",
+    ] {
+        assert!(
+            matches!(
+                RemoteEndpoint::from_url(url),
+                Err(TransportServicesError::InvalidParameters(_))
+            ),
+            "{url} was accepted"
+        );
+    }
+    assert!(matches!(
+        RemoteEndpoint::from_url("gopher://example.com:70"),
+        Err(TransportServicesError::NotSupported(_))
+    ));
+}
+
+#[test]
+fn test_remote_endpoint_to_url() {
+    for url in [
+        "quic://example.com:443",
+        "tcp://192.0.2.1:8080",
+        "udp://[fe80::1%25en0]:5353",
+        "tls://[2001:db8::1]:853",
+    ] {
+        let endpoint = RemoteEndpoint::from_url(url).unwrap();
+        assert_eq!(endpoint.to_url().as_deref(), Some(url));
+    }
+
+    // Endpoints without a protocol are written as TCP
+    let endpoint = RemoteEndpoint::builder()
+        .socket_address("[::1]:80".parse().unwrap())
+        .build();
+    assert_eq!(endpoint.to_url().as_deref(), Some("tcp://[::1]:80"));
+
+    // Without a port there is no URL
+    let endpoint = RemoteEndpoint::builder().hostname("example.com").build();
+    assert_eq!(endpoint.to_url(), None);
+}
+
+#[tokio::test]
+async fn test_preconnection_from_url() {
+    let preconn =
+        Preconnection::from_url("quic://example.com:4433?alpn=h3,h3-29&alpn=hq%2Dinterop").unwrap();
+    let security = preconn.security_parameters().await;
+    assert!(!security.disabled);
+    assert_eq!(security.alpn, vec!["h3", "h3-29", "hq-interop"]);
+
+    let preconn = Preconnection::from_url("udp://192.0.2.1:53").unwrap();
+    assert!(preconn.security_parameters().await.disabled);
+    assert_eq!(
+        preconn
+            .transport_properties()
+            .await
+            .selection_properties
+            .reliability,
+        Preference::Prohibit
+    );
+
+    for url in [
+        "tcp://example.com:80?alpn=h2",
+        "tls://example.com:443?timeout=5",
+        "tls://example.com:443?alpn=%zz",
+    ] {
+        assert!(
+            matches!(
+                Preconnection::from_url(url),
+                Err(TransportServicesError::InvalidParameters(_))
+            ),
+            "{url} was accepted"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_unavailable_protocol_fails_initiate() {
+    if capabilities().quic {
+        return;
+    }
+    let preconn = Preconnection::from_url("quic://127.0.0.1:443").unwrap();
+    match preconn.initiate().await {
+        Err(TransportServicesError::NotSupported(error)) => {
+            assert!(error.contains("QUIC"), "{error}")
+        }
+        other => panic!("Expected NotSupported, got {:?}", other.map(|_| ())),
+    }
+}
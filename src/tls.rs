@@ -0,0 +1,1253 @@
+//! TLS over an established TCP connection
+//!
+//! The session is driven without owning the socket: the connection passes it
+//! the bytes it reads and writes the records it produces, so framing, fault
+//! injection and shaping keep working on a secured connection.
+//!
+//! Clients start sessions in-band with `Connection::start_security`;
+//! Listeners with server identities terminate TLS on every accepted
+//! connection, presenting the identity chosen by the client's SNI.
+//!
+//! Peers pinned by certificate fingerprint or raw public key (RFC 7250) are
+//! authenticated by the pin alone, so endpoints with self-signed identities
+//! can secure peer-to-peer connections.
+//!
+//! Listeners resume sessions from tickets, kept in a cache of their own
+//! unless stateless tickets are configured; cached tickets are single-use,
+//! which is what makes accepting 0-RTT data on them safe against replay to
+//! the same Listener.
+//!
+//! With the `keylog` feature, session secrets are written in the NSS key log
+//! format so captures can be decrypted, e.g. in Wireshark.
+
+use crate::{
+    Certificate, CertificateChain, ClientCertificatePolicy, PeerPin, Result, SecurityParameters,
+    SecurityProtocol, ServerIdentity, TransportServicesError, TrustVerifier,
+};
+use std::collections::{HashMap, VecDeque};
+use std::io::{Read, Write};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::client::{ResolvesClientCert, WebPkiServerVerifier};
+use tokio_rustls::rustls::crypto::{CryptoProvider, WebPkiSupportedAlgorithms};
+use tokio_rustls::rustls::pki_types::UnixTime;
+use tokio_rustls::rustls::pki_types::{
+    CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, SubjectPublicKeyInfoDer,
+};
+use tokio_rustls::rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use tokio_rustls::rustls::server::{
+    AlwaysResolvesServerRawPublicKeys, ClientHello, ProducesTickets, ResolvesServerCert,
+    StoresServerSessions, WebPkiClientVerifier,
+};
+use tokio_rustls::rustls::sign::CertifiedKey;
+use tokio_rustls::rustls::{
+    self, ClientConfig, ClientConnection, RootCertStore, ServerConfig, ServerConnection,
+};
+use tokio_rustls::rustls::{DigitallySignedStruct, DistinguishedName, SignatureScheme};
+
+/// 0-RTT data a Listener accepts from a resuming client
+const MAX_EARLY_DATA: u32 = 16 * 1024;
+
+/// Sessions a Listener keeps for resumption when not configured
+const DEFAULT_CACHED_SESSIONS: usize = 256;
+
+/// How long resumable sessions stay valid when not configured
+const DEFAULT_SESSION_LIFETIME: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Trust configurations whose verifiers a Preconnection keeps
+const MAX_CLIENT_CREDENTIALS: usize = 16;
+
+/// Client sessions shared by the connections a Preconnection secures
+///
+/// rustls only resumes a session with the verifier and client credentials
+/// it was established with, so these are kept alongside the sessions, one
+/// pair per trust configuration.
+pub(crate) struct ClientSessions {
+    store: Arc<dyn rustls::client::ClientSessionStore>,
+    credentials: Mutex<VecDeque<(TrustKey, ClientCredentials)>>,
+}
+
+type ClientCredentials = (Arc<dyn ServerCertVerifier>, Arc<dyn ResolvesClientCert>);
+
+/// What the verifier and client credentials are built from
+#[derive(PartialEq)]
+struct TrustKey {
+    anchors: Vec<Vec<u8>>,
+    pins: Vec<PeerPin>,
+    raw_public_keys: bool,
+    identity: Option<(Vec<Vec<u8>>, Vec<u8>)>,
+    // Address of the trust verifier, which is shared rather than compared
+    trust: Option<usize>,
+}
+
+impl ClientSessions {
+    /// Keep up to `capacity` sessions, keyed by server name
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            store: Arc::new(rustls::client::ClientSessionMemoryCache::new(capacity)),
+            credentials: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// The verifier and client credentials for `parameters`, reused while
+    /// the trust configuration stays the same
+    fn credentials(
+        &self,
+        parameters: &SecurityParameters,
+        provider: &Arc<CryptoProvider>,
+    ) -> Result<ClientCredentials> {
+        let key = TrustKey {
+            anchors: parameters
+                .pinned_server_certificate
+                .iter()
+                .flat_map(|chain| &chain.certificates)
+                .map(|certificate| certificate.data.clone())
+                .collect(),
+            pins: parameters.pinned_peers.clone(),
+            raw_public_keys: parameters.raw_public_keys,
+            identity: parameters.client_identity.as_ref().map(|identity| {
+                let chain = identity.certificate_chain.certificates.iter();
+                let chain = chain.map(|certificate| certificate.data.clone());
+                (chain.collect(), identity.private_key.clone())
+            }),
+            trust: parameters
+                .trust_verifier
+                .as_ref()
+                .map(|verifier| Arc::as_ptr(verifier) as *const () as usize),
+        };
+        let mut cached = self.credentials.lock().unwrap();
+        if let Some((_, credentials)) = cached.iter().find(|(k, _)| *k == key) {
+            return Ok(credentials.clone());
+        }
+
+        let verifier: Arc<dyn ServerCertVerifier> = if parameters.pinned_peers.is_empty() {
+            let mut roots = RootCertStore::empty();
+            for anchor in &key.anchors {
+                roots
+                    .add(CertificateDer::from(anchor.clone()))
+                    .map_err(|e| TransportServicesError::SecurityError(e.to_string()))?;
+            }
+            if roots.is_empty() {
+                return Err(TransportServicesError::InvalidParameters(
+                    "No trust anchors: set pinned_server_certificate or pin the peer".to_string(),
+                ));
+            }
+            let anchors =
+                WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                    .build()
+                    .map_err(|e| TransportServicesError::InvalidParameters(e.to_string()))?;
+            match &parameters.trust_verifier {
+                Some(trust) => Arc::new(TrustedServerVerifier {
+                    anchors,
+                    trust: trust.clone(),
+                }),
+                None => anchors,
+            }
+        } else {
+            Arc::new(PeerVerifier::new(parameters, true, provider)?)
+        };
+        let identity = match &parameters.client_identity {
+            Some(identity) if parameters.raw_public_keys => Some(raw_key(identity, provider)?),
+            Some(identity) => Some(certified_key(identity, provider)?),
+            None => None,
+        };
+        let resolver = Arc::new(IdentityPresenter {
+            identity,
+            raw_public_keys: parameters.raw_public_keys,
+        });
+
+        let credentials: ClientCredentials = (verifier, resolver);
+        if cached.len() == MAX_CLIENT_CREDENTIALS {
+            cached.pop_front();
+        }
+        cached.push_back((key, credentials.clone()));
+        Ok(credentials)
+    }
+}
+
+/// Presents the client identity, if any, when a server asks for one
+#[derive(Debug)]
+struct IdentityPresenter {
+    identity: Option<Arc<CertifiedKey>>,
+    raw_public_keys: bool,
+}
+
+impl ResolvesClientCert for IdentityPresenter {
+    fn resolve(
+        &self,
+        _root_hint_subjects: &[&[u8]],
+        _sigschemes: &[SignatureScheme],
+    ) -> Option<Arc<CertifiedKey>> {
+        self.identity.clone()
+    }
+
+    fn only_raw_public_keys(&self) -> bool {
+        self.raw_public_keys
+    }
+
+    fn has_certs(&self) -> bool {
+        self.identity.is_some()
+    }
+}
+
+/// Either side of a TLS session
+pub(crate) struct TlsSession {
+    connection: rustls::Connection,
+    // 0-RTT data received on a server session during the handshake
+    early_data: Vec<u8>,
+    replay_policy: Option<Arc<dyn crate::ReplayPolicy>>,
+}
+
+impl TlsSession {
+    /// Create a client session for the given server
+    ///
+    /// The certificates in `pinned_server_certificate` are the trust anchors,
+    /// unless peers are pinned, `allowed_protocols` limits the TLS versions
+    /// and `alpn` is offered to the server. The client identity, if any, is
+    /// presented when the server asks for one. Sessions are resumed from and
+    /// stored in `sessions`.
+    pub(crate) fn client(
+        parameters: &SecurityParameters,
+        server: TlsServer,
+        sessions: &ClientSessions,
+    ) -> Result<Self> {
+        if parameters.disabled {
+            return Err(TransportServicesError::InvalidParameters(
+                "Security is disabled in the given parameters".to_string(),
+            ));
+        }
+
+        let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+        let (verifier, credentials) = sessions.credentials(parameters, &provider)?;
+        let versions = session_versions(parameters)?;
+        let mut config = ClientConfig::builder_with_provider(provider)
+            .with_protocol_versions(&versions)
+            .map_err(|e| TransportServicesError::SecurityError(e.to_string()))?
+            .dangerous()
+            .with_custom_certificate_verifier(verifier)
+            .with_client_cert_resolver(credentials);
+        config.alpn_protocols = parameters
+            .alpn
+            .iter()
+            .map(|protocol| protocol.as_bytes().to_vec())
+            .collect();
+        config.resumption = rustls::client::Resumption::store(Arc::clone(&sessions.store));
+        #[cfg(feature = "keylog")]
+        if let Some(key_log) = key_log(parameters)? {
+            config.key_log = key_log;
+        }
+
+        let name = match server {
+            TlsServer::Name(name) => ServerName::try_from(name)
+                .map_err(|e| TransportServicesError::InvalidParameters(e.to_string()))?,
+            TlsServer::Address(address) => ServerName::IpAddress(address.into()),
+        };
+        let connection = ClientConnection::new(Arc::new(config), name)
+            .map_err(|e| TransportServicesError::SecurityError(e.to_string()))?;
+        Ok(Self::new(connection.into()))
+    }
+
+    /// Create a server session, e.g. for a connection a Listener accepted
+    pub(crate) fn server(settings: &ServerSettings) -> Result<Self> {
+        let connection = ServerConnection::new(Arc::clone(&settings.config))
+            .map_err(|e| TransportServicesError::SecurityError(e.to_string()))?;
+        let mut session = Self::new(connection.into());
+        session.replay_policy = settings.replay_policy.clone();
+        Ok(session)
+    }
+
+    fn new(connection: rustls::Connection) -> Self {
+        Self {
+            connection,
+            early_data: Vec::new(),
+            replay_policy: None,
+        }
+    }
+
+    /// Run the handshake on the stream
+    ///
+    /// Returns any application data the peer sent along with its last
+    /// handshake records, after any 0-RTT data it sent. Early data the
+    /// application does not declare replay-safe fails the handshake.
+    pub(crate) async fn handshake(&mut self, stream: &mut TcpStream) -> Result<Vec<u8>> {
+        let mut buffer = [0u8; 8192];
+        let mut received = Vec::new();
+        loop {
+            let output = self.take_output()?;
+            if !output.is_empty() {
+                stream.write_all(&output).await?;
+            }
+            if !self.connection.is_handshaking() {
+                if self.early_data.is_empty() {
+                    return Ok(received);
+                }
+                // Early data is only accepted with a check configured
+                let replay_safe = self
+                    .replay_policy
+                    .as_ref()
+                    .is_some_and(|policy| policy.is_replay_safe(&self.early_data));
+                if !replay_safe {
+                    return Err(TransportServicesError::SecurityError(
+                        "Early data is not replay-safe".to_string(),
+                    ));
+                }
+                let mut early_data = std::mem::take(&mut self.early_data);
+                early_data.extend(received);
+                return Ok(early_data);
+            }
+
+            let n = stream.read(&mut buffer).await?;
+            if n == 0 {
+                return Err(TransportServicesError::SecurityError(
+                    "Connection closed during TLS handshake".to_string(),
+                ));
+            }
+            match self.decrypt(&buffer[..n]) {
+                Ok(plaintext) => received.extend(plaintext),
+                Err(e) => {
+                    // Tell the peer why, if the session produced an alert
+                    if let Ok(alert) = self.take_output() {
+                        let _ = stream.write_all(&alert).await;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Encrypt application data into TLS records
+    pub(crate) fn encrypt(&mut self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        self.connection.writer().write_all(plaintext)?;
+        self.take_output()
+    }
+
+    /// Decrypt TLS records received from the peer
+    pub(crate) fn decrypt(&mut self, mut records: &[u8]) -> Result<Vec<u8>> {
+        let mut plaintext = Vec::new();
+        while !records.is_empty() {
+            self.connection.read_tls(&mut records)?;
+            self.connection
+                .process_new_packets()
+                .map_err(|e| TransportServicesError::SecurityError(e.to_string()))?;
+            if let rustls::Connection::Server(server) = &mut self.connection {
+                if let Some(mut early_data) = server.early_data() {
+                    early_data.read_to_end(&mut self.early_data)?;
+                }
+            }
+            match self.connection.reader().read_to_end(&mut plaintext) {
+                Ok(_) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(TransportServicesError::SecurityError(e.to_string())),
+            }
+        }
+        Ok(plaintext)
+    }
+
+    /// Records the session needs to send, e.g. in response to a key update
+    pub(crate) fn take_output(&mut self) -> Result<Vec<u8>> {
+        let mut output = Vec::new();
+        while self.connection.wants_write() {
+            self.connection.write_tls(&mut output)?;
+        }
+        Ok(output)
+    }
+
+    /// The close_notify alert that ends the session
+    pub(crate) fn close_notify(&mut self) -> Vec<u8> {
+        self.connection.send_close_notify();
+        self.take_output().unwrap_or_default()
+    }
+
+    /// The negotiated TLS version
+    pub(crate) fn protocol(&self) -> Option<SecurityProtocol> {
+        match self.connection.protocol_version()? {
+            rustls::ProtocolVersion::TLSv1_2 => Some(SecurityProtocol::TLS12),
+            rustls::ProtocolVersion::TLSv1_3 => Some(SecurityProtocol::TLS13),
+            _ => None,
+        }
+    }
+
+    /// The application protocol the server selected
+    pub(crate) fn alpn(&self) -> Option<String> {
+        self.connection
+            .alpn_protocol()
+            .map(|protocol| String::from_utf8_lossy(protocol).into_owned())
+    }
+
+    /// The certificate chain the peer authenticated with, if it sent one
+    pub(crate) fn peer_certificates(&self) -> Option<CertificateChain> {
+        let certificates = self.connection.peer_certificates()?;
+        Some(CertificateChain {
+            certificates: certificates
+                .iter()
+                .map(|certificate| Certificate {
+                    data: certificate.to_vec(),
+                })
+                .collect(),
+        })
+    }
+
+    /// The server name the client asked for (SNI), on a server session
+    pub(crate) fn server_name(&self) -> Option<String> {
+        match &self.connection {
+            rustls::Connection::Server(server) => server.server_name().map(str::to_string),
+            rustls::Connection::Client(_) => None,
+        }
+    }
+}
+
+/// What a Listener terminates TLS with
+#[derive(Clone)]
+pub(crate) struct ServerSettings {
+    config: Arc<ServerConfig>,
+    replay_policy: Option<Arc<dyn crate::ReplayPolicy>>,
+}
+
+/// Build the settings a Listener terminates TLS with
+///
+/// Returns None when the parameters hold no server identities, so accepted
+/// connections stay in cleartext.
+pub(crate) fn server_config(parameters: &SecurityParameters) -> Result<Option<ServerSettings>> {
+    if parameters.disabled || parameters.server_identities.is_empty() {
+        return Ok(None);
+    }
+
+    let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+    // Raw public keys carry no names to choose by, so the first is presented
+    let resolver: Arc<dyn ResolvesServerCert> = if parameters.raw_public_keys {
+        Arc::new(AlwaysResolvesServerRawPublicKeys::new(raw_key(
+            &parameters.server_identities[0],
+            &provider,
+        )?))
+    } else {
+        let identities = parameters
+            .server_identities
+            .iter()
+            .map(|identity| certified_key(identity, &provider))
+            .collect::<Result<Vec<_>>>()?;
+        Arc::new(IdentityResolver {
+            identities: parameters.server_identities.clone(),
+            keys: identities,
+            selector: parameters.certificate_selector.clone(),
+        })
+    };
+
+    let client_verifier: Arc<dyn ClientCertVerifier> = match parameters.client_certificate_policy {
+        _ if !parameters.pinned_peers.is_empty() => Arc::new(PeerVerifier::new(
+            parameters,
+            parameters.client_certificate_policy != ClientCertificatePolicy::Request,
+            &provider,
+        )?),
+        ClientCertificatePolicy::None => WebPkiClientVerifier::no_client_auth(),
+        policy => Arc::new(ClientAuthVerifier::new(parameters, policy, &provider)?),
+    };
+
+    let versions = session_versions(parameters)?;
+    let mut config = ServerConfig::builder_with_provider(provider)
+        .with_protocol_versions(&versions)
+        .map_err(|e| TransportServicesError::SecurityError(e.to_string()))?
+        .with_client_cert_verifier(client_verifier)
+        .with_cert_resolver(resolver);
+    config.alpn_protocols = parameters
+        .alpn
+        .iter()
+        .map(|protocol| protocol.as_bytes().to_vec())
+        .collect();
+    #[cfg(feature = "keylog")]
+    if let Some(key_log) = key_log(parameters)? {
+        config.key_log = key_log;
+    }
+
+    // Resumption: tickets are either looked up in a cache of single-use
+    // sessions, which 0-RTT needs, or decrypted statelessly
+    let lifetime = parameters
+        .cached_session_lifetime_seconds
+        .map_or(DEFAULT_SESSION_LIFETIME, Duration::from_secs);
+    let capacity = parameters
+        .max_cached_sessions
+        .unwrap_or(DEFAULT_CACHED_SESSIONS);
+    config.send_tls13_tickets = parameters.session_tickets;
+    if parameters.stateless_session_tickets {
+        if parameters.accept_early_data {
+            return Err(TransportServicesError::InvalidParameters(
+                "0-RTT data cannot be accepted with stateless session tickets".to_string(),
+            ));
+        }
+        config.ticketer = Arc::new(TimedTicketer::new(lifetime)?);
+    } else {
+        config.session_storage = Arc::new(SessionCache::new(capacity, lifetime));
+    }
+    // Without a check for replay safety, 0-RTT data stays refused
+    if parameters.accept_early_data && parameters.replay_policy.is_some() {
+        config.max_early_data_size = MAX_EARLY_DATA;
+    }
+
+    Ok(Some(ServerSettings {
+        config: Arc::new(config),
+        replay_policy: parameters.replay_policy.clone(),
+    }))
+}
+
+/// Sessions a Listener can resume, each usable once and only within its
+/// lifetime
+struct SessionCache {
+    capacity: usize,
+    lifetime: Duration,
+    sessions: Mutex<CachedSessions>,
+}
+
+/// An encoded session and when it was stored
+type CachedSession = (Vec<u8>, Instant);
+
+#[derive(Default)]
+struct CachedSessions {
+    by_ticket: HashMap<Vec<u8>, CachedSession>,
+    // Tickets oldest first, for eviction
+    order: VecDeque<Vec<u8>>,
+}
+
+impl SessionCache {
+    fn new(capacity: usize, lifetime: Duration) -> Self {
+        Self {
+            capacity,
+            lifetime,
+            sessions: Mutex::default(),
+        }
+    }
+
+    fn live(&self, entry: Option<CachedSession>) -> Option<Vec<u8>> {
+        entry
+            .filter(|(_, stored)| stored.elapsed() <= self.lifetime)
+            .map(|(value, _)| value)
+    }
+}
+
+impl StoresServerSessions for SessionCache {
+    fn put(&self, key: Vec<u8>, value: Vec<u8>) -> bool {
+        if self.capacity == 0 {
+            return false;
+        }
+        let mut guard = self.sessions.lock().unwrap();
+        let CachedSessions { by_ticket, order } = &mut *guard;
+        while by_ticket.len() >= self.capacity {
+            match order.pop_front() {
+                Some(oldest) => by_ticket.remove(&oldest),
+                None => break,
+            };
+        }
+        order.push_back(key.clone());
+        by_ticket.insert(key, (value, Instant::now()));
+        true
+    }
+
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        let entry = self.sessions.lock().unwrap().by_ticket.get(key).cloned();
+        self.live(entry)
+    }
+
+    fn take(&self, key: &[u8]) -> Option<Vec<u8>> {
+        let mut guard = self.sessions.lock().unwrap();
+        let CachedSessions { by_ticket, order } = &mut *guard;
+        let entry = by_ticket.remove(key);
+        order.retain(|ticket| ticket.as_slice() != key);
+        drop(guard);
+        self.live(entry)
+    }
+
+    fn can_cache(&self) -> bool {
+        self.capacity > 0
+    }
+}
+
+impl std::fmt::Debug for SessionCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionCache")
+            .field("capacity", &self.capacity)
+            .field("lifetime", &self.lifetime)
+            .finish()
+    }
+}
+
+/// Stateless tickets that expire after the session lifetime
+///
+/// The issue time is sealed into each ticket, as the rotating ticket keys
+/// alone would accept tickets for hours.
+struct TimedTicketer {
+    inner: Arc<dyn ProducesTickets>,
+    lifetime: Duration,
+}
+
+impl TimedTicketer {
+    fn new(lifetime: Duration) -> Result<Self> {
+        let inner = rustls::crypto::aws_lc_rs::Ticketer::new()
+            .map_err(|e| TransportServicesError::SecurityError(e.to_string()))?;
+        Ok(Self { inner, lifetime })
+    }
+
+    fn now() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs())
+    }
+}
+
+impl ProducesTickets for TimedTicketer {
+    fn enabled(&self) -> bool {
+        true
+    }
+
+    fn lifetime(&self) -> u32 {
+        self.lifetime.as_secs().min(u64::from(u32::MAX)) as u32
+    }
+
+    fn encrypt(&self, plain: &[u8]) -> Option<Vec<u8>> {
+        let mut sealed = Self::now().to_be_bytes().to_vec();
+        sealed.extend_from_slice(plain);
+        self.inner.encrypt(&sealed)
+    }
+
+    fn decrypt(&self, cipher: &[u8]) -> Option<Vec<u8>> {
+        let sealed = self.inner.decrypt(cipher)?;
+        let (issued, plain) = sealed.split_first_chunk::<8>()?;
+        let age = Self::now().saturating_sub(u64::from_be_bytes(*issued));
+        (age <= self.lifetime.as_secs()).then(|| plain.to_vec())
+    }
+}
+
+impl std::fmt::Debug for TimedTicketer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TimedTicketer")
+            .field("lifetime", &self.lifetime)
+            .finish()
+    }
+}
+
+/// Where session secrets are written: the parameters' key log file, else
+/// the file named by SSLKEYLOGFILE, else nowhere
+#[cfg(feature = "keylog")]
+fn key_log(parameters: &SecurityParameters) -> Result<Option<Arc<dyn rustls::KeyLog>>> {
+    if let Some(path) = &parameters.key_log_file {
+        log::warn!("Writing TLS secrets to {}", path.display());
+        return Ok(Some(Arc::new(KeyLogWriter::open(path)?)));
+    }
+    if std::env::var_os("SSLKEYLOGFILE").is_some() {
+        log::warn!("Writing TLS secrets to SSLKEYLOGFILE");
+        return Ok(Some(Arc::new(rustls::KeyLogFile::new())));
+    }
+    Ok(None)
+}
+
+/// Appends secrets to a key log file, one NSS key log line per secret
+#[cfg(feature = "keylog")]
+struct KeyLogWriter {
+    file: std::sync::Mutex<std::fs::File>,
+}
+
+#[cfg(feature = "keylog")]
+impl KeyLogWriter {
+    fn open(path: &std::path::Path) -> Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(Self {
+            file: std::sync::Mutex::new(file),
+        })
+    }
+}
+
+#[cfg(feature = "keylog")]
+impl rustls::KeyLog for KeyLogWriter {
+    fn log(&self, label: &str, client_random: &[u8], secret: &[u8]) {
+        fn hex(bytes: &[u8]) -> String {
+            bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+        }
+        let line = format!("{label} {} {}\n", hex(client_random), hex(secret));
+        // Each line goes out in one write so concurrent sessions do not interleave
+        if let Err(e) = self.file.lock().unwrap().write_all(line.as_bytes()) {
+            log::debug!("Failed to write key log: {e}");
+        }
+    }
+
+    fn will_log(&self, _label: &str) -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "keylog")]
+impl std::fmt::Debug for KeyLogWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyLogWriter").finish_non_exhaustive()
+    }
+}
+
+fn certified_key(
+    identity: &ServerIdentity,
+    provider: &rustls::crypto::CryptoProvider,
+) -> Result<Arc<CertifiedKey>> {
+    let chain = identity
+        .certificate_chain
+        .certificates
+        .iter()
+        .map(|certificate| CertificateDer::from(certificate.data.clone()))
+        .collect::<Vec<_>>();
+    if chain.is_empty() {
+        return Err(TransportServicesError::InvalidParameters(format!(
+            "Server identity {:?} has no certificates",
+            identity.server_names
+        )));
+    }
+    CertifiedKey::from_der(chain, private_key(identity), provider)
+        .map(Arc::new)
+        .map_err(|e| {
+            TransportServicesError::InvalidParameters(format!(
+                "Server identity {:?}: {e}",
+                identity.server_names
+            ))
+        })
+}
+
+/// The identity's public key, presented in place of its certificate
+fn raw_key(
+    identity: &ServerIdentity,
+    provider: &rustls::crypto::CryptoProvider,
+) -> Result<Arc<CertifiedKey>> {
+    let key = provider
+        .key_provider
+        .load_private_key(private_key(identity))
+        .map_err(|e| TransportServicesError::InvalidParameters(e.to_string()))?;
+    let public_key = key.public_key().ok_or_else(|| {
+        TransportServicesError::NotSupported("Raw public keys for this key type".to_string())
+    })?;
+    let certificate = CertificateDer::from(public_key.to_vec());
+    Ok(Arc::new(CertifiedKey::new(vec![certificate], key)))
+}
+
+fn private_key(identity: &ServerIdentity) -> PrivateKeyDer<'static> {
+    PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(identity.private_key.clone()))
+}
+
+fn sha256(data: &[u8]) -> Vec<u8> {
+    let suite = rustls::crypto::aws_lc_rs::cipher_suite::TLS13_AES_128_GCM_SHA256;
+    let suite = suite.tls13().expect("TLS 1.3 suite");
+    suite.common.hash_provider.hash(data).as_ref().to_vec()
+}
+
+/// The platform's trusted root certificates, loaded once
+///
+/// Roots that fail to load are skipped; an empty chain means none could be.
+pub(crate) fn system_trust_anchors() -> CertificateChain {
+    static ANCHORS: once_cell::sync::Lazy<CertificateChain> = once_cell::sync::Lazy::new(|| {
+        let loaded = rustls_native_certs::load_native_certs();
+        for error in &loaded.errors {
+            log::warn!("Skipping system trust anchors: {error}");
+        }
+        CertificateChain {
+            certificates: loaded
+                .certs
+                .into_iter()
+                .map(|certificate| Certificate {
+                    data: certificate.to_vec(),
+                })
+                .collect(),
+        }
+    });
+    ANCHORS.clone()
+}
+
+/// Whether a presented leaf certificate, or raw public key, matches a pin
+pub(crate) fn pin_matches(pin: &PeerPin, leaf: &[u8]) -> bool {
+    match pin {
+        PeerPin::PublicKey(key) => key.as_slice() == leaf,
+        PeerPin::CertificateSha256(digest) => *digest == sha256(leaf),
+    }
+}
+
+/// SDP-style fingerprint of an identity's leaf certificate
+pub(crate) fn fingerprint(identity: &ServerIdentity) -> Result<String> {
+    let leaf = identity
+        .certificate_chain
+        .certificates
+        .first()
+        .ok_or_else(|| {
+            TransportServicesError::InvalidParameters("Identity has no certificates".to_string())
+        })?;
+    let digest = sha256(&leaf.data)
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<_>>();
+    Ok(format!("sha-256 {}", digest.join(":")))
+}
+
+/// DER SubjectPublicKeyInfo of an identity's private key
+pub(crate) fn public_key(identity: &ServerIdentity) -> Result<Vec<u8>> {
+    let provider = rustls::crypto::aws_lc_rs::default_provider();
+    let key = raw_key(identity, &provider)?;
+    Ok(key.cert[0].to_vec())
+}
+
+/// Chooses the certificate to present from the client's SNI
+struct IdentityResolver {
+    identities: Vec<ServerIdentity>,
+    keys: Vec<Arc<CertifiedKey>>,
+    selector: Option<Arc<dyn crate::CertificateSelector>>,
+}
+
+impl IdentityResolver {
+    fn select(&self, server_name: Option<&str>) -> Option<usize> {
+        if let Some(selector) = &self.selector {
+            return selector.select(server_name);
+        }
+        server_name
+            .and_then(|name| self.identities.iter().position(|id| id.matches(name)))
+            .or(Some(0))
+    }
+}
+
+impl ResolvesServerCert for IdentityResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let index = self.select(client_hello.server_name())?;
+        self.keys.get(index).cloned()
+    }
+}
+
+impl std::fmt::Debug for IdentityResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IdentityResolver")
+            .field("identities", &self.identities)
+            .finish()
+    }
+}
+
+/// Identity the server's certificate is checked against
+pub(crate) enum TlsServer {
+    Name(String),
+    Address(IpAddr),
+}
+
+/// TLS versions a session may negotiate; raw public keys need TLS 1.3
+fn session_versions(
+    parameters: &SecurityParameters,
+) -> Result<Vec<&'static rustls::SupportedProtocolVersion>> {
+    let mut versions = protocol_versions(&parameters.allowed_protocols)?;
+    if parameters.raw_public_keys {
+        versions.retain(|version| version.version == rustls::ProtocolVersion::TLSv1_3);
+        if versions.is_empty() {
+            return Err(TransportServicesError::NotSupported(
+                "Raw public keys need TLS 1.3".to_string(),
+            ));
+        }
+    }
+    Ok(versions)
+}
+
+fn protocol_versions(
+    allowed: &[SecurityProtocol],
+) -> Result<Vec<&'static rustls::SupportedProtocolVersion>> {
+    if allowed.is_empty() {
+        return Ok(vec![&rustls::version::TLS13, &rustls::version::TLS12]);
+    }
+    let versions = allowed
+        .iter()
+        .filter_map(|protocol| match protocol {
+            SecurityProtocol::TLS13 => Some(&rustls::version::TLS13),
+            SecurityProtocol::TLS12 => Some(&rustls::version::TLS12),
+            SecurityProtocol::DTLS12 | SecurityProtocol::DTLS13 => None,
+        })
+        .collect::<Vec<_>>();
+    if versions.is_empty() {
+        return Err(TransportServicesError::NotSupported(
+            "DTLS cannot secure a TCP connection".to_string(),
+        ));
+    }
+    Ok(versions)
+}
+
+/// Checks the certificates clients present to a Listener
+///
+/// Chains must lead to one of the client trust anchors, if there are any,
+/// and pass the trust verifier, if there is one.
+struct ClientAuthVerifier {
+    anchors: Option<Arc<dyn ClientCertVerifier>>,
+    mandatory: bool,
+    trust: Option<Arc<dyn TrustVerifier>>,
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl ClientAuthVerifier {
+    fn new(
+        parameters: &SecurityParameters,
+        policy: ClientCertificatePolicy,
+        provider: &Arc<CryptoProvider>,
+    ) -> Result<Self> {
+        let mut roots = RootCertStore::empty();
+        for certificate in parameters
+            .pinned_client_certificate
+            .iter()
+            .flat_map(|chain| &chain.certificates)
+        {
+            roots
+                .add(CertificateDer::from(certificate.data.clone()))
+                .map_err(|e| TransportServicesError::InvalidParameters(e.to_string()))?;
+        }
+        let anchors = if roots.is_empty() {
+            None
+        } else {
+            // Whether a certificate is needed at all is decided here, not by webpki
+            let verifier =
+                WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                    .allow_unauthenticated()
+                    .build()
+                    .map_err(|e| TransportServicesError::InvalidParameters(e.to_string()))?;
+            Some(verifier)
+        };
+
+        let trust = parameters.trust_verifier.clone();
+        if anchors.is_none() && trust.is_none() {
+            return Err(TransportServicesError::InvalidParameters(
+                "Client certificates need pinned_client_certificate or a trust verifier"
+                    .to_string(),
+            ));
+        }
+
+        Ok(Self {
+            anchors,
+            mandatory: policy == ClientCertificatePolicy::Require,
+            trust,
+            algorithms: provider.signature_verification_algorithms,
+        })
+    }
+}
+
+impl ClientCertVerifier for ClientAuthVerifier {
+    fn offer_client_auth(&self) -> bool {
+        true
+    }
+
+    fn client_auth_mandatory(&self) -> bool {
+        self.mandatory
+    }
+
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        match &self.anchors {
+            Some(anchors) => anchors.root_hint_subjects(),
+            None => &[],
+        }
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        now: UnixTime,
+    ) -> std::result::Result<ClientCertVerified, rustls::Error> {
+        if let Some(anchors) = &self.anchors {
+            anchors.verify_client_cert(end_entity, intermediates, now)?;
+        }
+
+        if let Some(trust) = &self.trust {
+            check_trust(trust.as_ref(), end_entity, intermediates)?;
+        }
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+impl std::fmt::Debug for ClientAuthVerifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientAuthVerifier")
+            .field("anchors", &self.anchors.is_some())
+            .field("mandatory", &self.mandatory)
+            .finish()
+    }
+}
+
+/// Check a presented chain with the trust verifier
+fn check_trust(
+    trust: &dyn TrustVerifier,
+    end_entity: &CertificateDer<'_>,
+    intermediates: &[CertificateDer<'_>],
+) -> std::result::Result<(), rustls::Error> {
+    let chain = CertificateChain {
+        certificates: std::iter::once(end_entity)
+            .chain(intermediates)
+            .map(|certificate| Certificate {
+                data: certificate.to_vec(),
+            })
+            .collect(),
+    };
+    if !trust.verify(&chain) {
+        return Err(rustls::Error::InvalidCertificate(
+            rustls::CertificateError::ApplicationVerificationFailure,
+        ));
+    }
+    Ok(())
+}
+
+/// Checks servers against the trust anchors, then the trust verifier
+struct TrustedServerVerifier {
+    anchors: Arc<WebPkiServerVerifier>,
+    trust: Arc<dyn TrustVerifier>,
+}
+
+impl ServerCertVerifier for TrustedServerVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        let verified = self.anchors.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+        check_trust(self.trust.as_ref(), end_entity, intermediates)?;
+        Ok(verified)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.anchors.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.anchors.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.anchors.supported_verify_schemes()
+    }
+}
+
+impl std::fmt::Debug for TrustedServerVerifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TrustedServerVerifier")
+            .finish_non_exhaustive()
+    }
+}
+
+/// Authenticates peers by pinned fingerprint or raw public key
+///
+/// Names, validity periods and issuers are not checked: a peer is who it
+/// says it is exactly when it proves possession of a pinned key.
+struct PeerVerifier {
+    pins: Vec<PeerPin>,
+    raw_public_keys: bool,
+    mandatory: bool,
+    trust: Option<Arc<dyn TrustVerifier>>,
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl PeerVerifier {
+    fn new(
+        parameters: &SecurityParameters,
+        mandatory: bool,
+        provider: &Arc<CryptoProvider>,
+    ) -> Result<Self> {
+        let mismatched = parameters
+            .pinned_peers
+            .iter()
+            .any(|pin| matches!(pin, PeerPin::PublicKey(_)) != parameters.raw_public_keys);
+        if mismatched {
+            return Err(TransportServicesError::InvalidParameters(
+                "Raw public keys are pinned as PublicKey, certificates by fingerprint".to_string(),
+            ));
+        }
+        Ok(Self {
+            pins: parameters.pinned_peers.clone(),
+            raw_public_keys: parameters.raw_public_keys,
+            mandatory,
+            trust: parameters.trust_verifier.clone(),
+            algorithms: provider.signature_verification_algorithms,
+        })
+    }
+
+    fn check(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+    ) -> std::result::Result<(), rustls::Error> {
+        if !self.pins.iter().any(|pin| pin_matches(pin, end_entity)) {
+            return Err(rustls::Error::InvalidCertificate(
+                rustls::CertificateError::ApplicationVerificationFailure,
+            ));
+        }
+        match &self.trust {
+            Some(trust) => check_trust(trust.as_ref(), end_entity, intermediates),
+            None => Ok(()),
+        }
+    }
+
+    fn verify_tls12(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        if self.raw_public_keys {
+            return Err(rustls::Error::PeerIncompatible(
+                rustls::PeerIncompatible::Tls12NotOffered,
+            ));
+        }
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        if self.raw_public_keys {
+            let key = SubjectPublicKeyInfoDer::from(cert.as_ref());
+            return rustls::crypto::verify_tls13_signature_with_raw_key(
+                message,
+                &key,
+                dss,
+                &self.algorithms,
+            );
+        }
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+}
+
+impl ServerCertVerifier for PeerVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        self.check(end_entity, intermediates)?;
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.verify_tls12(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.verify_tls13(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+
+    fn requires_raw_public_keys(&self) -> bool {
+        self.raw_public_keys
+    }
+}
+
+impl ClientCertVerifier for PeerVerifier {
+    fn offer_client_auth(&self) -> bool {
+        true
+    }
+
+    fn client_auth_mandatory(&self) -> bool {
+        self.mandatory
+    }
+
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        _now: UnixTime,
+    ) -> std::result::Result<ClientCertVerified, rustls::Error> {
+        self.check(end_entity, intermediates)?;
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.verify_tls12(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.verify_tls13(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+
+    fn requires_raw_public_keys(&self) -> bool {
+        self.raw_public_keys
+    }
+}
+
+impl std::fmt::Debug for PeerVerifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PeerVerifier")
+            .field("pins", &self.pins.len())
+            .field("raw_public_keys", &self.raw_public_keys)
+            .field("mandatory", &self.mandatory)
+            .finish()
+    }
+}
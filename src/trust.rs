@@ -0,0 +1,135 @@
+//! Trust policies for authenticating peers
+//!
+//! A trust policy is made of shared trait objects rather than bare closures,
+//! so it survives cloning SecurityParameters, can be supplied through the FFI
+//! and composes: `AllOf` requires every verifier to accept a chain, `AnyOf`
+//! one of them, and either applies on top of the configured trust anchors or
+//! pinned peers. Verifiers can also be registered under a name, letting
+//! configuration kept as text refer to them.
+
+use crate::{CertificateChain, Result, TransportServicesError};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Decides whether to trust the certificate chain a peer presents
+pub trait TrustVerifier: Send + Sync {
+    /// Whether to trust the peer presenting `chain`, leaf certificate first
+    ///
+    /// Runs after the chain has passed the configured trust anchors or peer
+    /// pins, if any.
+    fn verify(&self, chain: &CertificateChain) -> bool;
+}
+
+impl<F> TrustVerifier for F
+where
+    F: Fn(&CertificateChain) -> bool + Send + Sync,
+{
+    fn verify(&self, chain: &CertificateChain) -> bool {
+        self(chain)
+    }
+}
+
+/// Answers identity challenges on behalf of the local endpoint
+pub trait IdentityProvider: Send + Sync {
+    /// The response to `challenge`, e.g. the challenge signed with a key
+    /// the application keeps
+    fn respond(&self, challenge: &[u8]) -> Vec<u8>;
+}
+
+impl<F> IdentityProvider for F
+where
+    F: Fn(&[u8]) -> Vec<u8> + Send + Sync,
+{
+    fn respond(&self, challenge: &[u8]) -> Vec<u8> {
+        self(challenge)
+    }
+}
+
+/// Chooses which server identity a Listener terminating TLS presents
+pub trait CertificateSelector: Send + Sync {
+    /// Index in `server_identities` of the identity to present to a client
+    /// that asked for `server_name`, if any; None refuses the handshake
+    fn select(&self, server_name: Option<&str>) -> Option<usize>;
+}
+
+impl<F> CertificateSelector for F
+where
+    F: Fn(Option<&str>) -> Option<usize> + Send + Sync,
+{
+    fn select(&self, server_name: Option<&str>) -> Option<usize> {
+        self(server_name)
+    }
+}
+
+/// Decides whether early data a client sent is safe to act on
+pub trait ReplayPolicy: Send + Sync {
+    /// Whether acting on `data` is safe even if an attacker replays it; if
+    /// not, the handshake is refused
+    fn is_replay_safe(&self, data: &[u8]) -> bool;
+}
+
+impl<F> ReplayPolicy for F
+where
+    F: Fn(&[u8]) -> bool + Send + Sync,
+{
+    fn is_replay_safe(&self, data: &[u8]) -> bool {
+        self(data)
+    }
+}
+
+/// Trusts a chain only if every verifier does; trusts any chain if empty
+#[derive(Clone, Default)]
+pub struct AllOf(pub Vec<Arc<dyn TrustVerifier>>);
+
+impl TrustVerifier for AllOf {
+    fn verify(&self, chain: &CertificateChain) -> bool {
+        self.0.iter().all(|verifier| verifier.verify(chain))
+    }
+}
+
+/// Trusts a chain if any verifier does; trusts no chain if empty
+#[derive(Clone, Default)]
+pub struct AnyOf(pub Vec<Arc<dyn TrustVerifier>>);
+
+impl TrustVerifier for AnyOf {
+    fn verify(&self, chain: &CertificateChain) -> bool {
+        self.0.iter().any(|verifier| verifier.verify(chain))
+    }
+}
+
+/// Trusts chains whose leaf matches the pin: a certificate by its SHA-256
+/// fingerprint, a raw public key by its bytes
+#[cfg(feature = "tls")]
+impl TrustVerifier for crate::PeerPin {
+    fn verify(&self, chain: &CertificateChain) -> bool {
+        chain
+            .certificates
+            .first()
+            .is_some_and(|leaf| crate::tls::pin_matches(self, &leaf.data))
+    }
+}
+
+static REGISTRY: Lazy<RwLock<HashMap<String, Arc<dyn TrustVerifier>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Register a verifier under `name`, replacing any registered before
+pub fn register_trust_verifier(name: impl Into<String>, verifier: Arc<dyn TrustVerifier>) {
+    REGISTRY.write().unwrap().insert(name.into(), verifier);
+}
+
+/// Remove the verifier registered under `name`
+///
+/// SecurityParameters already using it keep it.
+pub fn unregister_trust_verifier(name: &str) -> Option<Arc<dyn TrustVerifier>> {
+    REGISTRY.write().unwrap().remove(name)
+}
+
+/// Get the verifier registered under `name`
+pub fn registered_trust_verifier(name: &str) -> Result<Arc<dyn TrustVerifier>> {
+    REGISTRY.read().unwrap().get(name).cloned().ok_or_else(|| {
+        TransportServicesError::InvalidParameters(format!(
+            "No trust verifier registered as {name:?}"
+        ))
+    })
+}
@@ -2,7 +2,7 @@
 //! Based on RFC 9622 Section 1.1 (Terminology and Notation)
 
 use std::net::{IpAddr, SocketAddr};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 /// Preference levels for Selection Properties (RFC Section 1.2)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -50,6 +50,34 @@ pub enum EndpointIdentifier {
     HopLimit(u8),
 }
 
+impl EndpointIdentifier {
+    /// Parse an IP address with an optional `%zone` suffix, as in
+    /// `fe80::1%en0` or `fe80::1%3`
+    ///
+    /// The zone, only allowed on IPv6 addresses, becomes an interface
+    /// identifier.
+    pub fn parse_scoped_ip(address: &str) -> crate::Result<Vec<EndpointIdentifier>> {
+        let invalid = || {
+            crate::TransportServicesError::InvalidParameters(format!(
+                "Invalid IP address {address:?}"
+            ))
+        };
+        let (ip, zone) = match address.split_once('%') {
+            Some((ip, zone)) => (ip, Some(zone)),
+            None => (address, None),
+        };
+        let ip = ip.parse::<IpAddr>().map_err(|_| invalid())?;
+        let mut identifiers = vec![EndpointIdentifier::IpAddress(ip)];
+        if let Some(zone) = zone {
+            if zone.is_empty() || !ip.is_ipv6() {
+                return Err(invalid());
+            }
+            identifiers.push(EndpointIdentifier::Interface(zone.to_string()));
+        }
+        Ok(identifiers)
+    }
+}
+
 /// STUN server credentials
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct StunCredentials {
@@ -96,6 +124,16 @@ impl LocalEndpoint {
         self
     }
 
+    /// Add an IP address given as text, with an optional zone index
+    ///
+    /// The zone of a link-local address such as `fe80::1%en0`, an interface
+    /// name or index, is added as the endpoint's interface.
+    pub fn with_scoped_ip_address(mut self, address: &str) -> crate::Result<Self> {
+        self.identifiers
+            .extend(EndpointIdentifier::parse_scoped_ip(address)?);
+        Ok(self)
+    }
+
     /// Add a STUN server for NAT traversal
     /// RFC Section 6.1: LocalSpecifier.WithStunServer(address, port, credentials)
     pub fn with_stun_server(
@@ -198,7 +236,7 @@ impl Default for LocalEndpointBuilder {
 }
 
 /// Remote endpoint specification
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct RemoteEndpoint {
     pub identifiers: Vec<EndpointIdentifier>,
     pub protocol: Option<Protocol>,
@@ -215,6 +253,23 @@ impl RemoteEndpoint {
         RemoteEndpointBuilder::new()
     }
 
+    /// Parse a URL such as `quic://example.com:443` or `tcp://[::1]:8080`
+    ///
+    /// The scheme sets the protocol: tcp, udp, tls, dtls, sctp and quic, or
+    /// http and https for TCP and TLS with ports 80 and 443 by default; quic
+    /// also defaults to 443, other schemes need a port. The path is ignored,
+    /// as are query parameters, which `Preconnection::from_url` applies.
+    pub fn from_url(url: &str) -> crate::Result<Self> {
+        crate::url::parse(url).map(|url| url.endpoint)
+    }
+
+    /// Write the endpoint as a URL, e.g. for logging
+    ///
+    /// Returns None unless the endpoint has both a host and a port.
+    pub fn to_url(&self) -> Option<String> {
+        crate::url::format(self)
+    }
+
     /// Add a hostname
     /// RFC Section 6.1: RemoteSpecifier.WithHostName("example.com")
     pub fn with_hostname(mut self, hostname: impl Into<String>) -> Self {
@@ -245,6 +300,16 @@ impl RemoteEndpoint {
         self
     }
 
+    /// Add an IP address given as text, with an optional zone index
+    ///
+    /// The zone of a link-local address such as `fe80::1%en0`, an interface
+    /// name or index, is added as the endpoint's interface.
+    pub fn with_scoped_ip_address(mut self, address: &str) -> crate::Result<Self> {
+        self.identifiers
+            .extend(EndpointIdentifier::parse_scoped_ip(address)?);
+        Ok(self)
+    }
+
     /// Add an interface (for link-local addresses)
     /// RFC Section 6.1: Used to qualify link-local addresses
     pub fn with_interface(mut self, interface: impl Into<String>) -> Self {
@@ -505,6 +570,87 @@ impl TransportProperties {
                     self.connection_properties.maximum_message_size_on_receive = Some(size);
                 }
             }
+            TransportProperty::SendBufferSize => {
+                if let PropertyValue::Size(size) = value {
+                    self.connection_properties.send_buffer_size = Some(size);
+                }
+            }
+            TransportProperty::ReceiveBufferSize => {
+                if let PropertyValue::Size(size) = value {
+                    self.connection_properties.receive_buffer_size = Some(size);
+                }
+            }
+            TransportProperty::NoDelay => {
+                if let PropertyValue::Bool(no_delay) = value {
+                    self.connection_properties.no_delay = Some(no_delay);
+                }
+            }
+            TransportProperty::ReceiveTimestamps => {
+                if let PropertyValue::Bool(enabled) = value {
+                    self.connection_properties.receive_timestamps = Some(enabled);
+                }
+            }
+            TransportProperty::StatsInterval => {
+                if let PropertyValue::Duration(interval) = value {
+                    self.connection_properties.stats_interval = Some(interval);
+                }
+            }
+            TransportProperty::NatKeepaliveInterval => {
+                if let PropertyValue::Duration(interval) = value {
+                    self.connection_properties.nat_keepalive_interval = Some(interval);
+                }
+            }
+            TransportProperty::UdpOffload => {
+                if let PropertyValue::Bool(enabled) = value {
+                    self.connection_properties.udp_offload = Some(enabled);
+                }
+            }
+            TransportProperty::SendHighWatermark => {
+                if let PropertyValue::Size(size) = value {
+                    self.connection_properties.send_high_watermark = Some(size);
+                }
+            }
+            TransportProperty::SendLowWatermark => {
+                if let PropertyValue::Size(size) = value {
+                    self.connection_properties.send_low_watermark = Some(size);
+                }
+            }
+            TransportProperty::AbortOnOversizedMessage => {
+                if let PropertyValue::Bool(abort) = value {
+                    self.connection_properties.abort_on_oversized_message = Some(abort);
+                }
+            }
+            TransportProperty::PathPinning => {
+                if let PropertyValue::Bool(pinned) = value {
+                    self.connection_properties.path_pinning = Some(pinned);
+                }
+            }
+            TransportProperty::ConnectionAttemptDelay => {
+                if let PropertyValue::Duration(delay) = value {
+                    self.connection_properties
+                        .happy_eyeballs
+                        .connection_attempt_delay = delay;
+                }
+            }
+            TransportProperty::AddressFamilyPreference => {
+                if let PropertyValue::AddressFamily(preference) = value {
+                    self.connection_properties
+                        .happy_eyeballs
+                        .address_family_preference = preference;
+                }
+            }
+            TransportProperty::CandidateTimeout => {
+                if let PropertyValue::Duration(timeout) = value {
+                    self.connection_properties.happy_eyeballs.candidate_timeout = Some(timeout);
+                }
+            }
+            TransportProperty::MaxParallelAttempts => {
+                if let PropertyValue::Size(attempts) = value {
+                    self.connection_properties
+                        .happy_eyeballs
+                        .max_parallel_attempts = attempts;
+                }
+            }
         }
         self
     }
@@ -513,6 +659,106 @@ impl TransportProperties {
     pub fn builder() -> TransportPropertiesBuilder {
         TransportPropertiesBuilder::new()
     }
+
+    /// Combinations of properties that no protocol can satisfy
+    ///
+    /// Only contradictions between the properties themselves are reported;
+    /// what this build supports is described by `capabilities()`.
+    pub fn conflicts(&self) -> Vec<PropertyConflict> {
+        let selection = &self.selection_properties;
+        let mut conflicts = Vec::new();
+
+        if selection.reliability == Preference::Prohibit
+            && selection.per_msg_reliability == Preference::Require
+        {
+            conflicts.push(PropertyConflict::new(
+                vec![
+                    TransportProperty::Reliability,
+                    TransportProperty::PerMsgReliability,
+                ],
+                "per-message reliability needs a reliable transport to vary",
+            ));
+        }
+        for (property, preference) in [
+            (
+                TransportProperty::FullChecksumSend,
+                selection.full_checksum_send,
+            ),
+            (
+                TransportProperty::FullChecksumRecv,
+                selection.full_checksum_recv,
+            ),
+        ] {
+            if selection.reliability == Preference::Require && preference == Preference::Prohibit {
+                conflicts.push(PropertyConflict::new(
+                    vec![TransportProperty::Reliability, property],
+                    "reliable transports checksum every byte",
+                ));
+            }
+        }
+        if selection.direction == CommunicationDirection::UnidirectionalSend
+            && selection.active_read_before_send == Preference::Require
+        {
+            conflicts.push(PropertyConflict::new(
+                vec![
+                    TransportProperty::Direction,
+                    TransportProperty::ActiveReadBeforeSend,
+                ],
+                "a send-only connection cannot read first",
+            ));
+        }
+        if selection.multipath != MultipathConfig::Disabled
+            && self.connection_properties.path_pinning == Some(true)
+        {
+            conflicts.push(PropertyConflict::new(
+                vec![TransportProperty::Multipath, TransportProperty::PathPinning],
+                "a pinned connection uses only the path it was established on",
+            ));
+        }
+        for (property, names) in [
+            (TransportProperty::Interface, &selection.interface),
+            (TransportProperty::Pvd, &selection.pvd),
+        ] {
+            for (name, preference) in names {
+                if *preference == Preference::Require
+                    && names
+                        .iter()
+                        .any(|(other, pref)| other == name && *pref == Preference::Prohibit)
+                {
+                    conflicts.push(PropertyConflict::new(
+                        vec![property],
+                        format!("{name} is both required and prohibited"),
+                    ));
+                }
+            }
+        }
+        conflicts
+    }
+}
+
+/// Properties that contradict each other, so no Connection can satisfy them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropertyConflict {
+    /// The properties involved
+    pub properties: Vec<TransportProperty>,
+    /// Why they cannot all hold
+    pub reason: String,
+}
+
+impl PropertyConflict {
+    pub(crate) fn new(properties: Vec<TransportProperty>, reason: impl Into<String>) -> Self {
+        Self {
+            properties,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for PropertyConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let properties: Vec<_> = self.properties.iter().map(|p| format!("{p:?}")).collect();
+        write!(f, "{}: {}", properties.join(" + "), self.reason)
+    }
 }
 
 /// Enumeration of all transport properties
@@ -543,6 +789,21 @@ pub enum TransportProperty {
     ConnectionPriority,
     MaximumMessageSizeOnSend,
     MaximumMessageSizeOnReceive,
+    SendBufferSize,
+    ReceiveBufferSize,
+    NoDelay,
+    ReceiveTimestamps,
+    StatsInterval,
+    NatKeepaliveInterval,
+    UdpOffload,
+    SendHighWatermark,
+    SendLowWatermark,
+    AbortOnOversizedMessage,
+    PathPinning,
+    ConnectionAttemptDelay,
+    AddressFamilyPreference,
+    CandidateTimeout,
+    MaxParallelAttempts,
 }
 
 /// Values that can be assigned to transport properties
@@ -556,6 +817,7 @@ pub enum PropertyValue {
     StringPreference(String, Preference),
     Multipath(MultipathConfig),
     Direction(CommunicationDirection),
+    AddressFamily(AddressFamilyPreference),
 }
 
 /// Selection properties (used during preestablishment)
@@ -614,6 +876,85 @@ pub struct ConnectionProperties {
     pub connection_priority: Option<i32>,
     pub maximum_message_size_on_send: Option<usize>,
     pub maximum_message_size_on_receive: Option<usize>,
+    /// Socket send buffer size in bytes
+    pub send_buffer_size: Option<usize>,
+    /// Socket receive buffer size in bytes
+    pub receive_buffer_size: Option<usize>,
+    /// Disable Nagle's algorithm (TCP_NODELAY)
+    pub no_delay: Option<bool>,
+    /// Have the kernel timestamp received data (SO_TIMESTAMPNS / SO_TIMESTAMP)
+    pub receive_timestamps: Option<bool>,
+    /// Emit a ConnectionEvent::Stats snapshot at this interval (telemetry mode)
+    pub stats_interval: Option<Duration>,
+    /// Send an empty datagram after this long without traffic on a UDP flow,
+    /// keeping NAT bindings alive; the peer receives it as an empty message
+    pub nat_keepalive_interval: Option<Duration>,
+    /// Batch datagrams through UDP segmentation and receive offload
+    /// (GSO/GRO) where the kernel supports it; used unless set to false
+    pub udp_offload: Option<bool>,
+    /// Abort the connection when the peer declares a message larger than
+    /// maximum_message_size_on_receive, rather than discarding the message
+    pub abort_on_oversized_message: Option<bool>,
+    /// Keep the connection on the path it was established on: it is aborted
+    /// with a ConnectionError when that path is lost or the peer's address
+    /// changes, rather than migrating
+    pub path_pinning: Option<bool>,
+    /// IP TTL (IPv4) or hop limit (IPv6) of packets the connection sends;
+    /// the system default if unset
+    pub hop_limit: Option<u8>,
+    /// Bytes queued for sending at which send() waits for the queue to
+    /// drain, and try_send() fails with WouldBlock; unbounded if unset
+    pub send_high_watermark: Option<usize>,
+    /// Bytes the send queue must drain below before a waiting send()
+    /// proceeds; half the high watermark if unset
+    pub send_low_watermark: Option<usize>,
+    /// Initial maxSendRate in bits per second; unlimited if unset
+    pub max_send_rate: Option<u64>,
+    /// Initial maxRecvRate in bits per second; unlimited if unset
+    pub max_recv_rate: Option<u64>,
+    /// Candidate racing configuration used during establishment
+    pub happy_eyeballs: HappyEyeballsConfig,
+}
+
+/// Address family preference when racing candidates
+/// RFC 8305 Section 4
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AddressFamilyPreference {
+    /// Try IPv6 first, interleaving with IPv4
+    #[default]
+    PreferIpv6,
+    /// Try IPv4 first, interleaving with IPv6
+    PreferIpv4,
+    /// Only attempt IPv6 candidates
+    Ipv6Only,
+    /// Only attempt IPv4 candidates
+    Ipv4Only,
+}
+
+/// Happy Eyeballs configuration for racing connection attempts
+/// RFC 8305 (Happy Eyeballs Version 2)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HappyEyeballsConfig {
+    /// Delay before starting the next attempt while earlier ones are pending
+    /// RFC 8305 Section 5 recommends 250ms, clamped to 10ms..2s
+    pub connection_attempt_delay: Duration,
+    /// Which address family to try first
+    pub address_family_preference: AddressFamilyPreference,
+    /// Timeout for a single connection attempt, bounded by the connection timeout
+    pub candidate_timeout: Option<Duration>,
+    /// Maximum number of attempts in flight at once
+    pub max_parallel_attempts: usize,
+}
+
+impl Default for HappyEyeballsConfig {
+    fn default() -> Self {
+        Self {
+            connection_attempt_delay: Duration::from_millis(250),
+            address_family_preference: AddressFamilyPreference::PreferIpv6,
+            candidate_timeout: None,
+            max_parallel_attempts: 4,
+        }
+    }
 }
 
 /// Message Capacity Profile for overriding connection defaults
@@ -674,6 +1015,19 @@ pub struct MessageProperties {
     /// RFC Section 9.1.3.10
     pub no_segmentation: bool,
 
+    /// Time allowed for writing the message to the network
+    ///
+    /// Unlike lifetime, which expires messages that have not been sent yet,
+    /// the deadline bounds a write that has started but stalled.
+    pub deadline: Option<Duration>,
+
+    /// IP TTL (IPv4) or hop limit (IPv6) of the datagram carrying this
+    /// message, overriding the connection's ipHopLimit
+    ///
+    /// Only datagram transports apply it; a stream's segments all take the
+    /// connection's.
+    pub hop_limit: Option<u8>,
+
     // Legacy fields (keeping for compatibility)
     #[deprecated(note = "Use safely_replayable instead")]
     pub idempotent: bool,
@@ -701,14 +1055,6 @@ pub enum CommunicationDirection {
     UnidirectionalReceive,
 }
 
-/// Type alias for trust verification callback
-#[cfg(not(feature = "ffi"))]
-pub type TrustVerificationCallback = Box<dyn Fn(&CertificateChain) -> bool + Send + Sync>;
-
-/// Type alias for identity challenge callback
-#[cfg(not(feature = "ffi"))]
-pub type IdentityChallengeCallback = Box<dyn Fn(&[u8]) -> Vec<u8> + Send + Sync>;
-
 /// Security parameters for connections
 pub struct SecurityParameters {
     pub disabled: bool,
@@ -721,15 +1067,48 @@ pub struct SecurityParameters {
     pub supported_groups: Vec<String>,
     pub ciphersuites: Vec<String>,
     pub signature_algorithms: Vec<String>,
+    /// Sessions a Listener keeps for resumption; Some(0) disables it
     pub max_cached_sessions: Option<usize>,
+    /// How long a resumable session stays valid
     pub cached_session_lifetime_seconds: Option<u64>,
     pub pre_shared_key: Option<PreSharedKey>,
-    // Callbacks are stored as Option<Box<dyn Fn>> in Rust
-    // For FFI, we'll use function pointers
-    #[cfg(not(feature = "ffi"))]
-    pub trust_verification_callback: Option<TrustVerificationCallback>,
-    #[cfg(not(feature = "ffi"))]
-    pub identity_challenge_callback: Option<IdentityChallengeCallback>,
+    /// Certificates a Listener terminating TLS can present, chosen by SNI
+    pub server_identities: Vec<ServerIdentity>,
+    /// Whether a Listener asks clients for certificates (mTLS)
+    pub client_certificate_policy: ClientCertificatePolicy,
+    /// Trust anchors for the certificates clients present
+    pub pinned_client_certificate: Vec<CertificateChain>,
+    /// Peers recognised by a fingerprint or public key exchanged out of
+    /// band, instead of by a CA-anchored chain; for peer-to-peer use
+    pub pinned_peers: Vec<PeerPin>,
+    /// Present and expect raw public keys (RFC 7250) instead of certificates
+    pub raw_public_keys: bool,
+    /// Certificate and key a client presents when the server asks for one;
+    /// its server names are not used
+    pub client_identity: Option<ServerIdentity>,
+    /// Session tickets a Listener issues after each TLS 1.3 handshake;
+    /// 0 disables resumption
+    pub session_tickets: usize,
+    /// Issue self-contained encrypted tickets instead of keeping sessions on
+    /// the Listener; such tickets cannot carry 0-RTT data
+    pub stateless_session_tickets: bool,
+    /// Accept up to 16 KiB of 0-RTT data from resuming clients; without a
+    /// replay policy to check it, none is accepted
+    pub accept_early_data: bool,
+    /// File TLS secrets are appended to, for decrypting captured traffic;
+    /// takes precedence over SSLKEYLOGFILE
+    #[cfg(feature = "keylog")]
+    pub key_log_file: Option<std::path::PathBuf>,
+    /// Decides whether to trust peers, on top of the trust anchors or
+    /// pinned peers; shared, so copies of the parameters keep it
+    pub trust_verifier: Option<std::sync::Arc<dyn crate::TrustVerifier>>,
+    /// Answers identity challenges for the local endpoint
+    pub identity_provider: Option<std::sync::Arc<dyn crate::IdentityProvider>>,
+    /// Chooses the server identity a Listener presents, instead of
+    /// matching the client's SNI against `server_names`
+    pub certificate_selector: Option<std::sync::Arc<dyn crate::CertificateSelector>>,
+    /// Decides whether early data, 0-RTT or sent in a SYN, is replay-safe
+    pub replay_policy: Option<std::sync::Arc<dyn crate::ReplayPolicy>>,
 }
 
 impl SecurityParameters {
@@ -835,22 +1214,137 @@ impl SecurityParameters {
     }
 
     /// Set trust verification callback
-    #[cfg(not(feature = "ffi"))]
     pub fn set_trust_verification_callback<F>(&mut self, callback: F) -> &mut Self
     where
         F: Fn(&CertificateChain) -> bool + Send + Sync + 'static,
     {
-        self.trust_verification_callback = Some(Box::new(callback));
+        self.set_trust_verifier(std::sync::Arc::new(callback))
+    }
+
+    /// Set the verifier deciding whether to trust peers, replacing any set
+    pub fn set_trust_verifier(
+        &mut self,
+        verifier: std::sync::Arc<dyn crate::TrustVerifier>,
+    ) -> &mut Self {
+        self.trust_verifier = Some(verifier);
         self
     }
 
-    /// Set identity challenge callback  
-    #[cfg(not(feature = "ffi"))]
+    /// Add a verifier peers must also pass, e.g. pinning on top of a CA check
+    pub fn add_trust_verifier(
+        &mut self,
+        verifier: std::sync::Arc<dyn crate::TrustVerifier>,
+    ) -> &mut Self {
+        let verifier = match self.trust_verifier.take() {
+            Some(existing) => std::sync::Arc::new(crate::AllOf(vec![existing, verifier])),
+            None => verifier,
+        };
+        self.set_trust_verifier(verifier)
+    }
+
+    /// Add the verifier registered under `name`, which peers must also pass
+    pub fn add_registered_trust_verifier(&mut self, name: &str) -> crate::Result<&mut Self> {
+        let verifier = crate::trust::registered_trust_verifier(name)?;
+        Ok(self.add_trust_verifier(verifier))
+    }
+
+    /// Set identity challenge callback
     pub fn set_identity_challenge_callback<F>(&mut self, callback: F) -> &mut Self
     where
         F: Fn(&[u8]) -> Vec<u8> + Send + Sync + 'static,
     {
-        self.identity_challenge_callback = Some(Box::new(callback));
+        self.set_identity_provider(std::sync::Arc::new(callback))
+    }
+
+    /// Set what answers identity challenges for the local endpoint
+    pub fn set_identity_provider(
+        &mut self,
+        provider: std::sync::Arc<dyn crate::IdentityProvider>,
+    ) -> &mut Self {
+        self.identity_provider = Some(provider);
+        self
+    }
+
+    /// Add a certificate a Listener can present
+    ///
+    /// Without a certificate selection callback, the identity whose
+    /// `server_names` match the client's SNI is presented, falling back to
+    /// the first identity added.
+    pub fn add_server_identity(&mut self, identity: ServerIdentity) -> &mut Self {
+        self.server_identities.push(identity);
+        self
+    }
+
+    /// Ask clients of a Listener for certificates
+    ///
+    /// Presented chains must lead to a `pinned_client_certificate` trust
+    /// anchor, if any are set, and pass the trust verifier, if one is set;
+    /// at least one of the two is needed.
+    pub fn set_client_certificate_policy(&mut self, policy: ClientCertificatePolicy) -> &mut Self {
+        self.client_certificate_policy = policy;
+        self
+    }
+
+    /// Authenticate peers by `pin` rather than by certificate authorities
+    ///
+    /// Once any peer is pinned, servers are accepted by pin alone, without
+    /// name or chain checks, and Listeners require clients to present a
+    /// pinned identity unless the client certificate policy is `Request`.
+    pub fn pin_peer(&mut self, pin: PeerPin) -> &mut Self {
+        self.pinned_peers.push(pin);
+        self
+    }
+
+    /// Set the identity a client presents when the server asks for one
+    pub fn set_client_identity(&mut self, identity: ServerIdentity) -> &mut Self {
+        self.client_identity = Some(identity);
+        self
+    }
+
+    /// Append the secrets of TLS sessions to `path` in the NSS key log format
+    #[cfg(feature = "keylog")]
+    pub fn set_key_log_file(&mut self, path: impl Into<std::path::PathBuf>) -> &mut Self {
+        self.key_log_file = Some(path.into());
+        self
+    }
+
+    /// Accept 0-RTT data on a Listener, checking it with `callback`
+    pub fn set_early_data_callback<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: Fn(&[u8]) -> bool + Send + Sync + 'static,
+    {
+        self.set_replay_policy(std::sync::Arc::new(callback))
+    }
+
+    /// Accept 0-RTT data on a Listener, checking it with `policy`
+    ///
+    /// Each session ticket is only accepted once, but early data may still
+    /// be replayed to other Listeners sharing the identity. Requires stateful
+    /// session tickets. Listeners without TLS check data a client sent in its
+    /// SYN (TCP Fast Open) with the same policy.
+    pub fn set_replay_policy(
+        &mut self,
+        policy: std::sync::Arc<dyn crate::ReplayPolicy>,
+    ) -> &mut Self {
+        self.accept_early_data = true;
+        self.replay_policy = Some(policy);
+        self
+    }
+
+    /// Set the callback choosing which server identity to present by SNI
+    pub fn set_certificate_selection_callback<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: Fn(Option<&str>) -> Option<usize> + Send + Sync + 'static,
+    {
+        self.set_certificate_selector(std::sync::Arc::new(callback))
+    }
+
+    /// Set what chooses which server identity to present, replacing any set
+    pub fn set_certificate_selector(
+        &mut self,
+        selector: std::sync::Arc<dyn crate::CertificateSelector>,
+    ) -> &mut Self {
+        self.certificate_selector = Some(selector);
         self
     }
 }
@@ -877,6 +1371,22 @@ impl std::fmt::Debug for SecurityParameters {
                 &self.cached_session_lifetime_seconds,
             )
             .field("pre_shared_key", &self.pre_shared_key.is_some())
+            .field("server_identities", &self.server_identities)
+            .field("client_certificate_policy", &self.client_certificate_policy)
+            .field("session_tickets", &self.session_tickets)
+            .field("stateless_session_tickets", &self.stateless_session_tickets)
+            .field("accept_early_data", &self.accept_early_data)
+            .field(
+                "pinned_client_certificate",
+                &self.pinned_client_certificate.len(),
+            )
+            .field("pinned_peers", &self.pinned_peers)
+            .field("raw_public_keys", &self.raw_public_keys)
+            .field("client_identity", &self.client_identity)
+            .field("trust_verifier", &self.trust_verifier.is_some())
+            .field("identity_provider", &self.identity_provider.is_some())
+            .field("certificate_selector", &self.certificate_selector.is_some())
+            .field("replay_policy", &self.replay_policy.is_some())
             .finish()
     }
 }
@@ -897,11 +1407,21 @@ impl Clone for SecurityParameters {
             max_cached_sessions: self.max_cached_sessions,
             cached_session_lifetime_seconds: self.cached_session_lifetime_seconds,
             pre_shared_key: self.pre_shared_key.clone(),
-            // Callbacks cannot be cloned, so new instances will have None
-            #[cfg(not(feature = "ffi"))]
-            trust_verification_callback: None,
-            #[cfg(not(feature = "ffi"))]
-            identity_challenge_callback: None,
+            server_identities: self.server_identities.clone(),
+            client_certificate_policy: self.client_certificate_policy,
+            pinned_client_certificate: self.pinned_client_certificate.clone(),
+            pinned_peers: self.pinned_peers.clone(),
+            raw_public_keys: self.raw_public_keys,
+            client_identity: self.client_identity.clone(),
+            session_tickets: self.session_tickets,
+            stateless_session_tickets: self.stateless_session_tickets,
+            accept_early_data: self.accept_early_data,
+            #[cfg(feature = "keylog")]
+            key_log_file: self.key_log_file.clone(),
+            trust_verifier: self.trust_verifier.clone(),
+            identity_provider: self.identity_provider.clone(),
+            certificate_selector: self.certificate_selector.clone(),
+            replay_policy: self.replay_policy.clone(),
         }
     }
 }
@@ -922,10 +1442,21 @@ impl Default for SecurityParameters {
             max_cached_sessions: None,
             cached_session_lifetime_seconds: None,
             pre_shared_key: None,
-            #[cfg(not(feature = "ffi"))]
-            trust_verification_callback: None,
-            #[cfg(not(feature = "ffi"))]
-            identity_challenge_callback: None,
+            server_identities: Vec::new(),
+            client_certificate_policy: ClientCertificatePolicy::default(),
+            pinned_client_certificate: Vec::new(),
+            pinned_peers: Vec::new(),
+            raw_public_keys: false,
+            client_identity: None,
+            session_tickets: 2,
+            stateless_session_tickets: false,
+            accept_early_data: false,
+            #[cfg(feature = "keylog")]
+            key_log_file: None,
+            trust_verifier: None,
+            identity_provider: None,
+            certificate_selector: None,
+            replay_policy: None,
         }
     }
 }
@@ -970,6 +1501,18 @@ pub enum SecurityProtocol {
     DTLS13,
 }
 
+/// Whether a server asks clients for certificates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClientCertificatePolicy {
+    /// Clients are not asked for a certificate
+    #[default]
+    None,
+    /// Clients are asked for a certificate, but may connect without one
+    Request,
+    /// Clients without an acceptable certificate are refused
+    Require,
+}
+
 /// Certificate representation (placeholder for actual implementation)
 #[derive(Debug, Clone)]
 pub struct Certificate {
@@ -982,6 +1525,108 @@ pub struct CertificateChain {
     pub certificates: Vec<Certificate>,
 }
 
+/// A certificate chain and private key a server presents
+#[derive(Clone)]
+pub struct ServerIdentity {
+    /// Names this identity serves, matched against the client's SNI;
+    /// `*.example.com` matches a single label
+    pub server_names: Vec<String>,
+    /// Leaf certificate first
+    pub certificate_chain: CertificateChain,
+    /// PKCS#8 DER private key of the leaf certificate
+    pub private_key: Vec<u8>,
+}
+
+impl ServerIdentity {
+    /// Create an identity serving the given names
+    pub fn new(
+        server_names: Vec<String>,
+        certificate_chain: CertificateChain,
+        private_key: Vec<u8>,
+    ) -> Self {
+        Self {
+            server_names,
+            certificate_chain,
+            private_key,
+        }
+    }
+
+    /// Whether this identity serves the name a client asked for
+    pub fn matches(&self, server_name: &str) -> bool {
+        self.server_names
+            .iter()
+            .any(|name| match name.strip_prefix("*.") {
+                Some(suffix) => server_name.split_once('.').is_some_and(|(label, rest)| {
+                    !label.is_empty() && rest.eq_ignore_ascii_case(suffix)
+                }),
+                None => name.eq_ignore_ascii_case(server_name),
+            })
+    }
+
+    /// Fingerprint of the leaf certificate to share with peers out of band,
+    /// in the `sha-256 AB:CD:…` form used by SDP
+    #[cfg(feature = "tls")]
+    pub fn fingerprint(&self) -> crate::Result<String> {
+        crate::tls::fingerprint(self)
+    }
+
+    /// DER SubjectPublicKeyInfo of the private key, for peers to pin when
+    /// raw public keys are used
+    #[cfg(feature = "tls")]
+    pub fn public_key(&self) -> crate::Result<Vec<u8>> {
+        crate::tls::public_key(self)
+    }
+}
+
+impl std::fmt::Debug for ServerIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The private key is never printed
+        f.debug_struct("ServerIdentity")
+            .field("server_names", &self.server_names)
+            .field("certificates", &self.certificate_chain.certificates.len())
+            .finish()
+    }
+}
+
+/// An identity a peer is recognised by without a certificate authority
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PeerPin {
+    /// SHA-256 digest of the peer's certificate, usually self-signed
+    CertificateSha256(Vec<u8>),
+    /// DER SubjectPublicKeyInfo of the peer's raw public key
+    PublicKey(Vec<u8>),
+}
+
+impl PeerPin {
+    /// Parse a certificate fingerprint as exchanged in SDP (RFC 8122),
+    /// e.g. `sha-256 AB:CD:…`
+    pub fn from_fingerprint(fingerprint: &str) -> crate::Result<Self> {
+        let invalid = || {
+            crate::TransportServicesError::InvalidParameters(format!(
+                "Invalid certificate fingerprint: {fingerprint}"
+            ))
+        };
+        let (algorithm, digest) = fingerprint.trim().split_once(' ').ok_or_else(invalid)?;
+        if !algorithm.eq_ignore_ascii_case("sha-256") {
+            return Err(crate::TransportServicesError::NotSupported(format!(
+                "Fingerprint algorithm {algorithm}"
+            )));
+        }
+        let digest = digest
+            .trim()
+            .split(':')
+            .map(|byte| match byte.len() {
+                2 => u8::from_str_radix(byte, 16).map_err(|_| invalid()),
+                _ => Err(invalid()),
+            })
+            .collect::<crate::Result<Vec<u8>>>()?;
+        if digest.len() != 32 {
+            return Err(invalid());
+        }
+        Ok(Self::CertificateSha256(digest))
+    }
+}
+
 /// Pre-shared key configuration
 #[derive(Debug, Clone)]
 pub struct PreSharedKey {
@@ -998,6 +1643,18 @@ pub enum ConnectionState {
     Closed,
 }
 
+/// What happens to a Connection when the last handle to it is dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DropPolicy {
+    /// Close gracefully, delivering outstanding data (RFC Section 10, Close)
+    #[default]
+    Close,
+    /// Abort immediately without delivering outstanding data (RFC Section 10, Abort)
+    Abort,
+    /// Close gracefully, but abort if closing takes longer than the timeout
+    Linger(Duration),
+}
+
 /// Event types that can be emitted by connections
 #[derive(Debug, Clone)]
 pub enum ConnectionEvent {
@@ -1041,6 +1698,219 @@ pub enum ConnectionEvent {
     ReceiveError {
         error: String,
     },
+    /// Peer sent its Final message; no more data will be received
+    /// RFC Section 9.3.2.1 (Final property of received messages)
+    FinalReceived,
+    /// Periodic statistics snapshot, emitted when a stats interval is set
+    Stats(ConnectionStats),
+    /// Heartbeat probes went unanswered; the peer may be gone even though
+    /// the transport is still up
+    Unresponsive {
+        missed_probes: u32,
+    },
+}
+
+impl ConnectionEvent {
+    /// The class of the event, for filtering with `Connection::events_filtered`
+    pub fn class(&self) -> EventClass {
+        match self {
+            ConnectionEvent::Ready
+            | ConnectionEvent::EstablishmentError(_)
+            | ConnectionEvent::ConnectionError(_)
+            | ConnectionEvent::Closed => EventClass::Lifecycle,
+            ConnectionEvent::Received { .. }
+            | ConnectionEvent::ReceivedPartial { .. }
+            | ConnectionEvent::ReceiveError { .. }
+            | ConnectionEvent::FinalReceived => EventClass::Data,
+            ConnectionEvent::Sent { .. }
+            | ConnectionEvent::Expired { .. }
+            | ConnectionEvent::SendError { .. } => EventClass::Send,
+            ConnectionEvent::PathChange
+            | ConnectionEvent::SoftError(_)
+            | ConnectionEvent::Unresponsive { .. } => EventClass::Path,
+            ConnectionEvent::Stats(_) => EventClass::Stats,
+        }
+    }
+}
+
+/// Classes of ConnectionEvent, each with its own buffer when filtered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventClass {
+    /// Ready, EstablishmentError, ConnectionError and Closed
+    Lifecycle,
+    /// Received, ReceivedPartial, ReceiveError and FinalReceived
+    Data,
+    /// Sent, Expired and SendError
+    Send,
+    /// PathChange, SoftError and Unresponsive
+    Path,
+    /// Periodic statistics snapshots
+    Stats,
+}
+
+impl EventClass {
+    /// Every class, in the order filtered events are taken
+    pub const ALL: [EventClass; 5] = [
+        EventClass::Lifecycle,
+        EventClass::Data,
+        EventClass::Send,
+        EventClass::Path,
+        EventClass::Stats,
+    ];
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// A set of event classes, built with `|`
+/// (e.g. `EventClass::Data | EventClass::Lifecycle`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct EventClasses(u8);
+
+impl EventClasses {
+    /// No classes
+    pub fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Every class
+    pub fn all() -> Self {
+        Self(
+            EventClass::ALL
+                .iter()
+                .fold(0, |bits, class| bits | class.bit()),
+        )
+    }
+
+    pub fn contains(&self, class: EventClass) -> bool {
+        self.0 & class.bit() != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// The classes in the set
+    pub fn iter(&self) -> impl Iterator<Item = EventClass> + '_ {
+        EventClass::ALL
+            .into_iter()
+            .filter(|class| self.contains(*class))
+    }
+}
+
+impl From<EventClass> for EventClasses {
+    fn from(class: EventClass) -> Self {
+        Self(class.bit())
+    }
+}
+
+impl<T: Into<EventClasses>> std::ops::BitOr<T> for EventClass {
+    type Output = EventClasses;
+
+    fn bitor(self, other: T) -> EventClasses {
+        EventClasses::from(self) | other
+    }
+}
+
+impl<T: Into<EventClasses>> std::ops::BitOr<T> for EventClasses {
+    type Output = EventClasses;
+
+    fn bitor(self, other: T) -> EventClasses {
+        Self(self.0 | other.into().0)
+    }
+}
+
+/// When the work behind an event happened, for measuring latency inside
+/// the library without instrumenting it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventTimestamps {
+    /// When the event was queued for the application
+    pub enqueued: Instant,
+    /// When the write carrying a Sent message began
+    pub send_started: Option<Instant>,
+    /// When that write completed
+    pub send_completed: Option<Instant>,
+    /// When a received message's data was read from the transport
+    pub received: Option<Instant>,
+}
+
+impl EventTimestamps {
+    /// Timestamps of an event queued now
+    pub(crate) fn queued_now() -> Self {
+        Self {
+            enqueued: Instant::now(),
+            send_started: None,
+            send_completed: None,
+            received: None,
+        }
+    }
+}
+
+/// A ConnectionEvent with its timestamps, as returned by
+/// `Connection::next_timed_event`
+#[derive(Debug, Clone)]
+pub struct TimedEvent {
+    pub event: ConnectionEvent,
+    pub timestamps: EventTimestamps,
+    /// When the application took the event
+    pub dequeued: Instant,
+}
+
+impl TimedEvent {
+    /// How long the event waited for the application
+    pub fn queue_latency(&self) -> Duration {
+        self.dequeued
+            .saturating_duration_since(self.timestamps.enqueued)
+    }
+
+    /// How long a Sent message's write took
+    pub fn send_latency(&self) -> Option<Duration> {
+        let started = self.timestamps.send_started?;
+        Some(
+            self.timestamps
+                .send_completed?
+                .saturating_duration_since(started),
+        )
+    }
+
+    /// How long received data took from the transport to the application
+    pub fn receive_latency(&self) -> Option<Duration> {
+        Some(
+            self.dequeued
+                .saturating_duration_since(self.timestamps.received?),
+        )
+    }
+}
+
+/// Snapshot of a connection's transport statistics
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConnectionStats {
+    /// Smoothed round-trip time, where the transport reports it
+    pub rtt: Option<Duration>,
+    /// Round-trip time variation, where the transport reports it
+    pub rtt_variance: Option<Duration>,
+    /// Segments retransmitted over the connection's lifetime, where the transport reports it
+    pub retransmits: Option<u32>,
+    /// Bytes handed to the transport
+    pub bytes_sent: u64,
+    /// Bytes read from the transport
+    pub bytes_received: u64,
+    /// Bytes per second sent since the previous snapshot
+    pub send_throughput: f64,
+    /// Bytes per second received since the previous snapshot
+    pub receive_throughput: f64,
+    /// Messages waiting to be sent, queued before establishment or in a batch
+    pub send_queue: usize,
+    /// Bytes received but not yet delivered as messages
+    pub receive_queue: usize,
+    /// Datagrams handed to the transport
+    pub datagrams_sent: u64,
+    /// System calls that sent them, fewer than datagrams when batched
+    /// through segmentation offload
+    pub datagram_send_calls: u64,
+    /// Bytes held in buffers, queues and undelivered events
+    pub memory: crate::memory::MemoryUsage,
 }
 
 /// Event types that can be emitted during rendezvous
@@ -1252,6 +2122,141 @@ impl TransportPropertiesBuilder {
         self
     }
 
+    /// Set socket send buffer size
+    pub fn send_buffer_size(mut self, size: usize) -> Self {
+        self.properties
+            .set(TransportProperty::SendBufferSize, PropertyValue::Size(size));
+        self
+    }
+
+    /// Set socket receive buffer size
+    pub fn receive_buffer_size(mut self, size: usize) -> Self {
+        self.properties.set(
+            TransportProperty::ReceiveBufferSize,
+            PropertyValue::Size(size),
+        );
+        self
+    }
+
+    /// Set whether Nagle's algorithm is disabled
+    pub fn no_delay(mut self, no_delay: bool) -> Self {
+        self.properties
+            .set(TransportProperty::NoDelay, PropertyValue::Bool(no_delay));
+        self
+    }
+
+    /// Set whether received messages carry the kernel's receive timestamp
+    pub fn receive_timestamps(mut self, enabled: bool) -> Self {
+        self.properties.set(
+            TransportProperty::ReceiveTimestamps,
+            PropertyValue::Bool(enabled),
+        );
+        self
+    }
+
+    /// Emit connection statistics as events at the given interval
+    pub fn stats_interval(mut self, interval: Duration) -> Self {
+        self.properties.set(
+            TransportProperty::StatsInterval,
+            PropertyValue::Duration(interval),
+        );
+        self
+    }
+
+    /// Keep NAT bindings of UDP flows alive when idle for the given interval
+    pub fn nat_keepalive_interval(mut self, interval: Duration) -> Self {
+        self.properties.set(
+            TransportProperty::NatKeepaliveInterval,
+            PropertyValue::Duration(interval),
+        );
+        self
+    }
+
+    /// Set whether UDP flows batch datagrams through segmentation and
+    /// receive offload (Linux GSO/GRO)
+    pub fn udp_offload(mut self, enabled: bool) -> Self {
+        self.properties
+            .set(TransportProperty::UdpOffload, PropertyValue::Bool(enabled));
+        self
+    }
+
+    /// Bound the send queue: once `high` bytes are queued, send() waits
+    /// until fewer than `low` are
+    pub fn send_watermarks(mut self, high: usize, low: usize) -> Self {
+        self.properties.set(
+            TransportProperty::SendHighWatermark,
+            PropertyValue::Size(high),
+        );
+        self.properties.set(
+            TransportProperty::SendLowWatermark,
+            PropertyValue::Size(low),
+        );
+        self
+    }
+
+    /// Set the largest message accepted from the peer
+    ///
+    /// Framed messages declared larger are discarded with a ReceiveError.
+    pub fn maximum_message_size_on_receive(mut self, size: usize) -> Self {
+        self.properties.set(
+            TransportProperty::MaximumMessageSizeOnReceive,
+            PropertyValue::Size(size),
+        );
+        self
+    }
+
+    /// Abort the connection when the peer exceeds maximum_message_size_on_receive
+    pub fn abort_on_oversized_message(mut self, abort: bool) -> Self {
+        self.properties.set(
+            TransportProperty::AbortOnOversizedMessage,
+            PropertyValue::Bool(abort),
+        );
+        self
+    }
+
+    /// Abort the connection rather than let it leave its path
+    pub fn path_pinning(mut self, pinned: bool) -> Self {
+        self.properties
+            .set(TransportProperty::PathPinning, PropertyValue::Bool(pinned));
+        self
+    }
+
+    /// Set the delay between starting racing connection attempts
+    pub fn connection_attempt_delay(mut self, delay: Duration) -> Self {
+        self.properties.set(
+            TransportProperty::ConnectionAttemptDelay,
+            PropertyValue::Duration(delay),
+        );
+        self
+    }
+
+    /// Set which address family to try first when racing
+    pub fn address_family_preference(mut self, preference: AddressFamilyPreference) -> Self {
+        self.properties.set(
+            TransportProperty::AddressFamilyPreference,
+            PropertyValue::AddressFamily(preference),
+        );
+        self
+    }
+
+    /// Set the timeout for a single connection attempt
+    pub fn candidate_timeout(mut self, timeout: Duration) -> Self {
+        self.properties.set(
+            TransportProperty::CandidateTimeout,
+            PropertyValue::Duration(timeout),
+        );
+        self
+    }
+
+    /// Set the maximum number of connection attempts in flight at once
+    pub fn max_parallel_attempts(mut self, attempts: usize) -> Self {
+        self.properties.set(
+            TransportProperty::MaxParallelAttempts,
+            PropertyValue::Size(attempts),
+        );
+        self
+    }
+
     /// Build the TransportProperties
     pub fn build(self) -> TransportProperties {
         self.properties
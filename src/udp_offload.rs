@@ -0,0 +1,168 @@
+//! UDP segmentation and receive offload (GSO/GRO) on Linux
+//! Runs of equally sized datagrams are handed to the kernel in one sendmsg
+//! with UDP_SEGMENT, and the kernel may coalesce received datagrams of one
+//! flow into a single read, reported with UDP_GRO. Elsewhere, and where the
+//! kernel or device refuses, datagrams are sent and received one by one.
+
+use std::net::SocketAddr;
+use tokio::net::UdpSocket;
+
+/// Most datagrams the kernel accepts in one segmented send (UDP_MAX_SEGMENTS)
+pub(crate) const MAX_SEGMENTS: usize = 64;
+
+/// Largest payload of one segmented send
+const MAX_PAYLOAD: usize = 65507;
+
+/// Group consecutive datagrams that can share a segmented send
+///
+/// Every datagram of a group but the last has the same length, the last
+/// being no longer; `key` must match too, e.g. the traffic class. Datagrams
+/// that fit no group form groups of one.
+pub(crate) fn group<T, K: PartialEq>(
+    datagrams: Vec<T>,
+    length: impl Fn(&T) -> usize,
+    key: impl Fn(&T) -> K,
+) -> Vec<Vec<T>> {
+    let mut groups: Vec<Vec<T>> = Vec::new();
+    for datagram in datagrams {
+        let joins = groups.last().is_some_and(|group| {
+            let first = &group[0];
+            let last = &group[group.len() - 1];
+            let size = length(first);
+            // A shorter datagram ends its group
+            length(last) == size
+                && length(&datagram) <= size
+                && length(&datagram) > 0
+                && key(&datagram) == key(first)
+                && group.len() < MAX_SEGMENTS
+                && size * (group.len() + 1) <= MAX_PAYLOAD
+        });
+        match groups.last_mut() {
+            Some(group) if joins => group.push(datagram),
+            _ => groups.push(vec![datagram]),
+        }
+    }
+    groups
+}
+
+/// Whether a segmented send failed because offload is unavailable, rather
+/// than for a reason that sending singly would hit too
+pub(crate) fn is_unsupported(error: &std::io::Error) -> bool {
+    matches!(
+        error.raw_os_error(),
+        Some(libc::EIO | libc::EINVAL | libc::ENOPROTOOPT | libc::EOPNOTSUPP)
+    )
+}
+
+/// Send `buffer` as datagrams of `segment_size` bytes, the last possibly
+/// shorter, in one system call
+#[cfg(target_os = "linux")]
+pub(crate) async fn send_segmented(
+    socket: &UdpSocket,
+    buffer: &[u8],
+    segment_size: usize,
+    peer: SocketAddr,
+) -> std::io::Result<usize> {
+    use std::os::unix::io::AsRawFd;
+
+    let segment_size =
+        u16::try_from(segment_size).map_err(|_| std::io::Error::from_raw_os_error(libc::EINVAL))?;
+    // A dual-stack socket addresses IPv4 peers IPv4-mapped
+    let peer = match (socket.local_addr()?, peer) {
+        (SocketAddr::V6(_), SocketAddr::V4(v4)) => {
+            SocketAddr::new(v4.ip().to_ipv6_mapped().into(), v4.port())
+        }
+        _ => peer,
+    };
+    let address = socket2::SockAddr::from(peer);
+
+    socket
+        .async_io(tokio::io::Interest::WRITABLE, || {
+            let mut control = [0u64; 4];
+            let mut iov = libc::iovec {
+                iov_base: buffer.as_ptr() as *mut libc::c_void,
+                iov_len: buffer.len(),
+            };
+            // SAFETY: msghdr points at buffers that outlive the call, with
+            // their true lengths, and the single control message is written
+            // within the control buffer, which has room for it
+            let n = unsafe {
+                let mut msg: libc::msghdr = std::mem::zeroed();
+                msg.msg_name = address.as_ptr() as *mut libc::c_void;
+                msg.msg_namelen = address.len();
+                msg.msg_iov = &mut iov;
+                msg.msg_iovlen = 1;
+                msg.msg_control = control.as_mut_ptr().cast();
+                msg.msg_controllen = libc::CMSG_SPACE(std::mem::size_of::<u16>() as u32) as _;
+
+                let cmsg = libc::CMSG_FIRSTHDR(&msg);
+                (*cmsg).cmsg_level = libc::SOL_UDP;
+                (*cmsg).cmsg_type = libc::UDP_SEGMENT;
+                (*cmsg).cmsg_len = libc::CMSG_LEN(std::mem::size_of::<u16>() as u32) as _;
+                std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut u16, segment_size);
+
+                libc::sendmsg(socket.as_raw_fd(), &msg, 0)
+            };
+            if n < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(n as usize)
+        })
+        .await
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) async fn send_segmented(
+    _socket: &UdpSocket,
+    _buffer: &[u8],
+    _segment_size: usize,
+    _peer: SocketAddr,
+) -> std::io::Result<usize> {
+    Err(std::io::Error::from_raw_os_error(libc::EOPNOTSUPP))
+}
+
+/// Let the kernel coalesce received datagrams of one flow into a single read
+#[cfg(target_os = "linux")]
+pub(crate) fn enable_gro(socket: &socket2::SockRef<'_>) {
+    use std::os::unix::io::AsRawFd;
+
+    let value: libc::c_int = 1;
+    // SAFETY: the fd is valid for the lifetime of the borrowed socket and the
+    // option value points to a c_int of the length passed
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_UDP,
+            libc::UDP_GRO,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if result != 0 {
+        log::debug!(
+            "UDP receive offload unavailable: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn enable_gro(_socket: &socket2::SockRef<'_>) {}
+
+/// Read the segment size from a UDP_GRO control message
+///
+/// # Safety
+/// The control message must lie within a buffer filled in by recvmsg.
+#[cfg(target_os = "linux")]
+pub(crate) unsafe fn control_segment_size(cmsg: &libc::cmsghdr) -> Option<usize> {
+    if cmsg.cmsg_level != libc::SOL_UDP || cmsg.cmsg_type != libc::UDP_GRO {
+        return None;
+    }
+    let size = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::c_int);
+    usize::try_from(size).ok().filter(|size| *size > 0)
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+pub(crate) unsafe fn control_segment_size(_cmsg: &libc::cmsghdr) -> Option<usize> {
+    None
+}
@@ -0,0 +1,177 @@
+//! URLs naming remote endpoints
+//! Converts between URLs such as `quic://example.com:443?alpn=h3` and
+//! Remote Endpoints, so endpoints can come from command lines and
+//! configuration files and be logged in the same form.
+
+use crate::{EndpointIdentifier, Protocol, RemoteEndpoint, Result, TransportServicesError};
+use std::net::IpAddr;
+
+/// A URL split into the endpoint it names and its query parameters
+pub(crate) struct EndpointUrl {
+    pub(crate) endpoint: RemoteEndpoint,
+    pub(crate) protocol: Protocol,
+    pub(crate) query: Vec<(String, String)>,
+}
+
+/// Protocol and default port of a URL scheme
+fn scheme_protocol(scheme: &str) -> Option<(Protocol, Option<u16>)> {
+    Some(match scheme {
+        "tcp" => (Protocol::TCP, None),
+        "udp" => (Protocol::UDP, None),
+        "tls" => (Protocol::TLS, None),
+        "dtls" => (Protocol::DTLS, None),
+        "sctp" => (Protocol::SCTP, None),
+        "quic" => (Protocol::QUIC, Some(443)),
+        "http" => (Protocol::TCP, Some(80)),
+        "https" => (Protocol::TLS, Some(443)),
+        _ => return None,
+    })
+}
+
+/// Scheme a protocol is written with
+fn protocol_scheme(protocol: Protocol) -> &'static str {
+    match protocol {
+        Protocol::TCP => "tcp",
+        Protocol::UDP => "udp",
+        Protocol::TLS => "tls",
+        Protocol::DTLS => "dtls",
+        Protocol::SCTP => "sctp",
+        Protocol::QUIC => "quic",
+    }
+}
+
+/// Parse `scheme://host[:port][/path][?query]`
+///
+/// The host may be a name, an IPv4 address or a bracketed IPv6 address with
+/// an optional `%25`-encoded zone (RFC 6874). The path is ignored.
+pub(crate) fn parse(url: &str) -> Result<EndpointUrl> {
+    let invalid = |reason: &str| {
+        TransportServicesError::InvalidParameters(format!("Invalid URL {url}: {reason}"))
+    };
+
+    let (scheme, rest) = url
+        .split_once("://")
+        .ok_or_else(|| invalid("it has no scheme"))?;
+    let scheme = scheme.to_ascii_lowercase();
+    let (protocol, default_port) = scheme_protocol(&scheme).ok_or_else(|| {
+        TransportServicesError::NotSupported(format!("Unsupported URL scheme: {scheme}"))
+    })?;
+
+    let (rest, query) = match rest.split_once('?') {
+        Some((rest, query)) => (rest, Some(query)),
+        None => (rest, None),
+    };
+    let rest = rest.split('#').next().unwrap_or_default();
+    let authority = rest.split('/').next().unwrap_or_default();
+    if authority.contains('@') {
+        return Err(invalid("user information is not supported"));
+    }
+
+    let (identifiers, port) = if let Some(bracketed) = authority.strip_prefix('[') {
+        let (host, after) = bracketed
+            .split_once(']')
+            .ok_or_else(|| invalid("'[' is not closed"))?;
+        let host = host.replacen("%25", "%", 1);
+        let identifiers = EndpointIdentifier::parse_scoped_ip(&host)?;
+        if !matches!(identifiers[0], EndpointIdentifier::IpAddress(IpAddr::V6(_))) {
+            return Err(invalid("only IPv6 addresses may be bracketed"));
+        }
+        (identifiers, after.strip_prefix(':'))
+    } else {
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        };
+        if host.is_empty() {
+            return Err(invalid("it has no host"));
+        }
+        let identifier = match host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => EndpointIdentifier::IpAddress(IpAddr::V4(ip)),
+            Ok(IpAddr::V6(_)) => return Err(invalid("IPv6 addresses must be bracketed")),
+            Err(_) => EndpointIdentifier::HostName(host.to_string()),
+        };
+        (vec![identifier], port)
+    };
+    let port = match port {
+        Some(port) => port
+            .parse::<u16>()
+            .map_err(|_| invalid(&format!("{port:?} is not a port")))?,
+        None => default_port.ok_or_else(|| invalid(&format!("{scheme} URLs need a port")))?,
+    };
+
+    let mut endpoint = RemoteEndpoint {
+        identifiers,
+        protocol: Some(protocol),
+    };
+    endpoint.identifiers.push(EndpointIdentifier::Port(port));
+
+    let mut parameters = Vec::new();
+    for pair in query.unwrap_or_default().split('&') {
+        if pair.is_empty() {
+            continue;
+        }
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        parameters.push((percent_decode(key)?, percent_decode(value)?));
+    }
+
+    Ok(EndpointUrl {
+        endpoint,
+        protocol,
+        query: parameters,
+    })
+}
+
+/// Write an endpoint as a URL, if it names a host and port
+pub(crate) fn format(endpoint: &RemoteEndpoint) -> Option<String> {
+    let mut host = None;
+    let mut port = None;
+    let mut interface = None;
+    for identifier in &endpoint.identifiers {
+        match identifier {
+            EndpointIdentifier::SocketAddress(addr) => {
+                host = Some(addr.ip().to_string());
+                port = Some(addr.port());
+            }
+            EndpointIdentifier::IpAddress(ip) => host = Some(ip.to_string()),
+            EndpointIdentifier::HostName(name) => {
+                host.get_or_insert_with(|| name.clone());
+            }
+            EndpointIdentifier::Port(p) => port = Some(*p),
+            EndpointIdentifier::Interface(name) => interface = Some(name.as_str()),
+            _ => {}
+        }
+    }
+    let mut host = host?;
+    if host.parse::<std::net::Ipv6Addr>().is_ok() {
+        host = match interface {
+            Some(zone) => format!("[{host}%25{zone}]"),
+            None => format!("[{host}]"),
+        };
+    }
+    let scheme = protocol_scheme(endpoint.protocol.unwrap_or(Protocol::TCP));
+    Some(format!("{scheme}://{host}:{}", port?))
+}
+
+/// Decode `%XX` escapes in a query component
+fn percent_decode(text: &str) -> Result<String> {
+    let invalid = || {
+        TransportServicesError::InvalidParameters(format!("Invalid escape in URL query {text:?}"))
+    };
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = tail
+                .get(..2)
+                .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                .ok_or_else(invalid)?;
+            let hex = std::str::from_utf8(hex).map_err(|_| invalid())?;
+            bytes.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| invalid())
+}